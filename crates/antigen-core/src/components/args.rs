@@ -1,5 +1,56 @@
-use crate::Usage;
+use hecs::World;
 
-pub enum EnvArgs {}
+use crate::{Construct, Usage};
 
+pub enum EnvArgs {}
+/// Command-line arguments, excluding the executable path
 pub type ArgsComponent = Usage<EnvArgs, Vec<String>>;
+
+/// Construct an ArgsComponent from the arguments this process was started with
+pub fn env_args_component() -> ArgsComponent {
+    ArgsComponent::construct(std::env::args().skip(1).collect())
+}
+
+/// Returns a copy of the arguments stored in `world`, if it has an ArgsComponent
+pub fn get_args(world: &World) -> Option<Vec<String>> {
+    world
+        .query::<&ArgsComponent>()
+        .into_iter()
+        .next()
+        .map(|(_, args)| (**args).clone())
+}
+
+/// Iterate over the arguments that aren't `--flags`,
+/// skipping those that are the values of flags listed in `value_flags`
+pub fn positional_args<'a>(
+    args: &'a [String],
+    value_flags: &'a [&str],
+) -> impl Iterator<Item = &'a str> + 'a {
+    let mut skip = false;
+    args.iter().filter_map(move |arg| {
+        if std::mem::take(&mut skip) {
+            return None;
+        }
+
+        if arg.starts_with("--") {
+            skip = value_flags.contains(&arg.as_str());
+            return None;
+        }
+
+        Some(arg.as_str())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positional_args_skip_flag_values() {
+        let args = ["--lut", "grade.cube", "my.map", "--skybox-overlay", "other.map"]
+            .map(String::from);
+
+        let positional = positional_args(&args, &["--lut"]).collect::<Vec<_>>();
+        assert_eq!(positional, ["my.map", "other.map"]);
+    }
+}
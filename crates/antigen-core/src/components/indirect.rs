@@ -56,4 +56,8 @@ where
     pub fn entities(&self) -> &Vec<Entity> {
         &self.entities
     }
+
+    pub fn entities_mut(&mut self) -> &mut Vec<Entity> {
+        &mut self.entities
+    }
 }
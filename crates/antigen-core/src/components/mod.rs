@@ -5,6 +5,8 @@ mod lazy_component;
 mod swap_with;
 mod tagged_entities;
 mod named_entities;
+mod paused;
+mod transform;
 mod usage;
 
 pub use ::usage::*;
@@ -15,6 +17,12 @@ pub use lazy_component::*;
 pub use swap_with::*;
 pub use tagged_entities::*;
 pub use named_entities::*;
+pub use paused::*;
+pub use transform::*;
+
+use std::marker::PhantomData;
+
+use crate::Construct;
 
 // Position
 pub enum Position {}
@@ -31,17 +39,302 @@ pub type ScaleComponent = Usage<Scale, nalgebra::Vector3<f32>>;
 pub enum CopyTo {}
 pub type CopyToComponent<'a, U, T> = Usage<U, IndirectMulti<&'a mut Changed<T>>>;
 
+pub struct Copied<U>(PhantomData<U>);
+pub type CopiedComponent<U, T> = Usage<Copied<U>, (T, Vec<hecs::Entity>)>;
+
+// Copy T to the Changed<T> of each CopyTo target, flagging targets whose value differs
+//
+// Sources stored as Changed<T> are only copied when flagged, and have their flag cleared
+// once copied. Plain T sources remember the value and targets they last copied to
+// in a Copied<U> component, and are only copied when either differs from it,
+// so replacing a source's CopyTo with new targets copies to them on the next run.
+// Unchanged sources skip their targets entirely, which avoids querying
+// thousands of mesh instance targets per frame (see copy_to_unchanged_sources_bench)
+//
+// Systems that consume the Changed flag (such as buffer writes) must run after this
+// to observe the copied value in the same frame
 pub fn copy_to_system<U: hecs::Component, T: hecs::Component + PartialEq + Copy>(
     world: &mut hecs::World,
+) {
+    let mut uncached = vec![];
+
+    for (entity, (value, copy_to, copied)) in world
+        .query::<(
+            &T,
+            &CopyToComponent<U, T>,
+            Option<&mut CopiedComponent<U, T>>,
+        )>()
+        .into_iter()
+    {
+        match copied {
+            Some(copied) if copied.0 == *value && copied.1 == *copy_to.entities() => continue,
+            Some(copied) => **copied = (*value, copy_to.entities().clone()),
+            None => uncached.push((entity, (*value, copy_to.entities().clone()))),
+        }
+
+        copy_to_targets(world, value, copy_to.entities());
+    }
+
+    for (entity, copied) in uncached {
+        world
+            .insert_one(entity, CopiedComponent::<U, T>::construct(copied))
+            .unwrap();
+    }
+
+    let changed = world
+        .query_mut::<(&Changed<T>, &CopyToComponent<U, T>)>()
+        .into_iter()
+        .filter(|(_, (value, _))| value.get_changed())
+        .map(|(_, (value, copy_to))| {
+            value.set_changed(false);
+            (**value, copy_to.entities().clone())
+        })
+        .collect::<Vec<_>>();
+
+    for (value, targets) in changed {
+        copy_to_targets(world, &value, &targets);
+    }
+}
+
+fn copy_to_targets<T: hecs::Component + PartialEq + Copy>(
+    world: &hecs::World,
+    value: &T,
+    targets: &[hecs::Entity],
+) {
+    for target in targets {
+        let mut query = world.query_one::<&mut Changed<T>>(*target).unwrap();
+        let target = query.get().unwrap();
+        if **target != *value {
+            **target = *value;
+            target.set_changed(true);
+        }
+    }
+}
+
+// Clone T to the Changed<T> of each CopyTo target, flagging targets whose value differs
+//
+// Counterpart to copy_to_system for non-Copy types such as strings;
+// values are compared by reference, and only cloned into targets that differ
+pub fn clone_to_system<U: hecs::Component, T: hecs::Component + PartialEq + Clone>(
+    world: &mut hecs::World,
 ) {
     for (_, (value, copy_to)) in world.query::<(&T, &CopyToComponent<U, T>)>().into_iter() {
         for target in copy_to.entities() {
             let mut query = world.query_one::<&mut Changed<T>>(*target).unwrap();
             let target = query.get().unwrap();
             if **target != *value {
-                **target = *value;
+                (**target).clone_from(value);
                 target.set_changed(true);
             }
         }
     }
 }
+
+pub struct MoveTo<U>(PhantomData<U>);
+pub type MoveToComponent<'a, U, T> = Usage<MoveTo<U>, IndirectMulti<&'a mut Changed<T>>>;
+
+// Write T to the Changed<T> of each MoveTo target exactly once
+//
+// Targets without a Changed<T> are considered pending and retried on the next run,
+// while written and despawned targets are dropped from the list.
+// The MoveTo component is removed from the source once no targets remain
+pub fn move_to_system<U: hecs::Component, T: hecs::Component + PartialEq + Clone>(
+    world: &mut hecs::World,
+) {
+    let mut finished = vec![];
+
+    for (entity, (value, move_to)) in world
+        .query::<(&T, &mut MoveToComponent<U, T>)>()
+        .into_iter()
+    {
+        move_to.entities_mut().retain(|target| {
+            if !world.contains(*target) {
+                return false;
+            }
+
+            let mut query = world.query_one::<&mut Changed<T>>(*target).unwrap();
+            let target = if let Some(target) = query.get() {
+                target
+            } else {
+                return true;
+            };
+
+            if **target != *value {
+                (**target).clone_from(value);
+                target.set_changed(true);
+            }
+
+            false
+        });
+
+        if move_to.entities().is_empty() {
+            finished.push(entity);
+        }
+    }
+
+    for entity in finished {
+        world.remove_one::<MoveToComponent<U, T>>(entity).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Construct;
+
+    enum TestTag {}
+    type TestString = Usage<TestTag, String>;
+
+    #[test]
+    fn clone_to_targets() {
+        let mut world = hecs::World::new();
+
+        let target = world.spawn((Changed::new(TestString::construct("Foo".into()), false),));
+        let source = world.spawn((
+            TestString::construct("Foo".into()),
+            CopyToComponent::<TestTag, TestString>::construct(vec![target]),
+        ));
+
+        // Equal values leave the target untouched
+        clone_to_system::<TestTag, TestString>(&mut world);
+        assert!(!world.get::<Changed<TestString>>(target).unwrap().get_changed());
+
+        **world.get_mut::<TestString>(source).unwrap() = "Bar".into();
+        clone_to_system::<TestTag, TestString>(&mut world);
+
+        let target = world.get::<Changed<TestString>>(target).unwrap();
+        assert_eq!(***target, "Bar");
+        assert!(target.get_changed());
+    }
+
+    enum CopyTag {}
+    type CopyData = Usage<CopyTag, [f32; 4]>;
+
+    #[test]
+    fn copy_to_changed_sources() {
+        let mut world = hecs::World::new();
+
+        let target = world.spawn((Changed::new(CopyData::construct([0.0; 4]), false),));
+        let source = world.spawn((
+            Changed::new(CopyData::construct([1.0; 4]), true),
+            CopyToComponent::<CopyTag, CopyData>::construct(vec![target]),
+        ));
+
+        copy_to_system::<CopyTag, CopyData>(&mut world);
+        assert_eq!(***world.get::<Changed<CopyData>>(target).unwrap(), [1.0; 4]);
+
+        // The source flag is cleared once copied
+        assert!(!world
+            .get::<Changed<CopyData>>(source)
+            .unwrap()
+            .get_changed());
+
+        // Unchanged sources leave their targets alone
+        ***world.get_mut::<Changed<CopyData>>(target).unwrap() = [2.0; 4];
+        copy_to_system::<CopyTag, CopyData>(&mut world);
+        assert_eq!(***world.get::<Changed<CopyData>>(target).unwrap(), [2.0; 4]);
+    }
+
+    #[test]
+    fn copy_to_plain_sources() {
+        let mut world = hecs::World::new();
+
+        let target = world.spawn((Changed::new(CopyData::construct([0.0; 4]), false),));
+        let source = world.spawn((
+            CopyData::construct([1.0; 4]),
+            CopyToComponent::<CopyTag, CopyData>::construct(vec![target]),
+        ));
+
+        copy_to_system::<CopyTag, CopyData>(&mut world);
+        assert_eq!(***world.get::<Changed<CopyData>>(target).unwrap(), [1.0; 4]);
+
+        // Sources whose value hasn't changed since their last copy are skipped
+        ***world.get_mut::<Changed<CopyData>>(target).unwrap() = [2.0; 4];
+        copy_to_system::<CopyTag, CopyData>(&mut world);
+        assert_eq!(***world.get::<Changed<CopyData>>(target).unwrap(), [2.0; 4]);
+
+        **world.get_mut::<CopyData>(source).unwrap() = [3.0; 4];
+        copy_to_system::<CopyTag, CopyData>(&mut world);
+        assert_eq!(***world.get::<Changed<CopyData>>(target).unwrap(), [3.0; 4]);
+
+        // Replacing the source's targets copies to the new ones, even if its value is unchanged
+        let new_target = world.spawn((Changed::new(CopyData::construct([0.0; 4]), false),));
+        world
+            .insert_one(
+                source,
+                CopyToComponent::<CopyTag, CopyData>::construct(vec![new_target]),
+            )
+            .unwrap();
+        copy_to_system::<CopyTag, CopyData>(&mut world);
+        assert_eq!(
+            ***world.get::<Changed<CopyData>>(new_target).unwrap(),
+            [3.0; 4]
+        );
+    }
+
+    // Compares unchanged plain sources, which compare against their last copied value,
+    // against unchanged Changed sources, which are skipped by flag.
+    // Run with `cargo test --release -p antigen-core -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn copy_to_unchanged_sources_bench() {
+        const INSTANCES: usize = 4000;
+        const FRAMES: u32 = 100;
+
+        fn bench<S: hecs::Component>(source: fn() -> S) -> std::time::Duration {
+            let mut world = hecs::World::new();
+            for _ in 0..INSTANCES {
+                let target = world.spawn((Changed::new(CopyData::construct([1.0; 4]), false),));
+                world.spawn((
+                    source(),
+                    CopyToComponent::<CopyTag, CopyData>::construct(vec![target]),
+                ));
+            }
+
+            let start = std::time::Instant::now();
+            for _ in 0..FRAMES {
+                copy_to_system::<CopyTag, CopyData>(&mut world);
+            }
+            start.elapsed() / FRAMES
+        }
+
+        let plain = bench(|| CopyData::construct([1.0; 4]));
+        let changed = bench(|| Changed::new(CopyData::construct([1.0; 4]), false));
+
+        println!(
+            "{} instances, per frame: {:?} for plain sources, {:?} for unchanged sources",
+            INSTANCES, plain, changed
+        );
+    }
+
+    #[test]
+    fn move_to_pending_targets() {
+        let mut world = hecs::World::new();
+
+        let ready = world.spawn((Changed::new(TestString::construct("Foo".into()), false),));
+        let pending = world.spawn(());
+        let source = world.spawn((
+            TestString::construct("Bar".into()),
+            MoveToComponent::<TestTag, TestString>::construct(vec![ready, pending]),
+        ));
+
+        // Only the ready target is written, leaving the pending one in place
+        move_to_system::<TestTag, TestString>(&mut world);
+        assert_eq!(***world.get::<Changed<TestString>>(ready).unwrap(), "Bar");
+        assert_eq!(
+            world.get::<MoveToComponent<TestTag, TestString>>(source).unwrap().entities(),
+            &vec![pending]
+        );
+
+        // Written targets are not overwritten on subsequent runs
+        ***world.get_mut::<Changed<TestString>>(ready).unwrap() = "Baz".into();
+        world
+            .insert_one(pending, Changed::new(TestString::construct("Foo".into()), false))
+            .unwrap();
+
+        move_to_system::<TestTag, TestString>(&mut world);
+        assert_eq!(***world.get::<Changed<TestString>>(ready).unwrap(), "Baz");
+        assert_eq!(***world.get::<Changed<TestString>>(pending).unwrap(), "Bar");
+        assert!(world.get::<MoveToComponent<TestTag, TestString>>(source).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use hecs::World;
+use usage::Usage;
+
+use crate::{Construct, MessageContext, MessageResult};
+
+pub enum Paused {}
+/// Per-world pause flag, checked by the thread that owns the world before running its systems
+pub type PausedComponent = Usage<Paused, bool>;
+
+/// Returns true if `world` has been paused
+pub fn is_paused(world: &World) -> bool {
+    world
+        .query::<&PausedComponent>()
+        .into_iter()
+        .next()
+        .map(|(_, paused)| **paused)
+        .unwrap_or_default()
+}
+
+/// Set the pause flag of `world`, spawning it if not already present
+pub fn set_paused(world: &mut World, paused: bool) {
+    let entity = world
+        .query_mut::<&PausedComponent>()
+        .into_iter()
+        .next()
+        .map(|(entity, _)| entity);
+
+    match entity {
+        Some(entity) => **world.get_mut::<PausedComponent>(entity).unwrap() = paused,
+        None => {
+            world.spawn((PausedComponent::construct(paused),));
+        }
+    }
+}
+
+/// Returns a message that will set the pause flag of the receiving world
+pub fn pause_message(
+    paused: bool,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |(world, channel)| {
+        set_paused(world, paused);
+        Ok((world, channel))
+    }
+}
+
+/// Returns a message that will toggle the pause flag of the receiving world
+pub fn toggle_pause_message(
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |(world, channel)| {
+        let paused = is_paused(world);
+        set_paused(world, !paused);
+        Ok((world, channel))
+    }
+}
@@ -2,7 +2,7 @@ use std::marker::PhantomData;
 
 use hecs::{Component, EntityBuilder};
 
-use crate::{Construct, Indirect, Usage};
+use crate::{Construct, Indirect, IndirectMulti, Usage};
 
 // Swap two components of the same type in-place
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -37,3 +37,109 @@ pub fn swap_with_system<T: Component>(world: &mut hecs::World) {
         std::mem::swap(component, indirect_buffer);
     }
 }
+
+// Cycle components of the same type in-place around a ring of entities,
+// moving each entity's component on to the next entity in the ring
+//
+// The ring starts at the entity holding RotateWith<T>, followed by its targets in order;
+// a single target is equivalent to SwapWith<T>
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RotateWith<T>(PhantomData<T>);
+
+impl<T> Default for RotateWith<T> {
+    fn default() -> Self {
+        RotateWith(Default::default())
+    }
+}
+
+pub fn rotate_with_builder<T: Component>(target_entities: Vec<hecs::Entity>) -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+
+    builder.add(RotateWith::<T>::default());
+    builder.add(Usage::<RotateWith<T>, IndirectMulti<&mut T>>::construct(
+        target_entities,
+    ));
+
+    builder
+}
+
+pub fn rotate_with_system<T: Component>(world: &mut hecs::World) {
+    let mut query = world
+        .query::<(&mut T, &Usage<RotateWith<T>, IndirectMulti<&mut T>>)>()
+        .with::<RotateWith<T>>();
+
+    for (_, (component, indirect_component)) in query.into_iter() {
+        // Swapping through the first entity hands each component on to the next,
+        // with the last component ending up at the start of the ring
+        for target in indirect_component.entities() {
+            let mut query = world.query_one::<&mut T>(*target).unwrap();
+            let indirect_buffer = query.get().unwrap();
+
+            std::mem::swap(component, indirect_buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum TestTag {}
+    type TestBuffer = Usage<TestTag, char>;
+
+    fn ring(world: &mut hecs::World, buffers: &[char]) -> Vec<hecs::Entity> {
+        let targets = buffers[1..]
+            .iter()
+            .map(|buffer| world.spawn((TestBuffer::construct(*buffer),)))
+            .collect::<Vec<_>>();
+
+        let head = world.spawn(
+            rotate_with_builder::<TestBuffer>(targets.clone())
+                .add(TestBuffer::construct(buffers[0]))
+                .build(),
+        );
+
+        std::iter::once(head).chain(targets).collect()
+    }
+
+    fn buffers(world: &hecs::World, ring: &[hecs::Entity]) -> Vec<char> {
+        ring.iter()
+            .map(|entity| **world.get::<TestBuffer>(*entity).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rotate_with_cycles_ring() {
+        let mut world = hecs::World::new();
+        let ring = ring(&mut world, &['a', 'b', 'c']);
+
+        rotate_with_system::<TestBuffer>(&mut world);
+        assert_eq!(buffers(&world, &ring), ['c', 'a', 'b']);
+
+        rotate_with_system::<TestBuffer>(&mut world);
+        assert_eq!(buffers(&world, &ring), ['b', 'c', 'a']);
+
+        rotate_with_system::<TestBuffer>(&mut world);
+        assert_eq!(buffers(&world, &ring), ['a', 'b', 'c']);
+    }
+
+    #[test]
+    fn rotate_with_pair_matches_swap_with() {
+        let mut world = hecs::World::new();
+        let rotated = ring(&mut world, &['a', 'b']);
+
+        let back = world.spawn((TestBuffer::construct('b'),));
+        let front = world.spawn(
+            swap_with_builder::<TestBuffer>(back)
+                .add(TestBuffer::construct('a'))
+                .build(),
+        );
+        let swapped = [front, back];
+
+        for _ in 0..3 {
+            rotate_with_system::<TestBuffer>(&mut world);
+            swap_with_system::<TestBuffer>(&mut world);
+            assert_eq!(buffers(&world, &rotated), buffers(&world, &swapped));
+        }
+    }
+}
@@ -40,6 +40,27 @@ pub fn get_tagged_entity<T: 'static>(world: &mut World) -> Option<Entity> {
     tagged_entities.get(&std::any::TypeId::of::<T>()).copied()
 }
 
+/// Error returned when no entity has been tagged with a given type
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TagError {
+    pub tag: &'static str,
+}
+
+impl std::fmt::Display for TagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No tagged entity for `{}`", self.tag)
+    }
+}
+
+impl std::error::Error for TagError {}
+
+/// Fallible counterpart to [`get_tagged_entity`] that names the missing tag type
+pub fn get_tagged_entity_or<T: 'static>(world: &mut World) -> Result<Entity, TagError> {
+    get_tagged_entity::<T>(world).ok_or(TagError {
+        tag: std::any::type_name::<T>(),
+    })
+}
+
 pub fn insert_tagged_entity<T: 'static>(world: &mut World, entity: Entity) {
     let type_id = std::any::TypeId::of::<T>();
     let mut tagged_entities = get_tagged_entities_mut(world).unwrap();
@@ -52,3 +73,27 @@ pub fn insert_tagged_entity_by_query<Q: hecs::Query + Send + Sync + 'static, T:
     let (entity, _) = world.query_mut::<Q>().into_iter().next().unwrap();
     insert_tagged_entity::<T>(world, entity);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Construct;
+
+    struct Vertices;
+    struct Indices;
+
+    #[test]
+    fn tagged_entity_error() {
+        let mut world = World::new();
+        world.spawn((TaggedEntitiesComponent::construct(Default::default()),));
+
+        let entity = world.spawn(());
+        insert_tagged_entity::<Vertices>(&mut world, entity);
+
+        assert_eq!(get_tagged_entity_or::<Vertices>(&mut world), Ok(entity));
+
+        let err = get_tagged_entity_or::<Indices>(&mut world).unwrap_err();
+        assert_eq!(err.tag, std::any::type_name::<Indices>());
+        assert!(err.to_string().ends_with("Indices`"));
+    }
+}
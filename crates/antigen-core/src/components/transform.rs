@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use hecs::{Entity, World};
+
+use crate::{Changed, ChangedTrait, PositionComponent, RotationComponent, ScaleComponent, Usage};
+
+// Parent entity whose world transform this entity's local transform is relative to
+pub enum Parent {}
+pub type ParentComponent = Usage<Parent, Entity>;
+
+/// World-space transform composed from an entity's local transform and those of its parents
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlobalTransform {
+    pub position: nalgebra::Vector3<f32>,
+    pub rotation: nalgebra::UnitQuaternion<f32>,
+    pub scale: nalgebra::Vector3<f32>,
+}
+
+pub type GlobalTransformComponent = Changed<GlobalTransform>;
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        GlobalTransform {
+            position: nalgebra::Vector3::zeros(),
+            rotation: nalgebra::UnitQuaternion::identity(),
+            scale: nalgebra::Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl GlobalTransform {
+    /// Read the local transform of `entity`, treating missing components as identity
+    ///
+    /// Each component may be stored either plain or wrapped in `Changed`
+    pub fn local(world: &World, entity: Entity) -> Self {
+        let default = GlobalTransform::default();
+        GlobalTransform {
+            position: local_component::<PositionComponent>(world, entity)
+                .map(|position| *position)
+                .unwrap_or(default.position),
+            rotation: local_component::<RotationComponent>(world, entity)
+                .map(|rotation| *rotation)
+                .unwrap_or(default.rotation),
+            scale: local_component::<ScaleComponent>(world, entity)
+                .map(|scale| *scale)
+                .unwrap_or(default.scale),
+        }
+    }
+
+    /// Apply this transform to a child's local transform
+    ///
+    /// Scale is composed per-axis, which approximates the skew that
+    /// a non-uniformly scaled parent would apply to a rotated child.
+    pub fn compose(&self, local: &GlobalTransform) -> Self {
+        GlobalTransform {
+            position: self.position + self.rotation * self.scale.component_mul(&local.position),
+            rotation: self.rotation * local.rotation,
+            scale: self.scale.component_mul(&local.scale),
+        }
+    }
+}
+
+// Read a component of `entity` stored as either C or Changed<C>
+fn local_component<C: hecs::Component + Copy>(world: &World, entity: Entity) -> Option<C> {
+    world
+        .get::<C>(entity)
+        .map(|component| *component)
+        .or_else(|_| world.get::<Changed<C>>(entity).map(|component| **component))
+        .ok()
+}
+
+// Compose the local transforms of each entity with a GlobalTransformComponent
+// with those of its parents
+//
+// Parents are resolved before their children regardless of iteration order.
+// Missing parents are treated as the world root,
+// and entities that are part of a parent cycle are skipped with a warning
+pub fn propagate_transforms_system(world: &mut World) {
+    let entities = world
+        .query_mut::<()>()
+        .with::<GlobalTransformComponent>()
+        .into_iter()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+
+    let mut globals = HashMap::new();
+    for entity in entities {
+        let global = global_transform(world, entity, &mut globals, &mut vec![]);
+
+        let global = if let Some(global) = global {
+            global
+        } else {
+            continue;
+        };
+
+        let mut component = world.get_mut::<GlobalTransformComponent>(entity).unwrap();
+        if **component != global {
+            **component = global;
+            component.set_changed(true);
+        }
+    }
+}
+
+fn global_transform(
+    world: &World,
+    entity: Entity,
+    globals: &mut HashMap<Entity, Option<GlobalTransform>>,
+    stack: &mut Vec<Entity>,
+) -> Option<GlobalTransform> {
+    if let Some(global) = globals.get(&entity) {
+        return *global;
+    }
+
+    if stack.contains(&entity) {
+        println!(
+            "Warning: Entity {:?} is its own ancestor, skipping transform propagation",
+            entity
+        );
+        return None;
+    }
+
+    let local = GlobalTransform::local(world, entity);
+
+    let parent = world
+        .get::<ParentComponent>(entity)
+        .ok()
+        .map(|parent| **parent)
+        .filter(|parent| world.contains(*parent));
+
+    let global = match parent {
+        Some(parent) => {
+            stack.push(entity);
+            let parent = global_transform(world, parent, globals, stack);
+            stack.pop();
+            parent.map(|parent| parent.compose(&local))
+        }
+        None => Some(local),
+    };
+
+    globals.insert(entity, global);
+    global
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Construct;
+
+    fn global(world: &World, entity: Entity) -> GlobalTransform {
+        **world.get::<GlobalTransformComponent>(entity).unwrap()
+    }
+
+    #[test]
+    fn propagate_transforms() {
+        let mut world = World::new();
+
+        // Spawn the grandchild first to make sure parents are resolved before children
+        let grandchild = world.spawn((
+            PositionComponent::construct(nalgebra::Vector3::new(0.0, 1.0, 0.0)),
+            GlobalTransformComponent::construct(GlobalTransform::default()),
+        ));
+
+        let child = world.spawn((
+            PositionComponent::construct(nalgebra::Vector3::new(1.0, 0.0, 0.0)),
+            GlobalTransformComponent::construct(GlobalTransform::default()),
+        ));
+
+        let root = world.spawn((
+            PositionComponent::construct(nalgebra::Vector3::new(1.0, 0.0, 0.0)),
+            RotationComponent::construct(nalgebra::UnitQuaternion::from_axis_angle(
+                &nalgebra::Vector3::z_axis(),
+                std::f32::consts::FRAC_PI_2,
+            )),
+            ScaleComponent::construct(nalgebra::Vector3::new(2.0, 2.0, 2.0)),
+        ));
+
+        world
+            .insert_one(grandchild, ParentComponent::construct(child))
+            .unwrap();
+        world.insert_one(child, ParentComponent::construct(root)).unwrap();
+
+        propagate_transforms_system(&mut world);
+
+        let child = global(&world, child);
+        assert!((child.position - nalgebra::Vector3::new(1.0, 2.0, 0.0)).norm() < 1e-5);
+        assert_eq!(child.scale, nalgebra::Vector3::new(2.0, 2.0, 2.0));
+
+        let grandchild = global(&world, grandchild);
+        assert!((grandchild.position - nalgebra::Vector3::new(-1.0, 2.0, 0.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn propagate_changed_transforms() {
+        let mut world = World::new();
+
+        let parent = world.spawn((
+            Changed::new(
+                PositionComponent::construct(nalgebra::Vector3::new(1.0, 0.0, 0.0)),
+                false,
+            ),
+            Changed::new(
+                ScaleComponent::construct(nalgebra::Vector3::new(2.0, 2.0, 2.0)),
+                false,
+            ),
+        ));
+
+        let child = world.spawn((
+            Changed::new(
+                PositionComponent::construct(nalgebra::Vector3::new(0.0, 1.0, 0.0)),
+                false,
+            ),
+            ParentComponent::construct(parent),
+            GlobalTransformComponent::construct(GlobalTransform::default()),
+        ));
+
+        propagate_transforms_system(&mut world);
+
+        let child = global(&world, child);
+        assert!((child.position - nalgebra::Vector3::new(1.0, 2.0, 0.0)).norm() < 1e-5);
+        assert_eq!(child.scale, nalgebra::Vector3::new(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn propagate_transforms_cycle_and_missing_parent() {
+        let mut world = World::new();
+
+        let a = world.spawn((
+            PositionComponent::construct(nalgebra::Vector3::new(1.0, 0.0, 0.0)),
+            GlobalTransformComponent::construct(GlobalTransform::default()),
+        ));
+        let b = world.spawn((
+            PositionComponent::construct(nalgebra::Vector3::new(1.0, 0.0, 0.0)),
+            GlobalTransformComponent::construct(GlobalTransform::default()),
+        ));
+        world.insert_one(a, ParentComponent::construct(b)).unwrap();
+        world.insert_one(b, ParentComponent::construct(a)).unwrap();
+
+        let despawned = world.spawn(());
+        world.despawn(despawned).unwrap();
+
+        let orphan = world.spawn((
+            PositionComponent::construct(nalgebra::Vector3::new(0.0, 0.0, 3.0)),
+            ParentComponent::construct(despawned),
+            GlobalTransformComponent::construct(GlobalTransform::default()),
+        ));
+
+        propagate_transforms_system(&mut world);
+
+        // Cyclic entities are left untouched
+        assert_eq!(global(&world, a), GlobalTransform::default());
+        assert!(!world.get::<GlobalTransformComponent>(a).unwrap().get_changed());
+        assert_eq!(global(&world, b), GlobalTransform::default());
+
+        // Entities with a missing parent are treated as roots
+        assert_eq!(
+            global(&world, orphan).position,
+            nalgebra::Vector3::new(0.0, 0.0, 3.0)
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use crate::TwoWayChannel;
-use crossbeam_channel::{Receiver, RecvError, SendError, Sender, TryRecvError, TrySendError};
+use crossbeam_channel::{
+    Receiver, RecvError, RecvTimeoutError, SendError, Sender, TryRecvError, TrySendError,
+};
 use hecs::{Component, DynamicBundle, Entity, Query, World};
-use std::any::TypeId;
+use std::{any::TypeId, collections::VecDeque, sync::Mutex, time::Duration};
 
 /// Struct for coordinating cross-thread communication between worlds
 #[derive(Default)]
@@ -12,8 +14,23 @@ pub struct WorldExchange {
 impl WorldExchange {
     pub fn create_channel<U: 'static>(&mut self) -> WorldChannel {
         let (cl, cr) = TwoWayChannel::unbounded();
-        self.channels.push((TypeId::of::<U>(), WorldChannel(cl)));
-        WorldChannel(cr)
+        self.channels.push((TypeId::of::<U>(), WorldChannel::new(cl)));
+        WorldChannel::new(cr)
+    }
+
+    /// Create a channel for world U whose queues hold at most `capacity` messages
+    ///
+    /// Applies backpressure to producers that outpace their consumer:
+    /// * `send_to` blocks until there is room in the queue
+    /// * `try_send_to` returns immediately with `TrySendError::Full`, handing the message back
+    ///   so the caller can drop it, retry later, or fall back to `send_to`
+    ///
+    /// The exchange blocks while forwarding to a full world,
+    /// so a bounded world should drain its messages before blocking on a send of its own.
+    pub fn create_bounded_channel<U: 'static>(&mut self, capacity: usize) -> WorldChannel {
+        let (cl, cr) = TwoWayChannel::bounded(capacity);
+        self.channels.push((TypeId::of::<U>(), WorldChannel::new(cl)));
+        WorldChannel::new(cr)
     }
 
     pub fn spawn(self) {
@@ -48,9 +65,13 @@ impl WorldExchange {
 }
 
 /// Two-way channel of world messages
-pub struct WorldChannel(TwoWayChannel<WorldMessage, WorldMessage>);
+pub struct WorldChannel(TwoWayChannel<WorldMessage, WorldMessage>, Mutex<MessageQueue>);
 
 impl WorldChannel {
+    fn new(channel: TwoWayChannel<WorldMessage, WorldMessage>) -> Self {
+        WorldChannel(channel, Default::default())
+    }
+
     pub fn tx(&self) -> &Sender<WorldMessage> {
         &self.0.tx
     }
@@ -74,6 +95,63 @@ impl WorldChannel {
     pub fn try_recv(&self) -> Result<WorldMessage, TryRecvError> {
         self.0.rx.try_recv()
     }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<WorldMessage, RecvTimeoutError> {
+        self.0.rx.recv_timeout(timeout)
+    }
+
+    /// Move received messages into the priority queue and pop the next one to handle
+    fn next_message(&self) -> Option<WorldMessage> {
+        let mut queue = self.1.lock().unwrap();
+        while let Ok(message) = self.try_recv() {
+            queue.push(message);
+        }
+        queue.pop()
+    }
+
+    fn has_queued_messages(&self) -> bool {
+        !self.1.lock().unwrap().is_empty()
+    }
+}
+
+/// Maximum number of consecutive high priority messages handled
+/// while normal priority messages are waiting
+pub const HIGH_PRIORITY_BURST: usize = 8;
+
+/// Two-tier queue of received messages, popped high priority first
+#[derive(Default)]
+pub struct MessageQueue {
+    high: VecDeque<WorldMessage>,
+    normal: VecDeque<WorldMessage>,
+    high_streak: usize,
+}
+
+impl MessageQueue {
+    pub fn push(&mut self, message: WorldMessage) {
+        match message.priority {
+            Priority::High => self.high.push_back(message),
+            Priority::Normal => self.normal.push_back(message),
+        }
+    }
+
+    /// Pop the next message, yielding a normal priority message
+    /// after every [`HIGH_PRIORITY_BURST`] high priority ones to avoid starving them
+    pub fn pop(&mut self) -> Option<WorldMessage> {
+        if self.high.is_empty() || self.high_streak >= HIGH_PRIORITY_BURST {
+            if let Some(message) = self.normal.pop_front() {
+                self.high_streak = 0;
+                return Some(message);
+            }
+        }
+
+        let message = self.high.pop_front()?;
+        self.high_streak += 1;
+        Some(message)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.high.is_empty() && self.normal.is_empty()
+    }
 }
 
 /// Trait for sending a message to a given world while inferring its type via param F
@@ -85,6 +163,14 @@ pub trait SendTo<Message, Value> {
     fn try_send_to<T>(&self, f: Message) -> Result<(), TrySendError<Value>>
     where
         T: 'static;
+
+    fn send_to_with_priority<T>(
+        &self,
+        f: Message,
+        priority: Priority,
+    ) -> Result<(), SendError<Value>>
+    where
+        T: 'static;
 }
 
 impl<Message> SendTo<Message, WorldMessage> for WorldChannel
@@ -104,12 +190,34 @@ where
     {
         self.try_send(WorldMessage::to::<W, _>(f))
     }
+
+    fn send_to_with_priority<W>(
+        &self,
+        f: Message,
+        priority: Priority,
+    ) -> Result<(), SendError<WorldMessage>>
+    where
+        W: 'static,
+    {
+        self.send(WorldMessage::to::<W, _>(f).with_priority(priority))
+    }
+}
+
+/// Order in which a receiving world handles its pending messages
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Bulk work such as asset loading
+    #[default]
+    Normal,
+    /// Latency-sensitive work such as input and window events
+    High,
 }
 
 /// Cross-thread message between worlds
 pub struct WorldMessage {
     sender: Option<std::any::TypeId>,
     receiver: std::any::TypeId,
+    priority: Priority,
     message: Box<
         dyn for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> + Send + 'static,
     >,
@@ -125,6 +233,14 @@ impl WorldMessage {
         self.receiver
     }
 
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn with_priority(self, priority: Priority) -> Self {
+        WorldMessage { priority, ..self }
+    }
+
     pub fn message(
         self,
     ) -> Box<dyn for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> + Send + 'static>
@@ -138,6 +254,7 @@ impl std::fmt::Debug for WorldMessage {
         f.debug_struct("WorldMessage")
             .field("from", &self.sender)
             .field("to", &self.receiver)
+            .field("priority", &self.priority)
             .finish()
     }
 }
@@ -150,11 +267,21 @@ impl WorldMessage {
     >(
         message: F,
     ) -> Self {
-        let receiver = TypeId::of::<U>();
+        WorldMessage::to_type_id(TypeId::of::<U>(), message)
+    }
+
+    /// Construct a message to be sent to the world identified by `receiver`
+    pub fn to_type_id<
+        F: for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> + Send + 'static,
+    >(
+        receiver: TypeId,
+        message: F,
+    ) -> Self {
         let message = Box::new(message);
         WorldMessage {
             sender: None,
             receiver,
+            priority: Priority::Normal,
             message,
         }
     }
@@ -170,6 +297,7 @@ impl WorldMessage {
         WorldMessage {
             sender: None,
             receiver,
+            priority: Priority::Normal,
             message,
         }
     }
@@ -229,7 +357,7 @@ fn insert_component<C: DynamicBundle>(
 }
 
 pub trait ClonedBundle {
-    type Bundle: DynamicBundle + Send + Sync + 'static;
+    type Bundle: DynamicBundle + Clone + Send + Sync + 'static;
 
     fn cloned_bundle(&self) -> Self::Bundle;
 }
@@ -323,6 +451,103 @@ where
     }
 }
 
+/// Compile-time set of receiving worlds, such as `(Game, Render)`
+pub trait Receivers {
+    fn type_ids() -> Vec<TypeId>;
+}
+
+impl<R1> Receivers for (R1,)
+where
+    R1: 'static,
+{
+    fn type_ids() -> Vec<TypeId> {
+        vec![TypeId::of::<R1>()]
+    }
+}
+
+impl<R1, R2> Receivers for (R1, R2)
+where
+    R1: 'static,
+    R2: 'static,
+{
+    fn type_ids() -> Vec<TypeId> {
+        vec![TypeId::of::<R1>(), TypeId::of::<R2>()]
+    }
+}
+
+impl<R1, R2, R3> Receivers for (R1, R2, R3)
+where
+    R1: 'static,
+    R2: 'static,
+    R3: 'static,
+{
+    fn type_ids() -> Vec<TypeId> {
+        vec![TypeId::of::<R1>(), TypeId::of::<R2>(), TypeId::of::<R3>()]
+    }
+}
+
+impl<R1, R2, R3, R4> Receivers for (R1, R2, R3, R4)
+where
+    R1: 'static,
+    R2: 'static,
+    R3: 'static,
+    R4: 'static,
+{
+    fn type_ids() -> Vec<TypeId> {
+        vec![
+            TypeId::of::<R1>(),
+            TypeId::of::<R2>(),
+            TypeId::of::<R3>(),
+            TypeId::of::<R4>(),
+        ]
+    }
+}
+
+/// Clone query Q once and send the result to each world in tuple R
+pub fn send_clone_query_multi<Q, R>(
+    entity: Entity,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b>
+where
+    Q: Query,
+    for<'q> <<Q as Query>::Fetch as hecs::Fetch<'q>>::Item: ClonedBundle,
+    R: Receivers,
+{
+    send_clone_query_to::<Q>(entity, R::type_ids())
+}
+
+/// Clone query Q once and send the result to each world in `receivers`
+pub fn send_clone_query_to<Q>(
+    entity: Entity,
+    receivers: Vec<TypeId>,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b>
+where
+    Q: Query,
+    for<'q> <<Q as Query>::Fetch as hecs::Fetch<'q>>::Item: ClonedBundle,
+{
+    move |mut ctx| {
+        let (world, channel) = &mut ctx;
+
+        println!(
+            "Thread {} sending cloned {} to {} threads",
+            std::thread::current().name().unwrap(),
+            std::any::type_name::<Q>(),
+            receivers.len(),
+        );
+
+        let components = world.query_one_mut::<Q>(entity).unwrap();
+        let bundle = components.cloned_bundle();
+        drop(components);
+
+        for receiver in receivers {
+            channel
+                .send(WorldMessage::to_type_id(receiver, spawn_bundle(bundle.clone())))
+                .unwrap();
+        }
+
+        Ok(ctx)
+    }
+}
+
 /// Clone singleton component C and send it to world U
 pub fn send_copy_component<C, U>(
     entity: Entity,
@@ -402,23 +627,171 @@ where
     }
 }
 
-/// Receive any pending messages from `channel` and handle them
+/// Receive any pending messages from `channel` and handle them, high priority first
 pub fn try_receive_messages(
     world: &mut World,
     channel: &WorldChannel,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    while let Ok(message) = channel.try_recv() {
+    while let Some(message) = channel.next_message() {
         (message.message())((world, channel))?;
     }
     Ok(())
 }
 
-/// Block until a message is received from `channel` and handle it
+/// Block until a message is received from `channel`, then handle all pending messages
 pub fn receive_messages(
     world: &mut World,
     channel: &WorldChannel,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let message = channel.recv().unwrap();
-    (message.message())((world, channel))?;
-    Ok(())
+    if !channel.has_queued_messages() {
+        let message = channel.recv().unwrap();
+        channel.1.lock().unwrap().push(message);
+    }
+    try_receive_messages(world, channel)
+}
+
+/// Outcome of [`receive_messages_timeout`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Received {
+    /// One or more messages were handled
+    Messages,
+    /// The timeout elapsed without any messages arriving
+    Timeout,
+}
+
+/// Wait up to `timeout` for a message from `channel`, then handle all pending messages
+///
+/// Allows an otherwise idle thread to run periodic maintenance without busy-waiting
+pub fn receive_messages_timeout(
+    world: &mut World,
+    channel: &WorldChannel,
+    timeout: Duration,
+) -> Result<Received, Box<dyn std::error::Error>> {
+    if !channel.has_queued_messages() {
+        match channel.recv_timeout(timeout) {
+            Ok(message) => channel.1.lock().unwrap().push(message),
+            Err(RecvTimeoutError::Timeout) => return Ok(Received::Timeout),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    try_receive_messages(world, channel)?;
+    Ok(Received::Messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounded_channel_full() {
+        let mut exchange = WorldExchange::default();
+        let channel = exchange.create_bounded_channel::<()>(2);
+
+        assert!(channel.try_send_to::<()>(|ctx: MessageContext| ctx.lift()).is_ok());
+        assert!(channel.try_send_to::<()>(|ctx: MessageContext| ctx.lift()).is_ok());
+
+        // A full queue hands the message back instead of blocking
+        let message = match channel.try_send_to::<()>(|ctx: MessageContext| ctx.lift()) {
+            Err(TrySendError::Full(message)) => message,
+            _ => panic!("Expected a full queue"),
+        };
+
+        // Draining the queue makes room to retry
+        let (_, exchange_channel) = &exchange.channels[0];
+        exchange_channel.try_recv().unwrap();
+        assert!(channel.try_send(message).is_ok());
+        assert!(matches!(
+            channel.try_send_to::<()>(|ctx: MessageContext| ctx.lift()),
+            Err(TrySendError::Full(_))
+        ));
+    }
+
+    fn record(
+        n: usize,
+    ) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+        move |mut ctx| {
+            let (world, _) = &mut ctx;
+            let (_, order) = world.query_mut::<&mut Vec<usize>>().into_iter().next().unwrap();
+            order.push(n);
+            Ok(ctx)
+        }
+    }
+
+    #[test]
+    fn receive_high_priority_first() {
+        let mut exchange = WorldExchange::default();
+        let channel = exchange.create_channel::<()>();
+        let (_, exchange_channel) = &exchange.channels[0];
+
+        let mut world = World::new();
+        world.spawn((Vec::<usize>::new(),));
+
+        // One normal message queued behind more high priority messages than the burst limit
+        exchange_channel.send(WorldMessage::to::<(), _>(record(0))).unwrap();
+        for n in 1..=HIGH_PRIORITY_BURST + 2 {
+            let message = WorldMessage::to::<(), _>(record(n)).with_priority(Priority::High);
+            exchange_channel.send(message).unwrap();
+        }
+
+        try_receive_messages(&mut world, &channel).unwrap();
+
+        let (_, order) = world.query_mut::<&Vec<usize>>().into_iter().next().unwrap();
+        let mut expected = (1..=HIGH_PRIORITY_BURST).collect::<Vec<_>>();
+        expected.extend([0, HIGH_PRIORITY_BURST + 1, HIGH_PRIORITY_BURST + 2]);
+        assert_eq!(*order, expected);
+    }
+
+    #[test]
+    fn send_clone_query_to_multiple_worlds() {
+        enum Game {}
+        enum Render {}
+
+        let mut exchange = WorldExchange::default();
+        let channel = exchange.create_channel::<()>();
+        let (_, exchange_channel) = &exchange.channels[0];
+
+        let mut world = World::new();
+        let entity = world.spawn((1usize, "Foo"));
+
+        send_clone_query_multi::<(&usize, &&str), (Game, Render)>(entity)((&mut world, &channel))
+            .unwrap();
+
+        for receiver in [TypeId::of::<Game>(), TypeId::of::<Render>()] {
+            let message = exchange_channel.try_recv().unwrap();
+            assert_eq!(message.receiver(), receiver);
+
+            let mut world = World::new();
+            (message.message())((&mut world, &channel)).unwrap();
+            let query = world.query_mut::<(&usize, &&str)>();
+            let (_, (n, s)) = query.into_iter().next().unwrap();
+            assert_eq!((*n, *s), (1, "Foo"));
+        }
+        assert!(exchange_channel.try_recv().is_err());
+    }
+
+    #[test]
+    fn receive_messages_with_timeout() {
+        let mut exchange = WorldExchange::default();
+        let channel = exchange.create_channel::<()>();
+        let (_, exchange_channel) = &exchange.channels[0];
+
+        let mut world = World::new();
+        world.spawn((Vec::<usize>::new(),));
+
+        let timeout = Duration::from_millis(10);
+        assert_eq!(
+            receive_messages_timeout(&mut world, &channel, timeout).unwrap(),
+            Received::Timeout
+        );
+
+        exchange_channel.send(WorldMessage::to::<(), _>(record(0))).unwrap();
+        exchange_channel.send(WorldMessage::to::<(), _>(record(1))).unwrap();
+        assert_eq!(
+            receive_messages_timeout(&mut world, &channel, timeout).unwrap(),
+            Received::Messages
+        );
+
+        let (_, order) = world.query_mut::<&Vec<usize>>().into_iter().next().unwrap();
+        assert_eq!(*order, [0, 1]);
+    }
 }
@@ -1,5 +1,10 @@
-use antigen_core::{Construct, MessageContext, MessageResult, Usage};
-use std::path::PathBuf;
+use antigen_core::{
+    Changed, ChangedTrait, Construct, MessageContext, MessageResult, Usage, WorldMessage,
+};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
 
 pub enum FilePath {}
 pub enum FileBytes {}
@@ -40,6 +45,22 @@ impl FileStringBundle {
     }
 }
 
+/// Minimum time a watched file must go unmodified before it is reloaded,
+/// so that rapid successive writes only trigger a single reload
+pub const FILE_WATCHER_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Modification-time watcher for a file entity
+///
+/// The Changed flag is set when the file's contents have been reloaded,
+/// and should be cleared by whichever system consumes the new contents.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct FileWatcher {
+    modified: Option<SystemTime>,
+    pending_since: Option<Instant>,
+}
+
+pub type FileWatcherComponent = Changed<FileWatcher>;
+
 #[derive(hecs::Query)]
 pub struct FileStringQuery<'a> {
     pub path: &'a FilePathComponent,
@@ -52,10 +73,56 @@ pub struct FileBytesQuery<'a> {
     pub string: &'a FileBytesComponent,
 }
 
-/// Load a file and store it in the World with a FileStringBundle
-pub fn load_file_string<'a, 'b, P: Into<PathBuf>>(
+/// Find the entities holding file strings loaded from `path`
+pub fn find_all_file_strings<'a>(
+    world: &'a mut hecs::World,
+    path: &'a Path,
+) -> impl Iterator<Item = (hecs::Entity, &'a FileStringComponent)> + 'a {
+    world
+        .query_mut::<FileStringQuery>()
+        .into_iter()
+        .filter(move |(_, query)| **query.path == *path)
+        .map(|(entity, FileStringQuery { string, .. })| (entity, string))
+}
+
+/// Find the entities holding file bytes loaded from `path`
+pub fn find_all_file_bytes<'a>(
+    world: &'a mut hecs::World,
+    path: &'a Path,
+) -> impl Iterator<Item = (hecs::Entity, &'a FileBytesComponent)> + 'a {
+    world
+        .query_mut::<FileBytesQuery>()
+        .into_iter()
+        .filter(move |(_, query)| **query.path == *path)
+        .map(|(entity, FileBytesQuery { string, .. })| (entity, string))
+}
+
+/// Find the first entity holding the file string loaded from `path`
+pub fn find_file_string<'a>(
+    world: &'a mut hecs::World,
+    path: &'a Path,
+) -> Option<(hecs::Entity, &'a FileStringComponent)> {
+    find_all_file_strings(world, path).next()
+}
+
+/// Find the first entity holding the file bytes loaded from `path`
+pub fn find_file_bytes<'a>(
+    world: &'a mut hecs::World,
+    path: &'a Path,
+) -> Option<(hecs::Entity, &'a FileBytesComponent)> {
+    find_all_file_bytes(world, path).next()
+}
+
+/// Load a file using `read` and store it in the World with the bundle produced by `bundle`
+fn load_file<'a, 'b, P, T, B>(
     path: P,
-) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    read: fn(&Path) -> std::io::Result<T>,
+    bundle: fn(PathBuf, T) -> B,
+) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b>
+where
+    P: Into<PathBuf>,
+    B: hecs::DynamicBundle,
+{
     move |mut ctx| -> MessageResult<'a, 'b> {
         let (world, _) = &mut ctx;
         let path = path.into();
@@ -65,33 +132,298 @@ pub fn load_file_string<'a, 'b, P: Into<PathBuf>>(
             std::thread::current().name().unwrap(),
             path,
         );
-        let file = std::fs::read_to_string(&path)?;
+        let file = read(&path)?;
 
         println!("Loaded file, spawning into world...");
-        world.spawn(FileStringBundle::new(path, file));
-        
+        world.spawn(bundle(path, file));
+
         Ok(ctx)
     }
 }
 
 /// Load a file and store it in the World with a FileStringBundle
+pub fn load_file_string<'a, 'b, P: Into<PathBuf>>(
+    path: P,
+) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    load_file(path, |path| std::fs::read_to_string(path), FileStringBundle::new)
+}
+
+/// Load a file and store it in the World with a FileBytesBundle
 pub fn load_file_bytes<'a, 'b, P: Into<PathBuf>>(
     path: P,
 ) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
-    move |mut ctx| {
-        let (world, _) = &mut ctx;
+    load_file(path, |path| std::fs::read(path), FileBytesBundle::new)
+}
+
+/// Load a file on a worker thread without blocking the calling world's message thread
+///
+/// Once the read completes, a follow-up message is sent back to world U
+/// that spawns a FileStringBundle and then runs `then`, which can be used to observe completion.
+/// Multiple deferred loads run concurrently.
+pub fn load_file_string_deferred<'a, 'b, U, P, F>(
+    path: P,
+    then: F,
+) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b>
+where
+    U: 'static,
+    P: Into<PathBuf>,
+    F: for<'c, 'd> FnOnce(MessageContext<'c, 'd>) -> MessageResult<'c, 'd> + Send + 'static,
+{
+    move |ctx| {
         let path = path.into();
+        let tx = ctx.1.tx().clone();
 
         println!(
-            "Thread {} loading file {:?}...",
+            "Thread {} deferring load of file {:?}...",
             std::thread::current().name().unwrap(),
             path,
         );
-        let file = std::fs::read_to_string(&path)?;
 
-        println!("Loaded file, spawning into world...");
-        world.spawn(FileStringBundle::new(path, file));
+        std::thread::Builder::new()
+            .name(format!("Load {:?}", path))
+            .spawn(move || {
+                let file = std::fs::read_to_string(&path);
+
+                let message = WorldMessage::to::<U, _>(move |mut ctx| {
+                    let (world, _) = &mut ctx;
+                    let file = file?;
+
+                    println!("Loaded deferred file {:?}, spawning into world...", path);
+                    world.spawn(FileStringBundle::new(path, file));
+
+                    then(ctx)
+                });
+
+                if tx.send(message).is_err() {
+                    println!("Failed to send deferred file load, receiving world has closed");
+                }
+            })?;
 
         Ok(ctx)
     }
 }
+
+/// Watch a file for changes, loading it into a FileStringBundle if not already present
+pub fn watch_file_string<'a, 'b, P: Into<PathBuf>>(
+    path: P,
+) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let path = path.into();
+        let modified = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+
+        let (world, _) = &mut ctx;
+        if find_file_string(world, &path).is_none() {
+            ctx = load_file_string(path.clone())(ctx)?;
+        }
+
+        let (world, _) = &mut ctx;
+        let entities = world
+            .query_mut::<FileStringQuery>()
+            .without::<FileWatcherComponent>()
+            .into_iter()
+            .filter(|(_, FileStringQuery { path: candidate, .. })| ***candidate == *path)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+
+        for entity in entities {
+            println!("Watching file {:?} for changes", path);
+            world.insert_one(
+                entity,
+                FileWatcherComponent::construct(FileWatcher {
+                    modified,
+                    pending_since: None,
+                }),
+            )?;
+        }
+
+        Ok(ctx)
+    }
+}
+
+/// Poll watched files and reload their contents once they have settled
+///
+/// Files that are missing or unreadable (such as when temporarily removed during an editor save)
+/// retain their existing contents and are retried on the next poll.
+pub fn file_watcher_system(world: &mut hecs::World) {
+    for (_, (path, string, watcher)) in world.query_mut::<(
+        &FilePathComponent,
+        &mut FileStringComponent,
+        &mut FileWatcherComponent,
+    )>() {
+        let modified = match std::fs::metadata(&**path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(_) => continue,
+        };
+
+        // Restart the debounce period whenever the file is written
+        if watcher.modified != Some(modified) {
+            watcher.modified = Some(modified);
+            watcher.pending_since = Some(Instant::now());
+            continue;
+        }
+
+        match watcher.pending_since {
+            Some(pending_since) if pending_since.elapsed() >= FILE_WATCHER_DEBOUNCE => (),
+            _ => continue,
+        }
+
+        let file = match std::fs::read_to_string(&**path) {
+            Ok(file) => file,
+            Err(_) => continue,
+        };
+
+        println!("Reloaded watched file {:?}", **path);
+        **string = file;
+        watcher.pending_since = None;
+        watcher.set_changed(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use antigen_core::WorldExchange;
+
+    #[test]
+    fn load_file_bytes_round_trip() {
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        let path = std::env::temp_dir().join("antigen-fs-load-file-bytes.bin");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut world = hecs::World::new();
+        let channel = WorldExchange::default().create_channel::<()>();
+        load_file_bytes(path.clone())((&mut world, &channel)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut query = world.query::<(&FilePathComponent, &FileBytesComponent)>();
+        let (_, (loaded_path, loaded_bytes)) = query.into_iter().next().unwrap();
+        assert_eq!(**loaded_path, path);
+        assert_eq!(**loaded_bytes, bytes);
+        drop(query);
+
+        assert!(world.query::<&FileStringComponent>().iter().next().is_none());
+    }
+
+    // Spawn a marker entity once a deferred load has completed
+    fn spawn_marker() -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+        |mut ctx| {
+            let (world, _) = &mut ctx;
+            world.spawn(("Loaded",));
+            Ok(ctx)
+        }
+    }
+
+    // Notify `tx` once a deferred load has completed
+    fn signal(
+        tx: std::sync::mpsc::Sender<()>,
+    ) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+        move |ctx| {
+            tx.send(()).unwrap();
+            Ok(ctx)
+        }
+    }
+
+    #[test]
+    fn load_file_string_deferred_spawns_on_reply() {
+        let path = std::env::temp_dir().join("antigen-fs-load-file-string-deferred.txt");
+        std::fs::write(&path, "Deferred").unwrap();
+
+        let mut exchange = WorldExchange::default();
+        let channel = exchange.create_channel::<()>();
+        exchange.spawn();
+
+        let mut world = hecs::World::new();
+        load_file_string_deferred::<(), _, _>(path.clone(), spawn_marker())((&mut world, &channel))
+            .unwrap();
+
+        // Nothing is spawned until the worker's reply is handled
+        assert!(world.query::<&FileStringComponent>().iter().next().is_none());
+
+        let timeout = Duration::from_secs(5);
+        while world.query::<&&str>().iter().next().is_none() {
+            assert_eq!(
+                antigen_core::receive_messages_timeout(&mut world, &channel, timeout).unwrap(),
+                antigen_core::Received::Messages
+            );
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        let mut query = world.query::<(&FilePathComponent, &FileStringComponent)>();
+        let (_, (loaded_path, loaded_string)) = query.into_iter().next().unwrap();
+        assert_eq!(**loaded_path, path);
+        assert_eq!(**loaded_string, "Deferred");
+    }
+
+    #[test]
+    fn load_file_string_deferred_drops_reply_for_closed_world() {
+        let path = std::env::temp_dir().join("antigen-fs-load-file-string-deferred-closed.txt");
+        std::fs::write(&path, "Deferred").unwrap();
+
+        // Without a running exchange, nothing receives the worker's reply
+        let channel = WorldExchange::default().create_channel::<()>();
+
+        // The reply message owns the sender, so its drop signals that the worker gave up on it
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+
+        let mut world = hecs::World::new();
+        load_file_string_deferred::<(), _, _>(path.clone(), signal(tx))((&mut world, &channel))
+            .unwrap();
+
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected)
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(channel.try_recv().is_err());
+        assert!(world.query::<&FileStringComponent>().iter().next().is_none());
+    }
+
+    #[test]
+    fn file_watcher_reloads_changed_files() {
+        let changed_path = std::env::temp_dir().join("antigen-fs-file-watcher-changed.txt");
+        let unchanged_path = std::env::temp_dir().join("antigen-fs-file-watcher-unchanged.txt");
+        std::fs::write(&changed_path, "Before").unwrap();
+        std::fs::write(&unchanged_path, "Before").unwrap();
+
+        let mut world = hecs::World::new();
+        let channel = WorldExchange::default().create_channel::<()>();
+        watch_file_string(changed_path.clone())((&mut world, &channel)).unwrap();
+        watch_file_string(unchanged_path.clone())((&mut world, &channel)).unwrap();
+
+        // Move the modification time explicitly, as filesystem timestamps may be coarse
+        std::fs::write(&changed_path, "After").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&changed_path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+
+        // The first poll starts the debounce period, and the next one after it reloads
+        file_watcher_system(&mut world);
+        std::thread::sleep(FILE_WATCHER_DEBOUNCE);
+        file_watcher_system(&mut world);
+
+        std::fs::remove_file(&changed_path).unwrap();
+        std::fs::remove_file(&unchanged_path).unwrap();
+
+        let watched = |path: &Path| {
+            world
+                .query::<(
+                    &FilePathComponent,
+                    &FileStringComponent,
+                    &FileWatcherComponent,
+                )>()
+                .into_iter()
+                .find(|(_, (candidate, _, _))| ***candidate == *path)
+                .map(|(_, (_, string, watcher))| ((**string).clone(), watcher.get_changed()))
+                .unwrap()
+        };
+
+        assert_eq!(watched(&changed_path), ("After".to_owned(), true));
+        assert_eq!(watched(&unchanged_path), ("Before".to_owned(), false));
+    }
+}
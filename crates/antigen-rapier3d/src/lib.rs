@@ -1,17 +1,22 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use parking_lot::{RwLock, RwLockReadGuard};
 
 pub use rapier3d;
 
 use antigen_core::{
-    Construct, Indirect, LazyComponent, PositionComponent, RotationComponent, Usage,
+    Changed, ChangedTrait, Construct, Indirect, LazyComponent, PositionComponent,
+    RotationComponent, ScaleComponent, Usage,
 };
 use hecs::{EntityBuilder, Query, World};
 use rapier3d::{
+    parry::query::TOIStatus,
     pipeline::EventHandler,
     prelude::{
         BroadPhase, CCDSolver, Collider, ColliderHandle, ColliderSet, ContactEvent, ContactPair,
-        IntegrationParameters, IntersectionEvent, IslandManager, JointSet, NarrowPhase,
-        PhysicsPipeline, RigidBody, RigidBodyHandle, RigidBodySet, RigidBodyType,
+        IntegrationParameters, InteractionGroups, IntersectionEvent, IslandManager, JointHandle,
+        JointParams, JointSet, NarrowPhase, PhysicsPipeline, QueryPipeline, RigidBody,
+        RigidBodyHandle, RigidBodySet, RigidBodyType, SharedShape, TypedShape,
     },
 };
 
@@ -19,6 +24,20 @@ use rapier3d::{
 pub enum Gravity {}
 pub type GravityComponent = Usage<Gravity, rapier3d::prelude::nalgebra::Vector3<f32>>;
 
+// Gravity Region, an axis-aligned volume that overrides global gravity for bodies inside it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GravityRegionComponent {
+    pub min: nalgebra::Vector3<f32>,
+    pub max: nalgebra::Vector3<f32>,
+    pub gravity: nalgebra::Vector3<f32>,
+}
+
+impl GravityRegionComponent {
+    pub fn contains(&self, point: &nalgebra::Vector3<f32>) -> bool {
+        (0..3).all(|i| point[i] >= self.min[i] && point[i] <= self.max[i])
+    }
+}
+
 // Linear Velocity
 pub enum LinearVelocity {}
 pub type LinearVelocityComponent = Usage<LinearVelocity, nalgebra::Vector3<f32>>;
@@ -27,11 +46,49 @@ pub type LinearVelocityComponent = Usage<LinearVelocity, nalgebra::Vector3<f32>>
 pub enum AngularVelocity {}
 pub type AngularVelocityComponent = Usage<AngularVelocity, nalgebra::Vector3<f32>>;
 
+// Previous Position, the position before the most recent physics read-back
+pub enum PreviousPosition {}
+pub type PreviousPositionComponent = Usage<PreviousPosition, nalgebra::Vector3<f32>>;
+
+// Previous Rotation, the rotation before the most recent physics read-back
+pub enum PreviousRotation {}
+pub type PreviousRotationComponent = Usage<PreviousRotation, nalgebra::UnitQuaternion<f32>>;
+
+// Contact start, with the normal impulse applied or needed to resolve it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ImpactEvent {
+    pub collider1: ColliderHandle,
+    pub collider2: ColliderHandle,
+    pub impulse: f32,
+}
+
+// Minimum impulse for a contact to register as an impact,
+// used to filter out resting contacts
+pub enum ImpactThreshold {}
+pub type ImpactThresholdComponent = Usage<ImpactThreshold, f32>;
+
+/// How long an EventCollector holds onto the intersection events it collects
+///
+/// Contact and impact events are always cleared each step,
+/// as they're only meaningful alongside the narrow phase state that produced them.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum EventRetention {
+    /// Clear events each time clear_physics_event_collector_system runs
+    #[default]
+    ClearEachStep,
+    /// Keep events until they're read with EventCollector::consume_intersection_events,
+    /// for consumers that run less often than physics
+    KeepUntilConsumed,
+}
+
 // Event Handler
 #[derive(Default)]
 pub struct EventCollector {
     pub intersection_events: parking_lot::RwLock<Vec<IntersectionEvent>>,
     pub contact_events: parking_lot::RwLock<Vec<(ContactEvent, ContactPair)>>,
+    pub impact_events: parking_lot::RwLock<Vec<ImpactEvent>>,
+    // Number of leading intersection events that have been consumed
+    intersection_events_consumed: AtomicUsize,
 }
 
 impl EventHandler for EventCollector {
@@ -55,13 +112,97 @@ impl EventCollector {
         self.intersection_events.read()
     }
 
+    /// Returns the intersection events that haven't been consumed yet, marking them as consumed
+    pub fn consume_intersection_events(&self) -> Vec<IntersectionEvent> {
+        let events = self.intersection_events.read();
+        let consumed = self
+            .intersection_events_consumed
+            .swap(events.len(), Ordering::Relaxed);
+        events[consumed.min(events.len())..].to_vec()
+    }
+
     pub fn contact_events(&self) -> RwLockReadGuard<Vec<(ContactEvent, ContactPair)>> {
         self.contact_events.read()
     }
 
+    pub fn impact_events(&self) -> RwLockReadGuard<Vec<ImpactEvent>> {
+        self.impact_events.read()
+    }
+
+    /// Returns the total normal impulse of each collected contact pair
+    pub fn contact_impulses(&self) -> Vec<(ColliderHandle, ColliderHandle, f32)> {
+        self.contact_events
+            .read()
+            .iter()
+            .map(|(_, contact_pair)| {
+                (
+                    contact_pair.collider1,
+                    contact_pair.collider2,
+                    total_normal_impulse(contact_pair),
+                )
+            })
+            .collect()
+    }
+
+    /// Replace collected contact pairs with their current state in `narrow_phase`
+    ///
+    /// Contact events are emitted before the solver runs,
+    /// so this is used after stepping to pick up the resolved impulses.
+    pub fn refresh_contact_pairs(&self, narrow_phase: &NarrowPhase) {
+        for (_, contact_pair) in self.contact_events.write().iter_mut() {
+            if let Some(current) =
+                narrow_phase.contact_pair(contact_pair.collider1, contact_pair.collider2)
+            {
+                *contact_pair = current.clone();
+            }
+        }
+    }
+
     pub fn clear(&self) {
         self.intersection_events.write().clear();
+        self.intersection_events_consumed.store(0, Ordering::Relaxed);
         self.contact_events.write().clear();
+        self.impact_events.write().clear();
+    }
+
+    /// Clear collected events according to `retention`
+    pub fn clear_with_retention(&self, retention: EventRetention) {
+        match retention {
+            EventRetention::ClearEachStep => self.clear(),
+            EventRetention::KeepUntilConsumed => {
+                let mut intersection_events = self.intersection_events.write();
+                let consumed = self.intersection_events_consumed.swap(0, Ordering::Relaxed);
+                let consumed = consumed.min(intersection_events.len());
+                intersection_events.drain(..consumed);
+
+                self.contact_events.write().clear();
+                self.impact_events.write().clear();
+            }
+        }
+    }
+}
+
+// Per-body multiplier on its backend's gravity, written to the rigid body on insertion
+// and again by update_gravity_scales_system whenever it's flagged as changed
+pub enum GravityScale {}
+pub type GravityScaleComponent = Usage<GravityScale, f32>;
+
+// Physics step configuration, applied onto IntegrationParameters by configure_physics_system
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PhysicsStepConfig {
+    pub dt: f32,
+    pub max_ccd_substeps: usize,
+    pub enabled: bool,
+}
+
+impl Default for PhysicsStepConfig {
+    fn default() -> Self {
+        let integration_parameters = IntegrationParameters::default();
+        PhysicsStepConfig {
+            dt: integration_parameters.dt,
+            max_ccd_substeps: integration_parameters.max_ccd_substeps,
+            enabled: true,
+        }
     }
 }
 
@@ -78,7 +219,9 @@ pub struct PhysicsQuery<'a> {
     pub collider_set: &'a mut ColliderSet,
     pub joint_set: &'a mut JointSet,
     pub ccd_solver: &'a mut CCDSolver,
+    pub query_pipeline: &'a mut QueryPipeline,
     pub event_collector: &'a EventCollector,
+    pub step_config: Option<&'a PhysicsStepConfig>,
 }
 
 pub fn physics_backend_builder(gravity: nalgebra::Vector3<f32>) -> EntityBuilder {
@@ -88,6 +231,7 @@ pub fn physics_backend_builder(gravity: nalgebra::Vector3<f32>) -> EntityBuilder
         rapier3d::prelude::nalgebra::Vector3::new(gravity.x, gravity.y, gravity.z),
     ));
     builder.add(IntegrationParameters::default());
+    builder.add(PhysicsStepConfig::default());
     builder.add(PhysicsPipeline::default());
     builder.add(IslandManager::new());
     builder.add(BroadPhase::new());
@@ -96,11 +240,24 @@ pub fn physics_backend_builder(gravity: nalgebra::Vector3<f32>) -> EntityBuilder
     builder.add(ColliderSet::new());
     builder.add(JointSet::new());
     builder.add(CCDSolver::new());
+    builder.add(QueryPipeline::new());
     builder.add(EventCollector::default());
 
     builder
 }
 
+/// Apply each backend's PhysicsStepConfig onto its IntegrationParameters
+pub fn configure_physics_system(world: &mut World) {
+    for (_, (step_config, integration_parameters)) in world
+        .query_mut::<(&PhysicsStepConfig, &mut IntegrationParameters)>()
+        .into_iter()
+    {
+        integration_parameters.dt = step_config.dt;
+        integration_parameters.max_ccd_substeps = step_config.max_ccd_substeps;
+    }
+}
+
+/// Step each physics backend, skipping those whose PhysicsStepConfig is disabled
 pub fn step_physics_system(world: &mut World) {
     for (
         _,
@@ -115,10 +272,16 @@ pub fn step_physics_system(world: &mut World) {
             collider_set,
             joint_set,
             ccd_solver,
+            query_pipeline,
             event_collector,
+            step_config,
         },
     ) in world.query_mut::<PhysicsQuery>().into_iter()
     {
+        if let Some(PhysicsStepConfig { enabled: false, .. }) = step_config {
+            continue;
+        }
+
         physics_pipeline.step(
             &gravity,
             integration_parameters,
@@ -132,12 +295,170 @@ pub fn step_physics_system(world: &mut World) {
             &(),
             event_collector,
         );
+
+        event_collector.refresh_contact_pairs(narrow_phase);
+
+        // Keep scene queries in sync with the stepped collider positions
+        query_pipeline.update(island_manager, rigid_body_set, collider_set);
+    }
+}
+
+/// Sum the normal impulses of each point in each manifold of `contact_pair`
+fn total_normal_impulse(contact_pair: &ContactPair) -> f32 {
+    contact_pair
+        .manifolds
+        .iter()
+        .flat_map(|manifold| manifold.points.iter())
+        .map(|point| point.data.impulse)
+        .sum()
+}
+
+/// Apply gravity region overrides to the dynamic bodies inside them
+///
+/// Rapier only supports a single global gravity vector,
+/// so the difference between region and global gravity is applied as a force prior to stepping.
+/// Where regions overlap, the first one found takes precedence.
+/// Backends whose PhysicsStepConfig is disabled are skipped so forces don't accumulate.
+pub fn apply_gravity_regions_system(world: &mut World) {
+    let regions = world
+        .query_mut::<&GravityRegionComponent>()
+        .into_iter()
+        .map(|(_, region)| *region)
+        .collect::<Vec<_>>();
+
+    if regions.is_empty() {
+        return;
+    }
+
+    for (_, (gravity, rigid_body_set, step_config)) in world
+        .query_mut::<(
+            &GravityComponent,
+            &mut RigidBodySet,
+            Option<&PhysicsStepConfig>,
+        )>()
+        .into_iter()
+    {
+        if let Some(PhysicsStepConfig { enabled: false, .. }) = step_config {
+            continue;
+        }
+
+        for (_, rb) in rigid_body_set.iter_mut() {
+            // Sleeping bodies are at rest and keep any force applied to them until woken,
+            // so leave them be rather than waking or loading them every tick
+            if !rb.is_dynamic() || rb.is_sleeping() {
+                continue;
+            }
+
+            let pos = rb.translation();
+            let pos = nalgebra::vector![pos.x, pos.y, pos.z];
+
+            let region = match regions.iter().find(|region| region.contains(&pos)) {
+                Some(region) => region,
+                None => continue,
+            };
+
+            let delta = (region.gravity - nalgebra::vector![gravity.x, gravity.y, gravity.z])
+                * rb.mass()
+                * rb.gravity_scale();
+
+            if delta == nalgebra::Vector3::zeros() {
+                continue;
+            }
+
+            // Waking here would reset the sleep timer and keep resting bodies awake forever
+            rb.apply_force(
+                rapier3d::prelude::nalgebra::Vector3::new(delta.x, delta.y, delta.z),
+                false,
+            );
+        }
+    }
+}
+
+/// Sum the impulse needed to stop the approach of each manifold of `contact_pair`
+///
+/// Contacts that start during the collision detection at the end of a step
+/// haven't been solved yet, so this measures them from the bodies' closing velocity.
+fn approach_impulse(contact_pair: &ContactPair, rigid_body_set: &RigidBodySet) -> f32 {
+    let body =
+        |handle: Option<RigidBodyHandle>| handle.and_then(|handle| rigid_body_set.get(handle));
+    let inv_mass = |rb: Option<&RigidBody>| match rb {
+        Some(rb) if rb.is_dynamic() => 1.0 / rb.mass(),
+        _ => 0.0,
+    };
+
+    contact_pair
+        .manifolds
+        .iter()
+        .filter(|manifold| !manifold.points.is_empty())
+        .map(|manifold| {
+            let rb1 = body(manifold.data.rigid_body1);
+            let rb2 = body(manifold.data.rigid_body2);
+
+            let inv_mass = inv_mass(rb1) + inv_mass(rb2);
+            if inv_mass <= 0.0 {
+                return 0.0;
+            }
+
+            let linvel = |rb: Option<&RigidBody>| rb.map(|rb| *rb.linvel()).unwrap_or_default();
+            let closing_speed = (linvel(rb1) - linvel(rb2)).dot(&manifold.data.normal);
+
+            closing_speed.max(0.0) / inv_mass
+        })
+        .sum()
+}
+
+/// Convert contacts started this step into impact events
+///
+/// Collected contact pairs are refreshed after each step, so contacts detected
+/// before the solve carry that step's impulses, while those detected after it
+/// are measured by the impulse needed to stop their approach.
+/// Contacts that start and separate within a single step keep their last known pair.
+pub fn collect_impact_events_system(world: &mut World) {
+    for (_, (rigid_body_set, event_collector, threshold)) in world
+        .query_mut::<(
+            &RigidBodySet,
+            &EventCollector,
+            Option<&ImpactThresholdComponent>,
+        )>()
+        .into_iter()
+    {
+        let threshold = threshold.map(|threshold| **threshold).unwrap_or_default();
+
+        let impacts = event_collector
+            .contact_events()
+            .iter()
+            .filter_map(|(event, contact_pair)| {
+                let (collider1, collider2) = match event {
+                    ContactEvent::Started(collider1, collider2) => (*collider1, *collider2),
+                    ContactEvent::Stopped(_, _) => return None,
+                };
+
+                let impulse = total_normal_impulse(contact_pair)
+                    .max(approach_impulse(contact_pair, rigid_body_set));
+
+                if impulse < threshold {
+                    return None;
+                }
+
+                Some(ImpactEvent {
+                    collider1,
+                    collider2,
+                    impulse,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        event_collector.impact_events.write().extend(impacts);
     }
 }
 
+/// Clear each backend's collected events, honoring its EventRetention if it has one
 pub fn clear_physics_event_collector_system(world: &mut World) {
-    for (_, event_collector) in world.query_mut::<&EventCollector>().into_iter() {
-        event_collector.clear()
+    for (_, (event_collector, retention)) in world
+        .query_mut::<(&EventCollector, Option<&EventRetention>)>()
+        .into_iter()
+    {
+        event_collector.clear_with_retention(retention.copied().unwrap_or_default())
     }
 }
 
@@ -146,21 +467,154 @@ pub type ColliderComponent = LazyComponent<ColliderHandle, Collider>;
 pub enum ColliderParent {}
 pub type ColliderParentComponent<'a> = Usage<ColliderParent, Indirect<&'a RigidBodyComponent>>;
 
+// Unscaled collider shape and the scale last applied to it,
+// used to regenerate the collider's shape when its entity's scale changes
+#[derive(Clone)]
+pub struct ScaledShape {
+    shape: SharedShape,
+    applied: nalgebra::Vector3<f32>,
+}
+
+impl ScaledShape {
+    pub fn new(shape: SharedShape) -> Self {
+        ScaledShape {
+            shape,
+            applied: nalgebra::Vector3::repeat(1.0),
+        }
+    }
+
+    pub fn scaled(&self, scale: &nalgebra::Vector3<f32>) -> SharedShape {
+        scale_shape(
+            &self.shape,
+            &rapier3d::prelude::nalgebra::Vector3::new(scale.x, scale.y, scale.z),
+        )
+    }
+}
+
+/// Regenerate `shape` with `scale` applied, since rapier has no concept of scale
+///
+/// Balls are scaled by their largest axis, cuboids, convex hulls and trimeshes per-axis.
+/// Capsules and cylinders are treated as upright, scaling their radius by the largest
+/// horizontal axis and their height by the vertical axis.
+/// Compound sub-shapes have their translations scaled and their shapes scaled in local space.
+/// Unsupported shapes are returned unchanged.
+pub fn scale_shape(
+    shape: &SharedShape,
+    scale: &rapier3d::prelude::nalgebra::Vector3<f32>,
+) -> SharedShape {
+    match shape.as_typed_shape() {
+        TypedShape::Ball(ball) => SharedShape::ball(ball.radius * scale.abs().max()),
+        TypedShape::Cuboid(cuboid) => {
+            let half_extents = cuboid.half_extents.component_mul(&scale.abs());
+            SharedShape::cuboid(half_extents.x, half_extents.y, half_extents.z)
+        }
+        TypedShape::Capsule(capsule) => SharedShape::capsule(
+            capsule.segment.a.coords.component_mul(scale).into(),
+            capsule.segment.b.coords.component_mul(scale).into(),
+            capsule.radius * scale.x.abs().max(scale.z.abs()),
+        ),
+        TypedShape::Cylinder(cylinder) => SharedShape::cylinder(
+            cylinder.half_height * scale.y.abs(),
+            cylinder.radius * scale.x.abs().max(scale.z.abs()),
+        ),
+        TypedShape::ConvexPolyhedron(convex) => {
+            let points = convex
+                .points()
+                .iter()
+                .map(|point| point.coords.component_mul(scale).into())
+                .collect::<Vec<_>>();
+            SharedShape::convex_hull(&points).unwrap_or_else(|| shape.clone())
+        }
+        TypedShape::TriMesh(trimesh) => {
+            let vertices = trimesh
+                .vertices()
+                .iter()
+                .map(|vertex| vertex.coords.component_mul(scale).into())
+                .collect();
+            SharedShape::trimesh(vertices, trimesh.indices().to_vec())
+        }
+        TypedShape::Compound(compound) => SharedShape::compound(
+            compound
+                .shapes()
+                .iter()
+                .map(|(isometry, shape)| {
+                    let mut isometry = *isometry;
+                    isometry.translation.vector.component_mul_assign(scale);
+                    (isometry, scale_shape(shape, scale))
+                })
+                .collect(),
+        ),
+        _ => shape.clone(),
+    }
+}
+
+/// Regenerate the shapes of inserted colliders whose entity's scale
+/// differs from the scale last applied to their ScaledShape
+pub fn rebuild_scaled_colliders_system(world: &mut World) {
+    let mut query = world.query::<&mut ColliderSet>();
+    let (_, collider_set) = query.into_iter().next().unwrap();
+
+    for (_, (collider_component, scaled_shape, scale)) in world
+        .query::<(&ColliderComponent, &mut ScaledShape, &ScaleComponent)>()
+        .into_iter()
+    {
+        if scaled_shape.applied == **scale {
+            continue;
+        }
+
+        if let LazyComponent::Ready(handle) = collider_component {
+            if let Some(collider) = collider_set.get_mut(*handle) {
+                collider.set_shape(scaled_shape.scaled(scale));
+                scaled_shape.applied = **scale;
+            }
+        }
+    }
+}
+
+// Collision Groups, applied to a collider on insertion
+pub enum CollisionGroups {}
+pub type CollisionGroupsComponent = Usage<CollisionGroups, InteractionGroups>;
+
+/// Insert pending colliders, attaching them to their rigid body if any
+///
+/// Unparented colliders take their position, rotation and scale from their entity.
+/// Scale is baked into ball, cuboid, capsule and cylinder shapes via scale_shape,
+/// and a ScaledShape is attached so rebuild_scaled_colliders_system can follow later changes;
+/// other shapes are expected to be built at scale, and are inserted as-is.
 pub fn insert_colliders_system(world: &mut World) {
     let mut query = world.query::<(&mut ColliderSet, &mut RigidBodySet)>();
     let (_, (collider_set, rigid_body_set)) = query.into_iter().next().unwrap();
 
-    for (_, (collider_component, position, rotation, rigid_body, collider_parent)) in world
+    let mut scaled_shapes = vec![];
+
+    for (
+        entity,
+        (
+            collider_component,
+            position,
+            rotation,
+            scale,
+            rigid_body,
+            collider_parent,
+            collision_groups,
+        ),
+    ) in world
         .query::<(
             &mut ColliderComponent,
             Option<&PositionComponent>,
             Option<&RotationComponent>,
+            Option<&ScaleComponent>,
             Option<&RigidBodyComponent>,
             Option<&ColliderParentComponent>,
+            Option<&CollisionGroupsComponent>,
         )>()
         .into_iter()
     {
         if let ColliderComponent::Pending(collider) = collider_component {
+            if let Some(collision_groups) = collision_groups {
+                collider.set_collision_groups(**collision_groups);
+            }
+
             // If not attached to a rigidbody, apply position / rotation directly
             if rigid_body.is_none() {
                 if let Some(position) = position {
@@ -173,6 +627,28 @@ pub fn insert_colliders_system(world: &mut World) {
                     let (x, y, z) = rotation.euler_angles();
                     collider.set_rotation(rapier3d::prelude::nalgebra::Vector3::new(x, y, z));
                 }
+
+                // Colliders with a ColliderParent may stay pending across frames,
+                // so are left unscaled rather than risk scaling them twice
+                if let (Some(scale), None) = (scale, collider_parent) {
+                    match collider.shape().as_typed_shape() {
+                        TypedShape::Ball(_)
+                        | TypedShape::Cuboid(_)
+                        | TypedShape::Capsule(_)
+                        | TypedShape::Cylinder(_) => {
+                            let mut scaled_shape =
+                                ScaledShape::new(collider.shared_shape().clone());
+                            collider.set_shape(scaled_shape.scaled(scale));
+                            scaled_shape.applied = **scale;
+                            scaled_shapes.push((entity, scaled_shape));
+                        }
+                        _ if **scale != nalgebra::Vector3::repeat(1.0) => println!(
+                            "Warning: Can't scale {:?} collider on insertion, leaving it as built",
+                            collider.shape().shape_type()
+                        ),
+                        _ => (),
+                    }
+                }
             }
 
             match (rigid_body, collider_parent) {
@@ -212,6 +688,132 @@ pub fn insert_colliders_system(world: &mut World) {
             }
         }
     }
+
+    drop(query);
+
+    for (entity, scaled_shape) in scaled_shapes {
+        world.insert_one(entity, scaled_shape).unwrap();
+    }
+}
+
+pub type JointComponent = LazyComponent<JointHandle, JointParams>;
+
+pub enum JointParent {}
+pub type JointParentComponent<'a> = Usage<JointParent, Indirect<&'a RigidBodyComponent>>;
+
+pub enum JointChild {}
+pub type JointChildComponent<'a> = Usage<JointChild, Indirect<&'a RigidBodyComponent>>;
+
+/// Insert pending joints once both their parent and child rigid bodies are ready
+pub fn insert_joints_system(world: &mut World) {
+    let mut query = world.query::<&mut JointSet>();
+    let (_, joint_set) = query.into_iter().next().unwrap();
+
+    for (_, (joint_component, joint_parent, joint_child)) in world
+        .query::<(
+            &mut JointComponent,
+            &JointParentComponent,
+            &JointChildComponent,
+        )>()
+        .into_iter()
+    {
+        if let JointComponent::Pending(_) = joint_component {
+            // Endpoints without a rigid body leave the joint pending until they have one
+            let mut query = joint_parent.get(world);
+            let parent = if let Some(parent) = query.get() {
+                parent
+            } else {
+                continue;
+            };
+            let parent = if let LazyComponent::Ready(parent) = **parent {
+                parent
+            } else {
+                continue;
+            };
+
+            let mut query = joint_child.get(world);
+            let child = if let Some(child) = query.get() {
+                child
+            } else {
+                continue;
+            };
+            let child = if let LazyComponent::Ready(child) = **child {
+                child
+            } else {
+                continue;
+            };
+
+            let joint = if let LazyComponent::Pending(joint) = joint_component.take() {
+                joint
+            } else {
+                panic!("No joint component")
+            };
+            let handle = joint_set.insert(parent, child, joint);
+            *joint_component = JointComponent::Ready(handle);
+        }
+    }
+}
+
+// Marker for entities whose rigid body and / or collider should be removed from the physics backend
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct PendingRemoval;
+
+/// Remove the colliders of entities marked PendingRemoval from the physics backend
+pub fn remove_colliders_system(world: &mut World) {
+    let mut query = world.query::<(&mut ColliderSet, &mut RigidBodySet, &mut IslandManager)>();
+    let (_, (collider_set, rigid_body_set, island_manager)) = query.into_iter().next().unwrap();
+
+    for (_, collider_component) in world
+        .query::<&mut ColliderComponent>()
+        .with::<PendingRemoval>()
+        .into_iter()
+    {
+        if let LazyComponent::Ready(handle) = *collider_component {
+            collider_set.remove(handle, island_manager, rigid_body_set, true);
+            *collider_component = LazyComponent::Dropped(());
+        }
+    }
+}
+
+/// Remove the rigid bodies of entities marked PendingRemoval from the physics backend
+///
+/// Colliders attached to a removed body are removed alongside it,
+/// and their components marked as dropped to avoid leaving dangling handles.
+pub fn remove_rigid_bodies_system(world: &mut World) {
+    let mut query = world.query::<(
+        &mut RigidBodySet,
+        &mut ColliderSet,
+        &mut JointSet,
+        &mut IslandManager,
+    )>();
+    let (_, (rigid_body_set, collider_set, joint_set, island_manager)) =
+        query.into_iter().next().unwrap();
+
+    for (_, rigid_body) in world
+        .query::<&mut RigidBodyComponent>()
+        .with::<PendingRemoval>()
+        .into_iter()
+    {
+        let handle = if let LazyComponent::Ready(handle) = **rigid_body {
+            handle
+        } else {
+            continue;
+        };
+
+        // Detach child colliders before removing their parent
+        let child_colliders = rigid_body_set[handle].colliders().to_vec();
+        for (_, collider_component) in world.query::<&mut ColliderComponent>().into_iter() {
+            if let LazyComponent::Ready(collider) = *collider_component {
+                if child_colliders.contains(&collider) {
+                    collider_set.remove(collider, island_manager, rigid_body_set, false);
+                    *collider_component = LazyComponent::Dropped(());
+                }
+            }
+        }
+
+        rigid_body_set.remove(handle, island_manager, collider_set, joint_set);
+        **rigid_body = LazyComponent::Dropped(());
+    }
 }
 
 pub enum RigidBodyTag {}
@@ -221,13 +823,17 @@ pub fn insert_rigid_bodies_system(world: &mut World) {
     let mut query = world.query::<&mut RigidBodySet>();
     let (_, rigid_body_set) = query.into_iter().next().unwrap();
 
-    for (_, (rigid_body, position, rotation, linear_velocity, angular_velocity)) in world
+    for (
+        _,
+        (rigid_body, position, rotation, linear_velocity, angular_velocity, gravity_scale),
+    ) in world
         .query::<(
             &mut RigidBodyComponent,
             Option<&PositionComponent>,
             Option<&RotationComponent>,
             Option<&LinearVelocityComponent>,
             Option<&AngularVelocityComponent>,
+            Option<&Changed<GravityScaleComponent>>,
         )>()
         .into_iter()
     {
@@ -267,12 +873,137 @@ pub fn insert_rigid_bodies_system(world: &mut World) {
                 rb.set_angvel(vel, false);
             }
 
+            if let Some(gravity_scale) = gravity_scale {
+                rb.set_gravity_scale(***gravity_scale, false);
+                gravity_scale.set_changed(false);
+            }
+
             let handle = rigid_body_set.insert(rb);
             **rigid_body = LazyComponent::Ready(handle);
         }
     }
 }
 
+/// Apply changed gravity scales to their inserted rigid bodies, waking them up
+pub fn update_gravity_scales_system(world: &mut World) {
+    let mut query = world.query::<&mut RigidBodySet>();
+    let (_, rigid_body_set) = query.into_iter().next().unwrap();
+
+    for (_, (rigid_body, gravity_scale)) in world
+        .query::<(&RigidBodyComponent, &Changed<GravityScaleComponent>)>()
+        .into_iter()
+    {
+        if !gravity_scale.get_changed() {
+            continue;
+        }
+
+        if let LazyComponent::Ready(handle) = **rigid_body {
+            if let Some(rb) = rigid_body_set.get_mut(handle) {
+                rb.set_gravity_scale(***gravity_scale, true);
+                gravity_scale.set_changed(false);
+            }
+        }
+    }
+}
+
+/// One-off linear and angular impulse, applied to a dynamic rigid body by apply_impulses_system
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct ApplyImpulseComponent {
+    pub linear: nalgebra::Vector3<f32>,
+    pub torque: nalgebra::Vector3<f32>,
+}
+
+/// Continuous force and torque, applied to a dynamic rigid body every tick by apply_forces_system
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct ApplyForceComponent {
+    pub linear: nalgebra::Vector3<f32>,
+    pub torque: nalgebra::Vector3<f32>,
+}
+
+/// Apply pending impulses to their dynamic rigid bodies, waking them and clearing the impulse
+///
+/// Impulses on other body types are discarded.
+pub fn apply_impulses_system(world: &mut World) {
+    let mut query = world.query::<&mut RigidBodySet>();
+    let (_, rigid_body_set) = query.into_iter().next().unwrap();
+
+    for (_, (rigid_body, impulse)) in world
+        .query::<(&RigidBodyComponent, &mut ApplyImpulseComponent)>()
+        .into_iter()
+    {
+        if *impulse == ApplyImpulseComponent::default() {
+            continue;
+        }
+
+        let handle = if let LazyComponent::Ready(handle) = **rigid_body {
+            handle
+        } else {
+            continue;
+        };
+
+        let impulse = std::mem::take(impulse);
+
+        let rb = &mut rigid_body_set[handle];
+        if rb.body_type() != RigidBodyType::Dynamic {
+            continue;
+        }
+
+        let ApplyImpulseComponent { linear, torque } = impulse;
+        rb.apply_impulse(
+            rapier3d::prelude::nalgebra::Vector3::new(linear.x, linear.y, linear.z),
+            true,
+        );
+        rb.apply_torque_impulse(
+            rapier3d::prelude::nalgebra::Vector3::new(torque.x, torque.y, torque.z),
+            true,
+        );
+    }
+}
+
+/// Apply continuous forces to their dynamic rigid bodies ahead of the next physics step
+///
+/// Rapier clears applied forces after each step, so this must run every tick.
+/// Nothing is applied while the backend's PhysicsStepConfig is disabled,
+/// since forces would otherwise accumulate until the next step.
+pub fn apply_forces_system(world: &mut World) {
+    let mut query = world.query::<(&mut RigidBodySet, Option<&PhysicsStepConfig>)>();
+    let (_, (rigid_body_set, step_config)) = query.into_iter().next().unwrap();
+
+    if let Some(PhysicsStepConfig { enabled: false, .. }) = step_config {
+        return;
+    }
+
+    for (_, (rigid_body, force)) in world
+        .query::<(&RigidBodyComponent, &ApplyForceComponent)>()
+        .into_iter()
+    {
+        if *force == ApplyForceComponent::default() {
+            continue;
+        }
+
+        let handle = if let LazyComponent::Ready(handle) = **rigid_body {
+            handle
+        } else {
+            continue;
+        };
+
+        let rb = &mut rigid_body_set[handle];
+        if rb.body_type() != RigidBodyType::Dynamic {
+            continue;
+        }
+
+        let ApplyForceComponent { linear, torque } = *force;
+        rb.apply_force(
+            rapier3d::prelude::nalgebra::Vector3::new(linear.x, linear.y, linear.z),
+            true,
+        );
+        rb.apply_torque(
+            rapier3d::prelude::nalgebra::Vector3::new(torque.x, torque.y, torque.z),
+            true,
+        );
+    }
+}
+
 pub fn write_rigid_body_isometries_system(world: &mut World) {
     let mut query = world.query::<&mut RigidBodySet>();
     let (_, rigid_body_set) = query.into_iter().next().unwrap();
@@ -331,33 +1062,68 @@ pub fn write_rigid_body_isometries_system(world: &mut World) {
     }
 }
 
+// Marker for forcing physics read-back on non-dynamic rigid bodies,
+// such as syncing the integrated position of a kinematic velocity-based platform
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct ReadBackPose;
+
+/// Read dynamic rigid body transforms and velocities back into their components
+///
+/// Non-dynamic rigid bodies are only read back if marked with ReadBackPose.
+///
+/// Entities with PreviousPositionComponent or PreviousRotationComponent
+/// have their pre-step transform stored alongside the new one,
+/// allowing renderers to interpolate between physics ticks via interpolate_isometry
 pub fn read_back_rigid_body_isometries_system(world: &mut World) {
     let mut query = world.query::<&mut RigidBodySet>();
     let (_, rigid_body_set) = query.into_iter().next().unwrap();
 
-    for (_, (rigid_body, position, rotation, linear_velocity, angular_velocity)) in world
+    for (
+        _,
+        (
+            rigid_body,
+            position,
+            rotation,
+            previous_position,
+            previous_rotation,
+            linear_velocity,
+            angular_velocity,
+            read_back_pose,
+        ),
+    ) in world
         .query::<(
             &RigidBodyComponent,
             Option<&mut PositionComponent>,
             Option<&mut RotationComponent>,
+            Option<&mut PreviousPositionComponent>,
+            Option<&mut PreviousRotationComponent>,
             Option<&mut LinearVelocityComponent>,
             Option<&mut AngularVelocityComponent>,
+            Option<&ReadBackPose>,
         )>()
         .into_iter()
     {
         if let LazyComponent::Ready(handle) = **rigid_body {
             let rb = &rigid_body_set[handle];
 
-            if rb.body_type() != RigidBodyType::Dynamic {
+            if rb.body_type() != RigidBodyType::Dynamic && read_back_pose.is_none() {
                 continue;
             }
 
             if let Some(position) = position {
+                if let Some(previous_position) = previous_position {
+                    **previous_position = **position;
+                }
+
                 let pos = rb.translation();
                 **position = nalgebra::vector![pos.x, pos.y, pos.z];
             }
 
             if let Some(rotation) = rotation {
+                if let Some(previous_rotation) = previous_rotation {
+                    **previous_rotation = **rotation;
+                }
+
                 let rot = rb.rotation();
                 let (x, y, z) = rot.euler_angles();
                 **rotation = nalgebra::UnitQuaternion::from_euler_angles(x, y, z);
@@ -375,3 +1141,1296 @@ pub fn read_back_rigid_body_isometries_system(world: &mut World) {
         }
     }
 }
+
+// Character controllers
+const CHARACTER_MAX_SLIDES: usize = 4;
+
+/// Kinematic character moved by sweeping its shape through the collider set,
+/// sliding along the surfaces it hits instead of passing through them
+///
+/// Up is +Y. Sensor colliders don't obstruct movement.
+#[derive(Clone)]
+pub struct CharacterControllerComponent {
+    pub shape: SharedShape,
+    /// Gap kept between the shape and the surfaces it touches
+    pub offset: f32,
+    /// Tallest ledge the character can step onto while moving horizontally
+    pub max_step_height: f32,
+    /// Steepest slope in radians that counts as ground
+    pub max_slope_angle: f32,
+    /// Distance a grounded character is pulled down after moving,
+    /// keeping it on the ground when walking down slopes and steps
+    pub snap_to_ground: f32,
+    /// Whether the character was standing on ground after its last move
+    pub grounded: bool,
+}
+
+impl CharacterControllerComponent {
+    pub fn new(shape: SharedShape) -> Self {
+        CharacterControllerComponent {
+            shape,
+            offset: 0.01,
+            max_step_height: 0.0,
+            max_slope_angle: std::f32::consts::FRAC_PI_4,
+            snap_to_ground: 0.0,
+            grounded: false,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_max_step_height(mut self, max_step_height: f32) -> Self {
+        self.max_step_height = max_step_height;
+        self
+    }
+
+    pub fn with_max_slope_angle(mut self, max_slope_angle: f32) -> Self {
+        self.max_slope_angle = max_slope_angle;
+        self
+    }
+
+    pub fn with_snap_to_ground(mut self, snap_to_ground: f32) -> Self {
+        self.snap_to_ground = snap_to_ground;
+        self
+    }
+
+    fn is_walkable(&self, normal: &rapier3d::prelude::Vector<f32>) -> bool {
+        normal.y >= self.max_slope_angle.cos()
+    }
+}
+
+/// Translation a character controller will attempt on its next move, consumed by the move
+pub enum DesiredMovement {}
+pub type DesiredMovementComponent = Usage<DesiredMovement, nalgebra::Vector3<f32>>;
+
+/// Translation a character controller actually applied on its last move
+pub enum EffectiveMovement {}
+pub type EffectiveMovementComponent = Usage<EffectiveMovement, nalgebra::Vector3<f32>>;
+
+// Shape casts for a single character against a collider set
+struct CharacterSweep<'a> {
+    query_pipeline: &'a QueryPipeline,
+    collider_set: &'a ColliderSet,
+    controller: &'a CharacterControllerComponent,
+}
+
+impl<'a> CharacterSweep<'a> {
+    // Move as far along `translation` as possible,
+    // returning the distance travelled and the normal of the surface hit, if any
+    fn cast(
+        &self,
+        position: &rapier3d::prelude::Vector<f32>,
+        translation: &rapier3d::prelude::Vector<f32>,
+    ) -> (
+        rapier3d::prelude::Vector<f32>,
+        Option<rapier3d::prelude::Vector<f32>>,
+    ) {
+        let length = translation.norm();
+        if length <= f32::EPSILON {
+            return (rapier3d::prelude::Vector::zeros(), None);
+        }
+
+        let direction = translation / length;
+        let filter = |handle: ColliderHandle| !self.collider_set[handle].is_sensor();
+
+        let hit = self.query_pipeline.cast_shape(
+            self.collider_set,
+            &rapier3d::prelude::Isometry::translation(position.x, position.y, position.z),
+            &direction,
+            &*self.controller.shape,
+            length + self.controller.offset,
+            InteractionGroups::all(),
+            Some(&filter),
+        );
+
+        match hit {
+            // Overlapping geometry doesn't obstruct, so characters can move back out of it
+            Some((_, toi)) if toi.status != TOIStatus::Penetrating => {
+                let travel = (toi.toi - self.controller.offset).clamp(0.0, length);
+                (direction * travel, Some(*toi.normal1))
+            }
+            _ => (*translation, None),
+        }
+    }
+
+    // Move along `translation`, redirecting the remainder along each surface hit
+    fn slide(
+        &self,
+        mut position: rapier3d::prelude::Vector<f32>,
+        mut translation: rapier3d::prelude::Vector<f32>,
+    ) -> rapier3d::prelude::Vector<f32> {
+        for _ in 0..CHARACTER_MAX_SLIDES {
+            let (travel, normal) = self.cast(&position, &translation);
+            position += travel;
+
+            let mut normal = if let Some(normal) = normal {
+                normal
+            } else {
+                break;
+            };
+
+            // Treat slopes too steep to stand on as walls, rather than climbing them
+            if normal.y > 0.0 && !self.controller.is_walkable(&normal) {
+                normal.y = 0.0;
+                normal = normal.try_normalize(f32::EPSILON).unwrap_or(normal);
+            }
+
+            let remaining = translation - travel;
+            translation = remaining - normal * remaining.dot(&normal).min(0.0);
+        }
+
+        position
+    }
+}
+
+fn horizontal_distance(translation: &rapier3d::prelude::Vector<f32>) -> f32 {
+    translation.x.hypot(translation.z)
+}
+
+/// Resolve `desired` movement from `position` against the colliders in `query_pipeline`,
+/// returning the corrected position and whether the character ends up on the ground
+pub fn move_character(
+    query_pipeline: &QueryPipeline,
+    collider_set: &ColliderSet,
+    controller: &CharacterControllerComponent,
+    position: rapier3d::prelude::Vector<f32>,
+    desired: rapier3d::prelude::Vector<f32>,
+) -> (rapier3d::prelude::Vector<f32>, bool) {
+    let sweep = CharacterSweep {
+        query_pipeline,
+        collider_set,
+        controller,
+    };
+
+    let up = rapier3d::prelude::Vector::y();
+    let mut end = sweep.slide(position, desired);
+
+    // If a grounded character is held back, retry the horizontal movement raised by the step
+    // height, keeping the result if it lands on ground further along
+    let horizontal = rapier3d::prelude::Vector::new(desired.x, 0.0, desired.z);
+    let blocked = horizontal_distance(&(end - position)) + controller.offset < horizontal.norm();
+    if controller.grounded && controller.max_step_height > 0.0 && blocked {
+        let (raise, _) = sweep.cast(&position, &(up * controller.max_step_height));
+        let stepped = sweep.slide(position + raise, horizontal);
+        let (lower, normal) = sweep.cast(&stepped, &(-up * raise.y));
+
+        let landed = normal.is_some_and(|normal| controller.is_walkable(&normal));
+        let further = horizontal_distance(&(stepped - position))
+            > horizontal_distance(&(end - position));
+        if landed && further {
+            end = stepped + lower;
+        }
+    }
+
+    // Probe for ground beneath the character,
+    // snapping down onto it if it was grounded and isn't moving upward
+    let snap = controller.grounded && desired.y <= 0.0;
+    let probe = if snap {
+        controller.snap_to_ground.max(controller.offset * 2.0)
+    } else {
+        controller.offset * 2.0
+    };
+
+    let (lower, normal) = sweep.cast(&end, &(-up * probe));
+    let grounded = normal.is_some_and(|normal| controller.is_walkable(&normal));
+    if grounded && snap {
+        end += lower;
+    }
+
+    (end, grounded)
+}
+
+/// Move each character controller by its desired movement through its backend's colliders,
+/// writing back the corrected position and consuming the movement
+pub fn move_character_controllers_system(world: &mut World) {
+    let mut query = world.query::<(&QueryPipeline, &ColliderSet)>();
+    let (_, (query_pipeline, collider_set)) = query.into_iter().next().unwrap();
+
+    for (_, (controller, desired, position, effective)) in world
+        .query::<(
+            &mut CharacterControllerComponent,
+            &mut DesiredMovementComponent,
+            &mut PositionComponent,
+            Option<&mut EffectiveMovementComponent>,
+        )>()
+        .into_iter()
+    {
+        let desired = std::mem::take(&mut **desired);
+
+        let translation = if desired == nalgebra::Vector3::zeros() {
+            nalgebra::Vector3::zeros()
+        } else {
+            let (end, grounded) = move_character(
+                query_pipeline,
+                collider_set,
+                controller,
+                rapier3d::prelude::Vector::new(position.x, position.y, position.z),
+                rapier3d::prelude::Vector::new(desired.x, desired.y, desired.z),
+            );
+            controller.grounded = grounded;
+
+            let end = nalgebra::vector![end.x, end.y, end.z];
+            let translation = end - **position;
+            **position = end;
+            translation
+        };
+
+        if let Some(effective) = effective {
+            **effective = translation;
+        }
+    }
+}
+
+/// Whether an entity's rigid body was asleep as of the last read_back_sleeping_system
+pub enum Sleeping {}
+pub type SleepingComponent = Usage<Sleeping, bool>;
+
+// Marker requesting that an entity's rigid body be woken,
+// removed by wake_rigid_bodies_system once the body has been inserted and woken
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct WakeRequest;
+
+/// Read the sleeping state of each inserted rigid body into its SleepingComponent
+pub fn read_back_sleeping_system(world: &mut World) {
+    let mut query = world.query::<&RigidBodySet>();
+    let (_, rigid_body_set) = query.into_iter().next().unwrap();
+
+    for (_, (rigid_body, sleeping)) in world
+        .query::<(&RigidBodyComponent, &mut SleepingComponent)>()
+        .into_iter()
+    {
+        if let LazyComponent::Ready(handle) = **rigid_body {
+            if let Some(rb) = rigid_body_set.get(handle) {
+                **sleeping = rb.is_sleeping();
+            }
+        }
+    }
+}
+
+/// Wake the rigid bodies of entities marked with WakeRequest
+///
+/// Bodies touching the woken body are woken alongside it,
+/// so the contacts between them are re-evaluated on the next step.
+pub fn wake_rigid_bodies_system(world: &mut World) {
+    let mut query = world.query::<(
+        &mut RigidBodySet,
+        &ColliderSet,
+        &NarrowPhase,
+        &mut IslandManager,
+    )>();
+    let (_, (rigid_body_set, collider_set, narrow_phase, island_manager)) =
+        query.into_iter().next().unwrap();
+
+    let mut woken = vec![];
+    for (entity, rigid_body) in world
+        .query::<&RigidBodyComponent>()
+        .with::<WakeRequest>()
+        .into_iter()
+    {
+        let handle = if let LazyComponent::Ready(handle) = **rigid_body {
+            handle
+        } else {
+            continue;
+        };
+
+        let colliders = if let Some(rb) = rigid_body_set.get(handle) {
+            rb.colliders().to_vec()
+        } else {
+            continue;
+        };
+
+        let touching = colliders
+            .into_iter()
+            .flat_map(|collider| narrow_phase.contacts_with(collider))
+            .filter(|contact_pair| contact_pair.has_any_active_contact)
+            .flat_map(|contact_pair| [contact_pair.collider1, contact_pair.collider2])
+            .filter_map(|collider| collider_set.get(collider)?.parent())
+            .collect::<Vec<_>>();
+
+        island_manager.wake_up(rigid_body_set, handle, true);
+        for body in touching {
+            island_manager.wake_up(rigid_body_set, body, true);
+        }
+
+        woken.push(entity);
+    }
+
+    drop(query);
+
+    for entity in woken {
+        world.remove_one::<WakeRequest>(entity).unwrap();
+    }
+}
+
+/// Interpolate between a previous and current isometry by the tick fraction `alpha`
+pub fn interpolate_isometry(
+    previous_position: &nalgebra::Vector3<f32>,
+    previous_rotation: &nalgebra::UnitQuaternion<f32>,
+    position: &nalgebra::Vector3<f32>,
+    rotation: &nalgebra::UnitQuaternion<f32>,
+    alpha: f32,
+) -> (nalgebra::Vector3<f32>, nalgebra::UnitQuaternion<f32>) {
+    let alpha = alpha.clamp(0.0, 1.0);
+    (
+        previous_position.lerp(position, alpha),
+        previous_rotation.slerp(rotation, alpha),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rapier3d::prelude::{
+        ActiveEvents, BallJoint, ColliderBuilder, Point, RevoluteJoint, RigidBodyBuilder,
+    };
+
+    #[test]
+    fn contact_impulses_on_impact() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::vector![0.0, -9.81, 0.0]).build());
+
+        let mut builder = EntityBuilder::new();
+        builder.add(RigidBodyComponent::construct(
+            RigidBodyBuilder::new_static().build(),
+        ));
+        builder.add(ColliderComponent::construct(
+            ColliderBuilder::cuboid(10.0, 0.5, 10.0)
+                .active_events(ActiveEvents::CONTACT_EVENTS)
+                .build(),
+        ));
+        world.spawn(builder.build());
+
+        let mut builder = EntityBuilder::new();
+        builder.add(PositionComponent::construct(nalgebra::vector![0.0, 1.5, 0.0]));
+        builder.add(LinearVelocityComponent::construct(nalgebra::vector![
+            0.0, -5.0, 0.0
+        ]));
+        builder.add(RigidBodyComponent::construct(
+            RigidBodyBuilder::new_dynamic().build(),
+        ));
+        builder.add(ColliderComponent::construct(
+            ColliderBuilder::cuboid(0.5, 0.5, 0.5)
+                .density(100.0)
+                .active_events(ActiveEvents::CONTACT_EVENTS)
+                .build(),
+        ));
+        world.spawn(builder.build());
+
+        insert_rigid_bodies_system(&mut world);
+        insert_colliders_system(&mut world);
+
+        // Collected contact pairs are refreshed after each step,
+        // so impulses accumulate once the body comes to rest on the floor
+        for _ in 0..30 {
+            step_physics_system(&mut world);
+        }
+
+        let mut query = world.query::<&EventCollector>();
+        let (_, event_collector) = query.into_iter().next().unwrap();
+        let impulses = event_collector.contact_impulses();
+
+        assert!(!impulses.is_empty());
+        assert!(impulses.iter().any(|(_, _, impulse)| *impulse > 0.0));
+    }
+
+    #[test]
+    fn impacts_measured_on_contact_start_step() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::vector![0.0, -9.81, 0.0]).build());
+
+        let mut builder = EntityBuilder::new();
+        builder.add(RigidBodyComponent::construct(
+            RigidBodyBuilder::new_static().build(),
+        ));
+        builder.add(ColliderComponent::construct(
+            ColliderBuilder::cuboid(10.0, 0.5, 10.0)
+                .active_events(ActiveEvents::CONTACT_EVENTS)
+                .build(),
+        ));
+        world.spawn(builder.build());
+
+        let mut builder = EntityBuilder::new();
+        builder.add(PositionComponent::construct(nalgebra::vector![
+            0.0, 1.5, 0.0
+        ]));
+        builder.add(LinearVelocityComponent::construct(nalgebra::vector![
+            0.0, -5.0, 0.0
+        ]));
+        builder.add(RigidBodyComponent::construct(
+            RigidBodyBuilder::new_dynamic().build(),
+        ));
+        builder.add(ColliderComponent::construct(
+            ColliderBuilder::cuboid(0.5, 0.5, 0.5)
+                .density(100.0)
+                .active_events(ActiveEvents::CONTACT_EVENTS)
+                .build(),
+        ));
+        world.spawn(builder.build());
+
+        insert_rigid_bodies_system(&mut world);
+        insert_colliders_system(&mut world);
+
+        let mut impact_steps = vec![];
+        for step in 0..30 {
+            step_physics_system(&mut world);
+            collect_impact_events_system(&mut world);
+
+            let mut query = world.query::<&EventCollector>();
+            let (_, event_collector) = query.into_iter().next().unwrap();
+            let started = event_collector
+                .contact_events()
+                .iter()
+                .any(|(event, _)| matches!(event, ContactEvent::Started(_, _)));
+            let impacts = event_collector.impact_events().clone();
+            drop(query);
+
+            // Each impact is reported alongside the contact that caused it
+            assert_eq!(started, !impacts.is_empty());
+            if !impacts.is_empty() {
+                assert!(impacts.iter().all(|impact| impact.impulse > 0.0));
+                impact_steps.push(step);
+            }
+
+            clear_physics_event_collector_system(&mut world);
+        }
+
+        assert_eq!(impact_steps.len(), 1);
+    }
+
+    #[test]
+    fn read_back_double_buffers_dynamic_transforms() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+
+        let spawn_position = nalgebra::vector![1.0, 2.0, 3.0];
+        let spawn_rotation = nalgebra::UnitQuaternion::identity();
+
+        let mut builder = EntityBuilder::new();
+        builder.add(PositionComponent::construct(spawn_position));
+        builder.add(RotationComponent::construct(spawn_rotation));
+        builder.add(PreviousPositionComponent::construct(spawn_position));
+        builder.add(PreviousRotationComponent::construct(spawn_rotation));
+        builder.add(LinearVelocityComponent::construct(nalgebra::vector![
+            6.0, 0.0, 0.0
+        ]));
+        builder.add(AngularVelocityComponent::construct(nalgebra::vector![
+            0.0, 3.0, 0.0
+        ]));
+        builder.add(RigidBodyComponent::construct(
+            RigidBodyBuilder::new_dynamic().build(),
+        ));
+        builder.add(ColliderComponent::construct(
+            ColliderBuilder::ball(0.5).build(),
+        ));
+        let body = world.spawn(builder.build());
+
+        insert_rigid_bodies_system(&mut world);
+        insert_colliders_system(&mut world);
+
+        let pose = |world: &World| {
+            (
+                **world.get::<PositionComponent>(body).unwrap(),
+                **world.get::<RotationComponent>(body).unwrap(),
+                **world.get::<PreviousPositionComponent>(body).unwrap(),
+                **world.get::<PreviousRotationComponent>(body).unwrap(),
+            )
+        };
+
+        for _ in 0..2 {
+            let (pre_position, pre_rotation, _, _) = pose(&world);
+
+            step_physics_system(&mut world);
+            read_back_rigid_body_isometries_system(&mut world);
+
+            // Previous holds the pre-step pose, current the post-step pose
+            let (position, rotation, previous_position, previous_rotation) = pose(&world);
+            assert_eq!(previous_position, pre_position);
+            assert_eq!(previous_rotation, pre_rotation);
+            assert!(position.x > pre_position.x);
+            assert!(rotation.angle_to(&pre_rotation) > 0.0);
+
+            // Interpolating spans the tick from the pre-step to the post-step pose
+            let interpolate = |alpha| {
+                interpolate_isometry(
+                    &previous_position,
+                    &previous_rotation,
+                    &position,
+                    &rotation,
+                    alpha,
+                )
+            };
+            let (start_position, start_rotation) = interpolate(0.0);
+            assert_eq!(start_position, previous_position);
+            assert!(start_rotation.angle_to(&previous_rotation) < 1e-3);
+
+            let (end_position, end_rotation) = interpolate(1.0);
+            assert_eq!(end_position, position);
+            assert!(end_rotation.angle_to(&rotation) < 1e-3);
+
+            let (halfway, _) = interpolate(0.5);
+            assert!((halfway - (previous_position + position) * 0.5).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn retained_intersection_events_clear_once_consumed() {
+        let event_collector = EventCollector::default();
+        let event = |intersecting| {
+            IntersectionEvent::new(
+                ColliderSet::invalid_handle(),
+                ColliderSet::invalid_handle(),
+                intersecting,
+            )
+        };
+
+        // Events collected over several steps survive until a consumer reads them
+        event_collector.handle_intersection_event(event(true));
+        event_collector.clear_with_retention(EventRetention::KeepUntilConsumed);
+        event_collector.handle_intersection_event(event(false));
+        event_collector.clear_with_retention(EventRetention::KeepUntilConsumed);
+
+        let consumed = event_collector.consume_intersection_events();
+        assert_eq!(consumed.len(), 2);
+        assert!(event_collector.consume_intersection_events().is_empty());
+
+        // Events arriving after consumption are kept through the next clear
+        event_collector.handle_intersection_event(event(true));
+        event_collector.clear_with_retention(EventRetention::KeepUntilConsumed);
+        assert_eq!(event_collector.intersection_events().len(), 1);
+
+        event_collector.clear_with_retention(EventRetention::ClearEachStep);
+        assert!(event_collector.intersection_events().is_empty());
+    }
+
+    #[test]
+    fn scale_ball() {
+        let shape =
+            ScaledShape::new(SharedShape::ball(1.0)).scaled(&nalgebra::vector![1.0, 3.0, 2.0]);
+        assert_eq!(shape.as_ball().unwrap().radius, 3.0);
+    }
+
+    #[test]
+    fn scale_cuboid() {
+        let shape = ScaledShape::new(SharedShape::cuboid(1.0, 2.0, 3.0))
+            .scaled(&nalgebra::vector![2.0, -1.0, 0.5]);
+        assert_eq!(
+            shape.as_cuboid().unwrap().half_extents,
+            rapier3d::prelude::Vector::new(2.0, 2.0, 1.5)
+        );
+    }
+
+    #[test]
+    fn scale_capsule_and_cylinder() {
+        let scale = nalgebra::vector![2.0, 3.0, -4.0];
+
+        let shape = ScaledShape::new(SharedShape::capsule(
+            rapier3d::prelude::Point::new(0.0, -1.0, 0.0),
+            rapier3d::prelude::Point::new(0.0, 1.0, 0.0),
+            0.5,
+        ))
+        .scaled(&scale);
+        let capsule = shape.as_capsule().unwrap();
+        assert_eq!(capsule.half_height(), 3.0);
+        assert_eq!(capsule.radius, 2.0);
+
+        let shape = ScaledShape::new(SharedShape::cylinder(1.0, 0.5)).scaled(&scale);
+        let cylinder = shape.as_cylinder().unwrap();
+        assert_eq!(cylinder.half_height, 3.0);
+        assert_eq!(cylinder.radius, 2.0);
+    }
+
+    #[test]
+    fn scale_convex_hull() {
+        let points = [
+            rapier3d::prelude::Point::new(-1.0, -1.0, -1.0),
+            rapier3d::prelude::Point::new(1.0, -1.0, -1.0),
+            rapier3d::prelude::Point::new(0.0, 1.0, -1.0),
+            rapier3d::prelude::Point::new(0.0, 0.0, 1.0),
+        ];
+        let shape = ScaledShape::new(SharedShape::convex_hull(&points).unwrap())
+            .scaled(&nalgebra::vector![2.0, 3.0, 4.0]);
+
+        let aabb = shape.compute_local_aabb();
+        assert_eq!(aabb.mins, rapier3d::prelude::Point::new(-2.0, -3.0, -4.0));
+        assert_eq!(aabb.maxs, rapier3d::prelude::Point::new(2.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn scale_compound() {
+        let shape = ScaledShape::new(SharedShape::compound(vec![
+            (
+                rapier3d::prelude::Isometry::translation(1.0, 1.0, 1.0),
+                SharedShape::cuboid(0.5, 0.5, 0.5),
+            ),
+            (
+                rapier3d::prelude::Isometry::translation(-1.0, 0.0, 0.0),
+                SharedShape::ball(0.5),
+            ),
+        ]))
+        .scaled(&nalgebra::vector![2.0, 1.0, 3.0]);
+
+        let shapes = shape.as_compound().unwrap().shapes();
+
+        let (isometry, cuboid) = &shapes[0];
+        assert_eq!(
+            isometry.translation.vector,
+            rapier3d::prelude::Vector::new(2.0, 1.0, 3.0)
+        );
+        assert_eq!(
+            cuboid.as_cuboid().unwrap().half_extents,
+            rapier3d::prelude::Vector::new(1.0, 0.5, 1.5)
+        );
+
+        let (isometry, ball) = &shapes[1];
+        assert_eq!(
+            isometry.translation.vector,
+            rapier3d::prelude::Vector::new(-2.0, 0.0, 0.0)
+        );
+        assert_eq!(ball.as_ball().unwrap().radius, 1.5);
+    }
+
+    #[test]
+    fn collision_groups_filter_contacts() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+
+        // Overlapping cuboids in groups that exclude one another
+        for (memberships, filter) in [(0b01, 0b01), (0b10, 0b10)] {
+            let mut builder = EntityBuilder::new();
+            builder.add(RigidBodyComponent::construct(
+                RigidBodyBuilder::new_dynamic().build(),
+            ));
+            builder.add(ColliderComponent::construct(
+                ColliderBuilder::cuboid(0.5, 0.5, 0.5)
+                    .active_events(ActiveEvents::CONTACT_EVENTS)
+                    .build(),
+            ));
+            builder.add(CollisionGroupsComponent::construct(InteractionGroups::new(
+                memberships,
+                filter,
+            )));
+            world.spawn(builder.build());
+        }
+
+        insert_rigid_bodies_system(&mut world);
+        insert_colliders_system(&mut world);
+
+        for _ in 0..10 {
+            step_physics_system(&mut world);
+        }
+
+        let mut query = world.query::<&EventCollector>();
+        let (_, event_collector) = query.into_iter().next().unwrap();
+        assert!(event_collector.contact_events().is_empty());
+    }
+
+    #[test]
+    fn revolute_joint_between_cuboids() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::vector![0.0, -9.81, 0.0]).build());
+
+        let mut builder = EntityBuilder::new();
+        builder.add(RigidBodyComponent::construct(
+            RigidBodyBuilder::new_static().build(),
+        ));
+        builder.add(ColliderComponent::construct(
+            ColliderBuilder::cuboid(0.1, 1.0, 0.1).build(),
+        ));
+        let frame = world.spawn(builder.build());
+
+        let mut builder = EntityBuilder::new();
+        builder.add(PositionComponent::construct(nalgebra::vector![0.6, 0.0, 0.0]));
+        builder.add(RigidBodyComponent::construct(
+            RigidBodyBuilder::new_dynamic().build(),
+        ));
+        builder.add(ColliderComponent::construct(
+            ColliderBuilder::cuboid(0.5, 1.0, 0.05).build(),
+        ));
+        let door = world.spawn(builder.build());
+
+        // Hinge the door's edge to the frame about the vertical axis
+        let hinge = RevoluteJoint::new(
+            rapier3d::prelude::Point::new(0.1, 0.0, 0.0),
+            rapier3d::prelude::Vector::y_axis(),
+            rapier3d::prelude::Point::new(-0.5, 0.0, 0.0),
+            rapier3d::prelude::Vector::y_axis(),
+        );
+
+        let mut builder = EntityBuilder::new();
+        builder.add(JointComponent::construct(JointParams::from(hinge)));
+        builder.add(JointParentComponent::construct(frame));
+        builder.add(JointChildComponent::construct(door));
+        let joint = world.spawn(builder.build());
+
+        // Joints remain pending until both bodies have been inserted
+        insert_joints_system(&mut world);
+        assert!(matches!(
+            *world.get::<JointComponent>(joint).unwrap(),
+            LazyComponent::Pending(_)
+        ));
+
+        insert_rigid_bodies_system(&mut world);
+        insert_colliders_system(&mut world);
+        insert_joints_system(&mut world);
+
+        let handle = match *world.get::<JointComponent>(joint).unwrap() {
+            LazyComponent::Ready(handle) => handle,
+            _ => panic!("Joint was not inserted"),
+        };
+
+        let mut query = world.query::<&JointSet>();
+        let (_, joint_set) = query.into_iter().next().unwrap();
+        assert!(joint_set.get(handle).is_some());
+    }
+
+    #[test]
+    fn joint_waits_for_endpoint_rigid_body() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::vector![0.0, -9.81, 0.0]).build());
+
+        let frame = world.spawn((RigidBodyComponent::construct(
+            RigidBodyBuilder::new_static().build(),
+        ),));
+        let door = world.spawn(());
+
+        let mut builder = EntityBuilder::new();
+        builder.add(JointComponent::construct(JointParams::from(
+            BallJoint::new(Point::origin(), Point::origin()),
+        )));
+        builder.add(JointParentComponent::construct(frame));
+        builder.add(JointChildComponent::construct(door));
+        let joint = world.spawn(builder.build());
+
+        // The child has no rigid body yet, so the joint is retried on a later frame
+        insert_rigid_bodies_system(&mut world);
+        insert_joints_system(&mut world);
+        assert!(matches!(
+            *world.get::<JointComponent>(joint).unwrap(),
+            LazyComponent::Pending(_)
+        ));
+
+        world
+            .insert_one(
+                door,
+                RigidBodyComponent::construct(RigidBodyBuilder::new_dynamic().build()),
+            )
+            .unwrap();
+
+        insert_rigid_bodies_system(&mut world);
+        insert_joints_system(&mut world);
+        assert!(matches!(
+            *world.get::<JointComponent>(joint).unwrap(),
+            LazyComponent::Ready(_)
+        ));
+    }
+
+    #[test]
+    fn read_back_kinematic_pose() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+
+        let spawn_platform = |world: &mut World| {
+            let mut builder = EntityBuilder::new();
+            builder.add(PositionComponent::construct(nalgebra::Vector3::zeros()));
+            builder.add(LinearVelocityComponent::construct(nalgebra::vector![
+                1.0, 0.0, 0.0
+            ]));
+            builder.add(RigidBodyComponent::construct(
+                RigidBodyBuilder::new_kinematic_velocity_based().build(),
+            ));
+            world.spawn(builder.build())
+        };
+
+        let platform = spawn_platform(&mut world);
+        let marked_platform = spawn_platform(&mut world);
+        world.insert_one(marked_platform, ReadBackPose).unwrap();
+
+        insert_rigid_bodies_system(&mut world);
+        for _ in 0..10 {
+            write_rigid_body_isometries_system(&mut world);
+            step_physics_system(&mut world);
+        }
+        read_back_rigid_body_isometries_system(&mut world);
+
+        assert_eq!(
+            **world.get::<PositionComponent>(platform).unwrap(),
+            nalgebra::Vector3::zeros()
+        );
+        assert!(world.get::<PositionComponent>(marked_platform).unwrap().x > 0.0);
+    }
+
+    #[test]
+    fn remove_rigid_body_and_child_collider() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+
+        let mut builder = EntityBuilder::new();
+        builder.add(RigidBodyComponent::construct(
+            RigidBodyBuilder::new_dynamic().build(),
+        ));
+        builder.add(ColliderComponent::construct(
+            ColliderBuilder::cuboid(1.0, 1.0, 1.0).build(),
+        ));
+        let cube = world.spawn(builder.build());
+
+        insert_rigid_bodies_system(&mut world);
+        insert_colliders_system(&mut world);
+
+        let rigid_body_handle = match **world.get::<RigidBodyComponent>(cube).unwrap() {
+            LazyComponent::Ready(handle) => handle,
+            _ => panic!("Rigid body was not inserted"),
+        };
+
+        let collider_handle = match *world.get::<ColliderComponent>(cube).unwrap() {
+            LazyComponent::Ready(handle) => handle,
+            _ => panic!("Collider was not inserted"),
+        };
+
+        world.insert_one(cube, PendingRemoval).unwrap();
+
+        remove_rigid_bodies_system(&mut world);
+        remove_colliders_system(&mut world);
+
+        let mut query = world.query::<(&RigidBodySet, &ColliderSet)>();
+        let (_, (rigid_body_set, collider_set)) = query.into_iter().next().unwrap();
+        assert!(rigid_body_set.get(rigid_body_handle).is_none());
+        assert!(collider_set.get(collider_handle).is_none());
+        drop(query);
+
+        assert!(matches!(
+            **world.get::<RigidBodyComponent>(cube).unwrap(),
+            LazyComponent::Dropped(())
+        ));
+        assert!(matches!(
+            *world.get::<ColliderComponent>(cube).unwrap(),
+            LazyComponent::Dropped(())
+        ));
+    }
+
+    #[test]
+    fn gravity_scale_applies_on_insert_and_change() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::vector![0.0, -9.81, 0.0]).build());
+
+        let mut spawn_body = |gravity_scale: Option<f32>| {
+            let mut builder = EntityBuilder::new();
+            builder.add(PositionComponent::construct(nalgebra::Vector3::zeros()));
+            builder.add(RigidBodyComponent::construct(
+                RigidBodyBuilder::new_dynamic().additional_mass(1.0).build(),
+            ));
+            if let Some(gravity_scale) = gravity_scale {
+                builder.add(Changed::new(GravityScaleComponent::construct(gravity_scale), false));
+            }
+            world.spawn(builder.build())
+        };
+
+        let falling = spawn_body(None);
+        let floating = spawn_body(Some(0.0));
+
+        let step = |world: &mut World| {
+            insert_rigid_bodies_system(world);
+            update_gravity_scales_system(world);
+            for _ in 0..10 {
+                step_physics_system(world);
+            }
+            read_back_rigid_body_isometries_system(world);
+        };
+
+        let height = |world: &World, entity| world.get::<PositionComponent>(entity).unwrap().y;
+
+        step(&mut world);
+        assert!(height(&world, falling) < 0.0);
+        assert_eq!(height(&world, floating), 0.0);
+
+        // Changing the scale at runtime reaches the rigid body once flagged
+        {
+            let mut gravity_scale = world
+                .get_mut::<Changed<GravityScaleComponent>>(floating)
+                .unwrap();
+            ***gravity_scale = 1.0;
+            gravity_scale.set_changed(true);
+        }
+
+        step(&mut world);
+        assert!(height(&world, floating) < 0.0);
+    }
+
+    #[test]
+    fn configure_physics_applies_step_config() {
+        let mut world = World::new();
+        let backend = world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+
+        *world.get_mut::<PhysicsStepConfig>(backend).unwrap() = PhysicsStepConfig {
+            dt: 1.0 / 120.0,
+            max_ccd_substeps: 4,
+            enabled: true,
+        };
+        configure_physics_system(&mut world);
+
+        let integration_parameters = world.get::<IntegrationParameters>(backend).unwrap();
+        assert_eq!(integration_parameters.dt, 1.0 / 120.0);
+        assert_eq!(integration_parameters.max_ccd_substeps, 4);
+    }
+
+    #[test]
+    fn paused_physics_does_not_accumulate_forces() {
+        let mut world = World::new();
+        let backend = world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+        world.spawn((GravityRegionComponent {
+            min: nalgebra::vector![-10.0, -10.0, -10.0],
+            max: nalgebra::vector![10.0, 10.0, 10.0],
+            gravity: nalgebra::vector![0.0, -1.0, 0.0],
+        },));
+
+        let mut builder = EntityBuilder::new();
+        builder.add(RigidBodyComponent::construct(
+            RigidBodyBuilder::new_dynamic().build(),
+        ));
+        builder.add(ColliderComponent::construct(
+            ColliderBuilder::ball(0.5).build(),
+        ));
+        builder.add(LinearVelocityComponent::construct(
+            nalgebra::Vector3::zeros(),
+        ));
+        builder.add(ApplyForceComponent {
+            linear: nalgebra::vector![1.0, 0.0, 0.0],
+            ..Default::default()
+        });
+        builder.add(ReadBackPose);
+        let body = world.spawn(builder.build());
+
+        insert_rigid_bodies_system(&mut world);
+        insert_colliders_system(&mut world);
+
+        let step = |world: &mut World| {
+            apply_gravity_regions_system(world);
+            apply_forces_system(world);
+            configure_physics_system(world);
+            step_physics_system(world);
+            read_back_rigid_body_isometries_system(world);
+        };
+
+        let set_enabled = |world: &mut World, enabled| {
+            world.get_mut::<PhysicsStepConfig>(backend).unwrap().enabled = enabled;
+        };
+
+        let velocity = |world: &World| **world.get::<LinearVelocityComponent>(body).unwrap();
+
+        step(&mut world);
+        let single_step = velocity(&world);
+        assert!(single_step.x > 0.0);
+        assert!(single_step.y < 0.0);
+
+        set_enabled(&mut world, false);
+        for _ in 0..10 {
+            step(&mut world);
+        }
+        assert_eq!(velocity(&world), single_step);
+
+        // Resuming applies a single tick's worth of force rather than the paused backlog
+        set_enabled(&mut world, true);
+        step(&mut world);
+        let resumed = velocity(&world) - single_step;
+        assert!((resumed - single_step).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn zero_gravity_regions_hold_bodies_and_let_them_sleep() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::vector![0.0, -9.81, 0.0]).build());
+        world.spawn((GravityRegionComponent {
+            min: nalgebra::vector![-5.0, -5.0, -5.0],
+            max: nalgebra::vector![5.0, 5.0, 5.0],
+            gravity: nalgebra::Vector3::zeros(),
+        },));
+
+        let mut spawn_body = |position: nalgebra::Vector3<f32>| {
+            let mut builder = EntityBuilder::new();
+            builder.add(PositionComponent::construct(position));
+            builder.add(RigidBodyComponent::construct(
+                RigidBodyBuilder::new_dynamic().build(),
+            ));
+            builder.add(ColliderComponent::construct(
+                ColliderBuilder::ball(0.5).build(),
+            ));
+            world.spawn(builder.build())
+        };
+
+        let floating = spawn_body(nalgebra::Vector3::zeros());
+        let falling = spawn_body(nalgebra::vector![20.0, 0.0, 0.0]);
+
+        insert_rigid_bodies_system(&mut world);
+        insert_colliders_system(&mut world);
+
+        for _ in 0..300 {
+            apply_gravity_regions_system(&mut world);
+            step_physics_system(&mut world);
+            read_back_rigid_body_isometries_system(&mut world);
+        }
+
+        let position = |entity| **world.get::<PositionComponent>(entity).unwrap();
+        assert!(position(floating).magnitude() < 1e-3);
+        assert!(position(falling).y < -10.0);
+
+        let handle = match **world.get::<RigidBodyComponent>(floating).unwrap() {
+            LazyComponent::Ready(handle) => handle,
+            _ => panic!("Rigid body not inserted"),
+        };
+        let mut query = world.query::<&RigidBodySet>();
+        let (_, rigid_body_set) = query.into_iter().next().unwrap();
+        assert!(rigid_body_set[handle].is_sleeping());
+    }
+
+    #[test]
+    fn impulses_apply_once_and_forces_every_step() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+
+        let mut spawn_body = |rigid_body: RigidBody| {
+            let mut builder = EntityBuilder::new();
+            builder.add(RigidBodyComponent::construct(rigid_body));
+            builder.add(ColliderComponent::construct(ColliderBuilder::ball(0.5).build()));
+            builder.add(LinearVelocityComponent::construct(nalgebra::Vector3::zeros()));
+            builder.add(ReadBackPose);
+            world.spawn(builder.build())
+        };
+
+        let launched = spawn_body(RigidBodyBuilder::new_dynamic().sleeping(true).build());
+        let pushed = spawn_body(RigidBodyBuilder::new_dynamic().build());
+        let kinematic = spawn_body(RigidBodyBuilder::new_kinematic_velocity_based().build());
+
+        insert_rigid_bodies_system(&mut world);
+        insert_colliders_system(&mut world);
+
+        let impulse = ApplyImpulseComponent {
+            linear: nalgebra::vector![0.0, 0.0, 2.0],
+            ..Default::default()
+        };
+        world.insert_one(launched, impulse).unwrap();
+        world.insert_one(kinematic, impulse).unwrap();
+        world
+            .insert_one(
+                pushed,
+                ApplyForceComponent {
+                    linear: nalgebra::vector![1.0, 0.0, 0.0],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let step = |world: &mut World| {
+            apply_impulses_system(world);
+            apply_forces_system(world);
+            step_physics_system(world);
+            read_back_rigid_body_isometries_system(world);
+        };
+
+        let velocity =
+            |world: &World, entity| **world.get::<LinearVelocityComponent>(entity).unwrap();
+
+        step(&mut world);
+        let launch_velocity = velocity(&world, launched);
+        let push_velocity = velocity(&world, pushed);
+        assert!(launch_velocity.z > 0.0);
+        assert_eq!(
+            *world.get::<ApplyImpulseComponent>(launched).unwrap(),
+            ApplyImpulseComponent::default()
+        );
+        assert_eq!(velocity(&world, kinematic), nalgebra::Vector3::zeros());
+        assert!(push_velocity.x > 0.0);
+
+        // The impulse is spent, while the force keeps accelerating
+        step(&mut world);
+        assert_eq!(velocity(&world, launched), launch_velocity);
+        assert!(velocity(&world, pushed).x > push_velocity.x);
+    }
+
+    #[test]
+    fn unparented_colliders_bake_scale_on_insertion() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+
+        let scale = nalgebra::vector![2.0, 2.0, 2.0];
+        let mut spawn = |collider: Collider| {
+            world.spawn((
+                ColliderComponent::construct(collider),
+                ScaleComponent::construct(scale),
+            ))
+        };
+
+        let cuboid = spawn(ColliderBuilder::cuboid(1.0, 2.0, 3.0).build());
+        let ball = spawn(ColliderBuilder::ball(0.5).build());
+        let trimesh = spawn(
+            ColliderBuilder::trimesh(
+                vec![
+                    rapier3d::prelude::Point::new(0.0, 0.0, 0.0),
+                    rapier3d::prelude::Point::new(1.0, 0.0, 0.0),
+                    rapier3d::prelude::Point::new(0.0, 0.0, 1.0),
+                ],
+                vec![[0, 1, 2]],
+            )
+            .build(),
+        );
+
+        insert_colliders_system(&mut world);
+
+        let shape = |entity| {
+            let handle = match *world.get::<ColliderComponent>(entity).unwrap() {
+                LazyComponent::Ready(handle) => handle,
+                _ => panic!("Collider not inserted"),
+            };
+            let mut query = world.query::<&ColliderSet>();
+            let (_, collider_set) = query.into_iter().next().unwrap();
+            collider_set[handle].shared_shape().clone()
+        };
+
+        assert_eq!(
+            shape(cuboid).as_cuboid().unwrap().half_extents,
+            rapier3d::prelude::Vector::new(2.0, 4.0, 6.0)
+        );
+        assert_eq!(shape(ball).as_ball().unwrap().radius, 1.0);
+
+        // Unsupported shapes are inserted unscaled
+        assert_eq!(
+            shape(trimesh).as_trimesh().unwrap().vertices()[1],
+            rapier3d::prelude::Point::new(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn scaled_colliders_follow_scale_changes() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+
+        let cuboid = world.spawn((
+            ColliderComponent::construct(ColliderBuilder::cuboid(1.0, 2.0, 3.0).build()),
+            ScaleComponent::construct(nalgebra::vector![2.0, 2.0, 2.0]),
+        ));
+
+        insert_colliders_system(&mut world);
+        assert!(world.get::<ScaledShape>(cuboid).is_ok());
+
+        let half_extents = |world: &World| {
+            let handle = match *world.get::<ColliderComponent>(cuboid).unwrap() {
+                LazyComponent::Ready(handle) => handle,
+                _ => panic!("Collider not inserted"),
+            };
+            let mut query = world.query::<&ColliderSet>();
+            let (_, collider_set) = query.into_iter().next().unwrap();
+            let half_extents = collider_set[handle].shape().as_cuboid().unwrap().half_extents;
+            half_extents
+        };
+
+        // Unchanged scales leave the inserted shape alone
+        rebuild_scaled_colliders_system(&mut world);
+        assert_eq!(
+            half_extents(&world),
+            rapier3d::prelude::Vector::new(2.0, 4.0, 6.0)
+        );
+
+        // Changes rescale the original shape rather than the scaled one
+        **world.get_mut::<ScaleComponent>(cuboid).unwrap() = nalgebra::vector![0.5, 1.0, 2.0];
+        rebuild_scaled_colliders_system(&mut world);
+        assert_eq!(
+            half_extents(&world),
+            rapier3d::prelude::Vector::new(0.5, 2.0, 6.0)
+        );
+    }
+
+    #[test]
+    fn wake_request_wakes_sleeping_bodies() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+
+        let mut builder = EntityBuilder::new();
+        builder.add(RigidBodyComponent::construct(
+            RigidBodyBuilder::new_dynamic().sleeping(true).build(),
+        ));
+        builder.add(ColliderComponent::construct(ColliderBuilder::ball(0.5).build()));
+        builder.add(SleepingComponent::construct(false));
+        let body = world.spawn(builder.build());
+
+        insert_rigid_bodies_system(&mut world);
+        insert_colliders_system(&mut world);
+
+        // Attaching a collider wakes its body, so put it back to sleep
+        let handle = match **world.get::<RigidBodyComponent>(body).unwrap() {
+            LazyComponent::Ready(handle) => handle,
+            _ => panic!("Rigid body not inserted"),
+        };
+        for (_, rigid_body_set) in world.query_mut::<&mut RigidBodySet>() {
+            rigid_body_set[handle].sleep();
+        }
+
+        read_back_sleeping_system(&mut world);
+        assert!(**world.get::<SleepingComponent>(body).unwrap());
+
+        world.insert_one(body, WakeRequest).unwrap();
+        wake_rigid_bodies_system(&mut world);
+        assert!(world.get::<WakeRequest>(body).is_err());
+
+        read_back_sleeping_system(&mut world);
+        assert!(!**world.get::<SleepingComponent>(body).unwrap());
+    }
+
+    #[test]
+    fn character_controller_slides_along_walls_and_steps_up() {
+        let mut world = World::new();
+        world.spawn(physics_backend_builder(nalgebra::Vector3::zeros()).build());
+
+        // Floor with its top at y = 0.5, a wall at x = 1.5 and a short ledge at z = -1.5
+        for (position, half_extents) in [
+            (nalgebra::vector![0.0, 0.0, 0.0], (10.0, 0.5, 10.0)),
+            (nalgebra::vector![2.0, 5.0, 0.0], (0.5, 5.0, 10.0)),
+            (nalgebra::vector![0.0, 0.7, -2.0], (1.0, 0.2, 0.5)),
+        ] {
+            let mut builder = EntityBuilder::new();
+            builder.add(PositionComponent::construct(position));
+            builder.add(ColliderComponent::construct(
+                ColliderBuilder::cuboid(half_extents.0, half_extents.1, half_extents.2).build(),
+            ));
+            world.spawn(builder.build());
+        }
+
+        insert_colliders_system(&mut world);
+        step_physics_system(&mut world);
+
+        let mut controller = CharacterControllerComponent::new(SharedShape::ball(0.5))
+            .with_max_step_height(0.6)
+            .with_snap_to_ground(0.5);
+        controller.grounded = true;
+
+        let mut builder = EntityBuilder::new();
+        builder.add(controller);
+        builder.add(PositionComponent::construct(nalgebra::vector![0.0, 1.01, 0.0]));
+        builder.add(DesiredMovementComponent::construct(nalgebra::vector![5.0, 0.0, 0.5]));
+        builder.add(EffectiveMovementComponent::construct(nalgebra::Vector3::zeros()));
+        let character = world.spawn(builder.build());
+
+        // Stops at the wall, keeping the movement along it
+        move_character_controllers_system(&mut world);
+        let position = **world.get::<PositionComponent>(character).unwrap();
+        assert!(position.x > 0.9 && position.x < 1.0);
+        assert!((position.z - 0.5).abs() < 1e-3);
+        assert!((position.y - 1.01).abs() < 1e-2);
+        assert!(world.get::<CharacterControllerComponent>(character).unwrap().grounded);
+        assert_eq!(
+            **world.get::<DesiredMovementComponent>(character).unwrap(),
+            nalgebra::Vector3::zeros()
+        );
+
+        // Steps up onto the ledge instead of stopping at it
+        **world.get_mut::<DesiredMovementComponent>(character).unwrap() =
+            nalgebra::vector![0.0, 0.0, -2.5];
+        move_character_controllers_system(&mut world);
+        let position = **world.get::<PositionComponent>(character).unwrap();
+        assert!((position.z + 2.0).abs() < 1e-3);
+        assert!((position.y - 1.41).abs() < 1e-2);
+
+        let effective = **world.get::<EffectiveMovementComponent>(character).unwrap();
+        assert!(effective.y > 0.3);
+    }
+}
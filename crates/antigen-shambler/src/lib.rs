@@ -1,10 +1,14 @@
 pub use shambler;
 
-use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 use antigen_core::{Construct, MessageContext, MessageResult, Usage};
-use antigen_fs::{FilePathComponent, FileStringQuery};
-use shambler::GeoMap;
+use antigen_fs::{find_all_file_bytes, find_all_file_strings, FilePathComponent};
+use shambler::{face::FaceId, shalrath::repr::Extension, GeoMap};
 
 pub enum MapFile {}
 pub type MapFileComponent = Usage<MapFile, GeoMap>;
@@ -15,7 +19,85 @@ pub struct MapFileQuery<'a> {
     pub map: &'a MapFileComponent,
 }
 
-/// Find a file entity with a matching path and parse it into a GeoMap
+pub enum Worldspawn {}
+/// Key-value properties of a map's worldspawn entity, such as `wad` or `_sunlight`
+pub type WorldspawnComponent = Usage<Worldspawn, BTreeMap<String, String>>;
+
+#[derive(hecs::Query)]
+pub struct WorldspawnQuery<'a> {
+    pub path: &'a FilePathComponent,
+    pub worldspawn: &'a WorldspawnComponent,
+}
+
+/// Extract the properties of the worldspawn entity from `map`
+///
+/// If multiple entities are classed as worldspawn, the first is used.
+pub fn worldspawn_properties(map: &GeoMap) -> Option<BTreeMap<String, String>> {
+    let mut worldspawns = map.entity_properties.values().filter(|properties| {
+        properties
+            .0
+            .iter()
+            .any(|property| property.key == "classname" && property.value == "worldspawn")
+    });
+
+    let worldspawn = worldspawns.next()?;
+
+    if worldspawns.next().is_some() {
+        println!("Warning: Map contains multiple worldspawn entities, using the first");
+    }
+
+    Some(
+        worldspawn
+            .0
+            .iter()
+            .map(|property| (property.key.clone(), property.value.clone()))
+            .collect(),
+    )
+}
+
+/// Quake 2-style content and surface bitmasks for a single face
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SurfaceFlags {
+    pub content_flags: u32,
+    pub surface_flags: u32,
+}
+
+/// Per-face content and surface flags for a map, for faces whose format carries them
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FaceFlagsComponent(BTreeMap<FaceId, SurfaceFlags>);
+
+impl FaceFlagsComponent {
+    pub fn new(map: &GeoMap) -> Self {
+        FaceFlagsComponent(
+            map.face_extensions
+                .iter()
+                .filter_map(|(face_id, extension)| match extension {
+                    Extension::Quake2 {
+                        content_flags,
+                        surface_flags,
+                        ..
+                    } => Some((
+                        *face_id,
+                        SurfaceFlags {
+                            content_flags: *content_flags,
+                            surface_flags: *surface_flags,
+                        },
+                    )),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the flags of `face_id`, or zeroed flags if it has none
+    pub fn face_flags(&self, face_id: &FaceId) -> SurfaceFlags {
+        self.0.get(face_id).copied().unwrap_or_default()
+    }
+}
+
+/// Find the file entities with a matching path and parse each into a GeoMap
+///
+/// If any of them fail to parse, none of the maps are inserted.
 pub fn parse_map_file_string<'a, 'b, P: Into<PathBuf>>(
     path: P,
 ) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
@@ -29,24 +111,279 @@ pub fn parse_map_file_string<'a, 'b, P: Into<PathBuf>>(
             map_path
         );
 
-        let components = world
-            .query_mut::<FileStringQuery>()
-            .into_iter()
-            .filter(|(_, FileStringQuery { path, .. })| ***path == *map_path)
-            .map(|(entity, FileStringQuery { string, .. })| {
+        let maps = find_all_file_strings(world, &map_path)
+            .map(|(entity, string)| {
                 println!("Parsing map file for entity {:?}", entity);
-                let map = string.parse::<shambler::shalrath::repr::Map>().unwrap();
-                let map = GeoMap::from(map);
-                (entity, MapFileComponent::construct(map))
+                Ok((entity, GeoMap::from(parse_map(&map_path, string)?)))
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, String>>()?;
+
+        for (entity, map) in maps {
+            insert_map(world, entity, map);
+        }
+
+        Ok(ctx)
+    }
+}
+
+/// Find the file bytes entities with a matching path and parse each into a GeoMap
+///
+/// Gzip-compressed files are detected by their magic bytes and decompressed transparently,
+/// so both `.map` and `.map.gz` files can be parsed.
+pub fn parse_map_file_bytes<'a, 'b, P: Into<PathBuf>>(
+    path: P,
+) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+
+        let map_path = path.into();
+        println!(
+            "Thread {} Looking for file bytes entities with path {:?}..",
+            std::thread::current().name().unwrap(),
+            map_path
+        );
 
-        for (entity, map) in components {
-            world
-                .insert(entity, (map,))
-                .expect("Failed to add map to entity");
+        let maps = find_all_file_bytes(world, &map_path)
+            .map(|(entity, bytes)| -> Result<_, Box<dyn std::error::Error>> {
+                println!("Parsing map file for entity {:?}", entity);
+                let string = if bytes.starts_with(&GZIP_MAGIC) {
+                    let mut string = String::new();
+                    flate2::read::GzDecoder::new(&bytes[..]).read_to_string(&mut string)?;
+                    string
+                } else {
+                    String::from_utf8(bytes.to_vec())?
+                };
+
+                Ok((entity, GeoMap::from(parse_map(&map_path, &string)?)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (entity, map) in maps {
+            insert_map(world, entity, map);
         }
 
         Ok(ctx)
     }
 }
+
+/// Parse `string` into a shalrath map
+///
+/// On failure, the error message contains `path` along with the line number
+/// and contents of the line where parsing stopped.
+pub fn parse_map(path: &Path, string: &str) -> Result<shambler::shalrath::repr::Map, String> {
+    string
+        .parse::<shambler::shalrath::repr::Map>()
+        .map_err(|e| {
+            let offset = error_offset(string).unwrap_or(string.len() - e.input.len());
+            let line = string[..offset].matches('\n').count() + 1;
+            let snippet = string
+                .lines()
+                .nth(line - 1)
+                .unwrap_or_default()
+                .trim();
+            format!(
+                "Failed to parse map {:?} at line {}: {:?} ({:?})",
+                path, line, snippet, e.code
+            )
+        })
+}
+
+// Offset of the deepest parse failure in `string`
+//
+// The map parser backtracks to the start of the first entity that fails,
+// so entities are re-parsed one at a time to find where the failing one stopped
+fn error_offset(string: &str) -> Option<usize> {
+    use shambler::shalrath::parser::{parse_eol_comment, repr::parse_entity};
+
+    let mut input = string.trim_start();
+    while !input.is_empty() {
+        input = match parse_eol_comment(input) {
+            Ok((rest, _)) => rest,
+            Err(_) => match parse_entity(input) {
+                Ok((rest, _)) => rest,
+                Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                    return Some(string.len() - e.input.len())
+                }
+                Err(nom::Err::Incomplete(_)) => return None,
+            },
+        }
+        .trim_start();
+    }
+
+    None
+}
+
+// Leading bytes identifying a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Insert a parsed map and its derived components into `entity`
+fn insert_map(world: &mut hecs::World, entity: hecs::Entity, map: GeoMap) {
+    match worldspawn_properties(&map) {
+        Some(worldspawn) => world
+            .insert_one(entity, WorldspawnComponent::construct(worldspawn))
+            .expect("Failed to add worldspawn to entity"),
+        None => println!("Warning: Map has no worldspawn entity"),
+    }
+
+    let face_flags = FaceFlagsComponent::new(&map);
+
+    world
+        .insert(entity, (MapFileComponent::construct(map), face_flags))
+        .expect("Failed to add map to entity");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use antigen_core::WorldExchange;
+    use antigen_fs::{FileBytesBundle, FileStringBundle};
+
+    const WORLDSPAWN_MAP: &str = r#"{
+"classname" "worldspawn"
+"wad" "textures/base.wad"
+"_sunlight" "200"
+}
+{
+"classname" "worldspawn"
+"wad" "textures/other.wad"
+}
+"#;
+
+    #[test]
+    fn parse_worldspawn() {
+        let mut world = hecs::World::new();
+        let channel = WorldExchange::default().create_channel::<()>();
+
+        world.spawn(FileStringBundle::new("worldspawn.map", WORLDSPAWN_MAP));
+        parse_map_file_string("worldspawn.map")((&mut world, &channel)).unwrap();
+
+        let mut query = world.query::<WorldspawnQuery>();
+        let (_, WorldspawnQuery { worldspawn, .. }) = query.into_iter().next().unwrap();
+        assert_eq!(worldspawn.get("classname").unwrap(), "worldspawn");
+        assert_eq!(worldspawn.get("wad").unwrap(), "textures/base.wad");
+        assert_eq!(worldspawn.get("_sunlight").unwrap(), "200");
+    }
+
+    const FACE_FLAGS_MAP: &str = r#"{
+"classname" "worldspawn"
+{
+( -64 -64 -16 ) ( -64 -63 -16 ) ( -64 -64 -15 ) clip 0 0 0 1 1 1 128 0
+( -64 -64 -16 ) ( -64 -64 -15 ) ( -63 -64 -16 ) base 0 0 0 1 1
+( -64 -64 -16 ) ( -63 -64 -16 ) ( -64 -63 -16 ) base 0 0 0 1 1
+( 64 64 16 ) ( 64 65 16 ) ( 65 64 16 ) base 0 0 0 1 1
+( 64 64 16 ) ( 65 64 16 ) ( 64 64 17 ) base 0 0 0 1 1
+( 64 64 16 ) ( 64 64 17 ) ( 64 65 16 ) base 0 0 0 1 1
+}
+}
+"#;
+
+    #[test]
+    fn parse_face_flags() {
+        let mut world = hecs::World::new();
+        let channel = WorldExchange::default().create_channel::<()>();
+
+        world.spawn(FileStringBundle::new("face_flags.map", FACE_FLAGS_MAP));
+        parse_map_file_string("face_flags.map")((&mut world, &channel)).unwrap();
+
+        let mut query = world.query::<&FaceFlagsComponent>();
+        let (_, face_flags) = query.into_iter().next().unwrap();
+        assert_eq!(
+            face_flags.face_flags(&FaceId(0)),
+            SurfaceFlags {
+                content_flags: 1,
+                surface_flags: 128,
+            }
+        );
+        assert_eq!(face_flags.face_flags(&FaceId(1)), SurfaceFlags::default());
+    }
+
+    #[test]
+    fn parse_map_bytes() {
+        let mut gzipped = vec![];
+        let mut encoder =
+            flate2::write::GzEncoder::new(&mut gzipped, flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, WORLDSPAWN_MAP.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let mut world = hecs::World::new();
+        let channel = WorldExchange::default().create_channel::<()>();
+
+        world.spawn(FileBytesBundle::new("plain.map", WORLDSPAWN_MAP.as_bytes()));
+        world.spawn(FileBytesBundle::new("gzipped.map.gz", gzipped));
+
+        parse_map_file_bytes("plain.map")((&mut world, &channel)).unwrap();
+        parse_map_file_bytes("gzipped.map.gz")((&mut world, &channel)).unwrap();
+
+        let mut query = world.query::<WorldspawnQuery>();
+        let worldspawns = query
+            .into_iter()
+            .map(|(_, WorldspawnQuery { worldspawn, .. })| worldspawn.get("wad").unwrap().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(worldspawns, ["textures/base.wad", "textures/base.wad"]);
+    }
+
+    #[test]
+    fn parse_every_matching_map_string() {
+        let mut world = hecs::World::new();
+        let channel = WorldExchange::default().create_channel::<()>();
+
+        world.spawn(FileStringBundle::new("shared.map", WORLDSPAWN_MAP));
+        world.spawn(FileStringBundle::new("shared.map", WORLDSPAWN_MAP));
+        world.spawn(FileStringBundle::new("other.map", WORLDSPAWN_MAP));
+        parse_map_file_string("shared.map")((&mut world, &channel)).unwrap();
+
+        let mut query = world.query::<MapFileQuery>();
+        let paths = query
+            .into_iter()
+            .map(|(_, MapFileQuery { path, .. })| (**path).clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [PathBuf::from("shared.map"), PathBuf::from("shared.map")]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_map_bytes() {
+        let mut world = hecs::World::new();
+        let channel = WorldExchange::default().create_channel::<()>();
+
+        world.spawn(FileBytesBundle::new("invalid.map", "{ \"classname\""));
+        assert!(parse_map_file_bytes("invalid.map")((&mut world, &channel)).is_err());
+    }
+
+    #[test]
+    fn parse_invalid_map_string() {
+        let mut world = hecs::World::new();
+        let channel = WorldExchange::default().create_channel::<()>();
+
+        world.spawn(FileStringBundle::new(
+            "invalid.map",
+            "{\n\"classname\" \"worldspawn\"\n( 0 0 0 ) garbage\n}\n",
+        ));
+
+        let err = parse_map_file_string("invalid.map")((&mut world, &channel))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("invalid.map"), "{}", err);
+        assert!(err.contains("line 3"), "{}", err);
+        assert!(err.contains("( 0 0 0 ) garbage"), "{}", err);
+        assert!(world.query_mut::<&MapFileComponent>().into_iter().next().is_none());
+    }
+
+    #[test]
+    fn parse_missing_worldspawn() {
+        let mut world = hecs::World::new();
+        let channel = WorldExchange::default().create_channel::<()>();
+
+        world.spawn(FileStringBundle::new(
+            "no_worldspawn.map",
+            "{\n\"classname\" \"light\"\n}\n",
+        ));
+        parse_map_file_string("no_worldspawn.map")((&mut world, &channel)).unwrap();
+
+        assert!(world.query::<&MapFileComponent>().iter().next().is_some());
+        assert!(world.query::<&WorldspawnComponent>().iter().next().is_none());
+    }
+}
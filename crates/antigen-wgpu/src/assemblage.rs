@@ -5,23 +5,24 @@ use antigen_core::{
 use hecs::{Component, Entity};
 use wgpu::{
     util::BufferInitDescriptor, Adapter, Backends, BufferAddress, BufferDescriptor,
-    CommandEncoderDescriptor, Device, DeviceDescriptor, ImageCopyTextureBase, ImageDataLayout,
-    Instance, Queue, SamplerDescriptor, ShaderModuleDescriptor, ShaderModuleDescriptorSpirV,
-    Surface, SurfaceConfiguration, TextureDescriptor, TextureFormat, TextureUsages,
-    TextureViewDescriptor,
+    CommandEncoderDescriptor, CompareFunction, Device, DeviceDescriptor, ImageCopyTextureBase,
+    ImageDataLayout, Instance, QuerySetDescriptor, Queue, SamplerDescriptor,
+    ShaderModuleDescriptor, ShaderModuleDescriptorSpirV, Surface, SurfaceConfiguration,
+    TextureDescriptor, TextureFormat, TextureUsages, TextureViewDescriptor,
 };
 
-use std::path::Path;
+use std::{num::NonZeroU8, ops::Range, path::Path};
 
 use crate::{
     AdapterComponent, BufferComponent, BufferDescriptorComponent, BufferInitDescriptorComponent,
-    BufferWriteComponent, CommandBuffersComponent, CommandEncoderComponent,
-    CommandEncoderDescriptorComponent, DeviceComponent, InstanceComponent, QueueComponent,
-    SamplerComponent, SamplerDescriptorComponent, ShaderModuleComponent,
-    ShaderModuleDescriptorComponent, ShaderModuleDescriptorSpirVComponent, SurfaceComponent,
-    SurfaceConfigurationComponent, SurfaceTextureComponent, TextureComponent,
+    BufferReadComponent, BufferWriteComponent, CommandBuffersComponent, CommandEncoderComponent,
+    CommandEncoderDescriptorComponent, DeviceComponent, InstanceComponent, QuerySetComponent,
+    QuerySetDescriptorComponent, QuerySetResolveComponent, QuerySetResolveEncoderComponent,
+    QuerySetResolveReadComponent, QueueComponent, SamplerComponent, SamplerDescriptorComponent,
+    ShaderModuleComponent, ShaderModuleDescriptorComponent, ShaderModuleDescriptorSpirVComponent,
+    SurfaceComponent, SurfaceConfigurationComponent, SurfaceTextureComponent, TextureComponent,
     TextureDescriptorComponent, TextureViewComponent, TextureViewDescriptorComponent,
-    TextureWriteComponent,
+    TextureWriteComponent, OPTIONAL_FEATURES,
 };
 
 #[derive(hecs::Bundle)]
@@ -66,8 +67,14 @@ impl BackendBundle {
         let adapter_info = adapter.get_info();
         println!("Acquired WGPU adapter: {:#?}\n", adapter_info);
 
+        // Drop any optional features the adapter doesn't support
+        let device_desc = DeviceDescriptor {
+            features: device_desc.features - (OPTIONAL_FEATURES - adapter.features()),
+            ..device_desc.clone()
+        };
+
         let (device, queue) =
-            pollster::block_on(adapter.request_device(device_desc, trace_path)).unwrap();
+            pollster::block_on(adapter.request_device(&device_desc, trace_path)).unwrap();
 
         println!("Acquired WGPU device: {:#?}\n", device);
         println!("Acquired WGPU queue: {:#?}\n", queue);
@@ -199,6 +206,68 @@ impl<T> BufferDataBundle<T> {
     }
 }
 
+#[derive(hecs::Bundle)]
+pub struct BufferReadBundle<T> {
+    data: Changed<T>,
+    buffer_read: BufferReadComponent<T>,
+    buffer_entity: Usage<BufferReadComponent<T>, Indirect<&'static BufferComponent>>,
+}
+
+impl<T> BufferReadBundle<T> {
+    pub fn new(data: T, offset: BufferAddress, buffer_entity: Entity) -> Self {
+        let data = Changed::<T>::construct(data);
+        let buffer_read = BufferReadComponent::<T>::new(offset);
+        let buffer_entity = BufferReadComponent::<T>::as_usage(
+            Indirect::<&BufferComponent>::construct(buffer_entity),
+        );
+        BufferReadBundle {
+            data,
+            buffer_read,
+            buffer_entity,
+        }
+    }
+}
+
+#[derive(hecs::Bundle)]
+pub struct QuerySetBundle {
+    descriptor: QuerySetDescriptorComponent<'static>,
+    query_set: QuerySetComponent,
+}
+
+impl QuerySetBundle {
+    pub fn new(descriptor: QuerySetDescriptor<'static>) -> Self {
+        let descriptor = QuerySetDescriptorComponent::construct(descriptor).with(ChangedFlag(true));
+        QuerySetBundle {
+            descriptor,
+            query_set: Default::default(),
+        }
+    }
+}
+
+#[derive(hecs::Bundle)]
+pub struct QuerySetResolveBundle<T: Send + Sync + 'static> {
+    resolve: QuerySetResolveComponent,
+    read: QuerySetResolveReadComponent<T>,
+    encoder: QuerySetResolveEncoderComponent,
+}
+
+impl<T: Send + Sync + 'static> QuerySetResolveBundle<T> {
+    /// Resolve `queries` into the buffer of the BufferReadBundle<T> on `read_entity`.
+    /// Each query resolves to a u64, so T is typically [u64; N]
+    pub fn new(queries: Range<u32>, read_entity: Entity, encoder_entity: Entity) -> Self {
+        let resolve = QuerySetResolveComponent::construct(queries);
+        let read = QuerySetResolveReadComponent::<T>::construct(Indirect::construct(read_entity));
+        let encoder =
+            QuerySetResolveEncoderComponent::construct(Indirect::construct(encoder_entity));
+
+        QuerySetResolveBundle {
+            resolve,
+            read,
+            encoder,
+        }
+    }
+}
+
 #[derive(hecs::Bundle)]
 pub struct TextureBundle {
     descriptor: TextureDescriptorComponent<'static>,
@@ -219,7 +288,13 @@ impl TextureBundle {
 pub struct TextureDataBundle<T> {
     data: Changed<T>,
     texture_write: TextureWriteComponent<T>,
-    texture_entity: Usage<TextureWriteComponent<T>, Indirect<&'static TextureComponent>>,
+    texture_entity: Usage<
+        TextureWriteComponent<T>,
+        Indirect<(
+            &'static TextureDescriptorComponent<'static>,
+            &'static TextureComponent,
+        )>,
+    >,
 }
 
 impl<T> TextureDataBundle<T>
@@ -234,9 +309,10 @@ where
     ) -> Self {
         let data = Changed::<T>::construct(data).with(ChangedFlag(true));
         let texture_write = TextureWriteComponent::<T>::new(image_copy_texture, image_data_layout);
-        let texture_entity = TextureWriteComponent::<T>::as_usage(
-            Indirect::<&TextureComponent>::construct(texture_entity),
-        );
+        let texture_entity = TextureWriteComponent::<T>::as_usage(Indirect::<(
+            &TextureDescriptorComponent,
+            &TextureComponent,
+        )>::construct(texture_entity));
         TextureDataBundle {
             data,
             texture_write,
@@ -276,6 +352,18 @@ impl SamplerBundle {
             sampler: Default::default(),
         }
     }
+
+    /// Request anisotropic filtering, validated against the adapter on sampler creation
+    pub fn with_anisotropy_clamp(mut self, clamp: u8) -> Self {
+        self.descriptor.anisotropy_clamp = NonZeroU8::new(clamp);
+        self
+    }
+
+    /// Make this a comparison sampler, as used for depth texture sampling
+    pub fn with_compare(mut self, compare: CompareFunction) -> Self {
+        self.descriptor.compare = Some(compare);
+        self
+    }
 }
 
 pub enum PushConstant {}
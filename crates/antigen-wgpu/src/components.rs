@@ -1,20 +1,24 @@
-use antigen_core::{Changed, LazyComponent, Usage};
+use antigen_core::{Changed, Indirect, IndirectMulti, LazyComponent, Usage};
 
 use wgpu::{
     util::BufferInitDescriptor, Adapter, BindGroup, BindGroupLayout, Buffer, BufferAddress,
-    BufferDescriptor, CommandBuffer, CommandEncoder, CommandEncoderDescriptor, ComputePipeline,
-    Device, ImageCopyTextureBase, ImageDataLayout, Instance, PipelineLayout, Queue, RenderBundle,
+    BufferAsyncError, BufferDescriptor, CommandBuffer, CommandEncoder, CommandEncoderDescriptor,
+    ComputePipeline, Device, ImageCopyTextureBase, ImageDataLayout, Instance, PipelineLayout,
+    QuerySet, QuerySetDescriptor, Queue, RenderBundle, RenderBundleEncoderDescriptor,
     RenderPipeline, Sampler, SamplerDescriptor, ShaderModule, ShaderModuleDescriptor,
     ShaderModuleDescriptorSpirV, Surface, SurfaceConfiguration, SurfaceTexture, Texture,
     TextureDescriptor, TextureView, TextureViewDescriptor,
 };
 
 use std::{
+    future::Future,
     marker::PhantomData,
+    ops::Range,
+    pin::Pin,
     sync::{atomic::AtomicU64, Arc},
 };
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 
 // Backend primitives
 pub type InstanceComponent = Arc<Instance>;
@@ -69,6 +73,9 @@ pub type RenderPipelineComponent = LazyComponent<RenderPipeline>;
 // WGPU compute pipeline
 pub type ComputePipelineComponent = LazyComponent<ComputePipeline>;
 
+// WGPU render bundle encoder descriptor
+pub type RenderBundleEncoderDescriptorComponent<'a> = Changed<RenderBundleEncoderDescriptor<'a>>;
+
 // WGPU render bundle
 pub type RenderBundleComponent = LazyComponent<RenderBundle>;
 
@@ -90,6 +97,14 @@ pub type BufferInitDescriptorComponent<'a> = Changed<BufferInitDescriptor<'a>>;
 // WGPU buffer
 pub type BufferComponent = Arc<RwLock<LazyComponent<Buffer>>>;
 
+// Growable buffer component
+//
+// Buffers tagged with this are reallocated by buffer_write_slice_system
+// instead of being overrun, with the Changed flag marking the bind groups that need recreating
+pub enum GrowableBuffer {}
+pub type GrowableBufferComponent =
+    Changed<Usage<GrowableBuffer, IndirectMulti<&'static mut BindGroupComponent>>>;
+
 // Buffer write operation
 pub struct BufferWriteComponent<T> {
     offset: BufferAddress,
@@ -109,6 +124,68 @@ impl<T> BufferWriteComponent<T> {
     }
 }
 
+type BufferMapFuture = Pin<Box<dyn Future<Output = Result<(), BufferAsyncError>> + Send>>;
+
+// Map state of a buffer read operation
+pub enum BufferMapState {
+    // Buffer is free for GPU use, and a new read may be requested
+    Unmapped,
+    // Map requested and in flight
+    Pending(BufferMapFuture),
+    // Map complete, awaiting a copy out and unmap
+    Mapped,
+}
+
+impl BufferMapState {
+    pub fn is_unmapped(&self) -> bool {
+        matches!(self, BufferMapState::Unmapped)
+    }
+}
+
+// Buffer read operation
+//
+// Holds the in-flight map request between frames;
+// the future is not Sync, so it's kept behind a mutex that is only ever accessed mutably
+pub struct BufferReadComponent<T> {
+    offset: BufferAddress,
+    map: Mutex<BufferMapState>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> BufferReadComponent<T> {
+    pub fn new(offset: BufferAddress) -> Self {
+        BufferReadComponent {
+            offset,
+            map: Mutex::new(BufferMapState::Unmapped),
+            _phantom: Default::default(),
+        }
+    }
+
+    pub fn offset(&self) -> BufferAddress {
+        self.offset
+    }
+
+    pub fn map_state_mut(&mut self) -> &mut BufferMapState {
+        self.map.get_mut()
+    }
+}
+
+// WGPU query set descriptor
+pub type QuerySetDescriptorComponent<'a> = Changed<QuerySetDescriptor<'a>>;
+
+// WGPU query set
+pub type QuerySetComponent = LazyComponent<QuerySet>;
+
+// Query set resolve operation
+//
+// Resolves a range of queries into the buffer of a BufferReadComponent to be read back
+pub enum QuerySetResolve {}
+pub type QuerySetResolveComponent = Usage<QuerySetResolve, Range<u32>>;
+pub type QuerySetResolveReadComponent<T> =
+    Usage<QuerySetResolve, Indirect<&'static mut BufferReadComponent<T>>>;
+pub type QuerySetResolveEncoderComponent =
+    Usage<QuerySetResolve, Indirect<&'static mut CommandEncoderComponent>>;
+
 // Texture write operation
 pub struct TextureWriteComponent<T> {
     image_copy_texture: ImageCopyTextureBase<()>,
@@ -3,8 +3,9 @@ use hecs::{Entity, EntityBuilder, World};
 use wgpu::{BufferAddress, ComputePassDescriptor, DynamicOffset};
 
 use crate::{
-    BindGroupComponent, BufferComponent, CommandEncoderComponent, ComputePipelineComponent,
-    PassOrderComponent, PushConstantQuery,
+    render_pass::draw_render_passes, BindGroupComponent, BufferComponent,
+    CommandEncoderComponent, ComputePipelineComponent, PassOrderComponent, PushConstantQuery,
+    RenderPassQuery,
 };
 
 pub enum ComputePassTag {}
@@ -16,6 +17,8 @@ pub type ComputePassBindGroupsComponent =
 pub type ComputePassPushConstantsComponent =
     Usage<ComputePassTag, Vec<Indirect<PushConstantQuery<'static>>>>;
 pub type ComputePassDispatchComponent = Usage<ComputePassTag, (u32, u32, u32)>;
+pub type ComputePassEncoderComponent =
+    Usage<ComputePassTag, Indirect<&'static mut CommandEncoderComponent>>;
 
 pub struct ComputePassDispatchIndirectComponent {
     buffer: Indirect<&'static BufferComponent>,
@@ -31,6 +34,7 @@ fn compute_pass_bundle_impl(
     pipeline_entity: Entity,
     bind_group_entities: Vec<(Entity, Vec<DynamicOffset>)>,
     push_constant_entities: Vec<Entity>,
+    encoder: Entity,
 ) {
     builder.add(PassOrderComponent::construct(order));
 
@@ -61,6 +65,9 @@ fn compute_pass_bundle_impl(
                 .collect(),
         ));
     }
+
+    let encoder = ComputePassEncoderComponent::construct(encoder);
+    builder.add(encoder);
 }
 
 impl ComputePassBundle {
@@ -71,6 +78,7 @@ impl ComputePassBundle {
         bind_group_entities: Vec<(Entity, Vec<DynamicOffset>)>,
         push_constant_entities: Vec<Entity>,
         dispatch: (u32, u32, u32),
+        encoder: Entity,
     ) -> EntityBuilder {
         let mut builder = EntityBuilder::new();
 
@@ -81,6 +89,7 @@ impl ComputePassBundle {
             pipeline_entity,
             bind_group_entities,
             push_constant_entities,
+            encoder,
         );
 
         let dispatch = ComputePassTag::as_usage(dispatch);
@@ -97,6 +106,7 @@ impl ComputePassBundle {
         push_constant_entities: Vec<Entity>,
         indirect_entity: Entity,
         indirect_offset: BufferAddress,
+        encoder: Entity,
     ) -> EntityBuilder {
         let mut builder = EntityBuilder::new();
 
@@ -107,6 +117,7 @@ impl ComputePassBundle {
             pipeline_entity,
             bind_group_entities,
             push_constant_entities,
+            encoder,
         );
 
         let buffer = Indirect::construct(indirect_entity);
@@ -121,125 +132,345 @@ impl ComputePassBundle {
 
 #[derive(hecs::Query)]
 pub struct ComputePassQuery<'a> {
-    order: &'a PassOrderComponent,
+    pub(crate) order: &'a PassOrderComponent,
     desc: &'a ComputePassDescriptor<'static>,
     pipeline: &'a ComputePassPipelineComponent,
     bind_groups: &'a ComputePassBindGroupsComponent,
     push_constants: Option<&'a ComputePassPushConstantsComponent>,
     dispatch: hecs::Or<&'a ComputePassDispatchComponent, &'a ComputePassDispatchIndirectComponent>,
-    encoder: &'a mut CommandEncoderComponent,
+    encoder: &'a ComputePassEncoderComponent,
 }
 
+// Dispatch compute passes in PassOrder
+//
+// Use encode_passes_system instead when compute passes need to be interleaved with render passes
 pub fn dispatch_compute_passes_system(world: &mut World) -> Option<()> {
     let mut query = world.query::<ComputePassQuery>();
 
     let mut components = query.into_iter().collect::<Vec<_>>();
-    components.sort_unstable_by(|(_, lhs), (_, rhs)| lhs.order.cmp(rhs.order));
+    components.sort_by(|(_, lhs), (_, rhs)| lhs.order.cmp(rhs.order));
 
-    for (
-        entity,
-        ComputePassQuery {
-            desc,
-            pipeline,
-            bind_groups,
-            push_constants,
-            dispatch,
-            encoder,
-            ..
-        },
-    ) in components.into_iter()
-    {
-        let encoder = encoder.get_mut()?;
+    for (_, pass) in components {
+        dispatch_compute_pass(world, &pass)?;
+    }
 
-        // Collect pipeline
-        let mut query = pipeline.get(world);
-        let pipeline = query.get()?;
-        let pipeline = pipeline.get()?;
+    Some(())
+}
 
-        // Collect bind group queries
-        let (mut bind_group_queries, bind_group_offsets): (Vec<_>, Vec<_>) = bind_groups
-            .iter()
-            .map(|(bind_group, offsets)| (bind_group.get(world), offsets))
-            .unzip();
-
-        let bind_groups = bind_group_queries
-            .iter_mut()
-            .map(|query| {
-                let bind_group = query.get().unwrap();
-                bind_group.get().unwrap()
-            })
-            .collect::<Vec<_>>();
-
-        // Collect push constant queries
-        let mut push_constant_queries = if let Some(push_constants) = push_constants {
-            let push_constant_queries = push_constants
-                .iter()
-                .map(|push_constant| push_constant.get(world))
-                .collect::<Vec<_>>();
-
-            push_constant_queries
-        } else {
-            vec![]
-        };
+enum Pass<'a> {
+    Render(Entity, RenderPassQuery<'a>),
+    Compute(ComputePassQuery<'a>),
+}
 
-        let push_constants = push_constant_queries
-            .iter_mut()
-            .map(|query| query.get().unwrap())
-            .collect::<Vec<_>>();
+impl Pass<'_> {
+    fn order(&self) -> usize {
+        match self {
+            Pass::Render(_, pass) => **pass.order,
+            Pass::Compute(pass) => **pass.order,
+        }
+    }
+}
 
-        let dispatch_ind = dispatch.right();
-        let mut dispatch_ind_query =
-            dispatch_ind.map(|dispatch_ind| (dispatch_ind.buffer.get(world), dispatch_ind.offset));
-        let dispatch_ind_buffer = dispatch_ind_query
-            .as_mut()
-            .map(|(query, offset)| (query.get().unwrap(), *offset));
-        let dispatch_ind_lock = dispatch_ind_buffer
-            .as_ref()
-            .map(|(buffer, offset)| (buffer.read(), *offset));
+// Record render and compute passes into their encoders in PassOrder
+//
+// Consecutive render passes are batched as per draw_render_passes_system,
+// and render passes are recorded before compute passes that share their order
+pub fn encode_passes_system(world: &mut World) -> Option<()> {
+    let mut render_query = world.query::<RenderPassQuery>();
+    let mut compute_query = world.query::<ComputePassQuery>();
+
+    let mut passes = render_query
+        .into_iter()
+        .map(|(entity, pass)| Pass::Render(entity, pass))
+        .chain(
+            compute_query
+                .into_iter()
+                .map(|(_, pass)| Pass::Compute(pass)),
+        )
+        .collect::<Vec<_>>();
+    passes.sort_by_key(Pass::order);
+
+    let mut render_passes = vec![];
+    for pass in passes {
+        match pass {
+            Pass::Render(entity, pass) => render_passes.push((entity, pass)),
+            Pass::Compute(pass) => {
+                if !render_passes.is_empty() {
+                    draw_render_passes(world, std::mem::take(&mut render_passes))?;
+                }
+                dispatch_compute_pass(world, &pass)?;
+            }
+        }
+    }
 
-        let dispatch = dispatch.left();
+    if !render_passes.is_empty() {
+        draw_render_passes(world, render_passes)?;
+    }
 
-        let mut cpass = encoder.begin_compute_pass(&desc);
-        println!("Setting pipeline {:?}", pipeline);
-        cpass.set_pipeline(pipeline);
+    Some(())
+}
+
+fn dispatch_compute_pass(world: &World, pass: &ComputePassQuery) -> Option<()> {
+    let ComputePassQuery {
+        desc,
+        pipeline,
+        bind_groups,
+        push_constants,
+        dispatch,
+        encoder,
+        ..
+    } = pass;
+
+    let mut query = encoder.get(world);
+    let encoder = query.get().unwrap().get_mut()?;
+
+    // Collect pipeline
+    let mut query = pipeline.get(world);
+    let pipeline = query.get()?;
+    let pipeline = pipeline.get()?;
+
+    // Collect bind group queries
+    let (mut bind_group_queries, bind_group_offsets): (Vec<_>, Vec<_>) = bind_groups
+        .iter()
+        .map(|(bind_group, offsets)| (bind_group.get(world), offsets))
+        .unzip();
+
+    let mut bind_groups = vec![];
+    for query in bind_group_queries.iter_mut() {
+        bind_groups.push(query.get().unwrap().get()?);
+    }
 
-        for (i, (bind_group, offsets)) in bind_groups
+    // Collect push constant queries
+    let mut push_constant_queries = if let Some(push_constants) = push_constants {
+        push_constants
             .iter()
-            .zip(bind_group_offsets.iter())
-            .enumerate()
-        {
-            println!(
-                "Setting bind group {}: {:?} with offsets {:?}",
-                i as u32, bind_group, offsets
-            );
-            cpass.set_bind_group(i as u32, bind_group, &offsets);
-        }
+            .map(|push_constant| push_constant.get(world))
+            .collect::<Vec<_>>()
+    } else {
+        vec![]
+    };
+
+    let push_constants = push_constant_queries
+        .iter_mut()
+        .map(|query| query.get().unwrap())
+        .collect::<Vec<_>>();
+
+    // Collect indirect dispatch buffer
+    let dispatch_ind = dispatch.right();
+    let mut dispatch_ind_query =
+        dispatch_ind.map(|dispatch_ind| (dispatch_ind.buffer.get(world), dispatch_ind.offset));
+    let dispatch_ind_buffer = dispatch_ind_query
+        .as_mut()
+        .map(|(query, offset)| (query.get().unwrap(), *offset));
+    let dispatch_ind_lock = dispatch_ind_buffer
+        .as_ref()
+        .map(|(buffer, offset)| (buffer.read(), *offset));
+
+    if let Some((buffer, _)) = &dispatch_ind_lock {
+        buffer.get()?;
+    }
 
-        for push_constant in push_constants {
-            println!(
-                "Setting push constant with offset {}",
-                **push_constant.offset
-            );
-            cpass.set_push_constants(**push_constant.offset, &***push_constant.data);
+    let dispatch = dispatch.left();
+
+    let mut cpass = encoder.begin_compute_pass(desc);
+    cpass.set_pipeline(pipeline);
+
+    for (i, (bind_group, offsets)) in bind_groups
+        .iter()
+        .zip(bind_group_offsets.iter())
+        .enumerate()
+    {
+        cpass.set_bind_group(i as u32, bind_group, offsets);
+    }
+
+    for push_constant in push_constants {
+        cpass.set_push_constants(**push_constant.offset, push_constant.data);
+    }
+
+    if let Some(dispatch) = dispatch {
+        cpass.dispatch(dispatch.0, dispatch.1, dispatch.2);
+    }
+
+    if let Some((buffer, offset)) = &dispatch_ind_lock {
+        cpass.dispatch_indirect(buffer.get().unwrap(), *offset);
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use antigen_core::LazyComponent;
+    use parking_lot::RwLock;
+    use wgpu::{
+        util::{BufferInitDescriptor, DeviceExt},
+        BindGroupDescriptor, BindGroupEntry, BufferDescriptor, BufferUsages,
+        CommandEncoderDescriptor, ComputePipelineDescriptor, Maintain, MapMode,
+        ShaderModuleDescriptor, ShaderSource,
+    };
+
+    use super::*;
+    use crate::{
+        create_command_encoders_system, flush_command_encoders_system,
+        submit_command_buffers_system, with_headless_backend, CommandBuffersComponent,
+        CommandEncoderBundle, DeviceComponent,
+    };
+
+    const COMPUTE_SHADER: &str = r#"
+        struct Data {
+            value: u32;
+        };
+
+        [[group(0), binding(0)]]
+        var<storage, read_write> data: Data;
+
+        [[stage(compute), workgroup_size(1)]]
+        fn double() {
+            data.value = data.value * 2u;
         }
 
-        if let Some(dispatch) = dispatch {
-            println!(
-                "Dispatching compute work groups ({}, {}, {}) for entity {:?}",
-                dispatch.0, dispatch.1, dispatch.2, entity
-            );
-            cpass.dispatch(dispatch.0, dispatch.1, dispatch.2);
+        [[stage(compute), workgroup_size(1)]]
+        fn increment() {
+            data.value = data.value + 1u;
         }
+    "#;
+
+    #[test]
+    fn compute_pass_order() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            world.spawn(backend);
+
+            let encoder_entity = world.reserve_entity();
+            world
+                .insert(
+                    encoder_entity,
+                    CommandEncoderBundle::new(
+                        CommandEncoderDescriptor {
+                            label: Some("Compute Test Encoder"),
+                        },
+                        encoder_entity,
+                    ),
+                )
+                .unwrap();
+            world
+                .insert_one(encoder_entity, CommandBuffersComponent::default())
+                .unwrap();
+
+            // Create the pipelines, bind group and buffers up-front
+            let mut query = world.query::<&DeviceComponent>();
+            let (_, device) = query.into_iter().next().unwrap();
+
+            let shader = device.create_shader_module(&ShaderModuleDescriptor {
+                label: None,
+                source: ShaderSource::Wgsl(COMPUTE_SHADER.into()),
+            });
+
+            let create_pipeline = |entry_point| {
+                let mut pipeline = ComputePipelineComponent::default();
+                pipeline.set_ready_with(device.create_compute_pipeline(
+                    &ComputePipelineDescriptor {
+                        label: None,
+                        layout: None,
+                        module: &shader,
+                        entry_point,
+                    },
+                ));
+                pipeline
+            };
+            let double = create_pipeline("double");
+            let increment = create_pipeline("increment");
+
+            let storage = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Compute Test Storage Buffer"),
+                contents: bytemuck::bytes_of(&1u32),
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            });
+
+            let readback = device.create_buffer(&BufferDescriptor {
+                label: Some("Compute Test Readback Buffer"),
+                size: std::mem::size_of::<u32>() as BufferAddress,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+
+            let mut bind_group = BindGroupComponent::default();
+            bind_group.set_ready_with(device.create_bind_group(&BindGroupDescriptor {
+                label: None,
+                layout: &double.get().unwrap().get_bind_group_layout(0),
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: storage.as_entire_binding(),
+                }],
+            }));
+            drop(query);
+
+            let double_entity = world.spawn((double,));
+            let increment_entity = world.spawn((increment,));
+            let bind_group_entity = world.spawn((bind_group,));
+            let storage: BufferComponent = Arc::new(RwLock::new(LazyComponent::Ready(storage)));
+            let storage_entity = world.spawn((storage,));
+
+            // Spawned out of order, to make sure passes are sorted before being dispatched
+            world.spawn(
+                ComputePassBundle::dispatch(
+                    1,
+                    ComputePassDescriptor::default(),
+                    increment_entity,
+                    vec![(bind_group_entity, vec![])],
+                    vec![],
+                    (1, 1, 1),
+                    encoder_entity,
+                )
+                .build(),
+            );
 
-        if let Some((buffer, offset)) = &dispatch_ind_lock {
-            println!(
-                "Dispatching indirect compute work group for entity {:?}",
-                entity
+            world.spawn(
+                ComputePassBundle::dispatch(
+                    0,
+                    ComputePassDescriptor::default(),
+                    double_entity,
+                    vec![(bind_group_entity, vec![])],
+                    vec![],
+                    (1, 1, 1),
+                    encoder_entity,
+                )
+                .build(),
             );
-            let buffer = buffer.get().unwrap();
-            cpass.dispatch_indirect(buffer, *offset);
-        }
-    }
 
-    Some(())
+            create_command_encoders_system(&mut world);
+            encode_passes_system(&mut world).unwrap();
+
+            {
+                let storage = world.get::<BufferComponent>(storage_entity).unwrap();
+                let storage = storage.read();
+                let mut encoder = world
+                    .get_mut::<CommandEncoderComponent>(encoder_entity)
+                    .unwrap();
+                encoder.get_mut().unwrap().copy_buffer_to_buffer(
+                    storage.get().unwrap(),
+                    0,
+                    &readback,
+                    0,
+                    std::mem::size_of::<u32>() as BufferAddress,
+                );
+            }
+
+            flush_command_encoders_system(&mut world);
+            submit_command_buffers_system(&mut world);
+
+            let mut query = world.query::<&DeviceComponent>();
+            let (_, device) = query.into_iter().next().unwrap();
+
+            let slice = readback.slice(..);
+            let map = slice.map_async(MapMode::Read);
+            device.poll(Maintain::Wait);
+            pollster::block_on(map).unwrap();
+
+            // (1 * 2) + 1, rather than (1 + 1) * 2
+            assert_eq!(*bytemuck::from_bytes::<u32>(&slice.get_mapped_range()), 3);
+        });
+    }
 }
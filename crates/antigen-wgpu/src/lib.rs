@@ -1,14 +1,21 @@
 mod assemblage;
 mod components;
-//mod staging_belt;
 mod compute_pass;
+mod offscreen;
+mod readback;
+mod render_bundle;
 mod render_pass;
+mod render_test;
+mod staging_belt;
 mod systems;
+mod vertex_layout;
 
 use std::path::PathBuf;
 
-use antigen_core::{MessageContext, MessageResult, WorldChannel};
-use antigen_fs::FileStringQuery;
+use antigen_core::{
+    ChangedFlag, Construct, MessageContext, MessageResult, Usage, With, WorldChannel,
+};
+use antigen_fs::{find_all_file_bytes, find_all_file_strings};
 use antigen_winit::{
     winit::{
         event::Event,
@@ -18,20 +25,73 @@ use antigen_winit::{
 };
 pub use assemblage::*;
 pub use components::*;
-//pub use staging_belt::*;
 pub use compute_pass::*;
+pub use offscreen::*;
+pub use readback::*;
+pub use render_bundle::*;
 pub use render_pass::*;
+pub use render_test::*;
+pub use staging_belt::*;
 use hecs::World;
 pub use systems::*;
+pub use vertex_layout::*;
 pub use wgpu;
 
-use wgpu::{BufferAddress, ShaderModuleDescriptor, ShaderSource};
+use wgpu::{
+    BindGroupLayoutEntry, BindingType, BufferAddress, Features, FilterMode, SamplerBindingType,
+    SamplerDescriptor, ShaderModuleDescriptor, ShaderModuleDescriptorSpirV, ShaderSource,
+    ShaderStages,
+};
+
+// Maximum sampler anisotropy clamp supported by wgpu
+pub const MAX_ANISOTROPY: u8 = 16;
+
+// Device features requested only when the adapter supports them;
+// systems that depend on them check the device and skip themselves when missing
+pub const OPTIONAL_FEATURES: Features = Features::TIMESTAMP_QUERY
+    .union(Features::MULTI_DRAW_INDIRECT)
+    .union(Features::MULTI_DRAW_INDIRECT_COUNT)
+    .union(Features::SPIRV_SHADER_PASSTHROUGH);
+
+// First word of every SPIR-V binary
+const SPIRV_MAGIC_NUMBER: u32 = 0x0723_0203;
+
+// Alignment of std140 uniform structs
+pub const UNIFORM_STRUCT_ALIGNMENT: BufferAddress = 16;
 
 // Return the size of type T in bytes, respresented as a BufferAddress
 pub fn buffer_size_of<T>() -> BufferAddress {
     std::mem::size_of::<T>() as BufferAddress
 }
 
+// Return the binding type a sampler created from the given descriptor must be bound as
+pub fn sampler_binding_type(descriptor: &SamplerDescriptor) -> SamplerBindingType {
+    if descriptor.compare.is_some() {
+        SamplerBindingType::Comparison
+    } else if descriptor.mag_filter == FilterMode::Nearest
+        && descriptor.min_filter == FilterMode::Nearest
+        && descriptor.mipmap_filter == FilterMode::Nearest
+    {
+        SamplerBindingType::NonFiltering
+    } else {
+        SamplerBindingType::Filtering
+    }
+}
+
+// Return a bind group layout entry matching a sampler created from the given descriptor
+pub fn sampler_layout_entry(
+    binding: u32,
+    visibility: ShaderStages,
+    descriptor: &SamplerDescriptor,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: BindingType::Sampler(sampler_binding_type(descriptor)),
+        count: None,
+    }
+}
+
 // Submit comomand buffers, present surface textures, and drop texture views
 pub fn submit_and_present_schedule(world: &mut World) {
     submit_command_buffers_system(world);
@@ -47,8 +107,6 @@ fn window_surfaces_schedule(world: &mut World) {
 
 /// Extend an event loop closure with wgpu resource handling
 pub fn winit_event_handler<T: Clone>(mut f: impl EventLoopHandler<T>) -> impl EventLoopHandler<T> {
-    //let mut staging_belt_manager = StagingBeltManager::new();
-
     move |world: &mut World,
           channel: &WorldChannel,
           event: Event<'static, T>,
@@ -57,14 +115,11 @@ pub fn winit_event_handler<T: Clone>(mut f: impl EventLoopHandler<T>) -> impl Ev
         match event {
             Event::MainEventsCleared => {
                 window_surfaces_schedule(world);
-                //create_staging_belt_thread_local(&world.read(), &mut staging_belt_manager);
+                create_staging_belts_system(world);
             }
             Event::RedrawRequested(_) => {
                 surfaces_textures_views_system(world);
             }
-            Event::RedrawEventsCleared => {
-                //staging_belt_finish_thread_local(&world.read(), &mut staging_belt_manager);
-            }
             _ => (),
         }
 
@@ -78,12 +133,13 @@ pub fn winit_event_handler<T: Clone>(mut f: impl EventLoopHandler<T>) -> impl Ev
 
         match event {
             Event::MainEventsCleared => {
-                //staging_belt_flush_thread_local(&world.read(), &mut staging_belt_manager);
+                staging_belt_flush_system(world);
+                staging_belt_finish_system(world);
                 reset_surface_config_changed_system(world);
             }
             Event::RedrawEventsCleared => {
                 submit_and_present_schedule(world);
-                //staging_belt_recall_thread_local(&world.read(), &mut staging_belt_manager);
+                staging_belt_recall_system(world);
             }
             _ => (),
         }
@@ -103,11 +159,8 @@ pub fn spawn_shader_from_file_string<'a, 'b, P: Into<PathBuf>>(
             map_path
         );
 
-        let components = world
-            .query_mut::<FileStringQuery>()
-            .into_iter()
-            .filter(|(_, FileStringQuery { path, .. })| ***path == *map_path)
-            .map(|(entity, FileStringQuery { string, .. })| {
+        let shaders = find_all_file_strings(world, &map_path)
+            .map(|(entity, string)| {
                 println!("Creating shader for entity {:?}", entity);
                 (
                     entity,
@@ -119,12 +172,166 @@ pub fn spawn_shader_from_file_string<'a, 'b, P: Into<PathBuf>>(
             })
             .collect::<Vec<_>>();
 
-        for (entity, map) in components {
+        for (entity, shader) in shaders {
             world
-                .insert(entity, map)
+                .insert(entity, shader)
                 .expect("Failed to add shader to entity");
         }
 
         Ok(ctx)
     }
 }
+
+// Validate a SPIR-V binary and reinterpret it as the words it is made of
+fn spirv_words(bytes: &[u8]) -> Result<Vec<u32>, String> {
+    if bytes.is_empty() || bytes.len() % 4 != 0 {
+        return Err(format!(
+            "SPIR-V binary length {} is not a non-zero multiple of 4 bytes",
+            bytes.len()
+        ));
+    }
+
+    let magic = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    if magic != SPIRV_MAGIC_NUMBER {
+        return Err(format!(
+            "SPIR-V binary has wrong magic word {:#010x}",
+            magic
+        ));
+    }
+
+    Ok(wgpu::util::make_spirv_raw(bytes).into_owned())
+}
+
+/// Create a usage-tagged SPIR-V shader module from the file bytes loaded from `path`
+///
+/// The module is created by `create_shader_modules_spirv_system::<T>`,
+/// and stays pending on devices without `Features::SPIRV_SHADER_PASSTHROUGH`.
+pub fn spawn_shader_from_file_bytes_spirv<'a, 'b, T: Send + Sync + 'static, P: Into<PathBuf>>(
+    path: P,
+) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+
+        let map_path = path.into();
+        println!(
+            "Thread {} Looking for file bytes entities with path {:?}..",
+            std::thread::current().name().unwrap(),
+            map_path
+        );
+
+        let shaders = find_all_file_bytes(world, &map_path)
+            .map(|(entity, bytes)| {
+                let source = spirv_words(bytes).map_err(|e| format!("{:?}: {}", map_path, e))?;
+
+                println!("Creating spir-v shader for entity {:?}", entity);
+                Ok((
+                    entity,
+                    (
+                        Usage::<T, ShaderModuleDescriptorSpirVComponent>::construct(
+                            ShaderModuleDescriptorSpirV {
+                                label: None,
+                                source: std::borrow::Cow::Owned(source),
+                            },
+                        )
+                        .with(ChangedFlag(true)),
+                        Usage::<T, ShaderModuleComponent>::default(),
+                    ),
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        for (entity, shader) in shaders {
+            world
+                .insert(entity, shader)
+                .expect("Failed to add shader to entity");
+        }
+
+        Ok(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use antigen_core::WorldExchange;
+    use antigen_fs::FileBytesBundle;
+
+    enum TestShader {}
+
+    // Minimal compute shader with an empty entry point
+    #[rustfmt::skip]
+    const EMPTY_COMPUTE_SPIRV: [u32; 35] = [
+        // Header: magic, version 1.0, generator, id bound, schema
+        0x07230203, 0x00010000, 0, 5, 0,
+        // OpCapability Shader
+        0x00020011, 1,
+        // OpMemoryModel Logical GLSL450
+        0x0003000e, 0, 1,
+        // OpEntryPoint GLCompute %1 "main"
+        0x0005000f, 5, 1, 0x6e69616d, 0,
+        // OpExecutionMode %1 LocalSize 1 1 1
+        0x00060010, 1, 17, 1, 1, 1,
+        // %2 = OpTypeVoid, %3 = OpTypeFunction %2
+        0x00020013, 2, 0x00030021, 3, 2,
+        // %1 = OpFunction %2 None %3, %4 = OpLabel, OpReturn, OpFunctionEnd
+        0x00050036, 2, 1, 0, 3, 0x000200f8, 4, 0x000100fd, 0x00010038,
+    ];
+
+    fn spirv_bytes(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_ne_bytes()).collect()
+    }
+
+    #[test]
+    fn spirv_file_bytes_create_tagged_shader_module() {
+        let mut world = World::new();
+        let channel = WorldExchange::default().create_channel::<()>();
+
+        let entity = world.spawn(FileBytesBundle::new(
+            "empty.spv",
+            spirv_bytes(&EMPTY_COMPUTE_SPIRV),
+        ));
+        spawn_shader_from_file_bytes_spirv::<TestShader, _>("empty.spv")((&mut world, &channel))
+            .unwrap();
+
+        // The module stays pending until a device is available
+        create_shader_modules_spirv_system::<TestShader>(&mut world);
+        assert!(world
+            .get::<Usage<TestShader, ShaderModuleComponent>>(entity)
+            .unwrap()
+            .is_pending());
+
+        with_headless_backend(|backend| {
+            world.spawn(backend);
+            let supported = {
+                let mut query = world.query::<&DeviceComponent>();
+                let (_, device) = query.into_iter().next().unwrap();
+                device
+                    .features()
+                    .contains(Features::SPIRV_SHADER_PASSTHROUGH)
+            };
+
+            // Devices without passthrough support leave the module pending rather than failing
+            create_shader_modules_spirv_system::<TestShader>(&mut world);
+            let shader_module = world
+                .get::<Usage<TestShader, ShaderModuleComponent>>(entity)
+                .unwrap();
+            assert_eq!(shader_module.is_ready(), supported);
+            assert_eq!(shader_module.is_pending(), !supported);
+        });
+    }
+
+    #[test]
+    fn spirv_words_rejects_invalid_binaries() {
+        let magic = 0x07230203u32.to_ne_bytes();
+        assert_eq!(spirv_words(&magic), Ok(vec![0x07230203]));
+
+        let error = spirv_words(&magic[..3]).unwrap_err();
+        assert!(error.contains("not a non-zero multiple of 4"));
+
+        let error = spirv_words(&[]).unwrap_err();
+        assert!(error.contains("not a non-zero multiple of 4"));
+
+        let error = spirv_words(&0x07230203u32.swap_bytes().to_ne_bytes()).unwrap_err();
+        assert!(error.contains("wrong magic word"));
+    }
+}
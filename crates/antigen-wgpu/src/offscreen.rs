@@ -0,0 +1,227 @@
+use antigen_core::{ChangedFlag, ChangedTrait, Construct, With};
+use hecs::{Entity, World};
+use wgpu::{
+    BufferAddress, BufferDescriptor, BufferUsages, Extent3d, PresentMode, SurfaceConfiguration,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor,
+};
+
+use crate::{
+    padded_bytes_per_row, read_texture, BufferComponent, BufferDescriptorComponent,
+    DeviceComponent, QueueComponent, SurfaceConfigurationComponent, TextureComponent,
+    TextureDescriptorComponent, TextureViewComponent, TextureViewDescriptorComponent,
+};
+
+// Offscreen render target tag
+pub struct OffscreenTarget;
+
+/// Windowless render target with a CPU readback buffer
+///
+/// Carries a SurfaceConfigurationComponent describing its size and format,
+/// so renderers that size themselves against a window surface can target it instead.
+/// Its Changed flag is set on creation and cleared by reset_surface_config_changed_system.
+#[derive(hecs::Bundle)]
+pub struct OffscreenTargetBundle {
+    offscreen_target: OffscreenTarget,
+    surface_config: SurfaceConfigurationComponent,
+    texture_desc: TextureDescriptorComponent<'static>,
+    texture: TextureComponent,
+    texture_view_desc: TextureViewDescriptorComponent<'static>,
+    texture_view: TextureViewComponent,
+    readback_buffer_desc: BufferDescriptorComponent<'static>,
+    readback_buffer: BufferComponent,
+}
+
+impl OffscreenTargetBundle {
+    pub fn new(label: &'static str, width: u32, height: u32, format: TextureFormat) -> Self {
+        let surface_config = SurfaceConfigurationComponent::construct(SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width,
+            height,
+            present_mode: PresentMode::Fifo,
+        })
+        .with(ChangedFlag(true));
+
+        let texture_desc = TextureDescriptorComponent::construct(TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+        })
+        .with(ChangedFlag(true));
+
+        let texture_view_desc =
+            TextureViewDescriptorComponent::construct(TextureViewDescriptor::default())
+                .with(ChangedFlag(true));
+
+        let readback_buffer_desc = BufferDescriptorComponent::construct(BufferDescriptor {
+            label: Some(label),
+            size: (padded_bytes_per_row(width, format) * height) as BufferAddress,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        })
+        .with(ChangedFlag(true));
+
+        OffscreenTargetBundle {
+            offscreen_target: OffscreenTarget,
+            surface_config,
+            texture_desc,
+            texture: Default::default(),
+            texture_view_desc,
+            texture_view: Default::default(),
+            readback_buffer_desc,
+            readback_buffer: Default::default(),
+        }
+    }
+}
+
+/// Resize offscreen target textures and readback buffers whose surface config has changed
+pub fn offscreen_target_resize_system(world: &mut World) {
+    for (_, (surface_config, texture_desc, texture_view_desc, readback_buffer_desc)) in world
+        .query::<(
+            &SurfaceConfigurationComponent,
+            &mut TextureDescriptorComponent,
+            &mut TextureViewDescriptorComponent,
+            &mut BufferDescriptorComponent,
+        )>()
+        .with::<OffscreenTarget>()
+        .into_iter()
+    {
+        if !surface_config.get_changed() {
+            continue;
+        }
+
+        let size = Extent3d {
+            width: surface_config.width,
+            height: surface_config.height,
+            depth_or_array_layers: 1,
+        };
+
+        if texture_desc.size == size && texture_desc.format == surface_config.format {
+            continue;
+        }
+
+        texture_desc.size = size;
+        texture_desc.format = surface_config.format;
+        texture_desc.set_changed(true);
+        texture_view_desc.set_changed(true);
+
+        readback_buffer_desc.size = (padded_bytes_per_row(size.width, surface_config.format)
+            * size.height) as BufferAddress;
+        readback_buffer_desc.set_changed(true);
+    }
+}
+
+/// Copy an offscreen target's texture into its readback buffer and wait for the result
+///
+/// Returns the texture's contents as tightly-packed rows in its own format,
+/// or None if the texture or readback buffer have yet to be created.
+/// Blocks until all previously submitted work has completed.
+///
+/// See texture_to_rgba8 for a format-converting equivalent.
+pub fn read_offscreen_target(world: &World, entity: Entity) -> Option<Vec<u8>> {
+    let mut query = world.query::<&DeviceComponent>();
+    let (_, device) = query.into_iter().next()?;
+
+    let mut query = world.query::<&QueueComponent>();
+    let (_, queue) = query.into_iter().next()?;
+
+    let mut query = world
+        .query_one::<(
+            &TextureDescriptorComponent,
+            &TextureComponent,
+            &BufferComponent,
+        )>(entity)
+        .ok()?;
+    let (texture_desc, texture, readback_buffer) = query.get()?;
+
+    let texture = texture.get()?;
+    let readback_buffer = readback_buffer.read();
+    let readback_buffer = readback_buffer.get()?;
+
+    read_texture(
+        device,
+        queue,
+        texture,
+        texture_desc.size,
+        texture_desc.format,
+        readback_buffer,
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wgpu::{
+        Color, CommandEncoderDescriptor, LoadOp, Operations, RenderPassColorAttachment,
+        RenderPassDescriptor,
+    };
+
+    use crate::{
+        create_buffers_system, create_texture_views_system, create_textures_system,
+        with_headless_backend,
+    };
+
+    #[test]
+    fn read_offscreen_target_strips_row_padding() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            world.spawn(backend);
+
+            // 10 texels * 4 bytes per row is padded to 256 bytes per row in the readback buffer
+            let entity = world.spawn(OffscreenTargetBundle::new(
+                "Offscreen Test Target",
+                10,
+                4,
+                TextureFormat::Rgba8Unorm,
+            ));
+
+            assert!(read_offscreen_target(&world, entity).is_none());
+
+            create_textures_system(&mut world);
+            create_texture_views_system(&mut world);
+            create_buffers_system(&mut world);
+
+            {
+                let mut query = world.query::<&DeviceComponent>();
+                let (_, device) = query.into_iter().next().unwrap();
+
+                let mut query = world.query::<&QueueComponent>();
+                let (_, queue) = query.into_iter().next().unwrap();
+
+                let view = world.get::<TextureViewComponent>(entity).unwrap();
+
+                let mut encoder =
+                    device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+                encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[RenderPassColorAttachment {
+                        view: view.get().unwrap(),
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Clear(Color::RED),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                queue.submit(Some(encoder.finish()));
+            }
+
+            let data = read_offscreen_target(&world, entity).unwrap();
+            assert_eq!(data.len(), 10 * 4 * 4);
+            assert!(data.chunks(4).all(|texel| texel == [255, 0, 0, 255]));
+        });
+    }
+}
@@ -0,0 +1,284 @@
+use std::num::NonZeroU32;
+
+use hecs::{Entity, World};
+use wgpu::{
+    Buffer, BufferAddress, BufferAsyncError, BufferDescriptor, BufferUsages,
+    CommandEncoderDescriptor, Device, Extent3d, ImageCopyBuffer, ImageCopyTexture,
+    ImageDataLayout, Maintain, MapMode, Origin3d, Queue, Texture, TextureAspect, TextureFormat,
+    COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+use crate::{DeviceComponent, Image, QueueComponent, TextureComponent, TextureDescriptorComponent};
+
+/// Error returned when a texture can't be read back as RGBA8
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadbackError {
+    /// The entity has no texture, or its texture has yet to be created
+    NotReady,
+    /// Floating-point formats hold unbounded values that must be tonemapped first
+    HdrFormat(TextureFormat),
+    /// The format has no direct RGBA8 representation
+    UnsupportedFormat(TextureFormat),
+    /// The readback buffer could not be mapped
+    Map(BufferAsyncError),
+}
+
+impl std::fmt::Display for ReadbackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadbackError::NotReady => write!(f, "Texture is not ready"),
+            ReadbackError::HdrFormat(format) => write!(
+                f,
+                "Cannot read back HDR format {:?} as RGBA8, tonemap it into an 8-bit target first",
+                format
+            ),
+            ReadbackError::UnsupportedFormat(format) => {
+                write!(f, "Cannot read back format {:?} as RGBA8", format)
+            }
+            ReadbackError::Map(e) => write!(f, "Failed to map readback buffer: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReadbackError {}
+
+// Return the number of bytes per row of a texture copied into a buffer,
+// padded to COPY_BYTES_PER_ROW_ALIGNMENT
+pub fn padded_bytes_per_row(width: u32, format: TextureFormat) -> u32 {
+    let unpadded_bytes_per_row = width * format.describe().block_size as u32;
+    unpadded_bytes_per_row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+// Return whether texels of the given format need their red and blue channels swapped
+// to produce RGBA8, or an error if the format can't be represented as RGBA8
+fn rgba8_swizzle(format: TextureFormat) -> Result<bool, ReadbackError> {
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => Ok(false),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => Ok(true),
+        TextureFormat::R16Float
+        | TextureFormat::R32Float
+        | TextureFormat::Rg16Float
+        | TextureFormat::Rg32Float
+        | TextureFormat::Rg11b10Float
+        | TextureFormat::Rgba16Float
+        | TextureFormat::Rgba32Float => Err(ReadbackError::HdrFormat(format)),
+        format => Err(ReadbackError::UnsupportedFormat(format)),
+    }
+}
+
+// Copy the first mip of a 2D texture into `buffer`, block until the copy completes,
+// and return its rows tightly packed with the buffer's padding removed
+pub(crate) fn read_texture(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+    size: Extent3d,
+    format: TextureFormat,
+    buffer: &Buffer,
+) -> Result<Vec<u8>, BufferAsyncError> {
+    let Extent3d { width, height, .. } = size;
+    let unpadded_bytes_per_row = width * format.describe().block_size as u32;
+    let padded_bytes_per_row = padded_bytes_per_row(width, format);
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Readback Encoder"),
+    });
+
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                rows_per_image: NonZeroU32::new(height),
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..(padded_bytes_per_row * height) as BufferAddress);
+    let map = slice.map_async(MapMode::Read);
+    device.poll(Maintain::Wait);
+    pollster::block_on(map)?;
+
+    let mut data = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let padded = slice.get_mapped_range();
+        for row in padded.chunks(padded_bytes_per_row as usize) {
+            data.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    buffer.unmap();
+
+    Ok(data)
+}
+
+/// Read back an entity's texture as a tightly-packed RGBA8 image
+///
+/// BGRA textures are swizzled to RGBA. sRGB textures store encoded values,
+/// so their bytes are returned as-is and can be written straight to PNG.
+/// Blocks until all previously submitted work has completed.
+pub fn texture_to_rgba8(world: &World, entity: Entity) -> Result<Image, ReadbackError> {
+    let mut query = world
+        .query_one::<(&TextureDescriptorComponent, &TextureComponent)>(entity)
+        .map_err(|_| ReadbackError::NotReady)?;
+    let (texture_desc, texture) = query.get().ok_or(ReadbackError::NotReady)?;
+
+    let swizzle = rgba8_swizzle(texture_desc.format)?;
+
+    let texture = texture.get().ok_or(ReadbackError::NotReady)?;
+
+    let mut query = world.query::<&DeviceComponent>();
+    let (_, device) = query.into_iter().next().ok_or(ReadbackError::NotReady)?;
+
+    let mut query = world.query::<&QueueComponent>();
+    let (_, queue) = query.into_iter().next().ok_or(ReadbackError::NotReady)?;
+
+    let Extent3d { width, height, .. } = texture_desc.size;
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_bytes_per_row(width, texture_desc.format) * height) as BufferAddress,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut data = read_texture(
+        device,
+        queue,
+        texture,
+        texture_desc.size,
+        texture_desc.format,
+        &buffer,
+    )
+    .map_err(ReadbackError::Map)?;
+
+    if swizzle {
+        for texel in data.chunks_mut(4) {
+            texel.swap(0, 2);
+        }
+    }
+
+    Ok(Image::new(width, height, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use antigen_core::{ChangedFlag, Construct, With};
+    use wgpu::{
+        Color, LoadOp, Operations, RenderPassColorAttachment, RenderPassDescriptor,
+        TextureDescriptor, TextureDimension, TextureUsages, TextureViewDescriptor,
+    };
+
+    use crate::{
+        create_texture_views_system, create_textures_system, with_headless_backend, TextureBundle,
+        TextureViewBundle, TextureViewComponent,
+    };
+
+    fn texture_bundle(width: u32, height: u32, format: TextureFormat) -> TextureBundle {
+        TextureBundle::new(TextureDescriptor {
+            label: Some("Readback Test Texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        })
+    }
+
+    #[test]
+    fn hdr_formats_error() {
+        let mut world = World::new();
+        let entity = world.spawn(texture_bundle(4, 4, TextureFormat::Rgba16Float));
+
+        assert_eq!(
+            texture_to_rgba8(&world, entity),
+            Err(ReadbackError::HdrFormat(TextureFormat::Rgba16Float))
+        );
+    }
+
+    #[test]
+    fn missing_texture_is_not_ready() {
+        let mut world = World::new();
+        let entity = world.spawn((TextureDescriptorComponent::construct(TextureDescriptor {
+            label: None,
+            size: Extent3d::default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::COPY_SRC,
+        })
+        .with(ChangedFlag(false)),));
+
+        assert_eq!(
+            texture_to_rgba8(&world, entity),
+            Err(ReadbackError::NotReady)
+        );
+    }
+
+    #[test]
+    fn bgra_readback_is_swizzled_and_unpadded() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            world.spawn(backend);
+
+            // 10 texels * 4 bytes per row is padded to 256 bytes per row during the copy
+            let mut builder = hecs::EntityBuilder::new();
+            builder.add_bundle(texture_bundle(10, 3, TextureFormat::Bgra8UnormSrgb));
+            builder.add_bundle(TextureViewBundle::new(TextureViewDescriptor::default()));
+            let entity = world.spawn(builder.build());
+
+            create_textures_system(&mut world);
+            create_texture_views_system(&mut world);
+
+            {
+                let mut query = world.query::<&DeviceComponent>();
+                let (_, device) = query.into_iter().next().unwrap();
+
+                let mut query = world.query::<&QueueComponent>();
+                let (_, queue) = query.into_iter().next().unwrap();
+
+                let view = world.get::<TextureViewComponent>(entity).unwrap();
+
+                let mut encoder =
+                    device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+                encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[RenderPassColorAttachment {
+                        view: view.get().unwrap(),
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Clear(Color::RED),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                queue.submit(Some(encoder.finish()));
+            }
+
+            let image = texture_to_rgba8(&world, entity).unwrap();
+            assert_eq!(image, Image::filled(10, 3, [255, 0, 0, 255]));
+        });
+    }
+}
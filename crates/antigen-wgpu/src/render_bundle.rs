@@ -0,0 +1,263 @@
+use std::ops::Range;
+
+use antigen_core::{ChangedFlag, ChangedTrait, Construct, Indirect, Usage, With};
+use hecs::{Entity, EntityBuilder, World};
+use wgpu::{
+    BufferAddress, DynamicOffset, IndexFormat, RenderBundleDescriptor,
+    RenderBundleEncoderDescriptor,
+};
+
+use crate::{
+    BindGroupComponent, BufferComponent, DeviceComponent, RenderBundleComponent,
+    RenderBundleEncoderDescriptorComponent, RenderPassBindGroupDesc, RenderPassIndexBufferDesc,
+    RenderPassVertexBufferDesc, RenderPipelineComponent,
+};
+
+pub enum RenderBundleTag {}
+
+pub type RenderBundlePipelineComponent =
+    Usage<RenderBundleTag, Indirect<&'static RenderPipelineComponent>>;
+pub type RenderBundleVertexBuffersComponent =
+    Usage<RenderBundleTag, Vec<(Indirect<&'static BufferComponent>, Range<BufferAddress>)>>;
+pub type RenderBundleIndexBufferComponent = Usage<
+    RenderBundleTag,
+    Option<(
+        Indirect<&'static BufferComponent>,
+        Range<BufferAddress>,
+        IndexFormat,
+    )>,
+>;
+pub type RenderBundleBindGroupsComponent =
+    Usage<RenderBundleTag, Vec<(Indirect<&'static BindGroupComponent>, Vec<DynamicOffset>)>>;
+
+pub type RenderBundleDrawComponent = Usage<RenderBundleTag, (Range<u32>, Range<u32>)>;
+pub type RenderBundleDrawIndexedComponent =
+    Usage<RenderBundleTag, (Range<u32>, i32, Range<u32>)>;
+
+pub enum RenderBundleBundle {}
+
+impl RenderBundleBundle {
+    fn builder_impl(
+        builder: &mut EntityBuilder,
+        descriptor: RenderBundleEncoderDescriptor<'static>,
+        pipeline: Entity,
+        vertex_buffers: Vec<(Entity, Range<BufferAddress>)>,
+        index_buffer: Option<(Entity, Range<BufferAddress>, IndexFormat)>,
+        bind_groups: Vec<(Entity, Vec<DynamicOffset>)>,
+    ) {
+        let descriptor =
+            RenderBundleEncoderDescriptorComponent::construct(descriptor).with(ChangedFlag(true));
+        builder.add(descriptor);
+
+        builder.add(RenderBundleComponent::default());
+
+        let pipeline = RenderBundlePipelineComponent::construct(Indirect::construct(pipeline));
+        builder.add(pipeline);
+
+        let vertex_buffers = RenderBundleVertexBuffersComponent::construct(
+            vertex_buffers
+                .into_iter()
+                .map(|(entity, range)| (Indirect::construct(entity), range))
+                .collect(),
+        );
+        builder.add(vertex_buffers);
+
+        let index_buffer = RenderBundleIndexBufferComponent::construct(
+            index_buffer.map(|(entity, range, format)| (Indirect::construct(entity), range, format)),
+        );
+        builder.add(index_buffer);
+
+        let bind_groups = RenderBundleBindGroupsComponent::construct(
+            bind_groups
+                .into_iter()
+                .map(|(entity, offsets)| (Indirect::construct(entity), offsets))
+                .collect(),
+        );
+        builder.add(bind_groups);
+    }
+
+    pub fn draw(
+        descriptor: RenderBundleEncoderDescriptor<'static>,
+        pipeline: Entity,
+        vertex_buffers: Vec<RenderPassVertexBufferDesc>,
+        index_buffer: Option<RenderPassIndexBufferDesc>,
+        bind_groups: Vec<RenderPassBindGroupDesc>,
+        draw: (Range<u32>, Range<u32>),
+    ) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
+
+        Self::builder_impl(
+            &mut builder,
+            descriptor,
+            pipeline,
+            vertex_buffers.into_iter().map(Into::into).collect(),
+            index_buffer.map(Into::into),
+            bind_groups.into_iter().map(Into::into).collect(),
+        );
+
+        builder.add(RenderBundleDrawComponent::construct(draw));
+
+        builder
+    }
+
+    pub fn draw_indexed(
+        descriptor: RenderBundleEncoderDescriptor<'static>,
+        pipeline: Entity,
+        vertex_buffers: Vec<RenderPassVertexBufferDesc>,
+        index_buffer: Option<RenderPassIndexBufferDesc>,
+        bind_groups: Vec<RenderPassBindGroupDesc>,
+        draw_indexed: (Range<u32>, i32, Range<u32>),
+    ) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
+
+        Self::builder_impl(
+            &mut builder,
+            descriptor,
+            pipeline,
+            vertex_buffers.into_iter().map(Into::into).collect(),
+            index_buffer.map(Into::into),
+            bind_groups.into_iter().map(Into::into).collect(),
+        );
+
+        builder.add(RenderBundleDrawIndexedComponent::construct(draw_indexed));
+
+        builder
+    }
+}
+
+#[derive(hecs::Query)]
+pub struct RenderBundleQuery<'a> {
+    descriptor: &'a RenderBundleEncoderDescriptorComponent<'static>,
+    render_bundle: &'a mut RenderBundleComponent,
+    pipeline: &'a RenderBundlePipelineComponent,
+    vertex_buffers: &'a RenderBundleVertexBuffersComponent,
+    index_buffer: &'a RenderBundleIndexBufferComponent,
+    bind_groups: &'a RenderBundleBindGroupsComponent,
+    draw: Option<&'a RenderBundleDrawComponent>,
+    draw_indexed: Option<&'a RenderBundleDrawIndexedComponent>,
+}
+
+/// Record pending render bundles, re-recording them if their descriptor's Changed flag is set
+///
+/// Bundles are recorded once and executed as-is on subsequent frames,
+/// so recreating a referenced buffer or bind group requires flagging the descriptor.
+/// Bundles are left pending until their pipeline, buffers and bind groups exist and are ready
+pub fn create_render_bundles_system(world: &mut World) {
+    let mut query = world.query::<&DeviceComponent>();
+    let (_, device) = if let Some(components) = query.iter().next() {
+        components
+    } else {
+        return;
+    };
+
+    let mut query = world.query::<RenderBundleQuery>();
+    for (entity, bundle) in query.into_iter() {
+        if !bundle.render_bundle.is_pending() && !bundle.descriptor.get_changed() {
+            continue;
+        }
+
+        let pipeline =
+            if let Ok(pipeline) = world.get::<RenderPipelineComponent>(bundle.pipeline.entity()) {
+                pipeline
+            } else {
+                continue;
+            };
+        let pipeline = if let Some(pipeline) = pipeline.get() {
+            pipeline
+        } else {
+            continue;
+        };
+
+        let vertex_buffers = bundle
+            .vertex_buffers
+            .iter()
+            .map(|(buffer, range)| {
+                let buffer = world.get::<BufferComponent>(buffer.entity()).ok()?;
+                Some(((*buffer).clone(), range.clone()))
+            })
+            .collect::<Option<Vec<_>>>();
+        let vertex_buffers = if let Some(vertex_buffers) = vertex_buffers {
+            vertex_buffers
+        } else {
+            continue;
+        };
+
+        let index_buffer = bundle.index_buffer.as_ref().map(|(buffer, range, format)| {
+            let buffer = world.get::<BufferComponent>(buffer.entity()).ok()?;
+            Some(((*buffer).clone(), range.clone(), *format))
+        });
+        let index_buffer = match index_buffer {
+            Some(None) => continue,
+            index_buffer => index_buffer.flatten(),
+        };
+
+        let bind_groups = bundle
+            .bind_groups
+            .iter()
+            .map(|(bind_group, offsets)| {
+                let bind_group = world.get::<BindGroupComponent>(bind_group.entity()).ok()?;
+                Some((bind_group, offsets))
+            })
+            .collect::<Option<Vec<_>>>();
+        let bind_groups = if let Some(bind_groups) = bind_groups {
+            bind_groups
+        } else {
+            continue;
+        };
+
+        let vertex_locks = vertex_buffers
+            .iter()
+            .map(|(buffer, _)| buffer.read())
+            .collect::<Vec<_>>();
+        let index_lock = index_buffer.as_ref().map(|(buffer, _, _)| buffer.read());
+
+        let ready = vertex_locks.iter().all(|lock| lock.get().is_some())
+            && index_lock.iter().all(|lock| lock.get().is_some())
+            && bind_groups
+                .iter()
+                .all(|(bind_group, _)| bind_group.get().is_some());
+
+        if !ready {
+            continue;
+        }
+
+        let mut encoder = device.create_render_bundle_encoder(bundle.descriptor);
+
+        encoder.set_pipeline(pipeline);
+
+        for (i, ((_, range), lock)) in vertex_buffers.iter().zip(vertex_locks.iter()).enumerate() {
+            encoder.set_vertex_buffer(i as u32, lock.get().unwrap().slice(range.clone()));
+        }
+
+        if let (Some((_, range, format)), Some(lock)) = (&index_buffer, &index_lock) {
+            encoder.set_index_buffer(lock.get().unwrap().slice(range.clone()), *format);
+        }
+
+        for (i, (bind_group, offsets)) in bind_groups.iter().enumerate() {
+            encoder.set_bind_group(i as u32, bind_group.get().unwrap(), offsets);
+        }
+
+        if let Some(draw) = bundle.draw {
+            let (vertices, instances) = &**draw;
+            encoder.draw(vertices.clone(), instances.clone());
+        }
+
+        if let Some(draw_indexed) = bundle.draw_indexed {
+            let (indices, base_vertex, instances) = &**draw_indexed;
+            encoder.draw_indexed(indices.clone(), *base_vertex, instances.clone());
+        }
+
+        bundle
+            .render_bundle
+            .set_ready_with(encoder.finish(&RenderBundleDescriptor {
+                label: bundle.descriptor.label,
+            }));
+
+        bundle.descriptor.set_changed(false);
+
+        println!(
+            "Recorded render bundle for entity {:?} with label {:?}",
+            entity, bundle.descriptor.label
+        );
+    }
+}
@@ -1,15 +1,20 @@
-use std::ops::Range;
+use std::{collections::BTreeSet, ops::Range, sync::Mutex};
 
-use antigen_core::{Construct, Indirect, Usage};
-use hecs::{Entity, EntityBuilder, World};
+use antigen_core::{
+    Changed, ChangedFlag, ChangedTrait, Construct, Indirect, LazyComponent, Usage, With,
+};
+use hecs::{Entity, EntityBuilder, Ref, World};
+use parking_lot::RwLockReadGuard;
 use wgpu::{
-    BufferAddress, Color, DynamicOffset, IndexFormat, Operations, RenderPassColorAttachment,
-    RenderPassDepthStencilAttachment, RenderPassDescriptor, ShaderStages,
+    Buffer, BufferAddress, Color, DynamicOffset, Features, IndexFormat, LoadOp, Operations,
+    RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor,
+    ShaderStages,
 };
 
 use crate::{
-    BindGroupComponent, BufferComponent, CommandEncoderComponent, PassOrderComponent,
-    PushConstantQuery, RenderPipelineComponent, TextureViewComponent,
+    BindGroupComponent, BufferComponent, CommandEncoderComponent, DeviceComponent,
+    PassOrderComponent, PushConstantComponent, PushConstantOffset, PushConstantQuery,
+    QuerySetComponent, RenderBundleComponent, RenderPipelineComponent, TextureViewComponent,
 };
 
 pub enum RenderPassTag {}
@@ -45,13 +50,22 @@ pub type RenderPassIndexBufferComponent = Usage<
 >;
 pub type RenderPassBindGroupsComponent =
     Usage<RenderPassTag, Vec<(Indirect<&'static BindGroupComponent>, Vec<DynamicOffset>)>>;
+
+// Data-driven dynamic offsets, keyed by bind group index
+pub enum BindGroupOffsets {}
+pub type RenderPassBindGroupOffsetsComponent =
+    Usage<(RenderPassTag, BindGroupOffsets), Changed<Vec<(usize, Vec<DynamicOffset>)>>>;
+
 pub type RenderPassPushConstantsComponent =
     Usage<RenderPassTag, Vec<(Indirect<PushConstantQuery<'static>>, ShaderStages)>>;
 pub type RenderPassEncoderComponent =
     Usage<RenderPassTag, Indirect<&'static mut CommandEncoderComponent>>;
 
-pub type RenderPassViewportComponent = Usage<RenderPassTag, (f32, f32, f32, f32, f32, f32)>;
-pub type RenderPassScissorRectComponent = Usage<RenderPassTag, (u32, u32, u32, u32)>;
+// Viewport and scissor rect can be changed between frames via
+// set_render_pass_viewport and set_render_pass_scissor_rect
+pub type RenderPassViewportComponent =
+    Usage<RenderPassTag, Changed<(f32, f32, f32, f32, f32, f32)>>;
+pub type RenderPassScissorRectComponent = Usage<RenderPassTag, Changed<(u32, u32, u32, u32)>>;
 
 pub type RenderPassBlendConstantComponent = Usage<RenderPassTag, Color>;
 pub type RenderPassStencilReferenceComponent = Usage<RenderPassTag, u32>;
@@ -69,24 +83,239 @@ pub type RenderPassDrawIndexedIndirectComponent = Usage<
     (Indirect<&'static BufferComponent>, BufferAddress),
 >;
 
+/// Number of draws issued by a multi-draw indirect render pass
+///
+/// Multi-draw is an optional wgpu feature that must be requested when creating the device;
+/// passes whose required features are missing are skipped with a warning
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MultiDrawCount {
+    /// Issue a fixed number of draws. Requires `Features::MULTI_DRAW_INDIRECT`
+    Count(u32),
+    /// Read the number of draws from a u32 at `offset` in a buffer entity, clamped to `max_count`.
+    /// Requires `Features::MULTI_DRAW_INDIRECT_COUNT`
+    Buffer {
+        buffer: Entity,
+        offset: BufferAddress,
+        max_count: u32,
+    },
+}
+
+impl MultiDrawCount {
+    pub fn features(&self) -> Features {
+        match self {
+            MultiDrawCount::Count(_) => Features::MULTI_DRAW_INDIRECT,
+            MultiDrawCount::Buffer { .. } => Features::MULTI_DRAW_INDIRECT_COUNT,
+        }
+    }
+}
+
+pub enum MultiDrawIndirect {}
+pub enum MultiDrawIndexedIndirect {}
+
+pub type RenderPassMultiDrawIndirectComponent = Usage<
+    (RenderPassTag, MultiDrawIndirect),
+    (Indirect<&'static BufferComponent>, BufferAddress, MultiDrawCount),
+>;
+pub type RenderPassMultiDrawIndexedIndirectComponent = Usage<
+    (RenderPassTag, MultiDrawIndexedIndirect),
+    (Indirect<&'static BufferComponent>, BufferAddress, MultiDrawCount),
+>;
+
+pub enum ExecuteBundles {}
+
+pub type RenderPassExecuteBundlesComponent = Usage<
+    (RenderPassTag, ExecuteBundles),
+    Vec<Indirect<&'static RenderBundleComponent>>,
+>;
+
+pub enum Timestamp {}
+
+/// Query set entity, and the query indices to write timestamps into before and after the pass
+///
+/// Timestamps require `Features::TIMESTAMP_QUERY`, and are skipped if the device is missing it.
+/// Timestamped passes are recorded into their own wgpu render pass, so they can be timed alone
+pub type RenderPassTimestampComponent =
+    Usage<(RenderPassTag, Timestamp), (Indirect<&'static QuerySetComponent>, u32, u32)>;
+
+/// Color attachment of a render pass bundle, with texture view entities in place of references
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderPassColorAttachmentDesc {
+    pub view: Entity,
+    pub resolve_target: Option<Entity>,
+    pub ops: Operations<Color>,
+}
+
+impl From<RenderPassColorAttachmentDesc> for (Entity, Option<Entity>, Operations<Color>) {
+    fn from(desc: RenderPassColorAttachmentDesc) -> Self {
+        (desc.view, desc.resolve_target, desc.ops)
+    }
+}
+
+/// Depth-stencil attachment of a render pass bundle
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderPassDepthStencilAttachmentDesc {
+    pub view: Entity,
+    pub depth_ops: Option<Operations<f32>>,
+    pub stencil_ops: Option<Operations<u32>>,
+}
+
+impl From<RenderPassDepthStencilAttachmentDesc>
+    for (Entity, Option<Operations<f32>>, Option<Operations<u32>>)
+{
+    fn from(desc: RenderPassDepthStencilAttachmentDesc) -> Self {
+        (desc.view, desc.depth_ops, desc.stencil_ops)
+    }
+}
+
+/// Vertex buffer slice, bound to the slot matching its position in the list
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderPassVertexBufferDesc {
+    pub buffer: Entity,
+    pub range: Range<BufferAddress>,
+}
+
+impl From<RenderPassVertexBufferDesc> for (Entity, Range<BufferAddress>) {
+    fn from(desc: RenderPassVertexBufferDesc) -> Self {
+        (desc.buffer, desc.range)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderPassIndexBufferDesc {
+    pub buffer: Entity,
+    pub range: Range<BufferAddress>,
+    pub format: IndexFormat,
+}
+
+impl From<RenderPassIndexBufferDesc> for (Entity, Range<BufferAddress>, IndexFormat) {
+    fn from(desc: RenderPassIndexBufferDesc) -> Self {
+        (desc.buffer, desc.range, desc.format)
+    }
+}
+
+/// Bind group, bound to the index matching its position in the list
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderPassBindGroupDesc {
+    pub bind_group: Entity,
+    pub offsets: Vec<DynamicOffset>,
+}
+
+impl From<RenderPassBindGroupDesc> for (Entity, Vec<DynamicOffset>) {
+    fn from(desc: RenderPassBindGroupDesc) -> Self {
+        (desc.bind_group, desc.offsets)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderPassPushConstantDesc {
+    pub push_constant: Entity,
+    pub stages: ShaderStages,
+}
+
+impl From<RenderPassPushConstantDesc> for (Entity, ShaderStages) {
+    fn from(desc: RenderPassPushConstantDesc) -> Self {
+        (desc.push_constant, desc.stages)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderPassViewportDesc {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+impl From<RenderPassViewportDesc> for (f32, f32, f32, f32, f32, f32) {
+    fn from(desc: RenderPassViewportDesc) -> Self {
+        (
+            desc.x,
+            desc.y,
+            desc.width,
+            desc.height,
+            desc.min_depth,
+            desc.max_depth,
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RenderPassScissorRectDesc {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl From<RenderPassScissorRectDesc> for (u32, u32, u32, u32) {
+    fn from(desc: RenderPassScissorRectDesc) -> Self {
+        (desc.x, desc.y, desc.width, desc.height)
+    }
+}
+
+/// Indirect draw arguments read from `offset` bytes into a buffer entity
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RenderPassIndirectDesc {
+    pub buffer: Entity,
+    pub offset: BufferAddress,
+}
+
+impl From<RenderPassIndirectDesc> for (Entity, BufferAddress) {
+    fn from(desc: RenderPassIndirectDesc) -> Self {
+        (desc.buffer, desc.offset)
+    }
+}
+
+/// Consecutive indirect draw arguments read from `offset` bytes into a buffer entity
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RenderPassMultiDrawIndirectDesc {
+    pub buffer: Entity,
+    pub offset: BufferAddress,
+    pub count: MultiDrawCount,
+}
+
+impl From<RenderPassMultiDrawIndirectDesc> for (Entity, BufferAddress, MultiDrawCount) {
+    fn from(desc: RenderPassMultiDrawIndirectDesc) -> Self {
+        (desc.buffer, desc.offset, desc.count)
+    }
+}
+
+// Per-view background, resolved into the clear color of attachments targeting its entity
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BackgroundColor {
+    Solid(Color),
+    // Cleared to its midpoint, leaving the full gradient to a background pass
+    Gradient { top: Color, bottom: Color },
+}
+
+impl BackgroundColor {
+    pub fn clear_color(&self) -> Color {
+        match self {
+            BackgroundColor::Solid(color) => *color,
+            BackgroundColor::Gradient { top, bottom } => Color {
+                r: (top.r + bottom.r) * 0.5,
+                g: (top.g + bottom.g) * 0.5,
+                b: (top.b + bottom.b) * 0.5,
+                a: (top.a + bottom.a) * 0.5,
+            },
+        }
+    }
+}
+
+pub enum Background {}
+pub type BackgroundComponent = Usage<Background, BackgroundColor>;
+
 pub enum RenderPassBundle {}
 
 impl RenderPassBundle {
-    fn builder_impl(
+    fn attachments_impl(
         builder: &mut EntityBuilder,
         order: usize,
         label: Option<String>,
         color_attachments: Vec<(Entity, Option<Entity>, Operations<Color>)>,
         depth_attachment: Option<(Entity, Option<Operations<f32>>, Option<Operations<u32>>)>,
-        pipeline: Entity,
-        vertex_buffers: Vec<(Entity, Range<BufferAddress>)>,
-        index_buffers: Option<(Entity, Range<BufferAddress>, IndexFormat)>,
-        bind_groups: Vec<(Entity, Vec<DynamicOffset>)>,
-        push_constants: Vec<(Entity, ShaderStages)>,
-        blend_constant: Option<Color>,
-        stencil_reference: Option<u32>,
-        viewport: Option<(f32, f32, f32, f32, f32, f32)>,
-        scissor_rect: Option<(u32, u32, u32, u32)>,
         encoder: Entity,
     ) {
         builder.add(PassOrderComponent::construct(order));
@@ -112,6 +341,36 @@ impl RenderPassBundle {
         ));
         builder.add(depth_attachment);
 
+        let encoder = RenderPassEncoderComponent::construct(encoder);
+        builder.add(encoder);
+    }
+
+    fn builder_impl(
+        builder: &mut EntityBuilder,
+        order: usize,
+        label: Option<String>,
+        color_attachments: Vec<(Entity, Option<Entity>, Operations<Color>)>,
+        depth_attachment: Option<(Entity, Option<Operations<f32>>, Option<Operations<u32>>)>,
+        pipeline: Entity,
+        vertex_buffers: Vec<(Entity, Range<BufferAddress>)>,
+        index_buffers: Option<(Entity, Range<BufferAddress>, IndexFormat)>,
+        bind_groups: Vec<(Entity, Vec<DynamicOffset>)>,
+        push_constants: Vec<(Entity, ShaderStages)>,
+        blend_constant: Option<Color>,
+        stencil_reference: Option<u32>,
+        viewport: Option<(f32, f32, f32, f32, f32, f32)>,
+        scissor_rect: Option<(u32, u32, u32, u32)>,
+        encoder: Entity,
+    ) {
+        Self::attachments_impl(
+            builder,
+            order,
+            label,
+            color_attachments,
+            depth_attachment,
+            encoder,
+        );
+
         let pipeline = RenderPassPipelineComponent::construct(Indirect::construct(pipeline));
         builder.add(pipeline);
 
@@ -162,25 +421,22 @@ impl RenderPassBundle {
         if let Some(scissor_rect) = scissor_rect {
             builder.add(RenderPassScissorRectComponent::construct(scissor_rect));
         }
-
-        let encoder = RenderPassEncoderComponent::construct(encoder);
-        builder.add(encoder);
     }
 
     pub fn draw(
         order: usize,
         label: Option<String>,
-        color_attachments: Vec<(Entity, Option<Entity>, Operations<Color>)>,
-        depth_attachment: Option<(Entity, Option<Operations<f32>>, Option<Operations<u32>>)>,
+        color_attachments: Vec<RenderPassColorAttachmentDesc>,
+        depth_attachment: Option<RenderPassDepthStencilAttachmentDesc>,
         pipeline: Entity,
-        vertex_buffers: Vec<(Entity, Range<BufferAddress>)>,
-        index_buffers: Option<(Entity, Range<BufferAddress>, IndexFormat)>,
-        bind_groups: Vec<(Entity, Vec<DynamicOffset>)>,
-        push_constants: Vec<(Entity, ShaderStages)>,
+        vertex_buffers: Vec<RenderPassVertexBufferDesc>,
+        index_buffers: Option<RenderPassIndexBufferDesc>,
+        bind_groups: Vec<RenderPassBindGroupDesc>,
+        push_constants: Vec<RenderPassPushConstantDesc>,
         blend_constant: Option<Color>,
         stencil_reference: Option<u32>,
-        viewport: Option<(f32, f32, f32, f32, f32, f32)>,
-        scissor_rect: Option<(u32, u32, u32, u32)>,
+        viewport: Option<RenderPassViewportDesc>,
+        scissor_rect: Option<RenderPassScissorRectDesc>,
         draw: (Range<u32>, Range<u32>),
         encoder: Entity,
     ) -> EntityBuilder {
@@ -190,17 +446,17 @@ impl RenderPassBundle {
             &mut builder,
             order,
             label,
-            color_attachments,
-            depth_attachment,
+            color_attachments.into_iter().map(Into::into).collect(),
+            depth_attachment.map(Into::into),
             pipeline,
-            vertex_buffers,
-            index_buffers,
-            bind_groups,
-            push_constants,
+            vertex_buffers.into_iter().map(Into::into).collect(),
+            index_buffers.map(Into::into),
+            bind_groups.into_iter().map(Into::into).collect(),
+            push_constants.into_iter().map(Into::into).collect(),
             blend_constant,
             stencil_reference,
-            viewport,
-            scissor_rect,
+            viewport.map(Into::into),
+            scissor_rect.map(Into::into),
             encoder,
         );
 
@@ -213,17 +469,17 @@ impl RenderPassBundle {
     pub fn draw_indexed(
         order: usize,
         label: Option<String>,
-        color_attachments: Vec<(Entity, Option<Entity>, Operations<Color>)>,
-        depth_attachment: Option<(Entity, Option<Operations<f32>>, Option<Operations<u32>>)>,
+        color_attachments: Vec<RenderPassColorAttachmentDesc>,
+        depth_attachment: Option<RenderPassDepthStencilAttachmentDesc>,
         pipeline: Entity,
-        vertex_buffers: Vec<(Entity, Range<BufferAddress>)>,
-        index_buffers: Option<(Entity, Range<BufferAddress>, IndexFormat)>,
-        bind_groups: Vec<(Entity, Vec<DynamicOffset>)>,
-        push_constants: Vec<(Entity, ShaderStages)>,
+        vertex_buffers: Vec<RenderPassVertexBufferDesc>,
+        index_buffers: Option<RenderPassIndexBufferDesc>,
+        bind_groups: Vec<RenderPassBindGroupDesc>,
+        push_constants: Vec<RenderPassPushConstantDesc>,
         blend_constant: Option<Color>,
         stencil_reference: Option<u32>,
-        viewport: Option<(f32, f32, f32, f32, f32, f32)>,
-        scissor_rect: Option<(u32, u32, u32, u32)>,
+        viewport: Option<RenderPassViewportDesc>,
+        scissor_rect: Option<RenderPassScissorRectDesc>,
         draw_indexed: (Range<u32>, i32, Range<u32>),
         encoder: Entity,
     ) -> EntityBuilder {
@@ -233,17 +489,17 @@ impl RenderPassBundle {
             &mut builder,
             order,
             label,
-            color_attachments,
-            depth_attachment,
+            color_attachments.into_iter().map(Into::into).collect(),
+            depth_attachment.map(Into::into),
             pipeline,
-            vertex_buffers,
-            index_buffers,
-            bind_groups,
-            push_constants,
+            vertex_buffers.into_iter().map(Into::into).collect(),
+            index_buffers.map(Into::into),
+            bind_groups.into_iter().map(Into::into).collect(),
+            push_constants.into_iter().map(Into::into).collect(),
             blend_constant,
             stencil_reference,
-            viewport,
-            scissor_rect,
+            viewport.map(Into::into),
+            scissor_rect.map(Into::into),
             encoder,
         );
 
@@ -256,18 +512,18 @@ impl RenderPassBundle {
     pub fn draw_indirect(
         order: usize,
         label: Option<String>,
-        color_attachments: Vec<(Entity, Option<Entity>, Operations<Color>)>,
-        depth_attachment: Option<(Entity, Option<Operations<f32>>, Option<Operations<u32>>)>,
+        color_attachments: Vec<RenderPassColorAttachmentDesc>,
+        depth_attachment: Option<RenderPassDepthStencilAttachmentDesc>,
         pipeline: Entity,
-        vertex_buffers: Vec<(Entity, Range<BufferAddress>)>,
-        index_buffers: Option<(Entity, Range<BufferAddress>, IndexFormat)>,
-        bind_groups: Vec<(Entity, Vec<DynamicOffset>)>,
-        push_constants: Vec<(Entity, ShaderStages)>,
+        vertex_buffers: Vec<RenderPassVertexBufferDesc>,
+        index_buffers: Option<RenderPassIndexBufferDesc>,
+        bind_groups: Vec<RenderPassBindGroupDesc>,
+        push_constants: Vec<RenderPassPushConstantDesc>,
         blend_constant: Option<Color>,
         stencil_reference: Option<u32>,
-        viewport: Option<(f32, f32, f32, f32, f32, f32)>,
-        scissor_rect: Option<(u32, u32, u32, u32)>,
-        draw_indirect: (Entity, BufferAddress),
+        viewport: Option<RenderPassViewportDesc>,
+        scissor_rect: Option<RenderPassScissorRectDesc>,
+        draw_indirect: RenderPassIndirectDesc,
         encoder: Entity,
     ) -> EntityBuilder {
         let mut builder = EntityBuilder::new();
@@ -276,21 +532,21 @@ impl RenderPassBundle {
             &mut builder,
             order,
             label,
-            color_attachments,
-            depth_attachment,
+            color_attachments.into_iter().map(Into::into).collect(),
+            depth_attachment.map(Into::into),
             pipeline,
-            vertex_buffers,
-            index_buffers,
-            bind_groups,
-            push_constants,
+            vertex_buffers.into_iter().map(Into::into).collect(),
+            index_buffers.map(Into::into),
+            bind_groups.into_iter().map(Into::into).collect(),
+            push_constants.into_iter().map(Into::into).collect(),
             blend_constant,
             stencil_reference,
-            viewport,
-            scissor_rect,
+            viewport.map(Into::into),
+            scissor_rect.map(Into::into),
             encoder,
         );
 
-        let (indirect_entity, indirect_offset) = draw_indirect;
+        let (indirect_entity, indirect_offset) = draw_indirect.into();
         let indirect = Indirect::construct(indirect_entity);
         let draw = RenderPassDrawIndirectComponent::construct((indirect, indirect_offset));
         builder.add(draw);
@@ -301,18 +557,18 @@ impl RenderPassBundle {
     pub fn draw_indexed_indirect(
         order: usize,
         label: Option<String>,
-        color_attachments: Vec<(Entity, Option<Entity>, Operations<Color>)>,
-        depth_attachment: Option<(Entity, Option<Operations<f32>>, Option<Operations<u32>>)>,
+        color_attachments: Vec<RenderPassColorAttachmentDesc>,
+        depth_attachment: Option<RenderPassDepthStencilAttachmentDesc>,
         pipeline: Entity,
-        vertex_buffers: Vec<(Entity, Range<BufferAddress>)>,
-        index_buffers: Option<(Entity, Range<BufferAddress>, IndexFormat)>,
-        bind_groups: Vec<(Entity, Vec<DynamicOffset>)>,
-        push_constants: Vec<(Entity, ShaderStages)>,
+        vertex_buffers: Vec<RenderPassVertexBufferDesc>,
+        index_buffers: Option<RenderPassIndexBufferDesc>,
+        bind_groups: Vec<RenderPassBindGroupDesc>,
+        push_constants: Vec<RenderPassPushConstantDesc>,
         blend_constant: Option<Color>,
         stencil_reference: Option<u32>,
-        viewport: Option<(f32, f32, f32, f32, f32, f32)>,
-        scissor_rect: Option<(u32, u32, u32, u32)>,
-        draw_indexed_indirect: (Entity, BufferAddress),
+        viewport: Option<RenderPassViewportDesc>,
+        scissor_rect: Option<RenderPassScissorRectDesc>,
+        draw_indexed_indirect: RenderPassIndirectDesc,
         encoder: Entity,
     ) -> EntityBuilder {
         let mut builder = EntityBuilder::new();
@@ -321,133 +577,1020 @@ impl RenderPassBundle {
             &mut builder,
             order,
             label,
-            color_attachments,
-            depth_attachment,
+            color_attachments.into_iter().map(Into::into).collect(),
+            depth_attachment.map(Into::into),
             pipeline,
-            vertex_buffers,
-            index_buffers,
-            bind_groups,
-            push_constants,
+            vertex_buffers.into_iter().map(Into::into).collect(),
+            index_buffers.map(Into::into),
+            bind_groups.into_iter().map(Into::into).collect(),
+            push_constants.into_iter().map(Into::into).collect(),
             blend_constant,
             stencil_reference,
-            viewport,
-            scissor_rect,
+            viewport.map(Into::into),
+            scissor_rect.map(Into::into),
             encoder,
         );
 
-        let (indirect_entity, indirect_offset) = draw_indexed_indirect;
+        let (indirect_entity, indirect_offset) = draw_indexed_indirect.into();
         let indirect = Indirect::construct(indirect_entity);
         let draw = RenderPassDrawIndexedIndirectComponent::construct((indirect, indirect_offset));
         builder.add(draw);
 
         builder
     }
-}
 
-#[derive(hecs::Query)]
-pub struct RenderPassQuery<'a> {
-    order: &'a PassOrderComponent,
-    label: &'a RenderPassLabelComponent,
-    color_attachments: &'a RenderPassColorAttachmentsComponent,
-    depth_attachment: &'a RenderPassDepthAttachmentComponent,
-    pipeline: &'a RenderPassPipelineComponent,
-    vertex_buffers: &'a RenderPassVertexBuffersComponent,
-    index_buffer: &'a RenderPassIndexBufferComponent,
-    bind_groups: &'a RenderPassBindGroupsComponent,
-    push_constants: Option<&'a RenderPassPushConstantsComponent>,
-    blend_constant: Option<&'a RenderPassBlendConstantComponent>,
-    stencil_reference: Option<&'a RenderPassStencilReferenceComponent>,
-    viewport: Option<&'a RenderPassViewportComponent>,
-    scissor_rect: Option<&'a RenderPassScissorRectComponent>,
-    encoder: &'a RenderPassEncoderComponent,
-}
+    /// Issue several indirect draws from consecutive arguments in a single command
+    ///
+    /// See [`MultiDrawCount`] for the wgpu features each draw count requires
+    pub fn multi_draw_indirect(
+        order: usize,
+        label: Option<String>,
+        color_attachments: Vec<RenderPassColorAttachmentDesc>,
+        depth_attachment: Option<RenderPassDepthStencilAttachmentDesc>,
+        pipeline: Entity,
+        vertex_buffers: Vec<RenderPassVertexBufferDesc>,
+        index_buffers: Option<RenderPassIndexBufferDesc>,
+        bind_groups: Vec<RenderPassBindGroupDesc>,
+        push_constants: Vec<RenderPassPushConstantDesc>,
+        blend_constant: Option<Color>,
+        stencil_reference: Option<u32>,
+        viewport: Option<RenderPassViewportDesc>,
+        scissor_rect: Option<RenderPassScissorRectDesc>,
+        multi_draw_indirect: RenderPassMultiDrawIndirectDesc,
+        encoder: Entity,
+    ) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
 
-pub fn draw_render_passes_system(world: &mut World) -> Option<()> {
-    let mut query = world.query::<RenderPassQuery>();
-    let mut components = query.into_iter().collect::<Vec<_>>();
-    components.sort_unstable_by(|(_, lhs), (_, rhs)| lhs.order.cmp(rhs.order));
-
-    let mut components = components.into_iter().collect::<Vec<_>>();
-    components.sort_unstable_by(
-        |(_, RenderPassQuery { order: lhs, .. }), (_, RenderPassQuery { order: rhs, .. })| {
-            lhs.cmp(rhs)
-        },
-    );
-
-    for (
-        entity,
-        RenderPassQuery {
+        Self::builder_impl(
+            &mut builder,
+            order,
             label,
-            color_attachments,
-            depth_attachment,
+            color_attachments.into_iter().map(Into::into).collect(),
+            depth_attachment.map(Into::into),
             pipeline,
-            vertex_buffers,
-            index_buffer,
-            bind_groups,
-            push_constants,
+            vertex_buffers.into_iter().map(Into::into).collect(),
+            index_buffers.map(Into::into),
+            bind_groups.into_iter().map(Into::into).collect(),
+            push_constants.into_iter().map(Into::into).collect(),
             blend_constant,
             stencil_reference,
-            viewport,
-            scissor_rect,
+            viewport.map(Into::into),
+            scissor_rect.map(Into::into),
             encoder,
-            ..
-        },
-    ) in components.into_iter()
-    {
-        // Collect draw commands
-        let mut draw_query = world.query_one::<&RenderPassDrawComponent>(entity).ok();
-        let draw = draw_query.as_mut().map(|query| query.get()).flatten();
+        );
 
-        let mut draw_indexed_query = world
-            .query_one::<&RenderPassDrawIndexedComponent>(entity)
-            .ok();
-        let draw_indexed = draw_indexed_query
-            .as_mut()
-            .map(|query| query.get())
-            .flatten();
+        let (indirect_entity, indirect_offset, count) = multi_draw_indirect.into();
+        let indirect = Indirect::construct(indirect_entity);
+        let draw =
+            RenderPassMultiDrawIndirectComponent::construct((indirect, indirect_offset, count));
+        builder.add(draw);
 
-        let mut draw_indirect_query = world
-            .query_one::<&RenderPassDrawIndirectComponent>(entity)
-            .ok();
-        let draw_indirect = draw_indirect_query
-            .as_mut()
-            .map(|query| query.get())
-            .flatten();
+        builder
+    }
 
-        let mut draw_indexed_indirect_query = world
-            .query_one::<&RenderPassDrawIndexedIndirectComponent>(entity)
-            .ok();
-        let draw_indexed_indirect = draw_indexed_indirect_query
-            .as_mut()
-            .map(|query| query.get())
-            .flatten();
+    /// Issue several indexed indirect draws from consecutive arguments in a single command
+    ///
+    /// See [`MultiDrawCount`] for the wgpu features each draw count requires
+    pub fn multi_draw_indexed_indirect(
+        order: usize,
+        label: Option<String>,
+        color_attachments: Vec<RenderPassColorAttachmentDesc>,
+        depth_attachment: Option<RenderPassDepthStencilAttachmentDesc>,
+        pipeline: Entity,
+        vertex_buffers: Vec<RenderPassVertexBufferDesc>,
+        index_buffers: Option<RenderPassIndexBufferDesc>,
+        bind_groups: Vec<RenderPassBindGroupDesc>,
+        push_constants: Vec<RenderPassPushConstantDesc>,
+        blend_constant: Option<Color>,
+        stencil_reference: Option<u32>,
+        viewport: Option<RenderPassViewportDesc>,
+        scissor_rect: Option<RenderPassScissorRectDesc>,
+        multi_draw_indexed_indirect: RenderPassMultiDrawIndirectDesc,
+        encoder: Entity,
+    ) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
 
-        let mut query = encoder.get(world);
-        let encoder = query.get().unwrap().get_mut().unwrap();
+        Self::builder_impl(
+            &mut builder,
+            order,
+            label,
+            color_attachments.into_iter().map(Into::into).collect(),
+            depth_attachment.map(Into::into),
+            pipeline,
+            vertex_buffers.into_iter().map(Into::into).collect(),
+            index_buffers.map(Into::into),
+            bind_groups.into_iter().map(Into::into).collect(),
+            push_constants.into_iter().map(Into::into).collect(),
+            blend_constant,
+            stencil_reference,
+            viewport.map(Into::into),
+            scissor_rect.map(Into::into),
+            encoder,
+        );
 
-        // Collect label
-        let label = (**label).clone();
-        let label = label.as_deref();
+        let (indirect_entity, indirect_offset, count) = multi_draw_indexed_indirect.into();
+        let indirect = Indirect::construct(indirect_entity);
+        let draw = RenderPassMultiDrawIndexedIndirectComponent::construct((
+            indirect,
+            indirect_offset,
+            count,
+        ));
+        builder.add(draw);
 
-        // Collect color attachments
-        let mut color_queries = color_attachments
-            .iter()
-            .map(|(view, resolve_target, ops)| {
-                (
-                    view.get(world),
-                    resolve_target
-                        .as_ref()
-                        .map(|resolve_target| resolve_target.get(world)),
-                    ops,
-                )
-            })
-            .collect::<Vec<_>>();
+        builder
+    }
 
-        let mut color = vec![];
-        for (view, resolve_target, ops) in color_queries.iter_mut() {
-            let view = view.get().unwrap().get()?;
-            let resolve_target = resolve_target
-                .as_mut()
+    /// Execute pre-recorded render bundles, as created by create_render_bundles_system
+    ///
+    /// Bundles carry their own pipeline and bindings, so none are bound by the pass itself.
+    /// Executing bundles resets pass state, so later passes in the same batch rebind theirs
+    pub fn execute_bundles(
+        order: usize,
+        label: Option<String>,
+        color_attachments: Vec<RenderPassColorAttachmentDesc>,
+        depth_attachment: Option<RenderPassDepthStencilAttachmentDesc>,
+        bundles: Vec<Entity>,
+        encoder: Entity,
+    ) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
+
+        Self::attachments_impl(
+            &mut builder,
+            order,
+            label,
+            color_attachments.into_iter().map(Into::into).collect(),
+            depth_attachment.map(Into::into),
+            encoder,
+        );
+
+        builder.add(RenderPassVertexBuffersComponent::construct(vec![]));
+        builder.add(RenderPassIndexBufferComponent::construct(None));
+        builder.add(RenderPassBindGroupsComponent::construct(vec![]));
+
+        let bundles = RenderPassExecuteBundlesComponent::construct(
+            bundles.into_iter().map(Indirect::construct).collect(),
+        );
+        builder.add(bundles);
+
+        builder
+    }
+}
+
+/// Chained alternative to the positional RenderPassBundle constructors
+///
+/// Produces the same EntityBuilder, with optional state left unset unless provided.
+/// Draw methods consume the builder, and panic if no pipeline has been set
+pub struct RenderPassBuilder {
+    order: usize,
+    label: Option<String>,
+    color_attachments: Vec<RenderPassColorAttachmentDesc>,
+    depth_attachment: Option<RenderPassDepthStencilAttachmentDesc>,
+    pipeline: Option<Entity>,
+    vertex_buffers: Vec<RenderPassVertexBufferDesc>,
+    index_buffer: Option<RenderPassIndexBufferDesc>,
+    bind_groups: Vec<RenderPassBindGroupDesc>,
+    push_constants: Vec<RenderPassPushConstantDesc>,
+    blend_constant: Option<Color>,
+    stencil_reference: Option<u32>,
+    viewport: Option<RenderPassViewportDesc>,
+    scissor_rect: Option<RenderPassScissorRectDesc>,
+    timestamp: Option<(Entity, u32, u32)>,
+    encoder: Entity,
+}
+
+impl RenderPassBuilder {
+    pub fn new(order: usize, encoder: Entity) -> Self {
+        RenderPassBuilder {
+            order,
+            label: None,
+            color_attachments: vec![],
+            depth_attachment: None,
+            pipeline: None,
+            vertex_buffers: vec![],
+            index_buffer: None,
+            bind_groups: vec![],
+            push_constants: vec![],
+            blend_constant: None,
+            stencil_reference: None,
+            viewport: None,
+            scissor_rect: None,
+            timestamp: None,
+            encoder,
+        }
+    }
+
+    pub fn label<S: Into<String>>(mut self, label: S) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Append a color attachment, in location order
+    pub fn color_attachment(
+        mut self,
+        view: Entity,
+        resolve_target: Option<Entity>,
+        ops: Operations<Color>,
+    ) -> Self {
+        self.color_attachments.push(RenderPassColorAttachmentDesc {
+            view,
+            resolve_target,
+            ops,
+        });
+        self
+    }
+
+    pub fn depth(
+        mut self,
+        view: Entity,
+        depth_ops: Option<Operations<f32>>,
+        stencil_ops: Option<Operations<u32>>,
+    ) -> Self {
+        self.depth_attachment = Some(RenderPassDepthStencilAttachmentDesc {
+            view,
+            depth_ops,
+            stencil_ops,
+        });
+        self
+    }
+
+    pub fn pipeline(mut self, pipeline: Entity) -> Self {
+        self.pipeline = Some(pipeline);
+        self
+    }
+
+    /// Append a vertex buffer, in slot order
+    pub fn vertex_buffer(mut self, buffer: Entity, range: Range<BufferAddress>) -> Self {
+        self.vertex_buffers.push(RenderPassVertexBufferDesc { buffer, range });
+        self
+    }
+
+    pub fn index_buffer(
+        mut self,
+        buffer: Entity,
+        range: Range<BufferAddress>,
+        format: IndexFormat,
+    ) -> Self {
+        self.index_buffer = Some(RenderPassIndexBufferDesc {
+            buffer,
+            range,
+            format,
+        });
+        self
+    }
+
+    /// Append a bind group, in group index order
+    pub fn bind_group(mut self, bind_group: Entity, offsets: Vec<DynamicOffset>) -> Self {
+        self.bind_groups.push(RenderPassBindGroupDesc { bind_group, offsets });
+        self
+    }
+
+    pub fn push_constant(mut self, push_constant: Entity, stages: ShaderStages) -> Self {
+        self.push_constants.push(RenderPassPushConstantDesc {
+            push_constant,
+            stages,
+        });
+        self
+    }
+
+    pub fn blend_constant(mut self, blend_constant: Color) -> Self {
+        self.blend_constant = Some(blend_constant);
+        self
+    }
+
+    pub fn stencil_reference(mut self, stencil_reference: u32) -> Self {
+        self.stencil_reference = Some(stencil_reference);
+        self
+    }
+
+    pub fn viewport(mut self, viewport: RenderPassViewportDesc) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    pub fn scissor_rect(mut self, scissor_rect: RenderPassScissorRectDesc) -> Self {
+        self.scissor_rect = Some(scissor_rect);
+        self
+    }
+
+    /// Write timestamps into `query_set` at `begin` and `end` around the pass
+    pub fn timestamp(mut self, query_set: Entity, begin: u32, end: u32) -> Self {
+        self.timestamp = Some((query_set, begin, end));
+        self
+    }
+
+    fn build_impl(self) -> EntityBuilder {
+        let pipeline = self.pipeline.unwrap_or_else(|| {
+            panic!(
+                "Render pass {:?} has no pipeline, call RenderPassBuilder::pipeline before drawing",
+                self.label
+            )
+        });
+
+        let mut builder = EntityBuilder::new();
+
+        RenderPassBundle::builder_impl(
+            &mut builder,
+            self.order,
+            self.label,
+            self.color_attachments.into_iter().map(Into::into).collect(),
+            self.depth_attachment.map(Into::into),
+            pipeline,
+            self.vertex_buffers.into_iter().map(Into::into).collect(),
+            self.index_buffer.map(Into::into),
+            self.bind_groups.into_iter().map(Into::into).collect(),
+            self.push_constants.into_iter().map(Into::into).collect(),
+            self.blend_constant,
+            self.stencil_reference,
+            self.viewport.map(Into::into),
+            self.scissor_rect.map(Into::into),
+            self.encoder,
+        );
+
+        if let Some((query_set, begin, end)) = self.timestamp {
+            builder.add(RenderPassTimestampComponent::construct((
+                Indirect::construct(query_set),
+                begin,
+                end,
+            )));
+        }
+
+        builder
+    }
+
+    pub fn draw(self, vertices: Range<u32>, instances: Range<u32>) -> EntityBuilder {
+        let mut builder = self.build_impl();
+        builder.add(RenderPassDrawComponent::construct((vertices, instances)));
+        builder
+    }
+
+    pub fn draw_indexed(
+        self,
+        indices: Range<u32>,
+        base_vertex: i32,
+        instances: Range<u32>,
+    ) -> EntityBuilder {
+        let mut builder = self.build_impl();
+        builder.add(RenderPassDrawIndexedComponent::construct((
+            indices,
+            base_vertex,
+            instances,
+        )));
+        builder
+    }
+
+    pub fn draw_indirect(self, buffer: Entity, offset: BufferAddress) -> EntityBuilder {
+        let mut builder = self.build_impl();
+        let indirect = Indirect::construct(buffer);
+        builder.add(RenderPassDrawIndirectComponent::construct((indirect, offset)));
+        builder
+    }
+
+    pub fn draw_indexed_indirect(self, buffer: Entity, offset: BufferAddress) -> EntityBuilder {
+        let mut builder = self.build_impl();
+        let indirect = Indirect::construct(buffer);
+        builder.add(RenderPassDrawIndexedIndirectComponent::construct((
+            indirect, offset,
+        )));
+        builder
+    }
+
+    /// See [`MultiDrawCount`] for the wgpu features each draw count requires
+    pub fn multi_draw_indirect(
+        self,
+        buffer: Entity,
+        offset: BufferAddress,
+        count: MultiDrawCount,
+    ) -> EntityBuilder {
+        let mut builder = self.build_impl();
+        let indirect = Indirect::construct(buffer);
+        builder.add(RenderPassMultiDrawIndirectComponent::construct((
+            indirect, offset, count,
+        )));
+        builder
+    }
+
+    /// See [`MultiDrawCount`] for the wgpu features each draw count requires
+    pub fn multi_draw_indexed_indirect(
+        self,
+        buffer: Entity,
+        offset: BufferAddress,
+        count: MultiDrawCount,
+    ) -> EntityBuilder {
+        let mut builder = self.build_impl();
+        let indirect = Indirect::construct(buffer);
+        builder.add(RenderPassMultiDrawIndexedIndirectComponent::construct((
+            indirect, offset, count,
+        )));
+        builder
+    }
+
+    /// Execute pre-recorded render bundles; pipeline and binding state is ignored
+    pub fn execute_bundles(self, bundles: Vec<Entity>) -> EntityBuilder {
+        RenderPassBundle::execute_bundles(
+            self.order,
+            self.label,
+            self.color_attachments,
+            self.depth_attachment,
+            bundles,
+            self.encoder,
+        )
+    }
+}
+
+#[derive(hecs::Query)]
+pub struct RenderPassQuery<'a> {
+    pub(crate) order: &'a PassOrderComponent,
+    label: &'a RenderPassLabelComponent,
+    color_attachments: &'a RenderPassColorAttachmentsComponent,
+    depth_attachment: &'a RenderPassDepthAttachmentComponent,
+    pipeline: Option<&'a RenderPassPipelineComponent>,
+    vertex_buffers: &'a RenderPassVertexBuffersComponent,
+    index_buffer: &'a RenderPassIndexBufferComponent,
+    bind_groups: &'a RenderPassBindGroupsComponent,
+    push_constants: Option<&'a RenderPassPushConstantsComponent>,
+    blend_constant: Option<&'a RenderPassBlendConstantComponent>,
+    stencil_reference: Option<&'a RenderPassStencilReferenceComponent>,
+    viewport: Option<&'a RenderPassViewportComponent>,
+    scissor_rect: Option<&'a RenderPassScissorRectComponent>,
+    timestamp: Option<&'a RenderPassTimestampComponent>,
+    encoder: &'a RenderPassEncoderComponent,
+}
+
+// Set a piece of changeable render pass state, inserting it if the pass doesn't have it yet
+fn set_render_pass_state<T>(world: &mut World, entity: Entity, value: T)
+where
+    T: PartialEq + Send + Sync + 'static,
+{
+    if let Ok(mut current) = world.get_mut::<Usage<RenderPassTag, Changed<T>>>(entity) {
+        if ***current != value {
+            ***current = value;
+            current.set_changed(true);
+        }
+        return;
+    }
+
+    world
+        .insert_one(
+            entity,
+            Usage::<RenderPassTag, Changed<T>>::construct(value).with(ChangedFlag(true)),
+        )
+        .unwrap();
+}
+
+/// Set the viewport of a render pass, flagging it as changed if it differs
+///
+/// The new viewport is used the next time the pass is recorded, which clears the flag
+pub fn set_render_pass_viewport(
+    world: &mut World,
+    entity: Entity,
+    viewport: RenderPassViewportDesc,
+) {
+    set_render_pass_state::<(f32, f32, f32, f32, f32, f32)>(world, entity, viewport.into())
+}
+
+/// Set the scissor rect of a render pass, flagging it as changed if it differs
+///
+/// The new scissor rect is used the next time the pass is recorded, which clears the flag
+pub fn set_render_pass_scissor_rect(
+    world: &mut World,
+    entity: Entity,
+    scissor_rect: RenderPassScissorRectDesc,
+) {
+    set_render_pass_state::<(u32, u32, u32, u32)>(world, entity, scissor_rect.into())
+}
+
+/// Write changed dynamic offsets into their render pass bind groups
+pub fn render_pass_bind_group_offsets_system(world: &mut World) {
+    for (entity, (offsets, bind_groups)) in world.query_mut::<(
+        &RenderPassBindGroupOffsetsComponent,
+        &mut RenderPassBindGroupsComponent,
+    )>() {
+        if !offsets.get_changed() {
+            continue;
+        }
+
+        // Offsets for bind groups the pass doesn't have are skipped
+        for (index, offsets) in offsets.iter() {
+            match bind_groups.get_mut(*index) {
+                Some((_, bind_group_offsets)) => *bind_group_offsets = offsets.clone(),
+                None => println!(
+                    "Warning: Skipping offsets for render pass {:?}, it has no bind group {}",
+                    entity, index
+                ),
+            }
+        }
+
+        offsets.set_changed(false);
+    }
+}
+
+/// Resolve clearing color attachments to the BackgroundComponent of their view entity
+pub fn render_pass_background_system(world: &mut World) {
+    for (_, color_attachments) in world
+        .query::<&mut RenderPassColorAttachmentsComponent>()
+        .into_iter()
+    {
+        for (view, _, ops) in color_attachments.iter_mut() {
+            if let LoadOp::Clear(color) = &mut ops.load {
+                if let Ok(background) = world.get::<BackgroundComponent>(view.entity()) {
+                    *color = background.clear_color();
+                }
+            }
+        }
+    }
+}
+
+/// Tracks state bound to an in-flight render pass so redundant binds can be skipped
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RenderPassState {
+    pipeline: Option<Entity>,
+    vertex_buffers: Vec<Option<(Entity, Range<BufferAddress>)>>,
+    index_buffer: Option<(Entity, Range<BufferAddress>, IndexFormat)>,
+    bind_groups: Vec<Option<(Entity, Vec<DynamicOffset>)>>,
+}
+
+impl RenderPassState {
+    fn bind_slot<T: PartialEq>(slots: &mut Vec<Option<T>>, index: usize, state: T) -> bool {
+        if slots.len() <= index {
+            slots.resize_with(index + 1, || None);
+        }
+
+        if slots[index].as_ref() == Some(&state) {
+            return false;
+        }
+
+        slots[index] = Some(state);
+        true
+    }
+
+    /// Returns true if the pipeline needs to be set
+    pub fn bind_pipeline(&mut self, pipeline: Entity) -> bool {
+        if self.pipeline == Some(pipeline) {
+            return false;
+        }
+
+        self.pipeline = Some(pipeline);
+        true
+    }
+
+    /// Returns true if the vertex buffer slot needs to be set
+    pub fn bind_vertex_buffer(
+        &mut self,
+        slot: usize,
+        buffer: Entity,
+        range: &Range<BufferAddress>,
+    ) -> bool {
+        Self::bind_slot(&mut self.vertex_buffers, slot, (buffer, range.clone()))
+    }
+
+    /// Returns true if the index buffer needs to be set
+    pub fn bind_index_buffer(
+        &mut self,
+        buffer: Entity,
+        range: &Range<BufferAddress>,
+        format: IndexFormat,
+    ) -> bool {
+        let state = (buffer, range.clone(), format);
+        if self.index_buffer.as_ref() == Some(&state) {
+            return false;
+        }
+
+        self.index_buffer = Some(state);
+        true
+    }
+
+    /// Returns true if the bind group slot needs to be set
+    pub fn bind_group(
+        &mut self,
+        index: usize,
+        bind_group: Entity,
+        offsets: &[DynamicOffset],
+    ) -> bool {
+        Self::bind_slot(&mut self.bind_groups, index, (bind_group, offsets.to_vec()))
+    }
+}
+
+// Returns true if an attachment can be carried over from the previous pass without clearing it
+fn continues_operations<V>(prev: &Operations<V>, next: &Operations<V>) -> bool {
+    matches!(next.load, LoadOp::Load) && prev.store == next.store
+}
+
+fn continues_optional_operations<V>(
+    prev: &Option<Operations<V>>,
+    next: &Option<Operations<V>>,
+) -> bool {
+    match (prev, next) {
+        (None, None) => true,
+        (Some(prev), Some(next)) => continues_operations(prev, next),
+        _ => false,
+    }
+}
+
+// Returns true if `next` can be recorded into the wgpu render pass begun for `prev`
+fn continues_render_pass(prev: &RenderPassQuery, next: &RenderPassQuery) -> bool {
+    if prev.encoder.entity() != next.encoder.entity() {
+        return false;
+    }
+
+    if prev.timestamp.is_some() || next.timestamp.is_some() {
+        return false;
+    }
+
+    if prev.color_attachments.len() != next.color_attachments.len() {
+        return false;
+    }
+
+    let color_matches = prev
+        .color_attachments
+        .iter()
+        .zip(next.color_attachments.iter())
+        .all(
+            |((prev_view, prev_resolve, prev_ops), (next_view, next_resolve, next_ops))| {
+                prev_view.entity() == next_view.entity()
+                    && prev_resolve.as_ref().map(Indirect::entity)
+                        == next_resolve.as_ref().map(Indirect::entity)
+                    && continues_operations(prev_ops, next_ops)
+            },
+        );
+
+    if !color_matches {
+        return false;
+    }
+
+    let depth_matches = match (&**prev.depth_attachment, &**next.depth_attachment) {
+        (None, None) => true,
+        (
+            Some((prev_view, prev_depth_ops, prev_stencil_ops)),
+            Some((next_view, next_depth_ops, next_stencil_ops)),
+        ) => {
+            prev_view.entity() == next_view.entity()
+                && continues_optional_operations(prev_depth_ops, next_depth_ops)
+                && continues_optional_operations(prev_stencil_ops, next_stencil_ops)
+        }
+        _ => false,
+    };
+
+    if !depth_matches {
+        return false;
+    }
+
+    // Dynamic state persists across draws within a pass, so it must match
+    prev.blend_constant.map(|c| **c) == next.blend_constant.map(|c| **c)
+        && prev.stencil_reference.map(|s| **s) == next.stencil_reference.map(|s| **s)
+        && prev.viewport.map(|v| ***v) == next.viewport.map(|v| ***v)
+        && prev.scissor_rect.map(|s| ***s) == next.scissor_rect.map(|s| ***s)
+}
+
+// Render passes already warned about missing device features, along with the features
+static MISSING_FEATURE_WARNINGS: Mutex<BTreeSet<(Entity, u64)>> = Mutex::new(BTreeSet::new());
+
+// Returns true the first time a render pass is found to be missing a given set of features,
+// so skipped passes only warn once rather than every frame
+fn first_missing_feature_warning(entity: Entity, features: Features) -> bool {
+    MISSING_FEATURE_WARNINGS
+        .lock()
+        .unwrap()
+        .insert((entity, features.bits()))
+}
+
+fn device_features(world: &World) -> Features {
+    world
+        .query::<&DeviceComponent>()
+        .into_iter()
+        .next()
+        .map(|(_, device)| device.features())
+        .unwrap_or_else(Features::empty)
+}
+
+// Indirect buffer, offset, optional count buffer and offset, and draw count or max count
+type MultiDrawResources = (
+    BufferComponent,
+    BufferAddress,
+    Option<(BufferComponent, BufferAddress)>,
+    u32,
+);
+
+// World resources referenced by a single render pass entity
+struct RenderPassResources<'a> {
+    pipeline: Option<(Entity, Ref<'a, RenderPipelineComponent>)>,
+    vertex_buffers: Vec<(Entity, BufferComponent, Range<BufferAddress>)>,
+    index_buffer: Option<(Entity, BufferComponent, Range<BufferAddress>, IndexFormat)>,
+    bind_groups: Vec<(Entity, Ref<'a, BindGroupComponent>, &'a [DynamicOffset])>,
+    push_constants: Vec<(
+        Ref<'a, PushConstantComponent>,
+        Ref<'a, PushConstantOffset>,
+        ShaderStages,
+    )>,
+    blend_constant: Option<Color>,
+    stencil_reference: Option<u32>,
+    viewport: Option<(f32, f32, f32, f32, f32, f32)>,
+    scissor_rect: Option<(u32, u32, u32, u32)>,
+    draw: Option<(Range<u32>, Range<u32>)>,
+    draw_indexed: Option<(Range<u32>, i32, Range<u32>)>,
+    draw_indirect: Option<(BufferComponent, BufferAddress)>,
+    draw_indexed_indirect: Option<(BufferComponent, BufferAddress)>,
+    multi_draw_indirect: Option<MultiDrawResources>,
+    multi_draw_indexed_indirect: Option<MultiDrawResources>,
+    bundles: Vec<Ref<'a, RenderBundleComponent>>,
+}
+
+impl<'a> RenderPassResources<'a> {
+    fn collect(world: &'a World, entity: Entity, pass: &'a RenderPassQuery) -> Option<Self> {
+        let pipeline = match pass.pipeline {
+            Some(pipeline) => {
+                let pipeline_entity = pipeline.entity();
+                let pipeline = world
+                    .get::<RenderPipelineComponent>(pipeline_entity)
+                    .unwrap();
+                pipeline.get()?;
+                Some((pipeline_entity, pipeline))
+            }
+            None => None,
+        };
+
+        let vertex_buffers = pass
+            .vertex_buffers
+            .iter()
+            .map(|(vertex_buffer, range)| {
+                let buffer_entity = vertex_buffer.entity();
+                let buffer = (*world.get::<BufferComponent>(buffer_entity).unwrap()).clone();
+                (buffer_entity, buffer, range.clone())
+            })
+            .collect();
+
+        let index_buffer = pass
+            .index_buffer
+            .as_ref()
+            .map(|(index_buffer, range, format)| {
+                let buffer_entity = index_buffer.entity();
+                let buffer = (*world.get::<BufferComponent>(buffer_entity).unwrap()).clone();
+                (buffer_entity, buffer, range.clone(), *format)
+            });
+
+        let bind_groups = pass
+            .bind_groups
+            .iter()
+            .map(|(bind_group, offsets)| {
+                let bind_group_entity = bind_group.entity();
+                let bind_group = world.get::<BindGroupComponent>(bind_group_entity).unwrap();
+                (bind_group_entity, bind_group, &offsets[..])
+            })
+            .collect();
+
+        let push_constants = pass
+            .push_constants
+            .map(|push_constants| {
+                push_constants
+                    .iter()
+                    .map(|(push_constant, shader_stages)| {
+                        let push_constant = push_constant.entity();
+                        (
+                            world.get::<PushConstantComponent>(push_constant).unwrap(),
+                            world.get::<PushConstantOffset>(push_constant).unwrap(),
+                            *shader_stages,
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let indirect_buffer = |indirect: &Indirect<&'static BufferComponent>| {
+            (*world.get::<BufferComponent>(indirect.entity()).unwrap()).clone()
+        };
+
+        // Collect draw commands
+        let draw = world
+            .get::<RenderPassDrawComponent>(entity)
+            .ok()
+            .map(|draw| (**draw).clone());
+
+        let draw_indexed = world
+            .get::<RenderPassDrawIndexedComponent>(entity)
+            .ok()
+            .map(|draw_indexed| (**draw_indexed).clone());
+
+        let draw_indirect = world
+            .get::<RenderPassDrawIndirectComponent>(entity)
+            .ok()
+            .map(|draw_indirect| {
+                let (buffer, offset) = &**draw_indirect;
+                (indirect_buffer(buffer), *offset)
+            });
+
+        let draw_indexed_indirect = world
+            .get::<RenderPassDrawIndexedIndirectComponent>(entity)
+            .ok()
+            .map(|draw_indexed_indirect| {
+                let (buffer, offset) = &**draw_indexed_indirect;
+                (indirect_buffer(buffer), *offset)
+            });
+
+        let multi_draw = |(buffer, offset, count): &(
+            Indirect<&'static BufferComponent>,
+            BufferAddress,
+            MultiDrawCount,
+        )| {
+            let features = count.features();
+            if !device_features(world).contains(features) {
+                if first_missing_feature_warning(entity, features) {
+                    println!(
+                        "Warning: Skipping multi-draw for render pass {:?}, device is missing {:?}",
+                        entity, features
+                    );
+                }
+                return None;
+            }
+
+            // Draws whose count buffer doesn't exist are skipped
+            let (count_buffer, count) = match *count {
+                MultiDrawCount::Count(count) => (None, count),
+                MultiDrawCount::Buffer {
+                    buffer,
+                    offset,
+                    max_count,
+                } => {
+                    let buffer = (*world.get::<BufferComponent>(buffer).ok()?).clone();
+                    (Some((buffer, offset)), max_count)
+                }
+            };
+
+            Some((indirect_buffer(buffer), *offset, count_buffer, count))
+        };
+
+        let multi_draw_indirect = world
+            .get::<RenderPassMultiDrawIndirectComponent>(entity)
+            .ok()
+            .and_then(|multi_draw_indirect| multi_draw(&multi_draw_indirect));
+
+        let multi_draw_indexed_indirect = world
+            .get::<RenderPassMultiDrawIndexedIndirectComponent>(entity)
+            .ok()
+            .and_then(|multi_draw_indexed_indirect| multi_draw(&multi_draw_indexed_indirect));
+
+        let bundles = world
+            .get::<RenderPassExecuteBundlesComponent>(entity)
+            .ok()
+            .map(|bundles| {
+                bundles
+                    .iter()
+                    .map(|bundle| world.get::<RenderBundleComponent>(bundle.entity()).unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        if bundles.iter().any(|bundle| bundle.get().is_none()) {
+            return None;
+        }
+
+        Some(RenderPassResources {
+            pipeline,
+            vertex_buffers,
+            index_buffer,
+            bind_groups,
+            push_constants,
+            blend_constant: pass.blend_constant.map(|c| **c),
+            stencil_reference: pass.stencil_reference.map(|s| **s),
+            viewport: pass.viewport.map(|v| {
+                v.set_changed(false);
+                ***v
+            }),
+            scissor_rect: pass.scissor_rect.map(|s| {
+                s.set_changed(false);
+                ***s
+            }),
+            draw,
+            draw_indexed,
+            draw_indirect,
+            draw_indexed_indirect,
+            multi_draw_indirect,
+            multi_draw_indexed_indirect,
+            bundles,
+        })
+    }
+}
+
+// Buffer locks held for the lifetime of a wgpu render pass
+struct RenderPassLocks<'a> {
+    vertex_buffers: Vec<RwLockReadGuard<'a, LazyComponent<Buffer>>>,
+    index_buffer: Option<RwLockReadGuard<'a, LazyComponent<Buffer>>>,
+    draw_indirect: Option<RwLockReadGuard<'a, LazyComponent<Buffer>>>,
+    draw_indexed_indirect: Option<RwLockReadGuard<'a, LazyComponent<Buffer>>>,
+    multi_draw_indirect: Option<MultiDrawLocks<'a>>,
+    multi_draw_indexed_indirect: Option<MultiDrawLocks<'a>>,
+}
+
+type MultiDrawLocks<'a> = (
+    RwLockReadGuard<'a, LazyComponent<Buffer>>,
+    Option<RwLockReadGuard<'a, LazyComponent<Buffer>>>,
+);
+
+impl<'a> RenderPassLocks<'a> {
+    fn lock(resources: &'a RenderPassResources) -> Self {
+        RenderPassLocks {
+            vertex_buffers: resources
+                .vertex_buffers
+                .iter()
+                .map(|(_, buffer, _)| buffer.read())
+                .collect(),
+            index_buffer: resources
+                .index_buffer
+                .as_ref()
+                .map(|(_, buffer, _, _)| buffer.read()),
+            draw_indirect: resources
+                .draw_indirect
+                .as_ref()
+                .map(|(buffer, _)| buffer.read()),
+            draw_indexed_indirect: resources
+                .draw_indexed_indirect
+                .as_ref()
+                .map(|(buffer, _)| buffer.read()),
+            multi_draw_indirect: resources
+                .multi_draw_indirect
+                .as_ref()
+                .map(Self::lock_multi_draw),
+            multi_draw_indexed_indirect: resources
+                .multi_draw_indexed_indirect
+                .as_ref()
+                .map(Self::lock_multi_draw),
+        }
+    }
+
+    fn lock_multi_draw(
+        (buffer, _, count_buffer, _): &'a MultiDrawResources,
+    ) -> MultiDrawLocks<'a> {
+        (
+            buffer.read(),
+            count_buffer.as_ref().map(|(buffer, _)| buffer.read()),
+        )
+    }
+}
+
+// Record render passes in PassOrder
+//
+// The sort is stable, so passes with equal orders are recorded in query order;
+// for passes with the same set of components, this is the order they were spawned in
+pub fn draw_render_passes_system(world: &mut World) -> Option<()> {
+    let mut query = world.query::<RenderPassQuery>();
+    let mut components = query.into_iter().collect::<Vec<_>>();
+    components.sort_by(|(_, lhs), (_, rhs)| lhs.order.cmp(rhs.order));
+
+    draw_render_passes(world, components)
+}
+
+// Record a set of render passes that have already been sorted by PassOrder
+pub(crate) fn draw_render_passes(
+    world: &World,
+    components: Vec<(Entity, RenderPassQuery)>,
+) -> Option<()> {
+    // Group consecutive passes that can be recorded into a single wgpu render pass
+    let mut batches: Vec<Vec<(Entity, RenderPassQuery)>> = vec![];
+    for (entity, pass) in components.into_iter() {
+        match batches.last_mut() {
+            Some(batch) if continues_render_pass(&batch[batch.len() - 1].1, &pass) => {
+                batch.push((entity, pass))
+            }
+            _ => batches.push(vec![(entity, pass)]),
+        }
+    }
+
+    for batch in batches.iter() {
+        let (
+            _,
+            RenderPassQuery {
+                label,
+                color_attachments,
+                depth_attachment,
+                encoder,
+                ..
+            },
+        ) = &batch[0];
+
+        let mut query = encoder.get(world);
+        let encoder = query.get().unwrap().get_mut().unwrap();
+
+        // Collect label
+        let label = (***label).clone();
+        let label = label.as_deref();
+
+        // Collect color attachments
+        let mut color_queries = color_attachments
+            .iter()
+            .map(|(view, resolve_target, ops)| {
+                (
+                    view.get(world),
+                    resolve_target
+                        .as_ref()
+                        .map(|resolve_target| resolve_target.get(world)),
+                    ops,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut color = vec![];
+        for (view, resolve_target, ops) in color_queries.iter_mut() {
+            let view = view.get().unwrap().get()?;
+            let resolve_target = resolve_target
+                .as_mut()
                 .map(|resolve_target| resolve_target.get().unwrap().get().unwrap());
 
             color.push((view, resolve_target, ops))
@@ -488,191 +1631,359 @@ pub fn draw_render_passes_system(world: &mut World) -> Option<()> {
             }
         });
 
-        // Collect pipeline
-        let mut query = pipeline.get(world);
-        let pipeline = query.get()?;
-        let pipeline = pipeline.get()?;
+        // Collect per-draw resources
+        let mut resources = vec![];
+        for (entity, pass) in batch.iter() {
+            resources.push(RenderPassResources::collect(world, *entity, pass)?);
+        }
 
-        // Collect vertex buffer queries
-        let mut vertex_buffer_queries = vertex_buffers
+        let locks = resources
             .iter()
-            .map(|(vertex_buffer, range)| (vertex_buffer.get(world), range))
-            .collect::<Vec<_>>();
-
-        let vertex_buffer_locks = vertex_buffer_queries
-            .iter_mut()
-            .map(|(query, range)| {
-                let bind_group = query.get().unwrap().read();
-                (bind_group, range)
-            })
+            .map(RenderPassLocks::lock)
             .collect::<Vec<_>>();
 
-        let vertex_buffers = vertex_buffer_locks
-            .iter()
-            .map(|(buf, range)| (buf.get().unwrap(), range))
-            .collect::<Vec<_>>();
+        // Collect timestamp query set, skipping timestamps if unsupported or not yet created
+        let timestamp = batch[0]
+            .1
+            .timestamp
+            .filter(|_| device_features(world).contains(Features::TIMESTAMP_QUERY))
+            .and_then(|timestamp| {
+                let (query_set, begin, end) = &**timestamp;
+                let query_set = world.get::<QuerySetComponent>(query_set.entity()).ok()?;
+                Some((query_set, *begin, *end))
+            });
 
-        // Collect index buffer query
-        let mut index_buffer_query = index_buffer
-            .as_ref()
-            .map(|(index_buffer, range, format)| (index_buffer.get(world), range, format));
+        if let Some((query_set, begin, _)) = &timestamp {
+            if let Some(query_set) = query_set.get() {
+                encoder.write_timestamp(query_set, *begin);
+            }
+        }
 
-        let index_buffer_lock = index_buffer_query.as_mut().map(|(query, range, format)| {
-            let bind_group = query.get().unwrap().read();
-            (bind_group, range, *format)
+        // Begin render pass
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label,
+            color_attachments: &color_attachments,
+            depth_stencil_attachment,
         });
 
-        let index_buffer = index_buffer_lock
-            .as_ref()
-            .map(|(lock, range, format)| (lock.get().unwrap(), range, format));
+        let mut state = RenderPassState::default();
 
-        // Collect bind group queries
-        let mut bind_group_queries = bind_groups
-            .iter()
-            .map(|(bind_group, offsets)| (bind_group.get(world), offsets))
-            .collect::<Vec<_>>();
-
-        let bind_groups = bind_group_queries
-            .iter_mut()
-            .map(|(query, offsets)| {
-                let bind_group = query.get().unwrap();
-                (bind_group.get().unwrap(), offsets)
-            })
-            .collect::<Vec<_>>();
+        for (resources, locks) in resources.iter().zip(locks.iter()) {
+            // Set pipeline
+            if let Some((pipeline_entity, pipeline)) = &resources.pipeline {
+                if state.bind_pipeline(*pipeline_entity) {
+                    rpass.set_pipeline(pipeline.get().unwrap());
+                }
+            }
 
-        // Collect push constant queries
-        let mut push_constant_queries = if let Some(push_constants) = push_constants {
-            let push_constant_queries = push_constants
+            // Set vertex buffers
+            for (i, ((buffer_entity, _, range), lock)) in resources
+                .vertex_buffers
                 .iter()
-                .map(|(push_constant, shader_stages)| (push_constant.get(world), shader_stages))
-                .collect::<Vec<_>>();
+                .zip(locks.vertex_buffers.iter())
+                .enumerate()
+            {
+                if state.bind_vertex_buffer(i, *buffer_entity, range) {
+                    rpass.set_vertex_buffer(i as u32, lock.get().unwrap().slice(range.clone()));
+                }
+            }
 
-            push_constant_queries
-        } else {
-            vec![]
-        };
+            // Set index buffer
+            if let (Some((buffer_entity, _, range, format)), Some(lock)) =
+                (&resources.index_buffer, &locks.index_buffer)
+            {
+                if state.bind_index_buffer(*buffer_entity, range, *format) {
+                    rpass.set_index_buffer(lock.get().unwrap().slice(range.clone()), *format);
+                }
+            }
 
-        let push_constants = push_constant_queries
-            .iter_mut()
-            .map(|(query, shader_stages)| (query.get().unwrap(), shader_stages))
-            .collect::<Vec<_>>();
+            // Set bind groups
+            for (i, (bind_group_entity, bind_group, offsets)) in
+                resources.bind_groups.iter().enumerate()
+            {
+                if state.bind_group(i, *bind_group_entity, offsets) {
+                    rpass.set_bind_group(i as u32, bind_group.get().unwrap(), offsets);
+                }
+            }
 
-        // Collect draw indirect query
-        let mut indirect_query = draw_indirect.map(|draw_indirect| {
-            let (indirect_query, indirect_offset) = &**draw_indirect;
-            (indirect_query.get(world), *indirect_offset)
-        });
+            // Set push constants
+            for (data, offset, shader_stages) in resources.push_constants.iter() {
+                rpass.set_push_constants(*shader_stages, ***offset, data);
+            }
 
-        let draw_indirect_lock =
-            indirect_query
-                .as_mut()
-                .map(|(indirect_query, indirect_offset)| {
-                    (indirect_query.get().unwrap().read(), *indirect_offset)
-                });
+            // Set blend constant
+            if let Some(blend_constant) = resources.blend_constant {
+                rpass.set_blend_constant(blend_constant);
+            }
 
-        let draw_indirect = draw_indirect_lock
-            .as_ref()
-            .map(|(lock, indirect_offset)| (lock.get().unwrap(), *indirect_offset));
+            // Set stencil reference
+            if let Some(stencil_reference) = resources.stencil_reference {
+                rpass.set_stencil_reference(stencil_reference);
+            }
 
-        // Collect draw indexed indirect query
-        let mut indexed_indirect_query = draw_indexed_indirect.map(|draw_indexed_indirect| {
-            let (indirect_query, indirect_offset) = &**draw_indexed_indirect;
-            (indirect_query.get(world), *indirect_offset)
-        });
+            // Set viewport
+            if let Some((x, y, w, h, min_depth, max_depth)) = resources.viewport {
+                rpass.set_viewport(x, y, w, h, min_depth, max_depth);
+            }
 
-        let draw_indexed_indirect_lock =
-            indexed_indirect_query
-                .as_mut()
-                .map(|(indirect_query, indirect_offset)| {
-                    (indirect_query.get().unwrap().read(), *indirect_offset)
-                });
+            // Set scissor_rect
+            if let Some((x, y, w, h)) = resources.scissor_rect {
+                rpass.set_scissor_rect(x, y, w, h);
+            }
 
-        let draw_indexed_indirect =
-            draw_indexed_indirect_lock
-                .as_ref()
-                .map(|(indirect_query, indirect_offset)| {
-                    (indirect_query.get().unwrap(), *indirect_offset)
-                });
+            // Draw
+            if let Some((vertices, instances)) = &resources.draw {
+                rpass.draw(vertices.clone(), instances.clone());
+            }
 
-        // Begin render pass
-        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
-            label,
-            color_attachments: &color_attachments,
-            depth_stencil_attachment,
-        });
+            // Draw indexed
+            if let Some((indices, base_vertex, instances)) = &resources.draw_indexed {
+                rpass.draw_indexed(indices.clone(), *base_vertex, instances.clone());
+            }
 
-        rpass.set_pipeline(pipeline);
+            // Draw indirect
+            if let (Some((_, indirect_offset)), Some(lock)) =
+                (&resources.draw_indirect, &locks.draw_indirect)
+            {
+                rpass.draw_indirect(lock.get().unwrap(), *indirect_offset);
+            }
 
-        // Set vertex buffers
-        for (i, (vertex_buffer, range)) in vertex_buffers.iter().enumerate() {
-            rpass.set_vertex_buffer(i as u32, vertex_buffer.slice((***range).clone()));
-        }
+            // Draw indexed indirect
+            if let (Some((_, indirect_offset)), Some(lock)) = (
+                &resources.draw_indexed_indirect,
+                &locks.draw_indexed_indirect,
+            ) {
+                rpass.draw_indexed_indirect(lock.get().unwrap(), *indirect_offset);
+            }
 
-        // Set index buffer
-        if let Some((index_buffer, range, format)) = index_buffer {
-            rpass.set_index_buffer(index_buffer.slice((*range).clone()), **format);
-        }
+            // Multi-draw indirect
+            if let (Some((_, offset, count_buffer, count)), Some((lock, count_lock))) =
+                (&resources.multi_draw_indirect, &locks.multi_draw_indirect)
+            {
+                match (count_buffer, count_lock) {
+                    (Some((_, count_offset)), Some(count_lock)) => rpass.multi_draw_indirect_count(
+                        lock.get().unwrap(),
+                        *offset,
+                        count_lock.get().unwrap(),
+                        *count_offset,
+                        *count,
+                    ),
+                    _ => rpass.multi_draw_indirect(lock.get().unwrap(), *offset, *count),
+                }
+            }
 
-        // Set bind groups
-        for (i, (bind_group, offsets)) in bind_groups.iter().enumerate() {
-            rpass.set_bind_group(i as u32, bind_group, &offsets);
-        }
+            // Multi-draw indexed indirect
+            if let (Some((_, offset, count_buffer, count)), Some((lock, count_lock))) = (
+                &resources.multi_draw_indexed_indirect,
+                &locks.multi_draw_indexed_indirect,
+            ) {
+                match (count_buffer, count_lock) {
+                    (Some((_, count_offset)), Some(count_lock)) => rpass
+                        .multi_draw_indexed_indirect_count(
+                            lock.get().unwrap(),
+                            *offset,
+                            count_lock.get().unwrap(),
+                            *count_offset,
+                            *count,
+                        ),
+                    _ => rpass.multi_draw_indexed_indirect(lock.get().unwrap(), *offset, *count),
+                }
+            }
 
-        // Set push constants
-        for (push_constant, shader_stages) in push_constants {
-            rpass.set_push_constants(
-                **shader_stages,
-                **push_constant.offset,
-                &***push_constant.data,
-            );
-        }
+            // Execute bundles
+            if !resources.bundles.is_empty() {
+                rpass.execute_bundles(resources.bundles.iter().map(|bundle| bundle.get().unwrap()));
 
-        // Set blend constant
-        if let Some(blend_constant) = blend_constant {
-            rpass.set_blend_constant(**blend_constant);
+                // Executing bundles clears the pipeline and bindings of the pass
+                state = RenderPassState::default();
+            }
         }
 
-        // Set stencil reference
-        if let Some(stencil_reference) = stencil_reference {
-            rpass.set_stencil_reference(**stencil_reference);
-        }
+        drop(rpass);
 
-        // Set viewport
-        if let Some(viewport) = viewport {
-            let (x, y, w, h, min_depth, max_depth) = **viewport;
-            rpass.set_viewport(x, y, w, h, min_depth, max_depth);
+        if let Some((query_set, _, end)) = &timestamp {
+            if let Some(query_set) = query_set.get() {
+                encoder.write_timestamp(query_set, *end);
+            }
         }
+    }
 
-        // Set scissor_rect
-        if let Some(scissor_rect) = scissor_rect {
-            let (x, y, w, h) = **scissor_rect;
-            rpass.set_scissor_rect(x, y, w, h);
-        }
+    Some(())
+}
 
-        // Draw
-        if let Some(draw) = draw {
-            rpass.draw(draw.0.clone(), draw.1.clone());
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_pass_state_skips_redundant_binds() {
+        let mut world = World::new();
+        let pipeline_a = world.spawn(());
+        let pipeline_b = world.spawn(());
+        let buffer = world.spawn(());
+        let bind_group = world.spawn(());
+
+        let mut state = RenderPassState::default();
+
+        assert!(state.bind_pipeline(pipeline_a));
+        assert!(!state.bind_pipeline(pipeline_a));
+        assert!(state.bind_pipeline(pipeline_b));
+        assert!(state.bind_pipeline(pipeline_a));
+
+        assert!(state.bind_vertex_buffer(1, buffer, &(0..16)));
+        assert!(!state.bind_vertex_buffer(1, buffer, &(0..16)));
+        assert!(state.bind_vertex_buffer(0, buffer, &(0..16)));
+        assert!(state.bind_vertex_buffer(1, buffer, &(16..32)));
+
+        assert!(state.bind_index_buffer(buffer, &(0..16), IndexFormat::Uint16));
+        assert!(!state.bind_index_buffer(buffer, &(0..16), IndexFormat::Uint16));
+        assert!(state.bind_index_buffer(buffer, &(0..16), IndexFormat::Uint32));
+
+        assert!(state.bind_group(0, bind_group, &[]));
+        assert!(!state.bind_group(0, bind_group, &[]));
+        assert!(state.bind_group(0, bind_group, &[256]));
+        assert!(!state.bind_group(0, bind_group, &[256]));
+        assert!(state.bind_group(2, bind_group, &[256]));
+    }
 
-        // Draw indexed
-        if let Some(draw_indexed) = draw_indexed {
-            rpass.draw_indexed(
-                draw_indexed.0.clone(),
-                draw_indexed.1,
-                draw_indexed.2.clone(),
-            );
-        }
+    #[test]
+    fn missing_feature_warnings_are_shown_once() {
+        let mut world = World::new();
+        let pass = world.spawn(());
 
-        // Draw indirect
-        if let Some((indirect_buffer, indirect_offset)) = draw_indirect {
-            rpass.draw_indirect(indirect_buffer, indirect_offset);
-        }
+        let multi_draw = Features::MULTI_DRAW_INDIRECT;
+        assert!(first_missing_feature_warning(pass, multi_draw));
+        assert!(!first_missing_feature_warning(pass, multi_draw));
 
-        // Draw indexed indirect
-        if let Some((indirect_buffer, indirect_offset)) = draw_indexed_indirect {
-            rpass.draw_indexed_indirect(indirect_buffer, indirect_offset);
-        }
+        // Other features warn separately
+        let multi_draw_count = Features::MULTI_DRAW_INDIRECT_COUNT;
+        assert!(first_missing_feature_warning(pass, multi_draw_count));
     }
 
-    Some(())
+    #[test]
+    fn bind_group_offsets_skip_missing_bind_groups() {
+        let mut world = World::new();
+        let bind_group = world.spawn(());
+
+        let pass = world.spawn((
+            RenderPassBindGroupsComponent::construct(vec![(
+                Indirect::construct(bind_group),
+                vec![0],
+            )]),
+            RenderPassBindGroupOffsetsComponent::construct(Changed::new(
+                vec![(0, vec![256]), (3, vec![512])],
+                true,
+            )),
+        ));
+
+        render_pass_bind_group_offsets_system(&mut world);
+
+        let bind_groups = world.get::<RenderPassBindGroupsComponent>(pass).unwrap();
+        assert_eq!(bind_groups.len(), 1);
+        assert_eq!(bind_groups[0].1, vec![256]);
+    }
+
+    #[test]
+    fn render_pass_builder_matches_positional() {
+        let mut world = World::new();
+        let view = world.spawn(());
+        let depth = world.spawn(());
+        let pipeline = world.spawn(());
+        let buffer = world.spawn(());
+        let bind_group = world.spawn(());
+        let encoder = world.spawn(());
+
+        let ops = Operations {
+            load: LoadOp::Load,
+            store: true,
+        };
+        let depth_ops = Operations {
+            load: LoadOp::Load,
+            store: false,
+        };
+
+        let positional = world.spawn(
+            RenderPassBundle::draw(
+                2,
+                Some("Pass".into()),
+                vec![RenderPassColorAttachmentDesc {
+                    view,
+                    resolve_target: None,
+                    ops,
+                }],
+                Some(RenderPassDepthStencilAttachmentDesc {
+                    view: depth,
+                    depth_ops: Some(depth_ops),
+                    stencil_ops: None,
+                }),
+                pipeline,
+                vec![RenderPassVertexBufferDesc {
+                    buffer,
+                    range: 0..16,
+                }],
+                None,
+                vec![RenderPassBindGroupDesc {
+                    bind_group,
+                    offsets: vec![256],
+                }],
+                vec![],
+                None,
+                None,
+                None,
+                Some(RenderPassScissorRectDesc {
+                    x: 0,
+                    y: 0,
+                    width: 4,
+                    height: 4,
+                }),
+                (0..3, 0..1),
+                encoder,
+            )
+            .build(),
+        );
+
+        let built = world.spawn(
+            RenderPassBuilder::new(2, encoder)
+                .label("Pass")
+                .color_attachment(view, None, ops)
+                .depth(depth, Some(depth_ops), None)
+                .pipeline(pipeline)
+                .vertex_buffer(buffer, 0..16)
+                .bind_group(bind_group, vec![256])
+                .scissor_rect(RenderPassScissorRectDesc {
+                    x: 0,
+                    y: 0,
+                    width: 4,
+                    height: 4,
+                })
+                .draw(0..3, 0..1)
+                .build(),
+        );
+
+        let mut query = world.query::<RenderPassQuery>();
+        let mut query = query.view();
+        let [positional, built] = query.get_mut_n([positional, built]);
+        let (positional, built) = (positional.unwrap(), built.unwrap());
+
+        assert_eq!(**positional.order, **built.order);
+        assert_eq!(**positional.label, **built.label);
+        // Matching attachments, encoder and dynamic state
+        assert!(continues_render_pass(&positional, &built));
+        assert_eq!(
+            positional.pipeline.map(|pipeline| pipeline.entity()),
+            built.pipeline.map(|pipeline| pipeline.entity())
+        );
+        assert_eq!(positional.vertex_buffers[0].1, built.vertex_buffers[0].1);
+        assert_eq!(positional.bind_groups[0].1, built.bind_groups[0].1);
+        assert!(positional.index_buffer.is_none() && built.index_buffer.is_none());
+        assert!(built.viewport.is_none() && built.blend_constant.is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn render_pass_builder_requires_pipeline() {
+        let mut world = World::new();
+        let encoder = world.spawn(());
+        RenderPassBuilder::new(0, encoder).draw(0..3, 0..1);
+    }
 }
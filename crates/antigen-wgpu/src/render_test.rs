@@ -0,0 +1,761 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    error::Error,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use hecs::World;
+use wgpu::{Backends, DeviceDescriptor, Instance, RequestAdapterOptions};
+
+use crate::{
+    create_buffers_init_system, create_buffers_system, create_command_encoders_system,
+    create_query_sets_system, create_render_bundles_system, create_samplers_system,
+    create_shader_modules_system, create_texture_views_system, create_textures_system,
+    encode_passes_system, flush_command_encoders_system, submit_command_buffers_system,
+    texture_to_rgba8, BackendBundle, OPTIONAL_FEATURES,
+};
+
+// Render test readback tag for TextureComponent
+pub struct RenderTestTarget;
+
+/// Tightly-packed RGBA8 image read back from a render target
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Self {
+        assert_eq!(
+            data.len(),
+            (width * height * 4) as usize,
+            "Image data does not match its dimensions"
+        );
+        Image {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Create an image filled with a single RGBA8 color
+    pub fn filled(width: u32, height: u32, color: [u8; 4]) -> Self {
+        let data = color
+            .iter()
+            .copied()
+            .cycle()
+            .take((width * height * 4) as usize)
+            .collect();
+        Image::new(width, height, data)
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        [
+            self.data[i],
+            self.data[i + 1],
+            self.data[i + 2],
+            self.data[i + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        let i = ((y * self.width + x) * 4) as usize;
+        self.data[i..i + 4].copy_from_slice(&color);
+    }
+
+    /// Hash the image contents, for cheap exact-match comparisons
+    pub fn hash_u64(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err("Reference image is not RGBA8".into());
+        }
+
+        data.truncate(info.buffer_size());
+        Ok(Image::new(info.width, info.height, data))
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), png::EncodingError> {
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.data)
+    }
+}
+
+/// Create a windowless backend, or None if no adapter is available
+pub fn headless_backend_bundle() -> Option<BackendBundle> {
+    let backend_bits = wgpu::util::backend_bits_from_env().unwrap_or(Backends::PRIMARY);
+    let instance = Instance::new(backend_bits);
+
+    let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+        compatible_surface: None,
+        ..Default::default()
+    }))?;
+
+    let device_desc = DeviceDescriptor {
+        features: adapter.features() & OPTIONAL_FEATURES,
+        ..Default::default()
+    };
+
+    let (device, queue) = pollster::block_on(adapter.request_device(&device_desc, None)).ok()?;
+
+    Some(BackendBundle::new(instance, adapter, device, queue))
+}
+
+/// Run `f` with a windowless backend, or skip it with a note if no adapter is available
+pub fn with_headless_backend<F: FnOnce(BackendBundle)>(f: F) {
+    match headless_backend_bundle() {
+        Some(backend) => f(backend),
+        None => println!(
+            "No WGPU adapter available, skipping {}",
+            std::thread::current().name().unwrap_or("test")
+        ),
+    }
+}
+
+/// Create resources, record and submit one frame of render passes,
+/// then read back the texture tagged with RenderTestTarget
+pub fn render_test_frame(world: &mut World) -> Image {
+    create_shader_modules_system(world);
+    create_buffers_system(world);
+    create_buffers_init_system(world);
+    create_textures_system(world);
+    create_texture_views_system(world);
+    create_samplers_system(world);
+    create_query_sets_system(world);
+    create_render_bundles_system(world);
+
+    create_command_encoders_system(world);
+    encode_passes_system(world);
+    flush_command_encoders_system(world);
+    submit_command_buffers_system(world);
+
+    read_back_render_test_target(world)
+}
+
+fn read_back_render_test_target(world: &mut World) -> Image {
+    let (entity, _) = world
+        .query_mut::<()>()
+        .with::<RenderTestTarget>()
+        .into_iter()
+        .next()
+        .expect("No RenderTestTarget texture");
+
+    texture_to_rgba8(world, entity)
+        .unwrap_or_else(|e| panic!("Failed to read back RenderTestTarget: {}", e))
+}
+
+/// Assert that two images match, allowing each channel to differ by up to `tolerance`
+pub fn assert_images_similar(actual: &Image, expected: &Image, tolerance: u8) {
+    assert_eq!(
+        (actual.width, actual.height),
+        (expected.width, expected.height),
+        "Image dimensions differ"
+    );
+
+    if actual.hash_u64() == expected.hash_u64() && actual == expected {
+        return;
+    }
+
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    let mut first_mismatch = None;
+    for (i, (lhs, rhs)) in actual
+        .data
+        .chunks(4)
+        .zip(expected.data.chunks(4))
+        .enumerate()
+    {
+        let difference = lhs
+            .iter()
+            .zip(rhs.iter())
+            .map(|(lhs, rhs)| (*lhs as i16 - *rhs as i16).unsigned_abs() as u8)
+            .max()
+            .unwrap();
+
+        if difference > tolerance {
+            mismatched += 1;
+            max_difference = max_difference.max(difference);
+            first_mismatch.get_or_insert((
+                i as u32 % actual.width,
+                i as u32 / actual.width,
+                lhs.to_vec(),
+                rhs.to_vec(),
+            ));
+        }
+    }
+
+    if let Some((x, y, lhs, rhs)) = first_mismatch {
+        panic!(
+            "{} pixels differ by more than {} (max difference {}), first at ({}, {}): {:?} != {:?}",
+            mismatched, tolerance, max_difference, x, y, lhs, rhs
+        );
+    }
+}
+
+/// Assert that an image matches a stored reference PNG.
+///
+/// If the BLESS_RENDER_TESTS environment variable is set,
+/// the reference is overwritten with `actual` instead.
+pub fn assert_image_matches_reference<P: AsRef<Path>>(actual: &Image, path: P, tolerance: u8) {
+    let path = path.as_ref();
+
+    if std::env::var_os("BLESS_RENDER_TESTS").is_some() {
+        actual
+            .save_png(path)
+            .expect("Failed to write reference image");
+        println!("Blessed reference image {:?}", path);
+        return;
+    }
+
+    let expected = Image::load_png(path).unwrap_or_else(|e| {
+        panic!(
+            "Failed to load reference image {:?} ({}), run with BLESS_RENDER_TESTS=1 to create it",
+            path, e
+        )
+    });
+
+    assert_images_similar(actual, &expected, tolerance);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hecs::{Entity, EntityBuilder};
+
+    use antigen_core::{Changed, ChangedTrait};
+    use wgpu::{
+        util::BufferInitDescriptor, BufferDescriptor, BufferUsages, Color, ColorTargetState,
+        CommandEncoderDescriptor, Extent3d, Features, FragmentState, LoadOp, Maintain,
+        MultisampleState, Operations, PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology,
+        QuerySetDescriptor, QueryType, RenderBundleEncoderDescriptor, RenderPipelineDescriptor,
+        ShaderModuleDescriptor, ShaderSource, TextureDescriptor, TextureDimension, TextureFormat,
+        TextureUsages, TextureViewDescriptor, VertexState,
+    };
+
+    use crate::{
+        buffer_read_system, device_poll_system, resolve_query_sets_system,
+        set_render_pass_scissor_rect, BufferBundle, BufferInitBundle, BufferReadBundle,
+        CommandBuffersComponent, CommandEncoderBundle, DeviceComponent, MultiDrawCount,
+        QuerySetBundle, QuerySetComponent, QuerySetResolveBundle, RenderBundleBundle,
+        RenderBundleComponent, RenderPassBuilder, RenderPassBundle, RenderPassColorAttachmentDesc,
+        RenderPassScissorRectComponent, RenderPassScissorRectDesc, RenderPipelineComponent,
+        TextureBundle, TextureViewBundle,
+    };
+
+    const WIDTH: u32 = 64;
+    const HEIGHT: u32 = 48;
+
+    // Fills the left half of the target with green, or blue via fs_blue
+    const HALF_SCREEN_SHADER: &str = r#"
+        [[stage(vertex)]]
+        fn vs_main([[builtin(vertex_index)]] index: u32) -> [[builtin(position)]] vec4<f32> {
+            let x = f32(index & 1u) - 1.0;
+            let y = f32(index >> 1u) * 2.0 - 1.0;
+            return vec4<f32>(x, y, 0.0, 1.0);
+        }
+
+        [[stage(fragment)]]
+        fn fs_main() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+        }
+
+        [[stage(fragment)]]
+        fn fs_blue() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(0.0, 0.0, 1.0, 1.0);
+        }
+    "#;
+
+    // Spawn the render target, encoder and pipeline, returning their entities
+    fn assemble_test_scene(world: &mut World, backend: BackendBundle) -> (Entity, Entity, Entity) {
+        world.spawn(backend);
+
+        let mut builder = EntityBuilder::new();
+        builder.add(RenderTestTarget);
+        builder.add_bundle(TextureBundle::new(TextureDescriptor {
+            label: Some("Render Test Target"),
+            size: Extent3d {
+                width: WIDTH,
+                height: HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        }));
+        builder.add_bundle(TextureViewBundle::new(TextureViewDescriptor::default()));
+        let target_entity = world.spawn(builder.build());
+
+        let renderer_entity = world.reserve_entity();
+        world
+            .insert(
+                renderer_entity,
+                CommandEncoderBundle::new(
+                    CommandEncoderDescriptor {
+                        label: Some("Render Test Encoder"),
+                    },
+                    renderer_entity,
+                ),
+            )
+            .unwrap();
+        world
+            .insert_one(renderer_entity, CommandBuffersComponent::default())
+            .unwrap();
+
+        let pipeline_entity = spawn_test_pipeline(world, "fs_main");
+
+        (target_entity, renderer_entity, pipeline_entity)
+    }
+
+    // Create a half screen pipeline with the given fragment entry point up-front,
+    // since pipeline creation is left to the user
+    fn spawn_test_pipeline(world: &mut World, fragment_entry_point: &str) -> Entity {
+        let mut query = world.query::<&DeviceComponent>();
+        let (_, device) = query.into_iter().next().unwrap();
+
+        let shader = device.create_shader_module(&ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(HALF_SCREEN_SHADER.into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Render Test Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: fragment_entry_point,
+                targets: &[ColorTargetState::from(TextureFormat::Rgba8Unorm)],
+            }),
+            multiview: None,
+        });
+        drop(query);
+
+        let mut pipeline_component = RenderPipelineComponent::default();
+        pipeline_component.set_ready_with(pipeline);
+        world.spawn((pipeline_component,))
+    }
+
+    fn clear_red(target_entity: Entity) -> Vec<RenderPassColorAttachmentDesc> {
+        vec![RenderPassColorAttachmentDesc {
+            view: target_entity,
+            resolve_target: None,
+            ops: Operations {
+                load: LoadOp::Clear(Color::RED),
+                store: true,
+            },
+        }]
+    }
+
+    // Stored reference for the half screen triangle, recreated with BLESS_RENDER_TESTS=1
+    const HALF_SCREEN_REFERENCE: &str =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/reference_images/half_screen_triangle.png");
+
+    fn half_screen_image() -> Image {
+        let mut expected = Image::filled(WIDTH, HEIGHT, [255, 0, 0, 255]);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH / 2 {
+                expected.set_pixel(x, y, [0, 255, 0, 255]);
+            }
+        }
+        expected
+    }
+
+    #[test]
+    fn image_similarity_tolerance() {
+        let lhs = Image::filled(4, 4, [100, 100, 100, 255]);
+        let mut rhs = lhs.clone();
+        rhs.set_pixel(1, 2, [102, 99, 100, 255]);
+
+        assert_ne!(lhs.hash_u64(), rhs.hash_u64());
+        assert_images_similar(&lhs, &rhs, 2);
+
+        let result = std::panic::catch_unwind(|| assert_images_similar(&lhs, &rhs, 1));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn half_screen_reference_matches_expected() {
+        assert_image_matches_reference(&half_screen_image(), HALF_SCREEN_REFERENCE, 0);
+    }
+
+    #[test]
+    fn render_half_screen_triangle() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            let (target_entity, renderer_entity, pipeline_entity) =
+                assemble_test_scene(&mut world, backend);
+
+            world.spawn(
+                RenderPassBundle::draw(
+                    0,
+                    Some("Render Test Pass".into()),
+                    clear_red(target_entity),
+                    None,
+                    pipeline_entity,
+                    vec![],
+                    None,
+                    vec![],
+                    vec![],
+                    None,
+                    None,
+                    None,
+                    None,
+                    (0..4, 0..1),
+                    renderer_entity,
+                )
+                .build(),
+            );
+
+            let image = render_test_frame(&mut world);
+            assert_image_matches_reference(&image, HALF_SCREEN_REFERENCE, 1);
+        });
+    }
+
+    #[test]
+    fn render_bundled_half_screen_triangle() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            let (target_entity, renderer_entity, pipeline_entity) =
+                assemble_test_scene(&mut world, backend);
+
+            let bundle_entity = world.spawn(
+                RenderBundleBundle::draw(
+                    RenderBundleEncoderDescriptor {
+                        label: Some("Render Test Bundle"),
+                        color_formats: &[TextureFormat::Rgba8Unorm],
+                        depth_stencil: None,
+                        sample_count: 1,
+                        multiview: None,
+                    },
+                    pipeline_entity,
+                    vec![],
+                    None,
+                    vec![],
+                    (0..4, 0..1),
+                )
+                .build(),
+            );
+
+            world.spawn(
+                RenderPassBundle::execute_bundles(
+                    0,
+                    Some("Render Test Bundle Pass".into()),
+                    clear_red(target_entity),
+                    None,
+                    vec![bundle_entity],
+                    renderer_entity,
+                )
+                .build(),
+            );
+
+            let image = render_test_frame(&mut world);
+            assert_images_similar(&image, &half_screen_image(), 1);
+
+            // The bundle is recorded once and reused on subsequent frames,
+            // so it keeps drawing after its pipeline component has been reset
+            *world
+                .get_mut::<RenderPipelineComponent>(pipeline_entity)
+                .unwrap() = RenderPipelineComponent::default();
+
+            let image = render_test_frame(&mut world);
+            assert_images_similar(&image, &half_screen_image(), 1);
+            assert!(world
+                .get::<RenderBundleComponent>(bundle_entity)
+                .unwrap()
+                .get()
+                .is_some());
+        });
+    }
+
+    #[test]
+    fn render_bundle_waits_for_device_and_pipeline() {
+        let mut world = World::new();
+        let pipeline_entity = world.spawn(());
+        world.despawn(pipeline_entity).unwrap();
+
+        let bundle_entity = world.spawn(
+            RenderBundleBundle::draw(
+                RenderBundleEncoderDescriptor {
+                    label: Some("Pending Render Bundle"),
+                    color_formats: &[TextureFormat::Rgba8Unorm],
+                    depth_stencil: None,
+                    sample_count: 1,
+                    multiview: None,
+                },
+                pipeline_entity,
+                vec![],
+                None,
+                vec![],
+                (0..4, 0..1),
+            )
+            .build(),
+        );
+
+        let is_pending = |world: &World| {
+            world
+                .get::<RenderBundleComponent>(bundle_entity)
+                .unwrap()
+                .is_pending()
+        };
+
+        // Without a device, recording waits for one to be created
+        create_render_bundles_system(&mut world);
+        assert!(is_pending(&world));
+
+        with_headless_backend(|backend| {
+            world.spawn(backend);
+
+            // The pipeline entity no longer exists, so the bundle is left pending
+            create_render_bundles_system(&mut world);
+            assert!(is_pending(&world));
+        });
+    }
+
+    #[test]
+    fn render_equal_order_passes_in_spawn_order() {
+        for (entry_points, color) in [
+            (["fs_main", "fs_blue"], [0, 0, 255, 255]),
+            (["fs_blue", "fs_main"], [0, 255, 0, 255]),
+        ] {
+            with_headless_backend(|backend| {
+                let mut world = World::new();
+                let (target_entity, renderer_entity, _) = assemble_test_scene(&mut world, backend);
+
+                // Both passes share an order, so the last one spawned draws over the first
+                for (i, entry_point) in entry_points.iter().enumerate() {
+                    let pipeline_entity = spawn_test_pipeline(&mut world, entry_point);
+
+                    let load = if i == 0 {
+                        LoadOp::Clear(Color::RED)
+                    } else {
+                        LoadOp::Load
+                    };
+
+                    world.spawn(
+                        RenderPassBuilder::new(0, renderer_entity)
+                            .color_attachment(target_entity, None, Operations { load, store: true })
+                            .pipeline(pipeline_entity)
+                            .draw(0..4, 0..1)
+                            .build(),
+                    );
+                }
+
+                let mut expected = half_screen_image();
+                for y in 0..HEIGHT {
+                    for x in 0..WIDTH / 2 {
+                        expected.set_pixel(x, y, color);
+                    }
+                }
+
+                let image = render_test_frame(&mut world);
+                assert_images_similar(&image, &expected, 1);
+            });
+        }
+    }
+
+    #[test]
+    fn render_timestamped_pass() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            let (target_entity, renderer_entity, pipeline_entity) =
+                assemble_test_scene(&mut world, backend);
+
+            let query_set_entity = world.spawn(QuerySetBundle::new(QuerySetDescriptor {
+                label: Some("Timestamps"),
+                ty: QueryType::Timestamp,
+                count: 2,
+            }));
+
+            let read_entity = world.spawn(BufferBundle::new(BufferDescriptor {
+                label: Some("Timestamp Read Buffer"),
+                size: 16,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+            world
+                .insert(read_entity, BufferReadBundle::new([0u64; 2], 0, read_entity))
+                .unwrap();
+
+            world
+                .insert(
+                    query_set_entity,
+                    QuerySetResolveBundle::<[u64; 2]>::new(0..2, read_entity, renderer_entity),
+                )
+                .unwrap();
+
+            world.spawn(
+                RenderPassBuilder::new(0, renderer_entity)
+                    .color_attachment(target_entity, None, clear_red(target_entity)[0].ops)
+                    .pipeline(pipeline_entity)
+                    .timestamp(query_set_entity, 0, 1)
+                    .draw(0..4, 0..1)
+                    .build(),
+            );
+
+            // Timestamped passes render as usual, whether or not timestamps are supported
+            let image = render_test_frame(&mut world);
+            assert_images_similar(&image, &half_screen_image(), 1);
+
+            let supported = {
+                let mut query = world.query::<&DeviceComponent>();
+                let (_, device) = query.into_iter().next().unwrap();
+                device.features().contains(Features::TIMESTAMP_QUERY)
+            };
+
+            let query_set = world.get::<QuerySetComponent>(query_set_entity).unwrap();
+            assert_eq!(query_set.is_ready(), supported);
+            assert_eq!(query_set.is_dropped(), !supported);
+            drop(query_set);
+
+            if !supported {
+                println!("Device is missing TIMESTAMP_QUERY, skipping timestamp resolve");
+                return;
+            }
+
+            create_command_encoders_system(&mut world);
+            encode_passes_system(&mut world);
+            resolve_query_sets_system::<[u64; 2]>(&mut world);
+            flush_command_encoders_system(&mut world);
+            submit_command_buffers_system(&mut world);
+
+            buffer_read_system::<[u64; 2]>(&mut world);
+            device_poll_system(&Maintain::Wait)(&mut world);
+            buffer_read_system::<[u64; 2]>(&mut world);
+
+            let timestamps = world.get::<Changed<[u64; 2]>>(read_entity).unwrap();
+            assert!(timestamps.get_changed());
+
+            let [begin, end] = **timestamps;
+            assert!(end >= begin);
+        });
+    }
+
+    #[test]
+    fn render_multi_draw_with_missing_count_buffer() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            let (target_entity, renderer_entity, pipeline_entity) =
+                assemble_test_scene(&mut world, backend);
+
+            static DRAW_ARGS: [u32; 4] = [4, 1, 0, 0];
+            let indirect_entity = world.spawn(BufferInitBundle::new(BufferInitDescriptor {
+                label: Some("Multi-Draw Indirect Buffer"),
+                contents: bytemuck::cast_slice(&DRAW_ARGS),
+                usage: BufferUsages::INDIRECT,
+            }));
+
+            let count_entity = world.spawn(());
+            world.despawn(count_entity).unwrap();
+
+            world.spawn(
+                RenderPassBuilder::new(0, renderer_entity)
+                    .color_attachment(target_entity, None, clear_red(target_entity)[0].ops)
+                    .pipeline(pipeline_entity)
+                    .multi_draw_indirect(
+                        indirect_entity,
+                        0,
+                        MultiDrawCount::Buffer {
+                            buffer: count_entity,
+                            offset: 0,
+                            max_count: 1,
+                        },
+                    )
+                    .build(),
+            );
+
+            // The draw is skipped instead of panicking, and the pass still clears its target
+            let image = render_test_frame(&mut world);
+            assert_images_similar(&image, &Image::filled(WIDTH, HEIGHT, [255, 0, 0, 255]), 1);
+        });
+    }
+
+    #[test]
+    fn render_toggled_scissor_rect() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            let (target_entity, renderer_entity, pipeline_entity) =
+                assemble_test_scene(&mut world, backend);
+
+            let top = RenderPassScissorRectDesc {
+                x: 0,
+                y: 0,
+                width: WIDTH,
+                height: HEIGHT / 2,
+            };
+
+            let bottom = RenderPassScissorRectDesc {
+                y: HEIGHT / 2,
+                ..top
+            };
+
+            let pass_entity = world.spawn(
+                RenderPassBuilder::new(0, renderer_entity)
+                    .color_attachment(target_entity, None, clear_red(target_entity)[0].ops)
+                    .pipeline(pipeline_entity)
+                    .scissor_rect(top)
+                    .draw(0..4, 0..1)
+                    .build(),
+            );
+
+            // Only the scissored half of the triangle is drawn
+            let scissored_image = |scissor_rect: RenderPassScissorRectDesc| {
+                let mut expected = Image::filled(WIDTH, HEIGHT, [255, 0, 0, 255]);
+                for y in scissor_rect.y..scissor_rect.y + scissor_rect.height {
+                    for x in 0..WIDTH / 2 {
+                        expected.set_pixel(x, y, [0, 255, 0, 255]);
+                    }
+                }
+                expected
+            };
+
+            for scissor_rect in [top, bottom, top] {
+                set_render_pass_scissor_rect(&mut world, pass_entity, scissor_rect);
+
+                let image = render_test_frame(&mut world);
+                assert_images_similar(&image, &scissored_image(scissor_rect), 1);
+
+                let scissor_rect = world
+                    .get::<RenderPassScissorRectComponent>(pass_entity)
+                    .unwrap();
+                assert!(!scissor_rect.get_changed());
+            }
+        });
+    }
+}
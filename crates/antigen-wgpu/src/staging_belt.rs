@@ -1,331 +1,393 @@
+use std::{
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Waker},
+};
+
 use antigen_core::{
-    impl_read_write_lock, AddIndirectComponent, Changed, ChangedTrait, GetIndirect,
-    IndirectComponent, LazyComponent, ReadWriteLock, RwLock, RwLockReadGuard, RwLockWriteGuard,
-    Usage,
+    AsUsage, Changed, ChangedFlag, ChangedTrait, Construct, Indirect, LazyComponent, Usage, With,
 };
-use legion::{world::SubWorld, Entity, IntoQuery, World};
+use hecs::{Entity, World};
+use parking_lot::Mutex;
 use wgpu::{
-    util::StagingBelt, Buffer, BufferAddress, BufferSize, CommandEncoder, CommandEncoderDescriptor,
-    Device,
+    util::StagingBelt, Buffer, BufferAddress, BufferSize, CommandEncoder,
+    CommandEncoderDescriptor, Device,
 };
 
-use std::{
-    collections::BTreeMap,
-    future::Future,
-    marker::PhantomData,
-    sync::atomic::{AtomicUsize, Ordering},
+use crate::{
+    buffer_size_of, buffer_write_fits, BufferComponent, CommandBuffersComponent, DeviceComponent,
+    UNIFORM_STRUCT_ALIGNMENT,
 };
 
-use crate::{BufferComponent, CommandBuffersComponent, ToBytes};
-
-// Staging belt
-static STAGING_BELT_ID_HEAD: AtomicUsize = AtomicUsize::new(0);
+type RecallFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct StagingBeltId(usize);
+// Staging belt handle
+//
+// The wgpu staging belt and its recall futures are not Sync,
+// so they're kept behind mutexes that are only ever accessed mutably
+pub struct StagingBeltComponent {
+    chunk_size: BufferAddress,
+    staging_belt: Mutex<LazyComponent<StagingBelt>>,
+    encoder: Option<CommandEncoder>,
+    recalls: Mutex<Vec<RecallFuture>>,
+}
 
-pub struct StagingBeltManager(BTreeMap<StagingBeltId, StagingBelt>);
+impl StagingBeltComponent {
+    pub fn new(chunk_size: BufferAddress) -> Self {
+        StagingBeltComponent {
+            chunk_size,
+            staging_belt: Default::default(),
+            encoder: None,
+            recalls: Default::default(),
+        }
+    }
 
-impl StagingBeltManager {
-    pub fn new() -> Self {
-        StagingBeltManager(Default::default())
+    pub fn chunk_size(&self) -> BufferAddress {
+        self.chunk_size
     }
 
-    pub fn create_staging_belt(&mut self, chunk_size: BufferAddress) -> StagingBeltId {
-        let staging_belt = StagingBelt::new(chunk_size);
-        let id = STAGING_BELT_ID_HEAD.fetch_add(1, Ordering::Relaxed);
-        let id = StagingBeltId(id);
-        self.0.insert(id, staging_belt);
-        id
+    pub fn is_pending(&mut self) -> bool {
+        self.staging_belt.get_mut().is_pending()
     }
 
+    // Record a write of `data` into `target` at `offset`,
+    // creating the belt's command encoder if this is the first write since the last flush
     pub fn write_buffer(
         &mut self,
         device: &Device,
-        encoder: &mut CommandEncoder,
         target: &Buffer,
         offset: BufferAddress,
         size: BufferSize,
-        belt_id: &StagingBeltId,
         data: &[u8],
     ) {
-        self.0
-            .get_mut(belt_id)
-            .unwrap()
-            .write_buffer(encoder, target, offset, size, device)
-            .copy_from_slice(data);
-    }
+        let staging_belt = self.staging_belt.get_mut();
+        let staging_belt = if let LazyComponent::Ready(staging_belt) = staging_belt {
+            staging_belt
+        } else {
+            panic!("Staging belt is not ready");
+        };
 
-    pub fn finish(&mut self, belt_id: &StagingBeltId) {
-        self.0.get_mut(belt_id).unwrap().finish()
-    }
+        let encoder = self.encoder.get_or_insert_with(|| {
+            device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Staging Belt Encoder"),
+            })
+        });
 
-    pub fn recall(&mut self, belt_id: &StagingBeltId) -> impl Future + Send {
-        self.0.get_mut(belt_id).unwrap().recall()
+        staging_belt
+            .write_buffer(encoder, target, offset, size, device)
+            .copy_from_slice(data);
     }
 }
 
-// Staging belt handle
-pub struct StagingBeltComponent {
-    chunk_size: BufferAddress,
-    staging_belt: RwLock<LazyComponent<StagingBeltId>>,
-    map_closures: RwLock<Vec<Box<dyn Fn(&World, &mut StagingBeltManager) + Send + Sync + 'static>>>,
+#[derive(hecs::Bundle)]
+pub struct StagingBeltBundle {
+    staging_belt: Changed<StagingBeltComponent>,
+    command_buffers_entity:
+        Usage<StagingBeltComponent, Indirect<&'static mut CommandBuffersComponent>>,
 }
 
-impl_read_write_lock!(
-    StagingBeltComponent,
-    staging_belt,
-    LazyComponent<StagingBeltId>
-);
-
-impl StagingBeltComponent {
-    pub fn new(chunk_size: BufferAddress) -> Self {
-        StagingBeltComponent {
-            chunk_size,
-            staging_belt: RwLock::new(LazyComponent::Pending),
-            map_closures: Default::default(),
-        }
-    }
-
-    pub fn chunk_size(&self) -> &BufferAddress {
-        &self.chunk_size
-    }
-
-    pub fn map(&self, f: impl Fn(&World, &mut StagingBeltManager) + Send + Sync + 'static) {
-        self.map_closures.write().push(Box::new(f));
-    }
-
-    pub fn flush_map_closures(&self, world: &World, staging_belt_manager: &mut StagingBeltManager) {
-        for f in self.map_closures.write().drain(..) {
-            f(world, staging_belt_manager);
+impl StagingBeltBundle {
+    pub fn new(chunk_size: BufferAddress, command_buffers_entity: Entity) -> Self {
+        let staging_belt = Changed::new(StagingBeltComponent::new(chunk_size), false);
+        let command_buffers_entity =
+            StagingBeltComponent::as_usage(Indirect::construct(command_buffers_entity));
+        StagingBeltBundle {
+            staging_belt,
+            command_buffers_entity,
         }
     }
 }
 
 // Staging belt buffer write operation
 pub struct StagingBeltWriteComponent<T> {
-    offset: RwLock<BufferAddress>,
-    size: RwLock<BufferSize>,
+    offset: BufferAddress,
     _phantom: PhantomData<T>,
 }
 
-impl<T> ReadWriteLock<BufferAddress> for StagingBeltWriteComponent<T> {
-    fn read(&self) -> RwLockReadGuard<BufferAddress> {
-        self.offset.read()
-    }
-
-    fn write(&self) -> RwLockWriteGuard<BufferAddress> {
-        self.offset.write()
-    }
-}
-
-impl<T> ReadWriteLock<BufferSize> for StagingBeltWriteComponent<T> {
-    fn read(&self) -> RwLockReadGuard<BufferSize> {
-        self.size.read()
-    }
-
-    fn write(&self) -> RwLockWriteGuard<BufferSize> {
-        self.size.write()
-    }
-}
-
 impl<T> StagingBeltWriteComponent<T> {
-    pub fn new(offset: BufferAddress, size: BufferSize) -> Self {
+    pub fn new(offset: BufferAddress) -> Self {
         StagingBeltWriteComponent {
-            offset: RwLock::new(offset),
-            size: RwLock::new(size),
+            offset,
             _phantom: Default::default(),
         }
     }
-}
 
-pub fn assemble_staging_belt(
-    cmd: &mut legion::systems::CommandBuffer,
-    entity: legion::Entity,
-    chunk_size: BufferAddress,
-) {
-    cmd.add_component(
-        entity,
-        Changed::new(StagingBeltComponent::new(chunk_size), false),
-    )
+    pub fn offset(&self) -> BufferAddress {
+        self.offset
+    }
 }
 
-pub fn assemble_staging_belt_data_with_usage<U, T>(
-    cmd: &mut legion::systems::CommandBuffer,
-    entity: legion::Entity,
-    data: T,
-    offset: BufferAddress,
-    size: BufferSize,
-) where
-    U: Send + Sync + 'static,
-    T: legion::storage::Component,
-{
-    cmd.add_component(entity, Changed::new(data, true));
-    cmd.add_component(entity, StagingBeltWriteComponent::<T>::new(offset, size));
-    cmd.add_indirect_component_self::<Changed<StagingBeltComponent>>(entity);
-    cmd.add_indirect_component_self::<Usage<U, BufferComponent>>(entity);
-    cmd.add_indirect_component_self::<CommandBuffersComponent>(entity);
+#[derive(hecs::Bundle)]
+pub struct StagingBeltDataBundle<T> {
+    data: Changed<T>,
+    staging_belt_write: StagingBeltWriteComponent<T>,
+    buffer_entity: Usage<StagingBeltWriteComponent<T>, Indirect<&'static BufferComponent>>,
+    staging_belt_entity:
+        Usage<StagingBeltWriteComponent<T>, Indirect<&'static mut Changed<StagingBeltComponent>>>,
 }
 
-// Initialize staging belts
-pub fn create_staging_belt_thread_local(
-    world: &World,
-    staging_belt_manager: &mut StagingBeltManager,
-) {
-    <&Changed<StagingBeltComponent>>::query().for_each(world, |staging_belt| {
-        if staging_belt.read().is_pending() {
-            let staging_belt_id =
-                staging_belt_manager.create_staging_belt(*staging_belt.chunk_size());
-            staging_belt.write().set_ready(staging_belt_id);
-            println!("Created staging belt with ID {:?}", staging_belt_id);
+impl<T> StagingBeltDataBundle<T> {
+    pub fn new(
+        data: T,
+        offset: BufferAddress,
+        buffer_entity: Entity,
+        staging_belt_entity: Entity,
+    ) -> Self {
+        let data = Changed::<T>::construct(data).with(ChangedFlag(true));
+        let staging_belt_write = StagingBeltWriteComponent::<T>::new(offset);
+        let buffer_entity =
+            StagingBeltWriteComponent::<T>::as_usage(Indirect::construct(buffer_entity));
+        let staging_belt_entity =
+            StagingBeltWriteComponent::<T>::as_usage(Indirect::construct(staging_belt_entity));
+        StagingBeltDataBundle {
+            data,
+            staging_belt_write,
+            buffer_entity,
+            staging_belt_entity,
         }
-    })
+    }
 }
 
-// Write data to buffer via staging belt
-#[legion::system(par_for_each)]
-#[read_component(Changed<StagingBeltComponent>)]
-pub fn staging_belt_write<
-    T: Send + Sync + 'static,
-    L: ReadWriteLock<V> + Send + Sync + 'static,
-    V: ToBytes,
->(
-    world: &SubWorld,
-    entity: &Entity,
-    staging_belt_write: &StagingBeltWriteComponent<L>,
-    staging_belt: &IndirectComponent<Changed<StagingBeltComponent>>,
-) {
-    let entity = *entity;
+// Initialize pending staging belts
+pub fn create_staging_belts_system(world: &mut World) {
+    for (entity, staging_belt) in world.query_mut::<&mut Changed<StagingBeltComponent>>() {
+        let chunk_size = staging_belt.chunk_size();
+        let staging_belt = staging_belt.staging_belt.get_mut();
+        if staging_belt.is_pending() {
+            staging_belt.set_ready_with(StagingBelt::new(chunk_size));
+            println!(
+                "Created staging belt with chunk size {} for entity {:?}",
+                chunk_size, entity
+            );
+        }
+    }
+}
 
-    let staging_belt = world.get_indirect(staging_belt).unwrap();
+// Write changed data to its buffer via the corresponding staging belt
+//
+// Writes are recorded into the belt's encoder,
+// and only reach the GPU once staging_belt_flush_system has run
+pub fn staging_belt_write_system<T: bytemuck::Pod + Send + Sync + 'static>(world: &mut World) {
+    let mut query = world.query::<&DeviceComponent>();
+    let (_, device) = if let Some(components) = query.into_iter().next() {
+        components
+    } else {
+        return;
+    };
+
+    let mut query = world.query::<(
+        &StagingBeltWriteComponent<T>,
+        &Changed<T>,
+        &Usage<StagingBeltWriteComponent<T>, Indirect<&BufferComponent>>,
+        &Usage<StagingBeltWriteComponent<T>, Indirect<&mut Changed<StagingBeltComponent>>>,
+    )>();
+
+    for (_, (staging_belt_write, data_component, buffer, staging_belt)) in query.into_iter() {
+        if !data_component.get_changed() {
+            continue;
+        }
 
-    let offset = *ReadWriteLock::<BufferAddress>::read(staging_belt_write);
-    let size = *ReadWriteLock::<BufferSize>::read(staging_belt_write);
+        let bytes = bytemuck::bytes_of(&**data_component);
+        let offset = staging_belt_write.offset();
+        if !buffer_write_fits::<T>(world, buffer.entity(), offset, bytes.len()) {
+            data_component.set_changed(false);
+            continue;
+        }
 
-    staging_belt.map(move |world, staging_belt_manager| {
-        let device = if let Some(device) = <&Device>::query().iter(world).next() {
-            device
+        let mut query = buffer.get(world);
+        let buffer = query.get().unwrap_or_else(|| {
+            panic!(
+                "No buffer component for data {}",
+                std::any::type_name::<T>()
+            )
+        });
+
+        let buffer = buffer.read();
+        let buffer = if let LazyComponent::Ready(buffer) = &*buffer {
+            buffer
         } else {
-            return;
+            continue;
         };
 
-        let (
-            data_component,
-            staging_belt,
-            buffer,
-            command_buffers,
-        ) = if let Ok(components) = <(
-            &Changed<L>,
-            &IndirectComponent<Changed<StagingBeltComponent>>,
-            &IndirectComponent<Usage<T, BufferComponent>>,
-            &IndirectComponent<CommandBuffersComponent>,
-        )>::query()
-            .get(world, entity) {
-                components
-            } else {
-                return;
-            };
-
-        let staging_belt_component = world.get_indirect(staging_belt).unwrap();
-        let buffer = world.get_indirect(buffer).unwrap();
-        let command_buffers = world.get_indirect(command_buffers).unwrap();
-
-        if data_component.get_changed() {
-            let staging_belt = staging_belt_component.read();
-            let staging_belt = if let LazyComponent::Ready(staging_belt) = &*staging_belt {
-                staging_belt
-            } else {
-                return;
-            };
-
-            let buffer = buffer.read();
-            let buffer = if let LazyComponent::Ready(buffer) = &*buffer {
-                buffer
-            } else {
-                return;
-            };
-
-            let data = data_component.read();
-            let bytes = data.to_bytes();
-
-            let mut encoder =
-                device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        let mut query = staging_belt.get(world);
+        let staging_belt = query.get().unwrap_or_else(|| {
+            panic!(
+                "No staging belt component for data {}",
+                std::any::type_name::<T>()
+            )
+        });
 
-            println!(
-                    "Writing {} bytes to {} buffer at offset {} with size {} via staging belt with id {:?}",
-                    bytes.len(),
-                    std::any::type_name::<T>(),
-                    offset,
-                    size,
-                    staging_belt,
-                );
-
-            staging_belt_manager.write_buffer(
-                device,
-                &mut encoder,
-                buffer,
-                offset,
-                size,
-                &*staging_belt,
-                bytes,
-            );
+        if staging_belt.is_pending() {
+            continue;
+        }
 
-            command_buffers.write().push(encoder.finish());
+        let size = BufferSize::new(bytes.len() as BufferAddress)
+            .expect("Staging belt writes must be non-empty");
 
-            data_component.set_changed(false);
-            staging_belt_component.set_changed(true);
-        }
-    });
+        staging_belt.write_buffer(device, buffer, offset, size, bytes);
+
+        data_component.set_changed(false);
+    }
 }
 
-pub fn staging_belt_flush_thread_local(
-    world: &World,
-    staging_belt_manager: &mut StagingBeltManager,
+// Staging belt counterpart to buffer_write_struct_system
+pub fn staging_belt_write_struct_system<T: bytemuck::Pod + Send + Sync + 'static>(
+    world: &mut World,
 ) {
-    for staging_belt_component in <&Changed<StagingBeltComponent>>::query().iter(world) {
-        staging_belt_component.flush_map_closures(world, staging_belt_manager);
+    assert!(
+        buffer_size_of::<T>().is_multiple_of(UNIFORM_STRUCT_ALIGNMENT),
+        "Size of {} is not a multiple of {} bytes, and is missing std140 padding",
+        std::any::type_name::<T>(),
+        UNIFORM_STRUCT_ALIGNMENT
+    );
+
+    staging_belt_write_system::<T>(world);
+}
+
+// Finish the encoders of staging belts that have been written to,
+// pushing the resulting command buffers for submission and flagging the belts for recall
+pub fn staging_belt_flush_system(world: &mut World) {
+    let mut query = world.query::<(
+        &mut Changed<StagingBeltComponent>,
+        &Usage<StagingBeltComponent, Indirect<&mut CommandBuffersComponent>>,
+    )>();
+
+    for (entity, (staging_belt, command_buffers)) in query.into_iter() {
+        if let Some(encoder) = staging_belt.encoder.take() {
+            let mut query = command_buffers.get(world);
+            let command_buffers = query.get().unwrap();
+
+            println!("Flushing staging belt encoder for entity {:?}", entity);
+            command_buffers.push(encoder.finish());
+            staging_belt.set_changed(true);
+        }
     }
 }
 
-pub fn staging_belt_finish_thread_local(
-    world: &World,
-    staging_belt_manager: &mut StagingBeltManager,
-) {
-    <&Changed<StagingBeltComponent>>::query().for_each(world, |staging_belt| {
+// Unmap the chunks used by flushed staging belts
+//
+// Must run between staging_belt_flush_system and command buffer submission,
+// as writes recorded in the meantime would have their chunk closed before being flushed
+pub fn staging_belt_finish_system(world: &mut World) {
+    for (_, staging_belt) in world.query_mut::<&mut Changed<StagingBeltComponent>>() {
         if !staging_belt.get_changed() {
-            return;
+            continue;
         }
 
-        let staging_belt = staging_belt.read();
-        let staging_belt = if let LazyComponent::Ready(staging_belt) = &*staging_belt {
-            staging_belt
-        } else {
-            return;
-        };
-        staging_belt_manager.finish(staging_belt);
-        println!("Finished staging belt with id {:?}", staging_belt);
-    });
+        if let LazyComponent::Ready(staging_belt) = staging_belt.staging_belt.get_mut() {
+            staging_belt.finish();
+        }
+    }
 }
 
-pub fn staging_belt_recall_thread_local(
-    world: &World,
-    staging_belt_manager: &mut StagingBeltManager,
-) {
-    <&Changed<StagingBeltComponent>>::query().for_each(world, |staging_belt_component| {
-        if !staging_belt_component.get_changed() {
-            return;
+// Recall the chunks of finished staging belts once their command buffers have been submitted
+//
+// Chunks are only returned to the belt once their recall future resolves,
+// which in turn relies on the device being polled
+pub fn staging_belt_recall_system(world: &mut World) {
+    let mut context = Context::from_waker(Waker::noop());
+
+    for (_, staging_belt) in world.query_mut::<&mut Changed<StagingBeltComponent>>() {
+        if staging_belt.get_changed() {
+            if let LazyComponent::Ready(belt) = staging_belt.staging_belt.get_mut() {
+                let recall = belt.recall();
+                staging_belt.recalls.get_mut().push(Box::pin(recall));
+            }
+            staging_belt.set_changed(false);
         }
 
-        let staging_belt = staging_belt_component.read();
-        let staging_belt = if let LazyComponent::Ready(staging_belt) = &*staging_belt {
-            staging_belt
-        } else {
-            return;
-        };
+        staging_belt
+            .recalls
+            .get_mut()
+            .retain_mut(|recall| recall.as_mut().poll(&mut context).is_pending());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wgpu::{BufferDescriptor, BufferUsages, Maintain, MapMode};
+
+    use crate::{
+        create_buffers_system, submit_command_buffers_system, with_headless_backend,
+        BufferBundle,
+    };
+
+    enum TestTag {}
+    type TestData = Usage<TestTag, [f32; 4]>;
+
+    fn read_buffer(world: &World, entity: Entity) -> [f32; 4] {
+        let mut query = world.query::<&DeviceComponent>();
+        let (_, device) = query.into_iter().next().unwrap();
+
+        let buffer = world.get::<BufferComponent>(entity).unwrap();
+        let buffer = buffer.read();
+        let buffer = buffer.get().unwrap();
 
-        // Ignore resulting future - this assumes the wgpu device is being polled in wait mode
-        let _ = staging_belt_manager.recall(staging_belt);
-        staging_belt_component.set_changed(false);
-        println!("Recalled staging belt with id {:?}", staging_belt);
-    });
+        let slice = buffer.slice(..);
+        let map = slice.map_async(MapMode::Read);
+        device.poll(Maintain::Wait);
+        pollster::block_on(map).unwrap();
+
+        let data = *bytemuck::from_bytes::<[f32; 4]>(&slice.get_mapped_range());
+        buffer.unmap();
+        data
+    }
+
+    fn staging_belt_frame(world: &mut World) {
+        create_staging_belts_system(world);
+        staging_belt_write_system::<TestData>(world);
+        staging_belt_flush_system(world);
+        staging_belt_finish_system(world);
+        submit_command_buffers_system(world);
+        staging_belt_recall_system(world);
+    }
+
+    #[test]
+    fn staging_belt_write() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            world.spawn(backend);
+
+            let buffer_entity = world.spawn(BufferBundle::new(BufferDescriptor {
+                label: Some("Staging Belt Test Buffer"),
+                size: std::mem::size_of::<[f32; 4]>() as BufferAddress,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }));
+            create_buffers_system(&mut world);
+
+            let command_buffers_entity = world.spawn((CommandBuffersComponent::default(),));
+            let staging_belt_entity = world.spawn(StagingBeltBundle::new(
+                std::mem::size_of::<[f32; 4]>() as BufferAddress,
+                command_buffers_entity,
+            ));
+
+            let data_entity = world.spawn(StagingBeltDataBundle::new(
+                TestData::construct([1.0, 2.0, 3.0, 4.0]),
+                0,
+                buffer_entity,
+                staging_belt_entity,
+            ));
+
+            staging_belt_frame(&mut world);
+            assert_eq!(read_buffer(&world, buffer_entity), [1.0, 2.0, 3.0, 4.0]);
+            assert!(!world
+                .get::<Changed<StagingBeltComponent>>(staging_belt_entity)
+                .unwrap()
+                .get_changed());
+
+            // Recalled chunks are reused for subsequent writes
+            ***world.get_mut::<Changed<TestData>>(data_entity).unwrap() = [5.0, 6.0, 7.0, 8.0];
+            world
+                .get::<Changed<TestData>>(data_entity)
+                .unwrap()
+                .set_changed(true);
+
+            staging_belt_frame(&mut world);
+            assert_eq!(read_buffer(&world, buffer_entity), [5.0, 6.0, 7.0, 8.0]);
+        });
+    }
 }
@@ -1,23 +1,35 @@
-use std::ops::Deref;
+use std::{
+    num::NonZeroU8,
+    ops::Deref,
+    task::{Context, Poll, Waker},
+};
 
 use super::{
-    BufferInitDescriptorComponent, BufferWriteComponent, CommandBuffersComponent, SurfaceComponent,
-    SurfaceTextureComponent, TextureDescriptorComponent, TextureViewComponent,
-    TextureViewDescriptorComponent, TextureWriteComponent,
+    BufferInitDescriptorComponent, BufferMapState, BufferReadComponent, BufferWriteComponent,
+    CommandBuffersComponent, SurfaceComponent, SurfaceTextureComponent,
+    TextureDescriptorComponent, TextureViewComponent, TextureViewDescriptorComponent,
+    TextureWriteComponent,
 };
 use crate::{
-    AdapterComponent, BufferComponent, BufferDescriptorComponent, CommandEncoderComponent,
-    DeviceComponent, InstanceComponent, QueueComponent, SamplerComponent,
-    SamplerDescriptorComponent, ShaderModuleComponent, ShaderModuleDescriptorComponent,
-    ShaderModuleDescriptorSpirVComponent, SurfaceConfigurationComponent, TextureComponent,
+    buffer_size_of, AdapterComponent, BindGroupComponent, BufferComponent,
+    BufferDescriptorComponent, CommandEncoderComponent, DeviceComponent, GrowableBufferComponent,
+    InstanceComponent, QuerySetComponent, QuerySetDescriptorComponent, QuerySetResolveComponent,
+    QuerySetResolveEncoderComponent, QuerySetResolveReadComponent, QueueComponent,
+    SamplerComponent, SamplerDescriptorComponent, ShaderModuleComponent,
+    ShaderModuleDescriptorComponent, ShaderModuleDescriptorSpirVComponent,
+    SurfaceConfigurationComponent, TextureComponent, MAX_ANISOTROPY, UNIFORM_STRUCT_ALIGNMENT,
 };
 
 use antigen_core::{Changed, ChangedTrait, Indirect, LazyComponent, Usage};
-use antigen_winit::{WindowComponent, WindowEntityMap, WindowEventComponent, WindowSizeComponent};
+use antigen_winit::{WindowComponent, WindowEventComponent, WindowSizeComponent};
 
 use hecs::{Entity, World};
 
-use wgpu::{util::DeviceExt, Maintain};
+use wgpu::{
+    util::DeviceExt, Adapter, BufferAddress, BufferDescriptor, BufferUsages,
+    CommandEncoderDescriptor, DownlevelFlags, Features, Maintain, MapMode, QueryType,
+    COPY_BUFFER_ALIGNMENT,
+};
 
 pub fn device_poll_system(maintain: &Maintain) -> impl FnMut(&mut World) {
     let maintain = *maintain;
@@ -224,8 +236,17 @@ pub fn create_shader_modules_system(world: &mut World) {
     }
 }
 
-/// Create pending usage-tagged shader modules, recreating them if a Changed flag is set
+/// Create pending usage-tagged SPIR-V shader modules, recreating them if a Changed flag is set
+///
+/// Modules are left pending on devices without `Features::SPIRV_SHADER_PASSTHROUGH`.
 pub fn create_shader_modules_spirv_system<T: Send + Sync + 'static>(world: &mut World) {
+    let mut query = world.query::<&DeviceComponent>();
+    let device = if let Some((_, device)) = query.into_iter().next() {
+        device
+    } else {
+        return;
+    };
+
     let mut query = world.query::<(
         &Usage<T, ShaderModuleDescriptorSpirVComponent>,
         &mut Usage<T, ShaderModuleComponent>,
@@ -235,8 +256,22 @@ pub fn create_shader_modules_spirv_system<T: Send + Sync + 'static>(world: &mut
             continue;
         }
 
-        let mut query = world.query::<&DeviceComponent>();
-        let (_, device) = query.into_iter().next().unwrap();
+        if !device
+            .features()
+            .contains(Features::SPIRV_SHADER_PASSTHROUGH)
+        {
+            // Warn once per load, rather than every time the pending module is revisited
+            if shader_module_desc.get_changed() {
+                shader_module_desc.set_changed(false);
+                println!(
+                    "Warning: Leaving {} spir-v shader module pending, device is missing {:?}",
+                    std::any::type_name::<T>(),
+                    Features::SPIRV_SHADER_PASSTHROUGH
+                );
+            }
+            continue;
+        }
+
         shader_module.set_ready_with(unsafe { device.create_shader_module_spirv(&shader_module_desc) });
 
         shader_module_desc.set_changed(false);
@@ -346,6 +381,30 @@ pub fn create_texture_views_system(world: &mut World) {
     }
 }
 
+/// Clamp a requested sampler anisotropy to a power of two supported by the adapter
+pub fn supported_anisotropy_clamp(
+    adapter: &Adapter,
+    clamp: Option<NonZeroU8>,
+) -> Option<NonZeroU8> {
+    let clamp = clamp?.get();
+
+    if !adapter
+        .get_downlevel_properties()
+        .flags
+        .contains(DownlevelFlags::ANISOTROPIC_FILTERING)
+    {
+        println!(
+            "Adapter does not support anisotropic filtering, ignoring anisotropy clamp {}",
+            clamp
+        );
+        return None;
+    }
+
+    // Round down to the nearest power of two
+    let clamp = clamp.min(MAX_ANISOTROPY);
+    NonZeroU8::new(1 << (7 - clamp.leading_zeros()))
+}
+
 /// Create pending usage-tagged samplers, recreating them if a Changed flag is set
 pub fn create_samplers_system(world: &mut World) {
     let mut query = world.query::<(&SamplerDescriptorComponent, &mut SamplerComponent)>();
@@ -355,9 +414,16 @@ pub fn create_samplers_system(world: &mut World) {
             continue;
         }
 
+        let mut query = world.query::<&AdapterComponent>();
+        let (_, adapter) = query.into_iter().next().unwrap();
+
+        let mut descriptor = (**sampler_descriptor).clone();
+        descriptor.anisotropy_clamp =
+            supported_anisotropy_clamp(adapter, descriptor.anisotropy_clamp);
+
         let mut query = world.query::<&DeviceComponent>();
         let (_, device) = query.into_iter().next().unwrap();
-        sampler.set_ready_with(device.create_sampler(&sampler_descriptor));
+        sampler.set_ready_with(device.create_sampler(&descriptor));
 
         sampler_descriptor.set_changed(false);
 
@@ -365,6 +431,54 @@ pub fn create_samplers_system(world: &mut World) {
     }
 }
 
+// Features a device needs in order to create query sets of a given type
+fn query_type_features(ty: QueryType) -> Features {
+    match ty {
+        QueryType::Occlusion => Features::empty(),
+        QueryType::PipelineStatistics(_) => Features::PIPELINE_STATISTICS_QUERY,
+        QueryType::Timestamp => Features::TIMESTAMP_QUERY,
+    }
+}
+
+/// Create pending query sets, recreating them if a Changed flag is set
+///
+/// Query sets whose type needs a feature the device is missing are dropped instead,
+/// which leaves anything writing to them as a no-op
+pub fn create_query_sets_system(world: &mut World) {
+    let mut query = world.query::<&DeviceComponent>();
+    let (_, device) = if let Some(components) = query.iter().next() {
+        components
+    } else {
+        return;
+    };
+
+    let mut query = world.query::<(&QuerySetDescriptorComponent, &mut QuerySetComponent)>();
+    for (entity, (query_set_descriptor, query_set)) in query.into_iter() {
+        if !query_set.is_pending() && !query_set_descriptor.get_changed() {
+            continue;
+        }
+
+        query_set_descriptor.set_changed(false);
+
+        let features = query_type_features(query_set_descriptor.ty);
+        if !device.features().contains(features) {
+            query_set.set_dropped();
+            println!(
+                "Warning: Skipping query set for entity {:?}, device is missing {:?}",
+                entity, features
+            );
+            continue;
+        }
+
+        query_set.set_ready_with(device.create_query_set(query_set_descriptor));
+
+        println!(
+            "Created query set for entity {:?} with label {:?}",
+            entity, query_set_descriptor.label
+        );
+    }
+}
+
 // Write data to buffer
 pub fn buffer_write_system<T: bytemuck::Pod + Send + Sync + 'static>(world: &mut World) {
     let mut query = world.query::<&QueueComponent>();
@@ -400,6 +514,11 @@ pub fn buffer_write_system<T: bytemuck::Pod + Send + Sync + 'static>(world: &mut
 
             let bytes = bytemuck::bytes_of(data_component.deref());
 
+            if !buffer_write_fits::<T>(world, buffer_entity, buffer_write.offset(), bytes.len()) {
+                data_component.set_changed(false);
+                continue;
+            }
+
             /*
             println!(
                 "Writing {} ({} bytes) to entity {:?} buffer at offset {}",
@@ -416,6 +535,70 @@ pub fn buffer_write_system<T: bytemuck::Pod + Send + Sync + 'static>(world: &mut
     }
 }
 
+// Check that a write of `len` bytes at `offset` fits within its buffer's declared size
+//
+// Overflowing writes are logged and should be skipped rather than submitted,
+// since wgpu would otherwise fail the write with no indication of its source
+pub(crate) fn buffer_write_fits<T>(
+    world: &World,
+    buffer_entity: Entity,
+    offset: BufferAddress,
+    len: usize,
+) -> bool {
+    let (label, capacity) =
+        if let Ok(descriptor) = world.get::<BufferDescriptorComponent>(buffer_entity) {
+            (descriptor.label, descriptor.size)
+        } else if let Ok(descriptor) = world.get::<BufferInitDescriptorComponent>(buffer_entity) {
+            (descriptor.label, descriptor.contents.len() as BufferAddress)
+        } else {
+            return true;
+        };
+
+    let len = len as BufferAddress;
+    if offset + len <= capacity {
+        return true;
+    }
+
+    println!(
+        "Error: Skipping write of {} bytes of {} at offset {} to buffer {:?}, \
+        which has a capacity of {} bytes",
+        len,
+        std::any::type_name::<T>(),
+        offset,
+        label,
+        capacity
+    );
+
+    false
+}
+
+// Write a uniform struct to its buffer in a single call
+//
+// T is expected to mirror its std140 shader layout, with explicit padding fields
+pub fn buffer_write_struct_system<T: bytemuck::Pod + Send + Sync + 'static>(world: &mut World) {
+    assert!(
+        buffer_size_of::<T>().is_multiple_of(UNIFORM_STRUCT_ALIGNMENT),
+        "Size of {} is not a multiple of {} bytes, and is missing std140 padding",
+        std::any::type_name::<T>(),
+        UNIFORM_STRUCT_ALIGNMENT
+    );
+
+    buffer_write_system::<T>(world);
+}
+
+// Copy data to CopyTo targets, then write changed targets to their buffers
+//
+// buffer_write_system only writes data whose Changed flag is set,
+// so running it before copy_to_system leaves the buffer a frame behind
+pub fn copy_and_write_system<U, T>(world: &mut World)
+where
+    U: hecs::Component,
+    T: bytemuck::Pod + PartialEq + Send + Sync + 'static,
+{
+    antigen_core::copy_to_system::<U, T>(world);
+    buffer_write_system::<T>(world);
+}
+
 pub fn buffer_write_slice_system<
     T: Deref<Target = [V]> + Send + Sync + 'static,
     V: bytemuck::Pod + 'static,
@@ -446,6 +629,19 @@ pub fn buffer_write_slice_system<
         });
 
         if data_component.get_changed() {
+            let bytes = bytemuck::cast_slice(data_component.deref());
+
+            grow_buffer(
+                world,
+                buffer_entity,
+                buffer_write.offset() + bytes.len() as BufferAddress,
+            );
+
+            if !buffer_write_fits::<T>(world, buffer_entity, buffer_write.offset(), bytes.len()) {
+                data_component.set_changed(false);
+                continue;
+            }
+
             let buffer = buffer.read();
             let buffer = if let LazyComponent::Ready(buffer) = &*buffer {
                 buffer
@@ -453,8 +649,6 @@ pub fn buffer_write_slice_system<
                 continue;
             };
 
-            let bytes = bytemuck::cast_slice(data_component.deref());
-
             /*
             println!(
                 "Writing {} ({} bytes) to entity {:?} buffer at offset {}",
@@ -471,6 +665,285 @@ pub fn buffer_write_slice_system<
     }
 }
 
+// Reallocate a growable buffer that can't fit `required` bytes, preserving its contents
+//
+// Capacity is doubled until the write fits, and existing contents are copied
+// by a command buffer submitted immediately, ahead of the write that triggered the growth.
+// Buffers written by the GPU rather than through BufferDataBundle can call this directly
+// to reserve space ahead of their passes
+pub fn grow_buffer(world: &World, entity: Entity, required: BufferAddress) {
+    let mut query = if let Ok(query) = world.query_one::<(
+        &mut BufferDescriptorComponent,
+        &BufferComponent,
+        &GrowableBufferComponent,
+    )>(entity)
+    {
+        query
+    } else {
+        return;
+    };
+
+    let (descriptor, buffer, growable) = if let Some(components) = query.get() {
+        components
+    } else {
+        return;
+    };
+
+    if required <= descriptor.size {
+        return;
+    }
+
+    assert!(
+        descriptor
+            .usage
+            .contains(BufferUsages::COPY_SRC | BufferUsages::COPY_DST),
+        "Growable buffer {:?} is missing COPY_SRC or COPY_DST usage",
+        descriptor.label
+    );
+
+    let mut size = descriptor.size.max(COPY_BUFFER_ALIGNMENT);
+    while size < required {
+        size *= 2;
+    }
+
+    let mut query = world.query::<(&DeviceComponent, &QueueComponent)>();
+    let (_, (device, queue)) = if let Some(components) = query.into_iter().next() {
+        components
+    } else {
+        return;
+    };
+
+    let grown = device.create_buffer(&BufferDescriptor {
+        size,
+        ..**descriptor
+    });
+
+    let mut buffer = buffer.write();
+    if let LazyComponent::Ready(old) = &*buffer {
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Buffer Growth Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(old, 0, &grown, 0, descriptor.size);
+        queue.submit(Some(encoder.finish()));
+    }
+    buffer.set_ready_with(grown);
+
+    println!(
+        "Grew buffer {:?} for entity {:?} from {} to {} bytes",
+        descriptor.label, entity, descriptor.size, size
+    );
+
+    descriptor.size = size;
+    growable.set_changed(true);
+}
+
+// Invalidate the bind groups of growable buffers that have been reallocated,
+// leaving them pending so they get recreated against the new buffer.
+// Entities that no longer have a bind group are skipped
+pub fn growable_buffer_bind_groups_system(world: &mut World) {
+    for (_, growable) in world.query::<&GrowableBufferComponent>().into_iter() {
+        if !growable.get_changed() {
+            continue;
+        }
+
+        for entity in growable.entities() {
+            if let Ok(mut bind_group) = world.get_mut::<BindGroupComponent>(*entity) {
+                bind_group.set_pending();
+            }
+        }
+
+        growable.set_changed(false);
+    }
+}
+
+// Read buffer data back into its Changed<T>, flagging it once the read completes
+//
+// Each read moves from unmapped to pending when its map is requested,
+// to mapped once the request completes, and back to unmapped after its data is copied out.
+// Mapping is asynchronous, so a read may stay pending across several runs;
+// a new map is only requested once the previous one has been unmapped.
+// Source buffers must have MAP_READ usage
+pub fn buffer_read_system<T: bytemuck::Pod + Send + Sync + 'static>(world: &mut World) {
+    let mut query = world.query::<&DeviceComponent>();
+    let (_, device) = if let Some(components) = query.into_iter().next() {
+        components
+    } else {
+        return;
+    };
+
+    let mut query = world.query::<(
+        &mut BufferReadComponent<T>,
+        &mut Changed<T>,
+        &Usage<BufferReadComponent<T>, Indirect<&BufferComponent>>,
+    )>();
+
+    let size = buffer_size_of::<T>();
+    let mut context = Context::from_waker(Waker::noop());
+
+    // Request maps for unmapped reads
+    for (_, (buffer_read, _, buffer)) in query.iter() {
+        if !buffer_read.map_state_mut().is_unmapped() {
+            continue;
+        }
+
+        if let Ok(descriptor) = world.get::<BufferDescriptorComponent>(buffer.entity()) {
+            if !descriptor.usage.contains(BufferUsages::MAP_READ) {
+                println!(
+                    "Buffer for data {} is missing MAP_READ usage, skipping read",
+                    std::any::type_name::<T>()
+                );
+                continue;
+            }
+        }
+
+        let mut query = buffer.get(world);
+        let buffer = if let Some(buffer) = query.get() {
+            buffer
+        } else {
+            continue;
+        };
+
+        let buffer = buffer.read();
+        let buffer = if let LazyComponent::Ready(buffer) = &*buffer {
+            buffer
+        } else {
+            continue;
+        };
+
+        let offset = buffer_read.offset();
+        let map = buffer.slice(offset..offset + size).map_async(MapMode::Read);
+        *buffer_read.map_state_mut() = BufferMapState::Pending(Box::pin(map));
+    }
+
+    device.poll(Maintain::Poll);
+
+    // Advance completed maps
+    for (_, (buffer_read, _, _)) in query.iter() {
+        let state = buffer_read.map_state_mut();
+        let result = if let BufferMapState::Pending(map) = state {
+            if let Poll::Ready(result) = map.as_mut().poll(&mut context) {
+                result
+            } else {
+                continue;
+            }
+        } else {
+            continue;
+        };
+
+        *state = match result {
+            Ok(()) => BufferMapState::Mapped,
+            Err(e) => {
+                println!(
+                    "Failed to map buffer for data {}: {}",
+                    std::any::type_name::<T>(),
+                    e
+                );
+                BufferMapState::Unmapped
+            }
+        };
+    }
+
+    // Copy out mapped reads and unmap their buffers
+    for (_, (buffer_read, data_component, buffer)) in query.iter() {
+        if !matches!(buffer_read.map_state_mut(), BufferMapState::Mapped) {
+            continue;
+        }
+
+        // A buffer that has since been dropped or recreated no longer holds the mapping
+        *buffer_read.map_state_mut() = BufferMapState::Unmapped;
+
+        let mut query = buffer.get(world);
+        let buffer = if let Some(buffer) = query.get() {
+            buffer
+        } else {
+            continue;
+        };
+
+        let buffer = buffer.read();
+        let buffer = if let LazyComponent::Ready(buffer) = &*buffer {
+            buffer
+        } else {
+            continue;
+        };
+
+        let offset = buffer_read.offset();
+        {
+            let view = buffer.slice(offset..offset + size).get_mapped_range();
+            **data_component = *bytemuck::from_bytes::<T>(&view);
+        }
+        buffer.unmap();
+
+        data_component.set_changed(true);
+    }
+}
+
+// Resolve query sets into the buffers of their BufferReadComponent<T>
+//
+// Encoded into the resolve encoder, so this must run after the queries are written
+// and before encoders are flushed, with buffer_read_system running after submission.
+// Resolves are skipped while a read is in flight, since its buffer can't be used until unmapped.
+// Read buffers need MAP_READ and COPY_DST usage, and read offsets must be 256-byte aligned.
+// Timestamps are in ticks of Queue::get_timestamp_period nanoseconds
+pub fn resolve_query_sets_system<T: Send + Sync + 'static>(world: &mut World) {
+    let mut query = world.query::<(
+        &QuerySetComponent,
+        &QuerySetResolveComponent,
+        &QuerySetResolveReadComponent<T>,
+        &QuerySetResolveEncoderComponent,
+    )>();
+
+    for (_, (query_set, resolve, read, encoder)) in query.into_iter() {
+        let query_set = if let Some(query_set) = query_set.get() {
+            query_set
+        } else {
+            continue;
+        };
+
+        let mut read_query = read.get(world);
+        let buffer_read = if let Some(buffer_read) = read_query.get() {
+            buffer_read
+        } else {
+            continue;
+        };
+
+        if !buffer_read.map_state_mut().is_unmapped() {
+            continue;
+        }
+
+        let read_buffer = if let Ok(read_buffer) =
+            world.get::<Usage<BufferReadComponent<T>, Indirect<&BufferComponent>>>(read.entity())
+        {
+            read_buffer
+        } else {
+            continue;
+        };
+
+        let read_buffer =
+            if let Ok(read_buffer) = world.get::<BufferComponent>(read_buffer.entity()) {
+                read_buffer
+            } else {
+                continue;
+            };
+
+        let read_buffer = read_buffer.read();
+        let read_buffer = if let Some(read_buffer) = read_buffer.get() {
+            read_buffer
+        } else {
+            continue;
+        };
+
+        let mut query = encoder.get(world);
+        let encoder = if let Some(encoder) = query.get().and_then(|encoder| encoder.get_mut()) {
+            encoder
+        } else {
+            continue;
+        };
+
+        let queries = (**resolve).clone();
+        encoder.resolve_query_set(query_set, queries, read_buffer, buffer_read.offset());
+    }
+}
+
 // Write data to texture
 pub fn texture_write_system<T>(world: &mut World)
 where
@@ -616,29 +1089,21 @@ pub fn submit_command_buffers_system(world: &mut World) {
     }
 }
 
-// Create textures and corresponding texture views for surfaces
+// Create textures and corresponding texture views for surfaces of windows with a pending redraw
 pub fn surfaces_textures_views_system(world: &mut World) {
-    let mut query = world.query::<&WindowEventComponent>();
-    let (_, window_event) = query.into_iter().next().unwrap();
-    let window_event = window_event.0;
-    drop(query);
-
-    let window_event = window_event.expect("No window for current event");
-
-    let mut query = world.query::<&WindowEntityMap>();
-
-    let (_, window_entity_map) = query.into_iter().next().unwrap();
-
-    let entity = *window_entity_map
-        .get(&window_event)
-        .expect("Redraw requested for window without entity");
-
-    drop(query);
+    let entities = world
+        .query_mut::<&WindowEventComponent>()
+        .into_iter()
+        .filter(|(_, (window_id, _))| window_id.is_some())
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
 
     // Create surface textures and views
     // These will be rendered to and presented during RedrawEventsCleared
-    surface_texture_query(world, entity);
-    surface_texture_view_query(world, entity);
+    for entity in entities {
+        surface_texture_query(world, entity);
+        surface_texture_view_query(world, entity);
+    }
 }
 
 /// Create pending CommandEncoders, recreating them if a Changed flag is set
@@ -683,3 +1148,287 @@ pub fn flush_command_encoders_system(world: &mut World) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use antigen_core::{Construct, CopyToComponent};
+    use wgpu::{BufferDescriptor, BufferUsages, MapMode};
+
+    use crate::{with_headless_backend, BufferBundle, BufferDataBundle, BufferReadBundle};
+
+    enum TestTag {}
+    type TestData = Usage<TestTag, [f32; 4]>;
+
+    fn read_buffer(world: &World, entity: Entity) -> [f32; 4] {
+        let mut query = world.query::<(&DeviceComponent, &QueueComponent)>();
+        let (_, (device, queue)) = query.into_iter().next().unwrap();
+
+        let buffer = world.get::<BufferComponent>(entity).unwrap();
+        let buffer = buffer.read();
+        let buffer = buffer.get().unwrap();
+
+        // Flush pending queue writes to the buffer before mapping it
+        queue.submit(None);
+        device.poll(Maintain::Wait);
+
+        let slice = buffer.slice(..);
+        let map = slice.map_async(MapMode::Read);
+        device.poll(Maintain::Wait);
+        pollster::block_on(map).unwrap();
+
+        let data = *bytemuck::from_bytes::<[f32; 4]>(&slice.get_mapped_range());
+        buffer.unmap();
+        data
+    }
+
+    #[test]
+    fn copy_and_write_ordering() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            world.spawn(backend);
+
+            let buffer_entity = world.spawn(BufferBundle::new(BufferDescriptor {
+                label: Some("Copy And Write Test Buffer"),
+                size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }));
+            create_buffers_system(&mut world);
+
+            let target_entity = world.spawn(BufferDataBundle::new(
+                TestData::construct([0.0; 4]),
+                0,
+                buffer_entity,
+            ));
+
+            let source_entity = world.spawn((
+                TestData::construct([1.0, 2.0, 3.0, 4.0]),
+                CopyToComponent::<TestTag, TestData>::construct(vec![target_entity]),
+            ));
+
+            // Writing before copying misses the copied value
+            buffer_write_system::<TestData>(&mut world);
+            antigen_core::copy_to_system::<TestTag, TestData>(&mut world);
+            assert_eq!(read_buffer(&world, buffer_entity), [0.0; 4]);
+
+            // Copying before writing picks it up in the same frame
+            **world.get_mut::<TestData>(source_entity).unwrap() = [5.0, 6.0, 7.0, 8.0];
+            copy_and_write_system::<TestTag, TestData>(&mut world);
+            assert_eq!(read_buffer(&world, buffer_entity), [5.0, 6.0, 7.0, 8.0]);
+        });
+    }
+
+    #[test]
+    fn buffer_read() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            world.spawn(backend);
+
+            let buffer_entity = world.spawn(BufferBundle::new(BufferDescriptor {
+                label: Some("Buffer Read Test Buffer"),
+                size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }));
+            create_buffers_system(&mut world);
+
+            let data_entity = world.spawn(BufferDataBundle::new(
+                TestData::construct([1.0, 2.0, 3.0, 4.0]),
+                0,
+                buffer_entity,
+            ));
+            buffer_write_system::<TestData>(&mut world);
+
+            let read_entity = world.spawn(BufferReadBundle::new(
+                TestData::construct([0.0; 4]),
+                0,
+                buffer_entity,
+            ));
+
+            // The map may not have completed by the time the first run polls the device,
+            // but is guaranteed to have done so after waiting on it
+            buffer_read_system::<TestData>(&mut world);
+            device_poll_system(&Maintain::Wait)(&mut world);
+            buffer_read_system::<TestData>(&mut world);
+
+            {
+                let data = world.get::<Changed<TestData>>(read_entity).unwrap();
+                assert_eq!(***data, [1.0, 2.0, 3.0, 4.0]);
+                assert!(data.get_changed());
+                data.set_changed(false);
+            }
+
+            // Completed reads leave the buffer unmapped, so it can be written between runs
+            assert!(world
+                .get_mut::<BufferReadComponent<TestData>>(read_entity)
+                .unwrap()
+                .map_state_mut()
+                .is_unmapped());
+
+            {
+                let mut data = world.get_mut::<Changed<TestData>>(data_entity).unwrap();
+                ***data = [5.0, 6.0, 7.0, 8.0];
+                data.set_changed(true);
+            }
+            buffer_write_system::<TestData>(&mut world);
+
+            buffer_read_system::<TestData>(&mut world);
+            device_poll_system(&Maintain::Wait)(&mut world);
+            buffer_read_system::<TestData>(&mut world);
+
+            let data = world.get::<Changed<TestData>>(read_entity).unwrap();
+            assert_eq!(***data, [5.0, 6.0, 7.0, 8.0]);
+            assert!(data.get_changed());
+        });
+    }
+
+    #[test]
+    fn grow_buffer_on_overrun() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            world.spawn(backend);
+
+            let bind_group_entity = world.spawn((BindGroupComponent::default(),));
+
+            let buffer_entity = world.spawn(BufferBundle::new(BufferDescriptor {
+                label: Some("Buffer Growth Test Buffer"),
+                size: std::mem::size_of::<[f32; 4]>() as BufferAddress,
+                usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }));
+            world
+                .insert_one(
+                    buffer_entity,
+                    GrowableBufferComponent::construct(vec![bind_group_entity]),
+                )
+                .unwrap();
+            create_buffers_system(&mut world);
+
+            world.spawn(BufferDataBundle::new(
+                vec![1.0f32, 2.0, 3.0, 4.0],
+                0,
+                buffer_entity,
+            ));
+            buffer_write_slice_system::<Vec<f32>, _>(&mut world);
+            assert!(!world
+                .get::<GrowableBufferComponent>(buffer_entity)
+                .unwrap()
+                .get_changed());
+
+            // Overrunning the buffer doubles its size and preserves existing contents
+            world.spawn(BufferDataBundle::new(
+                vec![5.0f32, 6.0, 7.0, 8.0],
+                std::mem::size_of::<[f32; 4]>() as BufferAddress,
+                buffer_entity,
+            ));
+            buffer_write_slice_system::<Vec<f32>, _>(&mut world);
+
+            assert_eq!(
+                world
+                    .get::<BufferDescriptorComponent>(buffer_entity)
+                    .unwrap()
+                    .size,
+                std::mem::size_of::<[f32; 8]>() as BufferAddress
+            );
+            assert!(world
+                .get::<GrowableBufferComponent>(buffer_entity)
+                .unwrap()
+                .get_changed());
+
+            {
+                let mut query = world.query::<(&DeviceComponent, &QueueComponent)>();
+                let (_, (device, queue)) = query.into_iter().next().unwrap();
+
+                let buffer = world.get::<BufferComponent>(buffer_entity).unwrap();
+                let buffer = buffer.read();
+                let buffer = buffer.get().unwrap();
+
+                // Flush the queued write into the grown buffer before mapping it
+                queue.submit(None);
+
+                let slice = buffer.slice(..);
+                let map = slice.map_async(MapMode::Read);
+                device.poll(Maintain::Wait);
+                pollster::block_on(map).unwrap();
+
+                assert_eq!(
+                    bytemuck::cast_slice::<u8, f32>(&slice.get_mapped_range()),
+                    &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]
+                );
+                buffer.unmap();
+            }
+
+            growable_buffer_bind_groups_system(&mut world);
+            assert!(!world
+                .get::<GrowableBufferComponent>(buffer_entity)
+                .unwrap()
+                .get_changed());
+            assert!(world
+                .get::<BindGroupComponent>(bind_group_entity)
+                .unwrap()
+                .is_pending());
+        });
+    }
+
+    #[test]
+    fn growable_buffer_skips_missing_bind_groups() {
+        let mut world = World::new();
+        let bind_group_entity = world.spawn((BindGroupComponent::Dropped(()),));
+        let despawned_entity = world.spawn(());
+        world.despawn(despawned_entity).unwrap();
+        let plain_entity = world.spawn(());
+
+        let growable_entity = world.spawn((GrowableBufferComponent::construct(vec![
+            despawned_entity,
+            plain_entity,
+            bind_group_entity,
+        ]),));
+        world
+            .get::<GrowableBufferComponent>(growable_entity)
+            .unwrap()
+            .set_changed(true);
+
+        growable_buffer_bind_groups_system(&mut world);
+
+        assert!(!world
+            .get::<GrowableBufferComponent>(growable_entity)
+            .unwrap()
+            .get_changed());
+        assert!(world
+            .get::<BindGroupComponent>(bind_group_entity)
+            .unwrap()
+            .is_pending());
+    }
+
+    #[test]
+    fn skip_overflowing_writes() {
+        with_headless_backend(|backend| {
+            let mut world = World::new();
+            world.spawn(backend);
+
+            let buffer_entity = world.spawn(BufferBundle::new(BufferDescriptor {
+                label: Some("Buffer Overflow Test Buffer"),
+                size: std::mem::size_of::<[f32; 4]>() as BufferAddress,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }));
+            create_buffers_system(&mut world);
+
+            let data_entity = world.spawn(BufferDataBundle::new(
+                TestData::construct([1.0, 2.0, 3.0, 4.0]),
+                4,
+                buffer_entity,
+            ));
+
+            // The write is dropped, leaving the buffer untouched
+            buffer_write_system::<TestData>(&mut world);
+            assert_eq!(read_buffer(&world, buffer_entity), [0.0; 4]);
+            assert!(!world
+                .get::<Changed<TestData>>(data_entity)
+                .unwrap()
+                .get_changed());
+        });
+    }
+}
@@ -0,0 +1,144 @@
+use wgpu::{VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
+
+use crate::buffer_size_of;
+
+/// A Rust type with a corresponding vertex attribute format
+pub trait VertexFormatOf {
+    const FORMAT: VertexFormat;
+}
+
+macro_rules! impl_vertex_format_of {
+    ($($ty:ty => $format:ident),* $(,)?) => {
+        $(
+            impl VertexFormatOf for $ty {
+                const FORMAT: VertexFormat = VertexFormat::$format;
+            }
+        )*
+    };
+}
+
+impl_vertex_format_of!(
+    [u8; 2] => Uint8x2,
+    [u8; 4] => Uint8x4,
+    [i8; 2] => Sint8x2,
+    [i8; 4] => Sint8x4,
+    [u16; 2] => Uint16x2,
+    [u16; 4] => Uint16x4,
+    [i16; 2] => Sint16x2,
+    [i16; 4] => Sint16x4,
+    f32 => Float32,
+    [f32; 2] => Float32x2,
+    [f32; 3] => Float32x3,
+    [f32; 4] => Float32x4,
+    u32 => Uint32,
+    [u32; 2] => Uint32x2,
+    [u32; 3] => Uint32x3,
+    [u32; 4] => Uint32x4,
+    i32 => Sint32,
+    [i32; 2] => Sint32x2,
+    [i32; 3] => Sint32x3,
+    [i32; 4] => Sint32x4,
+    f64 => Float64,
+    [f64; 2] => Float64x2,
+    [f64; 3] => Float64x3,
+    [f64; 4] => Float64x4,
+);
+
+/// A vertex type with attributes derived from its fields
+///
+/// Implemented via the vertex_layout! macro
+pub trait VertexLayout: Sized {
+    const ATTRIBUTES: &'static [VertexAttribute];
+
+    fn vertex_buffer_layout(step_mode: VertexStepMode) -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: buffer_size_of::<Self>(),
+            step_mode,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Declare a repr(C) vertex struct and implement VertexLayout for it
+///
+/// Fields annotated with #[location(n)] become vertex attributes,
+/// with formats derived from their types and offsets from their position in the struct.
+/// Unannotated fields (such as padding) are skipped.
+///
+/// Mismatches against the shader's vertex inputs are caught by wgpu on pipeline creation.
+#[macro_export]
+macro_rules! vertex_layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[location($location:literal)])?
+                $field_vis:vis $field:ident: $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        impl $crate::VertexLayout for $name {
+            const ATTRIBUTES: &'static [$crate::wgpu::VertexAttribute] = &[
+                $($(
+                    $crate::wgpu::VertexAttribute {
+                        format: <$ty as $crate::VertexFormatOf>::FORMAT,
+                        offset: std::mem::offset_of!($name, $field) as $crate::wgpu::BufferAddress,
+                        shader_location: $location,
+                    },
+                )?)*
+            ];
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::vertex_layout! {
+        #[derive(Debug, Default, Copy, Clone)]
+        struct TestVertex {
+            #[location(0)]
+            position: [f32; 3],
+            _pad: f32,
+            #[location(2)]
+            color: [u8; 4],
+            #[location(1)]
+            intensity: f32,
+        }
+    }
+
+    #[test]
+    fn vertex_layout_attributes() {
+        let layout = TestVertex::vertex_buffer_layout(VertexStepMode::Instance);
+
+        assert_eq!(layout.array_stride, 24);
+        assert_eq!(layout.step_mode, VertexStepMode::Instance);
+        assert_eq!(
+            layout.attributes,
+            &[
+                VertexAttribute {
+                    format: VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: 0,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Uint8x4,
+                    offset: 16,
+                    shader_location: 2,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 20,
+                    shader_location: 1,
+                },
+            ]
+        );
+    }
+}
@@ -2,21 +2,29 @@ use antigen_core::{ChangedFlag, Construct, With};
 use winit::dpi::PhysicalSize;
 
 use crate::{
-    DeviceEventComponent, WindowComponent, WindowEntityMap, WindowEventComponent,
-    WindowSizeComponent, WindowTitleComponent,
+    ControlFlowComponent, CursorGrabComponent, DeviceEventComponent, FullscreenComponent,
+    FullscreenMode, KeyboardStateComponent, TextInputComponent, WindowComponent, WindowEntityMap,
+    WindowEventComponent, WindowFocusedComponent, WindowIconComponent, WindowMaxSizeComponent,
+    WindowMinSizeComponent, WindowOccludedComponent, WindowSizeComponent, WindowTitleComponent,
+    WindowedGeometryComponent,
 };
 
 #[derive(Default, hecs::Bundle)]
 pub struct BackendBundle {
     window_entity_map: WindowEntityMap,
-    window_event: WindowEventComponent,
     device_event: DeviceEventComponent,
+    control_flow: ControlFlowComponent,
+    keyboard_state: KeyboardStateComponent,
+    text_input: TextInputComponent,
 }
 
 #[derive(hecs::Bundle)]
 pub struct WindowBundle {
     window: WindowComponent,
     size: WindowSizeComponent,
+    event: WindowEventComponent,
+    focused: WindowFocusedComponent,
+    occluded: WindowOccludedComponent,
 }
 
 impl Default for WindowBundle {
@@ -24,9 +32,16 @@ impl Default for WindowBundle {
         let size =
             WindowSizeComponent::construct(PhysicalSize::<u32>::default()).with(ChangedFlag(false));
 
+        // New windows take focus on creation, and are never created minimized
+        let focused = WindowFocusedComponent::construct(true).with(ChangedFlag(false));
+        let occluded = WindowOccludedComponent::construct(false).with(ChangedFlag(false));
+
         WindowBundle {
             window: Default::default(),
             size,
+            event: Default::default(),
+            focused,
+            occluded,
         }
     }
 }
@@ -42,3 +57,58 @@ impl WindowTitleBundle {
         WindowTitleBundle { title }
     }
 }
+
+#[derive(hecs::Bundle)]
+pub struct CursorGrabBundle {
+    cursor_grab: CursorGrabComponent,
+}
+
+impl CursorGrabBundle {
+    pub fn new(grab: bool) -> Self {
+        let cursor_grab = CursorGrabComponent::construct(grab).with(ChangedFlag(true));
+        CursorGrabBundle { cursor_grab }
+    }
+}
+
+#[derive(hecs::Bundle)]
+pub struct WindowIconBundle {
+    icon: WindowIconComponent,
+}
+
+impl WindowIconBundle {
+    /// Create a window icon from PNG-encoded image data
+    pub fn new(png_data: Vec<u8>) -> Self {
+        let icon = WindowIconComponent::construct(Some(png_data)).with(ChangedFlag(true));
+        WindowIconBundle { icon }
+    }
+}
+
+#[derive(hecs::Bundle)]
+pub struct WindowSizeLimitsBundle {
+    min_size: WindowMinSizeComponent,
+    max_size: WindowMaxSizeComponent,
+}
+
+impl WindowSizeLimitsBundle {
+    pub fn new(min_size: Option<PhysicalSize<u32>>, max_size: Option<PhysicalSize<u32>>) -> Self {
+        let min_size = WindowMinSizeComponent::construct(min_size).with(ChangedFlag(true));
+        let max_size = WindowMaxSizeComponent::construct(max_size).with(ChangedFlag(true));
+        WindowSizeLimitsBundle { min_size, max_size }
+    }
+}
+
+#[derive(hecs::Bundle)]
+pub struct FullscreenBundle {
+    fullscreen: FullscreenComponent,
+    windowed_geometry: WindowedGeometryComponent,
+}
+
+impl FullscreenBundle {
+    pub fn new(mode: FullscreenMode) -> Self {
+        let fullscreen = FullscreenComponent::construct(mode).with(ChangedFlag(true));
+        FullscreenBundle {
+            fullscreen,
+            windowed_geometry: Default::default(),
+        }
+    }
+}
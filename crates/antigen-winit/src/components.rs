@@ -1,8 +1,16 @@
 use antigen_core::{Changed, LazyComponent, Usage};
 
-use winit::{dpi::PhysicalSize, event::{WindowEvent, DeviceEvent, DeviceId}, window::WindowId};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{DeviceEvent, DeviceId, ElementState, VirtualKeyCode, WindowEvent},
+    monitor::{MonitorHandle, VideoMode},
+    window::WindowId,
+};
 
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
 use hecs::Entity;
 
 // Winit window
@@ -12,15 +20,87 @@ pub type WindowComponent = LazyComponent<winit::window::Window>;
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct RedrawUnconditionally;
 
+/// Event loop pacing
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ControlFlowMode {
+    /// Run continuously, for real-time rendering
+    #[default]
+    Poll,
+    /// Sleep until the next event arrives
+    Wait,
+    /// Sleep until the next event arrives or the duration elapses
+    WaitUntil(Duration),
+}
+
+/// Usage tag for event loop control flow
+pub enum EventLoopControlFlow {}
+pub type ControlFlowComponent = Usage<EventLoopControlFlow, ControlFlowMode>;
+
 // Window ID -> Entity ID map for winit event handling
 pub type WindowEntityMap = BTreeMap<WindowId, Entity>;
 
-/// Window event wrapper
+/// Window event wrapper, stored on the entity that owns the window
 pub type WindowEventComponent = (Option<WindowId>, Option<WindowEvent<'static>>);
 
-/// Window event wrapper
+/// Device event wrapper
 pub type DeviceEventComponent = (Option<DeviceId>, Option<DeviceEvent>);
 
+/// Currently-held keys, plus keys pressed or released since the last frame boundary
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KeyboardStateComponent {
+    pressed: BTreeSet<VirtualKeyCode>,
+    just_pressed: BTreeSet<VirtualKeyCode>,
+    just_released: BTreeSet<VirtualKeyCode>,
+}
+
+impl KeyboardStateComponent {
+    pub fn is_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    pub fn just_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.just_pressed.contains(&key)
+    }
+
+    pub fn just_released(&self, key: VirtualKeyCode) -> bool {
+        self.just_released.contains(&key)
+    }
+
+    pub fn pressed(&self) -> impl Iterator<Item = VirtualKeyCode> + '_ {
+        self.pressed.iter().copied()
+    }
+
+    // Key repeat events for held keys don't register as new presses
+    pub fn set_key_state(&mut self, key: VirtualKeyCode, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                if self.pressed.insert(key) {
+                    self.just_pressed.insert(key);
+                }
+            }
+            ElementState::Released => {
+                if self.pressed.remove(&key) {
+                    self.just_released.insert(key);
+                }
+            }
+        }
+    }
+
+    // Release all held keys, for when focus is lost and release events will go unseen
+    pub fn release_all(&mut self) {
+        self.just_released.extend(std::mem::take(&mut self.pressed));
+    }
+
+    pub fn clear_edges(&mut self) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+    }
+}
+
+/// Usage tag for text input received since the last frame boundary
+pub enum TextInput {}
+pub type TextInputComponent = Usage<TextInput, String>;
+
 /// Usage tag for SizeComponent
 pub enum WindowSize {}
 pub type WindowSizeComponent = Usage<WindowSize, Changed<PhysicalSize<u32>>>;
@@ -28,3 +108,104 @@ pub type WindowSizeComponent = Usage<WindowSize, Changed<PhysicalSize<u32>>>;
 /// Usage tag for NameComponent
 pub enum WindowTitle {}
 pub type WindowTitleComponent = Usage<WindowTitle, Changed<&'static str>>;
+
+/// Usage tag for PNG-encoded window icon data
+pub enum WindowIcon {}
+pub type WindowIconComponent = Usage<WindowIcon, Changed<Option<Vec<u8>>>>;
+
+/// Usage tag for minimum window inner size
+pub enum WindowMinSize {}
+pub type WindowMinSizeComponent = Usage<WindowMinSize, Changed<Option<PhysicalSize<u32>>>>;
+
+/// Usage tag for maximum window inner size
+pub enum WindowMaxSize {}
+pub type WindowMaxSizeComponent = Usage<WindowMaxSize, Changed<Option<PhysicalSize<u32>>>>;
+
+/// Window fullscreen state
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum FullscreenMode {
+    #[default]
+    Windowed,
+    /// Borderless fullscreen on the given monitor, or the window's current monitor if None
+    Borderless(Option<MonitorHandle>),
+    Exclusive(VideoMode),
+}
+
+/// Usage tag for window fullscreen state
+pub enum WindowFullscreen {}
+pub type FullscreenComponent = Usage<WindowFullscreen, Changed<FullscreenMode>>;
+
+/// Usage tag for the size and position to restore when leaving fullscreen
+pub enum WindowedGeometry {}
+pub type WindowedGeometryComponent =
+    Usage<WindowedGeometry, Option<(PhysicalSize<u32>, Option<PhysicalPosition<i32>>)>>;
+
+/// Usage tag for window focus state
+pub enum WindowFocused {}
+pub type WindowFocusedComponent = Usage<WindowFocused, Changed<bool>>;
+
+/// Usage tag for window occlusion state
+///
+/// winit 0.26 has no occlusion event, so a window is considered occluded
+/// while minimized, which platforms report as a resize to zero
+pub enum WindowOccluded {}
+pub type WindowOccludedComponent = Usage<WindowOccluded, Changed<bool>>;
+
+/// Usage tag for cursor grab state
+pub enum CursorGrab {}
+pub type CursorGrabComponent = Usage<CursorGrab, Changed<bool>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard_state_edges() {
+        let mut keyboard = KeyboardStateComponent::default();
+
+        keyboard.set_key_state(VirtualKeyCode::W, ElementState::Pressed);
+        assert!(keyboard.is_pressed(VirtualKeyCode::W));
+        assert!(keyboard.just_pressed(VirtualKeyCode::W));
+
+        keyboard.clear_edges();
+        assert!(keyboard.is_pressed(VirtualKeyCode::W));
+        assert!(!keyboard.just_pressed(VirtualKeyCode::W));
+
+        // Key repeat
+        keyboard.set_key_state(VirtualKeyCode::W, ElementState::Pressed);
+        assert!(!keyboard.just_pressed(VirtualKeyCode::W));
+
+        keyboard.set_key_state(VirtualKeyCode::W, ElementState::Released);
+        assert!(!keyboard.is_pressed(VirtualKeyCode::W));
+        assert!(keyboard.just_released(VirtualKeyCode::W));
+
+        keyboard.clear_edges();
+        assert!(!keyboard.just_released(VirtualKeyCode::W));
+    }
+
+    #[test]
+    fn keyboard_state_tap_within_frame() {
+        let mut keyboard = KeyboardStateComponent::default();
+
+        keyboard.set_key_state(VirtualKeyCode::Space, ElementState::Pressed);
+        keyboard.set_key_state(VirtualKeyCode::Space, ElementState::Released);
+
+        assert!(!keyboard.is_pressed(VirtualKeyCode::Space));
+        assert!(keyboard.just_pressed(VirtualKeyCode::Space));
+        assert!(keyboard.just_released(VirtualKeyCode::Space));
+    }
+
+    #[test]
+    fn keyboard_state_release_all() {
+        let mut keyboard = KeyboardStateComponent::default();
+
+        keyboard.set_key_state(VirtualKeyCode::A, ElementState::Pressed);
+        keyboard.set_key_state(VirtualKeyCode::D, ElementState::Pressed);
+        keyboard.clear_edges();
+        keyboard.release_all();
+
+        assert_eq!(keyboard.pressed().count(), 0);
+        assert!(keyboard.just_released(VirtualKeyCode::A));
+        assert!(keyboard.just_released(VirtualKeyCode::D));
+    }
+}
@@ -70,15 +70,29 @@ pub fn wrap_event_loop<T>(
     }
 }
 
-fn get_window_event_component(
+// Clear the events stored on each window entity during the previous iteration
+fn reset_window_event_components(world: &mut World) {
+    for (_, window_event) in world.query_mut::<&mut WindowEventComponent>() {
+        *window_event = (None, None);
+    }
+}
+
+// Store an event on the entity that owns its window,
+// so events from different windows don't clobber one another
+fn set_window_event_component(
     world: &mut World,
-) -> &mut (Option<WindowId>, Option<WindowEvent<'static>>) {
-    let (_, window_event) = world
-        .query_mut::<&mut WindowEventComponent>()
-        .into_iter()
-        .next()
-        .unwrap();
-    window_event
+    window_id: WindowId,
+    event: Option<WindowEvent<'static>>,
+) {
+    let entity = if let Some(entity) = entity_for_window(world, window_id) {
+        entity
+    } else {
+        return;
+    };
+
+    if let Ok(mut window_event) = world.get_mut::<WindowEventComponent>(entity) {
+        *window_event = (Some(window_id), event);
+    }
 }
 
 fn get_device_event_component(world: &mut World) -> &mut (Option<DeviceId>, Option<DeviceEvent>) {
@@ -97,10 +111,7 @@ pub fn winit_event_handler<T: Clone>(mut f: impl EventLoopHandler<T>) -> impl Ev
           event: Event<'static, T>,
           event_loop_window_target: &EventLoopWindowTarget<T>,
           control_flow: &mut ControlFlow| {
-        {
-            let window_event = get_window_event_component(world);
-            *window_event = (None, None);
-        }
+        reset_window_event_components(world);
 
         {
             let device_event = get_device_event_component(world);
@@ -111,20 +122,35 @@ pub fn winit_event_handler<T: Clone>(mut f: impl EventLoopHandler<T>) -> impl Ev
             winit::event::Event::MainEventsCleared => {
                 create_windows_system(world, event_loop_window_target);
                 window_title_system(world);
+                window_icon_system(world);
+                window_size_limits_system(world);
+                fullscreen_system(world);
+                cursor_grab_system(world);
                 redraw_unconditionally_system(world);
             }
             winit::event::Event::RedrawRequested(window_id) => {
-                get_window_event_component(world).0 = Some(*window_id);
+                set_window_event_component(world, *window_id, None);
             }
             winit::event::Event::WindowEvent { window_id, event } => {
-                *get_window_event_component(world) = (Some(*window_id), Some(event.clone()));
+                set_window_event_component(world, *window_id, Some(event.clone()));
                 match event {
                     WindowEvent::Resized(_) => {
                         resize_window_system(world);
+                        window_occlusion_system(world);
                     }
                     WindowEvent::CloseRequested => {
                         close_window_system(world);
                     }
+                    WindowEvent::Focused(focused) => {
+                        window_focus_system(world);
+                        keyboard_input_system(world);
+                        if *focused {
+                            cursor_grab_focus_system(world);
+                        }
+                    }
+                    WindowEvent::KeyboardInput { .. } | WindowEvent::ReceivedCharacter(_) => {
+                        keyboard_input_system(world);
+                    }
                     _ => (),
                 }
             }
@@ -134,6 +160,8 @@ pub fn winit_event_handler<T: Clone>(mut f: impl EventLoopHandler<T>) -> impl Ev
             _ => (),
         }
 
+        control_flow_system(world, control_flow);
+
         f(
             world,
             channel,
@@ -145,6 +173,8 @@ pub fn winit_event_handler<T: Clone>(mut f: impl EventLoopHandler<T>) -> impl Ev
         match &event {
             winit::event::Event::MainEventsCleared => {
                 reset_window_size_changed_system(world);
+                reset_window_focus_occlusion_changed_system(world);
+                reset_keyboard_input_system(world);
             }
             _ => (),
         }
@@ -159,3 +189,59 @@ pub fn winit_event_terminator<T>() -> impl EventLoopHandler<T> {
           _: &EventLoopWindowTarget<T>,
           _: &mut ControlFlow| {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_events_reach_only_their_window_entity() {
+        let mut world = World::new();
+        world.spawn(BackendBundle::default());
+        let first = world.spawn(WindowBundle::default());
+        let second = world.spawn(WindowBundle::default());
+
+        // winit only exposes a single dummy ID, so hand it from one window to the other
+        let window_id = unsafe { WindowId::dummy() };
+        let register = |world: &mut World, entity| {
+            let (_, window_entity_map) = world
+                .query_mut::<&mut WindowEntityMap>()
+                .into_iter()
+                .next()
+                .unwrap();
+            window_entity_map.clear();
+            window_entity_map.insert(window_id, entity);
+        };
+
+        let event = |world: &World, entity| {
+            let window_event = world.get::<WindowEventComponent>(entity).unwrap();
+            (window_event.0, window_event.1.clone())
+        };
+
+        let focused =
+            |world: &World, entity| ***world.get::<WindowFocusedComponent>(entity).unwrap();
+
+        assert_eq!(entity_for_window(&world, window_id), None);
+
+        for (target, other) in [(first, second), (second, first)] {
+            register(&mut world, target);
+            assert_eq!(entity_for_window(&world, window_id), Some(target));
+
+            reset_window_event_components(&mut world);
+            set_window_event_component(&mut world, window_id, Some(WindowEvent::Focused(false)));
+            window_focus_system(&mut world);
+
+            assert_eq!(
+                event(&world, target),
+                (Some(window_id), Some(WindowEvent::Focused(false)))
+            );
+            assert_eq!(event(&world, other), (None, None));
+            assert!(!focused(&world, target) && focused(&world, other));
+
+            // Restore focus for the next window's turn
+            set_window_event_component(&mut world, window_id, Some(WindowEvent::Focused(true)));
+            window_focus_system(&mut world);
+            assert!(focused(&world, target));
+        }
+    }
+}
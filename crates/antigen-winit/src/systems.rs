@@ -1,10 +1,28 @@
 use super::{RedrawUnconditionally, WindowComponent};
-use crate::{WindowEntityMap, WindowEventComponent, WindowSizeComponent, WindowTitleComponent};
-use hecs::World;
+use crate::{
+    ControlFlowComponent, ControlFlowMode, CursorGrabComponent, FullscreenComponent, FullscreenMode,
+    KeyboardStateComponent, TextInputComponent, WindowEntityMap, WindowEventComponent,
+    WindowFocusedComponent, WindowIconComponent, WindowMaxSizeComponent, WindowMinSizeComponent,
+    WindowOccludedComponent, WindowSizeComponent, WindowTitleComponent, WindowedGeometryComponent,
+};
+use hecs::{Entity, World};
+
+use std::error::Error;
 
 use antigen_core::{ChangedTrait, LazyComponent};
 
-use winit::event_loop::EventLoopWindowTarget;
+use winit::{
+    event::{KeyboardInput, WindowEvent},
+    event_loop::{ControlFlow, EventLoopWindowTarget},
+    window::{Fullscreen, Icon, WindowId},
+};
+
+/// Return the entity that owns the window with the given ID, if any
+pub fn entity_for_window(world: &World, window_id: WindowId) -> Option<Entity> {
+    let mut query = world.query::<&WindowEntityMap>();
+    let (_, window_entity_map) = query.into_iter().next()?;
+    window_entity_map.get(&window_id).copied()
+}
 
 // Create winit::Window for WindowComponent
 pub fn create_windows_system<T>(world: &mut World, event_loop_proxy: &EventLoopWindowTarget<T>) {
@@ -41,13 +59,17 @@ pub fn create_windows_system<T>(world: &mut World, event_loop_proxy: &EventLoopW
     }
 }
 
-// Request redraws for WindowComponents
+// Request redraws for WindowComponents, skipping occluded windows
 pub fn redraw_unconditionally_system(world: &mut World) {
-    for (_, window) in world
-        .query_mut::<&WindowComponent>()
+    for (_, (window, occluded)) in world
+        .query_mut::<(&WindowComponent, Option<&WindowOccludedComponent>)>()
         .with::<RedrawUnconditionally>()
         .into_iter()
     {
+        if occluded.map(|occluded| ***occluded).unwrap_or(false) {
+            continue;
+        }
+
         match &*window {
             LazyComponent::Ready(window) => window.request_redraw(),
             _ => (),
@@ -55,32 +77,93 @@ pub fn redraw_unconditionally_system(world: &mut World) {
     }
 }
 
-pub fn resize_window_system(world: &mut World) {
-    let mut query = world.query::<&WindowEventComponent>();
-    let (_, event_window) = query.into_iter().next().unwrap();
+// Set the event loop's control flow from the ControlFlowComponent,
+// polling regardless while any visible window redraws unconditionally
+pub fn control_flow_system(world: &mut World, control_flow: &mut ControlFlow) {
+    if *control_flow == ControlFlow::Exit {
+        return;
+    }
 
-    let window_id = event_window.0.expect("No window for current event");
+    let mut query = world.query::<&ControlFlowComponent>();
+    let mode = if let Some((_, mode)) = query.into_iter().next() {
+        **mode
+    } else {
+        return;
+    };
+    drop(query);
 
-    let mut query = world.query::<&WindowEntityMap>();
-    let (_, window_entity_map) = query.into_iter().next().unwrap();
+    let redraw_unconditionally = world
+        .query_mut::<(&WindowComponent, Option<&WindowOccludedComponent>)>()
+        .with::<RedrawUnconditionally>()
+        .into_iter()
+        .any(|(_, (window, occluded))| {
+            window.is_ready() && !occluded.map(|occluded| ***occluded).unwrap_or(false)
+        });
 
-    let entity = window_entity_map
-        .get(&window_id)
-        .expect("Resize requested for window without entity");
+    *control_flow = match mode {
+        _ if redraw_unconditionally => ControlFlow::Poll,
+        ControlFlowMode::Poll => ControlFlow::Poll,
+        ControlFlowMode::Wait => ControlFlow::Wait,
+        ControlFlowMode::WaitUntil(duration) => {
+            ControlFlow::WaitUntil(std::time::Instant::now() + duration)
+        }
+    };
+}
 
-    let mut query = world
-        .query_one::<(&WindowComponent, &mut WindowSizeComponent)>(*entity)
-        .unwrap();
+// Update the size of windows that received a resize event
+pub fn resize_window_system(world: &mut World) {
+    for (_, (window_event, window_component, size_component)) in world.query_mut::<(
+        &WindowEventComponent,
+        &WindowComponent,
+        &mut WindowSizeComponent,
+    )>() {
+        if !matches!(window_event, (_, Some(WindowEvent::Resized(_)))) {
+            continue;
+        }
 
-    let (window_component, size_component) = if let Some(components) = query.get() {
-        components
-    } else {
-        return;
-    };
+        if let LazyComponent::Ready(window) = window_component {
+            ***size_component = window.inner_size();
+            size_component.set_changed(true);
+        }
+    }
+}
 
-    if let LazyComponent::Ready(window) = &*window_component {
-        ***size_component = window.inner_size();
-        size_component.set_changed(true);
+// Update the focus state of windows that received a focus event
+pub fn window_focus_system(world: &mut World) {
+    for (_, (window_event, focused)) in
+        world.query_mut::<(&WindowEventComponent, &mut WindowFocusedComponent)>()
+    {
+        if let (_, Some(WindowEvent::Focused(focus))) = window_event {
+            if ***focused != *focus {
+                ***focused = *focus;
+                focused.set_changed(true);
+            }
+        }
+    }
+}
+
+// Update the occlusion state of windows that received a resize event
+pub fn window_occlusion_system(world: &mut World) {
+    for (_, (window_event, occluded)) in
+        world.query_mut::<(&WindowEventComponent, &mut WindowOccludedComponent)>()
+    {
+        if let (_, Some(WindowEvent::Resized(size))) = window_event {
+            let minimized = size.width == 0 || size.height == 0;
+            if ***occluded != minimized {
+                ***occluded = minimized;
+                occluded.set_changed(true);
+            }
+        }
+    }
+}
+
+pub fn reset_window_focus_occlusion_changed_system(world: &mut World) {
+    for (_, focused) in world.query_mut::<&mut WindowFocusedComponent>() {
+        focused.set_changed(false);
+    }
+
+    for (_, occluded) in world.query_mut::<&mut WindowOccludedComponent>() {
+        occluded.set_changed(false);
     }
 }
 
@@ -107,28 +190,286 @@ pub fn window_title_system(world: &mut World) {
         });
 }
 
-pub fn close_window_system(world: &mut World) {
-    let mut query = world.query::<&WindowEventComponent>();
-    let (_, window_event) = query.into_iter().next().unwrap();
+// Grab or release the cursor of windows whose CursorGrabComponent has changed,
+// hiding it while grabbed
+pub fn cursor_grab_system(world: &mut World) {
+    for (_, (window, cursor_grab)) in
+        world.query_mut::<(&WindowComponent, &CursorGrabComponent)>()
+    {
+        let window = if let LazyComponent::Ready(window) = window {
+            window
+        } else {
+            continue;
+        };
 
-    let window_id = if let (Some(window_id), _) = &*window_event {
-        window_id
-    } else {
-        return;
+        if !cursor_grab.get_changed() {
+            continue;
+        }
+
+        // Grabbing is unsupported on some platforms and may be refused while unfocused,
+        // so failure is logged rather than retried until focus is regained
+        let grab = ***cursor_grab;
+        if let Err(e) = window.set_cursor_grab(grab) {
+            println!("Failed to {} cursor: {}", if grab { "grab" } else { "release" }, e);
+        }
+        window.set_cursor_visible(!grab);
+
+        cursor_grab.set_changed(false);
+    }
+}
+
+// Re-grab the cursor of windows that have regained focus,
+// since losing focus releases the grab on some platforms
+pub fn cursor_grab_focus_system(world: &mut World) {
+    for (_, (window_event, cursor_grab)) in
+        world.query_mut::<(&WindowEventComponent, &CursorGrabComponent)>()
+    {
+        if ***cursor_grab && matches!(window_event, (_, Some(WindowEvent::Focused(true)))) {
+            cursor_grab.set_changed(true);
+        }
+    }
+}
+
+// Decode PNG-encoded image data into a window icon
+pub fn decode_window_icon(png_data: &[u8]) -> Result<Icon, Box<dyn Error>> {
+    let mut decoder = png::Decoder::new(png_data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    data.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => data,
+        png::ColorType::Rgb => data
+            .chunks(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks(2)
+            .flat_map(|la| [la[0], la[0], la[0], la[1]])
+            .collect(),
+        png::ColorType::Grayscale => data.iter().flat_map(|l| [*l, *l, *l, 255]).collect(),
+        color_type => return Err(format!("Unsupported icon color type {:?}", color_type).into()),
     };
 
-    let mut query = world.query::<&WindowEntityMap>();
-    let (_, window_entity_map) = query.into_iter().next().unwrap();
+    Ok(Icon::from_rgba(rgba, info.width, info.height)?)
+}
 
-    let entity = window_entity_map
-        .get(&window_id)
-        .expect("Close requested for window without entity");
+// Set the icon of windows whose WindowIconComponent has changed,
+// falling back to no icon if it fails to decode
+pub fn window_icon_system(world: &mut World) {
+    for (_, (window, icon)) in world.query_mut::<(&WindowComponent, &WindowIconComponent)>() {
+        let window = if let LazyComponent::Ready(window) = window {
+            window
+        } else {
+            continue;
+        };
 
-    let mut query = world.query_one::<&mut WindowComponent>(*entity).unwrap();
-    let window_component = query.get().unwrap();
-    if window_component.is_ready() {
-        window_component.set_dropped()
-    } else {
-        panic!("Close requested for a non-open window");
+        if !icon.get_changed() {
+            continue;
+        }
+
+        let icon_data = &***icon;
+        let icon_data = icon_data.as_ref().and_then(|png_data| {
+            decode_window_icon(png_data)
+                .map_err(|e| println!("Failed to decode window icon: {}", e))
+                .ok()
+        });
+        window.set_window_icon(icon_data);
+
+        icon.set_changed(false);
+    }
+}
+
+// Apply minimum and maximum inner sizes to windows whose limits have changed,
+// resizing windows that currently fall outside them
+pub fn window_size_limits_system(world: &mut World) {
+    for (_, (window, min_size, max_size)) in world.query_mut::<(
+        &WindowComponent,
+        Option<&WindowMinSizeComponent>,
+        Option<&WindowMaxSizeComponent>,
+    )>() {
+        let window = if let LazyComponent::Ready(window) = window {
+            window
+        } else {
+            continue;
+        };
+
+        let min_changed = min_size.map(|min_size| min_size.get_changed()).unwrap_or(false);
+        let max_changed = max_size.map(|max_size| max_size.get_changed()).unwrap_or(false);
+        if !min_changed && !max_changed {
+            continue;
+        }
+
+        let min_size = min_size.and_then(|min_size| {
+            min_size.set_changed(false);
+            ***min_size
+        });
+
+        let max_size = max_size.and_then(|max_size| {
+            max_size.set_changed(false);
+            ***max_size
+        });
+
+        window.set_min_inner_size(min_size);
+        window.set_max_inner_size(max_size);
+
+        let size = window.inner_size();
+        let mut clamped = size;
+        if let Some(min_size) = min_size {
+            clamped.width = clamped.width.max(min_size.width);
+            clamped.height = clamped.height.max(min_size.height);
+        }
+        if let Some(max_size) = max_size {
+            clamped.width = clamped.width.min(max_size.width);
+            clamped.height = clamped.height.min(max_size.height);
+        }
+
+        if clamped != size {
+            window.set_inner_size(clamped);
+        }
+    }
+}
+
+// Apply changed fullscreen modes, storing windowed size and position on entering fullscreen
+// and restoring them on leaving it
+pub fn fullscreen_system(world: &mut World) {
+    for (_, (window, fullscreen, windowed_geometry)) in world.query_mut::<(
+        &WindowComponent,
+        &FullscreenComponent,
+        &mut WindowedGeometryComponent,
+    )>() {
+        let window = if let LazyComponent::Ready(window) = window {
+            window
+        } else {
+            continue;
+        };
+
+        if !fullscreen.get_changed() {
+            continue;
+        }
+        fullscreen.set_changed(false);
+
+        let target = match &***fullscreen {
+            FullscreenMode::Windowed => None,
+            FullscreenMode::Borderless(monitor) => {
+                if window.available_monitors().next().is_none() {
+                    println!("No monitors available for borderless fullscreen, remaining windowed");
+                    continue;
+                }
+                Some(Fullscreen::Borderless(monitor.clone()))
+            }
+            FullscreenMode::Exclusive(video_mode) => {
+                Some(Fullscreen::Exclusive(video_mode.clone()))
+            }
+        };
+
+        match target {
+            Some(target) => {
+                if window.fullscreen().is_none() {
+                    **windowed_geometry = Some((window.inner_size(), window.outer_position().ok()));
+                }
+                window.set_fullscreen(Some(target));
+            }
+            None => {
+                window.set_fullscreen(None);
+                if let Some((size, position)) = windowed_geometry.take() {
+                    window.set_inner_size(size);
+                    if let Some(position) = position {
+                        window.set_outer_position(position);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Update keyboard state and text input from the current window event
+pub fn keyboard_input_system(world: &mut World) {
+    let mut query = world.query::<&WindowEventComponent>();
+    let events = query
+        .into_iter()
+        .flat_map(|(_, (_, event))| event.clone())
+        .collect::<Vec<_>>();
+    drop(query);
+
+    let mut query = world.query::<(&mut KeyboardStateComponent, &mut TextInputComponent)>();
+    let (_, (keyboard_state, text_input)) = query.into_iter().next().unwrap();
+
+    for event in events {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => keyboard_state.set_key_state(key, state),
+            WindowEvent::ReceivedCharacter(c) if !c.is_control() => text_input.push(c),
+            WindowEvent::Focused(false) => keyboard_state.release_all(),
+            _ => (),
+        }
+    }
+}
+
+// Clear per-frame key edges and text input
+pub fn reset_keyboard_input_system(world: &mut World) {
+    for (_, (keyboard_state, text_input)) in
+        world.query_mut::<(&mut KeyboardStateComponent, &mut TextInputComponent)>()
+    {
+        keyboard_state.clear_edges();
+        text_input.clear();
+    }
+}
+
+// Drop windows that received a close request
+pub fn close_window_system(world: &mut World) {
+    for (_, (window_event, window_component)) in
+        world.query_mut::<(&WindowEventComponent, &mut WindowComponent)>()
+    {
+        if !matches!(window_event, (_, Some(WindowEvent::CloseRequested))) {
+            continue;
+        }
+
+        if window_component.is_ready() {
+            window_component.set_dropped()
+        } else {
+            panic!("Close requested for a non-open window");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_png(width: u32, height: u32, color_type: png::ColorType, data: &[u8]) -> Vec<u8> {
+        let mut png_data = vec![];
+        let mut encoder = png::Encoder::new(&mut png_data, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        png_data
+    }
+
+    #[test]
+    fn decode_window_icon_formats() {
+        let rgba = encode_png(2, 1, png::ColorType::Rgba, &[255, 0, 0, 255, 0, 255, 0, 128]);
+        assert!(decode_window_icon(&rgba).is_ok());
+
+        let rgb = encode_png(2, 1, png::ColorType::Rgb, &[255, 0, 0, 0, 255, 0]);
+        assert!(decode_window_icon(&rgb).is_ok());
+
+        let grayscale = encode_png(2, 1, png::ColorType::Grayscale, &[0, 255]);
+        assert!(decode_window_icon(&grayscale).is_ok());
+    }
+
+    #[test]
+    fn decode_window_icon_invalid_data() {
+        assert!(decode_window_icon(&[]).is_err());
+        assert!(decode_window_icon(b"not a png").is_err());
     }
 }
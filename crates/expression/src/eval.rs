@@ -6,6 +6,38 @@ pub trait EvalTrait<C> {
 
 pub type Eval<T, C> = <T as EvalTrait<C>>::Eval;
 
+/// Fallible counterpart to [`EvalTrait`]
+pub trait TryEvalTrait<C> {
+    type Eval;
+
+    fn try_eval(&self, ctx: &C) -> Result<Self::Eval, EvalError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    MissingVariable(String),
+    UnknownFunction(String),
+    Unsupported(&'static str),
+    Overflow(&'static str),
+    NotANumber(&'static str),
+    DivisionByZero,
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::MissingVariable(name) => write!(f, "Missing variable {:?}", name),
+            EvalError::UnknownFunction(name) => write!(f, "Unknown function {:?}", name),
+            EvalError::Unsupported(op) => write!(f, "Unsupported operation {}", op),
+            EvalError::Overflow(op) => write!(f, "Overflow in operation {}", op),
+            EvalError::NotANumber(op) => write!(f, "Operation {} does not produce a number", op),
+            EvalError::DivisionByZero => write!(f, "Division by zero"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
 impl<C> EvalTrait<C> for u32 {
     type Eval = u32;
 
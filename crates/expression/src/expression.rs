@@ -1,4 +1,6 @@
-use crate::{EvalTrait, Token};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{EvalError, EvalTrait, FunctionRegistry, Token, TryEvalTrait};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expression<V> {
@@ -12,6 +14,7 @@ pub enum Expression<V> {
     Sin(Box<Expression<V>>),
     Cos(Box<Expression<V>>),
     Tan(Box<Expression<V>>),
+    Call(String, Vec<Expression<V>>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -20,6 +23,64 @@ pub enum TokenExpression<'a, V> {
     Expression(Expression<V>),
 }
 
+impl<V> Expression<V> {
+    /// Returns the names of all variables referenced by this expression
+    pub fn free_variables(&self) -> BTreeSet<&str> {
+        let mut vars = BTreeSet::new();
+        self.collect_free_variables(&mut vars);
+        vars
+    }
+
+    fn collect_free_variables<'a>(&'a self, vars: &mut BTreeSet<&'a str>) {
+        match self {
+            Expression::Val(_) => (),
+            Expression::Ident(name) => {
+                vars.insert(name);
+            }
+            Expression::Add(lhs, rhs)
+            | Expression::Sub(lhs, rhs)
+            | Expression::Mul(lhs, rhs)
+            | Expression::Div(lhs, rhs)
+            | Expression::Pow(lhs, rhs) => {
+                lhs.collect_free_variables(vars);
+                rhs.collect_free_variables(vars);
+            }
+            Expression::Sin(val) | Expression::Cos(val) | Expression::Tan(val) => {
+                val.collect_free_variables(vars)
+            }
+            Expression::Call(_, args) => {
+                for arg in args {
+                    arg.collect_free_variables(vars);
+                }
+            }
+        }
+    }
+
+    /// Returns the name of the first function called by this expression
+    /// that is not present in `functions`
+    pub fn unknown_function(&self, functions: &FunctionRegistry<V>) -> Option<&str> {
+        match self {
+            Expression::Val(_) | Expression::Ident(_) => None,
+            Expression::Add(lhs, rhs)
+            | Expression::Sub(lhs, rhs)
+            | Expression::Mul(lhs, rhs)
+            | Expression::Div(lhs, rhs)
+            | Expression::Pow(lhs, rhs) => lhs
+                .unknown_function(functions)
+                .or_else(|| rhs.unknown_function(functions)),
+            Expression::Sin(val) | Expression::Cos(val) | Expression::Tan(val) => {
+                val.unknown_function(functions)
+            }
+            Expression::Call(name, args) => {
+                if !functions.contains(name) {
+                    return Some(name);
+                }
+                args.iter().find_map(|arg| arg.unknown_function(functions))
+            }
+        }
+    }
+}
+
 impl<'a, V> From<Token<'a>> for TokenExpression<'a, V> {
     fn from(t: Token<'a>) -> Self {
         TokenExpression::Token(t)
@@ -32,42 +93,163 @@ impl<'a, V> From<Expression<V>> for TokenExpression<'a, V> {
     }
 }
 
-impl EvalTrait<std::collections::BTreeMap<&str, u32>> for Expression<u32>
-{
+/// Raise `base` to the power of `exponent` without producing NaN
+///
+/// * Integer exponents use repeated multiplication, so negative bases are well-defined:
+///   `(-2)^3 = -8`
+/// * `0^0` evaluates to `1`
+/// * `0` raised to a negative power errors with [`EvalError::DivisionByZero`]
+/// * Negative bases raised to fractional powers and NaN operands error with
+///   [`EvalError::NotANumber`]
+pub fn pow(base: f32, exponent: f32) -> Result<f32, EvalError> {
+    if base.is_nan() || exponent.is_nan() {
+        return Err(EvalError::NotANumber("^"));
+    }
+
+    if base == 0.0 && exponent < 0.0 {
+        return Err(EvalError::DivisionByZero);
+    }
+
+    if exponent.fract() == 0.0 && exponent.abs() <= i32::MAX as f32 {
+        return Ok(base.powi(exponent as i32));
+    }
+
+    if base < 0.0 {
+        return Err(EvalError::NotANumber("^"));
+    }
+
+    Ok(base.powf(exponent))
+}
+
+impl TryEvalTrait<BTreeMap<&str, u32>> for Expression<u32> {
     type Eval = u32;
 
-    fn eval(&self, ctx: &std::collections::BTreeMap<&str, u32>) -> Self::Eval {
-        match self {
-            Expression::Add(lhs, rhs) => (*lhs).eval(ctx) + (*rhs).eval(ctx),
-            Expression::Sub(lhs, rhs) => (*lhs).eval(ctx) - (*rhs).eval(ctx),
-            Expression::Mul(lhs, rhs) => (*lhs).eval(ctx) * (*rhs).eval(ctx),
-            Expression::Div(lhs, rhs) => (*lhs).eval(ctx) / (*rhs).eval(ctx),
-            Expression::Pow(lhs, rhs) => (*lhs).eval(ctx).pow((*rhs).eval(ctx)),
-            Expression::Sin(_) => panic!("No sine function for u32"),
-            Expression::Cos(_) => panic!("No cosine function for u32"),
-            Expression::Tan(_) => panic!("No tangent function for u32"),
+    fn try_eval(&self, ctx: &BTreeMap<&str, u32>) -> Result<Self::Eval, EvalError> {
+        self.try_eval(&(ctx, &FunctionRegistry::default()))
+    }
+}
+
+impl TryEvalTrait<(&BTreeMap<&str, u32>, &FunctionRegistry<u32>)> for Expression<u32> {
+    type Eval = u32;
+
+    fn try_eval(
+        &self,
+        ctx: &(&BTreeMap<&str, u32>, &FunctionRegistry<u32>),
+    ) -> Result<Self::Eval, EvalError> {
+        let (vars, functions) = ctx;
+        Ok(match self {
+            Expression::Add(lhs, rhs) => lhs
+                .try_eval(ctx)?
+                .checked_add(rhs.try_eval(ctx)?)
+                .ok_or(EvalError::Overflow("+"))?,
+            Expression::Sub(lhs, rhs) => lhs
+                .try_eval(ctx)?
+                .checked_sub(rhs.try_eval(ctx)?)
+                .ok_or(EvalError::Overflow("-"))?,
+            Expression::Mul(lhs, rhs) => lhs
+                .try_eval(ctx)?
+                .checked_mul(rhs.try_eval(ctx)?)
+                .ok_or(EvalError::Overflow("*"))?,
+            Expression::Div(lhs, rhs) => lhs
+                .try_eval(ctx)?
+                .checked_div(rhs.try_eval(ctx)?)
+                .ok_or(EvalError::DivisionByZero)?,
+            Expression::Pow(lhs, rhs) => lhs
+                .try_eval(ctx)?
+                .checked_pow(rhs.try_eval(ctx)?)
+                .ok_or(EvalError::Overflow("^"))?,
+            Expression::Sin(_) => return Err(EvalError::Unsupported("sin")),
+            Expression::Cos(_) => return Err(EvalError::Unsupported("cos")),
+            Expression::Tan(_) => return Err(EvalError::Unsupported("tan")),
+            Expression::Call(name, args) => {
+                let f = functions
+                    .get(name)
+                    .ok_or_else(|| EvalError::UnknownFunction(name.clone()))?;
+                let args = args
+                    .iter()
+                    .map(|arg| arg.try_eval(ctx))
+                    .collect::<Result<Vec<_>, _>>()?;
+                f(&args)
+            }
             Expression::Val(n) => *n,
-            Expression::Ident(k) => ctx[k.as_str()],
-        }
+            Expression::Ident(k) => *vars
+                .get(k.as_str())
+                .ok_or_else(|| EvalError::MissingVariable(k.clone()))?,
+        })
+    }
+}
+
+impl EvalTrait<BTreeMap<&str, u32>> for Expression<u32> {
+    type Eval = u32;
+
+    fn eval(&self, ctx: &BTreeMap<&str, u32>) -> Self::Eval {
+        self.try_eval(ctx).unwrap()
+    }
+}
+
+impl EvalTrait<(&BTreeMap<&str, u32>, &FunctionRegistry<u32>)> for Expression<u32> {
+    type Eval = u32;
+
+    fn eval(&self, ctx: &(&BTreeMap<&str, u32>, &FunctionRegistry<u32>)) -> Self::Eval {
+        self.try_eval(ctx).unwrap()
     }
 }
 
-impl EvalTrait<std::collections::BTreeMap<&str, f32>> for Expression<f32>
-{
+impl TryEvalTrait<BTreeMap<&str, f32>> for Expression<f32> {
     type Eval = f32;
 
-    fn eval(&self, ctx: &std::collections::BTreeMap<&str, f32>) -> Self::Eval {
-        match self {
-            Expression::Add(lhs, rhs) => (*lhs).eval(ctx) + (*rhs).eval(ctx),
-            Expression::Sub(lhs, rhs) => (*lhs).eval(ctx) - (*rhs).eval(ctx),
-            Expression::Mul(lhs, rhs) => (*lhs).eval(ctx) * (*rhs).eval(ctx),
-            Expression::Div(lhs, rhs) => (*lhs).eval(ctx) / (*rhs).eval(ctx),
-            Expression::Pow(lhs, rhs) => (*lhs).eval(ctx).powf((*rhs).eval(ctx)),
-            Expression::Sin(val) => (*val).eval(ctx).sin(),
-            Expression::Cos(val) => (*val).eval(ctx).cos(),
-            Expression::Tan(val) => (*val).eval(ctx).tan(),
+    fn try_eval(&self, ctx: &BTreeMap<&str, f32>) -> Result<Self::Eval, EvalError> {
+        self.try_eval(&(ctx, &FunctionRegistry::default()))
+    }
+}
+
+impl TryEvalTrait<(&BTreeMap<&str, f32>, &FunctionRegistry<f32>)> for Expression<f32> {
+    type Eval = f32;
+
+    fn try_eval(
+        &self,
+        ctx: &(&BTreeMap<&str, f32>, &FunctionRegistry<f32>),
+    ) -> Result<Self::Eval, EvalError> {
+        let (vars, functions) = ctx;
+        Ok(match self {
+            Expression::Add(lhs, rhs) => lhs.try_eval(ctx)? + rhs.try_eval(ctx)?,
+            Expression::Sub(lhs, rhs) => lhs.try_eval(ctx)? - rhs.try_eval(ctx)?,
+            Expression::Mul(lhs, rhs) => lhs.try_eval(ctx)? * rhs.try_eval(ctx)?,
+            Expression::Div(lhs, rhs) => lhs.try_eval(ctx)? / rhs.try_eval(ctx)?,
+            Expression::Pow(lhs, rhs) => pow(lhs.try_eval(ctx)?, rhs.try_eval(ctx)?)?,
+            Expression::Sin(val) => val.try_eval(ctx)?.sin(),
+            Expression::Cos(val) => val.try_eval(ctx)?.cos(),
+            Expression::Tan(val) => val.try_eval(ctx)?.tan(),
+            Expression::Call(name, args) => {
+                let f = functions
+                    .get(name)
+                    .ok_or_else(|| EvalError::UnknownFunction(name.clone()))?;
+                let args = args
+                    .iter()
+                    .map(|arg| arg.try_eval(ctx))
+                    .collect::<Result<Vec<_>, _>>()?;
+                f(&args)
+            }
             Expression::Val(n) => *n,
-            Expression::Ident(k) => ctx[k.as_str()],
-        }
+            Expression::Ident(k) => *vars
+                .get(k.as_str())
+                .ok_or_else(|| EvalError::MissingVariable(k.clone()))?,
+        })
+    }
+}
+
+impl EvalTrait<BTreeMap<&str, f32>> for Expression<f32> {
+    type Eval = f32;
+
+    fn eval(&self, ctx: &BTreeMap<&str, f32>) -> Self::Eval {
+        self.try_eval(ctx).unwrap()
+    }
+}
+
+impl EvalTrait<(&BTreeMap<&str, f32>, &FunctionRegistry<f32>)> for Expression<f32> {
+    type Eval = f32;
+
+    fn eval(&self, ctx: &(&BTreeMap<&str, f32>, &FunctionRegistry<f32>)) -> Self::Eval {
+        self.try_eval(ctx).unwrap()
     }
 }
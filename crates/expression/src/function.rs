@@ -0,0 +1,35 @@
+use std::collections::BTreeMap;
+
+/// Named functions that can be called from an [`Expression`](crate::Expression),
+/// such as `clamp(x, 0, 1)`
+#[derive(Debug, Clone)]
+pub struct FunctionRegistry<V> {
+    functions: BTreeMap<String, fn(&[V]) -> V>,
+}
+
+impl<V> Default for FunctionRegistry<V> {
+    fn default() -> Self {
+        FunctionRegistry {
+            functions: Default::default(),
+        }
+    }
+}
+
+impl<V> FunctionRegistry<V> {
+    pub fn with_function<S: Into<String>>(mut self, name: S, f: fn(&[V]) -> V) -> Self {
+        self.register(name, f);
+        self
+    }
+
+    pub fn register<S: Into<String>>(&mut self, name: S, f: fn(&[V]) -> V) {
+        self.functions.insert(name.into(), f);
+    }
+
+    pub fn get(&self, name: &str) -> Option<fn(&[V]) -> V> {
+        self.functions.get(name).copied()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+}
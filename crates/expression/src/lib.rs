@@ -1,9 +1,11 @@
 mod eval;
+mod function;
 mod op_types;
 mod expression;
 mod parse;
 
 pub use eval::*;
+pub use function::*;
 pub use op_types::*;
 pub use expression::*;
 pub use parse::*;
@@ -27,9 +29,17 @@ mod tests {
         println!(
             "Result: {}",
             Expression::Sub(
-                Expression::Add(Expression::Val(1.0).into(), Expression::Ident("x").into()).into(),
+                Expression::Add(
+                    Expression::Val(1.0).into(),
+                    Expression::Ident("x".into()).into(),
+                )
+                .into(),
                 Expression::Div(
-                    Expression::Mul(Expression::Val(3.0).into(), Expression::Ident("y").into()).into(),
+                    Expression::Mul(
+                        Expression::Val(3.0).into(),
+                        Expression::Ident("y".into()).into(),
+                    )
+                    .into(),
                     Expression::Val(5.0).into(),
                 )
                 .into(),
@@ -41,4 +51,87 @@ mod tests {
         println!("Expression: {:#?}", expression);
         println!("Result: {}", expression.eval(&vars));
     }
+
+    fn max3(args: &[f32]) -> f32 {
+        args.iter().copied().fold(f32::MIN, f32::max)
+    }
+
+    #[test]
+    fn test_function_registry() {
+        let vars = [("x", 2.0), ("y", 4.0)].into_iter().collect::<BTreeMap<_, _>>();
+        let functions = FunctionRegistry::default().with_function("max3", max3);
+
+        let expression =
+            parse_expression_with_functions("1 + max3(x, y * (2 - 1), sin(0)) * 2", &functions)
+                .unwrap();
+        assert_eq!(expression.eval(&(&vars, &functions)), 9.0);
+
+        let expression = parse_expression_with_functions("max3(max3(x, 8, 1), y, x)", &functions)
+            .unwrap();
+        assert_eq!(expression.eval(&(&vars, &functions)), 8.0);
+    }
+
+    #[test]
+    fn test_unknown_function() {
+        let functions = FunctionRegistry::default().with_function("max3", max3);
+
+        assert_eq!(
+            parse_expression_with_functions("2 * clamp(x, 0, 1)", &functions),
+            Err(ParseError::UnknownFunction("clamp".into()))
+        );
+    }
+
+    #[test]
+    fn test_free_variables() {
+        let expression = parse_expression("sin(f * t) + max3(x, f, 2) / (y ^ 2)");
+        assert_eq!(
+            expression.free_variables().into_iter().collect::<Vec<_>>(),
+            ["f", "t", "x", "y"]
+        );
+
+        assert!(parse_expression("1 + 2").free_variables().is_empty());
+    }
+
+    #[test]
+    fn test_try_eval() {
+        let vars = [("x", 2.0)].into_iter().collect::<BTreeMap<_, _>>();
+
+        assert_eq!(parse_expression("x * 3").try_eval(&vars), Ok(6.0));
+        assert_eq!(
+            parse_expression("x * y").try_eval(&vars),
+            Err(EvalError::MissingVariable("y".into()))
+        );
+        assert_eq!(
+            parse_expression("noise(x)").try_eval(&vars),
+            Err(EvalError::UnknownFunction("noise".into()))
+        );
+
+        let vars = [("x", 2)].into_iter().collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            Expression::Div(Expression::Ident("x".into()).into(), Expression::Val(0).into())
+                .try_eval(&vars),
+            Err(EvalError::DivisionByZero)
+        );
+        assert_eq!(
+            Expression::Sin(Expression::Ident("x".into()).into()).try_eval(&vars),
+            Err(EvalError::Unsupported("sin"))
+        );
+    }
+
+    #[test]
+    fn test_pow() {
+        let vars = BTreeMap::<&str, f32>::new();
+        let eval = |input| parse_expression(input).try_eval(&vars);
+
+        assert_eq!(eval("(0 - 2) ^ 3"), Ok(-8.0));
+        assert_eq!(eval("(0 - 2) ^ 2"), Ok(4.0));
+        assert_eq!(eval("(0 - 2) ^ (0 - 1)"), Ok(-0.5));
+        assert_eq!(eval("(0 - 2) ^ 0.5"), Err(EvalError::NotANumber("^")));
+        assert_eq!(eval("0 ^ 0"), Ok(1.0));
+        assert_eq!(eval("0 ^ (0 - 1)"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("4 ^ 0.5"), Ok(2.0));
+
+        assert_eq!(pow(f32::NAN, 2.0), Err(EvalError::NotANumber("^")));
+        assert_eq!(pow(2.0, f32::NAN), Err(EvalError::NotANumber("^")));
+    }
 }
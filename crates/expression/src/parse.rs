@@ -1,4 +1,4 @@
-use crate::{Expression, TokenExpression};
+use crate::{Expression, FunctionRegistry, TokenExpression};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Token<'a> {
@@ -14,8 +14,24 @@ pub enum Token<'a> {
     Var(&'a str),
     OpenBracket,
     CloseBracket,
+    Comma,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnknownFunction(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnknownFunction(name) => write!(f, "Unknown function {:?}", name),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 pub fn parse_expression(input: &str) -> Expression<f32> {
     // Parse tokens
     let (_, tokens) = parse_tokens(input).unwrap();
@@ -37,28 +53,67 @@ pub fn parse_expression(input: &str) -> Expression<f32> {
     parse_expression_impl(tokens)
 }
 
+/// Parse an expression, erroring if it calls a function not present in `functions`
+pub fn parse_expression_with_functions(
+    input: &str,
+    functions: &FunctionRegistry<f32>,
+) -> Result<Expression<f32>, ParseError> {
+    let expression = parse_expression(input);
+    if let Some(name) = expression.unknown_function(functions) {
+        return Err(ParseError::UnknownFunction(name.to_string()));
+    }
+    Ok(expression)
+}
+
 pub fn parse_expression_impl<'a, 'b>(mut tokens: Vec<TokenExpression<'a, f32>>) -> Expression<f32> {
     println!("Tokens: {:#?}", tokens);
 
-    // Recursively evalutate bracketed expressions
+    // Recursively evalutate bracketed expressions,
+    // treating those preceded by an identifier as function calls
     while let Some(i) = tokens
         .iter()
         .position(|t| *t == TokenExpression::Token(Token::OpenBracket))
     {
-        let mut sub_tokens = vec![];
+        tokens.remove(i);
+
+        let mut args = vec![vec![]];
+        let mut depth = 0;
         while i < tokens.len() {
             let token = tokens.remove(i);
-            if token == TokenExpression::Token(Token::OpenBracket) {
-                continue;
+            match token {
+                TokenExpression::Token(Token::OpenBracket) => depth += 1,
+                TokenExpression::Token(Token::CloseBracket) if depth == 0 => break,
+                TokenExpression::Token(Token::CloseBracket) => depth -= 1,
+                TokenExpression::Token(Token::Comma) if depth == 0 => {
+                    args.push(vec![]);
+                    continue;
+                }
+                _ => (),
             }
-            if token == TokenExpression::Token(Token::CloseBracket) {
-                break;
-            }
-            sub_tokens.push(token)
+            args.last_mut().unwrap().push(token)
+        }
+
+        if args.len() == 1 && args[0].is_empty() {
+            args.clear();
         }
 
-        let expression = parse_expression_impl(sub_tokens);
-        tokens.insert(i, TokenExpression::Expression(expression));
+        let mut args = args
+            .into_iter()
+            .map(parse_expression_impl)
+            .collect::<Vec<_>>();
+
+        match i.checked_sub(1).map(|j| &tokens[j]) {
+            Some(TokenExpression::Expression(Expression::Ident(name))) => {
+                let call = Expression::Call(name.clone(), args);
+                tokens[i - 1] = TokenExpression::Expression(call);
+            }
+            _ => {
+                if args.len() != 1 {
+                    panic!("Unexpected bracketed expression with {} values", args.len());
+                }
+                tokens.insert(i, TokenExpression::Expression(args.remove(0)));
+            }
+        }
     }
 
     // Parse functions
@@ -162,10 +217,8 @@ fn parse_token(input: &str) -> nom::IResult<&str, Token> {
         parse_mul,
         parse_add,
         parse_sub,
+        parse_comma,
         parse_number,
-        parse_sin,
-        parse_cos,
-        parse_tan,
         parse_var,
     ))(input)
 }
@@ -210,24 +263,29 @@ fn parse_pow(input: &str) -> nom::IResult<&str, Token> {
     Ok((input, Token::Pow))
 }
 
-fn parse_sin(input: &str) -> nom::IResult<&str, Token> {
-    let (input, _) = whitespaced(nom::bytes::complete::tag("sin"))(input)?;
-    Ok((input, Token::Sin))
+fn parse_comma(input: &str) -> nom::IResult<&str, Token<'_>> {
+    let (input, _) = ws_char(',')(input)?;
+    Ok((input, Token::Comma))
 }
 
-fn parse_cos(input: &str) -> nom::IResult<&str, Token> {
-    let (input, _) = whitespaced(nom::bytes::complete::tag("cos"))(input)?;
-    Ok((input, Token::Cos))
-}
+fn parse_var(input: &str) -> nom::IResult<&str, Token<'_>> {
+    let (input, output) = whitespaced(parse_identifier)(input)?;
 
-fn parse_tan(input: &str) -> nom::IResult<&str, Token> {
-    let (input, _) = whitespaced(nom::bytes::complete::tag("tan"))(input)?;
-    Ok((input, Token::Tan))
+    let token = match output {
+        "sin" => Token::Sin,
+        "cos" => Token::Cos,
+        "tan" => Token::Tan,
+        _ => Token::Var(output),
+    };
+
+    Ok((input, token))
 }
 
-fn parse_var(input: &str) -> nom::IResult<&str, Token> {
-    let (input, output) = whitespaced(nom::character::complete::alpha1)(input)?;
-    Ok((input, Token::Var(output)))
+fn parse_identifier(input: &str) -> nom::IResult<&str, &str> {
+    nom::combinator::recognize(nom::sequence::pair(
+        nom::character::complete::alpha1,
+        nom::character::complete::alphanumeric0,
+    ))(input)
 }
 
 fn whitespaced<'a, F: 'a, O, E: nom::error::ParseError<&'a str>>(
@@ -1,21 +1,25 @@
 use std::{borrow::Cow, sync::atomic::Ordering};
 
 use antigen_core::{
-    get_tagged_entity, Construct, PositionComponent, RotationComponent, ScaleComponent,
+    get_tagged_entity, get_tagged_entity_or, Changed, ChangedTrait, Construct, PositionComponent,
+    RotationComponent, ScaleComponent, Usage,
 };
 use antigen_wgpu::{
     buffer_size_of,
     wgpu::{BufferAddress, IndexFormat, LoadOp, Operations, COPY_BUFFER_ALIGNMENT},
-    BufferDataBundle,
+    BufferDataBundle, RenderPassBindGroupOffsetsComponent, RenderPassBuilder,
 };
-use hecs::{EntityBuilder, World};
+use hecs::{Entity, EntityBuilder, World};
 
 use super::{
-    BeamBuffer, BeamDepthBuffer, BeamMultisample, BeamTriangles, LineIndices, LineInstanceData,
-    LineInstances, LineMeshData, LineMeshIdComponent, LineMeshIds, LineMeshIdsComponent,
-    LineMeshInstanceData, LineMeshInstances, LineMeshes, PhosphorRenderer,
-    StorageBuffers, TriangleIndices, TriangleMeshData, TriangleMeshIds, TriangleMeshIdsComponent,
-    TriangleMeshInstanceData, TriangleMeshInstances, TriangleMeshes, Uniform, VertexData, Vertices,
+    BeamBuffer, BeamDepthBuffer, BeamMultisample, BeamTriangles, LineColorComponent, LineIndices,
+    LineInstanceData, LineInstances, LineIntensityComponent, LineMeshData, LineMeshIdComponent,
+    LineMeshIds, LineMeshIdsComponent, LineMeshInstanceData, LineMeshInstanceSlots,
+    LineMeshInstances, LineMeshNameComponent, LineMeshes, PhosphorRenderer, PortalTriangles,
+    PortalUniform, SlotAllocator, StorageBuffers, TriangleIndices, TriangleMeshBounds,
+    TriangleMeshBoundsData, TriangleMeshData, TriangleMeshIdComponent, TriangleMeshIds,
+    TriangleMeshIdsComponent, TriangleMeshInstanceData, TriangleMeshInstanceSlots,
+    TriangleMeshInstances, TriangleMeshes, Uniform, VertexData, Vertices,
     MAX_TRIANGLE_MESH_INSTANCES,
 };
 
@@ -30,7 +34,7 @@ pub fn pad_align_triangle_list(indices: &mut Vec<u16>) {
 pub fn vertices_builder(world: &mut World, vertices: Vec<VertexData>) -> EntityBuilder {
     let mut builder = EntityBuilder::new();
 
-    let vertex_entity = get_tagged_entity::<Vertices>(world).unwrap();
+    let vertex_entity = get_tagged_entity_or::<Vertices>(world).unwrap();
 
     let vertex_head = world
         .query_one_mut::<&mut antigen_wgpu::BufferLengthComponent>(vertex_entity)
@@ -53,7 +57,7 @@ pub fn vertices_builder(world: &mut World, vertices: Vec<VertexData>) -> EntityB
 pub fn line_indices_builder(world: &mut World, indices: Vec<u32>) -> EntityBuilder {
     let mut builder = EntityBuilder::new();
 
-    let line_index_entity = get_tagged_entity::<LineIndices>(world).unwrap();
+    let line_index_entity = get_tagged_entity_or::<LineIndices>(world).unwrap();
     let line_index_head = world
         .query_one_mut::<&mut antigen_wgpu::BufferLengthComponent>(line_index_entity)
         .unwrap();
@@ -80,8 +84,8 @@ pub fn line_mesh_builder(
 ) -> EntityBuilder {
     let mut builder = EntityBuilder::new();
 
-    let vertex_entity = get_tagged_entity::<Vertices>(world).unwrap();
-    let line_index_entity = get_tagged_entity::<LineIndices>(world).unwrap();
+    let vertex_entity = get_tagged_entity_or::<Vertices>(world).unwrap();
+    let line_index_entity = get_tagged_entity_or::<LineIndices>(world).unwrap();
 
     let vertex_offset = world
         .query_one_mut::<&antigen_wgpu::BufferLengthComponent>(vertex_entity)
@@ -123,7 +127,7 @@ pub fn line_mesh_data_builder(
 ) -> EntityBuilder {
     let mut builder = EntityBuilder::new();
 
-    let line_mesh_entity = get_tagged_entity::<LineMeshes>(world).unwrap();
+    let line_mesh_entity = get_tagged_entity_or::<LineMeshes>(world).unwrap();
     let line_mesh_head = world
         .query_one_mut::<&mut antigen_wgpu::BufferLengthComponent>(line_mesh_entity)
         .unwrap();
@@ -147,6 +151,8 @@ pub fn line_mesh_instance_builder(
     position: PositionComponent,
     rotation: RotationComponent,
     scale: ScaleComponent,
+    color: LineColorComponent,
+    intensity: LineIntensityComponent,
     mesh: &Cow<'static, str>,
 ) -> Option<EntityBuilder> {
     let mut builder = EntityBuilder::new();
@@ -191,6 +197,18 @@ pub fn line_mesh_instance_builder(
         line_mesh_instance_entity,
     ));
 
+    builder.add_bundle(BufferDataBundle::new(
+        intensity,
+        base_offset + buffer_size_of::<[f32; 11]>(),
+        line_mesh_instance_entity,
+    ));
+
+    builder.add_bundle(BufferDataBundle::new(
+        color,
+        base_offset + buffer_size_of::<[f32; 12]>(),
+        line_mesh_instance_entity,
+    ));
+
     let line_instance_head = world
         .query_one_mut::<&mut antigen_wgpu::BufferLengthComponent>(line_instance_entity)
         .ok()?;
@@ -211,6 +229,90 @@ pub fn line_mesh_instance_builder(
     Some(builder)
 }
 
+/// Reuse a freed line mesh instance slot with a matching line count,
+/// returning its buffer data entity
+pub fn recycle_line_mesh_instance(
+    world: &mut World,
+    position: PositionComponent,
+    rotation: RotationComponent,
+    scale: ScaleComponent,
+    color: LineColorComponent,
+    intensity: LineIntensityComponent,
+    mesh: &str,
+) -> Option<Entity> {
+    let query = world
+        .query_mut::<&LineMeshIdsComponent>()
+        .with::<LineMeshIds>();
+    let (_, mesh_ids) = query.into_iter().next()?;
+    let (line_mesh, line_count) = *mesh_ids.read().get(mesh)?;
+
+    // Line instances are allocated alongside their mesh instance,
+    // so only slots with the same line count can be reused
+    let entity = reuse_mesh_instance_slot::<LineMeshInstances, LineMeshInstanceSlots>(
+        world, line_count, position, rotation, scale,
+    )?;
+
+    let (color_data, intensity_data, mesh_id_data) = world
+        .query_one_mut::<(
+            &mut Changed<LineColorComponent>,
+            &mut Changed<LineIntensityComponent>,
+            &mut Changed<LineMeshIdComponent>,
+        )>(entity)
+        .ok()?;
+
+    **color_data = color;
+    color_data.set_changed(true);
+
+    **intensity_data = intensity;
+    intensity_data.set_changed(true);
+
+    ***mesh_id_data = line_mesh;
+    mesh_id_data.set_changed(true);
+
+    Some(entity)
+}
+
+// Allocate a freed slot under `key` from the slot allocator
+// on the instance buffer entity tagged with B,
+// writing a new transform to its buffer data entity
+fn reuse_mesh_instance_slot<B, T>(
+    world: &mut World,
+    key: u32,
+    position: PositionComponent,
+    rotation: RotationComponent,
+    scale: ScaleComponent,
+) -> Option<Entity>
+where
+    B: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+{
+    let mesh_instance_entity = get_tagged_entity::<B>(world)?;
+
+    let entity = world
+        .get_mut::<Usage<T, SlotAllocator<u32>>>(mesh_instance_entity)
+        .ok()?
+        .allocate(&key)?;
+
+    let (position_data, rotation_data, scale_data) = world
+        .query_one_mut::<(
+            &mut Changed<PositionComponent>,
+            &mut Changed<RotationComponent>,
+            &mut Changed<ScaleComponent>,
+        )>(entity)
+        .ok()?;
+
+    **position_data = position;
+    position_data.set_changed(true);
+
+    **rotation_data = rotation;
+    rotation_data.set_changed(true);
+
+    **scale_data = scale;
+    scale_data.set_changed(true);
+
+    Some(entity)
+}
+
 /// Assemble line indices for a vector of vertices in line list format
 pub fn line_list_mesh_builder(world: &mut World, vertices: Vec<VertexData>) -> EntityBuilder {
     let mut vs = 0u32;
@@ -251,7 +353,7 @@ pub fn line_builder(
 ) -> EntityBuilder {
     let mut builder = EntityBuilder::new();
 
-    let line_mesh_entity = get_tagged_entity::<LineMeshes>(world).unwrap();
+    let line_mesh_entity = get_tagged_entity_or::<LineMeshes>(world).unwrap();
 
     let vertices = vec![
         VertexData {
@@ -271,9 +373,10 @@ pub fn line_builder(
         .unwrap()
         .load(Ordering::Relaxed) as u32;
 
-    register_line_mesh_id(world, mesh, (line_mesh, line_count as u32));
+    register_line_mesh_id(world, mesh.clone(), (line_mesh, line_count as u32));
 
     builder.add_bundle(line_mesh_builder(world, vertices, indices).build());
+    builder.add(LineMeshNameComponent::construct(mesh));
 
     builder
 }
@@ -286,8 +389,8 @@ pub fn triangle_mesh_builder(
 ) -> EntityBuilder {
     let mut builder = EntityBuilder::new();
 
-    let vertex_entity = get_tagged_entity::<Vertices>(world).unwrap();
-    let triangle_index_entity = get_tagged_entity::<TriangleIndices>(world).unwrap();
+    let vertex_entity = get_tagged_entity_or::<Vertices>(world).unwrap();
+    let triangle_index_entity = get_tagged_entity_or::<TriangleIndices>(world).unwrap();
 
     // Vertices
     let vertex_head = world
@@ -330,11 +433,14 @@ pub fn triangle_mesh_data_builder(
     instance_count: u32,
     index_offset: u32,
     vertex_offset: u32,
+    bounds: TriangleMeshBoundsData,
 ) -> EntityBuilder {
     let mut builder = EntityBuilder::new();
 
-    let triangle_mesh_entity = get_tagged_entity::<TriangleMeshes>(world).unwrap();
-    let triangle_mesh_instance_entity = get_tagged_entity::<TriangleMeshInstances>(world).unwrap();
+    let triangle_mesh_entity = get_tagged_entity_or::<TriangleMeshes>(world).unwrap();
+    let triangle_mesh_bounds_entity = get_tagged_entity_or::<TriangleMeshBounds>(world).unwrap();
+    let triangle_mesh_instance_entity =
+        get_tagged_entity_or::<TriangleMeshInstances>(world).unwrap();
 
     let triangle_mesh_length = world
         .query_one_mut::<&mut antigen_wgpu::BufferLengthComponent>(triangle_mesh_entity)
@@ -354,6 +460,12 @@ pub fn triangle_mesh_data_builder(
         triangle_mesh_entity,
     ));
 
+    builder.add_bundle(BufferDataBundle::new(
+        vec![bounds],
+        buffer_size_of::<TriangleMeshBoundsData>() * triangle_mesh_head,
+        triangle_mesh_bounds_entity,
+    ));
+
     let mut indexed_indirect_builder = triangle_indexed_indirect_builder(world, triangle_mesh_head);
     builder.add_bundle(indexed_indirect_builder.build());
 
@@ -367,67 +479,98 @@ pub fn triangle_mesh_data_builder(
 }
 
 fn triangle_indexed_indirect_builder(world: &mut World, offset: u64) -> EntityBuilder {
-    let mut builder = EntityBuilder::new();
+    let uniform_entity = get_tagged_entity_or::<Uniform>(world).unwrap();
+    triangle_mesh_pass_builder(
+        world,
+        BeamTriangles,
+        5,
+        "Beam Meshes",
+        uniform_entity,
+        None,
+        offset,
+    )
+}
 
-    let beam_buffer_entity = get_tagged_entity::<BeamBuffer>(world).unwrap();
-    let beam_multisample_entity = get_tagged_entity::<BeamMultisample>(world).unwrap();
-    let beam_depth_buffer_entity = get_tagged_entity::<BeamDepthBuffer>(world).unwrap();
-    let beam_mesh_pass_entity = get_tagged_entity::<BeamTriangles>(world).unwrap();
-    let uniform_entity = get_tagged_entity::<Uniform>(world).unwrap();
-    let storage_bind_group_entity = get_tagged_entity::<StorageBuffers>(world).unwrap();
-    let renderer_entity = get_tagged_entity::<PhosphorRenderer>(world).unwrap();
+/// Assemble a pass drawing the triangle mesh at `offset` as seen through portals
+pub fn portal_triangle_mesh_pass_builder(world: &mut World, offset: u64) -> EntityBuilder {
+    let portal_uniform_entity = get_tagged_entity_or::<PortalUniform>(world).unwrap();
+    triangle_mesh_pass_builder(
+        world,
+        PortalTriangles,
+        2,
+        "Portal Meshes",
+        portal_uniform_entity,
+        Some(1),
+        offset,
+    )
+}
 
-    let vertex_entity = get_tagged_entity::<Vertices>(world).unwrap();
-    let triangle_index_entity = get_tagged_entity::<TriangleIndices>(world).unwrap();
-    let triangle_mesh_entity = get_tagged_entity::<TriangleMeshes>(world).unwrap();
+// Assemble an indirect draw of the triangle mesh at `offset`,
+// using the pipeline of the entity tagged with `T`
+fn triangle_mesh_pass_builder<T: hecs::Component>(
+    world: &mut World,
+    tag: T,
+    order: usize,
+    label: &str,
+    uniform_entity: Entity,
+    stencil_reference: Option<u32>,
+    offset: u64,
+) -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
 
-    builder.add(BeamTriangles);
+    let beam_buffer_entity = get_tagged_entity_or::<BeamBuffer>(world).unwrap();
+    let beam_multisample_entity = get_tagged_entity_or::<BeamMultisample>(world).unwrap();
+    let beam_depth_buffer_entity = get_tagged_entity_or::<BeamDepthBuffer>(world).unwrap();
+    let mesh_pass_entity = get_tagged_entity_or::<T>(world).unwrap();
+    let storage_bind_group_entity = get_tagged_entity_or::<StorageBuffers>(world).unwrap();
+    let renderer_entity = get_tagged_entity_or::<PhosphorRenderer>(world).unwrap();
+
+    let vertex_entity = get_tagged_entity_or::<Vertices>(world).unwrap();
+    let triangle_index_entity = get_tagged_entity_or::<TriangleIndices>(world).unwrap();
+    let triangle_mesh_entity = get_tagged_entity_or::<TriangleMeshes>(world).unwrap();
+
+    builder.add(tag);
+    builder.add(TriangleMeshIdComponent::construct(offset as u32));
+    builder.add(RenderPassBindGroupOffsetsComponent::construct(vec![]));
+
+    let mut render_pass = RenderPassBuilder::new(order, renderer_entity)
+        .label(label)
+        .color_attachment(
+            beam_multisample_entity,
+            Some(beam_buffer_entity),
+            Operations {
+                load: LoadOp::Load,
+                store: true,
+            },
+        )
+        .depth(
+            beam_depth_buffer_entity,
+            Some(Operations {
+                load: LoadOp::Load,
+                store: true,
+            }),
+            Some(Operations {
+                load: LoadOp::Load,
+                store: true,
+            }),
+        )
+        .pipeline(mesh_pass_entity)
+        .vertex_buffer(vertex_entity, 0..480000)
+        .index_buffer(triangle_index_entity, 0..20000, IndexFormat::Uint16)
+        .bind_group(uniform_entity, vec![])
+        .bind_group(storage_bind_group_entity, vec![0]);
+
+    if let Some(stencil_reference) = stencil_reference {
+        render_pass = render_pass.stencil_reference(stencil_reference);
+    }
 
     builder.add_bundle(
-        antigen_wgpu::RenderPassBundle::draw_indexed_indirect(
-            1,
-            Some("Beam Meshes".into()),
-            vec![(
-                beam_multisample_entity,
-                Some(beam_buffer_entity),
-                Operations {
-                    load: LoadOp::Load,
-                    store: true,
-                },
-            )],
-            Some((
-                beam_depth_buffer_entity,
-                Some(Operations {
-                    load: LoadOp::Load,
-                    store: true,
-                }),
-                None,
-            )),
-            beam_mesh_pass_entity,
-            vec![(vertex_entity, 0..480000)],
-            Some((triangle_index_entity, 0..20000, IndexFormat::Uint16)),
-            vec![
-                (uniform_entity, vec![]),
-                (
-                    storage_bind_group_entity,
-                    vec![
-                        buffer_size_of::<TriangleMeshInstanceData>() as u32
-                            * (MAX_TRIANGLE_MESH_INSTANCES * offset as usize) as u32,
-                    ],
-                ),
-            ],
-            vec![],
-            None,
-            None,
-            None,
-            None,
-            (
+        render_pass
+            .draw_indexed_indirect(
                 triangle_mesh_entity,
                 buffer_size_of::<TriangleMeshData>() * offset,
-            ),
-            renderer_entity,
-        )
-        .build(),
+            )
+            .build(),
     );
 
     builder
@@ -485,6 +628,30 @@ pub fn triangle_mesh_instance_builder(
     Some(builder)
 }
 
+/// Reuse a freed triangle mesh instance slot for the same mesh,
+/// returning its buffer data entity
+pub fn recycle_triangle_mesh_instance(
+    world: &mut World,
+    mesh: &Cow<'static, str>,
+    position: PositionComponent,
+    rotation: RotationComponent,
+    scale: ScaleComponent,
+) -> Option<Entity> {
+    let query = world
+        .query_mut::<&TriangleMeshIdsComponent>()
+        .with::<TriangleMeshIds>();
+    let (_, mesh_ids) = query.into_iter().next()?;
+    let triangle_mesh = *mesh_ids.read().get(mesh)?;
+
+    reuse_mesh_instance_slot::<TriangleMeshInstances, TriangleMeshInstanceSlots>(
+        world,
+        triangle_mesh,
+        position,
+        rotation,
+        scale,
+    )
+}
+
 /// Assemble triangle indices for a list of vertices in triangle list format
 pub fn triangle_list_mesh_builder(
     world: &mut World,
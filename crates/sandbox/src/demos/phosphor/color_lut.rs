@@ -0,0 +1,212 @@
+use std::{error::Error, ops::Deref};
+
+use antigen_wgpu::wgpu::Extent3d;
+
+/// 3D color lookup table applied to the tonemapped image
+///
+/// Texels are stored as RGBA8, ordered red-fastest, then green, then blue,
+/// matching both the .cube data order and the layout of a 3D texture upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorLutComponent {
+    size: u32,
+    texels: Vec<[u8; 4]>,
+}
+
+impl ColorLutComponent {
+    /// Neutral LUT that maps every color to itself
+    pub fn identity(size: u32) -> Self {
+        let max = (size - 1) as f32;
+        let texels = (0..size)
+            .flat_map(|b| (0..size).flat_map(move |g| (0..size).map(move |r| [r, g, b])))
+            .map(|rgb| {
+                let [r, g, b] = rgb.map(|c| unorm8(c as f32 / max));
+                [r, g, b, 255]
+            })
+            .collect();
+
+        ColorLutComponent { size, texels }
+    }
+
+    /// Parse an Adobe / Resolve .cube file
+    ///
+    /// Only 3D LUTs over the default 0..1 domain are supported.
+    pub fn from_cube(source: &str) -> Result<Self, Box<dyn Error>> {
+        let mut size = None;
+        let mut texels = vec![];
+
+        for line in source.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let keyword = tokens.next().unwrap();
+            match keyword {
+                "TITLE" => (),
+                "LUT_3D_SIZE" => {
+                    let value = tokens.next().ok_or("Missing LUT_3D_SIZE value")?;
+                    size = Some(value.parse::<u32>()?);
+                }
+                "LUT_1D_SIZE" => return Err("1D LUTs are not supported".into()),
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    for value in tokens {
+                        if value.parse::<f32>()? != expected {
+                            return Err(format!("Unsupported {} {}", keyword, line).into());
+                        }
+                    }
+                }
+                _ => {
+                    let mut rgb = [0.0; 3];
+                    rgb[0] = keyword.parse::<f32>()?;
+                    for c in rgb.iter_mut().skip(1) {
+                        *c = tokens.next().ok_or("Missing LUT component")?.parse()?;
+                    }
+                    let [r, g, b] = rgb.map(unorm8);
+                    texels.push([r, g, b, 255]);
+                }
+            }
+        }
+
+        let size = size.ok_or("Missing LUT_3D_SIZE")?;
+        if size < 2 {
+            return Err(format!("LUT size {} is too small", size).into());
+        }
+
+        let expected = (size * size * size) as usize;
+        if texels.len() != expected {
+            return Err(format!("Expected {} LUT entries, found {}", expected, texels.len()).into());
+        }
+
+        Ok(ColorLutComponent { size, texels })
+    }
+
+    /// Decode a LUT laid out as a horizontal strip of blue slices
+    ///
+    /// An N-sized LUT is an N*N x N image, where each N x N slice maps red to X
+    /// and green to Y, and successive slices step through blue.
+    pub fn from_strip_png(png_data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut decoder = png::Decoder::new(png_data);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+        data.truncate(info.buffer_size());
+
+        let size = info.height;
+        if size < 2 || info.width != size * size {
+            return Err(format!(
+                "Expected an N*N x N strip, found {}x{}",
+                info.width, info.height
+            )
+            .into());
+        }
+
+        let pixels: Vec<[u8; 4]> = match info.color_type {
+            png::ColorType::Rgba => data.chunks(4).map(|p| [p[0], p[1], p[2], 255]).collect(),
+            png::ColorType::Rgb => data.chunks(3).map(|p| [p[0], p[1], p[2], 255]).collect(),
+            color_type => return Err(format!("Unsupported LUT color type {:?}", color_type).into()),
+        };
+
+        let texels = (0..size)
+            .flat_map(|b| (0..size).flat_map(move |g| (0..size).map(move |r| (r, g, b))))
+            .map(|(r, g, b)| pixels[(g * info.width + b * size + r) as usize])
+            .collect();
+
+        Ok(ColorLutComponent { size, texels })
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn extent(&self) -> Extent3d {
+        Extent3d {
+            width: self.size,
+            height: self.size,
+            depth_or_array_layers: self.size,
+        }
+    }
+}
+
+impl Deref for ColorLutComponent {
+    type Target = [[u8; 4]];
+
+    fn deref(&self) -> &Self::Target {
+        &self.texels
+    }
+}
+
+fn unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texel(lut: &ColorLutComponent, r: u32, g: u32, b: u32) -> [u8; 4] {
+        let size = lut.size();
+        lut[(r + g * size + b * size * size) as usize]
+    }
+
+    #[test]
+    fn identity_spans_unit_cube() {
+        let lut = ColorLutComponent::identity(4);
+        assert_eq!(lut.len(), 64);
+        assert_eq!(texel(&lut, 0, 0, 0), [0, 0, 0, 255]);
+        assert_eq!(texel(&lut, 3, 0, 0), [255, 0, 0, 255]);
+        assert_eq!(texel(&lut, 0, 3, 0), [0, 255, 0, 255]);
+        assert_eq!(texel(&lut, 0, 0, 3), [0, 0, 255, 255]);
+        assert_eq!(texel(&lut, 1, 2, 3), [85, 170, 255, 255]);
+    }
+
+    #[test]
+    fn cube_identity_matches_generated() {
+        let mut cube = String::from("# Identity\nTITLE \"Identity\"\nLUT_3D_SIZE 2\n\n");
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    cube += &format!("{} {} {}\n", r as f32, g as f32, b as f32);
+                }
+            }
+        }
+
+        let lut = ColorLutComponent::from_cube(&cube).unwrap();
+        assert_eq!(lut, ColorLutComponent::identity(2));
+    }
+
+    #[test]
+    fn cube_rejects_wrong_entry_count() {
+        let cube = "LUT_3D_SIZE 2\n0 0 0\n1 1 1\n";
+        assert!(ColorLutComponent::from_cube(cube).is_err());
+    }
+
+    #[test]
+    fn strip_png_matches_generated() {
+        let size = 4;
+        let identity = ColorLutComponent::identity(size);
+
+        let mut strip = vec![0u8; (size * size * size * 3) as usize];
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let i = ((g * size * size + b * size + r) * 3) as usize;
+                    strip[i..i + 3].copy_from_slice(&texel(&identity, r, g, b)[..3]);
+                }
+            }
+        }
+
+        let mut png_data = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut png_data, size * size, size);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&strip).unwrap();
+        }
+
+        let lut = ColorLutComponent::from_strip_png(&png_data).unwrap();
+        assert_eq!(lut, identity);
+    }
+}
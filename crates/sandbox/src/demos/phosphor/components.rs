@@ -1,10 +1,24 @@
 use bytemuck::{Pod, Zeroable};
 use parking_lot::RwLock;
-use rapier3d::prelude::IntersectionEvent;
-use std::{borrow::Cow, collections::{BTreeMap, BTreeSet}, sync::Arc, time::Instant, marker::PhantomData};
+use rapier3d::prelude::{ColliderHandle, IntersectionEvent, Vector};
+use serde::Deserialize;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use std::path::PathBuf;
+use winit::event::{MouseButton, VirtualKeyCode};
 
 use antigen_core::{Changed, LazyComponent, Usage};
 
+use super::{ClassKind, ClassProperty, PropertyType};
+use antigen_wgpu::vertex_layout;
+use antigen_shambler::shambler::{entity::EntityId, shalrath::repr::Properties, ConvexHull};
+use hecs::{Entity, EntityBuilder, World};
+
 // Phosphor renderer tag
 pub struct PhosphorRenderer;
 
@@ -13,6 +27,8 @@ pub enum StartTime {}
 pub enum Timestamp {}
 pub enum TotalTime {}
 pub enum DeltaTime {}
+pub enum BloomIntensity {}
+pub enum BloomLevel {}
 
 pub struct BeamBuffer;
 pub struct BeamMultisample;
@@ -33,6 +49,12 @@ pub struct TriangleMeshInstances;
 #[derive(Debug, Copy, Clone)]
 pub struct LineVertices;
 
+#[derive(Debug, Copy, Clone)]
+pub struct TriangleMeshBounds;
+
+#[derive(Debug, Copy, Clone)]
+pub struct VisibleTriangleMeshInstances;
+
 #[derive(Debug, Copy, Clone)]
 pub struct LineIndices;
 
@@ -55,14 +77,63 @@ pub struct BeamClear;
 pub struct BeamLines;
 pub struct BeamTriangles;
 pub struct Tonemap;
+pub struct Bloom;
+pub struct BloomDownsample;
+pub struct BloomBlur;
+pub struct BloomDownsampleBuffer;
+pub struct BloomBlurBuffer;
+pub struct ColorLut;
+pub struct Skybox;
+pub struct FrustumCull;
+pub struct PortalUniform;
+pub struct PortalInstances;
+pub struct PortalStencil;
+pub struct PortalDepth;
+pub struct PortalTriangles;
+pub struct PortalLines;
+pub struct LineSmear;
+pub struct LineSmearFront;
+pub struct LineSmearBack;
+pub struct LineSmears;
+pub struct LineSmearUniform;
+pub struct BeamLineSmears;
 
 pub enum MapFile {}
 
+// Tag for entities spawned from a loaded map,
+// which are despawned when it's replaced by another
+pub struct MapEntity;
+
+/// Path of the map to load, which is reloaded whenever it changes
+pub enum MapPath {}
+pub type MapPathComponent = Usage<MapPath, PathBuf>;
+
+/// Marks a portal quad, spanning the XY plane of its transform scaled by its ScaleComponent,
+/// storing the offset from the camera of the view seen through it
+pub enum Portal {}
+pub type PortalComponent = Usage<Portal, nalgebra::Vector3<f32>>;
+
+/// Volume of a room, as the world-space hulls of the brushes it's built from
+pub enum Room {}
+pub type RoomComponent = Usage<Room, Vec<ConvexHull>>;
+
+/// World-space center of a room, used to pick between overlapping or distant rooms
+pub enum RoomCenter {}
+pub type RoomCenterComponent = Usage<RoomCenter, nalgebra::Vector3<f32>>;
+
+/// The room containing the camera, or the nearest one if it's outside all rooms
+pub enum CurrentRoom {}
+pub type CurrentRoomComponent = Usage<CurrentRoom, Option<Entity>>;
+
 // Usage-tagged components
 pub type StartTimeComponent = Usage<StartTime, Instant>;
 pub type TimestampComponent = Usage<Timestamp, Instant>;
 pub type TotalTimeComponent = Usage<TotalTime, f32>;
 pub type DeltaTimeComponent = Usage<DeltaTime, f32>;
+pub type BloomIntensityComponent = Usage<BloomIntensity, f32>;
+
+// Mip level of a bloom buffer, where level 0 is half the size of the phosphor buffer
+pub type BloomLevelComponent = Usage<BloomLevel, usize>;
 
 pub struct PerspectiveMatrix;
 pub type PerspectiveMatrixComponent = Usage<PerspectiveMatrix, nalgebra::Matrix4<f32>>;
@@ -72,14 +143,84 @@ pub type OrthographicMatrixComponent = Usage<OrthographicMatrix, nalgebra::Matri
 
 pub struct Camera;
 
-#[derive(Debug, Default, Copy, Clone)]
-pub struct PlayerInputComponent {
-    pub forward: f32,
-    pub back: f32,
-    pub left: f32,
-    pub right: f32,
-    pub up: f32,
-    pub down: f32,
+/// Where the skybox gradient is composited relative to the scene
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SkyboxMode {
+    /// Drawn into the beam buffer behind solid geometry, graded by view elevation
+    Underlay,
+    /// Added over the final image in screen space, like a Vectrex color overlay
+    Overlay,
+}
+
+/// MechWarrior 2-style gradient sky
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SkyboxComponent {
+    pub mode: SkyboxMode,
+    pub top: (f32, f32, f32),
+    pub bottom: (f32, f32, f32),
+}
+
+// Tag for the camera spawn point read from a loaded map,
+// consumed by spawn_camera_at_player_start_system
+pub struct PlayerStart;
+
+// Tag for the game thread's character controller that drives the camera
+pub struct Player;
+
+/// Input actions, decoupled from the physical inputs bound to them
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum InputAction {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Jump,
+    Interact,
+    Pause,
+    DebugColliders,
+}
+
+/// Physical inputs that can be bound to an action
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub enum PhysicalInput {
+    Key(VirtualKeyCode),
+    MouseButton(MouseButton),
+}
+
+// Binding table from physical inputs to actions, loaded from file
+pub type InputBindingsComponent = HashMap<PhysicalInput, InputAction>;
+
+/// Conventional WASD bindings, used until a bindings file has been loaded
+pub fn default_input_bindings() -> InputBindingsComponent {
+    [
+        (PhysicalInput::Key(VirtualKeyCode::W), InputAction::MoveForward),
+        (PhysicalInput::Key(VirtualKeyCode::S), InputAction::MoveBack),
+        (PhysicalInput::Key(VirtualKeyCode::A), InputAction::MoveLeft),
+        (PhysicalInput::Key(VirtualKeyCode::D), InputAction::MoveRight),
+        (PhysicalInput::Key(VirtualKeyCode::Space), InputAction::MoveUp),
+        (PhysicalInput::Key(VirtualKeyCode::LControl), InputAction::MoveDown),
+        (PhysicalInput::MouseButton(MouseButton::Left), InputAction::Interact),
+        (PhysicalInput::Key(VirtualKeyCode::P), InputAction::Pause),
+        (PhysicalInput::Key(VirtualKeyCode::F3), InputAction::DebugColliders),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// Current value of each input action
+#[derive(Debug, Default, Clone)]
+pub struct InputActionsComponent(BTreeMap<InputAction, f32>);
+
+impl InputActionsComponent {
+    pub fn get(&self, action: InputAction) -> f32 {
+        self.0.get(&action).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, action: InputAction, value: f32) {
+        self.0.insert(action, value);
+    }
 }
 
 /// Mesh ID map
@@ -91,6 +232,10 @@ pub type TriangleMeshIdsComponent = Arc<RwLock<BTreeMap<Cow<'static, str>, u32>>
 pub struct LineMeshIds;
 pub type LineMeshIdsComponent = Arc<RwLock<BTreeMap<Cow<'static, str>, (u32, u32)>>>;
 
+// Triangle Mesh ID
+pub enum TriangleMeshId {}
+pub type TriangleMeshIdComponent = Usage<TriangleMeshId, u32>;
+
 // Line Mesh ID
 pub enum LineMeshId {}
 pub type LineMeshIdComponent = Usage<LineMeshId, u32>;
@@ -99,35 +244,47 @@ pub type LineMeshIdComponent = Usage<LineMeshId, u32>;
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
 pub struct UniformData {
-    perspective: [[f32; 4]; 4],
-    orthographic: [[f32; 4]; 4],
-    cam_pos: [f32; 4],
-    cam_rot: [f32; 4],
-    total_time: f32,
-    delta_time: f32,
-    _pad_0: [f32; 2],
+    pub perspective: [[f32; 4]; 4],
+    pub orthographic: [[f32; 4]; 4],
+    pub cam_pos: [f32; 4],
+    pub cam_rot: [f32; 4],
+    pub total_time: f32,
+    pub delta_time: f32,
+    pub bloom_intensity: f32,
+    pub _pad_0: f32,
+    pub skybox_top: [f32; 4],
+    pub skybox_bottom: [f32; 4],
 }
 
-/// Vertex data for 2D line meshes
-#[repr(C)]
-#[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
-pub struct LineVertexData {
-    pub position: [f32; 3],
-    pub end: f32,
+vertex_layout! {
+    /// Vertex data for 2D line meshes
+    #[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
+    pub struct LineVertexData {
+        #[location(0)]
+        pub position: [f32; 3],
+        #[location(1)]
+        pub end: f32,
+    }
 }
 
 pub type LineVertexDataComponent = Vec<LineVertexData>;
 
-/// Vertex data for 3D triangle meshes
-#[repr(C)]
-#[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
-pub struct VertexData {
-    pub position: [f32; 3],
-    pub surface_color: [f32; 3],
-    pub line_color: [f32; 3],
-    pub intensity: f32,
-    pub delta_intensity: f32,
-    pub _pad: f32,
+vertex_layout! {
+    /// Vertex data for 3D triangle meshes
+    #[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
+    pub struct VertexData {
+        #[location(0)]
+        pub position: [f32; 3],
+        #[location(1)]
+        pub surface_color: [f32; 3],
+        #[location(2)]
+        pub line_color: [f32; 3],
+        #[location(3)]
+        pub intensity: f32,
+        #[location(4)]
+        pub delta_intensity: f32,
+        pub _pad: f32,
+    }
 }
 
 impl VertexData {
@@ -178,6 +335,46 @@ pub struct TriangleMeshInstanceData {
 
 pub type TriangleMeshInstanceDataComponent = Vec<TriangleMeshInstanceData>;
 
+/// Mesh-space bounding sphere of a triangle mesh, used for frustum culling its instances
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct TriangleMeshBoundsData {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+impl TriangleMeshBoundsData {
+    /// Sphere around the center of the vertices' bounding box,
+    /// which is looser than the minimal sphere but never excludes a vertex
+    pub fn from_vertices(vertices: &[VertexData]) -> Self {
+        if vertices.is_empty() {
+            return TriangleMeshBoundsData::default();
+        }
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for vertex in vertices {
+            for i in 0..3 {
+                min[i] = min[i].min(vertex.position[i]);
+                max[i] = max[i].max(vertex.position[i]);
+            }
+        }
+
+        let center = [0, 1, 2].map(|i| (min[i] + max[i]) * 0.5);
+        let radius = vertices
+            .iter()
+            .map(|vertex| {
+                let delta = [0, 1, 2].map(|i| vertex.position[i] - center[i]);
+                (delta[0] * delta[0] + delta[1] * delta[1] + delta[2] * delta[2]).sqrt()
+            })
+            .fold(0.0, f32::max);
+
+        TriangleMeshBoundsData { center, radius }
+    }
+}
+
+pub type TriangleMeshBoundsDataComponent = Vec<TriangleMeshBoundsData>;
+
 pub type LineIndexData = u32;
 pub type LineIndexDataComponent = Vec<LineIndexData>;
 
@@ -199,11 +396,26 @@ pub struct LineMeshInstanceData {
     pub mesh: u32,
     pub rotation: [f32; 4],
     pub scale: [f32; 3],
+    pub intensity: f32,
+    pub color: [f32; 3],
     pub _pad: f32,
 }
 
 pub type LineMeshInstanceDataComponent = Vec<LineMeshInstanceData>;
 
+/// Tint multiplied with the line color of a line mesh instance's vertices
+pub enum LineColor {}
+pub type LineColorComponent = Usage<LineColor, nalgebra::Vector3<f32>>;
+
+/// Multiplier for the intensity of a line mesh instance's vertices
+pub enum LineIntensity {}
+pub type LineIntensityComponent = Usage<LineIntensity, f32>;
+
+/// Toggles a line mesh instance's LineIntensityComponent on and off, staying in each state
+/// for the given duration
+pub enum Blink {}
+pub type BlinkComponent = Usage<Blink, Duration>;
+
 /// Instance data representing a single line
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
@@ -214,10 +426,61 @@ pub struct LineInstanceData {
 
 pub type LineInstanceDataComponent = Vec<LineInstanceData>;
 
+/// World-space endpoints of a line instance as of the frame that wrote them,
+/// with w set to 1.0 if the line was visible
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
+pub struct LineHistoryData {
+    pub v0: [f32; 4],
+    pub v1: [f32; 4],
+}
+
+/// Quad connecting a line instance's previous endpoints to its current ones,
+/// along with the color and intensity of each end
+///
+/// The w of the current endpoints is set to 1.0 if the line moved while visible.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
+pub struct LineSmearData {
+    pub previous: [[f32; 4]; 2],
+    pub current: [[f32; 4]; 2],
+    pub color: [[f32; 4]; 2],
+}
+
+/// Line smear compute pass parameters
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct LineSmearUniformData {
+    pub line_count: u32,
+    pub _pad: [u32; 3],
+}
+
+vertex_layout! {
+    /// Per-instance vertex data for portal quads
+    #[derive(Debug, Default, Copy, Clone, PartialEq, Pod, Zeroable)]
+    pub struct PortalInstanceData {
+        #[location(0)]
+        pub position: [f32; 3],
+        #[location(1)]
+        pub rotation: [f32; 4],
+        #[location(2)]
+        pub extents: [f32; 2],
+    }
+}
+
+pub type PortalInstanceDataComponent = Vec<PortalInstanceData>;
+
+/// Name a line mesh's vertex data was registered under, for lookup by other entities
+pub enum LineMeshName {}
+pub type LineMeshNameComponent = Usage<LineMeshName, Cow<'static, str>>;
+
+/// Animates the vertices of its entity's line mesh as a trail over a function of time
 pub struct Oscilloscope {
     f: Box<dyn Fn(f32) -> (f32, f32, f32) + Send + Sync>,
     speed: f32,
     magnitude: f32,
+    phase: f32,
+    period: Option<f32>,
 }
 
 impl Oscilloscope {
@@ -229,11 +492,43 @@ impl Oscilloscope {
             speed,
             magnitude,
             f: Box::new(f),
+            phase: 0.0,
+            period: None,
+        }
+    }
+
+    /// Offset added to the input of the function, after scaling time by speed
+    pub fn with_phase(mut self, phase: f32) -> Self {
+        self.phase = phase;
+        self.wrap_phase();
+        self
+    }
+
+    /// Loop the input of the function over `[0, period)`
+    ///
+    /// The function should repeat over the period, so wrapping doesn't introduce discontinuities.
+    /// Non-positive periods are ignored.
+    pub fn with_period(mut self, period: f32) -> Self {
+        self.period = Some(period).filter(|period| *period > 0.0);
+        self.wrap_phase();
+        self
+    }
+
+    // Keep the phase within the period, so any phase traces the same loop
+    fn wrap_phase(&mut self) {
+        if let Some(period) = self.period {
+            self.phase = self.phase.rem_euclid(period);
         }
     }
 
     pub fn eval(&self, f: f32) -> (f32, f32, f32) {
-        let (x, y, z) = (self.f)(f * self.speed);
+        let f = f * self.speed + self.phase;
+        let f = match self.period {
+            Some(period) => f.rem_euclid(period),
+            None => f,
+        };
+
+        let (x, y, z) = (self.f)(f);
         (x * self.magnitude, y * self.magnitude, z * self.magnitude)
     }
 }
@@ -242,10 +537,118 @@ impl Oscilloscope {
 pub struct Timer {
     pub timestamp: std::time::Instant,
     pub duration: std::time::Duration,
+    /// Whether the timer restarts after elapsing, rather than stopping
+    pub repeat: bool,
+    /// Whether the timer is counting toward its duration
+    pub running: bool,
 }
 
+impl Timer {
+    /// Create a timer that starts running now
+    pub fn new(duration: Duration, repeat: bool) -> Self {
+        Timer {
+            timestamp: Instant::now(),
+            duration,
+            repeat,
+            running: true,
+        }
+    }
+}
+
+/// Timer that's marked as changed each time it elapses
 pub type TimerComponent = Changed<Timer>;
 
+/// Column and row of a character in a block of text
+pub type TextCell = (usize, usize);
+
+/// Control character that sets the color of subsequent characters, followed by RRGGBB in hex
+pub const TEXT_CONTROL_COLOR: char = '\u{1}';
+
+/// Control character that toggles blinking for subsequent characters
+pub const TEXT_CONTROL_BLINK: char = '\u{2}';
+
+/// A visible character of a block of text, styled by the control characters preceding it
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Glyph {
+    pub c: char,
+    pub color: [u8; 3],
+    pub blink: bool,
+}
+
+/// A block of text, drawn as one line mesh instance per visible character
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub string: String,
+    /// Column at which lines are wrapped onto the next row
+    pub wrap: Option<usize>,
+}
+
+impl Text {
+    /// Lay out the visible characters of the string, split into rows by newlines and wrapping
+    ///
+    /// Styles set by control characters carry over into subsequent lines.
+    /// Control characters take up no column, and unknown ones are ignored.
+    pub fn cells(&self) -> BTreeMap<TextCell, Glyph> {
+        let mut cells = BTreeMap::new();
+        let mut row = 0;
+        let mut color = [0xff; 3];
+        let mut blink = false;
+
+        for line in self.string.split('\n') {
+            // Whitespace takes up a column without drawing a glyph
+            let mut glyphs = vec![];
+            let mut chars = line.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    TEXT_CONTROL_COLOR => {
+                        // A malformed color leaves its digits to be drawn as text
+                        let rest = chars.as_str();
+                        if let Some(rgb) = rest.get(..6).and_then(parse_hex_color) {
+                            color = rgb;
+                            chars = rest[6..].chars();
+                        }
+                    }
+                    TEXT_CONTROL_BLINK => blink = !blink,
+                    c if c.is_whitespace() => glyphs.push(None),
+                    c if c.is_control() => (),
+                    c => glyphs.push(Some(Glyph { c, color, blink })),
+                }
+            }
+
+            let width = self.wrap.unwrap_or(glyphs.len()).max(1);
+
+            // An empty line still takes up a row
+            for chunk in glyphs.chunks(width).chain(glyphs.is_empty().then(|| &[][..])) {
+                for (column, glyph) in chunk.iter().enumerate() {
+                    if let Some(glyph) = glyph {
+                        cells.insert((column, row), *glyph);
+                    }
+                }
+                row += 1;
+            }
+        }
+
+        cells
+    }
+}
+
+// Parse an RRGGBB hex color
+fn parse_hex_color(hex: &str) -> Option<[u8; 3]> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let [_, r, g, b] = u32::from_str_radix(hex, 16).ok()?.to_be_bytes();
+    Some([r, g, b])
+}
+
+/// Text to display, changed to redraw its damaged cells
+pub type TextComponent = Changed<Text>;
+
+/// Characters of a text block, and the line mesh instances drawing them
+pub enum TextCells {}
+pub type TextCellsComponent = Usage<TextCells, BTreeMap<TextCell, (Glyph, Entity)>>;
+
 pub enum TriangleMeshInstance {}
 pub type TriangleMeshInstanceComponent<'a> =
     Usage<TriangleMeshInstance, LazyComponent<(), Cow<'static, str>>>;
@@ -254,20 +657,172 @@ pub enum LineMeshInstance {}
 pub type LineMeshInstanceComponent<'a> =
     Usage<LineMeshInstance, LazyComponent<(), Cow<'static, str>>>;
 
+/// Freed mesh instance slots, held as their buffer data entities
+///
+/// Slots are grouped by a key describing which allocations can reuse them,
+/// such as the mesh a triangle instance was written for
+/// or the number of line instances trailing a line mesh instance.
+#[derive(Debug, Clone)]
+pub struct SlotAllocator<K> {
+    free: BTreeMap<K, Vec<Entity>>,
+}
+
+impl<K> Default for SlotAllocator<K> {
+    fn default() -> Self {
+        SlotAllocator {
+            free: Default::default(),
+        }
+    }
+}
+
+impl<K: Ord> SlotAllocator<K> {
+    /// Return a slot to the allocator
+    pub fn free(&mut self, key: K, entity: Entity) {
+        self.free.entry(key).or_default().push(entity);
+    }
+
+    /// Take a free slot matching the given key, if one exists
+    pub fn allocate(&mut self, key: &K) -> Option<Entity> {
+        let slots = self.free.get_mut(key)?;
+        let entity = slots.pop();
+        if slots.is_empty() {
+            self.free.remove(key);
+        }
+        entity
+    }
+
+    /// Forget a free slot, such as when it's released from the end of its buffer
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        let removed = self.free.values_mut().any(|slots| {
+            if let Some(index) = slots.iter().position(|candidate| *candidate == entity) {
+                slots.swap_remove(index);
+                true
+            } else {
+                false
+            }
+        });
+
+        self.free.retain(|_, slots| !slots.is_empty());
+        removed
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, Entity)> {
+        self.free
+            .iter()
+            .flat_map(|(key, slots)| slots.iter().map(move |entity| (key, *entity)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.free.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+}
+
+// Freed line mesh instance slots, keyed by line count
+pub enum LineMeshInstanceSlots {}
+pub type LineMeshInstanceSlotsComponent = Usage<LineMeshInstanceSlots, SlotAllocator<u32>>;
+
+// Freed triangle mesh instance slots, keyed by triangle mesh
+pub enum TriangleMeshInstanceSlots {}
+pub type TriangleMeshInstanceSlotsComponent =
+    Usage<TriangleMeshInstanceSlots, SlotAllocator<u32>>;
+
+// Marker for mesh instances to be despawned by despawn_*_mesh_instances_system
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct PendingDespawn;
+
+/// Whether collider outlines are overlaid on the scene
+pub enum DebugColliders {}
+pub type DebugCollidersComponent = Usage<DebugColliders, bool>;
+
+// Line mesh instances outlining each collider while collider debugging is enabled,
+// alongside the local offset and scale each instance is drawn with
+pub enum DebugColliderOutlines {}
+pub type DebugColliderOutlinesComponent = Usage<
+    DebugColliderOutlines,
+    HashMap<ColliderHandle, (Entity, Vector<f32>, Vector<f32>)>,
+>;
+
+// Outline meshes built from collider geometry and their line index counts,
+// keyed by a hash of that geometry so each distinct shape is only uploaded once
+pub enum DebugColliderMeshes {}
+pub type DebugColliderMeshesComponent = Usage<DebugColliderMeshes, HashMap<u64, (String, usize)>>;
+
+/// Constructs a collision shape at a given scale
+pub type SharedShapeFn = Box<
+    dyn Fn(nalgebra::Vector3<f32>) -> rapier3d::geometry::SharedShape + Send + Sync + 'static,
+>;
+
 pub struct SharedShapes;
-pub type SharedShapesComponent = Usage<
-    SharedShapes,
-    BTreeMap<
-        String,
-        Box<
-            dyn Fn(nalgebra::Vector3<f32>) -> rapier3d::geometry::SharedShape
-                + Send
-                + Sync
-                + 'static,
-        >,
-    >,
+pub type SharedShapesComponent = Usage<SharedShapes, BTreeMap<String, SharedShapeFn>>;
+
+/// Map entity being assembled by a classname handler
+pub struct ClassnameContext<'a> {
+    pub entity: &'a EntityId,
+    pub properties: &'a Properties,
+    pub origin: nalgebra::Vector3<f32>,
+    pub scale: nalgebra::Vector3<f32>,
+}
+
+/// Adds the components described by a map entity's properties to its builder,
+/// returning any additional entities to spawn alongside it
+pub type ClassnameHandler = Arc<
+    dyn Fn(&mut World, &ClassnameContext, &mut EntityBuilder) -> Vec<EntityBuilder>
+        + Send
+        + Sync
+        + 'static,
 >;
 
+/// Handler for a map entity class, along with the description exported to editors
+#[derive(Clone)]
+pub struct ClassDefinition {
+    pub kind: ClassKind,
+    pub description: &'static str,
+    pub properties: Vec<ClassProperty>,
+    pub handler: ClassnameHandler,
+}
+
+impl ClassDefinition {
+    pub fn new<F>(kind: ClassKind, description: &'static str, handler: F) -> Self
+    where
+        F: Fn(&mut World, &ClassnameContext, &mut EntityBuilder) -> Vec<EntityBuilder>
+            + Send
+            + Sync
+            + 'static,
+    {
+        ClassDefinition {
+            kind,
+            description,
+            properties: vec![],
+            handler: Arc::new(handler),
+        }
+    }
+
+    /// Declare a property read by the handler
+    pub fn property(
+        mut self,
+        key: &'static str,
+        ty: PropertyType,
+        default: Option<&'static str>,
+        description: &'static str,
+    ) -> Self {
+        self.properties.push(ClassProperty {
+            key,
+            ty,
+            default,
+            description,
+        });
+        self
+    }
+}
+
+pub struct ClassnameRegistry;
+pub type ClassnameRegistryComponent =
+    Usage<ClassnameRegistry, BTreeMap<String, ClassDefinition>>;
+
 pub struct EventInput;
 pub type EventInputComponent<T> = Usage<EventInput, Vec<T>>;
 
@@ -327,6 +882,26 @@ pub type MoverEventOutputComponent = EventOutputComponent<MoverEvent>;
 pub type ColliderEventInputComponent = EventInputComponent<IntersectionEvent>;
 pub type ColliderEventOutputComponent = EventOutputComponent<IntersectionEvent>;
 
+/// Sound played when this entity's collider is hit, selected per surface material
+#[derive(Debug, Clone)]
+pub struct ImpactSoundComponent {
+    pub sound: Cow<'static, str>,
+    // Impulse at which the sound plays at full volume
+    pub full_volume_impulse: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImpactSoundEvent {
+    pub sound: Cow<'static, str>,
+    pub volume: f32,
+}
+
+pub type ImpactSoundEventOutputComponent = EventOutputComponent<ImpactSoundEvent>;
+
+/// Event pushed to a co-located EventOutputComponent when a TimerComponent elapses
+pub struct TimerEvent;
+pub type TimerEventComponent<T> = Usage<TimerEvent, T>;
+
 pub struct EventIn;
 pub type EventInComponent = Usage<EventIn, Cow<'static, str>>;
 
@@ -335,3 +910,139 @@ pub type EventOutComponent = Usage<EventOut, Cow<'static, str>>;
 
 pub struct EventTarget<T>(PhantomData<T>);
 pub type EventTargetComponent<T> = Usage<EventTarget<T>, Cow<'static, str>>;
+
+// Render-thread entity that receives a watched shader file's module when it is reloaded
+pub enum ShaderReloadTarget {}
+pub type ShaderReloadTargetComponent = Usage<ShaderReloadTarget, Entity>;
+
+// Render-thread entity that receives a watched input bindings file's bindings when it is reloaded
+pub enum InputBindingsReloadTarget {}
+pub type InputBindingsReloadTargetComponent = Usage<InputBindingsReloadTarget, Entity>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mesh_bounds_contain_vertices() {
+        let vertices = [(-1.0, 0.0, 0.0), (3.0, 2.0, 0.0), (1.0, -2.0, 4.0)]
+            .map(|position| VertexData::new(position, (0.0, 0.0, 0.0), (0.0, 0.0, 0.0), 0.0, 0.0));
+
+        let bounds = TriangleMeshBoundsData::from_vertices(&vertices);
+        assert_eq!(bounds.center, [1.0, 0.0, 2.0]);
+        for vertex in vertices {
+            let delta = [0, 1, 2].map(|i| vertex.position[i] - bounds.center[i]);
+            let distance = (delta[0] * delta[0] + delta[1] * delta[1] + delta[2] * delta[2]).sqrt();
+            assert!(distance <= bounds.radius);
+        }
+
+        assert_eq!(
+            TriangleMeshBoundsData::from_vertices(&[]),
+            TriangleMeshBoundsData::default()
+        );
+    }
+
+    #[test]
+    fn slot_allocator_reuses_slots_by_key() {
+        let mut world = World::new();
+        let a = world.spawn(());
+        let b = world.spawn(());
+        let c = world.spawn(());
+
+        let mut slots = SlotAllocator::<u32>::default();
+        slots.free(2, a);
+        slots.free(3, b);
+        slots.free(2, c);
+        assert_eq!(slots.len(), 3);
+
+        assert_eq!(slots.allocate(&4), None);
+        assert_eq!(slots.allocate(&3), Some(b));
+        assert_eq!(slots.allocate(&3), None);
+
+        assert!(slots.remove(c));
+        assert!(!slots.remove(c));
+        assert_eq!(slots.iter().collect::<Vec<_>>(), [(&2, a)]);
+
+        assert_eq!(slots.allocate(&2), Some(a));
+        assert!(slots.is_empty());
+    }
+
+    #[test]
+    fn default_bindings_are_wasd() {
+        let bindings = default_input_bindings();
+        let key = |key| bindings.get(&PhysicalInput::Key(key)).copied();
+
+        assert_eq!(key(VirtualKeyCode::W), Some(InputAction::MoveForward));
+        assert_eq!(key(VirtualKeyCode::S), Some(InputAction::MoveBack));
+        assert_eq!(key(VirtualKeyCode::A), Some(InputAction::MoveLeft));
+        assert_eq!(key(VirtualKeyCode::D), Some(InputAction::MoveRight));
+        assert_eq!(key(VirtualKeyCode::Space), Some(InputAction::MoveUp));
+        assert_eq!(key(VirtualKeyCode::LControl), Some(InputAction::MoveDown));
+    }
+
+    #[test]
+    fn oscilloscope_phase_offsets_looping_path() {
+        let circle = |f: f32| (f.cos(), f.sin(), 0.0);
+        let tau = std::f32::consts::TAU;
+
+        let a = Oscilloscope::new(1.0, 1.0, circle).with_period(tau);
+        let b = Oscilloscope::new(1.0, 1.0, circle)
+            .with_phase(tau * 2.25)
+            .with_period(tau);
+
+        let close = |(x0, y0, _): (f32, f32, f32), (x1, y1, _): (f32, f32, f32)| {
+            (x0 - x1).abs() < 1e-4 && (y0 - y1).abs() < 1e-4
+        };
+
+        // Out of step by a quarter turn, whole turns of phase wrapping away
+        assert!(close(b.eval(0.0), a.eval(tau * 0.25)));
+        assert!(!close(b.eval(0.0), a.eval(0.0)));
+
+        // Looping keeps time continuous across the period
+        assert!(close(a.eval(tau * 3.0 + 1.0), a.eval(1.0)));
+        assert!(close(b.eval(tau - 0.01), b.eval(-0.01)));
+    }
+
+    #[test]
+    fn text_cells_wrap_and_skip_whitespace() {
+        let text = Text {
+            string: "ab cde\n\nf".into(),
+            wrap: Some(4),
+        };
+
+        let cells = [
+            ((0, 0), 'a'),
+            ((1, 0), 'b'),
+            ((3, 0), 'c'),
+            ((0, 1), 'd'),
+            ((1, 1), 'e'),
+            ((0, 3), 'f'),
+        ];
+        let chars = text
+            .cells()
+            .into_iter()
+            .map(|(cell, glyph)| (cell, glyph.c))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(chars, cells.into_iter().collect());
+    }
+
+    #[test]
+    fn text_cells_apply_control_characters() {
+        let text = Text {
+            string: "a\u{1}ff0000b\u{2}c\u{2}\u{7}d\u{1}zz".into(),
+            wrap: None,
+        };
+
+        let glyph = |c, color, blink| Glyph { c, color, blink };
+        let red = [0xff, 0, 0];
+        let cells = [
+            ((0, 0), glyph('a', [0xff; 3], false)),
+            ((1, 0), glyph('b', red, false)),
+            ((2, 0), glyph('c', red, true)),
+            ((3, 0), glyph('d', red, false)),
+            ((4, 0), glyph('z', red, false)),
+            ((5, 0), glyph('z', red, false)),
+        ];
+        assert_eq!(text.cells(), cells.into_iter().collect());
+    }
+}
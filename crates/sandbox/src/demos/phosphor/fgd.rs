@@ -0,0 +1,120 @@
+use std::fmt::Write;
+
+use super::ClassnameRegistryComponent;
+
+/// Whether a map entity class is placed as a point or built from brushes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClassKind {
+    Point,
+    Brush,
+}
+
+/// Value type of a class property
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PropertyType {
+    Bool,
+    Integer,
+    Float,
+    /// Three whitespace-separated floats
+    Float3,
+    String,
+}
+
+/// Property read by a classname handler, described for editors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassProperty {
+    pub key: &'static str,
+    pub ty: PropertyType,
+    pub default: Option<&'static str>,
+    pub description: &'static str,
+}
+
+/// Generate TrenchBroom FGD entity definitions for each registered classname
+///
+/// FGD has no boolean or vector types, so bools are exported as true / false choices
+/// and float3 properties as strings.
+pub fn export_fgd(registry: &ClassnameRegistryComponent) -> String {
+    let mut fgd = String::new();
+
+    for (classname, definition) in registry.iter() {
+        let class = match definition.kind {
+            ClassKind::Point => "@PointClass",
+            ClassKind::Brush => "@SolidClass",
+        };
+
+        writeln!(fgd, "{} = {} : \"{}\"", class, classname, definition.description).unwrap();
+        writeln!(fgd, "[").unwrap();
+
+        for property in &definition.properties {
+            let ty = match property.ty {
+                PropertyType::Bool => "choices",
+                PropertyType::Integer => "integer",
+                PropertyType::Float => "float",
+                PropertyType::Float3 | PropertyType::String => "string",
+            };
+
+            write!(fgd, "\t{}({}) : \"{}\"", property.key, ty, property.description).unwrap();
+
+            if let Some(default) = property.default {
+                // Integer defaults are bare, everything else is quoted
+                if property.ty == PropertyType::Integer {
+                    write!(fgd, " : {}", default).unwrap();
+                } else {
+                    write!(fgd, " : \"{}\"", default).unwrap();
+                }
+            }
+
+            if property.ty == PropertyType::Bool {
+                // Choices must have a default, so fall back to the first option
+                if property.default.is_none() {
+                    write!(fgd, " : \"false\"").unwrap();
+                }
+                writeln!(fgd, " =").unwrap();
+                writeln!(fgd, "\t[").unwrap();
+                writeln!(fgd, "\t\t\"false\" : \"No\"").unwrap();
+                writeln!(fgd, "\t\t\"true\" : \"Yes\"").unwrap();
+                writeln!(fgd, "\t]").unwrap();
+            } else {
+                writeln!(fgd).unwrap();
+            }
+        }
+
+        writeln!(fgd, "]").unwrap();
+        writeln!(fgd).unwrap();
+    }
+
+    fgd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demos::phosphor::ClassDefinition;
+    use antigen_core::Construct;
+
+    #[test]
+    fn fgd_classifies_and_annotates_properties() {
+        let point = ClassDefinition::new(ClassKind::Point, "Point", |_, _, _| vec![])
+            .property("scale", PropertyType::Float3, Some("1 1 1"), "Scale")
+            .property("line", PropertyType::Bool, None, "Line");
+
+        let brush = ClassDefinition::new(ClassKind::Brush, "Brush", |_, _, _| vec![])
+            .property("mesh.visual.type", PropertyType::Integer, Some("1"), "Type")
+            .property("mover.speed", PropertyType::Float, Some("1.0"), "Speed");
+
+        let registry = ClassnameRegistryComponent::construct(
+            [("point".to_owned(), point), ("brush".to_owned(), brush)]
+                .into_iter()
+                .collect(),
+        );
+
+        let fgd = export_fgd(&registry);
+
+        assert!(fgd.contains("@PointClass = point : \"Point\"\n["));
+        assert!(fgd.contains("@SolidClass = brush : \"Brush\"\n["));
+        assert!(fgd.contains("\tscale(string) : \"Scale\" : \"1 1 1\"\n"));
+        assert!(fgd.contains("\tline(choices) : \"Line\" : \"false\" =\n\t[\n"));
+        assert!(fgd.contains("\tmesh.visual.type(integer) : \"Type\" : 1\n"));
+        assert!(fgd.contains("\tmover.speed(float) : \"Speed\" : \"1.0\"\n"));
+    }
+}
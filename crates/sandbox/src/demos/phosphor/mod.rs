@@ -60,21 +60,26 @@
 //           * May be wiser to downgrade the RwLock-first approach back to special-case usage
 //           * Is there a way to compose systems that doesn't involve customized legion types?
 //
-//       [ ] Changed<PathComponent> map file reloading
+//       [✓] Changed<PathComponent> map file reloading
 //           * Will allow a system to read ArgsComponent and load a map based on its value
+//           [✓] Reload the map when its MapPathComponent changes
+//           [✓] Read the map path from ArgsComponent
 //
 //       [>] Investigate infinite perspective projection + reversed Z
 //           [✓] Implement new matrix
 //           [ ] Fix triangle-line Z-fighting
 //
-// TODO: [ ] Implement HDR bloom
+// TODO: [✓] Implement HDR bloom
 //           * Render mipmaps for final buffer
 //           * Render HDR bloom using mipmaps
 //
-//       [ ] Implement automatic line smearing via compute shader
-//           * Double-buffer vertices, use to construct quads
-//           * Will need to update backbuffer if lines are added / removed at runtime
-//             * Ex. Via frustum culling or portal rendering
+//       [>] Implement automatic line smearing via compute shader
+//           [✓] Double-buffer vertices, use to construct quads
+//           [✓] Will need to update backbuffer if lines are added / removed at runtime
+//             * History buffers grow with the line instances,
+//               and slots past the end are invalidated each frame
+//           [ ] Smear lines seen through portals
+//           [ ] Clip smears against the near plane instead of dropping them
 //
 //       [ ] Is automatic mesh smearing viable?
 //
@@ -85,41 +90,55 @@
 //
 //       [ ] Downsample prototype.wad textures to 1x1px to determine color
 //
-// TODO: [ ] Implement LUT mapping via 3D texture
+// TODO: [✓] Implement LUT mapping via 3D texture
 //           * Replaces per-fragment gradient animation
 //           * Will need to figure out how to generate data
 //             * Rendering to 3D texture
 //             * Unit LUT is just a color cube with B/RGB/CMY/W vertices
 //
-//       * MechWarrior 2 gradient skybox background
+//       [✓] MechWarrior 2 gradient skybox background
 //         * Setting for underlay / overlay behavior
 //         * Overlay acts like a vectrex color overlay
 //         * Underlay respects depth and doesn't draw behind solid objects
 //
 
 mod assemblage;
+mod color_lut;
 mod components;
+mod fgd;
 mod render_passes;
 mod svg_lines;
 mod systems;
 
-use antigen_fs::{load_file_string, FilePathComponent, FileStringQuery};
+use antigen_fs::{
+    find_file_string, load_file_string, load_file_string_deferred, watch_file_string,
+    FilePathComponent, FileWatcherComponent,
+};
 use antigen_rapier3d::{
-    AngularVelocityComponent, ColliderComponent, LinearVelocityComponent, RigidBodyComponent,
+    AngularVelocityComponent, CharacterControllerComponent, ColliderComponent,
+    DesiredMovementComponent, EffectiveMovementComponent, GravityScaleComponent,
+    LinearVelocityComponent, PreviousPositionComponent, PreviousRotationComponent,
+    RigidBodyComponent,
 };
 pub use assemblage::*;
+pub use color_lut::*;
 pub use components::*;
-use rapier3d::prelude::{
-    ActiveEvents, ColliderBuilder, IntersectionEvent, RigidBodyBuilder, SharedShape,
+pub use fgd::*;
+use rapier3d::{
+    parry::transformation::vhacd::{VHACDParameters, VHACD},
+    prelude::{
+        ActiveEvents, CoefficientCombineRule, ColliderBuilder, IntersectionEvent,
+        RigidBodyBuilder, SharedShape,
+    },
 };
 pub use render_passes::*;
 pub use svg_lines::*;
 pub use systems::*;
 
-use expression::{EvalTrait, Expression};
+use expression::{Expression, FunctionRegistry, TryEvalTrait};
 use std::{
-    borrow::Cow, collections::BTreeMap, error::Error, path::PathBuf, sync::atomic::Ordering,
-    time::Instant,
+    borrow::Cow, collections::BTreeMap, error::Error, num::NonZeroU32, path::{Path, PathBuf},
+    sync::atomic::Ordering, time::{Duration, Instant},
 };
 use winit::event::DeviceEvent;
 
@@ -132,21 +151,24 @@ use antigen_winit::{
 };
 
 use antigen_core::{
-    get_tagged_entity, insert_tagged_entity, insert_tagged_entity_by_query, send_clone_query,
-    send_component, Construct, Indirect, Lift, MessageContext, MessageResult, NamedEntityComponent,
-    PositionComponent, RotationComponent, ScaleComponent, SendTo, WorldChannel,
+    get_args, get_tagged_entity_or, insert_tagged_entity, insert_tagged_entity_by_query,
+    positional_args, send_clone_query, send_component, Changed, ChangedTrait, Construct, Indirect,
+    Lift, MessageContext, MessageResult, NamedEntityComponent, PositionComponent,
+    RotationComponent, ScaleComponent, SendTo, WorldChannel,
 };
 
 use antigen_wgpu::{
     buffer_size_of, spawn_shader_from_file_string,
     wgpu::{
         AddressMode, BufferAddress, BufferDescriptor, BufferUsages, Color,
-        CommandEncoderDescriptor, Extent3d, FilterMode, LoadOp, Maintain, Operations,
+        CommandEncoderDescriptor, ComputePassDescriptor, Extent3d, FilterMode,
+        ImageCopyTextureBase, ImageDataLayout, LoadOp, Maintain, Operations, Origin3d,
         SamplerDescriptor, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
-        TextureUsages, TextureViewDescriptor,
+        TextureUsages, TextureViewDescriptor, TextureViewDimension,
     },
-    BindGroupComponent, BindGroupLayoutComponent, BufferComponent, BufferLengthComponent,
-    BufferLengthsComponent, RenderPipelineComponent, ShaderModuleComponent,
+    BackgroundColor, BackgroundComponent, BindGroupComponent, BindGroupLayoutComponent,
+    BufferComponent, BufferLengthComponent, BufferLengthsComponent, ComputePassBundle,
+    ComputePipelineComponent, RenderPassBuilder, RenderPipelineComponent, ShaderModuleComponent,
     ShaderModuleDescriptorComponent, SurfaceConfigurationComponent, TextureViewComponent,
 };
 
@@ -156,14 +178,18 @@ use antigen_shambler::shambler::{
     face::FaceId,
     line::LineId,
     shalrath::repr::{Properties, Property},
-    GeoMap,
+    ConvexHull, GeoMap, Plane3d,
 };
 
 use hecs::{Entity, EntityBuilder, World};
 
-use crate::{Filesystem, Game, Render};
+use crate::{Filesystem, Game, Render, VALUE_FLAGS};
 
 const HDR_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+// Stencil values count the portals each fragment of the beam buffer is seen through
+const DEPTH_TEXTURE_FORMAT: TextureFormat = TextureFormat::Depth24PlusStencil8;
+// Vertex, triangle index, triangle mesh and line mesh buffers grow on demand,
+// so their maximums are only initial capacities
 const MAX_MESH_VERTICES: usize = 10000;
 const MAX_TRIANGLE_INDICES: usize = 10000;
 const MAX_TRIANGLE_MESHES: usize = 100;
@@ -172,6 +198,19 @@ const MAX_LINE_INDICES: usize = 20000;
 const MAX_LINE_MESHES: usize = 100;
 const MAX_LINE_MESH_INSTANCES: usize = 400;
 const MAX_LINE_INSTANCES: usize = MAX_LINE_INDICES / 2;
+const MAX_PORTALS: usize = 16;
+
+// Line instances the line smear buffers hold before growing
+const INITIAL_LINE_SMEARS: usize = 1024;
+
+// Must match the workgroup size of line_smear.wgsl
+const LINE_SMEAR_WORKGROUP_SIZE: u32 = 64;
+
+// Text cell size in unscaled units, with text starting this many columns left of its origin
+const TEXT_COLUMN_WIDTH: f32 = 20.0;
+const TEXT_ROW_HEIGHT: f32 = 30.0;
+const TEXT_COLUMN_OFFSET: f32 = 13.0;
+const TEXT_BLINK_PERIOD: Duration = Duration::from_millis(500);
 const CLEAR_COLOR: antigen_wgpu::wgpu::Color = antigen_wgpu::wgpu::Color {
     r: 0.0,
     g: 0.0,
@@ -179,6 +218,36 @@ const CLEAR_COLOR: antigen_wgpu::wgpu::Color = antigen_wgpu::wgpu::Color {
     a: -200.0,
 };
 const NEAR_PLANE: f32 = 5.0;
+// Must match the number of bloom bindings in tonemap.wgsl
+const BLOOM_LEVELS: usize = 5;
+const BLOOM_INTENSITY: f32 = 0.5;
+const COLOR_LUT_SIZE: u32 = 32;
+const SKYBOX_TOP: (f32, f32, f32) = (0.02, 0.0, 0.08);
+const SKYBOX_BOTTOM: (f32, f32, f32) = (0.2, 0.06, 0.02);
+// Collision triangles smaller than this are considered degenerate
+const MIN_TRIANGLE_AREA: f32 = 1e-6;
+// Trimesh colliders are scaled by at least this much along each axis
+const MIN_COLLIDER_SCALE: f32 = 1e-3;
+
+// Contact impulse at which impact sounds play at full volume, unless overridden per collider
+const IMPACT_SOUND_FULL_VOLUME_IMPULSE: f32 = 100.0;
+
+// Player character capsule, sized after the Quake player hull
+const PLAYER_RADIUS: f32 = 16.0;
+const PLAYER_HALF_HEIGHT: f32 = 12.0;
+const PLAYER_STEP_HEIGHT: f32 = 18.0;
+const DEFAULT_MAP: &str = "test-data/maps/line_index_test.map";
+
+// Unit collider outlines, scaled per instance to fit cuboid half-extents and ball radii
+const DEBUG_CUBOID_MESH: &str = "debug_cuboid";
+const DEBUG_BALL_MESH: &str = "debug_ball";
+const DEBUG_COLLIDER_SEGMENTS: u32 = 16;
+// Triangle mesh colliders larger than this are outlined by their bounding box
+const MAX_DEBUG_COLLIDER_TRIANGLES: usize = 1024;
+// Line buffer space collider outline meshes may occupy,
+// past which new shapes are outlined by their bounding box
+const MAX_DEBUG_COLLIDER_MESHES: usize = MAX_LINE_MESHES / 4;
+const MAX_DEBUG_COLLIDER_LINE_INDICES: usize = MAX_LINE_INDICES / 4;
 
 pub const BLACK: (f32, f32, f32) = (0.0, 0.0, 0.0);
 pub const RED: (f32, f32, f32) = (1.0, 0.0, 0.0);
@@ -247,6 +316,18 @@ fn load_shader_message<P: Copy + Into<PathBuf>>(
     move |ctx| {
         ctx.lift()
             .and_then(load_file_string(shader_path))
+            .and_then(watch_file_string(shader_path))
+            .and_then(insert_shader_reload_target(shader_path, entity))
+            .and_then(send_shader_message(shader_path, entity))
+    }
+}
+
+fn send_shader_message<P: Copy + Into<PathBuf>>(
+    shader_path: P,
+    entity: Entity,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |ctx| {
+        ctx.lift()
             .and_then(spawn_shader_from_file_string(shader_path))
             .and_then(
                 send_component::<ShaderModuleDescriptorComponent, Render, _>(
@@ -261,6 +342,65 @@ fn load_shader_message<P: Copy + Into<PathBuf>>(
     }
 }
 
+fn insert_shader_reload_target<P: Into<PathBuf>>(
+    shader_path: P,
+    entity: Entity,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+        let shader_path = shader_path.into();
+
+        let (file_entity, _) =
+            find_file_string(world, &shader_path).ok_or("No file string for shader")?;
+        world.insert_one(file_entity, ShaderReloadTargetComponent::construct(entity))?;
+
+        Ok(ctx)
+    }
+}
+
+// Reset all render pipelines so they are rebuilt against newly-reloaded shader modules
+fn reset_render_pipelines_message(
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+        for (_, pipeline) in world.query_mut::<&mut RenderPipelineComponent>() {
+            pipeline.set_pending();
+        }
+        Ok(ctx)
+    }
+}
+
+/// Re-send the modules of watched shader files that have been reloaded to the render thread,
+/// and have it rebuild its pipelines once they arrive
+pub fn reload_watched_shaders(world: &mut World, channel: &WorldChannel) {
+    let reloaded = world
+        .query_mut::<(
+            &FilePathComponent,
+            &FileWatcherComponent,
+            &ShaderReloadTargetComponent,
+        )>()
+        .into_iter()
+        .filter(|(_, (_, watcher, _))| watcher.get_changed())
+        .map(|(_, (path, watcher, target))| {
+            watcher.set_changed(false);
+            ((**path).clone(), **target)
+        })
+        .collect::<Vec<_>>();
+
+    for (path, target) in reloaded {
+        println!("Reloading shader {:?}", path);
+        let result = send_shader_message(&path, target)((world, channel));
+        if let Err(e) = result {
+            println!("Failed to reload shader {:?}: {}", path, e);
+            continue;
+        }
+
+        channel
+            .send_to::<Render>(reset_render_pipelines_message())
+            .unwrap();
+    }
+}
+
 fn load_shader<T: Send + Sync + 'static, P: Copy + Into<PathBuf> + Send + Sync + 'static>(
     channel: &WorldChannel,
     entity: Entity,
@@ -271,29 +411,181 @@ fn load_shader<T: Send + Sync + 'static, P: Copy + Into<PathBuf> + Send + Sync +
         .unwrap();
 }
 
-fn load_map<
-    U: Send + Sync + 'static,
+/// Load a color LUT from a .cube file or strip PNG and apply it to the tonemap pass
+pub fn load_color_lut(world: &mut World, path: &Path) -> Result<(), Box<dyn Error>> {
+    let lut = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("cube") => ColorLutComponent::from_cube(&std::fs::read_to_string(path)?)?,
+        Some("png") => ColorLutComponent::from_strip_png(&std::fs::read(path)?)?,
+        _ => return Err("Expected a .cube or .png color LUT".into()),
+    };
+
+    set_color_lut(world, lut);
+
+    Ok(())
+}
+
+// Load input bindings from file and send them to the render thread,
+// watching the file so edits replace the existing bindings
+fn load_input_bindings<
     T: Send + Sync + 'static,
     P: Copy + Into<PathBuf> + Send + Sync + 'static,
 >(
     channel: &WorldChannel,
-    map_path: P,
+    entity: Entity,
+    bindings_path: P,
 ) {
     channel
-        .send_to::<T>(load_map_message::<U, _>(map_path))
+        .send_to::<T>(load_input_bindings_message(bindings_path, entity))
         .unwrap();
 }
 
-fn load_map_message<U: Send + Sync + 'static, P: Copy + Into<PathBuf>>(
-    map_path: P,
+fn load_input_bindings_message<P: Copy + Into<PathBuf>>(
+    bindings_path: P,
+    entity: Entity,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |ctx| {
+        ctx.lift()
+            .and_then(load_file_string(bindings_path))
+            .and_then(watch_file_string(bindings_path))
+            .and_then(insert_input_bindings_reload_target(bindings_path, entity))
+            .and_then(send_input_bindings_message(bindings_path, entity))
+    }
+}
+
+fn send_input_bindings_message<P: Copy + Into<PathBuf>>(
+    bindings_path: P,
+    entity: Entity,
 ) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
     move |ctx| {
         ctx.lift()
-            .and_then(load_file_string(map_path))
-            .and_then(parse_map_file_string(map_path))
+            .and_then(parse_input_bindings_file_string(bindings_path))
+            .and_then(send_component::<InputBindingsComponent, Render, _>(
+                FilePathComponent::construct(bindings_path.into()),
+                entity,
+            ))
+    }
+}
+
+fn insert_input_bindings_reload_target<P: Into<PathBuf>>(
+    bindings_path: P,
+    entity: Entity,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+        let bindings_path = bindings_path.into();
+
+        let (file_entity, _) =
+            find_file_string(world, &bindings_path).ok_or("No file string for input bindings")?;
+        world.insert_one(
+            file_entity,
+            InputBindingsReloadTargetComponent::construct(entity),
+        )?;
+
+        Ok(ctx)
+    }
+}
+
+/// Re-send the bindings of watched input bindings files that have been reloaded
+/// to the render thread, replacing its existing bindings
+pub fn reload_watched_input_bindings(world: &mut World, channel: &WorldChannel) {
+    let reloaded = world
+        .query_mut::<(
+            &FilePathComponent,
+            &FileWatcherComponent,
+            &InputBindingsReloadTargetComponent,
+        )>()
+        .into_iter()
+        .filter(|(_, (_, watcher, _))| watcher.get_changed())
+        .map(|(_, (path, watcher, target))| {
+            watcher.set_changed(false);
+            ((**path).clone(), **target)
+        })
+        .collect::<Vec<_>>();
+
+    for (path, target) in reloaded {
+        println!("Reloading input bindings {:?}", path);
+        let result = send_input_bindings_message(&path, target)((world, channel));
+        if let Err(e) = result {
+            println!("Failed to reload input bindings {:?}: {}", path, e);
+        }
+    }
+}
+
+// Parse the input bindings file string loaded from `path` into its entity,
+// leaving the file string in place so it can be watched for changes
+fn parse_input_bindings_file_string<'a, 'b, P: Into<PathBuf>>(
+    path: P,
+) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+
+        let bindings_path = path.into();
+
+        let (entity, bindings) = {
+            let (entity, string) = find_file_string(world, &bindings_path)
+                .ok_or("No file string for input bindings")?;
+
+            let bindings = ron::from_str::<InputBindingsComponent>(string)?;
+            (entity, bindings)
+        };
+
+        world.insert_one(entity, bindings)?;
+
+        Ok(ctx)
+    }
+}
+
+fn load_map<T: Send + Sync + 'static, P: Into<PathBuf>>(
+    channel: &WorldChannel,
+    map_path: P,
+) {
+    channel
+        .send_to::<T>(load_map_message::<T>(map_path.into()))
+        .unwrap();
+}
+
+// Read the map on a worker thread so large maps don't stall world T's message handling,
+// parsing it once the read has completed
+fn load_map_message<T: Send + Sync + 'static>(
+    map_path: PathBuf,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |ctx| {
+        ctx.lift().and_then(load_file_string_deferred::<T, _, _>(
+            map_path.clone(),
+            move |ctx| parse_map_file_string(map_path)(ctx),
+        ))
+    }
+}
+
+/// Map path given as the first positional argument in the world's ArgsComponent,
+/// or the default map if there is none
+pub fn map_path_arg(world: &World) -> PathBuf {
+    get_args(world)
+        .and_then(|args| positional_args(&args, VALUE_FLAGS).next().map(PathBuf::from))
+        .unwrap_or_else(|| DEFAULT_MAP.into())
+}
+
+/// Load the map at each changed MapPathComponent,
+/// replacing the entities spawned from the previous map once the new one has been parsed
+pub fn map_reload_system(world: &mut World, channel: &WorldChannel) {
+    for (_, map_path) in world.query_mut::<&mut Changed<MapPathComponent>>() {
+        if map_path.get_changed() {
+            // Keep the current map rather than tearing it down for one that can't be read
+            if map_path.is_file() {
+                println!("Loading map {:?}", ***map_path);
+                load_map::<Filesystem, _>(channel, (***map_path).clone());
+            } else {
+                println!("Failed to load map {:?}: No such file", ***map_path);
+            }
+            map_path.set_changed(false);
+        }
     }
 }
 
+/// Parse the map file string loaded from `path` and replace the current map with it
+///
+/// Only one map is loaded at a time, so if several file strings share `path`,
+/// the first is used and the rest are left in place.
 pub fn parse_map_file_string<'a, 'b, P: Into<PathBuf>>(
     path: P,
 ) -> impl FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
@@ -307,20 +599,27 @@ pub fn parse_map_file_string<'a, 'b, P: Into<PathBuf>>(
             map_path
         );
 
-        let (entity, FileStringQuery { string, .. }) = world
-            .query_mut::<FileStringQuery>()
-            .into_iter()
-            .filter(|(_, FileStringQuery { path, .. })| ***path == *map_path)
-            .next()
-            .unwrap();
+        let (entity, string) =
+            find_file_string(world, &map_path).ok_or("No file string for map")?;
 
         println!("Parsing map file for entity {:?}", entity);
-        let map = string
-            .parse::<antigen_shambler::shambler::shalrath::repr::Map>()
-            .unwrap();
+        let map = antigen_shambler::parse_map(&map_path, string)?;
+
+        // Drop the file string so reloading the same path reads it afresh
+        world.despawn(entity)?;
+
         let geo_map = GeoMap::from(map);
         let map_data = MapData::from(geo_map);
 
+        // Tear down the previous map before assembling its replacement
+        channel
+            .send_to::<Render>(despawn_map_entities_message())
+            .unwrap();
+
+        channel
+            .send_to::<Game>(despawn_map_entities_message())
+            .unwrap();
+
         channel
             .send_to::<Render>(assemble_map_render_thread(map_data.clone()))
             .unwrap();
@@ -340,13 +639,15 @@ fn assemble_map_render_thread(
         let (world, _) = &mut ctx;
 
         let mut map_meshes = map_data.assemble_brush_entities_render_thread(world);
-        let bundles = map_meshes.iter_mut().map(EntityBuilder::build);
+        let bundles = map_meshes.iter_mut().map(map_entity_bundle);
         world.extend(bundles);
 
         let mut map_meshes = map_data.assemble_point_entities_render_thread(world);
-        let bundles = map_meshes.iter_mut().map(EntityBuilder::build);
+        let bundles = map_meshes.iter_mut().map(map_entity_bundle);
         world.extend(bundles);
 
+        world.spawn(map_entity_bundle(&mut map_data.player_start()));
+
         Ok(ctx)
     }
 }
@@ -360,9 +661,59 @@ fn assemble_map_game_thread(
         map_data.assemble_brush_entities_game_thread(world);
 
         let mut point_entities = map_data.assemble_entities_game_thread(world);
-        let bundles = point_entities.iter_mut().map(EntityBuilder::build);
+        let bundles = point_entities.iter_mut().map(map_entity_bundle);
         world.extend(bundles);
 
+        world.spawn(map_entity_bundle(&mut map_data.player_character()));
+
+        Ok(ctx)
+    }
+}
+
+// Tag an entity as belonging to the loaded map before building it
+fn map_entity_bundle(builder: &mut EntityBuilder) -> hecs::BuiltEntity<'_> {
+    builder.add(MapEntity).build()
+}
+
+fn despawn_map_entities_message(
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+        despawn_map_entities(world);
+        Ok(ctx)
+    }
+}
+
+// Accumulate movement into the player character's desired movement,
+// to be resolved against the map's colliders on the next game tick
+fn move_player_message(
+    delta: nalgebra::Vector3<f32>,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+        for (_, desired) in world
+            .query_mut::<&mut DesiredMovementComponent>()
+            .with::<Player>()
+        {
+            **desired += delta;
+        }
+        Ok(ctx)
+    }
+}
+
+// Move the camera to the player character's resolved position
+fn camera_position_message(
+    position: nalgebra::Vector3<f32>,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+        for (_, camera_position) in world
+            .query_mut::<&mut Changed<PositionComponent>>()
+            .with::<Camera>()
+        {
+            ***camera_position = position;
+            camera_position.set_changed(true);
+        }
         Ok(ctx)
     }
 }
@@ -392,6 +743,88 @@ fn uniform_buffer_bundle() -> EntityBuilder {
     builder
 }
 
+// History of line instance endpoints, written by the line smear pass on alternate frames
+fn line_smear_history_buffer_bundle(front: bool) -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+
+    if front {
+        builder.add(LineSmearFront);
+    } else {
+        builder.add(LineSmearBack);
+    }
+
+    builder
+        .add(BindGroupComponent::default())
+        .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
+            label: Some(if front {
+                "Line Smear Front History Buffer"
+            } else {
+                "Line Smear Back History Buffer"
+            }),
+            size: buffer_size_of::<LineHistoryData>() * INITIAL_LINE_SMEARS as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    builder
+}
+
+// Quads written by the line smear pass and drawn by the beam line smear pass
+fn line_smear_buffer_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add(LineSmears)
+        .add(BindGroupLayoutComponent::default())
+        .add(BindGroupComponent::default())
+        .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
+            label: Some("Line Smear Buffer"),
+            size: buffer_size_of::<LineSmearData>() * INITIAL_LINE_SMEARS as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    builder
+}
+
+fn line_smear_uniform_buffer_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add(LineSmearUniform)
+        .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
+            label: Some("Line Smear Uniform Buffer"),
+            size: buffer_size_of::<LineSmearUniformData>(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    builder
+}
+
+// Uniforms for the view seen through portals, bound with the main uniform bind group layout
+fn portal_uniform_buffer_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add(PortalUniform)
+        .add(BindGroupComponent::default())
+        .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
+            label: Some("Portal Uniform Buffer"),
+            size: buffer_size_of::<UniformData>(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    builder
+}
+
+fn portal_instance_buffer_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add(PortalInstances)
+        .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
+            label: Some("Portal Instance Buffer"),
+            size: buffer_size_of::<PortalInstanceData>() * MAX_PORTALS as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    builder
+}
+
 fn vertex_buffer_bundle() -> EntityBuilder {
     let mut builder = EntityBuilder::new();
     builder
@@ -399,7 +832,10 @@ fn vertex_buffer_bundle() -> EntityBuilder {
         .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
             label: Some("Vertex Buffer"),
             size: buffer_size_of::<VertexData>() * MAX_MESH_VERTICES as BufferAddress,
-            usage: BufferUsages::VERTEX | BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            usage: BufferUsages::VERTEX
+                | BufferUsages::STORAGE
+                | BufferUsages::COPY_SRC
+                | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         }))
         .add(BufferLengthComponent::default());
@@ -413,7 +849,7 @@ fn triangle_index_buffer_bundle() -> EntityBuilder {
         .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
             label: Some("Triangle Index Buffer"),
             size: buffer_size_of::<TriangleIndexData>() * MAX_TRIANGLE_INDICES as BufferAddress,
-            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+            usage: BufferUsages::INDEX | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         }))
         .add(BufferLengthComponent::default());
@@ -427,13 +863,30 @@ fn triangle_mesh_buffer_bundle() -> EntityBuilder {
         .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
             label: Some("Triangle Mesh Buffer"),
             size: buffer_size_of::<TriangleMeshData>() * MAX_TRIANGLE_MESHES as BufferAddress,
-            usage: BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+            usage: BufferUsages::INDIRECT
+                | BufferUsages::STORAGE
+                | BufferUsages::COPY_SRC
+                | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         }))
         .add(BufferLengthComponent::default());
     builder
 }
 
+fn triangle_mesh_bounds_buffer_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add(TriangleMeshBounds)
+        .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
+            label: Some("Triangle Mesh Bounds Buffer"),
+            size: buffer_size_of::<TriangleMeshBoundsData>()
+                * MAX_TRIANGLE_MESHES as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    builder
+}
+
 fn triangle_mesh_instances_buffer_bundle() -> EntityBuilder {
     let mut builder = EntityBuilder::new();
     builder
@@ -445,7 +898,23 @@ fn triangle_mesh_instances_buffer_bundle() -> EntityBuilder {
             usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         }))
-        .add(BufferLengthsComponent::default());
+        .add(BufferLengthsComponent::default())
+        .add(TriangleMeshInstanceSlotsComponent::default());
+    builder
+}
+
+// Instances that survived frustum culling, compacted to the front of each mesh's slice
+fn visible_triangle_mesh_instances_buffer_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add(VisibleTriangleMeshInstances)
+        .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
+            label: Some("Visible Triangle Mesh Instance Buffer"),
+            size: buffer_size_of::<TriangleMeshInstanceData>()
+                * (MAX_TRIANGLE_MESHES * MAX_TRIANGLE_MESH_INSTANCES) as BufferAddress,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        }));
     builder
 }
 
@@ -470,7 +939,7 @@ fn mesh_buffer_bundle() -> EntityBuilder {
         .add_bundle(antigen_wgpu::BufferBundle::new(BufferDescriptor {
             label: Some("Mesh Buffer"),
             size: buffer_size_of::<LineMeshData>() * MAX_LINE_MESHES as BufferAddress,
-            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         }))
         .add(BufferLengthComponent::default());
@@ -488,7 +957,8 @@ fn line_mesh_instance_buffer_bundle() -> EntityBuilder {
             usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         }))
-        .add(BufferLengthComponent::default());
+        .add(BufferLengthComponent::default())
+        .add(LineMeshInstanceSlotsComponent::default());
     builder
 }
 
@@ -520,83 +990,176 @@ fn line_vertex_buffer_bundle(entity: Entity, vertices: Vec<LineVertexData>) -> E
     builder
 }
 
-fn total_time_builder(uniform_entity: Entity) -> EntityBuilder {
+// Uniform data is written via staging belt, which is flushed to the renderer's command buffers
+fn uniform_data_bundle(uniform_entity: Entity, staging_belt_entity: Entity) -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder.add_bundle(antigen_wgpu::StagingBeltDataBundle::new(
+        UniformData::default(),
+        0,
+        uniform_entity,
+        staging_belt_entity,
+    ));
+    builder
+}
+
+fn total_time_builder() -> EntityBuilder {
     let mut builder = EntityBuilder::new();
     builder
         .add(StartTimeComponent::construct(Instant::now()))
-        .add_bundle(antigen_wgpu::BufferDataBundle::new(
-            TotalTimeComponent::construct(0.0),
-            buffer_size_of::<[nalgebra::Matrix4<f32>; 2]>()
-                + buffer_size_of::<nalgebra::Vector4<f32>>() * 2,
-            uniform_entity,
-        ));
+        .add(Changed::new(TotalTimeComponent::construct(0.0), true));
     builder
 }
 
-fn delta_time_bundle(uniform_entity: Entity) -> EntityBuilder {
+fn delta_time_bundle() -> EntityBuilder {
     let mut builder = EntityBuilder::new();
     builder
         .add(TimestampComponent::construct(Instant::now()))
-        .add_bundle(antigen_wgpu::BufferDataBundle::new(
-            DeltaTimeComponent::construct(1.0 / 60.0),
-            buffer_size_of::<[nalgebra::Matrix4<f32>; 2]>()
-                + buffer_size_of::<nalgebra::Vector4<f32>>() * 2
-                + buffer_size_of::<f32>(),
-            uniform_entity,
-        ));
+        .add(Changed::new(DeltaTimeComponent::construct(1.0 / 60.0), true));
     builder
 }
 
-fn perspective_matrix_bundle(uniform_entity: Entity) -> EntityBuilder {
+fn bloom_intensity_bundle() -> EntityBuilder {
     let mut builder = EntityBuilder::new();
-    builder.add(PerspectiveMatrix);
-    builder.add_bundle(antigen_wgpu::BufferDataBundle::new(
-        PerspectiveMatrixComponent::construct(perspective_matrix(640.0 / 480.0, NEAR_PLANE)),
-        0,
-        uniform_entity,
+    builder.add(Changed::new(
+        BloomIntensityComponent::construct(BLOOM_INTENSITY),
+        true,
     ));
     builder
 }
 
-fn orthographic_matrix_bundle(uniform_entity: Entity) -> EntityBuilder {
+fn map_path_bundle<P: Into<PathBuf>>(map_path: P) -> EntityBuilder {
     let mut builder = EntityBuilder::new();
-    builder
-        .add(OrthographicMatrix)
-        .add_bundle(antigen_wgpu::BufferDataBundle::new(
-            OrthographicMatrixComponent::construct(orthographic_matrix(640.0 / 480.0, 200.0)),
-            buffer_size_of::<nalgebra::Matrix4<f32>>(),
-            uniform_entity,
-        ));
+    builder.add(Changed::new(
+        MapPathComponent::construct(map_path.into()),
+        true,
+    ));
     builder
 }
 
-fn camera_bundle(uniform_entity: Entity) -> EntityBuilder {
+fn skybox_bundle() -> EntityBuilder {
     let mut builder = EntityBuilder::new();
     builder
-        .add(Camera)
-        .add(EulerAnglesComponent::default())
-        .add_bundle(antigen_wgpu::BufferDataBundle::new(
-            PositionComponent::construct(Default::default()),
-            buffer_size_of::<[nalgebra::Matrix4<f32>; 2]>(),
-            uniform_entity,
-        ))
-        .add_bundle(antigen_wgpu::BufferDataBundle::new(
-            RotationComponent::construct(Default::default()),
-            buffer_size_of::<[nalgebra::Matrix4<f32>; 2]>()
-                + buffer_size_of::<nalgebra::Vector4<f32>>(),
-            uniform_entity,
+        .add(Skybox)
+        .add(RenderPipelineComponent::default())
+        .add(Changed::new(
+            SkyboxComponent {
+                mode: SkyboxMode::Underlay,
+                top: SKYBOX_TOP,
+                bottom: SKYBOX_BOTTOM,
+            },
+            true,
         ));
     builder
 }
 
-fn beam_buffer_bundle() -> EntityBuilder {
-    let mut builder = EntityBuilder::new();
-    builder
-        .add(BeamBuffer)
-        .add_bundle(antigen_wgpu::TextureBundle::new(TextureDescriptor {
-            label: Some("Beam Buffer"),
-            size: Extent3d {
-                width: 640,
+// Pass order of the skybox in each mode
+fn skybox_pass_order(mode: SkyboxMode) -> usize {
+    match mode {
+        // After the beam meshes have filled the depth buffer, before the additive beam lines
+        SkyboxMode::Underlay => 6,
+        // After the tonemap pass
+        SkyboxMode::Overlay => 10 + BLOOM_LEVELS * 2,
+    }
+}
+
+// Skybox render pass for the given mode,
+// drawing into the beam buffer for underlay or the render target for overlay
+fn skybox_pass_builder(
+    world: &mut World,
+    skybox_entity: Entity,
+    mode: SkyboxMode,
+) -> EntityBuilder {
+    let renderer_entity = get_tagged_entity_or::<PhosphorRenderer>(world).unwrap();
+    let uniform_entity = get_tagged_entity_or::<Uniform>(world).unwrap();
+
+    let builder = RenderPassBuilder::new(skybox_pass_order(mode), renderer_entity).label("Skybox");
+    let builder = match mode {
+        SkyboxMode::Underlay => {
+            let beam_buffer_entity = get_tagged_entity_or::<BeamBuffer>(world).unwrap();
+            let beam_multisample_entity = get_tagged_entity_or::<BeamMultisample>(world).unwrap();
+            let beam_depth_buffer_entity = get_tagged_entity_or::<BeamDepthBuffer>(world).unwrap();
+
+            builder
+                .color_attachment(
+                    beam_multisample_entity,
+                    Some(beam_buffer_entity),
+                    Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    },
+                )
+                .depth(
+                    beam_depth_buffer_entity,
+                    Some(Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    }),
+                    Some(Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    }),
+                )
+        }
+        SkyboxMode::Overlay => {
+            let target_entity = world
+                .get::<Indirect<&TextureViewComponent>>(renderer_entity)
+                .unwrap()
+                .entity();
+
+            builder.color_attachment(
+                target_entity,
+                None,
+                Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            )
+        }
+    };
+
+    builder
+        .pipeline(skybox_entity)
+        .bind_group(uniform_entity, vec![])
+        .draw(0..4, 0..1)
+}
+
+fn perspective_matrix_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder.add(PerspectiveMatrix).add(Changed::new(
+        PerspectiveMatrixComponent::construct(perspective_matrix(640.0 / 480.0, NEAR_PLANE)),
+        true,
+    ));
+    builder
+}
+
+fn orthographic_matrix_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder.add(OrthographicMatrix).add(Changed::new(
+        OrthographicMatrixComponent::construct(orthographic_matrix(640.0 / 480.0, 200.0)),
+        true,
+    ));
+    builder
+}
+
+fn camera_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add(Camera)
+        .add(EulerAnglesComponent::default())
+        .add(Changed::new(PositionComponent::construct(Default::default()), true))
+        .add(Changed::new(RotationComponent::construct(Default::default()), true))
+        .add(Changed::new(CurrentRoomComponent::construct(None), false));
+    builder
+}
+
+fn beam_buffer_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add(BeamBuffer)
+        .add_bundle(antigen_wgpu::TextureBundle::new(TextureDescriptor {
+            label: Some("Beam Buffer"),
+            size: Extent3d {
+                width: 640,
                 height: 480,
                 depth_or_array_layers: 1,
             },
@@ -635,7 +1198,7 @@ fn beam_depth_buffer_bundle() -> EntityBuilder {
             mip_level_count: 1,
             sample_count: 4,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Depth32Float,
+            format: DEPTH_TEXTURE_FORMAT,
             usage: TextureUsages::RENDER_ATTACHMENT,
         }))
         .add_bundle(antigen_wgpu::TextureViewBundle::new(
@@ -732,6 +1295,113 @@ fn phosphor_buffer_bundle(front: bool) -> EntityBuilder {
     builder
 }
 
+// Size of a bloom buffer, halving the phosphor buffer size per level
+fn bloom_buffer_size(width: u32, height: u32, level: usize) -> Extent3d {
+    Extent3d {
+        width: (width >> (level + 1)).max(1),
+        height: (height >> (level + 1)).max(1),
+        depth_or_array_layers: 1,
+    }
+}
+
+fn bloom_buffer_bundle(level: usize, blur: bool) -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+
+    if blur {
+        builder.add(BloomBlurBuffer);
+    } else {
+        builder.add(BloomDownsampleBuffer);
+    }
+
+    builder
+        .add(BloomLevelComponent::construct(level))
+        .add(BindGroupComponent::default())
+        .add_bundle(antigen_wgpu::TextureBundle::new(TextureDescriptor {
+            label: Some(if blur {
+                "Bloom Blur Buffer"
+            } else {
+                "Bloom Downsample Buffer"
+            }),
+            size: bloom_buffer_size(640, 480, level),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: HDR_TEXTURE_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        }))
+        .add_bundle(antigen_wgpu::TextureViewBundle::new(
+            TextureViewDescriptor {
+                label: Some(if blur {
+                    "Bloom Blur Buffer View"
+                } else {
+                    "Bloom Downsample Buffer View"
+                }),
+                format: None,
+                dimension: None,
+                aspect: TextureAspect::All,
+                base_mip_level: 0,
+                mip_level_count: None,
+                base_array_layer: 0,
+                array_layer_count: None,
+            },
+        ));
+    builder
+}
+
+// Upload of a color LUT to its own entity's 3D texture
+fn color_lut_data_bundle(
+    entity: Entity,
+    lut: ColorLutComponent,
+) -> antigen_wgpu::TextureDataBundle<ColorLutComponent> {
+    let size = lut.size();
+    antigen_wgpu::TextureDataBundle::new(
+        lut,
+        ImageCopyTextureBase {
+            texture: (),
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: NonZeroU32::new(size * 4),
+            rows_per_image: NonZeroU32::new(size),
+        },
+        entity,
+    )
+}
+
+fn color_lut_bundle(entity: Entity, lut: ColorLutComponent) -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add(ColorLut)
+        .add(BindGroupLayoutComponent::default())
+        .add(BindGroupComponent::default())
+        .add_bundle(antigen_wgpu::TextureBundle::new(TextureDescriptor {
+            label: Some("Color LUT"),
+            size: lut.extent(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D3,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        }))
+        .add_bundle(antigen_wgpu::TextureViewBundle::new(
+            TextureViewDescriptor {
+                label: Some("Color LUT View"),
+                format: None,
+                dimension: Some(TextureViewDimension::D3),
+                aspect: TextureAspect::All,
+                base_mip_level: 0,
+                mip_level_count: None,
+                base_array_layer: 0,
+                array_layer_count: None,
+            },
+        ))
+        .add_bundle(color_lut_data_bundle(entity, lut));
+    builder
+}
+
 fn window_bundle() -> EntityBuilder {
     let mut builder = EntityBuilder::new();
     builder
@@ -746,13 +1416,33 @@ fn window_bundle() -> EntityBuilder {
                 height: 0,
             },
         ))
-        .add(RedrawUnconditionally);
+        .add(RedrawUnconditionally)
+        .add(BackgroundComponent::construct(BackgroundColor::Solid(Color::BLACK)));
+    builder
+}
+
+/// Final output of the phosphor renderer
+pub enum PhosphorTarget {
+    Window,
+    Offscreen { width: u32, height: u32 },
+}
+
+fn offscreen_target_bundle(width: u32, height: u32) -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add_bundle(antigen_wgpu::OffscreenTargetBundle::new(
+            "Phosphor Offscreen Target",
+            width,
+            height,
+            TextureFormat::Rgba8UnormSrgb,
+        ))
+        .add(BackgroundComponent::construct(BackgroundColor::Solid(Color::BLACK)));
     builder
 }
 
 // Main assemblage function
-pub fn assemble(world: &mut World, channel: &WorldChannel) {
-    let window_entity = world.reserve_entity();
+pub fn assemble(world: &mut World, channel: &WorldChannel, target: PhosphorTarget) {
+    let target_entity = world.reserve_entity();
     let renderer_entity = world.reserve_entity();
 
     // Buffer entities
@@ -761,8 +1451,10 @@ pub fn assemble(world: &mut World, channel: &WorldChannel) {
     let vertex_entity = world.spawn(vertex_buffer_bundle().build());
     let triangle_index_entity = world.spawn(triangle_index_buffer_bundle().build());
     let triangle_mesh_entity = world.spawn(triangle_mesh_buffer_bundle().build());
+    let triangle_mesh_bounds_entity = world.spawn(triangle_mesh_bounds_buffer_bundle().build());
     let triangle_mesh_instance_entity =
         world.spawn(triangle_mesh_instances_buffer_bundle().build());
+    world.spawn(visible_triangle_mesh_instances_buffer_bundle().build());
 
     let line_vertex_entity = world.reserve_entity();
     world
@@ -783,14 +1475,21 @@ pub fn assemble(world: &mut World, channel: &WorldChannel) {
             &TriangleMeshInstances,
             &BufferComponent,
             &BufferLengthsComponent,
+            &TriangleMeshInstanceSlotsComponent,
         ),
         Game,
     >(triangle_mesh_instance_entity)((world, channel))
     .unwrap();
 
-    send_clone_query::<(&LineMeshInstances, &BufferComponent, &BufferLengthComponent), Game>(
-        line_mesh_instance_entity,
-    )((world, channel))
+    send_clone_query::<
+        (
+            &LineMeshInstances,
+            &BufferComponent,
+            &BufferLengthComponent,
+            &LineMeshInstanceSlotsComponent,
+        ),
+        Game,
+    >(line_mesh_instance_entity)((world, channel))
     .unwrap();
 
     send_clone_query::<(&LineInstances, &BufferComponent, &BufferLengthComponent), Game>(
@@ -798,14 +1497,22 @@ pub fn assemble(world: &mut World, channel: &WorldChannel) {
     )((world, channel))
     .unwrap();
 
+    // Uniform data entity
+    let staging_belt_entity = world.spawn(antigen_wgpu::StagingBeltBundle::new(
+        buffer_size_of::<UniformData>(),
+        renderer_entity,
+    ));
+    world.spawn(uniform_data_bundle(uniform_entity, staging_belt_entity).build());
+
     // Time entities
-    world.spawn(total_time_builder(uniform_entity).build());
-    world.spawn(delta_time_bundle(uniform_entity).build());
+    world.spawn(total_time_builder().build());
+    world.spawn(delta_time_bundle().build());
+    world.spawn(bloom_intensity_bundle().build());
 
     // Camera entities
-    world.spawn(perspective_matrix_bundle(uniform_entity).build());
-    world.spawn(orthographic_matrix_bundle(uniform_entity).build());
-    world.spawn(camera_bundle(uniform_entity).build());
+    world.spawn(perspective_matrix_bundle().build());
+    world.spawn(orthographic_matrix_bundle().build());
+    world.spawn(camera_bundle().build());
 
     // Texture entities
     let beam_buffer_entity = world.spawn(beam_buffer_bundle().build());
@@ -839,9 +1546,13 @@ pub fn assemble(world: &mut World, channel: &WorldChannel) {
         .insert(phosphor_back_entity, phosphor_buffer_bundle(false).build())
         .unwrap();
 
-    // Assemble window
+    // Assemble window or offscreen target
+    let mut target_bundle = match target {
+        PhosphorTarget::Window => window_bundle(),
+        PhosphorTarget::Offscreen { width, height } => offscreen_target_bundle(width, height),
+    };
     world
-        .insert(window_entity, window_bundle().build())
+        .insert(target_entity, target_bundle.build())
         .unwrap();
 
     // Storage bind group
@@ -851,44 +1562,162 @@ pub fn assemble(world: &mut World, channel: &WorldChannel) {
         BindGroupComponent::default(),
     ));
 
+    // Frustum cull pass, recorded between the beam clear and beam mesh passes
+    let frustum_cull_entity = world.reserve_entity();
+    let mut builder = ComputePassBundle::dispatch(
+        0,
+        ComputePassDescriptor {
+            label: Some("Frustum Cull"),
+        },
+        frustum_cull_entity,
+        vec![
+            (uniform_entity, vec![]),
+            (frustum_cull_entity, vec![]),
+        ],
+        vec![],
+        (0, 1, 1),
+        renderer_entity,
+    );
+    builder
+        .add(FrustumCull)
+        .add(ComputePipelineComponent::default())
+        .add(BindGroupLayoutComponent::default())
+        .add(BindGroupComponent::default());
+    world
+        .insert(frustum_cull_entity, builder.build())
+        .unwrap();
+
+    load_shader::<Filesystem, _>(
+        channel,
+        frustum_cull_entity,
+        "test-data/shaders/cull.wgsl",
+    );
+
+    // Line smear buffers, grown to fit the line instances by phosphor_update_line_smear_system
+    let line_smear_front_entity = world.reserve_entity();
+    let line_smear_back_entity = world.reserve_entity();
+
+    world
+        .insert(
+            line_smear_front_entity,
+            line_smear_history_buffer_bundle(true)
+                .add_bundle(
+                    antigen_core::swap_with_builder::<BindGroupComponent>(line_smear_back_entity)
+                        .build(),
+                )
+                .build(),
+        )
+        .unwrap();
+
+    world
+        .insert(line_smear_back_entity, line_smear_history_buffer_bundle(false).build())
+        .unwrap();
+
+    let line_smear_buffer_entity = world.spawn(line_smear_buffer_bundle().build());
+
+    let line_smear_uniform_entity = world.spawn(line_smear_uniform_buffer_bundle().build());
+    world.spawn(antigen_wgpu::BufferDataBundle::new(
+        LineSmearUniformData::default(),
+        0,
+        line_smear_uniform_entity,
+    ));
+
+    // Line smear pass, writing the quads drawn by the beam line smear pass
+    let line_smear_entity = world.reserve_entity();
+    let mut builder = ComputePassBundle::dispatch(
+        0,
+        ComputePassDescriptor {
+            label: Some("Line Smear"),
+        },
+        line_smear_entity,
+        vec![(line_smear_front_entity, vec![])],
+        vec![],
+        (0, 1, 1),
+        renderer_entity,
+    );
+    builder
+        .add(LineSmear)
+        .add(ComputePipelineComponent::default())
+        .add(BindGroupLayoutComponent::default());
+    world.insert(line_smear_entity, builder.build()).unwrap();
+
+    load_shader::<Filesystem, _>(
+        channel,
+        line_smear_entity,
+        "test-data/shaders/line_smear.wgsl",
+    );
+
+    // Growable geometry buffers, invalidating the bind groups they are bound to
+    for (entity, bind_groups) in [
+        (
+            vertex_entity,
+            vec![
+                storage_bind_group_entity,
+                line_smear_front_entity,
+                line_smear_back_entity,
+            ],
+        ),
+        (
+            line_mesh_entity,
+            vec![
+                storage_bind_group_entity,
+                line_smear_front_entity,
+                line_smear_back_entity,
+            ],
+        ),
+        (triangle_index_entity, vec![]),
+        (triangle_mesh_entity, vec![frustum_cull_entity]),
+        (triangle_mesh_bounds_entity, vec![frustum_cull_entity]),
+        (line_smear_front_entity, vec![line_smear_front_entity, line_smear_back_entity]),
+        (line_smear_back_entity, vec![line_smear_front_entity, line_smear_back_entity]),
+        (
+            line_smear_buffer_entity,
+            vec![
+                line_smear_front_entity,
+                line_smear_back_entity,
+                line_smear_buffer_entity,
+            ],
+        ),
+    ] {
+        world
+            .insert_one(
+                entity,
+                antigen_wgpu::GrowableBufferComponent::construct(bind_groups),
+            )
+            .unwrap();
+    }
+
     // Clear pass
     let beam_clear_pass_entity = world.reserve_entity();
     let mut builder = EntityBuilder::new();
     builder.add(BeamClear);
     builder.add(RenderPipelineComponent::default());
     builder.add_bundle(
-        antigen_wgpu::RenderPassBundle::draw(
-            0,
-            Some("Beam Clear".into()),
-            vec![(
+        RenderPassBuilder::new(0, renderer_entity)
+            .label("Beam Clear")
+            .color_attachment(
                 beam_multisample_entity,
                 Some(beam_buffer_entity),
                 Operations {
                     load: LoadOp::Clear(CLEAR_COLOR),
                     store: true,
                 },
-            )],
-            Some((
+            )
+            .depth(
                 beam_depth_buffer_entity,
                 Some(Operations {
                     load: LoadOp::Clear(0.0),
-                    store: false,
+                    store: true,
                 }),
-                None,
-            )),
-            beam_clear_pass_entity,
-            vec![],
-            None,
-            vec![],
-            vec![],
-            None,
-            None,
-            None,
-            None,
-            (0..1, 0..1),
-            renderer_entity,
-        )
-        .build(),
+                // Reset portal stencils from the previous frame
+                Some(Operations {
+                    load: LoadOp::Clear(0),
+                    store: true,
+                }),
+            )
+            .pipeline(beam_clear_pass_entity)
+            .draw(0..1, 0..1)
+            .build(),
     );
     world
         .insert(beam_clear_pass_entity, builder.build())
@@ -903,129 +1732,358 @@ pub fn assemble(world: &mut World, channel: &WorldChannel) {
     builder.add(BeamLines);
     builder.add(RenderPipelineComponent::default());
     builder.add_bundle(
-        antigen_wgpu::RenderPassBundle::draw(
-            2,
-            Some("Beam Lines".into()),
-            vec![(
+        RenderPassBuilder::new(7, renderer_entity)
+            .label("Beam Lines")
+            .color_attachment(
                 beam_multisample_entity,
                 Some(beam_buffer_entity),
                 Operations {
                     load: LoadOp::Load,
                     store: true,
                 },
-            )],
-            Some((
+            )
+            .depth(
                 beam_depth_buffer_entity,
                 Some(Operations {
                     load: LoadOp::Load,
                     store: false,
                 }),
-                None,
-            )),
-            beam_line_pass_entity,
-            vec![
-                (line_vertex_entity, 0..224),
-                (line_instance_entity, 0..960000),
-            ],
-            None,
-            vec![
-                (uniform_entity, vec![]),
-                (storage_bind_group_entity, vec![0]),
-            ],
-            vec![],
-            None,
-            None,
-            None,
-            None,
-            (0..14, 0..MAX_LINE_INSTANCES as u32),
-            renderer_entity,
-        )
-        .build(),
+                Some(Operations {
+                    load: LoadOp::Load,
+                    store: false,
+                }),
+            )
+            .pipeline(beam_line_pass_entity)
+            .vertex_buffer(line_vertex_entity, 0..224)
+            .vertex_buffer(line_instance_entity, 0..960000)
+            .bind_group(uniform_entity, vec![])
+            .bind_group(storage_bind_group_entity, vec![0])
+            .draw(0..14, 0..MAX_LINE_INSTANCES as u32)
+            .build(),
     );
     world
         .insert(beam_line_pass_entity, builder.build())
         .unwrap();
 
-    let beam_entity = world.spawn((Beam,));
-    load_shader::<Filesystem, _>(
-        channel,
-        beam_entity,
-        "test-data/shaders/beam.wgsl",
-    );
-
-    // Phosphor pass
-    let phosphor_pass_entity = world.reserve_entity();
+    // Beam line smear pass, drawing a quad behind each line that moved since the last frame
+    let beam_line_smear_pass_entity = world.reserve_entity();
     let mut builder = EntityBuilder::new();
-    builder.add(PhosphorDecay);
+    builder.add(BeamLineSmears);
     builder.add(RenderPipelineComponent::default());
-    builder.add(BindGroupLayoutComponent::default());
     builder.add_bundle(
-        antigen_wgpu::RenderPassBundle::draw(
-            3,
-            Some("Phosphor Decay".into()),
-            vec![(
-                phosphor_front_entity,
-                None,
+        RenderPassBuilder::new(7, renderer_entity)
+            .label("Beam Line Smears")
+            .color_attachment(
+                beam_multisample_entity,
+                Some(beam_buffer_entity),
                 Operations {
                     load: LoadOp::Load,
                     store: true,
                 },
-            )],
-            None,
-            phosphor_pass_entity,
-            vec![],
-            None,
-            vec![(uniform_entity, vec![]), (phosphor_front_entity, vec![])],
-            vec![],
-            None,
-            None,
-            None,
-            None,
-            (0..4, 0..1 as u32),
-            renderer_entity,
-        )
-        .build(),
+            )
+            .depth(
+                beam_depth_buffer_entity,
+                Some(Operations {
+                    load: LoadOp::Load,
+                    store: false,
+                }),
+                Some(Operations {
+                    load: LoadOp::Load,
+                    store: false,
+                }),
+            )
+            .pipeline(beam_line_smear_pass_entity)
+            .bind_group(uniform_entity, vec![])
+            .bind_group(storage_bind_group_entity, vec![0])
+            .bind_group(line_smear_buffer_entity, vec![])
+            .draw(0..4, 0..0)
+            .build(),
     );
-    world.insert(phosphor_pass_entity, builder.build()).unwrap();
+    world
+        .insert(beam_line_smear_pass_entity, builder.build())
+        .unwrap();
 
-    load_shader::<Filesystem, _>(
-        channel,
-        phosphor_pass_entity,
-        "test-data/shaders/phosphor_decay.wgsl",
+    // Portal passes, which mark the fragments covered by portal quads in the stencil buffer
+    // and draw the scene as seen through them before drawing the scene itself
+    let portal_uniform_entity = world.spawn(portal_uniform_buffer_bundle().build());
+    let portal_staging_belt_entity = world.spawn(antigen_wgpu::StagingBeltBundle::new(
+        buffer_size_of::<UniformData>(),
+        renderer_entity,
+    ));
+    world.spawn(
+        uniform_data_bundle(portal_uniform_entity, portal_staging_belt_entity)
+            .add(PortalUniform)
+            .build(),
     );
 
-    // Tonemap pass
-    let tonemap_pass_entity = world.reserve_entity();
+    let portal_instance_entity = world.spawn(portal_instance_buffer_bundle().build());
+    world.spawn(antigen_wgpu::BufferDataBundle::new(
+        PortalInstanceDataComponent::new(),
+        0,
+        portal_instance_entity,
+    ));
+    let portal_instance_range =
+        0..buffer_size_of::<PortalInstanceData>() * MAX_PORTALS as BufferAddress;
 
+    let portal_stencil_pass_entity = world.reserve_entity();
     let mut builder = EntityBuilder::new();
-    builder.add(Tonemap);
+    builder.add(PortalStencil);
     builder.add(RenderPipelineComponent::default());
     builder.add_bundle(
-        antigen_wgpu::RenderPassBundle::draw(
-            4,
-            Some("Tonemap".into()),
-            vec![(
-                window_entity,
+        RenderPassBuilder::new(1, renderer_entity)
+            .label("Portal Stencil")
+            .color_attachment(
+                beam_multisample_entity,
+                Some(beam_buffer_entity),
+                Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            )
+            .depth(
+                beam_depth_buffer_entity,
+                Some(Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+                Some(Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+            )
+            .pipeline(portal_stencil_pass_entity)
+            .vertex_buffer(portal_instance_entity, portal_instance_range.clone())
+            .bind_group(uniform_entity, vec![])
+            .stencil_reference(0)
+            .draw(0..4, 0..0)
+            .build(),
+    );
+    world
+        .insert(portal_stencil_pass_entity, builder.build())
+        .unwrap();
+
+    // Pipeline for the per-mesh portal passes spawned by phosphor_portal_mesh_passes_system
+    let portal_mesh_pass_entity =
+        world.spawn((PortalTriangles, RenderPipelineComponent::default()));
+
+    let portal_line_pass_entity = world.reserve_entity();
+    let mut builder = EntityBuilder::new();
+    builder.add(PortalLines);
+    builder.add(RenderPipelineComponent::default());
+    builder.add_bundle(
+        RenderPassBuilder::new(3, renderer_entity)
+            .label("Portal Lines")
+            .color_attachment(
+                beam_multisample_entity,
+                Some(beam_buffer_entity),
+                Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            )
+            .depth(
+                beam_depth_buffer_entity,
+                Some(Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+                Some(Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+            )
+            .pipeline(portal_line_pass_entity)
+            .vertex_buffer(line_vertex_entity, 0..224)
+            .vertex_buffer(line_instance_entity, 0..960000)
+            .bind_group(portal_uniform_entity, vec![])
+            .bind_group(storage_bind_group_entity, vec![0])
+            .stencil_reference(1)
+            .draw(0..14, 0..MAX_LINE_INSTANCES as u32)
+            .build(),
+    );
+    world
+        .insert(portal_line_pass_entity, builder.build())
+        .unwrap();
+
+    let portal_depth_pass_entity = world.reserve_entity();
+    let mut builder = EntityBuilder::new();
+    builder.add(PortalDepth);
+    builder.add(RenderPipelineComponent::default());
+    builder.add_bundle(
+        RenderPassBuilder::new(4, renderer_entity)
+            .label("Portal Depth")
+            .color_attachment(
+                beam_multisample_entity,
+                Some(beam_buffer_entity),
+                Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            )
+            .depth(
+                beam_depth_buffer_entity,
+                Some(Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+                Some(Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+            )
+            .pipeline(portal_depth_pass_entity)
+            .vertex_buffer(portal_instance_entity, portal_instance_range)
+            .bind_group(uniform_entity, vec![])
+            .stencil_reference(1)
+            .draw(0..4, 0..0)
+            .build(),
+    );
+    world
+        .insert(portal_depth_pass_entity, builder.build())
+        .unwrap();
+
+    let beam_entity = world.spawn((Beam,));
+    load_shader::<Filesystem, _>(
+        channel,
+        beam_entity,
+        "test-data/shaders/beam.wgsl",
+    );
+
+    // Skybox pass, built by phosphor_skybox_pass_system to suit its mode
+    let skybox_entity = world.spawn(skybox_bundle().build());
+    load_shader::<Filesystem, _>(
+        channel,
+        skybox_entity,
+        "test-data/shaders/skybox.wgsl",
+    );
+
+    // Phosphor pass
+    let phosphor_pass_entity = world.reserve_entity();
+    let mut builder = EntityBuilder::new();
+    builder.add(PhosphorDecay);
+    builder.add(RenderPipelineComponent::default());
+    builder.add(BindGroupLayoutComponent::default());
+    builder.add_bundle(
+        RenderPassBuilder::new(8, renderer_entity)
+            .label("Phosphor Decay")
+            .color_attachment(
+                phosphor_front_entity,
                 None,
                 Operations {
-                    load: LoadOp::Clear(Color::BLACK),
+                    load: LoadOp::Load,
                     store: true,
                 },
-            )],
-            None,
-            tonemap_pass_entity,
-            vec![],
-            None,
-            vec![(phosphor_back_entity, vec![])],
-            vec![],
-            None,
-            None,
-            None,
-            None,
-            (0..4, 0..1),
-            renderer_entity,
+            )
+            .pipeline(phosphor_pass_entity)
+            .bind_group(uniform_entity, vec![])
+            .bind_group(phosphor_front_entity, vec![])
+            .draw(0..4, 0..1)
+            .build(),
+    );
+    world.insert(phosphor_pass_entity, builder.build()).unwrap();
+
+    load_shader::<Filesystem, _>(
+        channel,
+        phosphor_pass_entity,
+        "test-data/shaders/phosphor_decay.wgsl",
+    );
+
+    // Bloom passes
+    let bloom_entity = world.spawn((
+        Bloom,
+        BindGroupLayoutComponent::default(),
+        BindGroupComponent::default(),
+    ));
+
+    load_shader::<Filesystem, _>(
+        channel,
+        bloom_entity,
+        "test-data/shaders/bloom.wgsl",
+    );
+
+    let bloom_downsample_entity =
+        world.spawn((BloomDownsample, RenderPipelineComponent::default()));
+    let bloom_blur_entity = world.spawn((BloomBlur, RenderPipelineComponent::default()));
+
+    // Each level is downsampled from the blurred buffer of the level above it,
+    // starting from the phosphor buffer read by the tonemap pass
+    let mut bloom_source_entity = phosphor_back_entity;
+    for level in 0..BLOOM_LEVELS {
+        let downsample_buffer_entity = world.spawn(bloom_buffer_bundle(level, false).build());
+        let blur_buffer_entity = world.spawn(bloom_buffer_bundle(level, true).build());
+
+        world.spawn(
+            RenderPassBuilder::new(9 + level * 2, renderer_entity)
+                .label("Bloom Downsample")
+                .color_attachment(
+                    downsample_buffer_entity,
+                    None,
+                    Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                )
+                .pipeline(bloom_downsample_entity)
+                .bind_group(bloom_source_entity, vec![])
+                .draw(0..4, 0..1)
+                .build(),
+        );
+
+        world.spawn(
+            RenderPassBuilder::new(10 + level * 2, renderer_entity)
+                .label("Bloom Blur")
+                .color_attachment(
+                    blur_buffer_entity,
+                    None,
+                    Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                )
+                .pipeline(bloom_blur_entity)
+                .bind_group(downsample_buffer_entity, vec![])
+                .draw(0..4, 0..1)
+                .build(),
+        );
+
+        bloom_source_entity = blur_buffer_entity;
+    }
+
+    // Color LUT
+    let color_lut_entity = world.reserve_entity();
+    world
+        .insert(
+            color_lut_entity,
+            color_lut_bundle(color_lut_entity, ColorLutComponent::identity(COLOR_LUT_SIZE))
+                .build(),
         )
-        .build(),
+        .unwrap();
+
+    // Tonemap pass
+    let tonemap_pass_entity = world.reserve_entity();
+
+    let mut builder = EntityBuilder::new();
+    builder.add(Tonemap);
+    builder.add(RenderPipelineComponent::default());
+    builder.add_bundle(
+        RenderPassBuilder::new(9 + BLOOM_LEVELS * 2, renderer_entity)
+            .label("Tonemap")
+            .color_attachment(
+                target_entity,
+                None,
+                Operations {
+                    // Resolved from the target's BackgroundComponent
+                    load: LoadOp::Clear(Color::default()),
+                    store: true,
+                },
+            )
+            .pipeline(tonemap_pass_entity)
+            .bind_group(phosphor_back_entity, vec![])
+            .bind_group(uniform_entity, vec![])
+            .bind_group(bloom_entity, vec![])
+            .bind_group(color_lut_entity, vec![])
+            .draw(0..4, 0..1)
+            .build(),
     );
 
     world.insert(tonemap_pass_entity, builder.build()).unwrap();
@@ -1041,19 +2099,23 @@ pub fn assemble(world: &mut World, channel: &WorldChannel) {
 
     builder.add(PhosphorRenderer);
 
-    builder.add(PlayerInputComponent::default());
+    builder.add(default_input_bindings());
+    builder.add(InputActionsComponent::default());
 
     // Phosphor sampler
-    builder.add_bundle(antigen_wgpu::SamplerBundle::new(SamplerDescriptor {
-        label: Some("Linear Sampler"),
-        address_mode_u: AddressMode::ClampToEdge,
-        address_mode_v: AddressMode::ClampToEdge,
-        address_mode_w: AddressMode::ClampToEdge,
-        mag_filter: FilterMode::Linear,
-        min_filter: FilterMode::Linear,
-        mipmap_filter: FilterMode::Linear,
-        ..Default::default()
-    }));
+    builder.add_bundle(
+        antigen_wgpu::SamplerBundle::new(SamplerDescriptor {
+            label: Some("Linear Sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        })
+        .with_anisotropy_clamp(antigen_wgpu::MAX_ANISOTROPY),
+    );
 
     // Command encoder
     builder.add_bundle(antigen_wgpu::CommandEncoderBundle::new(
@@ -1068,16 +2130,21 @@ pub fn assemble(world: &mut World, channel: &WorldChannel) {
         .add(antigen_wgpu::CommandBuffersComponent::default())
         // Indirect surface config and view for resize handling
         .add(Indirect::<&SurfaceConfigurationComponent>::construct(
-            window_entity,
+            target_entity,
         ))
-        .add(Indirect::<&TextureViewComponent>::construct(window_entity))
-        // Indirect window for input handling
-        .add(Indirect::<&WindowComponent>::construct(window_entity));
+        .add(Indirect::<&TextureViewComponent>::construct(target_entity));
+
+    // Indirect window for input handling
+    if world.get::<WindowComponent>(target_entity).is_ok() {
+        builder.add(Indirect::<&WindowComponent>::construct(target_entity));
+    }
 
     // Done
     let bundle = builder.build();
     world.insert(renderer_entity, bundle).unwrap();
 
+    load_input_bindings::<Filesystem, _>(channel, renderer_entity, "test-data/input/bindings.ron");
+
     // Insert tagged entities
     insert_tagged_entity::<Uniform>(world, uniform_entity);
     insert_tagged_entity::<BeamBuffer>(world, beam_buffer_entity);
@@ -1085,11 +2152,14 @@ pub fn assemble(world: &mut World, channel: &WorldChannel) {
     insert_tagged_entity::<BeamMultisample>(world, beam_multisample_entity);
     insert_tagged_entity::<StorageBuffers>(world, storage_bind_group_entity);
     insert_tagged_entity::<BeamTriangles>(world, beam_mesh_pass_entity);
+    insert_tagged_entity::<PortalUniform>(world, portal_uniform_entity);
+    insert_tagged_entity::<PortalTriangles>(world, portal_mesh_pass_entity);
     insert_tagged_entity::<PhosphorRenderer>(world, renderer_entity);
 
     insert_tagged_entity::<Vertices>(world, vertex_entity);
     insert_tagged_entity::<TriangleIndices>(world, triangle_index_entity);
     insert_tagged_entity::<TriangleMeshes>(world, triangle_mesh_entity);
+    insert_tagged_entity::<TriangleMeshBounds>(world, triangle_mesh_bounds_entity);
     insert_tagged_entity::<TriangleMeshInstances>(world, triangle_mesh_instance_entity);
     insert_tagged_entity::<LineIndices>(world, line_index_entity);
     insert_tagged_entity::<LineMeshes>(world, line_mesh_entity);
@@ -1160,17 +2230,15 @@ pub fn assemble(world: &mut World, channel: &WorldChannel) {
     }
 
     assemble_test_geometry(world);
+    assemble_debug_collider_meshes(world);
 
-    load_map::<MapFile, Filesystem, _>(
-        channel,
-        //"test-data/maps/non_manifold_line.map",
-        //"test-data/maps/non_manifold_room.map",
-        "test-data/maps/line_index_test.map",
-    );
+    // Loaded by map_reload_system
+    let map_path = map_path_arg(world);
+    world.spawn(map_path_bundle(map_path).build());
 }
 
 fn assemble_test_geometry(world: &mut World) {
-    let line_mesh_entity = get_tagged_entity::<LineMeshes>(world).unwrap();
+    let line_mesh_entity = get_tagged_entity_or::<LineMeshes>(world).unwrap();
 
     // Equilateral triangle
     let line_mesh = world
@@ -1213,11 +2281,148 @@ fn assemble_test_geometry(world: &mut World) {
     world.spawn(bundle);
 }
 
+// Build the unit outlines shared by every cuboid and ball collider
+fn assemble_debug_collider_meshes(world: &mut World) {
+    let corners = (0..8).map(|i| {
+        let axis = |bit: u32| if i & bit == 0 { -1.0 } else { 1.0 };
+        (axis(1), axis(2), axis(4))
+    });
+
+    // Join each pair of corners that differ along a single axis
+    let cuboid_indices = (0..8u32)
+        .flat_map(|i| {
+            [1, 2, 4]
+                .into_iter()
+                .filter(move |bit| i & bit == 0)
+                .flat_map(move |bit| [i, i | bit])
+        })
+        .collect();
+
+    assemble_debug_collider_mesh(
+        world,
+        DEBUG_CUBOID_MESH.into(),
+        debug_collider_vertices(corners),
+        cuboid_indices,
+    );
+
+    // One great circle around each axis
+    let segments = DEBUG_COLLIDER_SEGMENTS;
+    let circles = (0..3).flat_map(|axis| {
+        (0..segments).map(move |i| {
+            let (sin, cos) = (std::f32::consts::TAU * i as f32 / segments as f32).sin_cos();
+            match axis {
+                0 => (0.0, sin, cos),
+                1 => (cos, 0.0, sin),
+                _ => (sin, cos, 0.0),
+            }
+        })
+    });
+
+    let ball_indices = (0..3)
+        .flat_map(|axis| {
+            (0..segments).flat_map(move |i| {
+                [axis * segments + i, axis * segments + (i + 1) % segments]
+            })
+        })
+        .collect();
+
+    assemble_debug_collider_mesh(
+        world,
+        DEBUG_BALL_MESH.into(),
+        debug_collider_vertices(circles),
+        ball_indices,
+    );
+}
+
+// Collider outline vertices, drawn as lines only
+fn debug_collider_vertices(
+    positions: impl IntoIterator<Item = (f32, f32, f32)>,
+) -> Vec<VertexData> {
+    positions
+        .into_iter()
+        .map(|position| VertexData::new(position, BLACK, GREEN, 1.0, 0.0))
+        .collect()
+}
+
+fn assemble_debug_collider_mesh(
+    world: &mut World,
+    mesh: Cow<'static, str>,
+    vertices: Vec<VertexData>,
+    indices: Vec<u32>,
+) {
+    let line_mesh_entity = get_tagged_entity_or::<LineMeshes>(world).unwrap();
+    let line_mesh = world
+        .query_one_mut::<&mut BufferLengthComponent>(line_mesh_entity)
+        .unwrap()
+        .load(Ordering::Relaxed) as u32;
+    let line_count = indices.len() as u32 / 2;
+
+    register_line_mesh_id(world, mesh, (line_mesh, line_count));
+
+    let mut builder = line_mesh_builder(world, vertices, indices);
+    world.spawn(builder.build());
+}
+
+// Build the outline mesh for a collider shape without a shared unit outline
+fn assemble_debug_collider_mesh_message(
+    mesh: Cow<'static, str>,
+    vertices: Vec<VertexData>,
+    indices: Vec<u32>,
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+        assemble_debug_collider_mesh(world, mesh, vertices, indices);
+        Ok(ctx)
+    }
+}
+
+/// Returns a message that will toggle collider outlines in the receiving world
+pub fn toggle_debug_colliders_message(
+) -> impl for<'a, 'b> FnOnce(MessageContext<'a, 'b>) -> MessageResult<'a, 'b> {
+    move |mut ctx| {
+        let (world, _) = &mut ctx;
+        for (_, debug_colliders) in world.query_mut::<&mut DebugCollidersComponent>() {
+            **debug_colliders = !**debug_colliders;
+        }
+        Ok(ctx)
+    }
+}
+
+// Expand the \n and \xHH escapes map properties use in place of control characters
+fn unescape_text(string: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = string.chars();
+
+    while let Some(c) = chars.next() {
+        let rest = chars.as_str();
+        let escape = match (c, rest.as_bytes()) {
+            ('\\', [b'n', ..]) => Some(('\n', 1)),
+            ('\\', [b'x', hi, lo, ..]) if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+                u8::from_str_radix(&rest[1..3], 16)
+                    .ok()
+                    .map(|byte| (char::from(byte), 3))
+            }
+            _ => None,
+        };
+
+        match escape {
+            Some((c, len)) => {
+                unescaped.push(c);
+                chars = rest[len..].chars();
+            }
+            None => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
 #[derive(Clone)]
 struct MapData {
     geo_map: antigen_shambler::shambler::GeoMap,
     lines: antigen_shambler::shambler::line::Lines,
     entity_centers: antigen_shambler::shambler::entity::EntityCenters,
+    face_planes: antigen_shambler::shambler::face::FacePlanes,
     brush_centers: antigen_shambler::shambler::brush::BrushCenters,
     face_vertices: antigen_shambler::shambler::face::FaceVertices,
     face_duplicates: antigen_shambler::shambler::face::FaceDuplicates,
@@ -1362,6 +2567,7 @@ impl From<GeoMap> for MapData {
             geo_map,
             lines,
             entity_centers,
+            face_planes,
             brush_centers,
             face_vertices,
             face_duplicates,
@@ -1397,6 +2603,76 @@ impl MapData {
             })
     }
 
+    // Position and camera euler angles of the map's first info_player_start,
+    // or the origin if it has none
+    fn player_start_transform(&self) -> (nalgebra::Vector3<f32>, nalgebra::Vector3<f32>) {
+        let properties = self
+            .geo_map
+            .point_entities
+            .iter()
+            .map(|entity| self.geo_map.entity_properties.get(entity).unwrap())
+            .find(|properties| {
+                properties
+                    .iter()
+                    .any(|p| p.key == "classname" && p.value == "info_player_start")
+            });
+
+        if let Some(properties) = properties {
+            let position = Self::property_origin(properties).unwrap_or_default();
+
+            // Quake angles face +X at 0 degrees and turn counter-clockwise,
+            // whereas camera yaw faces -Z at 0 radians and turns clockwise
+            let (pitch, yaw) =
+                if let Ok((pitch, yaw, _)) = Self::property_f32_3("mangle", properties) {
+                    (pitch, yaw)
+                } else {
+                    (0.0, Self::property_f32("angle", properties).unwrap_or(90.0))
+                };
+
+            (
+                position,
+                nalgebra::vector![pitch.to_radians(), (90.0 - yaw).to_radians(), 0.0],
+            )
+        } else {
+            println!("No info_player_start in map, spawning camera at origin");
+            Default::default()
+        }
+    }
+
+    // Camera spawn point at the map's player start
+    fn player_start(&self) -> EntityBuilder {
+        let (position, euler_angles) = self.player_start_transform();
+
+        let mut builder = EntityBuilder::new();
+        builder
+            .add(PlayerStart)
+            .add(PositionComponent::construct(position))
+            .add(EulerAnglesComponent::construct(euler_angles));
+        builder
+    }
+
+    // Character controller moving the camera through the map, starting at its player start
+    fn player_character(&self) -> EntityBuilder {
+        let (position, _) = self.player_start_transform();
+
+        let controller = CharacterControllerComponent::new(SharedShape::capsule(
+            rapier3d::prelude::Point::new(0.0, -PLAYER_HALF_HEIGHT, 0.0),
+            rapier3d::prelude::Point::new(0.0, PLAYER_HALF_HEIGHT, 0.0),
+            PLAYER_RADIUS,
+        ))
+        .with_max_step_height(PLAYER_STEP_HEIGHT)
+        .with_snap_to_ground(PLAYER_STEP_HEIGHT);
+
+        let mut builder = EntityBuilder::new();
+        builder
+            .add(Player)
+            .add(controller)
+            .add(PositionComponent::construct(position))
+            .add(DesiredMovementComponent::construct(nalgebra::Vector3::zeros()))
+            .add(EffectiveMovementComponent::construct(nalgebra::Vector3::zeros()));
+        builder
+    }
+
     fn entity_property<'a>(&'a self, entity: &EntityId, property: &str) -> Option<&Property> {
         let properties = self.geo_map.entity_properties.get(entity).unwrap();
         properties.iter().find(|p| p.key == property)
@@ -1550,9 +2826,13 @@ impl MapData {
         let mut builders = vec![];
 
         // Brush entity meshes
-        for (entity, _) in entity_brushes {
+        for (entity, brushes) in entity_brushes {
             let properties = self.geo_map.entity_properties.get(entity).unwrap();
 
+            if matches!(Self::property_bool("room", properties), Ok(true)) {
+                builders.push(self.entity_room(entity, brushes));
+            }
+
             if matches!(Self::property_bool("mesh.visual", properties), Ok(true)) {
                 let entity_mesh_name = Self::property_targetname("mesh.visual.name", properties)
                     .unwrap_or_else(|_| Self::default_entity_name(entity));
@@ -1595,52 +2875,163 @@ impl MapData {
         builders
     }
 
-    fn entity_line(world: &mut World, entity: &EntityId, properties: &Properties) -> EntityBuilder {
-        let mut builder = EntityBuilder::new();
-        if matches!(Self::property_bool("line", properties), Ok(true)) {
-            let name = Self::property_string("line.name", properties)
-                .map(ToString::to_string)
-                .unwrap_or_else(|_| Self::default_entity_name(entity));
+    // Convert the hulls of a room's brushes from map space into world space
+    fn entity_room(&self, entity: &EntityId, brushes: &[BrushId]) -> EntityBuilder {
+        let hulls = brushes
+            .iter()
+            .map(|brush| {
+                ConvexHull::from(self.geo_map.brush_faces[brush].iter().map(|face| {
+                    let Plane3d { n, d } = self.face_planes[face];
+                    Plane3d {
+                        n: nalgebra::vector![n.x, n.z, -n.y],
+                        d,
+                    }
+                }))
+            })
+            .collect::<Vec<_>>();
 
-            let line_count = Self::property_usize("line.segments", properties).unwrap_or(1);
-            let color =
-                Self::property_f32_3("line.color", properties).unwrap_or_else(|_| (1.0, 1.0, 1.0));
-            let intensity = Self::property_f32("line.intensity", properties).unwrap_or(1.0);
-            let delta_intensity =
-                Self::property_f32("line.delta_intensity", properties).unwrap_or(1.0);
-
-            builder.add_bundle(
-                line_builder(
-                    world,
-                    name.into(),
-                    line_count,
-                    color,
-                    intensity,
-                    delta_intensity,
-                )
-                .build(),
-            );
-        }
+        let center = self.entity_centers[entity];
+
+        let mut builder = EntityBuilder::new();
+        builder.add(RoomComponent::construct(hulls));
+        builder.add(RoomCenterComponent::construct(nalgebra::vector![
+            center.x, center.z, -center.y
+        ]));
         builder
     }
 
-    fn entity_oscilloscope(properties: &Properties) -> EntityBuilder {
+    fn entity_line(world: &mut World, entity: &EntityId, properties: &Properties) -> EntityBuilder {
         let mut builder = EntityBuilder::new();
-        if matches!(Self::property_bool("oscilloscope", properties), Ok(true)) {
-            let speed = Self::property_f32("oscilloscope.speed", properties).unwrap_or(1.0);
-            let magnitude = Self::property_f32("oscilloscope.magnitude", properties).unwrap_or(1.0);
-
-            let x = Self::property_expression_f32("oscilloscope.x", properties)
-                .unwrap_or(Expression::Val(0.0));
-            let y = Self::property_expression_f32("oscilloscope.y", properties)
-                .unwrap_or(Expression::Val(0.0));
-            let z = Self::property_expression_f32("oscilloscope.z", properties)
-                .unwrap_or(Expression::Val(0.0));
-
-            builder.add(Oscilloscope::new(speed, magnitude, move |f| {
-                let vars = [("f", f)].into_iter().collect::<BTreeMap<_, _>>();
-                (x.eval(&vars), y.eval(&vars), z.eval(&vars))
-            }));
+        if matches!(Self::property_bool("line", properties), Ok(true)) {
+            let name = Self::line_name(entity, properties);
+            builder.add_bundle(Self::line_stream(world, name.into(), properties).build());
+        }
+        builder
+    }
+
+    fn line_name(entity: &EntityId, properties: &Properties) -> String {
+        Self::property_string("line.name", properties)
+            .map(ToString::to_string)
+            .unwrap_or_else(|_| Self::default_entity_name(entity))
+    }
+
+    // Line mesh vertices shaped by the line.* properties
+    fn line_stream(
+        world: &mut World,
+        name: Cow<'static, str>,
+        properties: &Properties,
+    ) -> EntityBuilder {
+        let line_count = Self::property_usize("line.segments", properties).unwrap_or(1);
+        let color =
+            Self::property_f32_3("line.color", properties).unwrap_or_else(|_| (1.0, 1.0, 1.0));
+        let intensity = Self::property_f32("line.intensity", properties).unwrap_or(1.0);
+        let delta_intensity = Self::property_f32("line.delta_intensity", properties).unwrap_or(1.0);
+
+        line_builder(world, name, line_count, color, intensity, delta_intensity)
+    }
+
+    // Oscilloscopes trace a dedicated line mesh named by oscilloscope.name,
+    // which line mesh instances attach to by naming it as their mesh.
+    // A line of the same name on the same entity is traced in place, otherwise
+    // the oscilloscope is spawned alongside a line of its own, shaped by the line.* properties.
+    fn entity_oscilloscope(
+        world: &mut World,
+        entity: &EntityId,
+        properties: &Properties,
+        entity_builder: &mut EntityBuilder,
+    ) -> Vec<EntityBuilder> {
+        let mut builder = EntityBuilder::new();
+        if matches!(Self::property_bool("oscilloscope", properties), Ok(true)) {
+            let name = Self::property_targetname("oscilloscope.name", properties)
+                .unwrap_or_else(|_| Self::default_entity_name(entity));
+
+            let traces_line = matches!(Self::property_bool("line", properties), Ok(true))
+                && Self::line_name(entity, properties) == name;
+
+            if !traces_line {
+                let registered = world
+                    .query_mut::<&LineMeshIdsComponent>()
+                    .with::<LineMeshIds>()
+                    .into_iter()
+                    .any(|(_, mesh_ids)| mesh_ids.read().contains_key(name.as_str()));
+
+                // Tracing another entity's line would animate every instance of it
+                if registered {
+                    println!(
+                        "Warning: Skipping oscilloscope for entity {}, line mesh {} already exists",
+                        entity, name
+                    );
+                    return vec![];
+                }
+
+                builder.add_bundle(Self::line_stream(world, name.into(), properties).build());
+            }
+
+            let speed = Self::property_f32("oscilloscope.speed", properties).unwrap_or(1.0);
+            let magnitude = Self::property_f32("oscilloscope.magnitude", properties).unwrap_or(1.0);
+
+            // Only the built-in trigonometric functions are available to oscilloscopes
+            let functions = FunctionRegistry::default();
+
+            let expression =
+                |key| match Self::property_expression_f32(key, properties, &["f"], &functions) {
+                    Ok(expression) => expression,
+                    Err(e) => {
+                        if Self::property_string(key, properties).is_ok() {
+                            println!("Warning: {}", e);
+                        }
+                        Expression::Val(0.0)
+                    }
+                };
+
+            let x = expression("oscilloscope.x");
+            let y = expression("oscilloscope.y");
+            let z = expression("oscilloscope.z");
+
+            let mut oscilloscope = Oscilloscope::new(speed, magnitude, move |f| {
+                let vars = [("f", f)].into_iter().collect::<BTreeMap<_, _>>();
+                // Fall back to the origin rather than emitting NaN vertices
+                let eval = |expression: &Expression<f32>| {
+                    expression.try_eval(&(&vars, &functions)).unwrap_or(0.0)
+                };
+                (eval(&x), eval(&y), eval(&z))
+            });
+
+            if let Ok(period) = Self::property_f32("oscilloscope.period", properties) {
+                oscilloscope = oscilloscope.with_period(period);
+            }
+
+            if let Ok(phase) = Self::property_f32("oscilloscope.phase", properties) {
+                oscilloscope = oscilloscope.with_phase(phase);
+            }
+
+            builder.add(oscilloscope);
+
+            if traces_line {
+                entity_builder.add_bundle(builder.build());
+            } else {
+                return vec![builder];
+            }
+        }
+        vec![]
+    }
+
+    // Portals are drawn by the render thread, so carry their own copy of the entity transform
+    fn entity_portal(properties: &Properties) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
+        if let Ok(true) = Self::property_bool("portal", properties) {
+            let offset = Self::property_f32_3("portal.offset", properties)
+                .map(|(x, z, y)| nalgebra::vector![x, y, -z])
+                .unwrap_or_else(|_| nalgebra::Vector3::zeros());
+
+            builder.add(PortalComponent::construct(offset));
+            builder.add(PositionComponent::construct(
+                Self::property_origin(properties).unwrap_or_else(nalgebra::Vector3::zeros),
+            ));
+            builder.add(RotationComponent::construct(Self::property_rotation(
+                properties, false,
+            )));
+            builder.add(ScaleComponent::construct(Self::property_scale(properties)));
         }
         builder
     }
@@ -1654,9 +3045,11 @@ impl MapData {
             let properties = self.geo_map.entity_properties.get(entity).unwrap();
 
             builder.add_bundle(Self::entity_line(world, entity, properties).build());
-            builder.add_bundle(Self::entity_oscilloscope(properties).build());
+            let oscilloscopes = Self::entity_oscilloscope(world, entity, properties, &mut builder);
+            builder.add_bundle(Self::entity_portal(properties).build());
 
             builders.push(builder);
+            builders.extend(oscilloscopes);
         }
 
         builders
@@ -1668,40 +3061,43 @@ impl MapData {
         component_property: &str,
     ) -> impl Fn(&FaceId) -> bool + '_ {
         let properties = &self.geo_map.entity_properties[entity];
-        let component_property = component_property.to_string();
+        let cull = Self::property_usize(&format!("{}.cull.faces", component_property), properties)
+            .unwrap_or_default();
+        self.face_cull_flags_predicate(cull)
+    }
+
+    // 1: Duplicate faces, 2: Faces inside other faces, 4: Faces inside brushes,
+    // 8: Exterior faces, 16: Interior faces
+    fn face_cull_flags_predicate(&self, cull: usize) -> impl Fn(&FaceId) -> bool + '_ {
         move |face_id| {
-            if let Ok(cull) =
-                Self::property_usize(&(component_property.clone() + ".cull.faces"), properties)
-            {
-                if cull & 1 > 0 && self.face_duplicates.iter().any(|(_, b)| b == face_id) {
-                    return false;
-                }
+            if cull & 1 > 0 && self.face_duplicates.iter().any(|(_, b)| b == face_id) {
+                return false;
+            }
 
-                if cull & 2 > 0
-                    && self
-                        .face_face_containment
-                        .iter()
-                        .any(|(_, b)| b.contains(face_id))
-                {
-                    return false;
-                }
+            if cull & 2 > 0
+                && self
+                    .face_face_containment
+                    .iter()
+                    .any(|(_, b)| b.contains(face_id))
+            {
+                return false;
+            }
 
-                if cull & 4 > 0
-                    && self
-                        .brush_face_containment
-                        .iter()
-                        .any(|(_, b)| b.contains(face_id))
-                {
-                    return false;
-                }
+            if cull & 4 > 0
+                && self
+                    .brush_face_containment
+                    .iter()
+                    .any(|(_, b)| b.contains(face_id))
+            {
+                return false;
+            }
 
-                if cull & 8 > 0 && !self.interior_faces.contains(&face_id) {
-                    return false;
-                }
+            if cull & 8 > 0 && !self.interior_faces.contains(face_id) {
+                return false;
+            }
 
-                if cull & 16 > 0 && self.interior_faces.contains(&face_id) {
-                    return false;
-                }
+            if cull & 16 > 0 && self.interior_faces.contains(face_id) {
+                return false;
             }
 
             true
@@ -1748,7 +3144,8 @@ impl MapData {
                 let key = Self::property_targetname("convex_hull.name", properties)
                     .unwrap_or_else(|_| Self::default_entity_name(entity));
 
-                let shape = match Self::property_string("convex_hull.type", properties).unwrap() {
+                let shape_fn = match Self::property_string("convex_hull.type", properties).unwrap()
+                {
                     "single" => {
                         let mut brush_vertices = vec![];
                         for brush in brushes {
@@ -1767,10 +3164,10 @@ impl MapData {
                             .map(|vertex| vertex.xzy() - entity_center.xzy())
                             .collect::<Vec<_>>();
 
-                        vec![(
+                        Some(compound_hull_shape(vec![(
                             rapier3d::prelude::nalgebra::Isometry::identity(),
                             brush_vertices,
-                        )]
+                        )]))
                     }
                     "compound" => {
                         let mut brush_hulls = vec![];
@@ -1800,79 +3197,140 @@ impl MapData {
                             ));
                         }
 
-                        brush_hulls
+                        Some(compound_hull_shape(brush_hulls))
                     }
-                    _ => unimplemented!(),
-                };
-
-                let shape_fn = move |scale: nalgebra::Vector3<f32>| {
-                    let mut compound = vec![];
-                    for (mut isometry, convex_hull) in &shape {
-                        isometry.translation.x *= scale.x;
-                        isometry.translation.y *= scale.y;
-                        isometry.translation.z *= scale.z;
-
-                        let mut scaled_hull = vec![];
-                        for vertex in convex_hull {
-                            let scaled_vert = nalgebra::vector![
-                                vertex.x * scale.x,
-                                vertex.y * scale.y,
-                                vertex.z * scale.z
-                            ];
-                            scaled_hull.push(rapier3d::prelude::nalgebra::Point3::new(
-                                scaled_vert.x,
-                                scaled_vert.y,
-                                scaled_vert.z,
-                            ));
+                    // Approximate concave geometry with a set of convex parts,
+                    // falling back to a trimesh if decomposition produces none
+                    "decompose" => {
+                        let params = VHACDParameters {
+                            resolution: Self::property_usize(
+                                "convex_hull.decompose.resolution",
+                                properties,
+                            )
+                            .map(|resolution| resolution as u32)
+                            .unwrap_or(64),
+                            concavity: Self::property_f32(
+                                "convex_hull.decompose.concavity",
+                                properties,
+                            )
+                            .unwrap_or(0.01),
+                            max_convex_hulls: Self::property_usize(
+                                "convex_hull.decompose.max_hulls",
+                                properties,
+                            )
+                            .map(|max_hulls| max_hulls as u32)
+                            .unwrap_or(1024),
+                            ..Default::default()
+                        };
+
+                        let cull = Self::property_usize("convex_hull.cull.faces", properties)
+                            .unwrap_or(1 | 16);
+                        let hulls = self
+                            .brush_entity_triangles(entity, self.face_cull_flags_predicate(cull))
+                            .map(|(vertices, indices)| {
+                                convex_decomposition(&vertices, &indices, &params)
+                            })
+                            .unwrap_or_default();
+
+                        if hulls.is_empty() {
+                            println!(
+                                "Warning: Convex decomposition of {} failed, using a trimesh",
+                                key
+                            );
+                            self.brush_entity_trimesh(entity, self.face_cull_flags_predicate(cull))
+                        } else {
+                            Some(compound_hull_shape(
+                                hulls
+                                    .into_iter()
+                                    .map(|hull| {
+                                        (rapier3d::prelude::nalgebra::Isometry::identity(), hull)
+                                    })
+                                    .collect(),
+                            ))
                         }
-                        compound.push((
-                            isometry,
-                            SharedShape::convex_hull(&scaled_hull[..]).unwrap(),
-                        ))
-                    }
-                    if compound.len() == 1 {
-                        compound.remove(0).1
-                    } else {
-                        SharedShape::compound(compound)
                     }
+                    _ => unimplemented!(),
                 };
 
-                shared_shapes.insert(key.to_owned(), Box::new(shape_fn));
+                if let Some(shape_fn) = shape_fn {
+                    shared_shapes.insert(key.to_owned(), shape_fn);
+                } else {
+                    println!("Warning: Convex hull {} has no triangles", key);
+                }
             }
 
             if matches!(Self::property_bool("mesh.collision", properties), Ok(true)) {
                 let key = Self::property_targetname("mesh.collision.name", properties)
                     .unwrap_or_else(|_| Self::default_entity_name(entity));
 
-                let (mesh_vertices, triangle_indices, _) = self
-                    .assemble_brush_entity_triangle_mesh(
-                        entity,
-                        self.face_cull_predicate(entity, "mesh.collision"),
-                        |_| false,
-                    );
+                let cull_face = self.face_cull_predicate(entity, "mesh.collision");
+                if let Some(shape_fn) = self.brush_entity_trimesh(entity, cull_face) {
+                    shared_shapes.insert(key, shape_fn);
+                } else {
+                    println!("Warning: Collision mesh {} has no triangles", key);
+                }
+            }
 
-                let mesh_vertices = mesh_vertices
-                    .into_iter()
-                    .map(|VertexData { position, .. }| {
-                        rapier3d::prelude::nalgebra::Point3::new(
-                            position[0],
-                            position[1],
-                            position[2],
-                        )
-                    })
-                    .collect::<Vec<_>>();
+            // Solid brushes collide against their outward-facing surface by default,
+            // ignoring duplicate faces where brushes meet
+            if matches!(Self::property_bool("solid", properties), Ok(true)) {
+                let key = Self::property_targetname("solid.name", properties)
+                    .unwrap_or_else(|_| Self::default_entity_name(entity));
 
-                let triangle_indices = triangle_indices
-                    .chunks(3)
-                    .map(|inds| [inds[0] as u32, inds[1] as u32, inds[2] as u32])
-                    .collect::<Vec<_>>();
+                let cull = Self::property_usize("solid.cull.faces", properties).unwrap_or(1 | 16);
+                let cull_face = self.face_cull_flags_predicate(cull);
+                if let Some(shape_fn) = self.brush_entity_trimesh(entity, cull_face) {
+                    shared_shapes.insert(key, shape_fn);
+                } else {
+                    println!("Warning: Solid brush entity {} has no triangles", key);
+                }
+            }
+        }
+    }
 
-                let shape_fn =
-                    move |_| SharedShape::trimesh(mesh_vertices.clone(), triangle_indices.clone());
+    // Vertices and triangles of a brush entity's faces,
+    // or None if culling and degenerate triangle removal leave nothing to collide with
+    #[allow(clippy::type_complexity)]
+    fn brush_entity_triangles(
+        &self,
+        entity: &EntityId,
+        cull_face: impl Fn(&FaceId) -> bool,
+    ) -> Option<(Vec<rapier3d::prelude::nalgebra::Point3<f32>>, Vec<[u32; 3]>)> {
+        let (mesh_vertices, triangle_indices, _) =
+            self.assemble_brush_entity_triangle_mesh(entity, cull_face, |_| false);
 
-                shared_shapes.insert(key, Box::new(shape_fn));
-            }
+        let mesh_vertices = mesh_vertices
+            .into_iter()
+            .map(|VertexData { position, .. }| {
+                rapier3d::prelude::nalgebra::Point3::new(position[0], position[1], position[2])
+            })
+            .collect::<Vec<_>>();
+
+        let triangle_indices = triangle_indices
+            .chunks(3)
+            .map(|inds| [inds[0] as u32, inds[1] as u32, inds[2] as u32])
+            .collect::<Vec<_>>();
+
+        let triangle_indices = non_degenerate_triangles(&mesh_vertices, &triangle_indices);
+        if triangle_indices.is_empty() {
+            return None;
         }
+
+        Some((mesh_vertices, triangle_indices))
+    }
+
+    // Trimesh shape constructor for a brush entity's faces,
+    // or None if culling and degenerate triangle removal leave nothing to collide with
+    fn brush_entity_trimesh(
+        &self,
+        entity: &EntityId,
+        cull_face: impl Fn(&FaceId) -> bool,
+    ) -> Option<SharedShapeFn> {
+        let (mesh_vertices, triangle_indices) = self.brush_entity_triangles(entity, cull_face)?;
+
+        Some(Box::new(move |scale: nalgebra::Vector3<f32>| {
+            scaled_trimesh(&mesh_vertices, &triangle_indices, scale)
+        }))
     }
 
     fn build_brush_entity_triangle_line_meshes(
@@ -1885,11 +3343,11 @@ impl MapData {
         let mut builders = vec![];
 
         // Vertex information
-        let vertex_entity = get_tagged_entity::<Vertices>(world).unwrap();
-        let triangle_index_entity = get_tagged_entity::<TriangleIndices>(world).unwrap();
-        let triangle_mesh_entity = get_tagged_entity::<TriangleMeshes>(world).unwrap();
-        let line_index_entity = get_tagged_entity::<LineIndices>(world).unwrap();
-        let line_mesh_entity = get_tagged_entity::<LineMeshes>(world).unwrap();
+        let vertex_entity = get_tagged_entity_or::<Vertices>(world).unwrap();
+        let triangle_index_entity = get_tagged_entity_or::<TriangleIndices>(world).unwrap();
+        let triangle_mesh_entity = get_tagged_entity_or::<TriangleMeshes>(world).unwrap();
+        let line_index_entity = get_tagged_entity_or::<LineIndices>(world).unwrap();
+        let line_mesh_entity = get_tagged_entity_or::<LineMeshes>(world).unwrap();
 
         let base_vertex = world
             .query_one_mut::<&BufferLengthComponent>(vertex_entity)
@@ -1918,6 +3376,7 @@ impl MapData {
 
         let vertex_count = vertices.len() as u32;
         let triangle_index_count = triangle_indices.len() as u32;
+        let bounds = TriangleMeshBoundsData::from_vertices(&vertices);
         let line_index_count = line_indices.len() as u32;
 
         builders.extend([
@@ -1928,6 +3387,7 @@ impl MapData {
                 0,
                 base_triangle_index,
                 base_vertex,
+                bounds,
             ),
         ]);
 
@@ -1961,9 +3421,9 @@ impl MapData {
         let mut builders = vec![];
 
         // Vertex information
-        let vertex_entity = get_tagged_entity::<Vertices>(world).unwrap();
-        let triangle_index_entity = get_tagged_entity::<TriangleIndices>(world).unwrap();
-        let triangle_mesh_entity = get_tagged_entity::<TriangleMeshes>(world).unwrap();
+        let vertex_entity = get_tagged_entity_or::<Vertices>(world).unwrap();
+        let triangle_index_entity = get_tagged_entity_or::<TriangleIndices>(world).unwrap();
+        let triangle_mesh_entity = get_tagged_entity_or::<TriangleMeshes>(world).unwrap();
 
         let base_vertex = world
             .query_one_mut::<&BufferLengthComponent>(vertex_entity)
@@ -1981,6 +3441,7 @@ impl MapData {
             .load(Ordering::Relaxed) as u32;
 
         let triangle_index_count = triangle_indices.len() as u32;
+        let bounds = TriangleMeshBoundsData::from_vertices(&vertices);
 
         builders.extend([
             triangle_mesh_builder(world, vertices, triangle_indices),
@@ -1990,6 +3451,7 @@ impl MapData {
                 0,
                 base_triangle_index,
                 base_vertex,
+                bounds,
             ),
         ]);
 
@@ -2007,9 +3469,9 @@ impl MapData {
         let mut builders = vec![];
 
         // Vertex information
-        let vertex_entity = get_tagged_entity::<Vertices>(world).unwrap();
-        let line_index_entity = get_tagged_entity::<LineIndices>(world).unwrap();
-        let line_mesh_entity = get_tagged_entity::<LineMeshes>(world).unwrap();
+        let vertex_entity = get_tagged_entity_or::<Vertices>(world).unwrap();
+        let line_index_entity = get_tagged_entity_or::<LineIndices>(world).unwrap();
+        let line_mesh_entity = get_tagged_entity_or::<LineMeshes>(world).unwrap();
 
         let base_vertex = world
             .query_one_mut::<&BufferLengthComponent>(vertex_entity)
@@ -2115,9 +3577,13 @@ impl MapData {
             .parse::<usize>()?)
     }
 
+    /// Parse an expression property, erroring if it references a variable not in `vars`
+    /// or calls a function not in `functions`
     fn property_expression_f32(
         key: &str,
         properties: &Properties,
+        vars: &[&str],
+        functions: &FunctionRegistry<f32>,
     ) -> Result<Expression<f32>, Box<dyn Error>> {
         let value = properties
             .0
@@ -2126,7 +3592,22 @@ impl MapData {
             .ok_or("Key not found")?
             .value
             .as_str();
-        Ok(expression::parse_expression(value))
+
+        let expression = expression::parse_expression_with_functions(value, functions)
+            .map_err(|e| format!("{} in {} expression {:?}", e, key, value))?;
+        if let Some(var) = expression
+            .free_variables()
+            .into_iter()
+            .find(|var| !vars.contains(var))
+        {
+            return Err(format!(
+                "Unknown variable {:?} in {} expression {:?}, expected one of {:?}",
+                var, key, value, vars
+            )
+            .into());
+        }
+
+        Ok(expression)
     }
 
     fn property_string<'a>(
@@ -2208,7 +3689,7 @@ impl MapData {
         builder
     }
 
-    fn entity_rigid_body(properties: &Properties) -> EntityBuilder {
+    fn entity_rigid_body(properties: &Properties, origin: nalgebra::Vector3<f32>) -> EntityBuilder {
         let mut builder = EntityBuilder::new();
         if let Ok(true) = Self::property_bool("rigid_body", properties) {
             if let Ok(ty) = Self::property_string("rigid_body.type", properties) {
@@ -2220,6 +3701,15 @@ impl MapData {
                     _ => panic!("Incorrect variant for rigid_body.type"),
                 };
                 builder.add(RigidBodyComponent::construct(rigid_body_builder.build()));
+
+                // Double-buffer dynamic transforms for interpolation,
+                // starting from the spawn pose so the first tick doesn't sweep from the origin
+                if ty == "dynamic" {
+                    builder.add(PreviousPositionComponent::construct(origin));
+                    builder.add(PreviousRotationComponent::construct(
+                        Self::property_rotation(properties, false),
+                    ));
+                }
             }
 
             if let Ok(vel) = Self::property_f32_3("rigid_body.linear_velocity", properties) {
@@ -2233,6 +3723,13 @@ impl MapData {
                     vel.0, vel.1, vel.2
                 ]));
             }
+
+            if let Ok(gravity_scale) = Self::property_f32("rigid_body.gravity_scale", properties) {
+                builder.add(Changed::new(
+                    GravityScaleComponent::construct(gravity_scale),
+                    false,
+                ));
+            }
         }
         builder
     }
@@ -2247,20 +3744,6 @@ impl MapData {
         if let Ok(true) = Self::property_bool("collider", properties) {
             if let Ok(shape) = Self::property_string("collider.shape", properties) {
                 let collider_builder = match shape {
-                    "ball" => {
-                        let radius =
-                            Self::property_f32("collider.ball.radius", properties).unwrap();
-                        ColliderBuilder::ball(radius * scale.x.max(scale.y).max(scale.z))
-                    }
-                    "cuboid" => {
-                        let extents =
-                            Self::property_f32_3("collider.cuboid.extents", properties).unwrap();
-                        ColliderBuilder::cuboid(
-                            extents.0 * scale.x,
-                            extents.1 * scale.y,
-                            extents.2 * scale.z,
-                        )
-                    }
                     "convex_hull" => {
                         let mesh = Self::property_target("collider.convex_hull.mesh", properties)
                             .unwrap_or_else(|_| Self::default_entity_name(entity));
@@ -2289,16 +3772,22 @@ impl MapData {
 
                         ColliderBuilder::new(shape)
                     }
-                    _ => panic!("Incorrect variant for collider.shape"),
+                    _ => {
+                        // Unparented colliders have their entity's scale baked in on insertion
+                        let scale = if let Ok(true) = Self::property_bool("rigid_body", properties)
+                        {
+                            scale
+                        } else {
+                            nalgebra::vector![1.0, 1.0, 1.0]
+                        };
+
+                        Self::collider_primitive(shape, properties, scale)
+                            .expect("Incorrect variant for collider.shape")
+                    }
                 };
 
-                let collider_builder = if let Ok(restitution) =
-                    Self::property_f32("collider.restitution", properties)
-                {
-                    collider_builder.restitution(restitution)
-                } else {
-                    collider_builder
-                };
+                let collider_builder =
+                    Self::collider_material(collider_builder, "collider", properties);
 
                 let collider_builder =
                     if let Ok(ty) = Self::property_string("collider.type", properties) {
@@ -2341,12 +3830,160 @@ impl MapData {
                     collider_builder
                 };
 
+                // Impact sounds are driven by contact events
+                let collider_builder = if let Ok(sound) =
+                    Self::property_string("collider.impact_sound", properties)
+                {
+                    let full_volume_impulse = Self::property_f32(
+                        "collider.impact_sound.full_volume_impulse",
+                        properties,
+                    )
+                    .unwrap_or(IMPACT_SOUND_FULL_VOLUME_IMPULSE);
+
+                    builder.add(ImpactSoundComponent {
+                        sound: Cow::Owned(sound.to_owned()),
+                        full_volume_impulse,
+                    });
+                    builder.add(ImpactSoundEventOutputComponent::construct(Default::default()));
+
+                    let active_events = collider_builder.active_events;
+                    collider_builder.active_events(active_events | ActiveEvents::CONTACT_EVENTS)
+                } else {
+                    collider_builder
+                };
+
                 builder.add(ColliderComponent::construct(collider_builder.build()));
             }
         }
         builder
     }
 
+    // Static trimesh collider built from a solid brush entity's faces
+    fn entity_solid(
+        world: &mut World,
+        entity: &EntityId,
+        properties: &Properties,
+        scale: nalgebra::Vector3<f32>,
+    ) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
+        if let Ok(true) = Self::property_bool("solid", properties) {
+            let mesh = Self::property_targetname("solid.name", properties)
+                .unwrap_or_else(|_| Self::default_entity_name(entity));
+
+            let (_, shared_shapes) = world
+                .query_mut::<&SharedShapesComponent>()
+                .into_iter()
+                .next()
+                .expect("No SharedShapesComponent");
+
+            if let Some(shape) = shared_shapes.get(&mesh) {
+                let collider_builder = ColliderBuilder::new(shape(scale));
+                builder.add(ColliderComponent::construct(
+                    Self::collider_material(collider_builder, "solid", properties).build(),
+                ));
+            }
+        }
+        builder
+    }
+
+    // Collider for a primitive collider.shape, scaled by the entity's scale,
+    // or None if the shape isn't a primitive
+    fn collider_primitive(
+        shape: &str,
+        properties: &Properties,
+        scale: nalgebra::Vector3<f32>,
+    ) -> Option<ColliderBuilder> {
+        // Upright shapes take their radius from the widest horizontal axis
+        let horizontal = scale.x.max(scale.z);
+
+        let collider_builder = match shape {
+            "ball" => {
+                let radius = Self::property_f32("collider.ball.radius", properties).unwrap();
+                ColliderBuilder::ball(radius * scale.x.max(scale.y).max(scale.z))
+            }
+            "cuboid" => {
+                let extents = Self::property_f32_3("collider.cuboid.extents", properties).unwrap();
+                ColliderBuilder::cuboid(
+                    extents.0 * scale.x,
+                    extents.1 * scale.y,
+                    extents.2 * scale.z,
+                )
+            }
+            "capsule" => {
+                let half_height =
+                    Self::property_f32("collider.capsule.half_height", properties).unwrap();
+                let radius = Self::property_f32("collider.capsule.radius", properties).unwrap();
+                ColliderBuilder::capsule_y(half_height * scale.y, radius * horizontal)
+            }
+            "cylinder" => {
+                let half_height =
+                    Self::property_f32("collider.cylinder.half_height", properties).unwrap();
+                let radius = Self::property_f32("collider.cylinder.radius", properties).unwrap();
+                ColliderBuilder::cylinder(half_height * scale.y, radius * horizontal)
+            }
+            _ => return None,
+        };
+
+        Some(collider_builder)
+    }
+
+    // Apply the material properties under `component_property` to a collider,
+    // leaving rapier's defaults in place for absent or invalid values
+    fn collider_material(
+        mut collider_builder: ColliderBuilder,
+        component_property: &str,
+        properties: &Properties,
+    ) -> ColliderBuilder {
+        let non_negative = |key: String| match Self::property_f32(&key, properties) {
+            Ok(value) if value >= 0.0 => Some(value),
+            Ok(value) => {
+                println!("Warning: {} must not be negative, got {}", key, value);
+                None
+            }
+            Err(_) => None,
+        };
+
+        if let Some(restitution) = non_negative(format!("{}.restitution", component_property)) {
+            collider_builder = collider_builder.restitution(restitution);
+        }
+
+        if let Some(friction) = non_negative(format!("{}.friction", component_property)) {
+            collider_builder = collider_builder.friction(friction);
+        }
+
+        let key = format!("{}.density", component_property);
+        match Self::property_f32(&key, properties) {
+            Ok(density) if density > 0.0 => collider_builder = collider_builder.density(density),
+            Ok(density) => println!("Warning: {} must be positive, got {}", key, density),
+            Err(_) => (),
+        }
+
+        let combine_rule = |key: String| {
+            let rule = Self::property_string(&key, properties).ok()?;
+            let rule = match rule {
+                "average" => CoefficientCombineRule::Average,
+                "min" => CoefficientCombineRule::Min,
+                "multiply" => CoefficientCombineRule::Multiply,
+                "max" => CoefficientCombineRule::Max,
+                _ => {
+                    println!("Warning: Unknown combine rule {} for {}", rule, key);
+                    return None;
+                }
+            };
+            Some(rule)
+        };
+
+        if let Some(rule) = combine_rule(format!("{}.restitution.combine", component_property)) {
+            collider_builder = collider_builder.restitution_combine_rule(rule);
+        }
+
+        if let Some(rule) = combine_rule(format!("{}.friction.combine", component_property)) {
+            collider_builder = collider_builder.friction_combine_rule(rule);
+        }
+
+        collider_builder
+    }
+
     fn entity_mover(properties: &Properties) -> EntityBuilder {
         let mut builder = EntityBuilder::new();
         if let Ok(true) = Self::property_bool("mover", properties) {
@@ -2421,68 +4058,129 @@ impl MapData {
         builder
     }
 
-    fn entity_text(
-        properties: &Properties,
-        origin: nalgebra::Vector3<f32>,
-        scale: nalgebra::Vector3<f32>,
-    ) -> Vec<EntityBuilder> {
-        let mut builders = vec![];
-        if let Ok(true) = Self::property_bool("text", properties) {
-            let string = Self::property_string("text.string", properties).unwrap();
-            let rotation = Self::property_rotation(properties, true);
+    // Timers emit their event into the event plumbing each time they elapse
+    // Timers with a missing or unknown output, or no target, are skipped
+    fn entity_timer(properties: &Properties) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
+        if let Ok(true) = Self::property_bool("timer", properties) {
+            let event = match Self::property_string("timer.out", properties) {
+                Ok("mover.open") => MoverEvent::Open,
+                Ok("mover.close") => MoverEvent::Close,
+                Ok(output) => {
+                    println!("Warning: Unknown timer output {}, skipping timer", output);
+                    return builder;
+                }
+                Err(_) => {
+                    println!("Warning: Timer has no output, skipping timer");
+                    return builder;
+                }
+            };
 
-            let lines = string
-                .split("\\n")
-                .map(|line| line.chars().collect::<Vec<_>>())
-                .collect::<Vec<_>>();
+            let target = match Self::property_target("timer.target", properties) {
+                Ok(target) => target,
+                Err(_) => {
+                    println!("Warning: Timer has no target, skipping timer");
+                    return builder;
+                }
+            };
 
-            let step = 20.0;
-            for (iy, chars) in lines.iter().enumerate() {
-                for (ix, c) in chars.iter().enumerate() {
-                    if *c == ' ' {
-                        continue;
-                    }
+            let duration = Self::property_f32("timer.duration", properties).unwrap_or(1.0);
+            let repeat = Self::property_bool("timer.repeat", properties).unwrap_or(false);
+            builder.add(TimerComponent::new(
+                Timer::new(Duration::from_secs_f32(duration.max(0.0)), repeat),
+                false,
+            ));
 
-                    let ofs = nalgebra::vector![
-                        (-step * 13.0) + ix as f32 * 20.0,
-                        (iy as f32 * -30.0),
-                        0.0
-                    ];
-                    let ofs = ofs.component_mul(&scale);
-                    let ofs = rotation * ofs;
-
-                    let key = format!("char_{}", c.to_string().as_str());
-
-                    let mut builder = EntityBuilder::new();
-                    builder.add(PositionComponent::construct(origin + ofs));
-                    builder.add(RotationComponent::construct(rotation));
-                    builder.add(ScaleComponent::construct(scale));
-                    builder.add(LineMeshInstanceComponent::construct(Cow::Owned(key)));
-                    builders.push(builder);
-                }
-            }
+            builder.add(TimerEventComponent::<MoverEvent>::construct(event));
+            builder.add(MoverEventOutputComponent::construct(Default::default()));
+            builder.add(EventTargetComponent::<MoverEvent>::construct(target.into()));
         }
-        builders
+        builder
     }
 
+    // Character instances are spawned by text_damage_system
+    fn entity_text(properties: &Properties) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
+        if let Ok(true) = Self::property_bool("text", properties) {
+            let string = Self::property_string("text.string", properties).unwrap();
+            let wrap = Self::property_usize("text.wrap", properties).ok();
+
+            // Text reads along the entity's angle rather than facing it
+            builder.add(RotationComponent::construct(Self::property_rotation(
+                properties, true,
+            )));
+            builder.add(TextComponent::new(
+                Text {
+                    string: unescape_text(string),
+                    wrap,
+                },
+                true,
+            ));
+            builder.add(TextCellsComponent::construct(Default::default()));
+        }
+        builder
+    }
+
+    // Components of the generic point and brush classnames,
+    // each enabled by a bool property named after it
+    fn assemble_builtin_components(
+        world: &mut World,
+        context: &ClassnameContext,
+        builder: &mut EntityBuilder,
+    ) -> Vec<EntityBuilder> {
+        let ClassnameContext {
+            entity,
+            properties,
+            origin,
+            scale,
+        } = *context;
+
+        builder.add_bundle(Self::entity_line_mesh_instance(entity, properties).build());
+        builder.add_bundle(Self::entity_triangle_mesh_instance(entity, properties).build());
+        builder.add_bundle(Self::entity_rigid_body(properties, origin).build());
+        // An explicit collider replaces the solid brush collider
+        builder.add_bundle(Self::entity_solid(world, entity, properties, scale).build());
+        builder.add_bundle(Self::entity_collider(world, entity, properties, scale).build());
+        builder.add_bundle(Self::entity_mover(properties).build());
+        builder.add_bundle(Self::entity_event(properties).build());
+        builder.add_bundle(Self::entity_timer(properties).build());
+        builder.add_bundle(Self::entity_text(properties).build());
+
+        vec![]
+    }
+
+    // Spawn each entity whose classname has a registered handler
     pub fn assemble_entities_game_thread(&self, world: &mut World) -> Vec<EntityBuilder> {
         let mut builders: Vec<EntityBuilder> = vec![];
 
-        // Spawn generic point entities
-        let entities = self.geo_map.entities.iter().flat_map(|entity| {
-            let properties = self.geo_map.entity_properties.get(entity)?;
-            if let Some(classname) = properties.0.iter().find(|p| p.key == "classname") {
-                if classname.value == "point" || classname.value == "brush" {
-                    Some((entity, properties))
+        for entity in self.geo_map.entities.iter() {
+            let properties = if let Some(properties) = self.geo_map.entity_properties.get(entity) {
+                properties
+            } else {
+                continue;
+            };
+
+            let classname = if let Ok(classname) = Self::property_string("classname", properties) {
+                classname
+            } else {
+                continue;
+            };
+
+            let handler = {
+                let (_, registry) = world
+                    .query_mut::<&ClassnameRegistryComponent>()
+                    .with::<ClassnameRegistry>()
+                    .into_iter()
+                    .next()
+                    .expect("No ClassnameRegistryComponent");
+
+                if let Some(definition) = registry.get(classname) {
+                    definition.handler.clone()
                 } else {
-                    None
+                    continue;
                 }
-            } else {
-                None
-            }
-        });
+            };
 
-        for (entity, properties) in entities.into_iter() {
             let origin = Self::property_origin(properties).unwrap_or_else(|| {
                 self.entity_centers
                     .get(entity)
@@ -2497,91 +4195,448 @@ impl MapData {
             builder.add(RotationComponent::construct(rotation));
             builder.add(ScaleComponent::construct(scale));
 
-            builder.add_bundle(Self::entity_line_mesh_instance(entity, properties).build());
-            builder.add_bundle(Self::entity_triangle_mesh_instance(entity, properties).build());
-            builder.add_bundle(Self::entity_rigid_body(properties).build());
-            builder.add_bundle(Self::entity_collider(world, entity, properties, scale).build());
-            builder.add_bundle(Self::entity_mover(properties).build());
-            builder.add_bundle(Self::entity_event(properties).build());
-            builders.push(builder);
+            let context = ClassnameContext {
+                entity,
+                properties,
+                origin,
+                scale,
+            };
 
-            builders.extend(Self::entity_text(properties, origin, scale));
+            let extra_builders = handler(world, &context, &mut builder);
+            builders.push(builder);
+            builders.extend(extra_builders);
         }
 
         builders
     }
 }
 
-pub fn winit_event_handler<T>(mut f: impl EventLoopHandler<T>) -> impl EventLoopHandler<T> {
-    fn prepare_schedule(world: &mut World) {
-        assemble_triangle_mesh_instances_system(world);
-        assemble_line_mesh_instances_system(world);
-
-        // parallel
-        {
-            antigen_wgpu::create_shader_modules_system(world);
-            antigen_wgpu::create_buffers_system(world);
-            antigen_wgpu::create_textures_system(world);
-            antigen_wgpu::create_texture_views_system(world);
-            antigen_wgpu::create_samplers_system(world);
+// Shape constructor for a set of convex hulls, each placed by an isometry that's scaled with them
+fn compound_hull_shape(
+    hulls: Vec<(
+        rapier3d::prelude::Isometry<f32>,
+        Vec<nalgebra::Vector3<f32>>,
+    )>,
+) -> SharedShapeFn {
+    Box::new(move |scale: nalgebra::Vector3<f32>| {
+        let mut compound = vec![];
+        for (mut isometry, convex_hull) in &hulls {
+            isometry.translation.x *= scale.x;
+            isometry.translation.y *= scale.y;
+            isometry.translation.z *= scale.z;
+
+            let mut scaled_hull = vec![];
+            for vertex in convex_hull {
+                let scaled_vert = nalgebra::vector![
+                    vertex.x * scale.x,
+                    vertex.y * scale.y,
+                    vertex.z * scale.z
+                ];
+                scaled_hull.push(rapier3d::prelude::nalgebra::Point3::new(
+                    scaled_vert.x,
+                    scaled_vert.y,
+                    scaled_vert.z,
+                ));
+            }
+            compound.push((
+                isometry,
+                SharedShape::convex_hull(&scaled_hull[..]).unwrap(),
+            ))
         }
-
-        //parallel
-        {
-            antigen_wgpu::buffer_write_system::<TotalTimeComponent>(world);
-            antigen_wgpu::buffer_write_system::<DeltaTimeComponent>(world);
-            antigen_wgpu::buffer_write_system::<PerspectiveMatrixComponent>(world);
-            antigen_wgpu::buffer_write_system::<OrthographicMatrixComponent>(world);
-            antigen_wgpu::buffer_write_slice_system::<VertexDataComponent, _>(world);
-            antigen_wgpu::buffer_write_slice_system::<TriangleIndexDataComponent, _>(world);
-            antigen_wgpu::buffer_write_slice_system::<TriangleMeshDataComponent, _>(world);
-            antigen_wgpu::buffer_write_slice_system::<TriangleMeshInstanceDataComponent, _>(world);
-            antigen_wgpu::buffer_write_slice_system::<LineVertexDataComponent, _>(world);
-            antigen_wgpu::buffer_write_slice_system::<LineIndexDataComponent, _>(world);
-            antigen_wgpu::buffer_write_slice_system::<LineMeshDataComponent, _>(world);
-            antigen_wgpu::buffer_write_slice_system::<LineMeshInstanceDataComponent, _>(world);
-            antigen_wgpu::buffer_write_slice_system::<LineInstanceDataComponent, _>(world);
-            antigen_wgpu::buffer_write_system::<PositionComponent>(world);
-            antigen_wgpu::buffer_write_system::<RotationComponent>(world);
-            antigen_wgpu::buffer_write_system::<ScaleComponent>(world);
-            antigen_wgpu::buffer_write_system::<LineMeshIdComponent>(world);
+        if compound.len() == 1 {
+            compound.remove(0).1
+        } else {
+            SharedShape::compound(compound)
         }
-        phosphor_update_beam_mesh_draw_count_system(world);
-        phosphor_update_beam_line_draw_count_system(world);
-        phosphor_prepare_system(world);
+    })
+}
+
+// Decompose a trimesh into the vertices of convex parts with VHACD,
+// dropping parts too flat to form a hull
+fn convex_decomposition(
+    vertices: &[rapier3d::prelude::nalgebra::Point3<f32>],
+    indices: &[[u32; 3]],
+    params: &VHACDParameters,
+) -> Vec<Vec<nalgebra::Vector3<f32>>> {
+    if indices.is_empty() {
+        return vec![];
     }
 
-    fn render_schedule(world: &mut World) {
-        //parallel
-        {
-            phosphor_update_total_time_system(world);
-            phosphor_update_delta_time_system(world);
+    VHACD::decompose(params, vertices, indices, true)
+        .compute_exact_convex_hulls(vertices, indices)
+        .into_iter()
+        .filter(|(hull, _)| SharedShape::convex_hull(hull).is_some())
+        .map(|(hull, _)| {
+            hull.into_iter()
+                .map(|vertex| nalgebra::vector![vertex.x, vertex.y, vertex.z])
+                .collect()
+        })
+        .collect()
+}
+
+// Trimesh of non-degenerate triangles scaled by `scale`
+//
+// Each axis is scaled by at least MIN_COLLIDER_SCALE, and triangles too small to survive scaling
+// fall back to their unscaled size, so a degenerate scale never leaves rapier an empty trimesh.
+fn scaled_trimesh(
+    vertices: &[rapier3d::prelude::nalgebra::Point3<f32>],
+    indices: &[[u32; 3]],
+    scale: nalgebra::Vector3<f32>,
+) -> SharedShape {
+    let scale = scale.map(|axis| {
+        if axis.abs() < MIN_COLLIDER_SCALE {
+            MIN_COLLIDER_SCALE.copysign(axis)
+        } else {
+            axis
         }
-        phosphor_update_oscilloscopes_system(world);
-        antigen_wgpu::create_command_encoders_system(world);
-        antigen_wgpu::draw_render_passes_system(world);
-        antigen_core::swap_with_system::<TextureViewComponent>(world);
-        antigen_core::swap_with_system::<BindGroupComponent>(world);
-        antigen_wgpu::flush_command_encoders_system(world);
-        phosphor_update_timestamp_system(world);
-        antigen_wgpu::device_poll_system(&Maintain::Wait)(world);
+    });
+
+    let scaled_vertices = vertices
+        .iter()
+        .map(|vertex| {
+            rapier3d::prelude::nalgebra::Point3::new(
+                vertex.x * scale.x,
+                vertex.y * scale.y,
+                vertex.z * scale.z,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let scaled_indices = non_degenerate_triangles(&scaled_vertices, indices);
+    if scaled_indices.is_empty() {
+        println!(
+            "Warning: Scale {:?} leaves a trimesh collider with no triangles, leaving it unscaled",
+            scale
+        );
+        return SharedShape::trimesh(vertices.to_vec(), non_degenerate_triangles(vertices, indices));
+    }
+
+    SharedShape::trimesh(scaled_vertices, scaled_indices)
+}
+
+// Drop zero-area triangles, which rapier panics on when building a trimesh
+fn non_degenerate_triangles(
+    vertices: &[rapier3d::prelude::nalgebra::Point3<f32>],
+    indices: &[[u32; 3]],
+) -> Vec<[u32; 3]> {
+    indices
+        .iter()
+        .copied()
+        .filter(|[a, b, c]| {
+            let (a, b, c) = (
+                vertices[*a as usize],
+                vertices[*b as usize],
+                vertices[*c as usize],
+            );
+            (b - a).cross(&(c - a)).norm() * 0.5 > MIN_TRIANGLE_AREA
+        })
+        .collect()
+}
+
+pub fn classname_registry_bundle() -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    builder
+        .add(ClassnameRegistry)
+        .add(ClassnameRegistryComponent::construct(Default::default()));
+    builder
+}
+
+/// Register the generic point and brush classnames
+pub fn register_builtin_classnames(world: &mut World) {
+    use PropertyType::{Bool, Float, Float3, Integer};
+
+    let point = builtin_class_properties(ClassDefinition::new(
+        ClassKind::Point,
+        "Generic point entity",
+        MapData::assemble_builtin_components,
+    ))
+    .property("line", Bool, None, "Draw an animated line")
+    .property("line.name", PropertyType::String, None, "Line mesh name")
+    .property("line.segments", Integer, Some("1"), "Line segment count")
+    .property("line.color", Float3, Some("1 1 1"), "Line color")
+    .property("line.intensity", Float, Some("1"), "Line intensity")
+    .property("line.delta_intensity", Float, Some("1"), "Line intensity decay")
+    .property("oscilloscope", Bool, None, "Animate line as an oscilloscope")
+    .property("oscilloscope.speed", Float, Some("1"), "Oscilloscope speed")
+    .property("oscilloscope.magnitude", Float, Some("1"), "Oscilloscope magnitude")
+    .property("oscilloscope.name", PropertyType::String, None, "Traced line mesh name")
+    .property("oscilloscope.name.use_targetname", Bool, None, "Name traced mesh by targetname")
+    .property("oscilloscope.phase", Float, Some("0"), "Offset added to f")
+    .property("oscilloscope.period", Float, None, "Loop f over this period")
+    .property("oscilloscope.x", PropertyType::String, None, "X expression of f")
+    .property("oscilloscope.y", PropertyType::String, None, "Y expression of f")
+    .property("oscilloscope.z", PropertyType::String, None, "Z expression of f")
+    .property("portal", Bool, None, "Draw a portal quad spanning scale x and z")
+    .property("portal.offset", Float3, Some("0 0 0"), "Offset of the view through the portal");
+
+    let brush = builtin_class_properties(ClassDefinition::new(
+        ClassKind::Brush,
+        "Generic brush entity",
+        MapData::assemble_builtin_components,
+    ))
+    .property("mesh.visual", Bool, None, "Build a visual mesh")
+    .property("mesh.visual.name", PropertyType::String, None, "Visual mesh name")
+    .property("mesh.visual.name.use_targetname", Bool, None, "Name visual mesh by targetname")
+    .property("mesh.visual.type", Integer, Some("1"), "1: Triangles, 2: Lines, 3: Both")
+    .property("mesh.visual.cull.faces", Integer, Some("0"), "Visual face cull flags")
+    .property("mesh.visual.cull.lines", Integer, Some("0"), "Visual line cull flags")
+    .property("mesh.collision", Bool, None, "Build a collision trimesh")
+    .property("mesh.collision.name", PropertyType::String, None, "Collision mesh name")
+    .property("mesh.collision.name.use_targetname", Bool, None, "Name trimesh by targetname")
+    .property("mesh.collision.cull.faces", Integer, Some("0"), "Collision face cull flags")
+    .property("convex_hull", Bool, None, "Build a convex hull shape")
+    .property("convex_hull.name", PropertyType::String, None, "Convex hull name")
+    .property("convex_hull.name.use_targetname", Bool, None, "Name hull by targetname")
+    .property(
+        "convex_hull.type",
+        PropertyType::String,
+        Some("single"),
+        "single, compound or decompose",
+    )
+    .property("convex_hull.cull.faces", Integer, Some("17"), "Decomposed hull face cull flags")
+    .property("convex_hull.decompose.resolution", Integer, Some("64"), "VHACD voxel resolution")
+    .property("convex_hull.decompose.concavity", Float, Some("0.01"), "VHACD max concavity")
+    .property("convex_hull.decompose.max_hulls", Integer, Some("1024"), "VHACD max hull count")
+    .property("solid", Bool, None, "Collide with the brush faces as static geometry")
+    .property("solid.name", PropertyType::String, None, "Solid collision mesh name")
+    .property("solid.name.use_targetname", Bool, None, "Name solid mesh by targetname")
+    .property("solid.cull.faces", Integer, Some("17"), "Solid face cull flags")
+    // Combine rules are average, min, multiply or max
+    .property("solid.restitution", Float, None, "Solid restitution")
+    .property("solid.restitution.combine", PropertyType::String, None, "Restitution rule")
+    .property("solid.friction", Float, None, "Solid friction")
+    .property("solid.friction.combine", PropertyType::String, None, "Friction rule")
+    .property("room", Bool, None, "Track the brush volume as a room");
+
+    register_classname(world, "point", point);
+    register_classname(world, "brush", brush);
+}
+
+// Properties read by MapData::assemble_builtin_components
+fn builtin_class_properties(definition: ClassDefinition) -> ClassDefinition {
+    use PropertyType::{Bool, Float, Float3, Integer};
+
+    definition
+        .property("target", PropertyType::String, None, "Target")
+        .property("targetname", PropertyType::String, None, "Name")
+        .property("angle", Float, None, "Yaw")
+        .property("mangle", Float3, None, "Pitch yaw roll")
+        .property("scale", Float3, Some("1 1 1"), "Scale")
+        .property("mesh_instance.line", Bool, None, "Instance a line mesh")
+        .property("mesh_instance.line.mesh", PropertyType::String, None, "Line mesh name")
+        .property("mesh_instance.line.mesh.use_target", Bool, None, "Use target as line mesh")
+        .property("mesh_instance.triangle", Bool, None, "Instance a triangle mesh")
+        .property("mesh_instance.triangle.mesh", PropertyType::String, None, "Triangle mesh")
+        .property("mesh_instance.triangle.mesh.use_target", Bool, None, "Use target as mesh")
+        .property("rigid_body", Bool, None, "Simulate as a rigid body")
+        .property("rigid_body.type", PropertyType::String, Some("dynamic"), "Rigid body type")
+        .property("rigid_body.linear_velocity", Float3, None, "Initial linear velocity")
+        .property("rigid_body.angular_velocity", Float3, None, "Initial angular velocity")
+        .property("rigid_body.gravity_scale", Float, Some("1.0"), "Gravity multiplier")
+        .property("collider", Bool, None, "Add a collider")
+        .property("collider.shape", PropertyType::String, None, "Collider shape")
+        .property("collider.ball.radius", Float, None, "Ball radius")
+        .property("collider.cuboid.extents", Float3, None, "Cuboid half extents")
+        .property("collider.capsule.half_height", Float, None, "Capsule vertical half height")
+        .property("collider.capsule.radius", Float, None, "Capsule radius")
+        .property("collider.cylinder.half_height", Float, None, "Cylinder vertical half height")
+        .property("collider.cylinder.radius", Float, None, "Cylinder radius")
+        .property("collider.convex_hull.mesh", PropertyType::String, None, "Convex hull name")
+        .property("collider.convex_hull.mesh.use_target", Bool, None, "Use target as hull")
+        .property("collider.trimesh.mesh", PropertyType::String, None, "Trimesh name")
+        .property("collider.trimesh.mesh.use_target", Bool, None, "Use target as trimesh")
+        .property("collider.restitution", Float, None, "Restitution")
+        // Combine rules are average, min, multiply or max
+        .property("collider.restitution.combine", PropertyType::String, None, "Restitution rule")
+        .property("collider.friction", Float, None, "Friction")
+        .property("collider.friction.combine", PropertyType::String, None, "Friction rule")
+        .property("collider.density", Float, None, "Density, must be positive")
+        .property("collider.type", PropertyType::String, Some("solid"), "solid or sensor")
+        .property("collider.events.active", Integer, Some("0"), "1: Contact, 2: Intersection")
+        .property("collider.events.target", PropertyType::String, None, "Event target")
+        .property("collider.events.target.use_target", Bool, None, "Use target as event target")
+        .property("collider.impact_sound", PropertyType::String, None, "Impact sound name")
+        .property(
+            "collider.impact_sound.full_volume_impulse",
+            Float,
+            None,
+            "Impulse for a full volume impact sound",
+        )
+        .property("mover", Bool, None, "Move between offsets")
+        .property("mover.offset.position", Float3, None, "Position offset")
+        .property("mover.offset.rotation", Float3, None, "Rotation offset")
+        .property("mover.speed", Float, None, "Mover speed")
+        .property("mover.open", Bool, None, "Start open")
+        .property("mover.events", Bool, None, "Receive mover events")
+        .property("mover.name", PropertyType::String, None, "Mover name")
+        .property("mover.name.use_targetname", Bool, None, "Use targetname as mover name")
+        .property("event", Bool, None, "Transform events")
+        .property("event.in", PropertyType::String, None, "Input event")
+        .property("event.out", PropertyType::String, None, "Output event")
+        .property("event.target", PropertyType::String, None, "Event target")
+        .property("event.target.use_target", Bool, None, "Use target as event target")
+        .property("event.name", PropertyType::String, None, "Event name")
+        .property("event.name.use_targetname", Bool, None, "Use targetname as event name")
+        .property("timer", Bool, None, "Output an event when a timer elapses")
+        .property("timer.duration", Float, Some("1"), "Timer duration in seconds")
+        .property("timer.repeat", Bool, None, "Restart the timer after it elapses")
+        .property("timer.out", PropertyType::String, None, "mover.open or mover.close")
+        .property("timer.target", PropertyType::String, None, "Event target")
+        .property("timer.target.use_target", Bool, None, "Use target as event target")
+        .property("text", Bool, None, "Spawn text")
+        .property("text.string", PropertyType::String, None, "Text, with \\n for line breaks")
+        .property("text.wrap", Integer, None, "Column to wrap lines at")
+}
+
+/// Register a class to assemble map entities of the given classname,
+/// replacing any existing definition
+pub fn register_classname(world: &mut World, classname: &str, definition: ClassDefinition) {
+    let (_, registry) = world
+        .query_mut::<&mut ClassnameRegistryComponent>()
+        .with::<ClassnameRegistry>()
+        .into_iter()
+        .next()
+        .expect("No ClassnameRegistryComponent");
+
+    registry.insert(classname.to_owned(), definition);
+}
+
+/// Generate an FGD for the generic point and brush classnames
+pub fn builtin_fgd() -> String {
+    let mut world = World::new();
+    world.spawn(classname_registry_bundle().build());
+    register_builtin_classnames(&mut world);
+
+    let (_, registry) = world
+        .query_mut::<&ClassnameRegistryComponent>()
+        .into_iter()
+        .next()
+        .unwrap();
+
+    export_fgd(registry)
+}
+
+// Create resources, write buffers and prepare bind groups for the next frame
+fn prepare_schedule(world: &mut World) {
+    spawn_camera_at_player_start_system(world);
+    current_room_system(world);
+    assemble_triangle_mesh_instances_system(world);
+    assemble_line_mesh_instances_system(world);
+    phosphor_update_uniform_data_system(world);
+    phosphor_update_portals_system(world);
+    phosphor_portal_mesh_passes_system(world);
+
+    // parallel
+    {
+        antigen_wgpu::create_shader_modules_system(world);
+        antigen_wgpu::create_buffers_system(world);
+        antigen_wgpu::create_textures_system(world);
+        antigen_wgpu::create_texture_views_system(world);
+        antigen_wgpu::create_samplers_system(world);
     }
 
+    //parallel
+    {
+        antigen_wgpu::staging_belt_write_struct_system::<UniformData>(world);
+        antigen_wgpu::buffer_write_slice_system::<VertexDataComponent, _>(world);
+        antigen_wgpu::buffer_write_slice_system::<TriangleIndexDataComponent, _>(world);
+        antigen_wgpu::buffer_write_slice_system::<TriangleMeshDataComponent, _>(world);
+        antigen_wgpu::buffer_write_slice_system::<TriangleMeshInstanceDataComponent, _>(world);
+        antigen_wgpu::buffer_write_slice_system::<TriangleMeshBoundsDataComponent, _>(world);
+        antigen_wgpu::buffer_write_slice_system::<LineVertexDataComponent, _>(world);
+        antigen_wgpu::buffer_write_slice_system::<LineIndexDataComponent, _>(world);
+        antigen_wgpu::buffer_write_slice_system::<LineMeshDataComponent, _>(world);
+        antigen_wgpu::buffer_write_slice_system::<LineMeshInstanceDataComponent, _>(world);
+        antigen_wgpu::buffer_write_slice_system::<LineInstanceDataComponent, _>(world);
+        antigen_wgpu::buffer_write_slice_system::<PortalInstanceDataComponent, _>(world);
+        antigen_wgpu::buffer_write_system::<PositionComponent>(world);
+        antigen_wgpu::buffer_write_system::<RotationComponent>(world);
+        antigen_wgpu::buffer_write_system::<ScaleComponent>(world);
+        antigen_wgpu::buffer_write_system::<LineColorComponent>(world);
+        antigen_wgpu::buffer_write_system::<LineIntensityComponent>(world);
+        antigen_wgpu::buffer_write_system::<LineMeshIdComponent>(world);
+        antigen_wgpu::buffer_write_system::<LineSmearUniformData>(world);
+        antigen_wgpu::texture_write_slice_system::<ColorLutComponent, _>(world);
+    }
+    phosphor_update_beam_mesh_draw_count_system(world);
+    phosphor_update_beam_line_draw_count_system(world);
+    phosphor_update_beam_mesh_instance_offsets_system(world);
+    phosphor_update_frustum_cull_dispatch_system(world);
+    phosphor_update_line_smear_system(world);
+    antigen_wgpu::growable_buffer_bind_groups_system(world);
+    antigen_wgpu::render_pass_bind_group_offsets_system(world);
+    antigen_wgpu::render_pass_background_system(world);
+    phosphor_skybox_pass_system(world);
+    phosphor_prepare_system(world);
+}
+
+// Encode render passes and swap phosphor buffers
+fn render_schedule(world: &mut World) {
+    //parallel
+    {
+        phosphor_update_total_time_system(world);
+        phosphor_update_delta_time_system(world);
+    }
+    phosphor_update_oscilloscopes_system(world);
+    antigen_wgpu::create_command_encoders_system(world);
+    antigen_wgpu::encode_passes_system(world);
+    antigen_core::swap_with_system::<TextureViewComponent>(world);
+    antigen_core::swap_with_system::<BindGroupComponent>(world);
+    antigen_wgpu::flush_command_encoders_system(world);
+    phosphor_update_timestamp_system(world);
+    antigen_wgpu::device_poll_system(&Maintain::Wait)(world);
+}
+
+/// Run one frame of the phosphor renderer without a winit event loop,
+/// for use with an offscreen target
+pub fn headless_frame_schedule(world: &mut World, channel: &WorldChannel) {
+    map_reload_system(world, channel);
+    phosphor_resize_system(world);
+    antigen_wgpu::offscreen_target_resize_system(world);
+    antigen_wgpu::create_staging_belts_system(world);
+    prepare_schedule(world);
+    phosphor_camera_position_system(world, channel);
+    antigen_wgpu::staging_belt_flush_system(world);
+    antigen_wgpu::staging_belt_finish_system(world);
+    antigen_wgpu::reset_surface_config_changed_system(world);
+
+    render_schedule(world);
+    antigen_wgpu::submit_command_buffers_system(world);
+    antigen_wgpu::staging_belt_recall_system(world);
+}
+
+/// Returns true once every phosphor pipeline has been created
+pub fn phosphor_pipelines_ready(world: &World) -> bool {
+    world
+        .query::<&RenderPipelineComponent>()
+        .into_iter()
+        .all(|(_, pipeline)| pipeline.is_ready())
+}
+
+pub fn winit_event_handler<T>(mut f: impl EventLoopHandler<T>) -> impl EventLoopHandler<T> {
     move |world: &mut World,
           channel: &WorldChannel,
           event: Event<'static, T>,
           event_loop_window_target: &EventLoopWindowTarget<T>,
           control_flow: &mut ControlFlow| {
+        // While paused, the last presented frame remains on screen
+        // and input continues to be processed
+        let paused = antigen_core::is_paused(world);
+
         match &event {
-            Event::MainEventsCleared => {
+            Event::MainEventsCleared if !paused => {
+                map_reload_system(world, channel);
                 phosphor_resize_system(world);
                 prepare_schedule(world);
-                phosphor_camera_position_system(world);
+                phosphor_camera_position_system(world, channel);
             }
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::Resized(_) => {
                     phosphor_resize_system(world);
                 }
+                WindowEvent::MouseInput { state, button, .. } => {
+                    phosphor_mouse_button_event_system(world, *button, *state)
+                }
                 //WindowEvent::CursorMoved { .. } => phosphor_cursor_moved_system(world),
                 _ => (),
             },
@@ -2590,7 +4645,7 @@ pub fn winit_event_handler<T>(mut f: impl EventLoopHandler<T>) -> impl EventLoop
                 DeviceEvent::Key(key) => phosphor_key_event_system(world, *key),
                 _ => (),
             },
-            Event::RedrawEventsCleared => {
+            Event::RedrawEventsCleared if !paused => {
                 render_schedule(world);
             }
             _ => (),
@@ -2605,3 +4660,369 @@ pub fn winit_event_handler<T>(mut f: impl EventLoopHandler<T>) -> impl EventLoop
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use antigen_fs::FileStringComponent;
+    use winit::event::VirtualKeyCode;
+    use rapier3d::prelude::nalgebra::Point3;
+
+    #[test]
+    fn non_degenerate_triangles_drops_zero_area() {
+        let vertices = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+        ];
+
+        // Valid, repeated index, collinear
+        let indices = [[0, 1, 2], [0, 0, 2], [0, 1, 3]];
+
+        assert_eq!(non_degenerate_triangles(&vertices, &indices), [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn scaled_trimesh_survives_degenerate_scale() {
+        let vertices = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(64.0, 0.0, 0.0),
+            Point3::new(0.0, 64.0, 0.0),
+        ];
+        let indices = [[0, 1, 2]];
+
+        let shape = scaled_trimesh(&vertices, &indices, nalgebra::Vector3::zeros());
+        assert_eq!(shape.as_trimesh().unwrap().indices(), &indices);
+
+        // Triangles that scaling would make degenerate keep their unscaled size
+        let tiny = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.01, 0.0, 0.0),
+            Point3::new(0.0, 0.01, 0.0),
+        ];
+        let shape = scaled_trimesh(&tiny, &indices, nalgebra::Vector3::zeros());
+        assert_eq!(shape.as_trimesh().unwrap().vertices(), &tiny);
+    }
+
+    #[test]
+    fn convex_decomposition_splits_concave_meshes() {
+        // L-shaped prism, with caps fanned from the inner corner
+        let outline = [(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)];
+        let vertices = [0.0, 1.0]
+            .into_iter()
+            .flat_map(|y| outline.map(|(x, z)| Point3::new(x, y, z)))
+            .collect::<Vec<_>>();
+
+        let mut indices = vec![];
+        for i in 0..6 {
+            let j = (i + 1) % 6;
+            indices.push([i, j, j + 6]);
+            indices.push([i, j + 6, i + 6]);
+        }
+        for [a, b] in [[4, 5], [5, 0], [0, 1], [1, 2]] {
+            indices.push([3, b, a]);
+            indices.push([9, a + 6, b + 6]);
+        }
+
+        let hulls = convex_decomposition(&vertices, &indices, &VHACDParameters::default());
+        assert!(hulls.len() > 1);
+        assert!(hulls
+            .iter()
+            .flatten()
+            .all(|v| (-0.1..=2.1).contains(&v.x) && (-0.1..=1.1).contains(&v.y)));
+
+        assert!(convex_decomposition(&vertices, &[], &VHACDParameters::default()).is_empty());
+    }
+
+    // Axis-aligned box from min to max
+    fn box_hull(min: nalgebra::Vector3<f32>, max: nalgebra::Vector3<f32>) -> ConvexHull {
+        ConvexHull::from([
+            Plane3d { n: nalgebra::Vector3::x(), d: max.x },
+            Plane3d { n: nalgebra::Vector3::y(), d: max.y },
+            Plane3d { n: nalgebra::Vector3::z(), d: max.z },
+            Plane3d { n: -nalgebra::Vector3::x(), d: -min.x },
+            Plane3d { n: -nalgebra::Vector3::y(), d: -min.y },
+            Plane3d { n: -nalgebra::Vector3::z(), d: -min.z },
+        ])
+    }
+
+    #[test]
+    fn current_room_prefers_containing_then_nearest() {
+        let large = [box_hull(nalgebra::vector![0.0, 0.0, 0.0], nalgebra::vector![8.0, 2.0, 2.0])];
+        let small = [box_hull(nalgebra::vector![4.0, 0.0, 0.0], nalgebra::vector![6.0, 2.0, 2.0])];
+        let large_center = nalgebra::vector![4.0, 1.0, 1.0];
+        let small_center = nalgebra::vector![5.0, 1.0, 1.0];
+
+        let rooms = || [(0, &large[..], &large_center), (1, &small[..], &small_center)];
+        let room_at =
+            |x: f32| containing_or_nearest_room(&nalgebra::vector![x, 1.0, 1.0], rooms());
+
+        assert_eq!(room_at(1.0), Some(0));
+        // Overlapping rooms pick the closest center
+        assert_eq!(room_at(5.5), Some(1));
+        // Outside every room picks the closest center
+        assert_eq!(room_at(20.0), Some(1));
+        assert_eq!(containing_or_nearest_room::<usize>(&nalgebra::Vector3::zeros(), []), None);
+    }
+    // Entity properties from key-value pairs
+    fn properties(pairs: &[(&str, &str)]) -> Properties {
+        Properties::new(
+            pairs
+                .iter()
+                .map(|(key, value)| antigen_shambler::shambler::shalrath::repr::Property {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn expression_properties_reject_unknown_names() {
+        let properties = properties(&[
+            ("valid", "sin(f) * 2"),
+            ("unknown_function", "sinn(f)"),
+            ("unknown_variable", "sin(g)"),
+        ]);
+        let functions = FunctionRegistry::default();
+        let expression =
+            |key| MapData::property_expression_f32(key, &properties, &["f"], &functions);
+
+        assert!(expression("valid").is_ok());
+        assert_eq!(
+            expression("unknown_function").unwrap_err().to_string(),
+            "Unknown function \"sinn\" in unknown_function expression \"sinn(f)\""
+        );
+        assert!(expression("unknown_variable").is_err());
+        assert!(expression("missing").is_err());
+    }
+
+    #[test]
+    fn collider_primitive_parses_capsule_and_cylinder() {
+        let scale = nalgebra::vector![2.0, 3.0, 0.5];
+
+        let properties = properties(&[
+            ("collider.capsule.half_height", "1"),
+            ("collider.capsule.radius", "0.5"),
+            ("collider.cylinder.half_height", "2"),
+            ("collider.cylinder.radius", "0.25"),
+        ]);
+        let shape = |shape| {
+            MapData::collider_primitive(shape, &properties, scale)
+                .map(|collider_builder| collider_builder.build().shared_shape().clone())
+        };
+
+        let capsule = shape("capsule").unwrap();
+        let capsule = capsule.as_capsule().unwrap();
+        assert_eq!(capsule.half_height(), 3.0);
+        assert_eq!(capsule.radius, 1.0);
+
+        let cylinder = shape("cylinder").unwrap();
+        let cylinder = cylinder.as_cylinder().unwrap();
+        assert_eq!(cylinder.half_height, 6.0);
+        assert_eq!(cylinder.radius, 0.5);
+
+        assert!(shape("trimesh").is_none());
+    }
+
+    #[test]
+    fn collider_material_applies_valid_properties() {
+        let collider = |pairs| {
+            MapData::collider_material(ColliderBuilder::ball(1.0), "collider", &properties(pairs))
+                .build()
+        };
+
+        let ice = collider(&[("collider.friction", "0.02"), ("collider.friction.combine", "min")]);
+        assert_eq!(ice.friction(), 0.02);
+        assert_eq!(ice.friction_combine_rule(), CoefficientCombineRule::Min);
+
+        let ball = collider(&[("collider.restitution", "0.9"), ("collider.density", "2")]);
+        assert_eq!(ball.restitution(), 0.9);
+        assert_eq!(ball.density(), Some(2.0));
+
+        // Invalid values keep the defaults
+        let default = ColliderBuilder::ball(1.0).build();
+        let invalid = collider(&[("collider.density", "0"), ("collider.friction", "-1")]);
+        assert_eq!(invalid.density(), default.density());
+        assert_eq!(invalid.friction(), default.friction());
+    }
+
+    #[test]
+    fn oscilloscopes_skip_existing_line_meshes() {
+        let mut world = World::new();
+        world.spawn((LineMeshIds, LineMeshIdsComponent::default()));
+        register_line_mesh_id(&mut world, "shared".into(), (0, 1));
+
+        // Tracing another entity's line would animate all of its instances in lockstep
+        let mut builder = EntityBuilder::new();
+        let oscilloscopes = MapData::entity_oscilloscope(
+            &mut world,
+            &EntityId(0),
+            &properties(&[("oscilloscope", "true"), ("oscilloscope.name", "shared")]),
+            &mut builder,
+        );
+        assert!(oscilloscopes.is_empty());
+
+        let entity = world.spawn(builder.build());
+        assert!(world.get::<Oscilloscope>(entity).is_err());
+    }
+
+    #[test]
+    fn collider_impact_sound_emits_on_contact() {
+        let mut world = World::new();
+        let mut builder =
+            antigen_rapier3d::physics_backend_builder(nalgebra::vector![0.0, -9.81, 0.0]);
+        builder.add(antigen_rapier3d::ImpactThresholdComponent::construct(1.0));
+        world.spawn(builder.build());
+
+        // Floor with an impact sound, driven by its collider's contact events
+        let mut builder = MapData::entity_collider(
+            &mut world,
+            &EntityId(0),
+            &properties(&[
+                ("collider", "true"),
+                ("collider.shape", "cuboid"),
+                ("collider.cuboid.extents", "10 0.5 10"),
+                ("collider.impact_sound", "thud"),
+                ("collider.impact_sound.full_volume_impulse", "1"),
+            ]),
+            nalgebra::vector![1.0, 1.0, 1.0],
+        );
+        builder.add(RigidBodyComponent::construct(RigidBodyBuilder::new_static().build()));
+        let floor = world.spawn(builder.build());
+
+        let mut builder = EntityBuilder::new();
+        builder.add(PositionComponent::construct(nalgebra::vector![0.0, 1.5, 0.0]));
+        builder.add(LinearVelocityComponent::construct(nalgebra::vector![0.0, -5.0, 0.0]));
+        builder.add(RigidBodyComponent::construct(RigidBodyBuilder::new_dynamic().build()));
+        builder.add(ColliderComponent::construct(
+            ColliderBuilder::cuboid(0.5, 0.5, 0.5).density(100.0).build(),
+        ));
+        world.spawn(builder.build());
+
+        antigen_rapier3d::insert_rigid_bodies_system(&mut world);
+        antigen_rapier3d::insert_colliders_system(&mut world);
+
+        for _ in 0..30 {
+            antigen_rapier3d::step_physics_system(&mut world);
+            antigen_rapier3d::collect_impact_events_system(&mut world);
+            impact_sound_event_output_system(&mut world);
+            antigen_rapier3d::clear_physics_event_collector_system(&mut world);
+        }
+
+        // The box landing is heard once, and resting contact stays quiet
+        let output = world.get::<ImpactSoundEventOutputComponent>(floor).unwrap();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].sound, "thud");
+        assert!(output[0].volume > 0.0 && output[0].volume <= 1.0);
+    }
+
+    #[test]
+    fn entity_timer_skips_invalid_timers() {
+        let mut world = World::new();
+        let mut has_timer = |extra: &[(&str, &str)]| {
+            let mut pairs = vec![("timer", "true")];
+            pairs.extend_from_slice(extra);
+            let entity = world.spawn(MapData::entity_timer(&properties(&pairs)).build());
+            world.get::<TimerComponent>(entity).is_ok()
+        };
+
+        assert!(has_timer(&[("timer.out", "mover.open"), ("timer.target", "door")]));
+        assert!(!has_timer(&[("timer.target", "door")]));
+        assert!(!has_timer(&[("timer.out", "mover.jump"), ("timer.target", "door")]));
+        assert!(!has_timer(&[("timer.out", "mover.close")]));
+    }
+
+    #[test]
+    fn watched_input_bindings_reload() {
+        let path = std::env::temp_dir().join("antigen-sandbox-input-bindings.ron");
+        std::fs::write(&path, "{ Key(P): Pause }").unwrap();
+
+        let mut exchange = antigen_core::WorldExchange::default();
+        let fs_channel = exchange.create_channel::<Filesystem>();
+        let render_channel = exchange.create_channel::<Render>();
+        exchange.spawn();
+
+        let mut fs_world = World::new();
+        let mut render_world = World::new();
+        let renderer = render_world.spawn(());
+
+        let mut received_binding = || {
+            antigen_core::receive_messages_timeout(
+                &mut render_world,
+                &render_channel,
+                Duration::from_secs(5),
+            )
+            .unwrap();
+            let bindings = render_world.get::<InputBindingsComponent>(renderer).unwrap();
+            bindings.get(&PhysicalInput::Key(VirtualKeyCode::P)).copied()
+        };
+
+        load_input_bindings_message(path.as_path(), renderer)((&mut fs_world, &fs_channel))
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(received_binding(), Some(InputAction::Pause));
+
+        // Edits picked up by the file watcher replace the render thread's bindings
+        for (_, (string, watcher)) in
+            fs_world.query_mut::<(&mut FileStringComponent, &FileWatcherComponent)>()
+        {
+            **string = "{ Key(P): DebugColliders }".into();
+            watcher.set_changed(true);
+        }
+        reload_watched_input_bindings(&mut fs_world, &fs_channel);
+        assert_eq!(received_binding(), Some(InputAction::DebugColliders));
+    }
+
+    #[test]
+    fn unescape_text_expands_newlines_and_hex() {
+        assert_eq!(unescape_text("a\\nb\\x01ff0000c\\x02"), "a\nb\u{1}ff0000c\u{2}");
+        // Malformed escapes are left as-is
+        assert_eq!(unescape_text("\\xg1\\x1\\"), "\\xg1\\x1\\");
+    }
+
+    #[test]
+    fn loader_property_keys_are_declared_in_fgd() {
+        // Keys every FGD entity has implicitly
+        const IMPLICIT_KEYS: [&str; 2] = ["classname", "origin"];
+
+        let source = include_str!("mod.rs");
+        let source = &source[..source.find("#[cfg(test)]\nmod tests").unwrap()];
+        let fgd = builtin_fgd();
+
+        let mut checked = 0;
+        for (i, _) in source.match_indices("property_") {
+            let rest = &source[i + "property_".len()..];
+            let call = match rest.find('(') {
+                Some(call) => call,
+                None => continue,
+            };
+            if !rest[..call].chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                continue;
+            }
+
+            let args = rest[call + 1..].trim_start();
+            let key = match args
+                .strip_prefix('"')
+                .and_then(|args| args.split('"').next())
+            {
+                Some(key) => key,
+                None => continue,
+            };
+
+            checked += 1;
+            if IMPLICIT_KEYS.contains(&key) {
+                continue;
+            }
+
+            assert!(
+                fgd.contains(&format!("\t{}(", key)),
+                "Property {:?} is read by the map loader but not declared in the FGD",
+                key
+            );
+        }
+
+        assert!(checked > 0);
+    }
+}
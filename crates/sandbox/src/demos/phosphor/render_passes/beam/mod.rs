@@ -1,16 +1,15 @@
 use antigen_wgpu::{
-    buffer_size_of,
     wgpu::{
         BlendComponent, BlendFactor, BlendOperation, BlendState, ColorTargetState, ColorWrites,
         CompareFunction, DepthBiasState, DepthStencilState, Face, FragmentState, FrontFace,
         MultisampleState, PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology,
-        RenderPipelineDescriptor, StencilState, TextureFormat, VertexAttribute, VertexBufferLayout,
-        VertexFormat, VertexState, VertexStepMode,
+        RenderPipelineDescriptor, StencilState, VertexState, VertexStepMode,
     },
     BindGroupLayoutComponent, DeviceComponent, RenderPipelineComponent, ShaderModuleComponent,
+    VertexLayout,
 };
 
-use crate::demos::phosphor::{LineVertexData, VertexData, HDR_TEXTURE_FORMAT};
+use crate::demos::phosphor::{LineVertexData, VertexData, DEPTH_TEXTURE_FORMAT, HDR_TEXTURE_FORMAT};
 
 pub fn phosphor_prepare_beam_clear(
     device: &DeviceComponent,
@@ -44,7 +43,7 @@ pub fn phosphor_prepare_beam_clear(
                 ..Default::default()
             },
             depth_stencil: Some(DepthStencilState {
-                format: TextureFormat::Depth32Float,
+                format: DEPTH_TEXTURE_FORMAT,
                 depth_write_enabled: true,
                 depth_compare: CompareFunction::Greater,
                 stencil: StencilState::default(),
@@ -68,6 +67,7 @@ pub fn phosphor_prepare_beam_mesh(
     uniform_bind_group_layout: &BindGroupLayoutComponent,
     storage_bind_group_layout: &BindGroupLayoutComponent,
     beam_shader: &ShaderModuleComponent,
+    stencil: StencilState,
     beam_mesh_pipeline: &mut RenderPipelineComponent,
 ) -> Option<()> {
     let uniform_bind_group_layout = uniform_bind_group_layout.get()?;
@@ -87,37 +87,7 @@ pub fn phosphor_prepare_beam_mesh(
             vertex: VertexState {
                 module: &beam_mesh_shader,
                 entry_point: "vs_triangle",
-                buffers: &[VertexBufferLayout {
-                    array_stride: buffer_size_of::<VertexData>(),
-                    step_mode: VertexStepMode::Vertex,
-                    attributes: &[
-                        VertexAttribute {
-                            format: VertexFormat::Float32x3,
-                            offset: 0,
-                            shader_location: 0,
-                        },
-                        VertexAttribute {
-                            format: VertexFormat::Float32x3,
-                            offset: buffer_size_of::<[f32; 3]>(),
-                            shader_location: 1,
-                        },
-                        VertexAttribute {
-                            format: VertexFormat::Float32x3,
-                            offset: buffer_size_of::<[f32; 6]>(),
-                            shader_location: 2,
-                        },
-                        VertexAttribute {
-                            format: VertexFormat::Float32,
-                            offset: buffer_size_of::<[f32; 9]>(),
-                            shader_location: 3,
-                        },
-                        VertexAttribute {
-                            format: VertexFormat::Float32,
-                            offset: buffer_size_of::<[f32; 10]>(),
-                            shader_location: 4,
-                        },
-                    ],
-                }],
+                buffers: &[VertexData::vertex_buffer_layout(VertexStepMode::Vertex)],
             },
             fragment: Some(FragmentState {
                 module: &beam_mesh_shader,
@@ -131,10 +101,10 @@ pub fn phosphor_prepare_beam_mesh(
                 ..Default::default()
             },
             depth_stencil: Some(DepthStencilState {
-                format: TextureFormat::Depth32Float,
+                format: DEPTH_TEXTURE_FORMAT,
                 depth_write_enabled: true,
                 depth_compare: CompareFunction::Greater,
-                stencil: StencilState::default(),
+                stencil,
                 bias: DepthBiasState::default(),
             }),
             multisample: MultisampleState {
@@ -155,6 +125,7 @@ pub fn phosphor_prepare_beam_line(
     uniform_bind_group_layout: &BindGroupLayoutComponent,
     storage_bind_group_layout: &BindGroupLayoutComponent,
     beam_line_shader: &ShaderModuleComponent,
+    stencil: StencilState,
     beam_line_pipeline: &mut RenderPipelineComponent,
 ) -> Option<()> {
     let uniform_bind_group_layout = uniform_bind_group_layout.get()?;
@@ -174,22 +145,7 @@ pub fn phosphor_prepare_beam_line(
             vertex: VertexState {
                 module: &beam_line_shader,
                 entry_point: "vs_line",
-                buffers: &[VertexBufferLayout {
-                    array_stride: buffer_size_of::<LineVertexData>(),
-                    step_mode: VertexStepMode::Vertex,
-                    attributes: &[
-                        VertexAttribute {
-                            format: VertexFormat::Float32x3,
-                            offset: 0,
-                            shader_location: 0,
-                        },
-                        VertexAttribute {
-                            format: VertexFormat::Float32,
-                            offset: buffer_size_of::<[f32; 3]>(),
-                            shader_location: 1,
-                        },
-                    ],
-                }],
+                buffers: &[LineVertexData::vertex_buffer_layout(VertexStepMode::Vertex)],
             },
             fragment: Some(FragmentState {
                 module: &beam_line_shader,
@@ -214,10 +170,10 @@ pub fn phosphor_prepare_beam_line(
                 ..Default::default()
             },
             depth_stencil: Some(DepthStencilState {
-                format: TextureFormat::Depth32Float,
+                format: DEPTH_TEXTURE_FORMAT,
                 depth_write_enabled: false,
                 depth_compare: CompareFunction::Greater,
-                stencil: StencilState::default(),
+                stencil,
                 bias: DepthBiasState::default(),
             }),
             multisample: MultisampleState {
@@ -0,0 +1,195 @@
+use crate::demos::phosphor::{BloomLevelComponent, BLOOM_LEVELS, HDR_TEXTURE_FORMAT};
+
+use antigen_wgpu::{
+    wgpu::{
+        BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+        BindGroupLayoutEntry, BindingResource, BindingType, FragmentState, MultisampleState,
+        PipelineLayoutDescriptor, PrimitiveState, RenderPipelineDescriptor, Sampler, ShaderStages,
+        TextureSampleType, TextureViewDimension, VertexState,
+    },
+    BindGroupComponent, BindGroupLayoutComponent, DeviceComponent, RenderPipelineComponent,
+    SamplerComponent, ShaderModuleComponent, TextureViewComponent,
+};
+
+// Create the bind group used to sample a bloom buffer in the next pass of the chain,
+// with the unused second texture aliasing the first
+fn phosphor_prepare_bloom_source_bind_group(
+    device: &DeviceComponent,
+    phosphor_bind_group_layout: &BindGroupLayout,
+    linear_sampler: &Sampler,
+    view: &TextureViewComponent,
+    bind_group: &mut BindGroupComponent,
+) -> Option<()> {
+    if !bind_group.is_pending() {
+        return Some(());
+    }
+
+    let view = view.get()?;
+
+    let source_bind_group = device.create_bind_group(&BindGroupDescriptor {
+        layout: phosphor_bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(view),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::Sampler(linear_sampler),
+            },
+        ],
+        label: None,
+    });
+    bind_group.set_ready_with(source_bind_group);
+
+    Some(())
+}
+
+// Per-buffer components consumed by phosphor_prepare_bloom_bind_groups
+pub type BloomBufferQuery<'a> = (
+    &'a BloomLevelComponent,
+    &'a TextureViewComponent,
+    &'a mut BindGroupComponent,
+);
+
+/// Create source bind groups for each bloom buffer,
+/// and the bind group that exposes the blurred mip chain to the tonemap pass
+pub fn phosphor_prepare_bloom_bind_groups(
+    device: &DeviceComponent,
+    phosphor_bind_group_layout: &BindGroupLayoutComponent,
+    bloom_bind_group_layout: &mut BindGroupLayoutComponent,
+    bloom_bind_group: &mut BindGroupComponent,
+    linear_sampler: &SamplerComponent,
+    downsample_buffers: &mut [BloomBufferQuery],
+    blur_buffers: &mut [BloomBufferQuery],
+) -> Option<()> {
+    let phosphor_bind_group_layout = phosphor_bind_group_layout.get()?;
+    let linear_sampler = linear_sampler.get()?;
+
+    for (_, view, bind_group) in downsample_buffers.iter_mut() {
+        phosphor_prepare_bloom_source_bind_group(
+            device,
+            phosphor_bind_group_layout,
+            linear_sampler,
+            view,
+            bind_group,
+        )?;
+    }
+
+    for (_, view, bind_group) in blur_buffers.iter_mut() {
+        phosphor_prepare_bloom_source_bind_group(
+            device,
+            phosphor_bind_group_layout,
+            linear_sampler,
+            view,
+            bind_group,
+        )?;
+    }
+
+    // Mip chain bind group
+    let bloom_bind_group_layout = if let Some(bind_group_layout) = bloom_bind_group_layout.get() {
+        bind_group_layout
+    } else {
+        let entries = (0..BLOOM_LEVELS as u32)
+            .map(|binding| BindGroupLayoutEntry {
+                binding,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            })
+            .collect::<Vec<_>>();
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Bloom Bind Group Layout"),
+            entries: &entries,
+        });
+
+        bloom_bind_group_layout.set_ready_with(bind_group_layout);
+        bloom_bind_group_layout.get().unwrap()
+    };
+
+    if bloom_bind_group.is_pending() {
+        blur_buffers.sort_by_key(|(level, _, _)| ***level);
+
+        let views = blur_buffers
+            .iter()
+            .map(|(_, view, _)| view.get())
+            .collect::<Option<Vec<_>>>()?;
+
+        let entries = views
+            .iter()
+            .enumerate()
+            .map(|(binding, view)| BindGroupEntry {
+                binding: binding as u32,
+                resource: BindingResource::TextureView(view),
+            })
+            .collect::<Vec<_>>();
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: bloom_bind_group_layout,
+            entries: &entries,
+            label: Some("Bloom Bind Group"),
+        });
+        bloom_bind_group.set_ready_with(bind_group);
+    }
+
+    Some(())
+}
+
+pub fn phosphor_prepare_bloom_pipelines(
+    device: &DeviceComponent,
+    phosphor_bind_group_layout: &BindGroupLayoutComponent,
+    bloom_shader: &ShaderModuleComponent,
+    downsample_pipeline: &mut RenderPipelineComponent,
+    blur_pipeline: &mut RenderPipelineComponent,
+) -> Option<()> {
+    let phosphor_bind_group_layout = phosphor_bind_group_layout.get()?;
+    let bloom_shader = bloom_shader.get()?;
+
+    for (pipeline, label, entry_point) in [
+        (downsample_pipeline, "Bloom Downsample", "fs_downsample"),
+        (blur_pipeline, "Bloom Blur", "fs_blur"),
+    ] {
+        if !pipeline.is_pending() {
+            continue;
+        }
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[phosphor_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        println!("Creating {} pipeline", label);
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: bloom_shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: bloom_shader,
+                entry_point,
+                targets: &[HDR_TEXTURE_FORMAT.into()],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        pipeline.set_ready_with(render_pipeline);
+    }
+
+    Some(())
+}
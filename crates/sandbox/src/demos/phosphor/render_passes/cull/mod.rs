@@ -0,0 +1,90 @@
+use antigen_wgpu::{
+    wgpu::{
+        BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, ComputePipelineDescriptor,
+        PipelineLayoutDescriptor,
+    },
+    buffer_size_of, BindGroupComponent, BindGroupLayoutComponent, BufferComponent,
+    ComputePipelineComponent, DeviceComponent, ShaderModuleComponent,
+};
+
+use super::storage_entry;
+use crate::demos::phosphor::{TriangleMeshBoundsData, TriangleMeshData, TriangleMeshInstanceData};
+
+/// Create the frustum cull bind group and compute pipeline
+///
+/// The cull pass reads every triangle mesh instance, and writes those visible to the camera
+/// into the visible instance buffer read by the beam mesh pass,
+/// replacing the instance counts of the indirect draw arguments in the triangle mesh buffer.
+pub fn phosphor_prepare_frustum_cull(
+    device: &DeviceComponent,
+    uniform_bind_group_layout: &BindGroupLayoutComponent,
+    buffers: [&BufferComponent; 4],
+    cull_shader: &ShaderModuleComponent,
+    cull_bind_group_layout: &mut BindGroupLayoutComponent,
+    cull_bind_group: &mut BindGroupComponent,
+    cull_pipeline: &mut ComputePipelineComponent,
+) -> Option<()> {
+    let uniform_bind_group_layout = uniform_bind_group_layout.get()?;
+    let cull_shader = cull_shader.get()?;
+
+    let buffers = buffers.map(|buffer| buffer.read());
+    let buffers = buffers
+        .iter()
+        .map(|buffer| buffer.get())
+        .collect::<Option<Vec<_>>>()?;
+
+    let cull_bind_group_layout = if let Some(bind_group_layout) = cull_bind_group_layout.get() {
+        bind_group_layout
+    } else {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Frustum Cull Bind Group Layout"),
+            entries: &[
+                storage_entry(0, false, buffer_size_of::<TriangleMeshData>()),
+                storage_entry(1, true, buffer_size_of::<TriangleMeshBoundsData>()),
+                storage_entry(2, true, buffer_size_of::<TriangleMeshInstanceData>()),
+                storage_entry(3, false, buffer_size_of::<TriangleMeshInstanceData>()),
+            ],
+        });
+
+        cull_bind_group_layout.set_ready_with(bind_group_layout);
+        cull_bind_group_layout.get().unwrap()
+    };
+
+    if cull_bind_group.is_pending() {
+        let entries = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: cull_bind_group_layout,
+            entries: &entries,
+            label: Some("Frustum Cull Bind Group"),
+        });
+        cull_bind_group.set_ready_with(bind_group);
+    }
+
+    if cull_pipeline.is_pending() {
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[uniform_bind_group_layout, cull_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        println!("Creating frustum cull pipeline");
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Frustum Cull"),
+            layout: Some(&pipeline_layout),
+            module: cull_shader,
+            entry_point: "cs_main",
+        });
+
+        cull_pipeline.set_ready_with(pipeline);
+    }
+
+    Some(())
+}
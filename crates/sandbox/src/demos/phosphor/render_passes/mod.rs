@@ -1,7 +1,35 @@
 mod beam;
+mod bloom;
+mod cull;
 mod phosphor;
+mod portal;
+mod skybox;
+mod smear;
 mod tonemap;
 
 pub use beam::*;
+pub use bloom::*;
+pub use cull::*;
 pub use phosphor::*;
+pub use portal::*;
+pub use skybox::*;
+pub use smear::*;
 pub use tonemap::*;
+
+use antigen_wgpu::wgpu::{
+    BindGroupLayoutEntry, BindingType, BufferBindingType, BufferSize, ShaderStages,
+};
+
+// Storage buffer layout entry for the compute passes
+fn storage_entry(binding: u32, read_only: bool, min_binding_size: u64) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: BufferSize::new(min_binding_size),
+        },
+        count: None,
+    }
+}
@@ -4,11 +4,11 @@ use antigen_wgpu::{
     wgpu::{
         BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
         BindingResource, BindingType, FragmentState, MultisampleState, PipelineLayoutDescriptor,
-        PrimitiveState, RenderPipelineDescriptor, SamplerBindingType, ShaderStages,
+        PrimitiveState, RenderPipelineDescriptor, SamplerDescriptor, ShaderStages,
         TextureSampleType, TextureViewDimension, VertexState,
     },
-    BindGroupComponent, BindGroupLayoutComponent, DeviceComponent, RenderPipelineComponent,
-    SamplerComponent, ShaderModuleComponent, TextureViewComponent,
+    sampler_layout_entry, BindGroupComponent, BindGroupLayoutComponent, DeviceComponent,
+    RenderPipelineComponent, SamplerComponent, ShaderModuleComponent, TextureViewComponent,
 };
 
 pub fn phosphor_prepare_phosphor_decay(
@@ -19,6 +19,7 @@ pub fn phosphor_prepare_phosphor_decay(
     phosphor_decay_pipeline: &mut RenderPipelineComponent,
     uniform_bind_group_layout: &BindGroupLayoutComponent,
     phosphor_decay_shader: &ShaderModuleComponent,
+    linear_sampler_descriptor: &SamplerDescriptor,
     linear_sampler: &SamplerComponent,
     beam_buffer_view: &TextureViewComponent,
     phosphor_front_buffer_view: &TextureViewComponent,
@@ -59,12 +60,7 @@ pub fn phosphor_prepare_phosphor_decay(
                         },
                         count: None,
                     },
-                    BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                        count: None,
-                    },
+                    sampler_layout_entry(2, ShaderStages::FRAGMENT, linear_sampler_descriptor),
                 ],
             });
 
@@ -0,0 +1,143 @@
+use antigen_wgpu::{
+    wgpu::{
+        ColorTargetState, ColorWrites, CompareFunction, DepthBiasState, DepthStencilState,
+        FragmentState, MultisampleState, PipelineLayoutDescriptor, PrimitiveState,
+        PrimitiveTopology, RenderPipelineDescriptor, StencilFaceState, StencilOperation,
+        StencilState, VertexState, VertexStepMode,
+    },
+    BindGroupLayoutComponent, DeviceComponent, RenderPipelineComponent, ShaderModuleComponent,
+    VertexLayout,
+};
+
+use crate::demos::phosphor::{PortalInstanceData, DEPTH_TEXTURE_FORMAT, HDR_TEXTURE_FORMAT};
+
+fn stencil_state(
+    compare: CompareFunction,
+    pass_op: StencilOperation,
+    write_mask: u32,
+) -> StencilState {
+    let face = StencilFaceState {
+        compare,
+        fail_op: StencilOperation::Keep,
+        depth_fail_op: StencilOperation::Keep,
+        pass_op,
+    };
+
+    StencilState {
+        front: face,
+        back: face,
+        read_mask: 0xff,
+        write_mask,
+    }
+}
+
+/// Stencil state for geometry seen through a portal
+///
+/// The stencil value of a fragment is the number of portals it's seen through,
+/// so drawing with that number as the reference passes for fragments at least that deep
+pub fn portal_view_stencil() -> StencilState {
+    stencil_state(CompareFunction::LessEqual, StencilOperation::Keep, 0)
+}
+
+fn prepare_portal_quad(
+    device: &DeviceComponent,
+    uniform_bind_group_layout: &BindGroupLayoutComponent,
+    beam_shader: &ShaderModuleComponent,
+    depth_write_enabled: bool,
+    stencil: StencilState,
+    portal_pipeline: &mut RenderPipelineComponent,
+) -> Option<()> {
+    let uniform_bind_group_layout = uniform_bind_group_layout.get()?;
+    let beam_shader = beam_shader.get()?;
+
+    if portal_pipeline.is_pending() {
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[uniform_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: beam_shader,
+                entry_point: "vs_portal",
+                buffers: &[PortalInstanceData::vertex_buffer_layout(VertexStepMode::Instance)],
+            },
+            // Portal quads only touch the depth-stencil attachment
+            fragment: Some(FragmentState {
+                module: beam_shader,
+                entry_point: "fs_main",
+                targets: &[ColorTargetState {
+                    format: HDR_TEXTURE_FORMAT,
+                    blend: None,
+                    write_mask: ColorWrites::empty(),
+                }],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_TEXTURE_FORMAT,
+                depth_write_enabled,
+                depth_compare: CompareFunction::Always,
+                stencil,
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: 4,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        portal_pipeline.set_ready_with(pipeline);
+    }
+
+    Some(())
+}
+
+/// Create the pipeline that marks the fragments covered by portal quads in the stencil buffer
+///
+/// Fragments outside any portal are incremented to 1,
+/// so overlapping portals don't mark each other's fragments twice
+pub fn phosphor_prepare_portal_stencil(
+    device: &DeviceComponent,
+    uniform_bind_group_layout: &BindGroupLayoutComponent,
+    beam_shader: &ShaderModuleComponent,
+    portal_stencil_pipeline: &mut RenderPipelineComponent,
+) -> Option<()> {
+    prepare_portal_quad(
+        device,
+        uniform_bind_group_layout,
+        beam_shader,
+        false,
+        stencil_state(
+            CompareFunction::Equal,
+            StencilOperation::IncrementClamp,
+            0xff,
+        ),
+        portal_stencil_pipeline,
+    )
+}
+
+/// Create the pipeline that writes the depth of portal quads over the scene seen through them,
+/// so the scene in front of the portal occludes it and the scene behind it doesn't
+pub fn phosphor_prepare_portal_depth(
+    device: &DeviceComponent,
+    uniform_bind_group_layout: &BindGroupLayoutComponent,
+    beam_shader: &ShaderModuleComponent,
+    portal_depth_pipeline: &mut RenderPipelineComponent,
+) -> Option<()> {
+    prepare_portal_quad(
+        device,
+        uniform_bind_group_layout,
+        beam_shader,
+        true,
+        portal_view_stencil(),
+        portal_depth_pipeline,
+    )
+}
@@ -0,0 +1,100 @@
+use antigen_wgpu::{
+    wgpu::{
+        BlendComponent, BlendFactor, BlendOperation, BlendState, ColorTargetState, ColorWrites,
+        CompareFunction, DepthBiasState, DepthStencilState, FragmentState, MultisampleState,
+        PipelineLayoutDescriptor, PrimitiveState, RenderPipelineDescriptor, StencilState,
+        VertexState,
+    },
+    BindGroupLayoutComponent, DeviceComponent, RenderPipelineComponent, ShaderModuleComponent,
+    SurfaceConfigurationComponent,
+};
+
+use crate::demos::phosphor::{SkyboxMode, DEPTH_TEXTURE_FORMAT, HDR_TEXTURE_FORMAT};
+
+/// Create the skybox pipeline for the given mode
+///
+/// Underlay renders into the multisampled beam buffer, only passing depth where nothing was drawn.
+/// Overlay is added to the render target after tonemapping.
+/// Both leave alpha untouched, preserving the beam buffer's decay rate.
+pub fn phosphor_prepare_skybox(
+    device: &DeviceComponent,
+    uniform_bind_group_layout: &BindGroupLayoutComponent,
+    skybox_shader: &ShaderModuleComponent,
+    surface_config: &SurfaceConfigurationComponent,
+    mode: SkyboxMode,
+    skybox_pipeline: &mut RenderPipelineComponent,
+) -> Option<()> {
+    let uniform_bind_group_layout = uniform_bind_group_layout.get()?;
+    let skybox_shader = skybox_shader.get()?;
+
+    if skybox_pipeline.is_pending() {
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[uniform_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let (entry_point, target, depth_stencil, multisample) = match mode {
+            SkyboxMode::Underlay => (
+                "fs_underlay",
+                ColorTargetState {
+                    format: HDR_TEXTURE_FORMAT,
+                    blend: None,
+                    write_mask: ColorWrites::COLOR,
+                },
+                Some(DepthStencilState {
+                    format: DEPTH_TEXTURE_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: CompareFunction::GreaterEqual,
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                MultisampleState {
+                    count: 4,
+                    ..Default::default()
+                },
+            ),
+            SkyboxMode::Overlay => (
+                "fs_overlay",
+                ColorTargetState {
+                    format: surface_config.format,
+                    blend: Some(BlendState {
+                        color: BlendComponent {
+                            src_factor: BlendFactor::One,
+                            dst_factor: BlendFactor::One,
+                            operation: BlendOperation::Add,
+                        },
+                        alpha: BlendComponent::REPLACE,
+                    }),
+                    write_mask: ColorWrites::COLOR,
+                },
+                None,
+                MultisampleState::default(),
+            ),
+        };
+
+        println!("Creating {:?} skybox pipeline", mode);
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Skybox"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: skybox_shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: skybox_shader,
+                entry_point,
+                targets: &[target],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil,
+            multisample,
+            multiview: None,
+        });
+
+        skybox_pipeline.set_ready_with(pipeline);
+    }
+
+    Some(())
+}
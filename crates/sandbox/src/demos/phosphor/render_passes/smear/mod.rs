@@ -0,0 +1,262 @@
+use antigen_wgpu::{
+    wgpu::{
+        BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+        BindingType, BlendComponent, BlendFactor, BlendOperation, BlendState, Buffer,
+        BufferBindingType, BufferSize, ColorTargetState, ColorWrites, CompareFunction,
+        ComputePipelineDescriptor, DepthBiasState, DepthStencilState, FragmentState,
+        MultisampleState, PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology,
+        RenderPipelineDescriptor, ShaderStages, StencilState, VertexState,
+    },
+    buffer_size_of, BindGroupComponent, BindGroupLayoutComponent, BufferComponent,
+    ComputePipelineComponent, DeviceComponent, RenderPipelineComponent, ShaderModuleComponent,
+};
+
+use super::storage_entry;
+use crate::demos::phosphor::{
+    LineHistoryData, LineIndexData, LineInstanceData, LineMeshData, LineMeshInstanceData,
+    LineSmearData, LineSmearUniformData, VertexData, DEPTH_TEXTURE_FORMAT, HDR_TEXTURE_FORMAT,
+};
+
+// Bind one direction of the history double-buffer,
+// reading the previous frame's endpoints from one history buffer and writing to the other
+fn line_smear_bind_group_entries<'a>(
+    buffers: &[&'a Buffer],
+    previous_history: &'a Buffer,
+    current_history: &'a Buffer,
+) -> Vec<BindGroupEntry<'a>> {
+    buffers[..5]
+        .iter()
+        .copied()
+        .chain([previous_history, current_history])
+        .chain(buffers[5..].iter().copied())
+        .enumerate()
+        .map(|(binding, buffer)| BindGroupEntry {
+            binding: binding as u32,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect()
+}
+
+/// Create the line smear bind groups and compute pipeline
+///
+/// The smear pass reads the current endpoints of every line instance
+/// alongside those written to one history buffer on the previous frame,
+/// writing the current endpoints to the other history buffer
+/// and a quad connecting the two into the smear buffer drawn by the beam line smear pass.
+///
+/// The front and back bind groups read and write the history buffers in opposite directions,
+/// and are swapped each frame.
+pub fn phosphor_prepare_line_smear(
+    device: &DeviceComponent,
+    buffers: [&BufferComponent; 7],
+    front_history_buffer: &BufferComponent,
+    back_history_buffer: &BufferComponent,
+    smear_shader: &ShaderModuleComponent,
+    smear_bind_group_layout: &mut BindGroupLayoutComponent,
+    front_bind_group: &mut BindGroupComponent,
+    back_bind_group: &mut BindGroupComponent,
+    smear_pipeline: &mut ComputePipelineComponent,
+) -> Option<()> {
+    let smear_shader = smear_shader.get()?;
+
+    let buffers = buffers.map(|buffer| buffer.read());
+    let buffers = buffers
+        .iter()
+        .map(|buffer| buffer.get())
+        .collect::<Option<Vec<_>>>()?;
+
+    let front_history_buffer = front_history_buffer.read();
+    let front_history_buffer = front_history_buffer.get()?;
+
+    let back_history_buffer = back_history_buffer.read();
+    let back_history_buffer = back_history_buffer.get()?;
+
+    let smear_bind_group_layout = if let Some(bind_group_layout) = smear_bind_group_layout.get() {
+        bind_group_layout
+    } else {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Line Smear Bind Group Layout"),
+            entries: &[
+                storage_entry(0, true, buffer_size_of::<VertexData>()),
+                storage_entry(1, true, buffer_size_of::<LineIndexData>()),
+                storage_entry(2, true, buffer_size_of::<LineMeshData>()),
+                storage_entry(3, true, buffer_size_of::<LineMeshInstanceData>()),
+                storage_entry(4, true, buffer_size_of::<LineInstanceData>()),
+                storage_entry(5, true, buffer_size_of::<LineHistoryData>()),
+                storage_entry(6, false, buffer_size_of::<LineHistoryData>()),
+                storage_entry(7, false, buffer_size_of::<LineSmearData>()),
+                BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(buffer_size_of::<LineSmearUniformData>()),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        smear_bind_group_layout.set_ready_with(bind_group_layout);
+        smear_bind_group_layout.get().unwrap()
+    };
+
+    if front_bind_group.is_pending() {
+        let entries =
+            line_smear_bind_group_entries(&buffers, back_history_buffer, front_history_buffer);
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: smear_bind_group_layout,
+            entries: &entries,
+            label: Some("Line Smear Front Bind Group"),
+        });
+        front_bind_group.set_ready_with(bind_group);
+    }
+
+    if back_bind_group.is_pending() {
+        let entries =
+            line_smear_bind_group_entries(&buffers, front_history_buffer, back_history_buffer);
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: smear_bind_group_layout,
+            entries: &entries,
+            label: Some("Line Smear Back Bind Group"),
+        });
+        back_bind_group.set_ready_with(bind_group);
+    }
+
+    if smear_pipeline.is_pending() {
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[smear_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        println!("Creating line smear pipeline");
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Line Smear"),
+            layout: Some(&pipeline_layout),
+            module: smear_shader,
+            entry_point: "cs_main",
+        });
+
+        smear_pipeline.set_ready_with(pipeline);
+    }
+
+    Some(())
+}
+
+/// Create the bind group and render pipeline drawing the quads written by the line smear pass
+pub fn phosphor_prepare_beam_line_smear(
+    device: &DeviceComponent,
+    uniform_bind_group_layout: &BindGroupLayoutComponent,
+    storage_bind_group_layout: &BindGroupLayoutComponent,
+    beam_shader: &ShaderModuleComponent,
+    smear_buffer: &BufferComponent,
+    smear_bind_group_layout: &mut BindGroupLayoutComponent,
+    smear_bind_group: &mut BindGroupComponent,
+    beam_line_smear_pipeline: &mut RenderPipelineComponent,
+) -> Option<()> {
+    let uniform_bind_group_layout = uniform_bind_group_layout.get()?;
+    let storage_bind_group_layout = storage_bind_group_layout.get()?;
+    let beam_shader = beam_shader.get()?;
+
+    let smear_buffer = smear_buffer.read();
+    let smear_buffer = smear_buffer.get()?;
+
+    let smear_bind_group_layout = if let Some(bind_group_layout) = smear_bind_group_layout.get() {
+        bind_group_layout
+    } else {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Beam Line Smear Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(buffer_size_of::<LineSmearData>()),
+                },
+                count: None,
+            }],
+        });
+
+        smear_bind_group_layout.set_ready_with(bind_group_layout);
+        smear_bind_group_layout.get().unwrap()
+    };
+
+    if smear_bind_group.is_pending() {
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: smear_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: smear_buffer.as_entire_binding(),
+            }],
+            label: Some("Beam Line Smear Bind Group"),
+        });
+        smear_bind_group.set_ready_with(bind_group);
+    }
+
+    if beam_line_smear_pipeline.is_pending() {
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[
+                uniform_bind_group_layout,
+                storage_bind_group_layout,
+                smear_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Beam Line Smears"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: beam_shader,
+                entry_point: "vs_smear",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: beam_shader,
+                entry_point: "fs_main",
+                // Smears add to the beam buffer's color,
+                // leaving the intensity delta of the lines beneath them untouched
+                targets: &[ColorTargetState {
+                    format: HDR_TEXTURE_FORMAT,
+                    blend: Some(BlendState {
+                        color: BlendComponent {
+                            src_factor: BlendFactor::One,
+                            dst_factor: BlendFactor::One,
+                            operation: BlendOperation::Add,
+                        },
+                        alpha: BlendComponent::REPLACE,
+                    }),
+                    write_mask: ColorWrites::COLOR,
+                }],
+            }),
+            // Smears face whichever way their line moved
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_TEXTURE_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Greater,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: 4,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        beam_line_smear_pipeline.set_ready_with(pipeline);
+    }
+
+    Some(())
+}
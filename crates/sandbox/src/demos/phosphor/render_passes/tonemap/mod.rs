@@ -1,27 +1,82 @@
 use antigen_wgpu::{
     wgpu::{
-        FragmentState, MultisampleState, PipelineLayoutDescriptor, PrimitiveState,
-        RenderPipelineDescriptor, VertexState,
+        BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+        BindingResource, BindingType, FragmentState, MultisampleState, PipelineLayoutDescriptor,
+        PrimitiveState, RenderPipelineDescriptor, ShaderStages, TextureSampleType,
+        TextureViewDimension, VertexState,
     },
-    BindGroupLayoutComponent, DeviceComponent, RenderPipelineComponent, ShaderModuleComponent,
-    SurfaceConfigurationComponent,
+    BindGroupComponent, BindGroupLayoutComponent, DeviceComponent, RenderPipelineComponent,
+    ShaderModuleComponent, SurfaceConfigurationComponent, TextureViewComponent,
 };
 
+pub fn phosphor_prepare_color_lut(
+    device: &DeviceComponent,
+    color_lut_bind_group_layout: &mut BindGroupLayoutComponent,
+    color_lut_bind_group: &mut BindGroupComponent,
+    color_lut_view: &TextureViewComponent,
+) -> Option<()> {
+    let color_lut_view = color_lut_view.get()?;
+
+    // Color LUT bind group
+    let color_lut_bind_group_layout =
+        if let Some(bind_group_layout) = color_lut_bind_group_layout.get() {
+            bind_group_layout
+        } else {
+            let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Color LUT Bind Group Layout"),
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+            });
+
+            color_lut_bind_group_layout.set_ready_with(bind_group_layout);
+            color_lut_bind_group_layout.get().unwrap()
+        };
+
+    if color_lut_bind_group.is_pending() {
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: color_lut_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(color_lut_view),
+            }],
+            label: Some("Color LUT Bind Group"),
+        });
+        color_lut_bind_group.set_ready_with(bind_group);
+    }
+
+    Some(())
+}
+
+/// Create the tonemap pipeline
+///
+/// Bind group layouts are expected in group order:
+/// phosphor buffer, uniforms, bloom mip chain and color LUT.
 pub fn phosphor_prepare_tonemap(
     device: &DeviceComponent,
-    phosphor_bind_group_layout: &BindGroupLayoutComponent,
+    bind_group_layouts: [&BindGroupLayoutComponent; 4],
     tonemap_shader: &ShaderModuleComponent,
     surface_config: &SurfaceConfigurationComponent,
     tonemap_pipeline: &mut RenderPipelineComponent,
 ) -> Option<()> {
     let tonemap_shader = tonemap_shader.get()?;
-    let phosphor_bind_group_layout = phosphor_bind_group_layout.get()?;
+    let bind_group_layouts = bind_group_layouts
+        .iter()
+        .map(|bind_group_layout| bind_group_layout.get())
+        .collect::<Option<Vec<_>>>()?;
 
     if tonemap_pipeline.is_pending() {
         // Tonemap pipeline
         let pipeline_layout = device.create_pipeline_layout(&mut PipelineLayoutDescriptor {
             label: None,
-            bind_group_layouts: &[&phosphor_bind_group_layout],
+            bind_group_layouts: &bind_group_layouts,
             push_constant_ranges: &[],
         });
 
@@ -2,8 +2,18 @@ use std::{collections::BTreeMap, error::Error, path::Path};
 type Param1 = f32;
 type Param2 = (f32, f32);
 
+type Param3 = (Param2, Param2, Param2);
+
 type VecParam1 = Vec<Param1>;
 type VecParam2 = Vec<Param2>;
+type VecParam3 = Vec<Param3>;
+
+/// Number of line segments used to approximate a curve whose control points
+/// don't lie on the line between its ends
+pub const CURVE_SEGMENTS: usize = 8;
+
+// Distance control points may lie from a curve's chord while still drawing it as a single line
+const CURVE_FLATNESS: f32 = 0.1;
 
 #[derive(Debug, Copy, Clone)]
 pub enum CommandType {
@@ -17,6 +27,8 @@ pub enum SvgDraw {
     LineTo(CommandType, Param2),
     HorizontalTo(CommandType, Param1),
     VerticalTo(CommandType, Param1),
+    /// Cubic Bézier curve, with two control points followed by the end point
+    CubicTo(CommandType, Param3),
     EndPath,
     ClosePath,
 }
@@ -59,6 +71,10 @@ pub fn vec_param2(input: &str) -> nom::IResult<&str, VecParam2> {
     nom::multi::many1(ws(param2))(input)
 }
 
+pub fn vec_param3(input: &str) -> nom::IResult<&str, VecParam3> {
+    nom::multi::many1(nom::sequence::tuple((ws(param2), ws(param2), ws(param2))))(input)
+}
+
 pub fn move_to_absolute(input: &str) -> nom::IResult<&str, Vec<SvgDraw>> {
     let (input, _) = nom::bytes::complete::tag("M")(input)?;
     let (input, output) = vec_param2(input)?;
@@ -164,6 +180,30 @@ pub fn vertical_to_relative(input: &str) -> nom::IResult<&str, Vec<SvgDraw>> {
     Ok((input, output))
 }
 
+pub fn cubic_to_absolute(input: &str) -> nom::IResult<&str, Vec<SvgDraw>> {
+    let (input, _) = nom::bytes::complete::tag("C")(input)?;
+    let (input, output) = vec_param3(input)?;
+
+    let output = output
+        .into_iter()
+        .map(|output| SvgDraw::CubicTo(CommandType::Absolute, output))
+        .collect();
+
+    Ok((input, output))
+}
+
+pub fn cubic_to_relative(input: &str) -> nom::IResult<&str, Vec<SvgDraw>> {
+    let (input, _) = nom::bytes::complete::tag("c")(input)?;
+    let (input, output) = vec_param3(input)?;
+
+    let output = output
+        .into_iter()
+        .map(|output| SvgDraw::CubicTo(CommandType::Relative, output))
+        .collect();
+
+    Ok((input, output))
+}
+
 pub fn close_path(input: &str) -> nom::IResult<&str, Vec<SvgDraw>> {
     let (input, _) = nom::branch::alt((
         nom::bytes::complete::tag("Z"),
@@ -183,6 +223,8 @@ pub fn svg_draw_variant(input: &str) -> nom::IResult<&str, Vec<SvgDraw>> {
         horizontal_to_relative,
         vertical_to_absolute,
         vertical_to_relative,
+        cubic_to_absolute,
+        cubic_to_relative,
         close_path,
     ))(input)
 }
@@ -217,7 +259,19 @@ impl SvgLayers {
                     },
                     "path" => {
                         let value = attributes.get("d").unwrap().to_string();
-                        let (_, svg_draw) = svg_draw(&value).unwrap();
+                        let (rest, svg_draw) = svg_draw(&value)
+                            .map_err(|e| format!("Failed to parse SVG path {:?}: {}", value, e))?;
+
+                        // Stopping at an unsupported command would silently drop the rest
+                        // of the path, as happened to the cubic curve in lower-case z
+                        if !rest.trim().is_empty() {
+                            return Err(format!(
+                                "Unsupported SVG path data {:?} in {:?}",
+                                rest, value
+                            )
+                            .into());
+                        }
+
                         layers
                             .0
                             .entry(group_stack[0].clone())
@@ -306,6 +360,28 @@ impl SvgLayers {
                                     index += 1;
                                     indices.push(index);
                                 }
+                                SvgDraw::CubicTo(command_type, (c1, c2, end)) => {
+                                    let offset = match command_type {
+                                        CommandType::Absolute => (0.0, 0.0),
+                                        CommandType::Relative => cur_pos,
+                                    };
+                                    let absolute = |(x, y): Param2| (x + offset.0, y + offset.1);
+                                    let points =
+                                        [cur_pos, absolute(*c1), absolute(*c2), absolute(*end)];
+
+                                    let segments =
+                                        if is_flat(points) { 1 } else { CURVE_SEGMENTS };
+                                    for segment in 1..=segments {
+                                        let t = segment as f32 / segments as f32;
+                                        vertices.push(cubic_point(points, t));
+
+                                        indices.push(index);
+                                        index += 1;
+                                        indices.push(index);
+                                    }
+
+                                    cur_pos = points[3];
+                                }
                                 SvgDraw::EndPath => {
                                     cur_pos = (0.0, 0.0);
                                     index += 1;
@@ -326,6 +402,69 @@ impl SvgLayers {
     }
 }
 
+// Whether a cubic curve's control points lie close enough to its chord to draw it as a line
+fn is_flat([start, c1, c2, end]: [Param2; 4]) -> bool {
+    let chord = (end.0 - start.0, end.1 - start.1);
+    let length = chord.0.hypot(chord.1);
+
+    [c1, c2].into_iter().all(|(x, y)| {
+        let (dx, dy) = (x - start.0, y - start.1);
+        let distance = if length > 0.0 {
+            (chord.0 * dy - chord.1 * dx).abs() / length
+        } else {
+            dx.hypot(dy)
+        };
+        distance < CURVE_FLATNESS
+    })
+}
+
+// Evaluate a cubic Bézier curve at t
+fn cubic_point([p0, p1, p2, p3]: [Param2; 4], t: f32) -> Param2 {
+    let u = 1.0 - t;
+    let weights = [u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t];
+
+    [p0, p1, p2, p3]
+        .into_iter()
+        .zip(weights)
+        .fold((0.0, 0.0), |(x, y), ((px, py), w)| (x + px * w, y + py * w))
+}
+
 pub type SvgMeshes = BTreeMap<String, BTreeMap<String, (Vec<(f32, f32)>, Vec<usize>)>>;
 // Aa
 // Ee
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn font_draws_every_lowercase_letter() {
+        let font = concat!(env!("CARGO_MANIFEST_DIR"), "/../../test-data/fonts/basic.svg");
+        let meshes = SvgLayers::parse(font).unwrap().meshes();
+
+        for c in 'a'..='z' {
+            let grapheme = c.to_string();
+            let (vertices, indices) = meshes
+                .values()
+                .find_map(|graphemes| graphemes.get(&grapheme))
+                .unwrap_or_else(|| panic!("No mesh for {}", c));
+
+            assert!(!indices.is_empty(), "Empty mesh for {}", c);
+            assert!(indices.iter().all(|index| *index < vertices.len()));
+        }
+    }
+
+    #[test]
+    fn cubic_curves_draw_lines() {
+        let (_, commands) = svg_draw("m -4,15 c 2,0 8.1,0 8,0 l -8,14").unwrap();
+        assert!(matches!(
+            commands[1],
+            SvgDraw::CubicTo(CommandType::Relative, ((2.0, 0.0), _, (8.0, 0.0)))
+        ));
+
+        // Collinear control points draw a single line
+        assert!(is_flat([(-4.0, 15.0), (-2.0, 15.0), (4.1, 15.0), (4.0, 15.0)]));
+        assert!(!is_flat([(0.0, 0.0), (0.0, 4.0), (4.0, 4.0), (4.0, 0.0)]));
+        assert_eq!(cubic_point([(0.0, 0.0), (0.0, 4.0), (4.0, 4.0), (4.0, 0.0)], 0.5), (2.0, 3.0));
+    }
+}
@@ -1,24 +1,32 @@
-use std::{sync::atomic::Ordering, time::Instant};
+use std::{
+    sync::atomic::Ordering,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
 use super::*;
 use antigen_core::{
-    get_named_entities_component, Changed, ChangedTrait, CopyToComponent, Indirect, LazyComponent,
-    NamedEntitiesComponent,
+    get_named_entities_component, get_named_entities_component_mut, get_tagged_entity, Changed,
+    ChangedTrait, CopyToComponent, Indirect, LazyComponent, Usage,
 };
+use antigen_rapier3d::PendingRemoval;
+use rapier3d::prelude::{ColliderSet, Shape};
 
 use antigen_wgpu::{
     wgpu::{
         BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
-        BindingResource, BindingType, BufferBinding, BufferBindingType, BufferSize, Extent3d,
-        ShaderStages,
+        BindingResource, BindingType, BufferAddress, BufferBinding, BufferBindingType, BufferSize,
+        DynamicOffset, Extent3d, ShaderStages, StencilState,
     },
-    BindGroupComponent, BindGroupLayoutComponent, BufferComponent, DeviceComponent,
-    RenderPassDrawComponent, SamplerComponent, SurfaceConfigurationComponent,
-    TextureDescriptorComponent, TextureViewComponent, TextureViewDescriptorComponent,
+    buffer_size_of, BindGroupComponent, BindGroupLayoutComponent, BufferComponent,
+    BufferDescriptorComponent, ComputePassDispatchComponent, ComputePipelineComponent,
+    DeviceComponent, PassOrderComponent, RenderPassBindGroupOffsetsComponent,
+    RenderPassDrawComponent, SamplerComponent, SamplerDescriptorComponent,
+    SurfaceConfigurationComponent, TextureDescriptorComponent, TextureViewComponent,
+    TextureViewDescriptorComponent,
 };
 
-use hecs::World;
-use winit::event::{ElementState, KeyboardInput};
+use hecs::{Entity, World};
+use winit::event::{ElementState, KeyboardInput, MouseButton};
 
 // Initialize the hello triangle render pipeline
 pub fn phosphor_prepare_system(world: &mut World) {
@@ -54,7 +62,7 @@ pub fn phosphor_prepare_uniform_bind_group(
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Uniform,
                     has_dynamic_offset: false,
-                    min_binding_size: BufferSize::new(176),
+                    min_binding_size: BufferSize::new(208),
                 },
                 count: None,
             }],
@@ -165,7 +173,7 @@ pub fn phosphor_prepare_storage_bind_group(
                             ty: BindingType::Buffer {
                                 ty: BufferBindingType::Storage { read_only: true },
                                 has_dynamic_offset: false,
-                                min_binding_size: BufferSize::new(48),
+                                min_binding_size: BufferSize::new(64),
                             },
                             count: None,
                         },
@@ -233,8 +241,10 @@ pub fn phosphor_prepare_storage_bind_group(
 }
 
 pub fn phosphor_prepare(world: &World, entity: Entity, device: &DeviceComponent) -> Option<()> {
-    let mut query = world.query_one::<&SamplerComponent>(entity).unwrap();
-    let sampler = query.get().unwrap();
+    let mut query = world
+        .query_one::<(&SamplerDescriptorComponent, &SamplerComponent)>(entity)
+        .unwrap();
+    let (sampler_descriptor, sampler) = query.get().unwrap();
 
     let mut query = world
         .query_one::<&Indirect<&SurfaceConfigurationComponent>>(entity)
@@ -255,11 +265,24 @@ pub fn phosphor_prepare(world: &World, entity: Entity, device: &DeviceComponent)
     let mut query = world.query::<(&BufferComponent,)>().with::<Vertices>();
     let (_, (vertex_buffer,)) = query.into_iter().next()?;
 
+    let mut query = world.query::<(&BufferComponent,)>().with::<TriangleMeshes>();
+    let (_, (triangle_mesh_buffer,)) = query.into_iter().next()?;
+
+    let mut query = world
+        .query::<(&BufferComponent,)>()
+        .with::<TriangleMeshBounds>();
+    let (_, (triangle_mesh_bounds_buffer,)) = query.into_iter().next()?;
+
     let mut query = world
         .query::<(&BufferComponent,)>()
         .with::<TriangleMeshInstances>();
     let (_, (triangle_mesh_instance_buffer,)) = query.into_iter().next()?;
 
+    let mut query = world
+        .query::<(&BufferComponent,)>()
+        .with::<VisibleTriangleMeshInstances>();
+    let (_, (visible_triangle_mesh_instance_buffer,)) = query.into_iter().next()?;
+
     let mut query = world.query::<(&BufferComponent,)>().with::<LineIndices>();
     let (_, (line_index_buffer,)) = query.into_iter().next()?;
 
@@ -296,16 +319,29 @@ pub fn phosphor_prepare(world: &World, entity: Entity, device: &DeviceComponent)
         uniform_bind_group,
     );
 
+    let mut query = world
+        .query::<(&BufferComponent, &mut BindGroupComponent)>()
+        .with::<PortalUniform>();
+    let (_, (portal_uniform_buffer, portal_uniform_bind_group)) = query.into_iter().next()?;
+
+    phosphor_prepare_uniform_bind_group(
+        device,
+        portal_uniform_buffer,
+        uniform_bind_group_layout,
+        portal_uniform_bind_group,
+    )?;
+
     let mut query = world
         .query::<(&mut BindGroupLayoutComponent, &mut BindGroupComponent)>()
         .with::<StorageBuffers>();
     let (_, (storage_bind_group_layout, storage_bind_group)) = query.into_iter().next()?;
     println!("Fetched storage bind group entity");
 
+    // Beam meshes draw the instances that survived frustum culling
     phosphor_prepare_storage_bind_group(
         device,
         vertex_buffer,
-        triangle_mesh_instance_buffer,
+        visible_triangle_mesh_instance_buffer,
         line_index_buffer,
         line_mesh_buffer,
         line_mesh_instance_buffer,
@@ -314,6 +350,83 @@ pub fn phosphor_prepare(world: &World, entity: Entity, device: &DeviceComponent)
         storage_bind_group,
     )?;
 
+    let mut query = world
+        .query::<(
+            &ShaderModuleComponent,
+            &mut BindGroupLayoutComponent,
+            &mut BindGroupComponent,
+            &mut ComputePipelineComponent,
+        )>()
+        .with::<FrustumCull>();
+    let (_, (cull_shader, cull_bind_group_layout, cull_bind_group, cull_pipeline)) =
+        query.into_iter().next()?;
+    println!("Fetched frustum cull entity");
+
+    phosphor_prepare_frustum_cull(
+        device,
+        uniform_bind_group_layout,
+        [
+            triangle_mesh_buffer,
+            triangle_mesh_bounds_buffer,
+            triangle_mesh_instance_buffer,
+            visible_triangle_mesh_instance_buffer,
+        ],
+        cull_shader,
+        cull_bind_group_layout,
+        cull_bind_group,
+        cull_pipeline,
+    )?;
+
+    let mut query = world.query::<(&BufferComponent,)>().with::<LineSmears>();
+    let (_, (line_smear_buffer,)) = query.into_iter().next()?;
+
+    let mut query = world
+        .query::<(&BufferComponent,)>()
+        .with::<LineSmearUniform>();
+    let (_, (line_smear_uniform_buffer,)) = query.into_iter().next()?;
+
+    let mut query = world
+        .query::<(&BufferComponent, &mut BindGroupComponent)>()
+        .with::<LineSmearFront>();
+    let (_, (line_smear_front_buffer, line_smear_front_bind_group)) =
+        query.into_iter().next()?;
+
+    let mut query = world
+        .query::<(&BufferComponent, &mut BindGroupComponent)>()
+        .with::<LineSmearBack>();
+    let (_, (line_smear_back_buffer, line_smear_back_bind_group)) = query.into_iter().next()?;
+
+    let mut query = world
+        .query::<(
+            &ShaderModuleComponent,
+            &mut BindGroupLayoutComponent,
+            &mut ComputePipelineComponent,
+        )>()
+        .with::<LineSmear>();
+    let (_, (line_smear_shader, line_smear_bind_group_layout, line_smear_pipeline)) =
+        query.into_iter().next()?;
+    println!("Fetched line smear entity");
+
+    phosphor_prepare_line_smear(
+        device,
+        [
+            vertex_buffer,
+            line_index_buffer,
+            line_mesh_buffer,
+            line_mesh_instance_buffer,
+            line_instance_buffer,
+            line_smear_buffer,
+            line_smear_uniform_buffer,
+        ],
+        line_smear_front_buffer,
+        line_smear_back_buffer,
+        line_smear_shader,
+        line_smear_bind_group_layout,
+        line_smear_front_bind_group,
+        line_smear_back_bind_group,
+        line_smear_pipeline,
+    )?;
+
     let mut query = world.query::<&ShaderModuleComponent>().with::<Beam>();
 
     let (_, beam_shader) = query.into_iter().next()?;
@@ -340,9 +453,25 @@ pub fn phosphor_prepare(world: &World, entity: Entity, device: &DeviceComponent)
         uniform_bind_group_layout,
         storage_bind_group_layout,
         beam_shader,
+        StencilState::default(),
         beam_mesh_pipeline,
     )?;
 
+    let mut query = world
+        .query::<&mut RenderPipelineComponent>()
+        .with::<PortalTriangles>();
+    let (_, portal_mesh_pipeline) = query.into_iter().next()?;
+    println!("Fetched portal mesh pass entity");
+
+    phosphor_prepare_beam_mesh(
+        device,
+        uniform_bind_group_layout,
+        storage_bind_group_layout,
+        beam_shader,
+        portal_view_stencil(),
+        portal_mesh_pipeline,
+    )?;
+
     let mut query = world
         .query::<&mut RenderPipelineComponent>()
         .with::<BeamLines>();
@@ -354,9 +483,74 @@ pub fn phosphor_prepare(world: &World, entity: Entity, device: &DeviceComponent)
         uniform_bind_group_layout,
         storage_bind_group_layout,
         beam_shader,
+        StencilState::default(),
         beam_line_pipeline,
     )?;
 
+    let mut query = world
+        .query::<(&mut BindGroupLayoutComponent, &mut BindGroupComponent)>()
+        .with::<LineSmears>();
+    let (_, (line_smear_render_bind_group_layout, line_smear_render_bind_group)) =
+        query.into_iter().next()?;
+
+    let mut query = world
+        .query::<&mut RenderPipelineComponent>()
+        .with::<BeamLineSmears>();
+    let (_, beam_line_smear_pipeline) = query.into_iter().next()?;
+    println!("Fetched beam line smear pass entity");
+
+    phosphor_prepare_beam_line_smear(
+        device,
+        uniform_bind_group_layout,
+        storage_bind_group_layout,
+        beam_shader,
+        line_smear_buffer,
+        line_smear_render_bind_group_layout,
+        line_smear_render_bind_group,
+        beam_line_smear_pipeline,
+    )?;
+
+    let mut query = world
+        .query::<&mut RenderPipelineComponent>()
+        .with::<PortalLines>();
+    let (_, portal_line_pipeline) = query.into_iter().next()?;
+    println!("Fetched portal line pass entity");
+
+    phosphor_prepare_beam_line(
+        device,
+        uniform_bind_group_layout,
+        storage_bind_group_layout,
+        beam_shader,
+        portal_view_stencil(),
+        portal_line_pipeline,
+    )?;
+
+    let mut query = world
+        .query::<&mut RenderPipelineComponent>()
+        .with::<PortalStencil>();
+    let (_, portal_stencil_pipeline) = query.into_iter().next()?;
+    println!("Fetched portal stencil pass entity");
+
+    phosphor_prepare_portal_stencil(
+        device,
+        uniform_bind_group_layout,
+        beam_shader,
+        portal_stencil_pipeline,
+    )?;
+
+    let mut query = world
+        .query::<&mut RenderPipelineComponent>()
+        .with::<PortalDepth>();
+    let (_, portal_depth_pipeline) = query.into_iter().next()?;
+    println!("Fetched portal depth pass entity");
+
+    phosphor_prepare_portal_depth(
+        device,
+        uniform_bind_group_layout,
+        beam_shader,
+        portal_depth_pipeline,
+    )?;
+
     let mut query = world
         .query::<(
             &ShaderModuleComponent,
@@ -386,12 +580,79 @@ pub fn phosphor_prepare(world: &World, entity: Entity, device: &DeviceComponent)
         phosphor_decay_pipeline,
         uniform_bind_group_layout,
         phosphor_decay_shader,
+        sampler_descriptor,
         sampler,
         beam_buffer_view,
         phosphor_front_buffer_view,
         phosphor_back_buffer_view,
     )?;
 
+    let mut query = world
+        .query::<(
+            &ShaderModuleComponent,
+            &mut BindGroupLayoutComponent,
+            &mut BindGroupComponent,
+        )>()
+        .with::<Bloom>();
+    let (_, (bloom_shader, bloom_bind_group_layout, bloom_bind_group)) =
+        query.into_iter().next()?;
+    println!("Fetched bloom entity");
+
+    let mut query = world
+        .query::<BloomBufferQuery>()
+        .with::<BloomDownsampleBuffer>();
+    let mut downsample_buffers = query.into_iter().map(|(_, buffer)| buffer).collect::<Vec<_>>();
+
+    let mut query = world.query::<BloomBufferQuery>().with::<BloomBlurBuffer>();
+    let mut blur_buffers = query.into_iter().map(|(_, buffer)| buffer).collect::<Vec<_>>();
+
+    phosphor_prepare_bloom_bind_groups(
+        device,
+        phosphor_bind_group_layout,
+        bloom_bind_group_layout,
+        bloom_bind_group,
+        sampler,
+        &mut downsample_buffers,
+        &mut blur_buffers,
+    )?;
+
+    let mut query = world
+        .query::<&mut RenderPipelineComponent>()
+        .with::<BloomDownsample>();
+    let (_, bloom_downsample_pipeline) = query.into_iter().next()?;
+
+    let mut query = world
+        .query::<&mut RenderPipelineComponent>()
+        .with::<BloomBlur>();
+    let (_, bloom_blur_pipeline) = query.into_iter().next()?;
+    println!("Fetched bloom pass entities");
+
+    phosphor_prepare_bloom_pipelines(
+        device,
+        phosphor_bind_group_layout,
+        bloom_shader,
+        bloom_downsample_pipeline,
+        bloom_blur_pipeline,
+    )?;
+
+    let mut query = world
+        .query::<(
+            &TextureViewComponent,
+            &mut BindGroupLayoutComponent,
+            &mut BindGroupComponent,
+        )>()
+        .with::<ColorLut>();
+    let (_, (color_lut_view, color_lut_bind_group_layout, color_lut_bind_group)) =
+        query.into_iter().next()?;
+    println!("Fetched color LUT entity");
+
+    phosphor_prepare_color_lut(
+        device,
+        color_lut_bind_group_layout,
+        color_lut_bind_group,
+        color_lut_view,
+    )?;
+
     let mut query = world
         .query::<(&ShaderModuleComponent, &mut RenderPipelineComponent)>()
         .with::<Tonemap>();
@@ -400,12 +661,36 @@ pub fn phosphor_prepare(world: &World, entity: Entity, device: &DeviceComponent)
 
     phosphor_prepare_tonemap(
         device,
-        phosphor_bind_group_layout,
+        [
+            phosphor_bind_group_layout,
+            uniform_bind_group_layout,
+            bloom_bind_group_layout,
+            color_lut_bind_group_layout,
+        ],
         tonemap_shader,
         surface_config,
         tonemap_pipeline,
     )?;
 
+    let mut query = world
+        .query::<(
+            &ShaderModuleComponent,
+            &Changed<SkyboxComponent>,
+            &mut RenderPipelineComponent,
+        )>()
+        .with::<Skybox>();
+    let (_, (skybox_shader, skybox, skybox_pipeline)) = query.into_iter().next()?;
+    println!("Fetched skybox pass entity");
+
+    phosphor_prepare_skybox(
+        device,
+        uniform_bind_group_layout,
+        skybox_shader,
+        surface_config,
+        skybox.mode,
+        skybox_pipeline,
+    )?;
+
     Some(())
 }
 
@@ -431,6 +716,232 @@ pub fn phosphor_update_delta_time_system(world: &mut World) {
     }
 }
 
+// Gather camera and time state into the uniform struct
+pub fn phosphor_update_uniform_data_system(world: &mut World) {
+    let mut query = world
+        .query::<&mut Changed<UniformData>>()
+        .without::<PortalUniform>();
+    let (_, uniform_data) = query.into_iter().next().unwrap();
+
+    let mut query = world
+        .query::<&Changed<PerspectiveMatrixComponent>>()
+        .with::<PerspectiveMatrix>();
+    let (_, perspective_matrix) = query.into_iter().next().unwrap();
+
+    let mut query = world
+        .query::<&Changed<OrthographicMatrixComponent>>()
+        .with::<OrthographicMatrix>();
+    let (_, orthographic_matrix) = query.into_iter().next().unwrap();
+
+    let mut query = world
+        .query::<(&Changed<PositionComponent>, &Changed<RotationComponent>)>()
+        .with::<Camera>();
+    let (_, (position, rotation)) = query.into_iter().next().unwrap();
+
+    let mut query = world.query::<&Changed<TotalTimeComponent>>();
+    let (_, total_time) = query.into_iter().next().unwrap();
+
+    let mut query = world.query::<&Changed<DeltaTimeComponent>>();
+    let (_, delta_time) = query.into_iter().next().unwrap();
+
+    let mut query = world.query::<&Changed<BloomIntensityComponent>>();
+    let (_, bloom_intensity) = query.into_iter().next().unwrap();
+
+    let mut query = world.query::<&Changed<SkyboxComponent>>();
+    let (_, skybox) = query.into_iter().next().unwrap();
+
+    let sources: [&dyn ChangedTrait; 8] = [
+        perspective_matrix,
+        orthographic_matrix,
+        position,
+        rotation,
+        total_time,
+        delta_time,
+        bloom_intensity,
+        skybox,
+    ];
+
+    if !sources.iter().any(|source| source.get_changed()) {
+        return;
+    }
+
+    uniform_data.perspective = (***perspective_matrix).into();
+    uniform_data.orthographic = (***orthographic_matrix).into();
+    uniform_data.cam_pos = position.push(0.0).into();
+    uniform_data.cam_rot = rotation.coords.into();
+    uniform_data.total_time = ***total_time;
+    uniform_data.delta_time = ***delta_time;
+    uniform_data.bloom_intensity = ***bloom_intensity;
+    let ((top_r, top_g, top_b), (bottom_r, bottom_g, bottom_b)) = (skybox.top, skybox.bottom);
+    uniform_data.skybox_top = [top_r, top_g, top_b, 0.0];
+    uniform_data.skybox_bottom = [bottom_r, bottom_g, bottom_b, 0.0];
+    uniform_data.set_changed(true);
+
+    for source in sources {
+        source.set_changed(false);
+    }
+}
+
+/// Returns the first of `rooms` whose hulls contain `position`,
+/// preferring the one with the nearest center if several overlap,
+/// or the room with the nearest center if none contain it
+pub fn containing_or_nearest_room<'a, T: Copy + 'a>(
+    position: &nalgebra::Vector3<f32>,
+    rooms: impl IntoIterator<Item = (T, &'a [ConvexHull], &'a nalgebra::Vector3<f32>)>,
+) -> Option<T> {
+    let nearest = |(_, lhs): &(T, f32), (_, rhs): &(T, f32)| lhs.total_cmp(rhs);
+
+    let (containing, outside): (Vec<_>, Vec<_>) = rooms
+        .into_iter()
+        .map(|(room, hulls, center)| {
+            let contains = hulls.iter().any(|hull| hull.contains(position));
+            (contains, (room, (center - position).norm()))
+        })
+        .partition(|(contains, _)| *contains);
+
+    containing
+        .into_iter()
+        .map(|(_, room)| room)
+        .min_by(nearest)
+        .or_else(|| outside.into_iter().map(|(_, room)| room).min_by(nearest))
+        .map(|(room, _)| room)
+}
+
+// Track the room containing the camera, as the starting point for portal traversal
+pub fn current_room_system(world: &mut World) {
+    let mut query = world.query::<(&RoomComponent, &RoomCenterComponent)>();
+    let rooms = query
+        .into_iter()
+        .map(|(entity, (hulls, center))| (entity, &hulls[..], &**center));
+
+    let mut query = world
+        .query::<(&Changed<PositionComponent>, &mut Changed<CurrentRoomComponent>)>()
+        .with::<Camera>();
+    let (_, (position, current_room)) = query.into_iter().next().unwrap();
+
+    let room = containing_or_nearest_room(position, rooms);
+    if ***current_room != room {
+        println!("Current room: {:?}", room);
+        ***current_room = room;
+        current_room.set_changed(true);
+    }
+}
+
+// Gather portal quads into instance data,
+// and derive the uniforms of the view through them from those of the camera
+pub fn phosphor_update_portals_system(world: &mut World) {
+    let mut query = world.query::<(
+        &PortalComponent,
+        &PositionComponent,
+        &RotationComponent,
+        &ScaleComponent,
+    )>();
+
+    let portals = query
+        .into_iter()
+        .take(MAX_PORTALS)
+        .map(|(_, (offset, position, rotation, scale))| {
+            (
+                **offset,
+                PortalInstanceData {
+                    position: (**position).into(),
+                    rotation: rotation.coords.into(),
+                    extents: [scale.x, scale.y],
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    drop(query);
+
+    // All portals currently share the view through the first
+    let offset = portals
+        .first()
+        .map(|(offset, _)| *offset)
+        .unwrap_or_else(nalgebra::Vector3::zeros);
+
+    let instances = portals
+        .into_iter()
+        .map(|(_, instance)| instance)
+        .collect::<Vec<_>>();
+
+    let mut query = world.query::<&mut Changed<PortalInstanceDataComponent>>();
+    let (_, portal_instance_data) = query.into_iter().next().unwrap();
+
+    if **portal_instance_data != instances {
+        **portal_instance_data = instances;
+        portal_instance_data.set_changed(true);
+    }
+
+    let instance_count = portal_instance_data.len() as u32;
+    drop(query);
+
+    for (_, render_pass_draw) in world
+        .query_mut::<&mut RenderPassDrawComponent>()
+        .with::<PortalStencil>()
+    {
+        render_pass_draw.1 = 0..instance_count;
+    }
+
+    for (_, render_pass_draw) in world
+        .query_mut::<&mut RenderPassDrawComponent>()
+        .with::<PortalDepth>()
+    {
+        render_pass_draw.1 = 0..instance_count;
+    }
+
+    let mut query = world
+        .query::<&Changed<UniformData>>()
+        .without::<PortalUniform>();
+    let (_, uniform_data) = query.into_iter().next().unwrap();
+
+    let mut query = world
+        .query::<&mut Changed<UniformData>>()
+        .with::<PortalUniform>();
+    let (_, portal_uniform_data) = query.into_iter().next().unwrap();
+
+    let mut portal_view = **uniform_data;
+    portal_view.cam_pos[0] += offset.x;
+    portal_view.cam_pos[1] += offset.y;
+    portal_view.cam_pos[2] += offset.z;
+
+    if bytemuck::bytes_of(&**portal_uniform_data) != bytemuck::bytes_of(&portal_view) {
+        **portal_uniform_data = portal_view;
+        portal_uniform_data.set_changed(true);
+    }
+}
+
+// Keep one portal mesh pass per beam mesh pass, so each mesh is also drawn through portals
+pub fn phosphor_portal_mesh_passes_system(world: &mut World) {
+    let beam_meshes = world
+        .query_mut::<&TriangleMeshIdComponent>()
+        .with::<BeamTriangles>()
+        .into_iter()
+        .map(|(_, triangle_mesh)| **triangle_mesh)
+        .collect::<std::collections::BTreeSet<_>>();
+
+    let portal_meshes = world
+        .query_mut::<&TriangleMeshIdComponent>()
+        .with::<PortalTriangles>()
+        .into_iter()
+        .map(|(entity, triangle_mesh)| (entity, **triangle_mesh))
+        .collect::<Vec<_>>();
+
+    for (entity, triangle_mesh) in portal_meshes.iter() {
+        if !beam_meshes.contains(triangle_mesh) {
+            world.despawn(*entity).unwrap();
+        }
+    }
+
+    for triangle_mesh in beam_meshes {
+        if portal_meshes.iter().any(|(_, mesh)| *mesh == triangle_mesh) {
+            continue;
+        }
+
+        let mut builder = portal_triangle_mesh_pass_builder(world, triangle_mesh as u64);
+        world.spawn(builder.build());
+    }
+}
+
 pub fn phosphor_update_timestamp_system(world: &mut World) {
     for (_, timestamp) in world.query_mut::<&mut TimestampComponent>() {
         **timestamp = Instant::now();
@@ -440,8 +951,9 @@ pub fn phosphor_update_timestamp_system(world: &mut World) {
 pub fn phosphor_update_timers_system(world: &mut World) {
     for (_, timer) in world.query_mut::<&mut TimerComponent>() {
         let now = Instant::now();
-        if now.duration_since(timer.timestamp) > timer.duration {
+        if timer.running && now.duration_since(timer.timestamp) >= timer.duration {
             timer.timestamp = now;
+            timer.running = timer.repeat;
             timer.set_changed(true);
         }
     }
@@ -455,6 +967,8 @@ pub fn phosphor_update_oscilloscopes_system(world: &mut World) {
     let mut query = world.query::<&Changed<DeltaTimeComponent>>();
     let (_, delta_time) = query.iter().next().expect("No delta time component");
 
+    // Each oscilloscope traces the dedicated line mesh on its own entity,
+    // so instances of other line meshes are never animated
     for (_, (oscilloscope, vertex_data)) in world
         .query::<(&Oscilloscope, &mut Changed<VertexDataComponent>)>()
         .into_iter()
@@ -579,6 +1093,25 @@ pub fn phosphor_resize_system(world: &mut World) {
     front_bind_group.set_pending();
     back_bind_group.set_pending();
 
+    for (_, (level, desc, view_desc, bind_group)) in world
+        .query::<(
+            &BloomLevelComponent,
+            &mut TextureDescriptorComponent,
+            &mut TextureViewDescriptorComponent,
+            &mut BindGroupComponent,
+        )>()
+        .into_iter()
+    {
+        desc.size = bloom_buffer_size(extent.width, extent.height, **level);
+        desc.set_changed(true);
+        view_desc.set_changed(true);
+        bind_group.set_pending();
+    }
+
+    let mut query = world.query::<&mut BindGroupComponent>().with::<Bloom>();
+    let (_, bloom_bind_group) = query.into_iter().next().unwrap();
+    bloom_bind_group.set_pending();
+
     let aspect = surface_config.width as f32 / surface_config.height as f32;
 
     ***perspective_matrix = super::perspective_matrix(aspect, NEAR_PLANE);
@@ -588,105 +1121,344 @@ pub fn phosphor_resize_system(world: &mut World) {
     orthographic_matrix.set_changed(true);
 }
 
-pub fn phosphor_mouse_moved_system(world: &mut World, (delta_x, delta_y): (f64, f64)) {
+/// Replace the color LUT applied by the tonemap pass,
+/// recreating its texture if the size has changed
+pub fn set_color_lut(world: &mut World, lut: ColorLutComponent) {
     let mut query = world
-        .query::<(&mut EulerAnglesComponent, &mut Changed<RotationComponent>)>()
-        .with::<Camera>();
-    let (_, (euler_angles, rotation)) = query.into_iter().next().unwrap();
+        .query::<(
+            &mut TextureDescriptorComponent,
+            &mut TextureViewDescriptorComponent,
+            &mut BindGroupComponent,
+        )>()
+        .with::<ColorLut>();
+    let (entity, (texture_desc, texture_view_desc, bind_group)) =
+        query.into_iter().next().unwrap();
 
-    euler_angles.y += delta_x as f32 * 0.004;
-    euler_angles.x += delta_y as f32 * 0.004;
+    if texture_desc.size != lut.extent() {
+        texture_desc.size = lut.extent();
+        texture_desc.set_changed(true);
+        texture_view_desc.set_changed(true);
+        bind_group.set_pending();
+    }
 
-    let pitch = nalgebra::UnitQuaternion::from_euler_angles(euler_angles.x, 0.0, 0.0);
-    let yaw = nalgebra::UnitQuaternion::from_euler_angles(0.0, euler_angles.y, 0.0);
-    let quat = pitch * yaw;
+    drop(query);
 
-    ***rotation = quat.into();
-    rotation.set_changed(true);
+    world.insert(entity, color_lut_data_bundle(entity, lut)).unwrap();
 }
 
-pub fn phosphor_key_event_system(world: &mut World, key_event: KeyboardInput) {
-    let (_, player_input) = world
-        .query_mut::<&mut PlayerInputComponent>()
-        .into_iter()
-        .next()
-        .unwrap();
-
-    let key_value = || match key_event.state {
-        ElementState::Pressed => 1.0,
-        ElementState::Released => 0.0,
-    };
-
-    match key_event.virtual_keycode {
-        Some(key) => match key {
-            winit::event::VirtualKeyCode::D => player_input.back = key_value(),
-            winit::event::VirtualKeyCode::E => player_input.forward = key_value(),
-            winit::event::VirtualKeyCode::F => player_input.right = key_value(),
-            winit::event::VirtualKeyCode::S => player_input.left = key_value(),
-            winit::event::VirtualKeyCode::W => player_input.down = key_value(),
-            winit::event::VirtualKeyCode::R => player_input.up = key_value(),
-            _ => (),
-        },
-        _ => (),
+/// Switch the skybox between underlay and overlay
+pub fn set_skybox_mode(world: &mut World, mode: SkyboxMode) {
+    for (_, skybox) in world.query_mut::<&mut Changed<SkyboxComponent>>() {
+        if skybox.mode != mode {
+            skybox.mode = mode;
+            skybox.set_changed(true);
+        }
     }
 }
 
-pub fn phosphor_camera_position_system(world: &mut World) {
-    // Get player input
-    let mut query = world.query::<&mut PlayerInputComponent>();
-    let (_, player_input) = query.into_iter().next().unwrap();
-
-    // Get camera entity
+// Rebuild the skybox pass when its pass order doesn't match its mode,
+// moving it between the beam buffer and the render target
+pub fn phosphor_skybox_pass_system(world: &mut World) {
+    let mut query = world
+        .query::<(
+            &Changed<SkyboxComponent>,
+            Option<&PassOrderComponent>,
+            &mut RenderPipelineComponent,
+        )>()
+        .with::<Skybox>();
+    let (entity, (skybox, pass_order, pipeline)) = if let Some(skybox) = query.into_iter().next() {
+        skybox
+    } else {
+        return;
+    };
+
+    let mode = skybox.mode;
+    if pass_order.map(|pass_order| **pass_order) == Some(skybox_pass_order(mode)) {
+        return;
+    }
+
+    pipeline.set_pending();
+    drop(query);
+
+    let mut builder = skybox_pass_builder(world, entity, mode);
+    world.insert(entity, builder.build()).unwrap();
+}
+
+// Camera view rotation from pitch (x) and yaw (y) euler angles
+fn camera_rotation(euler_angles: &nalgebra::Vector3<f32>) -> nalgebra::UnitQuaternion<f32> {
+    let pitch = nalgebra::UnitQuaternion::from_euler_angles(euler_angles.x, 0.0, 0.0);
+    let yaw = nalgebra::UnitQuaternion::from_euler_angles(0.0, euler_angles.y, 0.0);
+    pitch * yaw
+}
+
+pub fn phosphor_mouse_moved_system(world: &mut World, (delta_x, delta_y): (f64, f64)) {
+    let mut query = world
+        .query::<(&mut EulerAnglesComponent, &mut Changed<RotationComponent>)>()
+        .with::<Camera>();
+    let (_, (euler_angles, rotation)) = query.into_iter().next().unwrap();
+
+    euler_angles.y += delta_x as f32 * 0.004;
+    euler_angles.x += delta_y as f32 * 0.004;
+
+    ***rotation = camera_rotation(euler_angles);
+    rotation.set_changed(true);
+}
+
+// Move the camera to a newly-loaded map's player start, consuming it
+pub fn spawn_camera_at_player_start_system(world: &mut World) {
+    let player_starts = world
+        .query_mut::<(&PositionComponent, &EulerAnglesComponent)>()
+        .with::<PlayerStart>()
+        .into_iter()
+        .map(|(entity, (position, euler_angles))| (entity, **position, **euler_angles))
+        .collect::<Vec<_>>();
+
+    // Maps loaded in quick succession leave the most recent player start last
+    let (position, euler_angles) =
+        if let Some((_, position, euler_angles)) = player_starts.last() {
+            (*position, *euler_angles)
+        } else {
+            return;
+        };
+
+    for (entity, _, _) in player_starts {
+        world.despawn(entity).unwrap();
+    }
+
     let mut query = world
         .query::<(
             &mut Changed<PositionComponent>,
             &mut Changed<RotationComponent>,
+            &mut EulerAnglesComponent,
         )>()
         .with::<Camera>();
-    let (_, (position, rotation)) = query.into_iter().next().unwrap();
+    let (_, (camera_position, camera_rotation_component, camera_euler_angles)) =
+        query.into_iter().next().unwrap();
+
+    ***camera_position = position;
+    camera_position.set_changed(true);
+
+    **camera_euler_angles = euler_angles;
+    ***camera_rotation_component = camera_rotation(&euler_angles);
+    camera_rotation_component.set_changed(true);
+}
+
+// Update the action bound to a physical input
+pub fn phosphor_input_event_system(world: &mut World, input: PhysicalInput, state: ElementState) {
+    let (_, (bindings, actions)) = world
+        .query_mut::<(&InputBindingsComponent, &mut InputActionsComponent)>()
+        .into_iter()
+        .next()
+        .unwrap();
+
+    let action = if let Some(action) = bindings.get(&input) {
+        *action
+    } else {
+        return;
+    };
+
+    let value = match state {
+        ElementState::Pressed => 1.0,
+        ElementState::Released => 0.0,
+    };
+
+    actions.set(action, value);
+}
+
+pub fn phosphor_key_event_system(world: &mut World, key_event: KeyboardInput) {
+    if let Some(key) = key_event.virtual_keycode {
+        phosphor_input_event_system(world, PhysicalInput::Key(key), key_event.state);
+    }
+}
+
+pub fn phosphor_mouse_button_event_system(
+    world: &mut World,
+    button: MouseButton,
+    state: ElementState,
+) {
+    phosphor_input_event_system(world, PhysicalInput::MouseButton(button), state);
+}
+
+// Send camera-relative movement input to the game thread's player character,
+// which resolves it against the map's colliders and moves the camera in return
+pub fn phosphor_camera_position_system(world: &mut World, channel: &WorldChannel) {
+    // Get input actions
+    let mut query = world.query::<&InputActionsComponent>();
+    let (_, actions) = query.into_iter().next().unwrap();
+
+    // Get camera entity
+    let mut query = world.query::<&Changed<RotationComponent>>().with::<Camera>();
+    let (_, rotation) = query.into_iter().next().unwrap();
 
     let mut delta = nalgebra::Vector3::<f32>::default();
 
-    delta.x += player_input.right;
-    delta.x -= player_input.left;
-    delta.z -= player_input.forward;
-    delta.z += player_input.back;
-    delta.y += player_input.up;
-    delta.y -= player_input.down;
+    delta.x += actions.get(InputAction::MoveRight);
+    delta.x -= actions.get(InputAction::MoveLeft);
+    delta.z -= actions.get(InputAction::MoveForward);
+    delta.z += actions.get(InputAction::MoveBack);
+    delta.y += actions.get(InputAction::MoveUp);
+    delta.y -= actions.get(InputAction::MoveDown);
 
-    ***position += rotation.conjugate() * delta;
+    if delta == nalgebra::Vector3::zeros() {
+        return;
+    }
 
-    position.set_changed(true);
+    channel
+        .send_to::<Game>(move_player_message(rotation.conjugate() * delta))
+        .unwrap();
 }
 
+// Send the player character's position to the render thread's camera whenever it moves
+pub fn player_camera_position_system(world: &mut World, channel: &WorldChannel) {
+    for (_, (position, effective)) in world
+        .query_mut::<(&PositionComponent, &EffectiveMovementComponent)>()
+        .with::<Player>()
+    {
+        if **effective == nalgebra::Vector3::zeros() {
+            continue;
+        }
+
+        channel
+            .send_to::<Render>(camera_position_message(**position))
+            .unwrap();
+    }
+}
+
+// Write each mesh's total instance count into its draw arguments,
+// which the frustum cull pass reads and replaces with the visible instance count
 pub fn phosphor_update_beam_mesh_draw_count_system(world: &mut World) {
     let mut query = world
         .query::<&antigen_wgpu::BufferLengthsComponent>()
         .with::<TriangleMeshInstances>();
     let (_, mesh_instance_counts) = query.into_iter().next().unwrap();
 
+    // Meshes are looked up by ID, since those of a replaced map leave gaps when despawned
     let mut query = world
-        .query::<&mut Changed<TriangleMeshDataComponent>>()
+        .query::<(&TriangleMeshIdComponent, &mut Changed<TriangleMeshDataComponent>)>()
         .with::<BeamTriangles>();
 
-    for (i, (_, triangle_mesh_data)) in query.into_iter().enumerate() {
-        triangle_mesh_data[0].instance_count = mesh_instance_counts.read()[i] as u32;
+    for (_, (triangle_mesh, triangle_mesh_data)) in query.into_iter() {
+        triangle_mesh_data[0].instance_count =
+            mesh_instance_counts.read()[**triangle_mesh as usize] as u32;
         triangle_mesh_data.set_changed(true);
     }
 }
 
+// Dispatch one frustum cull workgroup per triangle mesh
+pub fn phosphor_update_frustum_cull_dispatch_system(world: &mut World) {
+    let mut query = world
+        .query::<&antigen_wgpu::BufferLengthComponent>()
+        .with::<TriangleMeshes>();
+    let (_, triangle_mesh_count) = query.into_iter().next().unwrap();
+    let triangle_mesh_count = triangle_mesh_count.load(Ordering::Relaxed) as u32;
+
+    let mut query = world
+        .query::<&mut ComputePassDispatchComponent>()
+        .with::<FrustumCull>();
+    let (_, dispatch) = query.into_iter().next().unwrap();
+
+    dispatch.0 = triangle_mesh_count;
+}
+
+// Grow the line smear buffers to fit every line instance,
+// dispatching one invocation per history slot and drawing one smear per line instance
+pub fn phosphor_update_line_smear_system(world: &mut World) {
+    let mut query = world
+        .query::<&antigen_wgpu::BufferLengthComponent>()
+        .with::<LineInstances>();
+    let (_, line_instance_count) = query.into_iter().next().unwrap();
+    let line_instance_count = line_instance_count.load(Ordering::Relaxed);
+    drop(query);
+
+    let smear_buffers = [
+        (
+            get_tagged_buffer::<LineSmearFront>(world),
+            buffer_size_of::<LineHistoryData>(),
+        ),
+        (
+            get_tagged_buffer::<LineSmearBack>(world),
+            buffer_size_of::<LineHistoryData>(),
+        ),
+        (
+            get_tagged_buffer::<LineSmears>(world),
+            buffer_size_of::<LineSmearData>(),
+        ),
+    ];
+
+    for (entity, stride) in smear_buffers {
+        antigen_wgpu::grow_buffer(world, entity, line_instance_count * stride);
+    }
+
+    // Slots past the line instance count are still dispatched to invalidate their history
+    let history_capacity = world
+        .get::<BufferDescriptorComponent>(smear_buffers[0].0)
+        .unwrap()
+        .size
+        / buffer_size_of::<LineHistoryData>();
+
+    let mut query = world
+        .query::<&mut ComputePassDispatchComponent>()
+        .with::<LineSmear>();
+    let (_, dispatch) = query.into_iter().next().unwrap();
+    dispatch.0 =
+        (history_capacity as u32 + LINE_SMEAR_WORKGROUP_SIZE - 1) / LINE_SMEAR_WORKGROUP_SIZE;
+
+    let mut query = world
+        .query::<&mut RenderPassDrawComponent>()
+        .with::<BeamLineSmears>();
+    let (_, render_pass_draw) = query.into_iter().next().unwrap();
+    render_pass_draw.1 = 0..line_instance_count as u32;
+
+    let mut query = world.query::<&mut Changed<LineSmearUniformData>>();
+    let (_, uniform) = query.into_iter().next().unwrap();
+    if uniform.line_count != line_instance_count as u32 {
+        uniform.line_count = line_instance_count as u32;
+        uniform.set_changed(true);
+    }
+}
+
+// Entity holding the buffer tagged with T
+fn get_tagged_buffer<T: hecs::Component>(world: &World) -> Entity {
+    let mut query = world.query::<&BufferComponent>().with::<T>();
+    let (entity, _) = query.into_iter().next().unwrap();
+    entity
+}
+
+pub fn phosphor_update_beam_mesh_instance_offsets_system(world: &mut World) {
+    for (_, (triangle_mesh, offsets)) in world
+        .query_mut::<(&TriangleMeshIdComponent, &mut RenderPassBindGroupOffsetsComponent)>()
+    {
+        // Index into the storage bind group's instance buffer by mesh
+        let instance_base = buffer_size_of::<TriangleMeshInstanceData>()
+            * (**triangle_mesh as usize * MAX_TRIANGLE_MESH_INSTANCES) as BufferAddress;
+
+        let storage_offsets = vec![(1, vec![instance_base as DynamicOffset])];
+        if ***offsets != storage_offsets {
+            ***offsets = storage_offsets;
+            offsets.set_changed(true);
+        }
+    }
+}
+
 pub fn phosphor_update_beam_line_draw_count_system(world: &mut World) {
     let mut query = world
         .query::<&antigen_wgpu::BufferLengthComponent>()
         .with::<LineInstances>();
     let (_, line_instance_count) = query.into_iter().next().unwrap();
 
+    let line_instance_count = line_instance_count.load(Ordering::Relaxed) as u32;
+
     let mut query = world
         .query::<&mut RenderPassDrawComponent>()
         .with::<BeamLines>();
     let (_, render_pass_draw) = query.into_iter().next().unwrap();
+    render_pass_draw.1 = 0..line_instance_count;
 
-    render_pass_draw.1 = 0..(line_instance_count.load(Ordering::Relaxed) as u32);
+    let mut query = world
+        .query::<&mut RenderPassDrawComponent>()
+        .with::<PortalLines>();
+    let (_, render_pass_draw) = query.into_iter().next().unwrap();
+    render_pass_draw.1 = 0..line_instance_count;
 }
 
 pub fn assemble_triangle_mesh_instances_system(world: &mut World) {
@@ -728,19 +1500,32 @@ pub fn assemble_triangle_mesh_instances_system(world: &mut World) {
         .collect::<Vec<_>>();
 
     for (entity, mesh, position, rotation, scale) in instances {
-        if let Some(mut builder) = triangle_mesh_instance_builder(
+        let copy_to_entity = if let Some(copy_to_entity) = recycle_triangle_mesh_instance(
             world,
             &mesh,
             position.into(),
             rotation.into(),
             scale.into(),
         ) {
+            Some(copy_to_entity)
+        } else {
+            triangle_mesh_instance_builder(
+                world,
+                &mesh,
+                position.into(),
+                rotation.into(),
+                scale.into(),
+            )
+            .map(|mut builder| world.spawn(builder.build()))
+        };
+
+        if let Some(copy_to_entity) = copy_to_entity {
             world
                 .get_mut::<TriangleMeshInstanceComponent>(entity)
                 .unwrap()
                 .set_ready();
 
-            let copy_to_entity = vec![world.spawn(builder.build())];
+            let copy_to_entity = vec![copy_to_entity];
 
             world
                 .insert(
@@ -769,10 +1554,12 @@ pub fn assemble_line_mesh_instances_system(world: &mut World) {
             Option<&PositionComponent>,
             Option<&RotationComponent>,
             Option<&ScaleComponent>,
+            Option<&LineColorComponent>,
+            Option<&LineIntensityComponent>,
         )>()
         .into_iter()
         .flat_map(
-            |(entity, (line_mesh_instance, position, rotation, scale))| {
+            |(entity, (line_mesh_instance, position, rotation, scale, color, intensity))| {
                 let position = if let Some(position) = position {
                     **position
                 } else {
@@ -791,8 +1578,11 @@ pub fn assemble_line_mesh_instances_system(world: &mut World) {
                     nalgebra::vector![1.0, 1.0, 1.0]
                 };
 
+                let color = color.map(|color| **color).unwrap_or(nalgebra::vector![1.0, 1.0, 1.0]);
+                let intensity = intensity.map(|intensity| **intensity).unwrap_or(1.0);
+
                 if let LazyComponent::Pending(mesh) = &**line_mesh_instance {
-                    Some((entity, mesh.clone(), position, rotation, scale))
+                    Some((entity, mesh.clone(), position, rotation, scale, color, intensity))
                 } else {
                     None
                 }
@@ -800,16 +1590,37 @@ pub fn assemble_line_mesh_instances_system(world: &mut World) {
         )
         .collect::<Vec<_>>();
 
-    for (entity, mesh, position, rotation, scale) in instances {
-        if let Some(mut builder) =
-            line_mesh_instance_builder(world, position.into(), rotation.into(), scale.into(), &mesh)
-        {
+    for (entity, mesh, position, rotation, scale, color, intensity) in instances {
+        let copy_to_entity = if let Some(copy_to_entity) = recycle_line_mesh_instance(
+            world,
+            position.into(),
+            rotation.into(),
+            scale.into(),
+            color.into(),
+            intensity.into(),
+            &mesh,
+        ) {
+            Some(copy_to_entity)
+        } else {
+            line_mesh_instance_builder(
+                world,
+                position.into(),
+                rotation.into(),
+                scale.into(),
+                color.into(),
+                intensity.into(),
+                &mesh,
+            )
+            .map(|mut builder| world.spawn(builder.build()))
+        };
+
+        if let Some(copy_to_entity) = copy_to_entity {
             world
                 .get_mut::<LineMeshInstanceComponent>(entity)
                 .unwrap()
                 .set_ready();
 
-            let copy_to_entity = vec![world.spawn(builder.build())];
+            let copy_to_entity = vec![copy_to_entity];
 
             world
                 .insert(
@@ -822,6 +1633,12 @@ pub fn assemble_line_mesh_instances_system(world: &mut World) {
                             copy_to_entity.clone(),
                         ),
                         CopyToComponent::<LineMeshInstance, ScaleComponent>::construct(
+                            copy_to_entity.clone(),
+                        ),
+                        CopyToComponent::<LineMeshInstance, LineColorComponent>::construct(
+                            copy_to_entity.clone(),
+                        ),
+                        CopyToComponent::<LineMeshInstance, LineIntensityComponent>::construct(
                             copy_to_entity,
                         ),
                     ),
@@ -831,6 +1648,452 @@ pub fn assemble_line_mesh_instances_system(world: &mut World) {
     }
 }
 
+/// Despawn a triangle mesh instance, hiding its buffer slot and returning it to the slot allocator
+///
+/// Free slots left at the end of their mesh's instances are released,
+/// shrinking that mesh's instance count instead of waiting for reuse.
+pub fn despawn_triangle_mesh_instance(world: &mut World, entity: Entity) -> Option<()> {
+    free_mesh_instance_slots::<
+        TriangleMeshInstance,
+        TriangleMeshInstances,
+        TriangleMeshInstanceSlots,
+    >(world, entity, |world, copy_to_entity| {
+        triangle_mesh_instance_slot(world, copy_to_entity).map(|(triangle_mesh, _)| triangle_mesh)
+    })?;
+
+    truncate_triangle_mesh_instances(world)
+}
+
+/// Despawn triangle mesh instances marked PendingDespawn, freeing their buffer slots for reuse
+pub fn despawn_triangle_mesh_instances_system(world: &mut World) {
+    despawn_pending_mesh_instances::<TriangleMeshInstanceComponent>(
+        world,
+        despawn_triangle_mesh_instance,
+    );
+}
+
+// Despawn a mesh instance, hiding each of its buffer data entities
+// and freeing them into the slot allocator on the instance buffer entity tagged with B
+// under the key returned by `slot_key`
+fn free_mesh_instance_slots<I, B, T>(
+    world: &mut World,
+    entity: Entity,
+    slot_key: fn(&World, Entity) -> Option<u32>,
+) -> Option<()>
+where
+    I: Send + Sync + 'static,
+    B: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+{
+    let copy_to_entities = world
+        .get::<CopyToComponent<I, PositionComponent>>(entity)
+        .ok()
+        .map(|copy_to| copy_to.entities().clone())
+        .unwrap_or_default();
+
+    world.despawn(entity).ok()?;
+
+    let mesh_instance_entity = get_tagged_entity::<B>(world)?;
+
+    for copy_to_entity in copy_to_entities {
+        // Zero scale hides the freed instance until its slot is reused
+        if let Ok(mut scale) = world.get_mut::<Changed<ScaleComponent>>(copy_to_entity) {
+            ***scale = nalgebra::Vector3::zeros();
+            scale.set_changed(true);
+        }
+
+        let key = slot_key(world, copy_to_entity)?;
+
+        world
+            .get_mut::<Usage<T, SlotAllocator<u32>>>(mesh_instance_entity)
+            .ok()?
+            .free(key, copy_to_entity);
+    }
+
+    Some(())
+}
+
+// Despawn each mesh instance carrying component C that is marked PendingDespawn
+fn despawn_pending_mesh_instances<C: hecs::Component>(
+    world: &mut World,
+    despawn: fn(&mut World, Entity) -> Option<()>,
+) {
+    let entities = world
+        .query_mut::<&C>()
+        .with::<PendingDespawn>()
+        .into_iter()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+
+    for entity in entities {
+        despawn(world, entity);
+    }
+}
+
+// Triangle mesh and instance index within that mesh's slice of the instance buffer
+// for a triangle mesh instance's buffer data entity
+fn triangle_mesh_instance_slot(world: &World, entity: Entity) -> Option<(u32, BufferAddress)> {
+    let index = world
+        .get::<antigen_wgpu::BufferWriteComponent<PositionComponent>>(entity)
+        .ok()?
+        .offset()
+        / buffer_size_of::<TriangleMeshInstanceData>();
+
+    let max_instances = MAX_TRIANGLE_MESH_INSTANCES as BufferAddress;
+    Some(((index / max_instances) as u32, index % max_instances))
+}
+
+// Release freed slots at the end of each triangle mesh's instances,
+// shrinking its instance count so the freed instances are no longer drawn
+fn truncate_triangle_mesh_instances(world: &mut World) -> Option<()> {
+    let triangle_mesh_instance_entity = get_tagged_entity::<TriangleMeshInstances>(world)?;
+
+    loop {
+        let top = {
+            let heads = world
+                .get::<antigen_wgpu::BufferLengthsComponent>(triangle_mesh_instance_entity)
+                .ok()?;
+            let heads = heads.read();
+
+            let slots = world
+                .get::<TriangleMeshInstanceSlotsComponent>(triangle_mesh_instance_entity)
+                .ok()?;
+
+            let top = slots.iter().find_map(|(triangle_mesh, entity)| {
+                let (_, instance) = triangle_mesh_instance_slot(world, entity)?;
+                if instance + 1 == *heads.get(*triangle_mesh as usize)? {
+                    Some((*triangle_mesh, entity))
+                } else {
+                    None
+                }
+            });
+            top
+        };
+
+        let (triangle_mesh, entity) = if let Some(top) = top {
+            top
+        } else {
+            return Some(());
+        };
+
+        world
+            .get_mut::<TriangleMeshInstanceSlotsComponent>(triangle_mesh_instance_entity)
+            .ok()?
+            .remove(entity);
+
+        world.despawn(entity).ok()?;
+
+        *world
+            .get::<antigen_wgpu::BufferLengthsComponent>(triangle_mesh_instance_entity)
+            .ok()?
+            .write()
+            .get_mut(triangle_mesh as usize)? -= 1;
+    }
+}
+
+/// Despawn a line mesh instance, hiding its buffer slot and returning it to the slot allocator
+///
+/// Free slots left at the end of the instance buffers are released,
+/// shrinking the buffers' lengths instead of waiting for reuse.
+pub fn despawn_line_mesh_instance(world: &mut World, entity: Entity) -> Option<()> {
+    // Line instances are allocated alongside their mesh instance,
+    // so slots are keyed by line count
+    free_mesh_instance_slots::<LineMeshInstance, LineMeshInstances, LineMeshInstanceSlots>(
+        world,
+        entity,
+        |world, copy_to_entity| {
+            world
+                .get::<Changed<LineInstanceDataComponent>>(copy_to_entity)
+                .ok()
+                .map(|line_instances| line_instances.len() as u32)
+        },
+    )?;
+
+    truncate_line_mesh_instances(world)
+}
+
+/// Despawn line mesh instances marked PendingDespawn, freeing their buffer slots for reuse
+pub fn despawn_line_mesh_instances_system(world: &mut World) {
+    despawn_pending_mesh_instances::<LineMeshInstanceComponent>(world, despawn_line_mesh_instance);
+}
+
+// Mesh instance index, first line instance index and line count
+// of a line mesh instance's buffer data entity
+fn line_mesh_instance_slot(
+    world: &World,
+    entity: Entity,
+) -> Option<(BufferAddress, BufferAddress, BufferAddress)> {
+    let mesh_instance = world
+        .get::<antigen_wgpu::BufferWriteComponent<PositionComponent>>(entity)
+        .ok()?
+        .offset()
+        / buffer_size_of::<LineMeshInstanceData>();
+
+    let line_instance = world
+        .get::<antigen_wgpu::BufferWriteComponent<LineInstanceDataComponent>>(entity)
+        .ok()?
+        .offset()
+        / buffer_size_of::<LineInstanceData>();
+
+    let line_count = world
+        .get::<Changed<LineInstanceDataComponent>>(entity)
+        .ok()?
+        .len() as BufferAddress;
+
+    Some((mesh_instance, line_instance, line_count))
+}
+
+// Release freed slots at the end of the line mesh instance and line instance buffers,
+// shrinking their lengths so the freed lines are no longer drawn
+fn truncate_line_mesh_instances(world: &mut World) -> Option<()> {
+    let line_mesh_instance_entity = get_tagged_entity::<LineMeshInstances>(world)?;
+    let line_instance_entity = get_tagged_entity::<LineInstances>(world)?;
+
+    loop {
+        let mesh_instance_head = world
+            .get::<antigen_wgpu::BufferLengthComponent>(line_mesh_instance_entity)
+            .ok()?
+            .load(Ordering::Relaxed);
+
+        let line_instance_head = world
+            .get::<antigen_wgpu::BufferLengthComponent>(line_instance_entity)
+            .ok()?
+            .load(Ordering::Relaxed);
+
+        let top = {
+            let slots = world
+                .get::<LineMeshInstanceSlotsComponent>(line_mesh_instance_entity)
+                .ok()?;
+
+            let top = slots.iter().find_map(|(_, entity)| {
+                let (mesh_instance, line_instance, line_count) =
+                    line_mesh_instance_slot(world, entity)?;
+
+                if mesh_instance + 1 == mesh_instance_head
+                    && line_instance + line_count == line_instance_head
+                {
+                    Some((entity, line_count))
+                } else {
+                    None
+                }
+            });
+            top
+        };
+
+        let (entity, line_count) = if let Some(top) = top {
+            top
+        } else {
+            return Some(());
+        };
+
+        world
+            .get_mut::<LineMeshInstanceSlotsComponent>(line_mesh_instance_entity)
+            .ok()?
+            .remove(entity);
+
+        world.despawn(entity).ok()?;
+
+        world
+            .get::<antigen_wgpu::BufferLengthComponent>(line_mesh_instance_entity)
+            .ok()?
+            .fetch_sub(1, Ordering::Relaxed);
+
+        world
+            .get::<antigen_wgpu::BufferLengthComponent>(line_instance_entity)
+            .ok()?
+            .fetch_sub(line_count, Ordering::Relaxed);
+    }
+}
+
+// Instances to keep, move between cells, spawn and despawn when redrawing a block of text
+struct TextDamage<T> {
+    kept: BTreeMap<TextCell, (Glyph, T)>,
+    moved: Vec<(TextCell, Glyph, T)>,
+    spawned: Vec<(TextCell, Glyph)>,
+    despawned: Vec<T>,
+}
+
+// Diff the glyphs drawn by a block of text against its new layout,
+// leaving cells that still show the same glyph untouched
+// and moving vacated instances to cells showing their glyph elsewhere
+fn text_damage<T: Copy>(
+    drawn: &BTreeMap<TextCell, (Glyph, T)>,
+    cells: &BTreeMap<TextCell, Glyph>,
+) -> TextDamage<T> {
+    let mut kept = BTreeMap::new();
+    let mut vacated = BTreeMap::<Glyph, Vec<T>>::new();
+
+    for (cell, (glyph, instance)) in drawn {
+        if cells.get(cell) == Some(glyph) {
+            kept.insert(*cell, (*glyph, *instance));
+        } else {
+            vacated.entry(*glyph).or_default().push(*instance);
+        }
+    }
+
+    let mut moved = vec![];
+    let mut spawned = vec![];
+    for (cell, glyph) in cells {
+        if kept.contains_key(cell) {
+            continue;
+        }
+
+        match vacated.get_mut(glyph).and_then(Vec::pop) {
+            Some(instance) => moved.push((*cell, *glyph, instance)),
+            None => spawned.push((*cell, *glyph)),
+        }
+    }
+
+    TextDamage {
+        kept,
+        moved,
+        spawned,
+        despawned: vacated.into_values().flatten().collect(),
+    }
+}
+
+// Redraw the damaged cells of changed text blocks with character line mesh instances
+pub fn text_damage_system(world: &mut World) {
+    let texts = world
+        .query_mut::<(
+            &mut TextComponent,
+            &TextCellsComponent,
+            &PositionComponent,
+            &RotationComponent,
+            &ScaleComponent,
+            Option<&MapEntity>,
+        )>()
+        .into_iter()
+        .filter(|(_, (text, ..))| text.get_changed())
+        .map(
+            |(entity, (text, drawn, position, rotation, scale, map_entity))| {
+                text.set_changed(false);
+                let damage = text_damage(drawn, &text.cells());
+                (entity, damage, **position, **rotation, **scale, map_entity.is_some())
+            },
+        )
+        .collect::<Vec<_>>();
+
+    for (entity, damage, position, rotation, scale, map_entity) in texts {
+        let cell_position = |(column, row): TextCell| {
+            let offset = nalgebra::vector![
+                (column as f32 - TEXT_COLUMN_OFFSET) * TEXT_COLUMN_WIDTH,
+                row as f32 * -TEXT_ROW_HEIGHT,
+                0.0
+            ];
+            position + rotation * offset.component_mul(&scale)
+        };
+
+        for instance in damage.despawned {
+            despawn_line_mesh_instance(world, instance);
+        }
+
+        let mut drawn = damage.kept;
+
+        for (cell, glyph, instance) in damage.moved {
+            **world.get_mut::<PositionComponent>(instance).unwrap() = cell_position(cell);
+            drawn.insert(cell, (glyph, instance));
+        }
+
+        for (cell, glyph) in damage.spawned {
+            let [r, g, b] = glyph.color;
+
+            let mut builder = EntityBuilder::new();
+            builder.add(PositionComponent::construct(cell_position(cell)));
+            builder.add(RotationComponent::construct(rotation));
+            builder.add(ScaleComponent::construct(scale));
+            builder.add(LineColorComponent::construct(
+                nalgebra::vector![r as f32, g as f32, b as f32] / 255.0,
+            ));
+            builder.add(LineMeshInstanceComponent::construct(Cow::Owned(format!(
+                "char_{}",
+                glyph.c
+            ))));
+
+            if glyph.blink {
+                builder.add(LineIntensityComponent::construct(1.0));
+                builder.add(BlinkComponent::construct(TEXT_BLINK_PERIOD));
+            }
+
+            // Characters are despawned along with the map that spawned their text
+            if map_entity {
+                builder.add(MapEntity);
+            }
+
+            drawn.insert(cell, (glyph, world.spawn(builder.build())));
+        }
+
+        **world.get_mut::<TextCellsComponent>(entity).unwrap() = drawn;
+    }
+}
+
+// Toggle the intensity of blinking line mesh instances,
+// timed against the system clock so that blinking text stays in phase
+pub fn blink_system(world: &mut World) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    for (_, (intensity, period)) in
+        world.query_mut::<(&mut LineIntensityComponent, &BlinkComponent)>()
+    {
+        let on = (now.as_secs_f64() / period.as_secs_f64()) % 2.0 < 1.0;
+        **intensity = if on { 1.0 } else { 0.0 };
+    }
+}
+
+/// Despawn every entity spawned from a map, releasing the resources it holds
+///
+/// Physics objects are removed from the backend, mesh instances are hidden,
+/// and named entities are unregistered.
+pub fn despawn_map_entities(world: &mut World) {
+    let entities = world
+        .query_mut::<(
+            Option<&RigidBodyComponent>,
+            Option<&ColliderComponent>,
+            Option<&NamedEntityComponent>,
+        )>()
+        .with::<MapEntity>()
+        .into_iter()
+        .map(|(entity, (rigid_body, collider, name))| {
+            let physics = rigid_body.is_some() || collider.is_some();
+            (entity, physics, name.map(|name| (**name).clone()))
+        })
+        .collect::<Vec<_>>();
+
+    if entities.is_empty() {
+        return;
+    }
+
+    println!("Despawning {} map entities", entities.len());
+
+    // Remove physics objects before their entities to avoid leaking backend handles
+    if entities.iter().any(|(_, physics, _)| *physics) {
+        for (entity, _, _) in entities.iter().filter(|(_, physics, _)| *physics) {
+            world.insert_one(*entity, PendingRemoval).unwrap();
+        }
+        antigen_rapier3d::remove_colliders_system(world);
+        antigen_rapier3d::remove_rigid_bodies_system(world);
+    }
+
+    for (entity, _, name) in entities {
+        if let Some(name) = name {
+            let mut named_entities = get_named_entities_component_mut(world).unwrap();
+            if let Some(named) = named_entities.get_mut(&name) {
+                named.remove(&entity);
+            }
+        }
+
+        if world.get::<LineMeshInstanceComponent>(entity).is_ok() {
+            despawn_line_mesh_instance(world, entity);
+        } else if world.get::<TriangleMeshInstanceComponent>(entity).is_ok() {
+            despawn_triangle_mesh_instance(world, entity);
+        } else {
+            world.despawn(entity).unwrap();
+        }
+    }
+}
+
 pub fn movers_position_system(world: &mut World) {
     for (_, (position, position_offset, speed, mover_open)) in world
         .query_mut::<(
@@ -890,7 +2153,7 @@ pub fn movers_rotation_system(world: &mut World) {
 pub fn intersection_event_output_system(world: &mut World) {
     let mut query = world.query::<&antigen_rapier3d::EventCollector>();
     for (_, event_collector) in query.into_iter() {
-        for intersection in event_collector.intersection_events().iter() {
+        for intersection in event_collector.consume_intersection_events().iter() {
             // Find the entity corresponding to this collider
             let mut query =
                 world.query::<(&ColliderComponent, &mut ColliderEventOutputComponent)>();
@@ -916,6 +2179,36 @@ pub fn intersection_event_output_system(world: &mut World) {
     }
 }
 
+// Emit impact sound events for colliders involved in impacts, scaled by impulse
+pub fn impact_sound_event_output_system(world: &mut World) {
+    let mut query = world.query::<&antigen_rapier3d::EventCollector>();
+    for (_, event_collector) in query.into_iter() {
+        for impact in event_collector.impact_events().iter() {
+            let mut query = world.query::<(
+                &ColliderComponent,
+                &ImpactSoundComponent,
+                &mut ImpactSoundEventOutputComponent,
+            )>();
+
+            for (_, (_, impact_sound, output)) in
+                query
+                    .into_iter()
+                    .filter(|(_, (collider, _, _))| match collider {
+                        LazyComponent::Ready(collider) => {
+                            *collider == impact.collider1 || *collider == impact.collider2
+                        }
+                        _ => false,
+                    })
+            {
+                output.push(ImpactSoundEvent {
+                    sound: impact_sound.sound.clone(),
+                    volume: (impact.impulse / impact_sound.full_volume_impulse).min(1.0),
+                });
+            }
+        }
+    }
+}
+
 pub fn movers_event_input_system(world: &mut World) {
     for (_, (events, mover_open)) in world
         .query_mut::<(&mut MoverEventInputComponent, &mut MoverOpenComponent)>()
@@ -953,6 +2246,23 @@ where
     }
 }
 
+// Output the events of elapsed timers, resetting their changed flag so each elapse fires once
+pub fn timer_event_output_system<T>(world: &mut World)
+where
+    T: Clone + Send + Sync + 'static,
+{
+    for (_, (timer, event, output)) in world.query_mut::<(
+        &mut TimerComponent,
+        &TimerEventComponent<T>,
+        &mut EventOutputComponent<T>,
+    )>() {
+        if timer.get_changed() {
+            output.push((**event).clone());
+            timer.set_changed(false);
+        }
+    }
+}
+
 pub fn event_transform_system<I, O, F>(world: &mut World, mut f: F)
 where
     I: Send + Sync + 'static,
@@ -990,3 +2300,548 @@ where
         output.clear()
     }
 }
+
+// Outline each collider with a line mesh instance while collider debugging is enabled,
+// sending meshes for shapes without a shared unit outline to the render thread to be built
+pub fn debug_colliders_system(world: &mut World, channel: &WorldChannel) {
+    let (enabled, mut outlines, mut meshes) = if let Some((_, (enabled, outlines, meshes))) = world
+        .query_mut::<(
+            &DebugCollidersComponent,
+            &mut DebugColliderOutlinesComponent,
+            &mut DebugColliderMeshesComponent,
+        )>()
+        .into_iter()
+        .next()
+    {
+        (
+            **enabled,
+            std::mem::take(&mut **outlines),
+            std::mem::take(&mut **meshes),
+        )
+    } else {
+        return;
+    };
+
+    let colliders = if enabled {
+        world
+            .query_mut::<&ColliderSet>()
+            .into_iter()
+            .next()
+            .map(|(_, collider_set)| {
+                collider_set
+                    .iter()
+                    .map(|(handle, collider)| {
+                        let (mesh, offset, scale) = debug_collider_unit_outline(collider.shape());
+                        let geometry = if mesh.is_none() && !outlines.contains_key(&handle) {
+                            debug_collider_geometry(collider.shape())
+                        } else {
+                            None
+                        };
+                        let outline = (mesh, offset, scale, geometry);
+                        let bounds = debug_collider_bounds(collider.shape());
+                        (handle, *collider.position(), outline, bounds)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    } else {
+        vec![]
+    };
+
+    // Outlines of removed colliders are despawned, as are all outlines once disabled
+    let live = colliders
+        .iter()
+        .map(|(handle, ..)| *handle)
+        .collect::<std::collections::HashSet<_>>();
+
+    outlines.retain(|handle, (entity, ..)| {
+        let keep = live.contains(handle);
+        if !keep {
+            despawn_line_mesh_instance(world, *entity);
+        }
+        keep
+    });
+
+    for (handle, isometry, (mesh, offset, scale, geometry), (bounds_offset, bounds_scale)) in
+        colliders
+    {
+        // Convert from rapier's nalgebra version at the boundary
+        let transform = |offset, scale: rapier3d::prelude::Vector<f32>| {
+            let position = isometry * rapier3d::prelude::Point::from(offset);
+            let rotation = isometry.rotation;
+            (
+                nalgebra::vector![position.x, position.y, position.z],
+                nalgebra::UnitQuaternion::new_unchecked(nalgebra::Quaternion::new(
+                    rotation.w, rotation.i, rotation.j, rotation.k,
+                )),
+                nalgebra::vector![scale.x, scale.y, scale.z],
+            )
+        };
+
+        if let Some((entity, offset, scale)) = outlines.get(&handle) {
+            let (outline_position, outline_rotation, outline_scale) = world
+                .query_one_mut::<(
+                    &mut PositionComponent,
+                    &mut RotationComponent,
+                    &mut ScaleComponent,
+                )>(*entity)
+                .unwrap();
+
+            let (position, rotation, scale) = transform(*offset, *scale);
+            **outline_position = position;
+            **outline_rotation = rotation;
+            **outline_scale = scale;
+            continue;
+        }
+
+        let geometry_mesh = geometry
+            .and_then(|geometry| debug_collider_mesh(&mut meshes, geometry, channel));
+
+        let (mesh, offset, scale) = match (mesh, geometry_mesh) {
+            (Some(mesh), _) => (Cow::Borrowed(mesh), offset, scale),
+            (None, Some(mesh)) => (Cow::Owned(mesh), offset, scale),
+            (None, None) => (
+                Cow::Borrowed(DEBUG_CUBOID_MESH),
+                bounds_offset,
+                bounds_scale,
+            ),
+        };
+
+        let (position, rotation, instance_scale) = transform(offset, scale);
+
+        let mut builder = EntityBuilder::new();
+        builder.add(PositionComponent::construct(position));
+        builder.add(RotationComponent::construct(rotation));
+        builder.add(ScaleComponent::construct(instance_scale));
+        builder.add(LineMeshInstanceComponent::construct(mesh));
+
+        outlines.insert(handle, (world.spawn(builder.build()), offset, scale));
+    }
+
+    for (_, (debug_outlines, debug_meshes)) in world.query_mut::<(
+        &mut DebugColliderOutlinesComponent,
+        &mut DebugColliderMeshesComponent,
+    )>() {
+        **debug_outlines = std::mem::take(&mut outlines);
+        **debug_meshes = std::mem::take(&mut meshes);
+    }
+}
+
+// Name of the outline mesh for a collider's geometry,
+// sending it to the render thread to be built the first time that geometry is seen
+//
+// Returns None once outline meshes have used their share of the line buffers,
+// as line meshes are never freed.
+fn debug_collider_mesh(
+    meshes: &mut std::collections::HashMap<u64, (String, usize)>,
+    (vertices, indices): (Vec<VertexData>, Vec<u32>),
+    channel: &WorldChannel,
+) -> Option<String> {
+    let key = {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        bytemuck::cast_slice::<_, u8>(&vertices).hash(&mut hasher);
+        indices.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    if let Some((mesh, _)) = meshes.get(&key) {
+        return Some(mesh.clone());
+    }
+
+    let index_count = meshes.values().map(|(_, count)| count).sum::<usize>();
+    if meshes.len() >= MAX_DEBUG_COLLIDER_MESHES
+        || index_count + indices.len() > MAX_DEBUG_COLLIDER_LINE_INDICES
+    {
+        return None;
+    }
+
+    let mesh = format!("debug_collider_{:016x}", key);
+    meshes.insert(key, (mesh.clone(), indices.len()));
+
+    channel
+        .send_to::<Render>(assemble_debug_collider_mesh_message(
+            mesh.clone().into(),
+            vertices,
+            indices,
+        ))
+        .unwrap();
+
+    Some(mesh)
+}
+
+// Shared unit mesh, local offset and scale outlining a collider shape,
+// or no mesh if the shape is outlined by a mesh built from its own triangles
+fn debug_collider_unit_outline(
+    shape: &dyn Shape,
+) -> (
+    Option<&'static str>,
+    rapier3d::prelude::Vector<f32>,
+    rapier3d::prelude::Vector<f32>,
+) {
+    let zero = rapier3d::prelude::Vector::zeros();
+
+    if let Some(cuboid) = shape.as_cuboid() {
+        return (Some(DEBUG_CUBOID_MESH), zero, cuboid.half_extents);
+    }
+
+    if let Some(ball) = shape.as_ball() {
+        return (
+            Some(DEBUG_BALL_MESH),
+            zero,
+            rapier3d::prelude::Vector::repeat(ball.radius),
+        );
+    }
+
+    if has_debug_collider_triangles(shape) {
+        return (None, zero, rapier3d::prelude::Vector::repeat(1.0));
+    }
+
+    // Dense triangle meshes and unsupported shapes are outlined by their bounding box
+    let (offset, scale) = debug_collider_bounds(shape);
+    (Some(DEBUG_CUBOID_MESH), offset, scale)
+}
+
+// Local offset and scale fitting the unit cuboid outline to a shape's bounding box
+fn debug_collider_bounds(
+    shape: &dyn Shape,
+) -> (
+    rapier3d::prelude::Vector<f32>,
+    rapier3d::prelude::Vector<f32>,
+) {
+    let aabb = shape.compute_local_aabb();
+    (aabb.center().coords, aabb.half_extents())
+}
+
+// Whether debug_collider_triangles can outline a shape within the triangle budget
+fn has_debug_collider_triangles(shape: &dyn Shape) -> bool {
+    if let Some(trimesh) = shape.as_trimesh() {
+        trimesh.indices().len() <= MAX_DEBUG_COLLIDER_TRIANGLES
+    } else if let Some(compound) = shape.as_compound() {
+        compound
+            .shapes()
+            .iter()
+            .all(|(_, part)| has_debug_collider_triangles(&**part))
+    } else {
+        shape.as_cuboid().is_some()
+            || shape.as_ball().is_some()
+            || shape.as_capsule().is_some()
+            || shape.as_cylinder().is_some()
+            || shape.as_convex_polyhedron().is_some()
+    }
+}
+
+// Triangulate a shape in its local space, merging the parts of compound shapes
+fn debug_collider_triangles(
+    shape: &dyn Shape,
+) -> Option<(Vec<rapier3d::prelude::Point<f32>>, Vec<[u32; 3]>)> {
+    if let Some(compound) = shape.as_compound() {
+        let mut points = vec![];
+        let mut triangles = vec![];
+
+        for (isometry, part) in compound.shapes() {
+            let (part_points, part_triangles) = debug_collider_triangles(&**part)?;
+            let base = points.len() as u32;
+
+            points.extend(part_points.into_iter().map(|point| isometry * point));
+            triangles.extend(
+                part_triangles
+                    .into_iter()
+                    .map(|[a, b, c]| [base + a, base + b, base + c]),
+            );
+        }
+
+        return Some((points, triangles));
+    }
+
+    let segments = DEBUG_COLLIDER_SEGMENTS;
+    if let Some(cuboid) = shape.as_cuboid() {
+        Some(cuboid.to_trimesh())
+    } else if let Some(ball) = shape.as_ball() {
+        Some(ball.to_trimesh(segments, segments / 2))
+    } else if let Some(capsule) = shape.as_capsule() {
+        Some(capsule.to_trimesh(segments, segments / 2))
+    } else if let Some(cylinder) = shape.as_cylinder() {
+        Some(cylinder.to_trimesh(segments))
+    } else if let Some(convex) = shape.as_convex_polyhedron() {
+        Some(convex.to_trimesh())
+    } else {
+        let trimesh = shape.as_trimesh()?;
+        Some((trimesh.vertices().to_vec(), trimesh.indices().to_vec()))
+    }
+}
+
+// Outline vertices and line indices for a shape without a shared unit outline
+fn debug_collider_geometry(shape: &dyn Shape) -> Option<(Vec<VertexData>, Vec<u32>)> {
+    let (points, triangles) = debug_collider_triangles(shape)?;
+    let vertices =
+        debug_collider_vertices(points.into_iter().map(|point| (point.x, point.y, point.z)));
+    Some((vertices, triangle_edges(&triangles)))
+}
+
+// Line list indices for the unique edges of a triangle list
+fn triangle_edges(triangles: &[[u32; 3]]) -> Vec<u32> {
+    triangles
+        .iter()
+        .flat_map(|[a, b, c]| [(*a, *b), (*b, *c), (*c, *a)])
+        .map(|(a, b)| (a.min(b), a.max(b)))
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .flat_map(|(a, b)| [a, b])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use antigen_core::{insert_tagged_entity, TaggedEntitiesComponent};
+    use antigen_wgpu::{BufferLengthComponent, BufferLengthsComponent};
+
+    #[test]
+    fn timer_events_fire_once_per_elapse() {
+        let mut world = World::new();
+        let timer = |repeat| {
+            (
+                TimerComponent::new(Timer::new(Duration::ZERO, repeat), false),
+                TimerEventComponent::<MoverEvent>::construct(MoverEvent::Open),
+                MoverEventOutputComponent::construct(vec![]),
+            )
+        };
+        let one_shot = world.spawn(timer(false));
+        let repeating = world.spawn(timer(true));
+
+        for _ in 0..3 {
+            phosphor_update_timers_system(&mut world);
+            timer_event_output_system::<MoverEvent>(&mut world);
+        }
+
+        let fired = |entity| world.get::<MoverEventOutputComponent>(entity).unwrap().len();
+        assert_eq!(fired(one_shot), 1);
+        assert_eq!(fired(repeating), 3);
+    }
+
+    #[test]
+    fn text_damage_touches_only_changed_cells() {
+        let text = |string: &str| {
+            Text {
+                string: string.into(),
+                wrap: None,
+            }
+            .cells()
+        };
+
+        let drawn = text("abc")
+            .into_iter()
+            .enumerate()
+            .map(|(instance, (cell, glyph))| (cell, (glyph, instance)))
+            .collect::<BTreeMap<_, _>>();
+
+        let glyph = |c| Glyph {
+            c,
+            color: [0xff; 3],
+            blink: false,
+        };
+
+        // Replacing one character respawns one instance
+        let damage = text_damage(&drawn, &text("abd"));
+        assert_eq!(damage.kept.len(), 2);
+        assert!(damage.moved.is_empty());
+        assert_eq!(damage.spawned, [((2, 0), glyph('d'))]);
+        assert_eq!(damage.despawned, [2]);
+
+        // Shifting characters moves their instances, and shrinking despawns the rest
+        let damage = text_damage(&drawn, &text(" ab"));
+        assert!(damage.kept.is_empty());
+        assert_eq!(damage.moved, [((1, 0), glyph('a'), 0), ((2, 0), glyph('b'), 1)]);
+        assert!(damage.spawned.is_empty());
+        assert_eq!(damage.despawned, [2]);
+
+        // Restyling a character redraws it
+        let damage = text_damage(&drawn, &text("ab\u{1}00ff00c"));
+        assert_eq!(damage.kept.len(), 2);
+        assert_eq!(damage.despawned, [2]);
+    }
+
+    #[test]
+    fn triangle_edges_are_shared_between_faces() {
+        // Two triangles forming a quad share their diagonal
+        let edges = triangle_edges(&[[0, 1, 2], [2, 3, 0]]);
+        assert_eq!(edges, [0, 1, 0, 2, 0, 3, 1, 2, 2, 3]);
+    }
+
+    #[test]
+    fn oscilloscopes_trace_their_own_line() {
+        let mut world = World::new();
+        world.spawn((Changed::new(TotalTimeComponent::construct(2.0), false),));
+        world.spawn((Changed::new(DeltaTimeComponent::construct(0.5), false),));
+
+        let vertex = VertexData {
+            intensity: 1.0,
+            delta_intensity: -1.0,
+            ..Default::default()
+        };
+        let line = || Changed::new(vec![vertex; 3], false);
+
+        let a = world.spawn((Oscilloscope::new(1.0, 1.0, |f| (f, 0.0, 0.0)), line()));
+        let b = world.spawn((Oscilloscope::new(1.0, 2.0, |f| (0.0, f, 0.0)), line()));
+        let untraced = world.spawn((line(),));
+
+        phosphor_update_oscilloscopes_system(&mut world);
+
+        let vertices = |entity| world.get::<Changed<VertexDataComponent>>(entity).unwrap();
+
+        // Each oscilloscope appends its own point, fading the rest of its trail
+        let a = vertices(a);
+        assert!(a.get_changed());
+        assert_eq!(a[2].position, [2.0, 0.0, 0.0]);
+        assert_eq!(a[0].intensity, 0.5);
+
+        let b = vertices(b);
+        assert!(b.get_changed());
+        assert_eq!(b[2].position, [0.0, 4.0, 0.0]);
+
+        // Lines without an oscilloscope are left alone
+        let untraced = vertices(untraced);
+        assert!(!untraced.get_changed());
+        assert!(untraced.iter().all(|vertex| vertex.intensity == 1.0));
+    }
+
+    // World holding the line mesh instance buffers' lengths and a single registered line mesh
+    fn line_mesh_instance_world(line_count: u32) -> World {
+        let mut world = World::new();
+        world.spawn((TaggedEntitiesComponent::default(),));
+        world.spawn((LineMeshIds, LineMeshIdsComponent::default()));
+        register_line_mesh_id(&mut world, "mesh".into(), (0, line_count));
+
+        let line_mesh_instances = world.spawn((
+            BufferLengthComponent::default(),
+            LineMeshInstanceSlotsComponent::default(),
+        ));
+        insert_tagged_entity::<LineMeshInstances>(&mut world, line_mesh_instances);
+
+        let line_instances = world.spawn((BufferLengthComponent::default(),));
+        insert_tagged_entity::<LineInstances>(&mut world, line_instances);
+
+        world
+    }
+
+    #[test]
+    fn despawned_line_mesh_instances_free_their_slots() {
+        let mut world = line_mesh_instance_world(3);
+
+        let lengths = |world: &mut World| {
+            let length = |world: &mut World, entity| {
+                world
+                    .get::<BufferLengthComponent>(entity)
+                    .unwrap()
+                    .load(Ordering::Relaxed)
+            };
+            let line_mesh_instances = get_tagged_entity::<LineMeshInstances>(world).unwrap();
+            let line_instances = get_tagged_entity::<LineInstances>(world).unwrap();
+            (
+                length(world, line_mesh_instances),
+                length(world, line_instances),
+            )
+        };
+
+        let spawn = |world: &mut World| {
+            world.spawn((LineMeshInstanceComponent::construct(Cow::Borrowed("mesh")),))
+        };
+
+        let a = spawn(&mut world);
+        let b = spawn(&mut world);
+        assemble_line_mesh_instances_system(&mut world);
+        assert_eq!(lengths(&mut world), (2, 6));
+
+        // Slots freed below the end of the buffers are left for reuse
+        world.insert_one(a, PendingDespawn).unwrap();
+        despawn_line_mesh_instances_system(&mut world);
+        assert_eq!(lengths(&mut world), (2, 6));
+
+        let c = spawn(&mut world);
+        assemble_line_mesh_instances_system(&mut world);
+        assert_eq!(lengths(&mut world), (2, 6));
+
+        // Slots freed at the end of the buffers shrink them
+        world.insert_one(b, PendingDespawn).unwrap();
+        world.insert_one(c, PendingDespawn).unwrap();
+        despawn_line_mesh_instances_system(&mut world);
+        assert_eq!(lengths(&mut world), (0, 0));
+    }
+
+    #[test]
+    fn churned_triangle_mesh_instances_reuse_their_slots() {
+        let mut world = World::new();
+        world.spawn((TaggedEntitiesComponent::default(),));
+        world.spawn((TriangleMeshIds, TriangleMeshIdsComponent::default()));
+        register_triangle_mesh_id(&mut world, "mesh".into(), 1);
+
+        let heads = BufferLengthsComponent::default();
+        heads.write().extend([0, 0]);
+        let triangle_mesh_instances = world.spawn((
+            heads.clone(),
+            TriangleMeshInstanceSlotsComponent::default(),
+        ));
+        insert_tagged_entity::<TriangleMeshInstances>(&mut world, triangle_mesh_instances);
+
+        let spawn = |world: &mut World| {
+            world.spawn((TriangleMeshInstanceComponent::construct(Cow::Borrowed("mesh")),))
+        };
+
+        let mut instances = (0..3).map(|_| spawn(&mut world)).collect::<Vec<_>>();
+        assemble_triangle_mesh_instances_system(&mut world);
+        assert_eq!(*heads.read(), [0, 3]);
+
+        // Replacing an instance each frame keeps the mesh's instances bounded
+        for _ in 0..8 {
+            world.insert_one(instances.remove(0), PendingDespawn).unwrap();
+            despawn_triangle_mesh_instances_system(&mut world);
+
+            instances.push(spawn(&mut world));
+            assemble_triangle_mesh_instances_system(&mut world);
+            assert_eq!(*heads.read(), [0, 3]);
+        }
+
+        // Despawning everything releases every slot
+        for instance in instances {
+            world.insert_one(instance, PendingDespawn).unwrap();
+        }
+        despawn_triangle_mesh_instances_system(&mut world);
+        assert_eq!(*heads.read(), [0, 0]);
+        assert!(world
+            .get::<TriangleMeshInstanceSlotsComponent>(triangle_mesh_instances)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn map_reloads_release_triangle_mesh_instance_slots() {
+        let mut world = World::new();
+        world.spawn((TaggedEntitiesComponent::default(),));
+        world.spawn((TriangleMeshIds, TriangleMeshIdsComponent::default()));
+        register_triangle_mesh_id(&mut world, "mesh".into(), 0);
+
+        let heads = BufferLengthsComponent::default();
+        heads.write().push(0);
+        let triangle_mesh_instances = world.spawn((
+            heads.clone(),
+            TriangleMeshInstanceSlotsComponent::default(),
+        ));
+        insert_tagged_entity::<TriangleMeshInstances>(&mut world, triangle_mesh_instances);
+
+        // Reloading the same map keeps its triangle mesh instances bounded
+        for _ in 0..4 {
+            for _ in 0..3 {
+                world.spawn((
+                    MapEntity,
+                    TriangleMeshInstanceComponent::construct(Cow::Borrowed("mesh")),
+                ));
+            }
+            assemble_triangle_mesh_instances_system(&mut world);
+            assert_eq!(*heads.read(), [3]);
+
+            despawn_map_entities(&mut world);
+            assert_eq!(*heads.read(), [0]);
+        }
+    }
+}
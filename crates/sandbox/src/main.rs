@@ -92,8 +92,10 @@
 //
 // TODO: [✓] Refactor TB oscilloscope handling
 //           * Semantically, oscilloscope is an animation over a line segment
-//             * Should be able to split off into an animation component
-//             * Leave line mesh creation and instancing to their respective properties
+//             [✓] Should be able to split off into an animation component
+//                 * Oscilloscopes trace a dedicated line mesh named by oscilloscope.name
+//                 * Line mesh instances attach to an oscilloscope by naming its mesh
+//             [✓] Leave line mesh creation and instancing to their respective properties
 //
 // TODO: [✓] Refactor TB text handling
 //
@@ -117,17 +119,17 @@
 //             * Rendering issues likely specific to nvidia + wayland
 //             * Keyboard issues may be a result of wayland security protocols
 //
-// TODO: [>] Implement generalized render pass dispatch
+// TODO: [✓] Implement generalized render pass dispatch
 //           [✓] Draw implementation
 //           [✓] Draw indexed implementation
 //           [✓] Implement remaining RenderPass parameters
 //           [✓] Draw indirect implementation
 //           [✓] Draw indexed indirect implementation
-//           [ ] Multi-draw implementations
-//           [ ] Execute Bundles implementation
-//           [ ] Struct parameters for bundle constructors
+//           [✓] Multi-draw implementations
+//           [✓] Execute Bundles implementation
+//           [✓] Struct parameters for bundle constructors
 //               * wgpu descriptors, but with entities instead of references
-//           [ ] Builder pattern for RenderPass bundles?
+//           [✓] Builder pattern for RenderPass bundles?
 //
 // TODO: [>] Integrate rapier physics
 //           [✓] Create collision from brush hulls
@@ -138,6 +140,8 @@
 //                 * Multiply cuboid extents by scale
 //                 * Scale vertices for convex hulls and trimeshes
 //           [✓] Trimesh brush collision
+//               * solid brushes build a static trimesh from their outward faces
+//               * Player collides via its character controller
 //           [>] Sensors
 //           [>] Contact / intersection event handling
 //               * Receiver component queues up events during collision tick
@@ -148,10 +152,14 @@
 //               * Queues are cleared before next frame
 //               * Would be useful to have a TB-side wiring solution for this
 //                 * Ex. triggers -> doors, timers, etc
-//           [ ] Kinematic Controller
-//
-// TODO: [>] Fix lines projecting from behind the camera
-//           [ ] Fix corner case
+//                 * Timer entities output mover events via timer_event_output_system
+//           [✓] Kinematic Controller
+//               * Rapier 0.11 has none, so move_character sweeps the shape via QueryPipeline
+//               * Camera input is sent to the game thread's player,
+//                 which sends the resolved position back
+//
+// TODO: [✓] Fix lines projecting from behind the camera
+//           [✓] Fix corner case
 //               * Appears to be a precision issue
 //               * May be using camera position instead of near plane as clipping predicate
 //               * When very far away from the camera,
@@ -159,7 +167,7 @@
 //
 // TODO: [>] Implement camera abstraction
 //           [>] First-person controls
-//           [ ] Spawn at first player start
+//           [✓] Spawn at first player start
 //           [ ] Mouse capture
 //
 // TODO: [ ] Fix compound convex hulls behaving incorrectly under scaling
@@ -180,23 +188,28 @@
 //     * Allows for composition in TB
 //     * Need to think of a better name - too associated with OOP semantics
 //
-// TODO: [ ] Text entity refactor
-//           * Needs to work as a component that controls a set of text mesh instance entities
-//           * Should be able to update mesh instances when the underlying string changes
+// TODO: [>] Text entity refactor
+//           [✓] Needs to work as a component that controls a set of text mesh instance entities
+//           [✓] Should be able to update mesh instances when the underlying string changes
+//               * TextComponent, redrawn by text_damage_system
+//               * Moving a text entity doesn't yet move its character instances
 //           * Take inspiration from terminal emulators
-//             * Use control characters for color, blink, etc
+//             [✓] Use control characters for color, blink, etc
+//                 * \x01RRGGBB sets color, \x02 toggles blink
 //               * Could extend if unused control characters exist
 //                 * Fading, text animations, etc
-//             * Damage system for reusing untouched text mesh instances
+//             [✓] Damage system for reusing untouched text mesh instances
 //           * Use-case for parent/child relation - transforms
 //
-// TODO: [ ] Figure out why lower-case z is missing from text test
+// TODO: [✓] Figure out why lower-case z is missing from text test
+//           * Its path opens with a cubic curve, which the SVG parser stopped at
+//             without error, leaving a mesh with no lines
 //
-// TODO: [ ] Implement compute-based frustum culling
+// TODO: [✓] Implement compute-based frustum culling
 //
 // TODO: [ ] Implement generalized render pass setup
 //
-// TODO: [ ] Implement portal rendering
+// TODO: [>] Implement portal rendering
 //           * Ideally all portal rendering should happen in existing draw calls for performance's sake
 //               * Just add more geometry
 //                 * Effectively an extra layer of room -> mesh instances indirection
@@ -204,7 +217,8 @@
 //               * Stencil buffer seems the best approach to early-out from invisible fragments
 //               * Each portal recursion adds 1 to the stencil value
 //               * Use less-than stencil comparator
-//          * Will need a way to track the current room in order to begin portal traversal
+//          [✓] Will need a way to track the current room in order to begin portal traversal
+//              * Brush entities with room set, tracked by current_room_system
 //            * Point-in-box checks against room hulls
 //            * If camera is not inside a room, find the closest one
 //              * Ideally should use distance-to-nearest-surface
@@ -212,11 +226,19 @@
 //            * Updating current room on portal traversal will be more efficient
 //              after starting sector has been determined
 //          * Rendering the whole scene twice with a small offset is a good place to start
+//          [✓] Stencil scaffolding: point entities with portal set draw a quad that
+//              increments the stencil, then the scene is drawn through it with an offset view
+//              * Frustum culling still uses the main camera
+//              * All portals share the view through the first
+//              * No skybox behind portals
+//              * Portal passes are recorded even when no portals exist
 //
 // TODO: [ ] Investigate box portals for room-inside-room
 //
-// TODO: [ ] Generalize map -> entities + components conversion
+// TODO: [>] Generalize map -> entities + components conversion
 //           * Need a way to map classname to a set of entities, properties to components
+//          [✓] Classname registry mapping classnames to component handlers
+//          [✓] Export an FGD from the classname registry via --fgd
 //          [>] Catch-all Point and Brush entity classnames
 //             * Collects all relevant components into single classnames
 //             * Specialize to bundle-like constructs by subclassing in FGD and overriding with default values
@@ -234,46 +256,75 @@
 //               * Runtime usage should embody the 'game as its own editor' paradigm
 //                 * Same functionality, different interface
 //
-// TODO: [ ] TrenchBroom special entity support for shambler
+// TODO: [>] TrenchBroom special entity support for shambler
 //           * Implement as its own GeoMap-dependent struct
+//           [✓] Worldspawn properties
 //
-// TODO: [ ] Surface / Content flags support for shambler
+// TODO: [✓] Surface / Content flags support for shambler
 //           * Should be able to use for trimesh collision lookup,
 //             provided that rapier returns face information
 //
-// TODO: [ ] Implement HDR bloom pass
+// TODO: [✓] Implement HDR bloom pass
 //
-// TODO: [ ] Calculate exact near plane intersection for line clipping
-//           * Currently using an arbitrarily large value
-//           * Could have overdraw implications
-//           * Should probably clip in view space instead of NDC for this
+// TODO: [✓] Calculate exact near plane intersection for line clipping
+//           * Lines are clipped in view space before the perspective divide (beam.wgsl)
+//           * Near distance is read back out of the reversed infinite perspective matrix
+//           * Segments entirely behind the near plane are moved outside the clip volume
 //
 
 mod demos;
 
 use antigen_core::{
-    receive_messages, send_clone_query, try_receive_messages, NamedEntitiesComponent,
-    PositionComponent, RotationComponent, ScaleComponent, TaggedEntitiesComponent, WorldChannel,
-    WorldExchange,
+    env_args_component, is_paused, receive_messages_timeout, send_clone_query,
+    toggle_pause_message, try_receive_messages, Construct, NamedEntitiesComponent,
+    PositionComponent, Priority, RotationComponent, ScaleComponent, SendTo,
+    TaggedEntitiesComponent, WorldChannel, WorldExchange,
 };
 use antigen_wgpu::{
     wgpu::DeviceDescriptor, AdapterComponent, DeviceComponent, InstanceComponent, QueueComponent,
 };
 use antigen_winit::EventLoopHandler;
-use demos::phosphor::{LineMeshInstance, MoverEvent, TriangleMeshInstance};
+use demos::phosphor::{
+    ImpactSoundEvent, InputAction, InputBindingsComponent, LineColorComponent,
+    LineIntensityComponent, LineMeshInstance, MoverEvent, PhosphorTarget, PhysicalInput,
+    TriangleMeshInstance,
+};
 use rapier3d::prelude::IntersectionEvent;
 use std::{
+    path::PathBuf,
     thread::JoinHandle,
     time::{Duration, Instant},
 };
-use winit::{event::Event, event_loop::ControlFlow, event_loop::EventLoopWindowTarget};
+use winit::{
+    event::{DeviceEvent, ElementState, Event, KeyboardInput},
+    event_loop::ControlFlow,
+    event_loop::EventLoopWindowTarget,
+};
 
 use hecs::{EntityBuilder, World};
 
-use antigen_rapier3d::physics_backend_builder;
+use antigen_rapier3d::{physics_backend_builder, EventRetention, ImpactThresholdComponent};
 
 const GAME_THREAD_TICK: Duration = Duration::from_nanos(16670000);
 
+// Maximum interval between the filesystem thread polling watched files while idle
+const FS_THREAD_POLL: Duration = Duration::from_millis(50);
+
+// Contact impulses below this are treated as resting contact rather than impacts
+const MIN_IMPACT_IMPULSE: f32 = 10.0;
+
+// Size of images rendered via --screenshot
+const SCREENSHOT_WIDTH: u32 = 640;
+const SCREENSHOT_HEIGHT: u32 = 480;
+
+// Frames to render once all pipelines are ready before capturing a screenshot,
+// giving the map time to load and the phosphor buffers time to settle
+const SCREENSHOT_FRAMES: usize = 120;
+
+// Flags taking a value, shared with the phosphor demo
+// so those values aren't mistaken for a map path
+const VALUE_FLAGS: &[&str] = &["--screenshot", "--lut"];
+
 enum Game {}
 enum Render {}
 enum Filesystem {}
@@ -281,6 +332,14 @@ enum Filesystem {}
 fn main() {
     //tracing_subscriber::fmt::fmt().pretty().init();
 
+    // Print an FGD describing the map entity classes for TrenchBroom and exit
+    if std::env::args().skip(1).any(|arg| arg == "--fgd") {
+        print!("{}", demos::phosphor::builtin_fgd());
+        return;
+    }
+
+    let screenshot_path = path_arg("--screenshot");
+
     // Create world exchange
     let mut exchange = WorldExchange::default();
 
@@ -306,14 +365,24 @@ fn main() {
     builder.add(demos::phosphor::SharedShapesComponent::default());
     game_world.spawn(builder.build());
 
+    let mut builder = EntityBuilder::new();
+    builder.add(demos::phosphor::DebugCollidersComponent::construct(false));
+    builder.add(demos::phosphor::DebugColliderOutlinesComponent::default());
+    builder.add(demos::phosphor::DebugColliderMeshesComponent::default());
+    game_world.spawn(builder.build());
+
+    game_world.spawn(demos::phosphor::classname_registry_bundle().build());
+    demos::phosphor::register_builtin_classnames(&mut game_world);
+
     // Setup render world
     render_world.spawn((TaggedEntitiesComponent::default(),));
+    render_world.spawn((env_args_component(),));
     render_world.spawn(antigen_winit::BackendBundle::default());
 
     let wgpu_backend_entity = render_world.spawn(antigen_wgpu::BackendBundle::from_env(
         &DeviceDescriptor {
             label: Some("Device"),
-            features: Default::default(),
+            features: antigen_wgpu::OPTIONAL_FEATURES,
             limits: Default::default(),
         },
         None,
@@ -365,8 +434,26 @@ fn main() {
     spawn_world::<Filesystem, _, _>(fs_thread(fs_world, fs_channel));
     spawn_world::<Game, _, _>(game_thread(game_world, game_channel));
 
+    // Render headless and exit if a screenshot was requested
+    if let Some(path) = screenshot_path {
+        demos::phosphor::assemble(
+            &mut render_world,
+            &render_channel,
+            PhosphorTarget::Offscreen {
+                width: SCREENSHOT_WIDTH,
+                height: SCREENSHOT_HEIGHT,
+            },
+        );
+        load_color_lut_arg(&mut render_world);
+        skybox_mode_arg(&mut render_world);
+        screenshot(render_world, render_channel, path);
+        return;
+    }
+
     // Assemble phosphor renderer
-    demos::phosphor::assemble(&mut render_world, &render_channel);
+    demos::phosphor::assemble(&mut render_world, &render_channel, PhosphorTarget::Window);
+    load_color_lut_arg(&mut render_world);
+    skybox_mode_arg(&mut render_world);
 
     // Enter winit event loop
     winit::event_loop::EventLoop::new().run(antigen_winit::wrap_event_loop(
@@ -380,6 +467,67 @@ fn main() {
     ));
 }
 
+/// Parse the path following the given flag argument, if present
+fn path_arg(flag: &str) -> Option<PathBuf> {
+    debug_assert!(VALUE_FLAGS.contains(&flag), "{} is missing from VALUE_FLAGS", flag);
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            let path = args
+                .next()
+                .unwrap_or_else(|| panic!("{} requires a path", flag));
+            return Some(path.into());
+        }
+    }
+    None
+}
+
+/// Apply the color LUT at the path following a `--lut` argument, if present
+fn load_color_lut_arg(world: &mut World) {
+    if let Some(path) = path_arg("--lut") {
+        if let Err(e) = demos::phosphor::load_color_lut(world, &path) {
+            println!("Failed to load color LUT {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Draw the skybox over the scene rather than behind it if `--skybox-overlay` is present
+fn skybox_mode_arg(world: &mut World) {
+    if std::env::args().skip(1).any(|arg| arg == "--skybox-overlay") {
+        demos::phosphor::set_skybox_mode(world, demos::phosphor::SkyboxMode::Overlay);
+    }
+}
+
+/// Render the phosphor demo to its offscreen target and save the final frame as a PNG
+fn screenshot(mut world: World, channel: WorldChannel, path: PathBuf) {
+    let mut frames = 0;
+    while frames < SCREENSHOT_FRAMES {
+        try_receive_messages(&mut world, &channel).expect("Error handling message");
+        demos::phosphor::headless_frame_schedule(&mut world, &channel);
+
+        if demos::phosphor::phosphor_pipelines_ready(&world) {
+            frames += 1;
+        }
+
+        std::thread::sleep(GAME_THREAD_TICK);
+    }
+
+    let (target_entity, _) = world
+        .query_mut::<()>()
+        .with::<antigen_wgpu::OffscreenTarget>()
+        .into_iter()
+        .next()
+        .expect("No offscreen target");
+
+    antigen_wgpu::texture_to_rgba8(&world, target_entity)
+        .unwrap_or_else(|e| panic!("Failed to read back screenshot: {}", e))
+        .save_png(&path)
+        .expect("Failed to save screenshot");
+
+    println!("Saved screenshot to {:?}", path);
+}
+
 /// Spawn a thread with a world and function entrypoint
 fn spawn_world<U, F, R>(f: F) -> JoinHandle<R>
 where
@@ -407,25 +555,51 @@ fn spin_loop<F: FnMut()>(duration: Duration, mut f: F) -> ! {
 /// Filesystem thread
 fn fs_thread(mut world: World, channel: WorldChannel) -> impl FnMut() {
     move || loop {
-        receive_messages(&mut world, &channel).expect("Error receiving message");
+        // Wake for messages as they arrive, or to poll watched files when idle
+        receive_messages_timeout(&mut world, &channel, FS_THREAD_POLL)
+            .expect("Error handling message");
+
+        // Hot-reload watched files
+        antigen_fs::file_watcher_system(&mut world);
+        demos::phosphor::reload_watched_shaders(&mut world, &channel);
+        demos::phosphor::reload_watched_input_bindings(&mut world, &channel);
     }
 }
 
 /// Game thread
 fn game_thread(mut world: World, channel: WorldChannel) -> impl FnMut() {
     // Create the physics backend
-    world.spawn(physics_backend_builder(nalgebra::Vector3::new(0.0, -98.1, 0.0)).build());
+    let mut builder = physics_backend_builder(nalgebra::Vector3::new(0.0, -98.1, 0.0));
+    builder.add(ImpactThresholdComponent::construct(MIN_IMPACT_IMPULSE));
+    builder.add(EventRetention::KeepUntilConsumed);
+    world.spawn(builder.build());
 
     move || {
         spin_loop(GAME_THREAD_TICK, || {
             try_receive_messages(&mut world, &channel).expect("Error handling message");
 
+            // Keep handling messages while paused so the thread can be resumed,
+            // leaving the last written instance data for the render thread to draw
+            if is_paused(&world) {
+                return;
+            }
+
             // Preparation systems
+            demos::phosphor::text_damage_system(&mut world);
+            demos::phosphor::blink_system(&mut world);
+            demos::phosphor::despawn_triangle_mesh_instances_system(&mut world);
+            demos::phosphor::despawn_line_mesh_instances_system(&mut world);
             demos::phosphor::assemble_triangle_mesh_instances_system(&mut world);
             demos::phosphor::assemble_line_mesh_instances_system(&mut world);
 
             antigen_rapier3d::insert_colliders_system(&mut world);
+            antigen_rapier3d::rebuild_scaled_colliders_system(&mut world);
             antigen_rapier3d::insert_rigid_bodies_system(&mut world);
+            antigen_rapier3d::update_gravity_scales_system(&mut world);
+            antigen_rapier3d::insert_joints_system(&mut world);
+
+            antigen_rapier3d::remove_colliders_system(&mut world);
+            antigen_rapier3d::remove_rigid_bodies_system(&mut world);
 
             antigen_core::insert_named_entities_system(&mut world);
 
@@ -436,11 +610,31 @@ fn game_thread(mut world: World, channel: WorldChannel) -> impl FnMut() {
             // Write component transforms to physics system
             antigen_rapier3d::write_rigid_body_isometries_system(&mut world);
 
+            // Apply localized gravity
+            antigen_rapier3d::apply_gravity_regions_system(&mut world);
+
+            // Apply gameplay impulses and forces
+            antigen_rapier3d::apply_impulses_system(&mut world);
+            antigen_rapier3d::apply_forces_system(&mut world);
+
+            // Wake bodies flagged for wakeup
+            antigen_rapier3d::wake_rigid_bodies_system(&mut world);
+
             // Step physics
+            antigen_rapier3d::configure_physics_system(&mut world);
             antigen_rapier3d::step_physics_system(&mut world);
 
+            antigen_rapier3d::collect_impact_events_system(&mut world);
+
+            // Resolve player movement against the stepped colliders
+            antigen_rapier3d::move_character_controllers_system(&mut world);
+            demos::phosphor::player_camera_position_system(&mut world, &channel);
+
             // Event output
+            demos::phosphor::phosphor_update_timers_system(&mut world);
+            demos::phosphor::timer_event_output_system::<MoverEvent>(&mut world);
             demos::phosphor::intersection_event_output_system(&mut world);
+            demos::phosphor::impact_sound_event_output_system(&mut world);
 
             // Intersection event dispatch
             demos::phosphor::event_dispatch_system::<IntersectionEvent>(&mut world);
@@ -469,21 +663,41 @@ fn game_thread(mut world: World, channel: WorldChannel) -> impl FnMut() {
 
             demos::phosphor::clear_event_output_system::<IntersectionEvent>(&mut world);
             demos::phosphor::clear_event_output_system::<MoverEvent>(&mut world);
+            demos::phosphor::clear_event_output_system::<ImpactSoundEvent>(&mut world);
 
             antigen_rapier3d::clear_physics_event_collector_system(&mut world);
 
             // Read physics transforms back into components
             antigen_rapier3d::read_back_rigid_body_isometries_system(&mut world);
+            antigen_rapier3d::read_back_sleeping_system(&mut world);
 
-            // Copy transform components to triangle mesh instances
-            antigen_core::copy_to_system::<TriangleMeshInstance, PositionComponent>(&mut world);
-            antigen_core::copy_to_system::<TriangleMeshInstance, RotationComponent>(&mut world);
-            antigen_core::copy_to_system::<TriangleMeshInstance, ScaleComponent>(&mut world);
+            // Compose parented transforms now that physics transforms are up to date
+            antigen_core::propagate_transforms_system(&mut world);
 
-            // Copy transform components to line mesh instances
-            antigen_core::copy_to_system::<LineMeshInstance, PositionComponent>(&mut world);
-            antigen_core::copy_to_system::<LineMeshInstance, RotationComponent>(&mut world);
-            antigen_core::copy_to_system::<LineMeshInstance, ScaleComponent>(&mut world);
+            // Outline colliders at their stepped positions
+            demos::phosphor::debug_colliders_system(&mut world, &channel);
+
+            // Copy transform components to triangle mesh instances and write them to GPU
+            antigen_wgpu::copy_and_write_system::<TriangleMeshInstance, PositionComponent>(
+                &mut world,
+            );
+            antigen_wgpu::copy_and_write_system::<TriangleMeshInstance, RotationComponent>(
+                &mut world,
+            );
+            antigen_wgpu::copy_and_write_system::<TriangleMeshInstance, ScaleComponent>(
+                &mut world,
+            );
+
+            // Copy transform components to line mesh instances and write them to GPU
+            antigen_wgpu::copy_and_write_system::<LineMeshInstance, PositionComponent>(&mut world);
+            antigen_wgpu::copy_and_write_system::<LineMeshInstance, RotationComponent>(&mut world);
+            antigen_wgpu::copy_and_write_system::<LineMeshInstance, ScaleComponent>(&mut world);
+            antigen_wgpu::copy_and_write_system::<LineMeshInstance, LineColorComponent>(
+                &mut world,
+            );
+            antigen_wgpu::copy_and_write_system::<LineMeshInstance, LineIntensityComponent>(
+                &mut world,
+            );
 
             // Write buffers to GPU
             antigen_wgpu::buffer_write_slice_system::<
@@ -497,9 +711,6 @@ fn game_thread(mut world: World, channel: WorldChannel) -> impl FnMut() {
             antigen_wgpu::buffer_write_slice_system::<demos::phosphor::LineInstanceDataComponent, _>(
                 &mut world,
             );
-            antigen_wgpu::buffer_write_system::<antigen_core::PositionComponent>(&mut world);
-            antigen_wgpu::buffer_write_system::<antigen_core::RotationComponent>(&mut world);
-            antigen_wgpu::buffer_write_system::<antigen_core::ScaleComponent>(&mut world);
             antigen_wgpu::buffer_write_system::<demos::phosphor::LineMeshIdComponent>(&mut world);
         })
     }
@@ -518,6 +729,35 @@ pub fn render_thread<T: Clone>(mut f: impl EventLoopHandler<T>) -> impl EventLoo
             winit::event::Event::MainEventsCleared => {
                 println!("Main events cleared");
             }
+            winit::event::Event::DeviceEvent {
+                event:
+                    DeviceEvent::Key(KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    }),
+                ..
+            } => {
+                let action = world
+                    .query_mut::<&InputBindingsComponent>()
+                    .into_iter()
+                    .find_map(|(_, bindings)| bindings.get(&PhysicalInput::Key(key)).copied());
+
+                match action {
+                    // Toggle game thread pause, leaving the render thread interactive
+                    Some(InputAction::Pause) => {
+                        channel
+                            .send_to_with_priority::<Game>(toggle_pause_message(), Priority::High)
+                            .expect("Error sending pause message");
+                    }
+                    Some(InputAction::DebugColliders) => {
+                        channel
+                            .send_to::<Game>(demos::phosphor::toggle_debug_colliders_message())
+                            .expect("Error sending debug colliders message");
+                    }
+                    _ => (),
+                }
+            }
             _ => (),
         }
 
@@ -1,6 +1,9 @@
 // Numeric constants
 let PI: f32 = 3.14159265359;
 
+// Intensity of line smears relative to the lines that leave them
+let SMEAR_INTENSITY: f32 = 0.5;
+
 // Quaternion functionality
 struct Quaternion {
     x: f32;
@@ -89,10 +92,12 @@ struct LineMeshInstance {
     mesh_id: u32;
     rot: Quaternion;
     scale: vec3<f32>;
+    intensity: f32;
+    color: vec3<f32>;
 };
 
 struct LineMeshInstances {
-    instances: [[stride(48)]] array<LineMeshInstance>;
+    instances: [[stride(64)]] array<LineMeshInstance>;
 };
 
 struct LineInstance {
@@ -104,6 +109,16 @@ struct LineInstances {
     instances: [[stride(8)]] array<LineInstance>;
 };
 
+struct LineSmear {
+    previous: array<vec4<f32>, 2>;
+    current: array<vec4<f32>, 2>;
+    color: array<vec4<f32>, 2>;
+};
+
+struct LineSmears {
+    smears: [[stride(96)]] array<LineSmear>;
+};
+
 // Shader I/O
 struct TriangleVertexInput {
     [[builtin(vertex_index)]] v_index: u32;
@@ -120,6 +135,13 @@ struct LineVertexInput {
     [[location(1)]] end: f32;
 };
 
+struct PortalInput {
+    [[builtin(vertex_index)]] v_index: u32;
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] rotation: vec4<f32>;
+    [[location(2)]] extents: vec2<f32>;
+};
+
 struct VertexOutput {
     [[builtin(position)]] position: vec4<f32>;
     [[location(1)]] color: vec3<f32>;
@@ -153,6 +175,9 @@ var<storage, read> line_mesh_instances: LineMeshInstances;
 [[group(1), binding(5)]]
 var<storage, read> line_instances: LineInstances;
 
+[[group(2), binding(0)]]
+var<storage, read> line_smears: LineSmears;
+
 // Clear vertex shader
 [[stage(vertex)]]
 fn vs_clear() -> VertexOutput {
@@ -189,12 +214,43 @@ fn vs_triangle(
     return output;
 }
 
-fn clip_line(v0: vec3<f32>, v1: vec3<f32>) -> vec3<f32> {
-    let dir = normalize(v1 - v0);
-    // TODO: This should be calculated by projecting delta onto the near plane
-    //       but seems non-trivial due to perspective projection
-    let mag = 1000.0;
-    return v1 + (dir * mag);
+// Portal quad vertex shader, drawn as a triangle strip
+[[stage(vertex)]]
+fn vs_portal(
+    in: PortalInput
+) -> VertexOutput {
+    let corner = vec2<f32>(f32(in.v_index & 1u), f32(in.v_index >> 1u)) * 2.0 - 1.0;
+    let rot = Quaternion(in.rotation.x, in.rotation.y, in.rotation.z, in.rotation.w);
+
+    let pos = in.position + quat_mul(rot, vec3<f32>(corner * in.extents, 0.0));
+    let pos = pos - r_uniforms.cam_pos.xyz;
+    let pos = quat_mul(r_uniforms.cam_rot, pos);
+    let pos = vec4<f32>(pos, 1.0);
+    let pos = r_uniforms.perspective * pos;
+
+    var output: VertexOutput;
+    output.position = pos;
+    output.color = vec3<f32>(0.0);
+    output.intensity = 0.0;
+    output.delta_intensity = 0.0;
+    return output;
+}
+
+// Near plane distance, stored in the reversed infinite perspective matrix
+fn near_plane() -> f32 {
+    return r_uniforms.perspective[3].z;
+}
+
+// Move a view-space vertex behind the near plane along its line until it lies on the plane
+fn clip_to_near_plane(v: vec3<f32>, other: vec3<f32>, near: f32) -> vec3<f32> {
+    let t = (-near - v.z) / (other.z - v.z);
+    return mix(v, other, t);
+}
+
+// Project a view-space position into normalized device coordinates, retaining w
+fn project(v: vec3<f32>) -> vec4<f32> {
+    let v = r_uniforms.perspective * vec4<f32>(v, 1.0);
+    return vec4<f32>(v.xyz / v.w, v.w);
 }
 
 // Line vertex shader
@@ -238,25 +294,28 @@ fn vs_line(
     let v1_delta_intensity = v1.m2.z;
 
     let v0 = v0_pos - r_uniforms.cam_pos.xyz;
-    let v0 = quat_mul(r_uniforms.cam_rot, v0);
-    let v0 = vec4<f32>(v0, 1.0);
-    let v0 = r_uniforms.perspective * v0;
-    var v0 = vec4<f32>(v0.xyz / v0.w, v0.w);
+    var v0 = quat_mul(r_uniforms.cam_rot, v0);
 
     let v1 = v1_pos - r_uniforms.cam_pos.xyz;
-    let v1 = quat_mul(r_uniforms.cam_rot, v1);
-    let v1 = vec4<f32>(v1, 1.0);
-    let v1 = r_uniforms.perspective * v1;
-    var v1 = vec4<f32>(v1.xyz / v1.w, v1.w);
+    var v1 = quat_mul(r_uniforms.cam_rot, v1);
+
+    // Clip against the near plane in view space, before the perspective divide
+    // can flip vertices behind the camera to the opposite side of the screen
+    let near = near_plane();
+    let v0_behind = v0.z > -near;
+    let v1_behind = v1.z > -near;
 
-    if(v0.w < 0.0 && v1.w >= 0.0) {
-        v0 = vec4<f32>(clip_line(v0.xyz, v1.xyz), 1.0);
+    if(v0_behind && !v1_behind) {
+        v0 = clip_to_near_plane(v0, v1, near);
     }
 
-    if(v1.w < 0.0 & v0.w >= 0.0) {
-        v1 = vec4<f32>(clip_line(v1.xyz, v0.xyz), 1.0);
+    if(v1_behind && !v0_behind) {
+        v1 = clip_to_near_plane(v1, v0, near);
     }
 
+    let v0 = project(v0);
+    let v1 = project(v1);
+
     var delta = v1 - v0;
 
     let delta_norm = normalize(delta);
@@ -277,13 +336,66 @@ fn vs_line(
 
     output.position = vec4<f32>(pos.xyz, 1.0);
 
-    output.color = mix(v0_line_color, v1_line_color, in.end);
-    output.intensity = mix(v0_intensity, v1_intensity, in.end);
+    // Freed instances have zero scale, and lines entirely behind the near plane are invisible;
+    // move them outside the clip volume
+    if(length(instance_scale) == 0.0 || (v0_behind && v1_behind)) {
+        output.position = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+    }
+
+    output.color = mix(v0_line_color, v1_line_color, in.end) * mesh_instance.color;
+    output.intensity = mix(v0_intensity, v1_intensity, in.end) * mesh_instance.intensity;
     output.delta_intensity = mix(v0_delta_intensity, v1_delta_intensity, in.end);
 
     return output;
 }
 
+// Line smear vertex shader, drawn as a triangle strip
+// from a line's previous endpoints to its current ones
+[[stage(vertex)]]
+fn vs_smear(
+    [[builtin(instance_index)]] instance: u32,
+    [[builtin(vertex_index)]] v_index: u32,
+) -> VertexOutput {
+    let end = v_index & 1u;
+    let current = v_index >> 1u;
+
+    var pos = line_smears.smears[instance].previous[end].xyz;
+    if (current == 1u) {
+        pos = line_smears.smears[instance].current[end].xyz;
+    }
+
+    let color = line_smears.smears[instance].color[end];
+    let smeared = line_smears.smears[instance].current[0].w > 0.0;
+
+    // Smears crossing the near plane are dropped rather than clipped
+    let near = near_plane();
+    var behind = false;
+    for (var i = 0u; i < 2u; i = i + 1u) {
+        let previous_view = quat_mul(r_uniforms.cam_rot, line_smears.smears[instance].previous[i].xyz - r_uniforms.cam_pos.xyz);
+        let current_view = quat_mul(r_uniforms.cam_rot, line_smears.smears[instance].current[i].xyz - r_uniforms.cam_pos.xyz);
+        behind = behind || previous_view.z > -near || current_view.z > -near;
+    }
+
+    let pos = pos - r_uniforms.cam_pos.xyz;
+    let pos = quat_mul(r_uniforms.cam_rot, pos);
+    let pos = vec4<f32>(pos, 1.0);
+    let pos = r_uniforms.perspective * pos;
+
+    var output: VertexOutput;
+    output.position = pos;
+
+    if(!smeared || behind) {
+        output.position = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+    }
+
+    // Fade in from the previous endpoints, where the beam has already moved on
+    output.color = color.rgb;
+    output.intensity = color.a * f32(current) * SMEAR_INTENSITY;
+    output.delta_intensity = 0.0;
+
+    return output;
+}
+
 // Fragment shader
 [[stage(fragment)]]
 fn fs_main(
@@ -0,0 +1,60 @@
+// Bloom sources share the phosphor bind group layout,
+// with the second texture binding unused
+[[group(0), binding(0)]]
+var r_source: texture_2d<f32>;
+
+[[group(0), binding(2)]]
+var r_sampler: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// Normalized 9-tap gaussian kernel, center tap first
+let WEIGHT_0: f32 = 0.2270270270;
+let WEIGHT_1: f32 = 0.1945945946;
+let WEIGHT_2: f32 = 0.1216216216;
+let WEIGHT_3: f32 = 0.0540540541;
+let WEIGHT_4: f32 = 0.0162162162;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let x: f32 = f32(i32(vertex_index & 1u) << 2u) - 1.0;
+    let y: f32 = f32(i32(vertex_index & 2u) << 1u) - 1.0;
+    var output: VertexOutput;
+    output.position = vec4<f32>(x, -y, 0.0, 1.0);
+    output.uv = vec2<f32>(x + 1.0, y + 1.0) * 0.5;
+    return output;
+}
+
+// Sample the source along a line of texels with the gaussian kernel
+fn blur(uv: vec2<f32>, step: vec2<f32>) -> vec3<f32> {
+    var color = textureSample(r_source, r_sampler, uv).rgb * WEIGHT_0;
+    color = color + textureSample(r_source, r_sampler, uv + step).rgb * WEIGHT_1;
+    color = color + textureSample(r_source, r_sampler, uv - step).rgb * WEIGHT_1;
+    color = color + textureSample(r_source, r_sampler, uv + step * 2.0).rgb * WEIGHT_2;
+    color = color + textureSample(r_source, r_sampler, uv - step * 2.0).rgb * WEIGHT_2;
+    color = color + textureSample(r_source, r_sampler, uv + step * 3.0).rgb * WEIGHT_3;
+    color = color + textureSample(r_source, r_sampler, uv - step * 3.0).rgb * WEIGHT_3;
+    color = color + textureSample(r_source, r_sampler, uv + step * 4.0).rgb * WEIGHT_4;
+    color = color + textureSample(r_source, r_sampler, uv - step * 4.0).rgb * WEIGHT_4;
+    return color;
+}
+
+// Downsample a source twice the size of the target, blurring horizontally
+// Linear filtering averages each pair of source rows
+[[stage(fragment)]]
+fn fs_downsample(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(r_source, 0));
+    let color = blur(in.uv, vec2<f32>(texel.x * 2.0, 0.0));
+    return vec4<f32>(color, 1.0);
+}
+
+// Blur a source the same size as the target vertically
+[[stage(fragment)]]
+fn fs_blur(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(r_source, 0));
+    let color = blur(in.uv, vec2<f32>(0.0, texel.y));
+    return vec4<f32>(color, 1.0);
+}
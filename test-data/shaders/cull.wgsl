@@ -0,0 +1,117 @@
+// Must match MAX_TRIANGLE_MESH_INSTANCES
+let MAX_INSTANCES: u32 = 256u;
+let WORKGROUP_SIZE: u32 = 64u;
+
+struct Uniforms {
+    perspective: mat4x4<f32>;
+    orthographic: mat4x4<f32>;
+    cam_pos: vec4<f32>;
+    cam_rot: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> r_uniforms: Uniforms;
+
+// Indexed indirect draw arguments, one per mesh
+struct TriangleMesh {
+    index_count: u32;
+    instance_count: u32;
+    index_offset: u32;
+    vertex_offset: u32;
+    first_instance: u32;
+};
+
+struct TriangleMeshes {
+    meshes: [[stride(20)]] array<TriangleMesh>;
+};
+
+struct TriangleMeshBounds {
+    bounds: [[stride(16)]] array<vec4<f32>>;
+};
+
+struct TriangleMeshInstance {
+    position: vec4<f32>;
+    rotation: vec4<f32>;
+    scale: vec4<f32>;
+};
+
+struct TriangleMeshInstances {
+    instances: [[stride(48)]] array<TriangleMeshInstance>;
+};
+
+[[group(1), binding(0)]]
+var<storage, read_write> triangle_meshes: TriangleMeshes;
+
+[[group(1), binding(1)]]
+var<storage, read> triangle_mesh_bounds: TriangleMeshBounds;
+
+[[group(1), binding(2)]]
+var<storage, read> triangle_mesh_instances: TriangleMeshInstances;
+
+[[group(1), binding(3)]]
+var<storage, read_write> visible_instances: TriangleMeshInstances;
+
+var<workgroup> instance_count: u32;
+var<workgroup> visible_count: atomic<u32>;
+
+// Rotate a vector by a unit quaternion
+fn quat_mul(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    let t = 2.0 * cross(q.xyz, v);
+    return v + q.w * t + cross(q.xyz, t);
+}
+
+// Whether a view-space sphere is at least partially inside the view frustum
+// The reversed infinite projection has no far plane,
+// and its side planes pass through the eye, with normals derived from the projection scale
+fn sphere_visible(center: vec3<f32>, radius: f32) -> bool {
+    let near = r_uniforms.perspective[3].z;
+    if (center.z - radius > -near) {
+        return false;
+    }
+
+    let x = r_uniforms.perspective[0].x;
+    let y = r_uniforms.perspective[1].y;
+
+    return dot(normalize(vec3<f32>(x, 0.0, 1.0)), center) <= radius
+        && dot(normalize(vec3<f32>(-x, 0.0, 1.0)), center) <= radius
+        && dot(normalize(vec3<f32>(0.0, y, 1.0)), center) <= radius
+        && dot(normalize(vec3<f32>(0.0, -y, 1.0)), center) <= radius;
+}
+
+// One workgroup per mesh, compacting its visible instances into the front of its slice
+// and replacing its instance count with the number that survived
+[[stage(compute), workgroup_size(64)]]
+fn cs_main(
+    [[builtin(workgroup_id)]] workgroup_id: vec3<u32>,
+    [[builtin(local_invocation_index)]] local_index: u32,
+) {
+    let mesh = workgroup_id.x;
+
+    if (local_index == 0u) {
+        instance_count = min(triangle_meshes.meshes[mesh].instance_count, MAX_INSTANCES);
+        atomicStore(&visible_count, 0u);
+    }
+    workgroupBarrier();
+
+    let bounds = triangle_mesh_bounds.bounds[mesh];
+    let base = mesh * MAX_INSTANCES;
+
+    for (var i = local_index; i < instance_count; i = i + WORKGROUP_SIZE) {
+        let instance = triangle_mesh_instances.instances[base + i];
+
+        let scale = instance.scale.xyz;
+        let center = instance.position.xyz + quat_mul(instance.rotation, bounds.xyz) * scale;
+        let center = quat_mul(r_uniforms.cam_rot, center - r_uniforms.cam_pos.xyz);
+        let radius = bounds.w * max(abs(scale.x), max(abs(scale.y), abs(scale.z)));
+
+        if (sphere_visible(center, radius)) {
+            let slot = atomicAdd(&visible_count, 1u);
+            visible_instances.instances[base + slot] = instance;
+        }
+    }
+    workgroupBarrier();
+
+    if (local_index == 0u) {
+        triangle_meshes.meshes[mesh].instance_count = atomicLoad(&visible_count);
+    }
+}
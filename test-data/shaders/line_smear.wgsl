@@ -0,0 +1,175 @@
+struct Quaternion {
+    x: f32;
+    y: f32;
+    z: f32;
+    w: f32;
+};
+
+struct MeshVertex {
+    m0: vec4<f32>;
+    m1: vec4<f32>;
+    m2: vec4<f32>;
+};
+
+struct MeshVertices {
+    vertices: [[stride(48)]] array<MeshVertex>;
+};
+
+struct LineIndices {
+    indices: [[stride(4)]] array<u32>;
+};
+
+struct LineMesh {
+    vertex_offset: u32;
+    vertex_count: u32;
+    index_offset: u32;
+    index_count: u32;
+};
+
+struct LineMeshes {
+    meshes: [[stride(16)]] array<LineMesh>;
+};
+
+struct LineMeshInstance {
+    pos: vec3<f32>;
+    mesh_id: u32;
+    rot: Quaternion;
+    scale: vec3<f32>;
+    intensity: f32;
+    color: vec3<f32>;
+};
+
+struct LineMeshInstances {
+    instances: [[stride(64)]] array<LineMeshInstance>;
+};
+
+struct LineInstance {
+    mesh_instance_id: u32;
+    line_index: u32;
+};
+
+struct LineInstances {
+    instances: [[stride(8)]] array<LineInstance>;
+};
+
+// World-space endpoints, with w set if the line was visible
+struct LineHistory {
+    v0: vec4<f32>;
+    v1: vec4<f32>;
+};
+
+struct LineHistories {
+    lines: [[stride(32)]] array<LineHistory>;
+};
+
+// Previous and current endpoints, with the color and intensity of each end
+struct LineSmear {
+    previous: array<vec4<f32>, 2>;
+    current: array<vec4<f32>, 2>;
+    color: array<vec4<f32>, 2>;
+};
+
+struct LineSmears {
+    smears: [[stride(96)]] array<LineSmear>;
+};
+
+struct SmearUniforms {
+    line_count: u32;
+};
+
+[[group(0), binding(0)]]
+var<storage, read> mesh_vertices: MeshVertices;
+
+[[group(0), binding(1)]]
+var<storage, read> line_indices: LineIndices;
+
+[[group(0), binding(2)]]
+var<storage, read> line_meshes: LineMeshes;
+
+[[group(0), binding(3)]]
+var<storage, read> line_mesh_instances: LineMeshInstances;
+
+[[group(0), binding(4)]]
+var<storage, read> line_instances: LineInstances;
+
+[[group(0), binding(5)]]
+var<storage, read> previous_history: LineHistories;
+
+[[group(0), binding(6)]]
+var<storage, read_write> current_history: LineHistories;
+
+[[group(0), binding(7)]]
+var<storage, read_write> line_smears: LineSmears;
+
+[[group(0), binding(8)]]
+var<uniform> smear_uniforms: SmearUniforms;
+
+// Rotate a vector by a unit quaternion
+fn quat_mul(q: Quaternion, v: vec3<f32>) -> vec3<f32> {
+    let q_xyz = vec3<f32>(q.x, q.y, q.z);
+    let t = 2.0 * cross(q_xyz, v);
+    return v + q.w * t + cross(q_xyz, t);
+}
+
+// Line color and intensity of a mesh vertex
+fn vertex_color(v: MeshVertex) -> vec4<f32> {
+    return vec4<f32>(v.m1.zw, v.m2.x, v.m2.y);
+}
+
+// One invocation per history slot, smearing each line from its previous endpoints to its current ones
+// Slots past the end of the line instances are marked invisible,
+// so lines added to them later don't smear from wherever their previous occupant was
+[[stage(compute), workgroup_size(64)]]
+fn cs_main(
+    [[builtin(global_invocation_id)]] global_id: vec3<u32>,
+) {
+    let index = global_id.x;
+    if (index >= arrayLength(&current_history.lines) || index >= arrayLength(&line_smears.smears)) {
+        return;
+    }
+
+    var current: LineHistory;
+    var smear: LineSmear;
+
+    if (index < smear_uniforms.line_count) {
+        let line_instance = line_instances.instances[index];
+        let mesh_instance = line_mesh_instances.instances[line_instance.mesh_instance_id];
+        let mesh = line_meshes.meshes[mesh_instance.mesh_id];
+
+        let idx0 = mesh.index_offset + line_instance.line_index * 2u;
+        let v0 = mesh_vertices.vertices[mesh.vertex_offset + line_indices.indices[idx0]];
+        let v1 = mesh_vertices.vertices[mesh.vertex_offset + line_indices.indices[idx0 + 1u]];
+
+        let v0_pos = mesh_instance.pos + quat_mul(mesh_instance.rot, v0.m0.xyz) * mesh_instance.scale;
+        let v1_pos = mesh_instance.pos + quat_mul(mesh_instance.rot, v1.m0.xyz) * mesh_instance.scale;
+
+        // Freed instances have zero scale, and neither leave nor receive a smear
+        var visible = 0.0;
+        if (length(mesh_instance.scale) > 0.0) {
+            visible = 1.0;
+        }
+
+        current.v0 = vec4<f32>(v0_pos, visible);
+        current.v1 = vec4<f32>(v1_pos, visible);
+
+        let previous = previous_history.lines[index];
+        let moved = any(previous.v0.xyz != v0_pos) || any(previous.v1.xyz != v1_pos);
+
+        var smeared = 0.0;
+        if (visible > 0.0 && previous.v0.w > 0.0 && moved) {
+            smeared = 1.0;
+        }
+
+        let tint = vec4<f32>(mesh_instance.color, mesh_instance.intensity);
+
+        smear.previous[0] = vec4<f32>(previous.v0.xyz, 0.0);
+        smear.previous[1] = vec4<f32>(previous.v1.xyz, 0.0);
+        smear.current[0] = vec4<f32>(v0_pos, smeared);
+        smear.current[1] = vec4<f32>(v1_pos, smeared);
+        smear.color[0] = vertex_color(v0) * tint;
+        smear.color[1] = vertex_color(v1) * tint;
+    }
+
+    current_history.lines[index] = current;
+    line_smears.smears[index] = smear;
+}
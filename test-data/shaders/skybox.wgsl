@@ -0,0 +1,83 @@
+// Quaternion functionality
+struct Quaternion {
+    x: f32;
+    y: f32;
+    z: f32;
+    w: f32;
+};
+
+fn quat_inv(q: Quaternion) -> Quaternion {
+    return Quaternion(-q.x, -q.y, -q.z, q.w);
+}
+
+fn quat_dot(q1: Quaternion, q2: Quaternion) -> Quaternion {
+    let q1_xyz = vec3<f32>(q1.x, q1.y, q1.z);
+    let q2_xyz = vec3<f32>(q2.x, q2.y, q2.z);
+
+    let scalar = q1.w * q2.w - dot(q1_xyz, q2_xyz);
+    let v = cross(q1_xyz, q2_xyz) + q1.w * q2_xyz + q2.w * q1_xyz;
+
+    return Quaternion(v.x, v.y, v.z, scalar);
+}
+
+fn quat_mul(q: Quaternion, v: vec3<f32>) -> vec3<f32> {
+    let r = quat_dot(q, quat_dot(Quaternion(v.x, v.y, v.z, 0.0), quat_inv(q)));
+    return vec3<f32>(r.x, r.y, r.z);
+}
+
+struct Uniforms {
+    perspective: mat4x4<f32>;
+    orthographic: mat4x4<f32>;
+    cam_pos: vec4<f32>;
+    cam_rot: Quaternion;
+    total: f32;
+    delta: f32;
+    bloom_intensity: f32;
+    pad: f32;
+    skybox_top: vec4<f32>;
+    skybox_bottom: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> r_uniforms: Uniforms;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// Fullscreen triangle at the far plane, so the underlay only passes depth where nothing was drawn
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let x: f32 = f32(i32(vertex_index & 1u) << 2u) - 1.0;
+    let y: f32 = f32(i32(vertex_index & 2u) << 1u) - 1.0;
+    var output: VertexOutput;
+    output.position = vec4<f32>(x, -y, 0.0, 1.0);
+    output.uv = vec2<f32>(x + 1.0, y + 1.0) * 0.5;
+    return output;
+}
+
+fn gradient(t: f32) -> vec3<f32> {
+    return mix(r_uniforms.skybox_bottom.rgb, r_uniforms.skybox_top.rgb, clamp(t, 0.0, 1.0));
+}
+
+// Graded from the horizon to the zenith by the elevation of the view ray through the fragment
+[[stage(fragment)]]
+fn fs_underlay(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let view_dir = vec3<f32>(
+        ndc.x / r_uniforms.perspective[0].x,
+        ndc.y / r_uniforms.perspective[1].y,
+        -1.0,
+    );
+    let world_dir = normalize(quat_mul(quat_inv(r_uniforms.cam_rot), view_dir));
+
+    // Alpha is masked off by the pipeline, leaving the cleared decay rate intact
+    return vec4<f32>(gradient(world_dir.y), 0.0);
+}
+
+// Graded from the bottom to the top of the screen, independent of the camera
+[[stage(fragment)]]
+fn fs_overlay(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(gradient(1.0 - in.uv.y), 0.0);
+}
@@ -4,6 +4,38 @@ var r_phosphor: texture_2d<f32>;
 [[group(0), binding(2)]]
 var r_sampler: sampler;
 
+struct Uniforms {
+    perspective: mat4x4<f32>;
+    orthographic: mat4x4<f32>;
+    cam_pos: vec4<f32>;
+    cam_rot: vec4<f32>;
+    total: f32;
+    delta: f32;
+    bloom_intensity: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> r_uniforms: Uniforms;
+
+// Blurred bloom mip chain, one binding per level
+[[group(2), binding(0)]]
+var r_bloom_0: texture_2d<f32>;
+
+[[group(2), binding(1)]]
+var r_bloom_1: texture_2d<f32>;
+
+[[group(2), binding(2)]]
+var r_bloom_2: texture_2d<f32>;
+
+[[group(2), binding(3)]]
+var r_bloom_3: texture_2d<f32>;
+
+[[group(2), binding(4)]]
+var r_bloom_4: texture_2d<f32>;
+
+[[group(3), binding(0)]]
+var r_color_lut: texture_3d<f32>;
+
 struct VertexOutput {
     [[builtin(position)]] position: vec4<f32>;
     [[location(0)]] uv: vec2<f32>;
@@ -19,11 +51,29 @@ fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
     return output;
 }
 
+// Remap a color through the LUT
+// Coordinates are inset by half a texel so that 0 and 1 land on the centers of the edge texels
+// rather than their outer edges, where trilinear filtering would skew the mapping
+fn color_grade(color: vec3<f32>) -> vec3<f32> {
+    let size = vec3<f32>(textureDimensions(r_color_lut));
+    let color = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    let uvw = color * ((size - 1.0) / size) + 0.5 / size;
+    return textureSample(r_color_lut, r_sampler, uvw).rgb;
+}
+
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     let phosphor = textureSample(r_phosphor, r_sampler, in.uv);
 
-    let color = phosphor.rgb;
+    // Average the bloom levels and composite additively
+    let bloom = textureSample(r_bloom_0, r_sampler, in.uv).rgb
+        + textureSample(r_bloom_1, r_sampler, in.uv).rgb
+        + textureSample(r_bloom_2, r_sampler, in.uv).rgb
+        + textureSample(r_bloom_3, r_sampler, in.uv).rgb
+        + textureSample(r_bloom_4, r_sampler, in.uv).rgb;
+    let bloom = bloom / 5.0;
+
+    let color = phosphor.rgb + bloom * r_uniforms.bloom_intensity;
     let black = vec3<f32>(0.0, 0.0, 0.0);
     let white = vec3<f32>(1.0, 1.0, 1.0);
 
@@ -32,5 +82,5 @@ fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
 
     let color = mix(color, white, clamp(fac, 0.0, 1.0));
 
-    return vec4<f32>(color, 1.0);
+    return vec4<f32>(color_grade(color), 1.0);
 }